@@ -0,0 +1,10 @@
+//! Hands the target and host triples to the integration tests, which find
+//! the C compiler with the `cc` crate the way a build script would.
+
+fn main() {
+    for key in ["TARGET", "HOST"] {
+        let triple = std::env::var(key).expect("cargo sets TARGET and HOST for build scripts");
+        println!("cargo:rustc-env=H6XSERIAL_BUILD_{}={}", key, triple);
+    }
+    println!("cargo:rerun-if-changed=build.rs");
+}
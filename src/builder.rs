@@ -0,0 +1,1013 @@
+//! Builders for constructing message definitions without JSON.
+//!
+//! [`MessageDefinition::scalar`], [`MessageDefinition::array`],
+//! [`MessageDefinition::structure`] and [`MessageDefinition::union`] start
+//! a [`MessageBuilder`];
+//! [`StructSpec::builder`] assembles struct fields. `build()` enforces the
+//! same rules as the JSON parser:
+//!
+//! - `packet_id` fits the `packet_id_width` (at most 255 by default)
+//! - every `max_length` is at least 1 and at most the array length cap
+//! - structs (and nested structs) have at least one field, with unique names
+//! - structs nest no deeper than `max_nesting_depth`, and a message has at
+//!   most 1024 fields counting those of nested structs
+//! - unions have at least one variant, each with a tag value that fits the
+//!   tag type and a name of its own
+//! - the encoded size of array and struct messages fits the payload limit
+//! - only integer values are scaled, and never by zero
+//! - `min`/`max` ranges apply to numeric scalars and fit their type
+//! - `since_version` is only set on the top-level fields of a struct
+//!   message, never above its `msg_version` and never decreasing
+//!
+//! Limits default to those of [`Metadata::default`]; pass the metadata the
+//! messages are generated with to [`MessageBuilder::limits`] when it raises
+//! them. Errors use the JSON pointer the equivalent input would have, e.g.
+//! `/packets/imu/fields/samples/max_length`.
+
+use std::collections::HashSet;
+
+use crate::validate::pointer_push;
+use crate::{
+    ArraySpec, Bound, Deprecation, Endian, IdlError, Limits, MessageBody, MessageDefinition,
+    Metadata, PrimitiveType, Radix, RequestType, ScalarSpec, Scaling, StructField,
+    StructFieldArraySpec, StructFieldType, StructSpec, TimestampResolution, UnionSpec,
+    UnionVariant, ValueRange, check_array_length, check_body_shape, check_body_size, check_group,
+    check_max_length, check_packet_id, check_range, check_scaling, check_sector_bytes,
+    check_versions, to_snake_case,
+};
+
+/// Builder for a [`MessageDefinition`], created by
+/// [`MessageDefinition::scalar`], [`MessageDefinition::array`],
+/// [`MessageDefinition::structure`] or [`MessageDefinition::union`].
+#[derive(Debug)]
+#[must_use]
+pub struct MessageBuilder {
+    name: String,
+    packet_id: u32,
+    description: Option<String>,
+    body: MessageBody,
+    request_type: Option<RequestType>,
+    target_client_id: Option<i32>,
+    deprecated: Option<Deprecation>,
+    reserved_ok: bool,
+    response: Option<String>,
+    msg_version: Option<u8>,
+    group: Option<String>,
+    limits: Limits,
+}
+
+impl MessageDefinition {
+    /// Starts a single-value message.
+    ///
+    /// # Example
+    /// ```
+    /// use std::path::Path;
+    /// use h6xserial_idl::{Endian, MessageDefinition, Metadata, PrimitiveType, emit_c};
+    ///
+    /// let status = MessageDefinition::scalar("status", 1, PrimitiveType::Uint16)
+    ///     .endian(Endian::Big)
+    ///     .description("Device status flags")
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// let header = emit_c::generate(
+    ///     &Metadata::default(),
+    ///     &[status],
+    ///     Path::new("protocol.json"),
+    ///     Path::new("status.h"),
+    /// )
+    /// .unwrap();
+    /// assert!(header.contains("uint16_t value;"));
+    /// assert!(header.contains("h6xserial_write_u16_be"));
+    /// ```
+    pub fn scalar(
+        name: impl Into<String>,
+        packet_id: u32,
+        primitive: PrimitiveType,
+    ) -> MessageBuilder {
+        MessageBuilder::new(
+            name.into(),
+            packet_id,
+            MessageBody::Scalar(ScalarSpec {
+                primitive,
+                endian: Endian::default(),
+                scaling: None,
+                range: None,
+                description: None,
+                unit: None,
+            }),
+        )
+    }
+
+    /// Starts a variable-length array message of up to `max_length` elements.
+    ///
+    /// # Example
+    /// ```
+    /// use std::path::Path;
+    /// use h6xserial_idl::{MessageDefinition, Metadata, PrimitiveType, emit_c};
+    ///
+    /// let samples = MessageDefinition::array("samples", 2, PrimitiveType::Int16, 64)
+    ///     .build()
+    ///     .unwrap();
+    /// let header = emit_c::generate(
+    ///     &Metadata::default(),
+    ///     &[samples],
+    ///     Path::new("protocol.json"),
+    ///     Path::new("samples.h"),
+    /// )
+    /// .unwrap();
+    /// assert!(header.contains("#define PROTOCOL_MSG_SAMPLES_MAX_LENGTH 64"));
+    /// assert!(header.contains("int16_t data[PROTOCOL_MSG_SAMPLES_MAX_LENGTH];"));
+    ///
+    /// // 200 * 2 bytes exceeds the default 251-byte payload limit
+    /// let err = MessageDefinition::array("big", 3, PrimitiveType::Int16, 200)
+    ///     .build()
+    ///     .unwrap_err();
+    /// assert_eq!(err.pointer(), Some("/packets/big/max_length"));
+    /// ```
+    pub fn array(
+        name: impl Into<String>,
+        packet_id: u32,
+        primitive: PrimitiveType,
+        max_length: usize,
+    ) -> MessageBuilder {
+        MessageBuilder::new(
+            name.into(),
+            packet_id,
+            MessageBody::Array(ArraySpec {
+                primitive,
+                endian: Endian::default(),
+                max_length,
+                sector_bytes: None,
+                scaling: None,
+                description: None,
+                unit: None,
+                string_semantics: false,
+            }),
+        )
+    }
+
+    /// Starts a struct message with the fields of `spec` (see
+    /// [`StructSpec::builder`]).
+    pub fn structure(name: impl Into<String>, packet_id: u32, spec: StructSpec) -> MessageBuilder {
+        MessageBuilder::new(name.into(), packet_id, MessageBody::Struct(spec))
+    }
+
+    /// Starts a union message with a `tag` of an integer type; add its
+    /// variants with [`MessageBuilder::variant`].
+    ///
+    /// # Example
+    /// ```
+    /// use std::path::Path;
+    /// use h6xserial_idl::{MessageDefinition, Metadata, PrimitiveType, StructSpec, emit_c};
+    ///
+    /// let gps = StructSpec::builder()
+    ///     .field("lat", PrimitiveType::Float32)
+    ///     .build()
+    ///     .unwrap();
+    /// let odom = StructSpec::builder()
+    ///     .field("ticks", PrimitiveType::Int32)
+    ///     .build()
+    ///     .unwrap();
+    /// let sample = MessageDefinition::union("sample", 4, PrimitiveType::Uint8)
+    ///     .variant(1, "gps", gps)
+    ///     .variant(2, "odom", odom)
+    ///     .build()
+    ///     .unwrap();
+    /// let header = emit_c::generate(
+    ///     &Metadata::default(),
+    ///     &[sample],
+    ///     Path::new("protocol.json"),
+    ///     Path::new("sample.h"),
+    /// )
+    /// .unwrap();
+    /// assert!(header.contains("#define PROTOCOL_MSG_SAMPLE_ODOM_TAG 2"));
+    /// assert!(header.contains("PROTOCOL_MSG_SAMPLE_MAX_SIZE 5"));
+    /// ```
+    pub fn union(name: impl Into<String>, packet_id: u32, tag: PrimitiveType) -> MessageBuilder {
+        MessageBuilder::new(
+            name.into(),
+            packet_id,
+            MessageBody::Union(UnionSpec {
+                tag,
+                endian: Endian::default(),
+                variants: Vec::new(),
+            }),
+        )
+    }
+}
+
+impl MessageBuilder {
+    fn new(name: String, packet_id: u32, body: MessageBody) -> Self {
+        Self {
+            name,
+            packet_id,
+            description: None,
+            body,
+            request_type: None,
+            target_client_id: None,
+            deprecated: None,
+            reserved_ok: false,
+            response: None,
+            msg_version: None,
+            group: None,
+            limits: Limits::of(&Metadata::default()),
+        }
+    }
+
+    /// Byte order of a scalar or array message, or of the tag of a union.
+    /// Struct fields carry their own endianness (see
+    /// [`StructSpecBuilder::endian`]).
+    pub fn endian(mut self, endian: Endian) -> Self {
+        match &mut self.body {
+            MessageBody::Scalar(spec) => spec.endian = endian,
+            MessageBody::Array(spec) => spec.endian = endian,
+            MessageBody::Union(spec) => spec.endian = endian,
+            MessageBody::Struct(_) => {}
+        }
+        self
+    }
+
+    /// Adds the variant `name` of a union message, selected by the tag
+    /// value `tag`. Ignored for other shapes.
+    pub fn variant(mut self, tag: i128, name: impl Into<String>, spec: StructSpec) -> Self {
+        if let MessageBody::Union(union) = &mut self.body {
+            union.variants.push(UnionVariant {
+                name: name.into(),
+                tag,
+                spec,
+                description: None,
+            });
+        }
+        self
+    }
+
+    /// Fixed-point scaling of a scalar or array message of an integer type.
+    /// Ignored for structs, whose fields are scaled one by one (see
+    /// [`StructSpecBuilder::scaling`]).
+    pub fn scaling(mut self, scaling: Scaling) -> Self {
+        match &mut self.body {
+            MessageBody::Scalar(spec) => spec.scaling = Some(scaling),
+            MessageBody::Array(spec) => spec.scaling = Some(scaling),
+            MessageBody::Struct(_) | MessageBody::Union(_) => {}
+        }
+        self
+    }
+
+    /// Allowed values of a scalar message, checked by the generated
+    /// decoder. Ignored for other shapes; struct fields carry their own
+    /// ranges (see [`StructSpecBuilder::range`]).
+    pub fn range(mut self, range: ValueRange) -> Self {
+        if let MessageBody::Scalar(spec) = &mut self.body {
+            spec.range = Some(float_bounds(range, Some(spec.primitive)));
+        }
+        self
+    }
+
+    /// Unit of the value of a scalar or array message, e.g. `"rpm"`. Ignored
+    /// for structs, whose fields carry their own units (see
+    /// [`StructSpecBuilder::unit`]).
+    pub fn unit(mut self, unit: impl Into<String>) -> Self {
+        match &mut self.body {
+            MessageBody::Scalar(spec) => spec.unit = Some(unit.into()),
+            MessageBody::Array(spec) => spec.unit = Some(unit.into()),
+            MessageBody::Struct(_) | MessageBody::Union(_) => {}
+        }
+        self
+    }
+
+    /// Free-form description, emitted into generated comments and docs.
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Direction from the server's point of view (defaults to publish).
+    pub fn request_type(mut self, request_type: RequestType) -> Self {
+        self.request_type = Some(request_type);
+        self
+    }
+
+    /// Marks the message as deprecated; it is still generated, but its
+    /// functions warn when used.
+    pub fn deprecated(mut self, deprecation: Deprecation) -> Self {
+        self.deprecated = Some(deprecation);
+        self
+    }
+
+    /// Allows a packet ID from the reserved ranges of the metadata
+    /// (`reserved_ok`), checked when the message is merged with them.
+    pub fn reserved_ok(mut self, reserved_ok: bool) -> Self {
+        self.reserved_ok = reserved_ok;
+        self
+    }
+
+    /// Restricts the message to one client (defaults to -1, all clients).
+    pub fn target_client_id(mut self, target_client_id: i32) -> Self {
+        self.target_client_id = Some(target_client_id);
+        self
+    }
+
+    /// Names the message that answers this one. The name is checked when
+    /// the message is parsed from a document, not here.
+    pub fn response(mut self, response: impl Into<String>) -> Self {
+        self.response = Some(response.into());
+        self
+    }
+
+    /// Version of the payload layout (`msg_version`), from 1. Fields
+    /// appended in later versions carry theirs (see
+    /// [`StructSpecBuilder::since_version`]).
+    pub fn version(mut self, msg_version: u8) -> Self {
+        self.msg_version = Some(msg_version);
+        self
+    }
+
+    /// Family of the message (`group`), which goes into its C names.
+    pub fn group(mut self, group: impl Into<String>) -> Self {
+        self.group = Some(group.into());
+        self
+    }
+
+    /// Sector size of an array message, in bytes. Ignored for other shapes.
+    pub fn sector_bytes(mut self, sector_bytes: usize) -> Self {
+        if let MessageBody::Array(spec) = &mut self.body {
+            spec.sector_bytes = Some(sector_bytes);
+        }
+        self
+    }
+
+    /// Marks a `char` array message as text (`string_semantics`). Ignored
+    /// for other shapes and element types.
+    pub fn string_semantics(mut self) -> Self {
+        if let MessageBody::Array(spec) = &mut self.body
+            && spec.primitive == PrimitiveType::Char
+        {
+            spec.string_semantics = true;
+        }
+        self
+    }
+
+    /// Validates against the payload and array limits and the packet ID
+    /// width of `metadata` instead of the defaults.
+    pub fn limits(mut self, metadata: &Metadata) -> Self {
+        self.limits = Limits::of(metadata);
+        self
+    }
+
+    /// Validates the definition and returns it.
+    pub fn build(self) -> Result<MessageDefinition, IdlError> {
+        let pointer = pointer_push("/packets", &self.name);
+        check_packet_id(
+            &self.name,
+            u64::from(self.packet_id),
+            &self.limits,
+            &pointer,
+        )?;
+        let what = format!("message '{}'", self.name);
+        match &self.body {
+            MessageBody::Scalar(spec) => {
+                if let Some(scaling) = spec.scaling {
+                    check_scaling(scaling, "scale", Some(spec.primitive), &what, &pointer)?;
+                }
+                if let Some(range) = spec.range {
+                    check_range(range, Some(spec.primitive), false, &what, &pointer)?;
+                }
+            }
+            MessageBody::Array(spec) => {
+                if let Some(scaling) = spec.scaling {
+                    check_scaling(scaling, "scale", Some(spec.primitive), &what, &pointer)?;
+                }
+                check_max_length(
+                    &format!("array message '{}'", self.name),
+                    spec.max_length,
+                    &pointer,
+                    &self.limits,
+                )?;
+                check_body_size(&self.name, &self.body, &pointer, &self.limits)?;
+                check_sector_bytes(&self.name, spec, &pointer, &self.limits)?;
+            }
+            MessageBody::Struct(spec) => {
+                // Before the recursive checks, which descend once per level
+                check_body_shape(&self.name, &self.body, &pointer, &self.limits)?;
+                check_struct_spec(
+                    spec,
+                    &self.name,
+                    &pointer_push(&pointer, "fields"),
+                    &self.limits,
+                )?;
+                check_body_size(&self.name, &self.body, &pointer, &self.limits)?;
+            }
+            MessageBody::Union(spec) => {
+                check_body_shape(&self.name, &self.body, &pointer, &self.limits)?;
+                check_union_spec(spec, &self.name, &pointer, &self.limits)?;
+                check_body_size(&self.name, &self.body, &pointer, &self.limits)?;
+            }
+        }
+        check_versions(&self.name, self.msg_version, &self.body, &pointer)?;
+        if let Some(group) = &self.group {
+            check_group(&self.name, group, &pointer_push(&pointer, "group"))?;
+        }
+
+        Ok(MessageDefinition {
+            name: self.name,
+            packet_id: self.packet_id,
+            packet_id_radix: Radix::Decimal,
+            description: self.description,
+            body: self.body,
+            request_type: self.request_type.unwrap_or_default(),
+            request_type_given: self.request_type.is_some(),
+            target_client_id: self.target_client_id.unwrap_or(-1),
+            target_client_id_given: self.target_client_id.is_some(),
+            deprecated: self.deprecated,
+            reserved_ok: self.reserved_ok,
+            response: self.response,
+            msg_version: self.msg_version,
+            group: self.group,
+        })
+    }
+}
+
+/// Checks the tag type and the variants of a union message.
+fn check_union_spec(
+    spec: &UnionSpec,
+    name: &str,
+    pointer: &str,
+    limits: &Limits,
+) -> Result<(), IdlError> {
+    let Some((min, max)) = spec.tag.integer_range() else {
+        return Err(IdlError::invalid_value(
+            &pointer_push(pointer, "tag"),
+            spec.tag.name(),
+            format!(
+                "union message '{}' has 'tag' type '{}', which is not an integer type",
+                name,
+                spec.tag.name()
+            ),
+        ));
+    };
+    let variants_pointer = pointer_push(pointer, "variants");
+    if spec.variants.is_empty() {
+        return Err(IdlError::invalid_value(
+            &variants_pointer,
+            "{}",
+            format!(
+                "union message '{}' must define at least one variant in 'variants' object",
+                name
+            ),
+        ));
+    }
+    let mut tags = HashSet::new();
+    let mut names = HashSet::new();
+    for variant in &spec.variants {
+        let variant_pointer = pointer_push(&variants_pointer, &variant.tag.to_string());
+        if !(min..=max).contains(&variant.tag) {
+            return Err(IdlError::invalid_value(
+                &variant_pointer,
+                variant.tag,
+                format!(
+                    "variant '{}' of union message '{}' has tag value {} outside the range of {} ({} to {})",
+                    variant.name,
+                    name,
+                    variant.tag,
+                    spec.tag.name(),
+                    min,
+                    max
+                ),
+            ));
+        }
+        if !tags.insert(variant.tag) {
+            return Err(IdlError::invalid_value(
+                &variant_pointer,
+                variant.tag,
+                format!(
+                    "union message '{}' has more than one variant with tag value {}",
+                    name, variant.tag
+                ),
+            ));
+        }
+        if !names.insert(to_snake_case(&variant.name)) {
+            return Err(IdlError::invalid_value(
+                &pointer_push(&variant_pointer, "name"),
+                &variant.name,
+                format!(
+                    "variant '{}' of union message '{}' maps to the same C identifier as another variant",
+                    variant.name, name
+                ),
+            ));
+        }
+        check_struct_spec(
+            &variant.spec,
+            &format!("{}.{}", name, variant.name),
+            &pointer_push(&variant_pointer, "fields"),
+            limits,
+        )?;
+    }
+    Ok(())
+}
+
+/// Checks field names and array lengths of a struct, recursively.
+/// `pointer` is the struct's `fields` object.
+fn check_struct_spec(
+    spec: &StructSpec,
+    parent_name: &str,
+    pointer: &str,
+    limits: &Limits,
+) -> Result<(), IdlError> {
+    if spec.fields.is_empty() {
+        return Err(IdlError::invalid_value(
+            pointer,
+            "{}",
+            format!("struct '{}' must define at least one field", parent_name),
+        ));
+    }
+    let mut seen = HashSet::new();
+    for field in &spec.fields {
+        let field_pointer = pointer_push(pointer, &field.name);
+        if !seen.insert(field.name.as_str()) {
+            return Err(IdlError::invalid_value(
+                &field_pointer,
+                &field.name,
+                format!(
+                    "field '{}' in '{}' is defined more than once",
+                    field.name, parent_name
+                ),
+            ));
+        }
+        let primitive = field_primitive(&field.field_type);
+        let what = format!("field '{}' in '{}'", field.name, parent_name);
+        if let Some(scaling) = field.scaling {
+            check_scaling(scaling, "scale", primitive, &what, &field_pointer)?;
+        }
+        if let Some(range) = field.range {
+            let is_array = matches!(field.field_type, StructFieldType::Array(_));
+            check_range(range, primitive, is_array, &what, &field_pointer)?;
+        }
+        if field.timestamp.is_some()
+            && !matches!(
+                field.field_type,
+                StructFieldType::Primitive(PrimitiveType::Uint32 | PrimitiveType::Uint64)
+            )
+        {
+            return Err(IdlError::invalid_value(
+                &pointer_push(&field_pointer, "storage"),
+                primitive.map_or("struct", PrimitiveType::name),
+                format!("'storage' of {} must be uint32 or uint64", what),
+            ));
+        }
+        match &field.field_type {
+            StructFieldType::Primitive(_) => {}
+            StructFieldType::Array(arr) => check_array_length(
+                &format!("array field '{}' in '{}'", field.name, parent_name),
+                if arr.fixed {
+                    "fixed_length"
+                } else {
+                    "max_length"
+                },
+                arr.max_length,
+                &field_pointer,
+                limits,
+            )?,
+            StructFieldType::Nested(nested) => check_struct_spec(
+                nested,
+                &format!("{}.{}", parent_name, field.name),
+                &pointer_push(&field_pointer, "fields"),
+                limits,
+            )?,
+            StructFieldType::Bytes(0) => {
+                return Err(IdlError::invalid_value(
+                    &pointer_push(&field_pointer, "length"),
+                    0,
+                    format!("{} has length of 0, must be at least 1", what),
+                ));
+            }
+            StructFieldType::Bytes(_) => {}
+        }
+    }
+    Ok(())
+}
+
+/// The element type of a primitive or array field.
+fn field_primitive(field_type: &StructFieldType) -> Option<PrimitiveType> {
+    match field_type {
+        StructFieldType::Primitive(primitive) => Some(*primitive),
+        StructFieldType::Array(arr) => Some(arr.primitive),
+        StructFieldType::Nested(_) | StructFieldType::Bytes(_) => None,
+    }
+}
+
+/// Turns the bounds of a range on a floating point type into floats, as
+/// the parser does.
+fn float_bounds(range: ValueRange, primitive: Option<PrimitiveType>) -> ValueRange {
+    if !primitive.is_some_and(PrimitiveType::is_float) {
+        return range;
+    }
+    let float = |bound: Option<Bound>| bound.map(|bound| Bound::Float(bound.as_f64()));
+    ValueRange {
+        min: float(range.min),
+        max: float(range.max),
+    }
+}
+
+/// Builder for a [`StructSpec`], created by [`StructSpec::builder`].
+#[derive(Debug, Default)]
+#[must_use]
+pub struct StructSpecBuilder {
+    fields: Vec<StructField>,
+}
+
+impl StructSpec {
+    /// Starts an empty list of struct fields; fields keep the order in
+    /// which they are added, which is also their wire order.
+    ///
+    /// # Example
+    /// ```
+    /// use std::path::Path;
+    /// use h6xserial_idl::{Endian, MessageDefinition, Metadata, PrimitiveType, StructSpec, emit_c};
+    ///
+    /// let position = StructSpec::builder()
+    ///     .field("x", PrimitiveType::Float32)
+    ///     .field("y", PrimitiveType::Float32)
+    ///     .build()
+    ///     .unwrap();
+    /// let fields = StructSpec::builder()
+    ///     .field("timestamp", PrimitiveType::Uint32)
+    ///     .endian(Endian::Big)
+    ///     .nested("position", position)
+    ///     .array("samples", PrimitiveType::Int16, 8)
+    ///     .build()
+    ///     .unwrap();
+    /// let imu = MessageDefinition::structure("imu", 10, fields)
+    ///     .description("Inertial measurement")
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// let header = emit_c::generate(
+    ///     &Metadata::default(),
+    ///     &[imu],
+    ///     Path::new("protocol.json"),
+    ///     Path::new("imu.h"),
+    /// )
+    /// .unwrap();
+    /// assert!(header.contains("float x;"));
+    /// assert!(header.contains("int16_t samples[PROTOCOL_MSG_IMU_SAMPLES_MAX_LENGTH];"));
+    /// ```
+    pub fn builder() -> StructSpecBuilder {
+        StructSpecBuilder::default()
+    }
+}
+
+impl StructSpecBuilder {
+    fn push(mut self, name: impl Into<String>, field_type: StructFieldType) -> Self {
+        self.fields.push(StructField {
+            name: name.into(),
+            field_type,
+            endian: Endian::default(),
+            scaling: None,
+            range: None,
+            description: None,
+            unit: None,
+            timestamp: None,
+            since_version: None,
+            at: None,
+            reserved: false,
+        });
+        self
+    }
+
+    /// Adds a single-value field.
+    pub fn field(self, name: impl Into<String>, primitive: PrimitiveType) -> Self {
+        self.push(name, StructFieldType::Primitive(primitive))
+    }
+
+    /// Adds a variable-length array field of up to `max_length` elements.
+    pub fn array(
+        self,
+        name: impl Into<String>,
+        primitive: PrimitiveType,
+        max_length: usize,
+    ) -> Self {
+        self.push(
+            name,
+            StructFieldType::Array(StructFieldArraySpec {
+                primitive,
+                max_length,
+                fixed: false,
+                string_semantics: false,
+            }),
+        )
+    }
+
+    /// Adds a `char` array field of up to `max_length` characters holding
+    /// text (`string_semantics`).
+    pub fn string(self, name: impl Into<String>, max_length: usize) -> Self {
+        self.push(
+            name,
+            StructFieldType::Array(StructFieldArraySpec {
+                primitive: PrimitiveType::Char,
+                max_length,
+                fixed: false,
+                string_semantics: true,
+            }),
+        )
+    }
+
+    /// Adds an array field of exactly `length` elements, with no length
+    /// member.
+    pub fn fixed_array(
+        self,
+        name: impl Into<String>,
+        primitive: PrimitiveType,
+        length: usize,
+    ) -> Self {
+        self.push(
+            name,
+            StructFieldType::Array(StructFieldArraySpec {
+                primitive,
+                max_length: length,
+                fixed: true,
+                string_semantics: false,
+            }),
+        )
+    }
+
+    /// Adds a `timestamp` field stored as `storage`, which must be
+    /// [`PrimitiveType::Uint32`] or [`PrimitiveType::Uint64`].
+    pub fn timestamp(
+        mut self,
+        name: impl Into<String>,
+        storage: PrimitiveType,
+        resolution: TimestampResolution,
+    ) -> Self {
+        self = self.push(name, StructFieldType::Primitive(storage));
+        if let Some(field) = self.fields.last_mut() {
+            field.timestamp = Some(resolution);
+        }
+        self
+    }
+
+    /// Adds a `bytes` field of exactly `length` bytes, e.g. a UUID.
+    pub fn bytes(self, name: impl Into<String>, length: usize) -> Self {
+        self.push(name, StructFieldType::Bytes(length))
+    }
+
+    /// Adds a nested struct field.
+    pub fn nested(self, name: impl Into<String>, spec: StructSpec) -> Self {
+        self.push(name, StructFieldType::Nested(spec))
+    }
+
+    /// Sets the byte order of the most recently added field. Does nothing
+    /// before the first field.
+    pub fn endian(mut self, endian: Endian) -> Self {
+        if let Some(field) = self.fields.last_mut() {
+            field.endian = endian;
+        }
+        self
+    }
+
+    /// Sets the fixed-point scaling of the most recently added field, which
+    /// must be of an integer type. Does nothing before the first field.
+    pub fn scaling(mut self, scaling: Scaling) -> Self {
+        if let Some(field) = self.fields.last_mut() {
+            field.scaling = Some(scaling);
+        }
+        self
+    }
+
+    /// Sets the allowed values of the most recently added field, which must
+    /// be a numeric scalar. Does nothing before the first field.
+    pub fn range(mut self, range: ValueRange) -> Self {
+        if let Some(field) = self.fields.last_mut() {
+            field.range = Some(float_bounds(range, field_primitive(&field.field_type)));
+        }
+        self
+    }
+
+    /// Describes the most recently added field in generated comments and
+    /// docs. Does nothing before the first field.
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        if let Some(field) = self.fields.last_mut() {
+            field.description = Some(description.into());
+        }
+        self
+    }
+
+    /// Sets the unit of the most recently added field, e.g. `"rpm"`. Does
+    /// nothing before the first field.
+    pub fn unit(mut self, unit: impl Into<String>) -> Self {
+        if let Some(field) = self.fields.last_mut() {
+            field.unit = Some(unit.into());
+        }
+        self
+    }
+
+    /// Marks the most recently added field as appended in message version
+    /// `since_version`, which must not exceed the version of the message.
+    /// Does nothing before the first field.
+    pub fn since_version(mut self, since_version: u8) -> Self {
+        if let Some(field) = self.fields.last_mut() {
+            field.since_version = Some(since_version);
+        }
+        self
+    }
+
+    /// Returns the fields, checking that there is at least one and that
+    /// names are unique. Array lengths are checked by
+    /// [`MessageBuilder::build`], which knows the active limits.
+    pub fn build(self) -> Result<StructSpec, IdlError> {
+        let spec = StructSpec {
+            fields: self.fields,
+            shared: None,
+        };
+        if spec.fields.is_empty() {
+            return Err(IdlError::invalid_value(
+                "/fields",
+                "{}",
+                "struct must define at least one field",
+            ));
+        }
+        let mut seen = HashSet::new();
+        for field in &spec.fields {
+            if !seen.insert(field.name.as_str()) {
+                return Err(IdlError::invalid_value(
+                    &pointer_push("/fields", &field.name),
+                    &field.name,
+                    format!("field '{}' is defined more than once", field.name),
+                ));
+            }
+        }
+        Ok(spec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_enforces_parser_limits() {
+        let err = MessageDefinition::scalar("ping", 256, PrimitiveType::Uint8)
+            .build()
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            IdlError::LimitExceeded {
+                value: 256,
+                max: 255,
+                ..
+            }
+        ));
+
+        let err = MessageDefinition::array("log", 1, PrimitiveType::Uint8, 0)
+            .build()
+            .unwrap_err();
+        assert_eq!(err.pointer(), Some("/packets/log/max_length"));
+
+        let metadata = Metadata {
+            max_payload_bytes: 2048,
+            max_array_length: 2048,
+            ..Default::default()
+        };
+        let big = || MessageDefinition::array("blob", 2, PrimitiveType::Uint8, 2000);
+        assert!(big().build().is_err());
+        assert!(big().limits(&metadata).build().is_ok());
+
+        let wide = Metadata {
+            packet_id_width: 16,
+            ..Default::default()
+        };
+        let ping = || MessageDefinition::scalar("ping", 256, PrimitiveType::Uint8);
+        assert!(ping().limits(&wide).build().is_ok());
+    }
+
+    #[test]
+    fn test_nested_struct_errors_point_at_the_field() {
+        let inner = StructSpec {
+            fields: vec![StructField {
+                name: "values".to_string(),
+                field_type: StructFieldType::Array(StructFieldArraySpec {
+                    primitive: PrimitiveType::Uint8,
+                    max_length: 5000,
+                    fixed: false,
+                    string_semantics: false,
+                }),
+                endian: Endian::Little,
+                scaling: None,
+                range: None,
+                description: None,
+                unit: None,
+                timestamp: None,
+                since_version: None,
+                at: None,
+                reserved: false,
+            }],
+            shared: None,
+        };
+        let outer = StructSpec::builder()
+            .field("id", PrimitiveType::Uint8)
+            .nested("inner", inner)
+            .build()
+            .unwrap();
+        let err = MessageDefinition::structure("frame", 4, outer)
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "/packets/frame/fields/inner/fields/values/max_length: array field 'values' in \
+             'frame.inner' has max_length 5000 which exceeds maximum of 1024 (configured limit)"
+        );
+    }
+
+    #[test]
+    fn test_struct_builder_rejects_empty_and_duplicate_fields() {
+        assert!(StructSpec::builder().build().is_err());
+        let err = StructSpec::builder()
+            .field("x", PrimitiveType::Int8)
+            .field("x", PrimitiveType::Int16)
+            .build()
+            .unwrap_err();
+        assert_eq!(err.pointer(), Some("/fields/x"));
+    }
+
+    #[test]
+    fn test_endian_applies_to_last_field() {
+        let spec = StructSpec::builder()
+            .endian(Endian::Big)
+            .field("a", PrimitiveType::Uint16)
+            .field("b", PrimitiveType::Uint16)
+            .endian(Endian::Big)
+            .build()
+            .unwrap();
+        assert_eq!(spec.fields[0].endian, Endian::Little);
+        assert_eq!(spec.fields[1].endian, Endian::Big);
+    }
+
+    #[test]
+    fn test_scaling_requires_integer_types() {
+        let scaling = Scaling {
+            scale: 0.1,
+            offset: 0.0,
+        };
+        assert!(
+            MessageDefinition::scalar("temp", 1, PrimitiveType::Int16)
+                .scaling(scaling)
+                .build()
+                .is_ok()
+        );
+        let err = MessageDefinition::scalar("speed", 1, PrimitiveType::Float32)
+            .scaling(scaling)
+            .build()
+            .unwrap_err();
+        assert_eq!(err.pointer(), Some("/packets/speed/scale"));
+
+        let fields = StructSpec::builder()
+            .field("ok", PrimitiveType::Uint8)
+            .scaling(scaling)
+            .field("flag", PrimitiveType::Bool)
+            .scaling(scaling)
+            .build()
+            .unwrap();
+        let err = MessageDefinition::structure("status", 2, fields)
+            .build()
+            .unwrap_err();
+        assert_eq!(err.pointer(), Some("/packets/status/fields/flag/scale"));
+    }
+
+    #[test]
+    fn test_range_must_fit_the_type() {
+        let range = ValueRange {
+            min: Some(Bound::Int(-1)),
+            max: Some(Bound::Int(100)),
+        };
+        assert!(
+            MessageDefinition::scalar("trim", 1, PrimitiveType::Int8)
+                .range(range)
+                .build()
+                .is_ok()
+        );
+        let err = MessageDefinition::scalar("speed", 1, PrimitiveType::Uint8)
+            .range(range)
+            .build()
+            .unwrap_err();
+        assert_eq!(err.pointer(), Some("/packets/speed/min"));
+
+        let message = MessageDefinition::scalar("gain", 2, PrimitiveType::Float32)
+            .range(range)
+            .build()
+            .unwrap();
+        let MessageBody::Scalar(spec) = &message.body else {
+            panic!("expected scalar body");
+        };
+        assert_eq!(spec.range.unwrap().max, Some(Bound::Float(100.0)));
+
+        let fields = StructSpec::builder()
+            .field("level", PrimitiveType::Uint16)
+            .range(range)
+            .build()
+            .unwrap();
+        let err = MessageDefinition::structure("status", 3, fields)
+            .build()
+            .unwrap_err();
+        assert_eq!(err.pointer(), Some("/packets/status/fields/level/min"));
+    }
+}
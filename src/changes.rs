@@ -0,0 +1,1068 @@
+//! Semantic diff of two message sets.
+//!
+//! Compares two versions of a protocol by what changed on the wire rather
+//! than in the JSON text. Messages are matched by name, then by packet ID,
+//! and every difference is classified as breaking or compatible. A change
+//! is compatible when the new decoders still accept every payload the old
+//! encoders produce, with the same meaning.
+
+use std::cmp::Ordering;
+use std::fmt::Write as FmtWrite;
+
+use serde_json::{Value, json};
+
+use crate::{
+    Bound, Endian, MessageBody, MessageDefinition, PrimitiveType, Scaling, StructFieldType,
+    StructSpec, TimestampResolution, UnionSpec, UnionVariant, ValueRange, message_body_max_size,
+    message_body_min_size,
+};
+
+/// Whether a change keeps the old payloads decodable.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Breaking,
+    Compatible,
+}
+
+impl Severity {
+    pub fn name(self) -> &'static str {
+        match self {
+            Severity::Breaking => "breaking",
+            Severity::Compatible => "compatible",
+        }
+    }
+}
+
+/// What kind of difference a [`Change`] is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChangeKind {
+    MessageAdded,
+    MessageRemoved,
+    /// Same packet ID under another name; the wire is unchanged
+    MessageRenamed,
+    PacketIdChanged,
+    /// Scalar, array, struct or union became another of the four
+    ShapeChanged,
+    PayloadSizeChanged,
+    FieldAdded,
+    FieldRemoved,
+    FieldRetyped,
+    FieldMoved,
+    ArrayLengthChanged,
+    EndiannessChanged,
+    RangeChanged,
+    ScalingChanged,
+    /// A union gained a tag value; old payloads never carry it
+    VariantAdded,
+    VariantRemoved,
+}
+
+impl ChangeKind {
+    /// Identifier used in the JSON report.
+    pub fn name(self) -> &'static str {
+        match self {
+            ChangeKind::MessageAdded => "message_added",
+            ChangeKind::MessageRemoved => "message_removed",
+            ChangeKind::MessageRenamed => "message_renamed",
+            ChangeKind::PacketIdChanged => "packet_id_changed",
+            ChangeKind::ShapeChanged => "shape_changed",
+            ChangeKind::PayloadSizeChanged => "payload_size_changed",
+            ChangeKind::FieldAdded => "field_added",
+            ChangeKind::FieldRemoved => "field_removed",
+            ChangeKind::FieldRetyped => "field_retyped",
+            ChangeKind::FieldMoved => "field_moved",
+            ChangeKind::ArrayLengthChanged => "array_length_changed",
+            ChangeKind::EndiannessChanged => "endianness_changed",
+            ChangeKind::RangeChanged => "range_changed",
+            ChangeKind::ScalingChanged => "scaling_changed",
+            ChangeKind::VariantAdded => "variant_added",
+            ChangeKind::VariantRemoved => "variant_removed",
+        }
+    }
+
+    /// Wording used in the markdown report.
+    fn label(self) -> &'static str {
+        match self {
+            ChangeKind::MessageAdded => "message added",
+            ChangeKind::MessageRemoved => "message removed",
+            ChangeKind::MessageRenamed => "message renamed",
+            ChangeKind::PacketIdChanged => "packet ID changed",
+            ChangeKind::ShapeChanged => "shape changed",
+            ChangeKind::PayloadSizeChanged => "payload size changed",
+            ChangeKind::FieldAdded => "field added",
+            ChangeKind::FieldRemoved => "field removed",
+            ChangeKind::FieldRetyped => "field retyped",
+            ChangeKind::FieldMoved => "field moved",
+            ChangeKind::ArrayLengthChanged => "max_length changed",
+            ChangeKind::EndiannessChanged => "endianness changed",
+            ChangeKind::RangeChanged => "range changed",
+            ChangeKind::ScalingChanged => "scaling changed",
+            ChangeKind::VariantAdded => "variant added",
+            ChangeKind::VariantRemoved => "variant removed",
+        }
+    }
+}
+
+/// One difference between the old and the new message set.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Change {
+    /// Name of the message in the new set, or in the old one when removed
+    pub message: String,
+    pub packet_id: u32,
+    /// Dotted path of the field; `value` or `data` for scalar and array
+    /// messages, `None` for changes of the whole message
+    pub field: Option<String>,
+    pub kind: ChangeKind,
+    pub severity: Severity,
+    /// Old and new value, e.g. `int16_t -> int32_t`
+    pub detail: String,
+}
+
+/// Result of [`semantic_diff`], ordered by packet ID.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DiffReport {
+    pub changes: Vec<Change>,
+}
+
+impl DiffReport {
+    pub fn breaking(&self) -> usize {
+        self.count(Severity::Breaking)
+    }
+
+    pub fn compatible(&self) -> usize {
+        self.count(Severity::Compatible)
+    }
+
+    fn count(&self, severity: Severity) -> usize {
+        self.changes
+            .iter()
+            .filter(|change| change.severity == severity)
+            .count()
+    }
+
+    /// Human-readable report with one table per severity.
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "# Protocol Changes").unwrap();
+        writeln!(out).unwrap();
+        if self.changes.is_empty() {
+            writeln!(out, "No wire-level changes.").unwrap();
+            return out;
+        }
+        writeln!(
+            out,
+            "{} breaking and {} compatible change(s).",
+            self.breaking(),
+            self.compatible()
+        )
+        .unwrap();
+        for (severity, title) in [
+            (Severity::Breaking, "Breaking Changes"),
+            (Severity::Compatible, "Compatible Changes"),
+        ] {
+            let changes: Vec<&Change> = self
+                .changes
+                .iter()
+                .filter(|change| change.severity == severity)
+                .collect();
+            if changes.is_empty() {
+                continue;
+            }
+            writeln!(out).unwrap();
+            writeln!(out, "## {}", title).unwrap();
+            writeln!(out).unwrap();
+            writeln!(out, "| Message | Packet ID | Field | Change |").unwrap();
+            writeln!(out, "|---------|-----------|-------|--------|").unwrap();
+            for change in changes {
+                writeln!(
+                    out,
+                    "| `{}` | {} | {} | {}: {} |",
+                    change.message,
+                    change.packet_id,
+                    change
+                        .field
+                        .as_ref()
+                        .map_or_else(String::new, |field| format!("`{}`", field)),
+                    change.kind.label(),
+                    change.detail.replace('|', "\\|")
+                )
+                .unwrap();
+            }
+        }
+        out
+    }
+
+    /// Machine-readable report for CI gates.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "breaking": self.breaking(),
+            "compatible": self.compatible(),
+            "changes": self.changes.iter().map(|change| json!({
+                "message": change.message,
+                "packet_id": change.packet_id,
+                "field": change.field,
+                "kind": change.kind.name(),
+                "severity": change.severity.name(),
+                "detail": change.detail,
+            })).collect::<Vec<_>>(),
+        })
+    }
+}
+
+/// Compares two message sets. Messages are paired by name; a leftover old
+/// and new message with the same packet ID count as a rename.
+pub fn semantic_diff(old: &[MessageDefinition], new: &[MessageDefinition]) -> DiffReport {
+    let mut changes = Vec::new();
+    let mut unmatched_old: Vec<&MessageDefinition> = Vec::new();
+    let mut matched_new = vec![false; new.len()];
+
+    for old_msg in old {
+        match new.iter().position(|m| m.name == old_msg.name) {
+            Some(index) => {
+                matched_new[index] = true;
+                compare_messages(old_msg, &new[index], &mut changes);
+            }
+            None => unmatched_old.push(old_msg),
+        }
+    }
+    for old_msg in unmatched_old {
+        let renamed = new
+            .iter()
+            .enumerate()
+            .find(|(index, m)| !matched_new[*index] && m.packet_id == old_msg.packet_id);
+        match renamed {
+            Some((index, new_msg)) => {
+                matched_new[index] = true;
+                changes.push(message_change(
+                    new_msg,
+                    ChangeKind::MessageRenamed,
+                    Severity::Compatible,
+                    format!("{} -> {}", old_msg.name, new_msg.name),
+                ));
+                compare_messages(old_msg, new_msg, &mut changes);
+            }
+            None => changes.push(message_change(
+                old_msg,
+                ChangeKind::MessageRemoved,
+                Severity::Breaking,
+                format!("{} {}", old_msg.body.kind(), size_text(&old_msg.body)),
+            )),
+        }
+    }
+    for (new_msg, _) in new
+        .iter()
+        .zip(&matched_new)
+        .filter(|(_, matched)| !**matched)
+    {
+        changes.push(message_change(
+            new_msg,
+            ChangeKind::MessageAdded,
+            Severity::Compatible,
+            format!("{} {}", new_msg.body.kind(), size_text(&new_msg.body)),
+        ));
+    }
+
+    changes.sort_by_key(|change| change.packet_id);
+    DiffReport { changes }
+}
+
+fn message_change(
+    msg: &MessageDefinition,
+    kind: ChangeKind,
+    severity: Severity,
+    detail: String,
+) -> Change {
+    Change {
+        message: msg.name.clone(),
+        packet_id: msg.packet_id,
+        field: None,
+        kind,
+        severity,
+        detail,
+    }
+}
+
+/// `(min, max)` payload size of a body.
+fn payload_size(body: &MessageBody) -> (usize, usize) {
+    (message_body_min_size(body), message_body_max_size(body))
+}
+
+fn size_text(body: &MessageBody) -> String {
+    match payload_size(body) {
+        (1, 1) => "1 byte".to_string(),
+        (min, max) if min == max => format!("{} bytes", max),
+        (min, max) => format!("{}-{} bytes", min, max),
+    }
+}
+
+fn compare_messages(old: &MessageDefinition, new: &MessageDefinition, out: &mut Vec<Change>) {
+    if old.packet_id != new.packet_id {
+        out.push(message_change(
+            new,
+            ChangeKind::PacketIdChanged,
+            Severity::Breaking,
+            format!("{} -> {}", old.packet_id, new.packet_id),
+        ));
+    }
+    if old.body.kind() != new.body.kind() {
+        out.push(message_change(
+            new,
+            ChangeKind::ShapeChanged,
+            Severity::Breaking,
+            format!("{} -> {}", old.body.kind(), new.body.kind()),
+        ));
+        return;
+    }
+
+    let (old_min, old_max) = payload_size(&old.body);
+    let (new_min, new_max) = payload_size(&new.body);
+    if (old_min, old_max) != (new_min, new_max) {
+        // Every old payload length must still be accepted
+        let severity = if new_min <= old_min && new_max >= old_max {
+            Severity::Compatible
+        } else {
+            Severity::Breaking
+        };
+        out.push(message_change(
+            new,
+            ChangeKind::PayloadSizeChanged,
+            severity,
+            format!("{} -> {}", size_text(&old.body), size_text(&new.body)),
+        ));
+    }
+
+    if let (MessageBody::Union(old_spec), MessageBody::Union(new_spec)) = (&old.body, &new.body) {
+        compare_variants(new, old_spec, new_spec, out);
+    } else {
+        compare_leaves(new, &leaves(&old.body), &leaves(&new.body), out);
+    }
+}
+
+/// Compares the tags of two versions of a union and every variant kept
+/// under the same tag value. Variants are decoded independently, so each
+/// is compared like a struct message of its own.
+fn compare_variants(
+    new: &MessageDefinition,
+    old_spec: &UnionSpec,
+    new_spec: &UnionSpec,
+    out: &mut Vec<Change>,
+) {
+    compare_leaves(new, &[tag_leaf(old_spec)], &[tag_leaf(new_spec)], out);
+    let variant_change = |variant: &UnionVariant, kind, severity| Change {
+        message: new.name.clone(),
+        packet_id: new.packet_id,
+        field: Some(variant.name.clone()),
+        kind,
+        severity,
+        detail: format!("tag {}", variant.tag),
+    };
+    for old_variant in &old_spec.variants {
+        if new_spec.variant(old_variant.tag).is_none() {
+            out.push(variant_change(
+                old_variant,
+                ChangeKind::VariantRemoved,
+                Severity::Breaking,
+            ));
+        }
+    }
+    for new_variant in &new_spec.variants {
+        // Matched by tag value, so a renamed variant compares field by field
+        let prefix = format!("{}.", new_variant.name);
+        match old_spec.variant(new_variant.tag) {
+            Some(old_variant) => compare_leaves(
+                new,
+                &struct_leaves(&old_variant.spec, &prefix),
+                &struct_leaves(&new_variant.spec, &prefix),
+                out,
+            ),
+            None => out.push(variant_change(
+                new_variant,
+                ChangeKind::VariantAdded,
+                Severity::Compatible,
+            )),
+        }
+    }
+}
+
+fn compare_leaves(
+    new: &MessageDefinition,
+    old_leaves: &[Leaf],
+    new_leaves: &[Leaf],
+    out: &mut Vec<Change>,
+) {
+    let field_change = |leaf: &Leaf, kind, severity, detail| Change {
+        message: new.name.clone(),
+        packet_id: new.packet_id,
+        field: Some(leaf.path.clone()),
+        kind,
+        severity,
+        detail,
+    };
+
+    for old_leaf in old_leaves {
+        if !new_leaves.iter().any(|leaf| leaf.path == old_leaf.path) {
+            out.push(field_change(
+                old_leaf,
+                ChangeKind::FieldRemoved,
+                Severity::Breaking,
+                old_leaf.type_text(),
+            ));
+        }
+    }
+    for (index, new_leaf) in new_leaves.iter().enumerate() {
+        // Greedy decoding hands spare bytes to the arrays in order, so only
+        // the last array can grow without shifting old payloads
+        let last_array = new_leaves[index + 1..]
+            .iter()
+            .all(|leaf| leaf.max_length.is_none() || leaf.fixed);
+        let Some(old_leaf) = old_leaves.iter().find(|leaf| leaf.path == new_leaf.path) else {
+            let severity = if new_leaf.max_length.is_some() && !new_leaf.fixed && last_array {
+                Severity::Compatible
+            } else {
+                Severity::Breaking
+            };
+            out.push(field_change(
+                new_leaf,
+                ChangeKind::FieldAdded,
+                severity,
+                new_leaf.type_text(),
+            ));
+            continue;
+        };
+
+        if old_leaf.primitive != new_leaf.primitive
+            || old_leaf.max_length.is_some() != new_leaf.max_length.is_some()
+            || old_leaf.bytes != new_leaf.bytes
+            || old_leaf.fixed != new_leaf.fixed
+            || matches!(
+                (old_leaf.timestamp, new_leaf.timestamp),
+                (Some(old), Some(new)) if old != new
+            )
+        {
+            out.push(field_change(
+                new_leaf,
+                ChangeKind::FieldRetyped,
+                Severity::Breaking,
+                format!("{} -> {}", old_leaf.type_text(), new_leaf.type_text()),
+            ));
+            continue;
+        }
+        if let (Some(old_max), Some(new_max)) = (old_leaf.max_length, new_leaf.max_length)
+            && old_max != new_max
+        {
+            let severity = if new_max > old_max && !new_leaf.fixed && last_array {
+                Severity::Compatible
+            } else {
+                Severity::Breaking
+            };
+            out.push(field_change(
+                new_leaf,
+                ChangeKind::ArrayLengthChanged,
+                severity,
+                format!("{} -> {}", old_max, new_max),
+            ));
+        }
+        if old_leaf.endian != new_leaf.endian && new_leaf.primitive.byte_len() > 1 {
+            out.push(field_change(
+                new_leaf,
+                ChangeKind::EndiannessChanged,
+                Severity::Breaking,
+                format!("{} -> {}", old_leaf.endian.name(), new_leaf.endian.name()),
+            ));
+        }
+        if old_leaf.range != new_leaf.range {
+            let old_range = old_leaf.range.unwrap_or_default();
+            let new_range = new_leaf.range.unwrap_or_default();
+            let widened = covers(new_range.min, old_range.min, Ordering::Less)
+                && covers(new_range.max, old_range.max, Ordering::Greater);
+            out.push(field_change(
+                new_leaf,
+                ChangeKind::RangeChanged,
+                if widened {
+                    Severity::Compatible
+                } else {
+                    Severity::Breaking
+                },
+                format!("{} -> {}", range_text(old_range), range_text(new_range)),
+            ));
+        }
+        if old_leaf.scaling != new_leaf.scaling {
+            out.push(field_change(
+                new_leaf,
+                ChangeKind::ScalingChanged,
+                Severity::Breaking,
+                format!(
+                    "{} -> {}",
+                    scaling_text(old_leaf.scaling),
+                    scaling_text(new_leaf.scaling)
+                ),
+            ));
+        }
+    }
+
+    // Fields kept by both versions must stay in the same wire order
+    let kept_old: Vec<&str> = old_leaves
+        .iter()
+        .map(|leaf| leaf.path.as_str())
+        .filter(|path| new_leaves.iter().any(|leaf| leaf.path == *path))
+        .collect();
+    let kept_new: Vec<&Leaf> = new_leaves
+        .iter()
+        .filter(|leaf| kept_old.contains(&leaf.path.as_str()))
+        .collect();
+    for (position, leaf) in kept_new.iter().enumerate() {
+        if kept_old[position] != leaf.path {
+            let old_position = kept_old.iter().position(|path| *path == leaf.path).unwrap();
+            out.push(field_change(
+                leaf,
+                ChangeKind::FieldMoved,
+                Severity::Breaking,
+                format!(
+                    "position {} -> {} among the kept fields",
+                    old_position + 1,
+                    position + 1
+                ),
+            ));
+        }
+    }
+}
+
+/// A primitive value or array of a message, in wire order.
+struct Leaf {
+    path: String,
+    primitive: PrimitiveType,
+    /// `Some` for arrays
+    max_length: Option<usize>,
+    endian: Endian,
+    range: Option<ValueRange>,
+    scaling: Option<Scaling>,
+    timestamp: Option<TimestampResolution>,
+    /// Set on `bytes` fields, which never change size compatibly
+    bytes: Option<usize>,
+    /// A `fixed_length` array, which takes no spare bytes
+    fixed: bool,
+}
+
+impl Leaf {
+    fn type_text(&self) -> String {
+        if let Some(length) = self.bytes {
+            return format!("bytes[{}]", length);
+        }
+        match (self.max_length, self.timestamp) {
+            (Some(max_length), _) if self.fixed => {
+                format!("{}[{}] (fixed)", self.primitive.wire_type(), max_length)
+            }
+            (Some(max_length), _) => format!("{}[{}]", self.primitive.wire_type(), max_length),
+            (None, Some(resolution)) => format!(
+                "timestamp ({}, {})",
+                self.primitive.wire_type(),
+                resolution.name()
+            ),
+            (None, None) => self.primitive.wire_type().to_string(),
+        }
+    }
+}
+
+fn leaves(body: &MessageBody) -> Vec<Leaf> {
+    let mut out = Vec::new();
+    match body {
+        MessageBody::Scalar(spec) => out.push(Leaf {
+            path: "value".to_string(),
+            primitive: spec.primitive,
+            max_length: None,
+            endian: spec.endian,
+            range: spec.range,
+            scaling: spec.scaling,
+            timestamp: None,
+            bytes: None,
+            fixed: false,
+        }),
+        MessageBody::Array(spec) => out.push(Leaf {
+            path: "data".to_string(),
+            primitive: spec.primitive,
+            max_length: Some(spec.max_length),
+            endian: spec.endian,
+            range: None,
+            scaling: spec.scaling,
+            timestamp: None,
+            bytes: None,
+            fixed: false,
+        }),
+        MessageBody::Struct(spec) => return struct_leaves(spec, ""),
+        // Variants are compared one by one
+        MessageBody::Union(spec) => out.push(tag_leaf(spec)),
+    }
+    out
+}
+
+fn tag_leaf(spec: &UnionSpec) -> Leaf {
+    Leaf {
+        path: "tag".to_string(),
+        primitive: spec.tag,
+        max_length: None,
+        endian: spec.endian,
+        range: None,
+        scaling: None,
+        timestamp: None,
+        bytes: None,
+        fixed: false,
+    }
+}
+
+/// The leaves of a struct, their paths starting with `prefix`.
+fn struct_leaves(spec: &StructSpec, prefix: &str) -> Vec<Leaf> {
+    fn visit(spec: &StructSpec, prefix: &str, out: &mut Vec<Leaf>) {
+        for field in &spec.fields {
+            let path = format!("{}{}", prefix, field.name);
+            let (primitive, max_length, bytes) = match &field.field_type {
+                StructFieldType::Primitive(primitive) => (*primitive, None, None),
+                StructFieldType::Array(array) => (array.primitive, Some(array.max_length), None),
+                StructFieldType::Bytes(length) => (PrimitiveType::Uint8, None, Some(*length)),
+                StructFieldType::Nested(nested) => {
+                    visit(nested, &format!("{}.", path), out);
+                    continue;
+                }
+            };
+            out.push(Leaf {
+                path,
+                primitive,
+                max_length,
+                endian: field.endian,
+                range: field.range,
+                scaling: field.scaling,
+                timestamp: field.timestamp,
+                bytes,
+                fixed: matches!(&field.field_type, StructFieldType::Array(array) if array.fixed),
+            });
+        }
+    }
+
+    let mut out = Vec::new();
+    visit(spec, prefix, &mut out);
+    out
+}
+
+/// Whether the new bound allows everything the old one did: it is missing,
+/// equal, or further out in the `outward` direction.
+fn covers(new: Option<Bound>, old: Option<Bound>, outward: Ordering) -> bool {
+    match (new, old) {
+        (None, _) => true,
+        (Some(_), None) => false,
+        (Some(new), Some(old)) => {
+            let ordering = match (new, old) {
+                (Bound::Int(new), Bound::Int(old)) => new.cmp(&old),
+                _ => new
+                    .as_f64()
+                    .partial_cmp(&old.as_f64())
+                    .unwrap_or(Ordering::Equal),
+            };
+            ordering == Ordering::Equal || ordering == outward
+        }
+    }
+}
+
+fn range_text(range: ValueRange) -> String {
+    let side = |bound: Option<Bound>| bound.map_or_else(String::new, |b| b.to_string());
+    format!("{}..={}", side(range.min), side(range.max))
+}
+
+fn scaling_text(scaling: Option<Scaling>) -> String {
+    match scaling {
+        Some(scaling) => format!("scale {:?}, offset {:?}", scaling.scale, scaling.offset),
+        None => "none".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(json: &str) -> Vec<MessageDefinition> {
+        crate::parse_str(json).unwrap().1
+    }
+
+    /// `(message, field, kind, severity)` of every change.
+    fn summary(old: &str, new: &str) -> Vec<(String, Option<String>, ChangeKind, Severity)> {
+        semantic_diff(&messages(old), &messages(new))
+            .changes
+            .into_iter()
+            .map(|c| (c.message, c.field, c.kind, c.severity))
+            .collect()
+    }
+
+    fn change(
+        message: &str,
+        field: Option<&str>,
+        kind: ChangeKind,
+        severity: Severity,
+    ) -> (String, Option<String>, ChangeKind, Severity) {
+        (
+            message.to_string(),
+            field.map(str::to_string),
+            kind,
+            severity,
+        )
+    }
+
+    const STATUS: &str = r#"{
+        "ping": { "packet_id": 1, "msg_type": "uint8" },
+        "status": { "packet_id": 2, "msg_type": "struct", "fields": {
+            "mode": { "type": "u8", "min": 0, "max": 3 },
+            "temp": { "type": "i16" },
+            "log": { "type": "char", "array": true, "max_length": 8 } } } }"#;
+
+    #[test]
+    fn test_identical_sets_have_no_changes() {
+        let report = semantic_diff(&messages(STATUS), &messages(STATUS));
+        assert!(report.changes.is_empty());
+        assert_eq!(
+            report.to_markdown(),
+            "# Protocol Changes\n\nNo wire-level changes.\n"
+        );
+    }
+
+    #[test]
+    fn test_messages_added_removed_and_renamed() {
+        let new = r#"{
+            "heartbeat": { "packet_id": 1, "msg_type": "uint8" },
+            "reset": { "packet_id": 9, "msg_type": "uint8" } }"#;
+        assert_eq!(
+            summary(STATUS, new),
+            [
+                change(
+                    "heartbeat",
+                    None,
+                    ChangeKind::MessageRenamed,
+                    Severity::Compatible
+                ),
+                change(
+                    "status",
+                    None,
+                    ChangeKind::MessageRemoved,
+                    Severity::Breaking
+                ),
+                change(
+                    "reset",
+                    None,
+                    ChangeKind::MessageAdded,
+                    Severity::Compatible
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_packet_id_and_shape_changes_are_breaking() {
+        let old = r#"{ "ping": { "packet_id": 1, "msg_type": "uint8" } }"#;
+        let new = r#"{ "ping": { "packet_id": 5, "msg_type": "uint8", "array": true, "max_length": 4 } }"#;
+        assert_eq!(
+            summary(old, new),
+            [
+                change(
+                    "ping",
+                    None,
+                    ChangeKind::PacketIdChanged,
+                    Severity::Breaking
+                ),
+                change("ping", None, ChangeKind::ShapeChanged, Severity::Breaking),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_appending_an_array_is_compatible() {
+        let new = STATUS.replace(
+            r#""max_length": 8 } } } }"#,
+            r#""max_length": 8 },
+               "tail": { "type": "u8", "array": true, "max_length": 4 } } } }"#,
+        );
+        assert_eq!(
+            summary(STATUS, &new),
+            [
+                change(
+                    "status",
+                    None,
+                    ChangeKind::PayloadSizeChanged,
+                    Severity::Compatible
+                ),
+                change(
+                    "status",
+                    Some("tail"),
+                    ChangeKind::FieldAdded,
+                    Severity::Compatible
+                ),
+            ]
+        );
+
+        // In front of another array it takes the bytes that array had
+        let new = STATUS.replace(
+            r#""log":"#,
+            r#""head": { "type": "u8", "array": true, "max_length": 4 }, "log":"#,
+        );
+        assert_eq!(
+            summary(STATUS, &new),
+            [
+                change(
+                    "status",
+                    None,
+                    ChangeKind::PayloadSizeChanged,
+                    Severity::Compatible
+                ),
+                change(
+                    "status",
+                    Some("head"),
+                    ChangeKind::FieldAdded,
+                    Severity::Breaking
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fields_added_removed_and_moved() {
+        // A fixed field grows the minimum size, which old payloads lack
+        let new = r#"{
+            "ping": { "packet_id": 1, "msg_type": "uint8" },
+            "status": { "packet_id": 2, "msg_type": "struct", "fields": {
+                "temp": { "type": "i16" },
+                "mode": { "type": "u8", "min": 0, "max": 3 },
+                "flags": { "type": "u8" } } } }"#;
+        assert_eq!(
+            summary(STATUS, new),
+            [
+                change(
+                    "status",
+                    None,
+                    ChangeKind::PayloadSizeChanged,
+                    Severity::Breaking
+                ),
+                change(
+                    "status",
+                    Some("log"),
+                    ChangeKind::FieldRemoved,
+                    Severity::Breaking
+                ),
+                change(
+                    "status",
+                    Some("flags"),
+                    ChangeKind::FieldAdded,
+                    Severity::Breaking
+                ),
+                change(
+                    "status",
+                    Some("temp"),
+                    ChangeKind::FieldMoved,
+                    Severity::Breaking
+                ),
+                change(
+                    "status",
+                    Some("mode"),
+                    ChangeKind::FieldMoved,
+                    Severity::Breaking
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fixed_length_arrays_never_grow_compatibly() {
+        let old = STATUS.replace(
+            r#""max_length": 8 }"#,
+            r#""max_length": 8 },
+            "gains": { "type": "u8", "array": true, "fixed_length": 2 }"#,
+        );
+        let new = old
+            .replace(r#""max_length": 8"#, r#""max_length": 16"#)
+            .replace(r#""fixed_length": 2"#, r#""fixed_length": 3"#);
+        assert_eq!(
+            summary(&old, &new),
+            [
+                change(
+                    "status",
+                    None,
+                    ChangeKind::PayloadSizeChanged,
+                    Severity::Breaking
+                ),
+                change(
+                    "status",
+                    Some("log"),
+                    ChangeKind::ArrayLengthChanged,
+                    Severity::Compatible
+                ),
+                change(
+                    "status",
+                    Some("gains"),
+                    ChangeKind::ArrayLengthChanged,
+                    Severity::Breaking
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_union_variants_matched_by_tag() {
+        let old = r#"{ "sample": { "packet_id": 3, "msg_type": "union", "tag": "u8", "variants": {
+            "1": { "name": "gps", "fields": { "lat": { "type": "i32" }, "lon": { "type": "i32" } } },
+            "2": { "name": "odom", "fields": { "ticks": { "type": "i32" } } } } } }"#;
+        let new = r#"{ "sample": { "packet_id": 3, "msg_type": "union", "tag": "u8", "variants": {
+            "1": { "name": "gps", "fields": { "lat": { "type": "i32" }, "lon": { "type": "i16" } } },
+            "3": { "name": "imu", "fields": { "rate": { "type": "i16" } } } } } }"#;
+        assert_eq!(
+            summary(old, new),
+            [
+                change(
+                    "sample",
+                    None,
+                    ChangeKind::PayloadSizeChanged,
+                    Severity::Breaking
+                ),
+                change(
+                    "sample",
+                    Some("odom"),
+                    ChangeKind::VariantRemoved,
+                    Severity::Breaking
+                ),
+                change(
+                    "sample",
+                    Some("gps.lon"),
+                    ChangeKind::FieldRetyped,
+                    Severity::Breaking
+                ),
+                change(
+                    "sample",
+                    Some("imu"),
+                    ChangeKind::VariantAdded,
+                    Severity::Compatible
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_field_types_lengths_and_byte_order() {
+        let new = r#"{
+            "ping": { "packet_id": 1, "msg_type": "uint8" },
+            "status": { "packet_id": 2, "msg_type": "struct", "fields": {
+                "mode": { "type": "u8", "endianess": "big", "min": 0, "max": 3 },
+                "temp": { "type": "i16", "endianess": "big" },
+                "log": { "type": "char", "array": true, "max_length": 16 } } } }"#;
+        assert_eq!(
+            summary(STATUS, new),
+            [
+                change(
+                    "status",
+                    None,
+                    ChangeKind::PayloadSizeChanged,
+                    Severity::Compatible
+                ),
+                change(
+                    "status",
+                    Some("temp"),
+                    ChangeKind::EndiannessChanged,
+                    Severity::Breaking
+                ),
+                change(
+                    "status",
+                    Some("log"),
+                    ChangeKind::ArrayLengthChanged,
+                    Severity::Compatible
+                ),
+            ]
+        );
+
+        let new = STATUS
+            .replace(r#""type": "i16""#, r#""type": "i32""#)
+            .replace(r#""max_length": 8"#, r#""max_length": 4"#);
+        let report = semantic_diff(&messages(STATUS), &messages(&new));
+        let details: Vec<(ChangeKind, Severity, &str)> = report
+            .changes
+            .iter()
+            .map(|c| (c.kind, c.severity, c.detail.as_str()))
+            .collect();
+        assert_eq!(
+            details,
+            [
+                (
+                    ChangeKind::PayloadSizeChanged,
+                    Severity::Breaking,
+                    "3-11 bytes -> 5-9 bytes"
+                ),
+                (
+                    ChangeKind::FieldRetyped,
+                    Severity::Breaking,
+                    "int16_t -> int32_t"
+                ),
+                (ChangeKind::ArrayLengthChanged, Severity::Breaking, "8 -> 4"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ranges_and_scaling() {
+        let widened = STATUS.replace(r#""max": 3"#, r#""max": 7"#);
+        let narrowed = STATUS.replace(r#""min": 0, "max": 3"#, r#""min": 1"#);
+        let scaled = STATUS.replace(r#""type": "i16""#, r#""type": "i16", "scale": 0.1"#);
+        let kinds = |new: &str| -> Vec<(ChangeKind, Severity, String)> {
+            semantic_diff(&messages(STATUS), &messages(new))
+                .changes
+                .into_iter()
+                .map(|c| (c.kind, c.severity, c.detail))
+                .collect()
+        };
+        assert_eq!(
+            kinds(&widened),
+            [(
+                ChangeKind::RangeChanged,
+                Severity::Compatible,
+                "0..=3 -> 0..=7".to_string()
+            )]
+        );
+        assert_eq!(
+            kinds(&narrowed),
+            [(
+                ChangeKind::RangeChanged,
+                Severity::Breaking,
+                "0..=3 -> 1..=".to_string()
+            )]
+        );
+        assert_eq!(
+            kinds(&scaled),
+            [(
+                ChangeKind::ScalingChanged,
+                Severity::Breaking,
+                "none -> scale 0.1, offset 0.0".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_report_formats() {
+        let new = r#"{
+            "ping": { "packet_id": 1, "msg_type": "uint16" },
+            "reset": { "packet_id": 9, "msg_type": "uint8" } }"#;
+        let report = semantic_diff(&messages(STATUS), &messages(new));
+        assert_eq!(report.breaking(), 3);
+        assert_eq!(report.compatible(), 1);
+        assert_eq!(
+            report.to_markdown(),
+            "# Protocol Changes\n\n\
+             3 breaking and 1 compatible change(s).\n\n\
+             ## Breaking Changes\n\n\
+             | Message | Packet ID | Field | Change |\n\
+             |---------|-----------|-------|--------|\n\
+             | `ping` | 1 |  | payload size changed: 1 byte -> 2 bytes |\n\
+             | `ping` | 1 | `value` | field retyped: uint8_t -> uint16_t |\n\
+             | `status` | 2 |  | message removed: struct 3-11 bytes |\n\n\
+             ## Compatible Changes\n\n\
+             | Message | Packet ID | Field | Change |\n\
+             |---------|-----------|-------|--------|\n\
+             | `reset` | 9 |  | message added: scalar 1 byte |\n"
+        );
+        let json = report.to_json();
+        assert_eq!(json["breaking"], 3);
+        assert_eq!(json["changes"][1]["field"], "value");
+        assert_eq!(json["changes"][1]["kind"], "field_retyped");
+        assert_eq!(json["changes"][3]["severity"], "compatible");
+        assert_eq!(json["changes"][3]["field"], Value::Null);
+    }
+}
@@ -0,0 +1,572 @@
+//! Command line parsing.
+//!
+//! The options are declared once on [`Cli`] with clap's derive API, which
+//! also generates `--help`, `--version` and the "similar argument" hint for
+//! misspelled flags. Options taking one of a fixed set of values parse into
+//! [`ValueEnum`]s, and flags that exclude or need each other say so with
+//! clap's `conflicts_with` and `requires`. The positional arguments are
+//! resolved afterwards by [`Invocation::resolve`], which keeps the
+//! deprecated positional language.
+
+use std::ffi::OsString;
+use std::path::Path;
+
+use clap::error::ErrorKind;
+use clap::{ArgGroup, CommandFactory, Parser, Subcommand, ValueEnum};
+
+use crate::emit_c::{CDialect, EncodeRange, ImplStyle, Package, StructLayout};
+use crate::list::SortKey;
+use crate::{InputFormat, SourceComment, TargetLanguage};
+
+/// What the command line asks for.
+#[derive(Debug)]
+pub(crate) enum Command {
+    Run(Box<Cli>),
+    Diff(DiffArgs),
+}
+
+/// Generate C99 serializers and markdown docs from a JSON message definition.
+#[derive(Debug, Parser)]
+#[command(
+    version,
+    args_conflicts_with_subcommands = true,
+    disable_help_subcommand = true
+)]
+struct Invocation {
+    #[command(subcommand)]
+    command: Option<Subcommands>,
+    /// Message definition file, directory of *.json files, or '-' for stdin
+    /// [default: msgs/intermediate_msg.json]
+    #[arg(value_name = "INPUT")]
+    input: Option<String>,
+    /// Output directory, or '-' for stdout [default: generated_c, or docs
+    /// for the --export_* flags]
+    #[arg(value_name = "OUTPUT")]
+    output: Option<String>,
+    /// Output of the deprecated `h6xserial_idl c INPUT OUTPUT` form
+    #[arg(hide = true)]
+    legacy_output: Option<String>,
+    #[command(flatten)]
+    cli: Cli,
+}
+
+#[derive(Debug, Subcommand)]
+enum Subcommands {
+    /// Compare two versions of a definition by their wire format
+    Diff(DiffArgs),
+}
+
+/// Arguments of `diff OLD NEW`.
+#[derive(Debug, clap::Args)]
+pub(crate) struct DiffArgs {
+    /// Old version of the definition
+    pub(crate) old: String,
+    /// New version of the definition
+    pub(crate) new: String,
+    /// Report format [default: markdown]
+    #[arg(long, value_name = "FORMAT", value_enum, ignore_case = true)]
+    pub(crate) format: Option<DiffFormat>,
+    /// Succeed even when the new version has breaking changes
+    #[arg(long)]
+    pub(crate) allow_breaking: bool,
+    #[command(flatten)]
+    pub(crate) input_options: InputOptions,
+}
+
+/// Options on reading the input, shared by a generator run and `diff`.
+#[derive(Debug, Default, clap::Args)]
+pub(crate) struct InputOptions {
+    /// Configuration file (default: h6xserial.toml in the current directory, if present)
+    #[arg(long, value_name = "FILE")]
+    pub(crate) config: Option<String>,
+    /// Syntax of the input (default: from the file extension)
+    #[arg(long, value_name = "FORMAT", value_enum, ignore_case = true)]
+    pub(crate) input_format: Option<InputFormat>,
+    /// Override the max_payload_bytes limit
+    #[arg(long, value_name = "BYTES")]
+    pub(crate) max_payload: Option<usize>,
+    /// Override the max_array_length limit
+    #[arg(long, value_name = "N")]
+    pub(crate) max_array_length: Option<usize>,
+    /// Override the max_nesting_depth limit
+    #[arg(long, value_name = "N")]
+    pub(crate) max_nesting_depth: Option<usize>,
+    /// Treat unknown keys as errors
+    #[arg(long)]
+    pub(crate) strict: bool,
+    /// Stop at the first error
+    #[arg(long)]
+    pub(crate) fail_fast: bool,
+}
+
+/// `--format` of a generator run: the report of `--validate` or the kind of
+/// `--export_docs` document.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Format {
+    Text,
+    Json,
+    #[value(alias = "md")]
+    Markdown,
+    Csv,
+    Mermaid,
+    Dot,
+}
+
+impl Format {
+    /// Name on the command line.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Format::Text => "text",
+            Format::Json => "json",
+            Format::Markdown => "markdown",
+            Format::Csv => "csv",
+            Format::Mermaid => "mermaid",
+            Format::Dot => "dot",
+        }
+    }
+}
+
+/// `--format` of `diff`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub(crate) enum DiffFormat {
+    #[default]
+    #[value(alias = "md")]
+    Markdown,
+    Json,
+}
+
+/// `--role`; the ID of a client comes from `--client-id`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub(crate) enum RoleKind {
+    Server,
+    #[value(aliases = ["client_common", "common"])]
+    ClientCommon,
+    Client,
+}
+
+/// Options of a generator run. Checks involving values the configuration
+/// file can also set are left to [`crate::run`].
+#[derive(Debug, Default, clap::Args)]
+#[command(
+    group(ArgGroup::new("export").multiple(false)),
+    group(ArgGroup::new("role_selection").args(["role", "client_id"]).multiple(true)),
+    group(ArgGroup::new("formatted").args(["validate", "export_docs"]).multiple(true))
+)]
+pub(crate) struct Cli {
+    /// Another message definition file or directory to merge with INPUT (repeatable)
+    // After parsing, the INPUT argument comes first
+    #[arg(long = "input", value_name = "PATH")]
+    pub(crate) inputs: Vec<String>,
+    #[arg(skip)]
+    pub(crate) output: Option<String>,
+    /// The language was given as the first positional argument
+    /// (`h6xserial_idl c input.json out`), which is deprecated
+    #[arg(skip)]
+    pub(crate) positional_language: bool,
+    /// Target language
+    #[arg(
+        short,
+        long = "lang",
+        value_name = "LANG",
+        value_enum,
+        ignore_case = true
+    )]
+    pub(crate) language: Option<TargetLanguage>,
+    #[command(flatten)]
+    pub(crate) input_options: InputOptions,
+    /// Generate markdown documentation instead of code
+    #[arg(long = "export_docs", group = "export")]
+    pub(crate) export_docs: bool,
+    /// Write the parsed definition as canonical JSON instead of code
+    #[arg(long = "export_ir", group = "export")]
+    pub(crate) export_ir: bool,
+    /// Write a Kaitai Struct description of the protocol instead of code
+    #[arg(long = "export_ksy", group = "export")]
+    pub(crate) export_ksy: bool,
+    /// Write a protobuf (proto3) schema of the messages instead of code
+    #[arg(long = "export_proto", group = "export")]
+    pub(crate) export_proto: bool,
+    /// Write a CAN database (.dbc) of the messages that fit a CAN frame
+    #[arg(long = "export_dbc", group = "export")]
+    pub(crate) export_dbc: bool,
+    /// Size DBC frames for CAN FD (64 bytes) instead of 8 bytes
+    #[arg(long)]
+    pub(crate) can_fd: bool,
+    /// Compare the output with the files on disk instead of writing them
+    #[arg(long)]
+    pub(crate) check: bool,
+    /// Rewrite every output file, even those whose content is unchanged
+    #[arg(long)]
+    pub(crate) force: bool,
+    /// Mark the generated files read-only and add a DO NOT EDIT banner to the C files
+    #[arg(long)]
+    pub(crate) readonly_output: bool,
+    /// Check the input and report every problem without generating anything
+    #[arg(long)]
+    pub(crate) validate: bool,
+    /// Rewrite the input JSON files in the canonical style
+    #[arg(long, conflicts_with = "out_dir")]
+    pub(crate) fmt: bool,
+    /// Fail, showing a diff, if an input JSON file is not in the canonical style
+    #[arg(long, conflicts_with = "out_dir")]
+    pub(crate) fmt_check: bool,
+    /// Report format of --validate (text, the default, or json) or of
+    /// --export_docs (markdown, the default, csv, mermaid or dot)
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        value_enum,
+        ignore_case = true,
+        requires = "formatted"
+    )]
+    pub(crate) format: Option<Format>,
+    /// Print a table of the messages instead of generating anything
+    #[arg(long)]
+    pub(crate) list: bool,
+    /// Row order of --list [default: id]
+    #[arg(
+        long,
+        value_name = "KEY",
+        value_enum,
+        ignore_case = true,
+        requires = "list"
+    )]
+    pub(crate) sort: Option<SortKey>,
+    /// Only list messages whose name contains TEXT
+    #[arg(long, value_name = "TEXT", requires = "list")]
+    pub(crate) filter: Option<String>,
+    /// With --list, also report the used and free packet IDs and the largest free range
+    #[arg(long, requires = "list")]
+    pub(crate) free_ids: bool,
+    /// Regenerate whenever the input or the template directory changes
+    #[arg(long, conflicts_with_all = ["check", "list"])]
+    pub(crate) watch: bool,
+    /// Directory for the generated files
+    #[arg(long, value_name = "DIR")]
+    pub(crate) out_dir: Option<String>,
+    /// Write a JSON manifest of the inputs and generated files with their SHA-256
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["check", "list"])]
+    pub(crate) manifest: Option<String>,
+    /// Only generate the C files of one role (client needs --client-id)
+    #[arg(long, value_name = "ROLE", value_enum, ignore_case = true)]
+    pub(crate) role: Option<RoleKind>,
+    /// Only generate the C files of client N
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(i32).range(1..),
+        required_if_eq("role", "client")
+    )]
+    pub(crate) client_id: Option<i32>,
+    /// With --export_docs, write one document per client
+    #[arg(long, conflicts_with = "role_selection")]
+    pub(crate) all_clients: bool,
+    /// With --role, include this existing types header instead of generating one
+    #[arg(long, value_name = "PATH", requires = "role_selection")]
+    pub(crate) types_header: Option<String>,
+    /// Base name of the generated files (default: input file stem)
+    #[arg(long, value_name = "NAME")]
+    pub(crate) base_name: Option<String>,
+    /// inline: static inline functions in headers (default); source: .h/.c pairs
+    #[arg(long, value_name = "STYLE", value_enum, ignore_case = true)]
+    pub(crate) impl_style: Option<ImplStyle>,
+    /// C standard of the generated code; c89 is for legacy compilers [default: c99]
+    #[arg(long, value_name = "STD", value_enum, ignore_case = true)]
+    pub(crate) std: Option<CDialect>,
+    /// Write the C code for MISRA C:2012 checkers, with a list of deviations
+    #[arg(long)]
+    pub(crate) misra: bool,
+    /// Document the generated types and functions with Doxygen comment blocks
+    #[arg(long)]
+    pub(crate) doc_comments: bool,
+    /// Out-of-range min/max values in encoders [default: off]
+    #[arg(long, value_name = "MODE", value_enum, ignore_case = true)]
+    pub(crate) encode_range: Option<EncodeRange>,
+    /// Padding of the generated structs [default: natural]
+    #[arg(long, value_name = "LAYOUT", value_enum, ignore_case = true)]
+    pub(crate) struct_layout: Option<StructLayout>,
+    /// memcpy encode/decode for eligible structs on little-endian hosts
+    #[arg(long)]
+    pub(crate) fast_path: bool,
+    /// Zero unused array elements and union bytes after decoding
+    #[arg(long)]
+    pub(crate) deterministic_decode: bool,
+    /// Emit every byte order helper, not only those the messages use
+    #[arg(long)]
+    pub(crate) all_helpers: bool,
+    /// Also emit an encode/decode self-test
+    #[arg(long)]
+    pub(crate) emit_tests: bool,
+    /// Also emit a decoder fuzz harness
+    #[arg(long)]
+    pub(crate) emit_fuzz: bool,
+    /// Also emit Python ctypes bindings (needs --impl-style source)
+    #[arg(long)]
+    pub(crate) emit_python: bool,
+    /// Also emit a CMake module declaring an INTERFACE library of the output
+    #[arg(long)]
+    pub(crate) emit_cmake: bool,
+    /// Also emit send stubs passing each encoded message to a transmit callback
+    #[arg(long)]
+    pub(crate) emit_send: bool,
+    /// Also write a server skeleton (<base>_server.example.c), never overwriting one
+    #[arg(long)]
+    pub(crate) emit_skeleton: bool,
+    /// Messages larger than this are sent from a caller buffer (default: 256)
+    #[arg(long, value_name = "BYTES")]
+    pub(crate) send_stack_limit: Option<usize>,
+    /// Lay the C output out as a library: arduino (library.properties, src/, examples/)
+    #[arg(long, value_name = "KIND", value_enum, ignore_case = true)]
+    pub(crate) package: Option<Package>,
+    /// Leave the table of contents out of the markdown
+    #[arg(long)]
+    pub(crate) no_toc: bool,
+    /// Override helper templates and add prologue.h/epilogue.h
+    #[arg(long, value_name = "DIR")]
+    pub(crate) template_dir: Option<String>,
+    /// Prefix of the byte order helper functions (default: h6xserial)
+    #[arg(long, value_name = "NAME")]
+    pub(crate) symbol_prefix: Option<String>,
+    /// How generated files name the input [default: path]
+    #[arg(long, value_name = "MODE", value_enum, ignore_case = true)]
+    pub(crate) source_comment: Option<SourceComment>,
+}
+
+/// Parses the command line, program name first. `--help`, `--version` and
+/// malformed arguments come back as clap errors; [`clap::Error::exit`]
+/// prints them with the right exit status.
+pub(crate) fn parse<I, T>(args: I) -> Result<Command, clap::Error>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    Invocation::try_parse_from(args)?.resolve()
+}
+
+impl Invocation {
+    /// Moves the positional arguments into the [`Cli`].
+    fn resolve(self) -> Result<Command, clap::Error> {
+        if let Some(Subcommands::Diff(args)) = self.command {
+            return Ok(Command::Diff(args));
+        }
+        let mut cli = self.cli;
+        let mut positionals: Vec<String> = [self.input, self.output, self.legacy_output]
+            .into_iter()
+            .flatten()
+            .collect();
+
+        // Deprecated `h6xserial_idl c input.json out`, kept as an alias of
+        // --lang. Without --lang a leading language name is taken as the
+        // language, unless it is a lone existing file.
+        if cli.language.is_none()
+            && let Some(first) = positionals.first()
+            && let Some(language) = TargetLanguage::try_from_str(first)
+            && (positionals.len() > 1 || !Path::new(first).exists())
+        {
+            cli.language = Some(language);
+            cli.positional_language = true;
+            positionals.remove(0);
+        }
+
+        let mut positionals = positionals.into_iter();
+        cli.inputs.splice(0..0, positionals.next());
+        cli.output = positionals.next();
+        if let Some(extra) = positionals.next() {
+            return Err(Invocation::command().error(
+                ErrorKind::TooManyValues,
+                format!(
+                    "unexpected argument '{}' (expected at most INPUT and OUTPUT; use --input to merge several inputs)",
+                    extra
+                ),
+            ));
+        }
+        Ok(Command::Run(Box::new(cli)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_args(args: &[&str]) -> Result<Command, clap::Error> {
+        parse(std::iter::once("h6xserial_idl").chain(args.iter().copied()))
+    }
+
+    fn run_args(args: &[&str]) -> Cli {
+        match parse_args(args).unwrap() {
+            Command::Run(cli) => *cli,
+            other => panic!("expected a run, got {:?}", other),
+        }
+    }
+
+    fn error(args: &[&str]) -> clap::Error {
+        parse_args(args).unwrap_err()
+    }
+
+    #[test]
+    fn test_command_is_well_formed() {
+        Invocation::command().debug_assert();
+    }
+
+    #[test]
+    fn test_options_and_positionals_in_any_order() {
+        let cli = run_args(&[
+            "in.json",
+            "--impl-style=source",
+            "--strict",
+            "-l",
+            "c",
+            "out",
+            "--max-payload",
+            "512",
+        ]);
+        assert_eq!(cli.inputs, ["in.json"]);
+        assert_eq!(cli.output.as_deref(), Some("out"));
+        assert_eq!(cli.language, Some(TargetLanguage::C));
+        assert!(!cli.positional_language);
+        assert!(cli.input_options.strict);
+        assert!(!cli.check);
+        assert_eq!(cli.impl_style, Some(ImplStyle::Source));
+        assert_eq!(cli.input_options.max_payload, Some(512));
+
+        let cli = run_args(&["--std", "ANSI", "--role", "Client_Common"]);
+        assert_eq!(cli.std, Some(CDialect::C89));
+        assert_eq!(cli.role, Some(RoleKind::ClientCommon));
+
+        let cli = run_args(&["-", "-"]);
+        assert_eq!(cli.inputs, ["-"]);
+        assert_eq!(cli.output.as_deref(), Some("-"));
+
+        let cli = run_args(&["--", "--strict"]);
+        assert_eq!(cli.inputs, ["--strict"]);
+
+        let cli = run_args(&["--input", "app.json", "base.json", "--input=msgs", "out"]);
+        assert_eq!(cli.inputs, ["base.json", "app.json", "msgs"]);
+        assert_eq!(cli.output.as_deref(), Some("out"));
+    }
+
+    #[test]
+    fn test_positional_language_alias() {
+        let cli = run_args(&["c", "input.json", "out"]);
+        assert!(cli.positional_language);
+        assert_eq!(cli.inputs, ["input.json"]);
+        assert_eq!(cli.output.as_deref(), Some("out"));
+
+        // With --lang, a leading "c" is the input file
+        let cli = run_args(&["--lang", "c", "c", "out"]);
+        assert!(!cli.positional_language);
+        assert_eq!(cli.inputs, ["c"]);
+    }
+
+    #[test]
+    fn test_rejects_unknown_and_malformed_options() {
+        let err = error(&["--export-docs"]);
+        assert_eq!(err.kind(), ErrorKind::UnknownArgument);
+        assert!(err.to_string().contains("'--export_docs'"), "{}", err);
+        assert_eq!(error(&["-x"]).kind(), ErrorKind::UnknownArgument);
+        assert_eq!(error(&["--max-payload"]).kind(), ErrorKind::InvalidValue);
+        assert_eq!(error(&["--strict=yes"]).kind(), ErrorKind::TooManyValues);
+        assert_eq!(
+            error(&["--check", "--check"]).kind(),
+            ErrorKind::ArgumentConflict
+        );
+        assert_eq!(
+            error(&["--client-id", "0"]).kind(),
+            ErrorKind::ValueValidation
+        );
+        let err = error(&["a", "b", "c"]);
+        assert!(
+            err.to_string().contains(
+                "unexpected argument 'c' (expected at most INPUT and OUTPUT; use --input to merge several inputs)"
+            ),
+            "{}",
+            err
+        );
+        let err = error(&["--lang", "rust"]);
+        assert_eq!(err.kind(), ErrorKind::InvalidValue);
+        assert!(err.to_string().contains("[possible values: c]"), "{}", err);
+        assert_eq!(
+            error(&["--impl-style", "header-only"]).kind(),
+            ErrorKind::InvalidValue
+        );
+    }
+
+    #[test]
+    fn test_rejects_conflicting_flags() {
+        for args in [
+            &["--export_docs", "--export_ir"][..],
+            &["--all-clients", "--client-id", "1"],
+            &["--watch", "--check"],
+            &["--manifest", "m.json", "--list"],
+            &["--fmt", "--out-dir", "out"],
+        ] {
+            assert_eq!(
+                error(args).kind(),
+                ErrorKind::ArgumentConflict,
+                "{:?}",
+                args
+            );
+        }
+        for args in [
+            &["--sort", "name"][..],
+            &["--free-ids"],
+            &["--format", "json"],
+            &["--types-header", "types.h"],
+            &["--role", "client"],
+        ] {
+            assert_eq!(
+                error(args).kind(),
+                ErrorKind::MissingRequiredArgument,
+                "{:?}",
+                args
+            );
+        }
+        run_args(&["--list", "--sort", "name"]);
+        run_args(&["--export_docs", "--format", "csv"]);
+        run_args(&[
+            "--role",
+            "client",
+            "--client-id",
+            "2",
+            "--types-header",
+            "t.h",
+        ]);
+    }
+
+    #[test]
+    fn test_diff_subcommand() {
+        match parse_args(&["diff", "old.json", "--format", "json", "new.json"]).unwrap() {
+            Command::Diff(args) => {
+                assert_eq!(
+                    (args.old.as_str(), args.new.as_str()),
+                    ("old.json", "new.json")
+                );
+                assert_eq!(args.format, Some(DiffFormat::Json));
+            }
+            other => panic!("expected a diff, got {:?}", other),
+        }
+        assert_eq!(
+            error(&["diff", "old.json"]).kind(),
+            ErrorKind::MissingRequiredArgument
+        );
+        // Code generation flags are not diff options
+        assert_eq!(
+            error(&["diff", "old.json", "new.json", "--impl-style", "source"]).kind(),
+            ErrorKind::UnknownArgument
+        );
+        assert_eq!(
+            error(&["--allow-breaking"]).kind(),
+            ErrorKind::UnknownArgument
+        );
+    }
+
+    #[test]
+    fn test_help_and_version() {
+        assert_eq!(error(&["in.json", "-h"]).kind(), ErrorKind::DisplayHelp);
+        assert_eq!(error(&["--version"]).kind(), ErrorKind::DisplayVersion);
+        let help = Invocation::command().render_help().to_string();
+        assert!(help.contains("-l, --lang <LANG>"), "{}", help);
+        assert!(help.contains("--export_docs"), "{}", help);
+        assert!(!help.contains("LEGACY"), "{}", help);
+    }
+}
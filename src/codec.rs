@@ -0,0 +1,1278 @@
+//! Reference encoder and decoder of the wire format.
+//!
+//! An executable model of what the generated C encoders write and their
+//! decoders accept, used for the example payloads of the documentation and
+//! as an oracle when checking emitters. Encoded values are checked against
+//! the C types but not against `min`/`max`, as with `encode_range = off`;
+//! decoding rejects out-of-range values like the C decoders do.
+
+use std::fmt;
+use std::ops::Range;
+
+use crate::emit_c::{struct_byte_len, struct_min_byte_len};
+use crate::{
+    Bound, Endian, MessageBody, MessageDefinition, PrimitiveType, StructField, StructFieldType,
+    StructSpec, UnionSpec, UnionVariant, ValueRange,
+};
+
+/// Dynamic value of a message body.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MessageValue {
+    Bool(bool),
+    /// One `char`, as its byte
+    Char(u8),
+    Int(i128),
+    Float(f64),
+    /// Elements of an array, up to its `max_length`
+    Array(Vec<MessageValue>),
+    /// Fields of a struct by name, in any order. A union is `tag` and the
+    /// fields of the variant it selects under the variant's name.
+    Struct(Vec<(String, MessageValue)>),
+}
+
+impl MessageValue {
+    /// A `char` array holding `text`.
+    pub fn text(text: &str) -> Self {
+        MessageValue::Array(text.bytes().map(MessageValue::Char).collect())
+    }
+}
+
+impl fmt::Display for MessageValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MessageValue::Bool(value) => write!(f, "{}", value),
+            MessageValue::Char(byte) => write!(f, "{:?}", char::from(*byte)),
+            MessageValue::Int(value) => write!(f, "{}", value),
+            MessageValue::Float(value) => write!(f, "{:?}", value),
+            MessageValue::Array(elements) => {
+                if !elements.is_empty()
+                    && let Some(bytes) = elements
+                        .iter()
+                        .map(|e| match e {
+                            MessageValue::Char(byte) => Some(*byte),
+                            _ => None,
+                        })
+                        .collect::<Option<Vec<u8>>>()
+                {
+                    return write!(f, "{:?}", String::from_utf8_lossy(&bytes));
+                }
+                write!(f, "[")?;
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", element)?;
+                }
+                write!(f, "]")
+            }
+            MessageValue::Struct(fields) => {
+                write!(f, "{{ ")?;
+                for (i, (name, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", name, value)?;
+                }
+                write!(f, " }}")
+            }
+        }
+    }
+}
+
+/// A value that does not fit the message it is encoded as, or a payload
+/// the message cannot be decoded from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CodecError {
+    /// Dotted path of the offending value, e.g. `limits.current` or
+    /// `ranges[3]`; `value` or `data` for scalar and array messages, and
+    /// `payload` when the payload length is wrong
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+impl std::error::Error for CodecError {}
+
+fn error(path: &str, message: impl Into<String>) -> CodecError {
+    CodecError {
+        path: path.to_string(),
+        message: message.into(),
+    }
+}
+
+/// Where a value ended up in an encoded payload.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EncodedField {
+    /// Dotted path, as in [`CodecError::path`]
+    pub path: String,
+    pub bytes: Range<usize>,
+    pub value: MessageValue,
+}
+
+/// Encodes the payload of `msg` the way its generated C encoder does.
+///
+/// Struct array fields longer than `max_length` are cut to `max_length`
+/// like in C, while an array message that is too long is an error (the C
+/// encoder returns 0).
+pub fn encode_message(
+    msg: &MessageDefinition,
+    value: &MessageValue,
+) -> Result<Vec<u8>, CodecError> {
+    encode_message_with_layout(msg, value).map(|(bytes, _)| bytes)
+}
+
+/// Same as [`encode_message`], also returning where each primitive value
+/// and array went, in payload order. Struct fields are listed by their
+/// leaves only.
+pub fn encode_message_with_layout(
+    msg: &MessageDefinition,
+    value: &MessageValue,
+) -> Result<(Vec<u8>, Vec<EncodedField>), CodecError> {
+    let mut encoder = Encoder::default();
+    match &msg.body {
+        MessageBody::Scalar(spec) => {
+            encoder.primitive("value", spec.primitive, spec.endian, value)?
+        }
+        MessageBody::Array(spec) => {
+            let MessageValue::Array(elements) = value else {
+                return Err(error("data", "expected an array"));
+            };
+            if elements.len() > spec.max_length {
+                return Err(error(
+                    "data",
+                    format!(
+                        "{} elements exceed max_length {}",
+                        elements.len(),
+                        spec.max_length
+                    ),
+                ));
+            }
+            encoder.array("data", spec.primitive, spec.endian, elements)?;
+        }
+        MessageBody::Struct(spec) => encoder.fields(spec, "", value)?,
+        MessageBody::Union(spec) => encoder.union(spec, value)?,
+    }
+    Ok((encoder.out, encoder.layout))
+}
+
+#[derive(Default)]
+struct Encoder {
+    out: Vec<u8>,
+    layout: Vec<EncodedField>,
+}
+
+impl Encoder {
+    /// The tag, then the fields of the variant it selects.
+    fn union(&mut self, spec: &UnionSpec, value: &MessageValue) -> Result<(), CodecError> {
+        let MessageValue::Struct(values) = value else {
+            return Err(error("value", "expected a struct"));
+        };
+        let Some((_, tag)) = values.iter().find(|(name, _)| name == "tag") else {
+            return Err(error("tag", "missing field"));
+        };
+        self.primitive("tag", spec.tag, spec.endian, tag)?;
+        let variant = match tag {
+            MessageValue::Int(tag) => spec.variant(*tag),
+            _ => None,
+        }
+        .ok_or_else(|| error("tag", format!("{} is not the tag of a variant", tag)))?;
+        if let Some((name, _)) = values
+            .iter()
+            .find(|(name, _)| name != "tag" && *name != variant.name)
+        {
+            return Err(error(
+                name,
+                format!("not the variant '{}' of tag {}", variant.name, tag),
+            ));
+        }
+        let Some((_, fields)) = values.iter().find(|(name, _)| *name == variant.name) else {
+            return Err(error(&variant.name, "missing field"));
+        };
+        self.fields(&variant.spec, &format!("{}.", variant.name), fields)
+    }
+
+    fn fields(
+        &mut self,
+        spec: &StructSpec,
+        prefix: &str,
+        value: &MessageValue,
+    ) -> Result<(), CodecError> {
+        let MessageValue::Struct(values) = value else {
+            let path = prefix.strip_suffix('.').unwrap_or("value");
+            return Err(error(path, "expected a struct"));
+        };
+        if let Some((name, _)) = values
+            .iter()
+            .find(|(name, _)| !spec.fields.iter().any(|f| &f.name == name))
+        {
+            return Err(error(&format!("{}{}", prefix, name), "no such field"));
+        }
+        for field in &spec.fields {
+            let path = format!("{}{}", prefix, field.name);
+            // The padding of an `at` gap is always zeros
+            if let (true, StructFieldType::Bytes(length)) = (field.reserved, &field.field_type) {
+                let zeros = vec![MessageValue::Int(0); *length];
+                self.array(&path, PrimitiveType::Uint8, field.endian, &zeros)?;
+                continue;
+            }
+            let Some((_, value)) = values.iter().find(|(name, _)| *name == field.name) else {
+                return Err(error(&path, "missing field"));
+            };
+            self.field(field, &path, value)?;
+        }
+        Ok(())
+    }
+
+    fn field(
+        &mut self,
+        field: &StructField,
+        path: &str,
+        value: &MessageValue,
+    ) -> Result<(), CodecError> {
+        match &field.field_type {
+            StructFieldType::Primitive(primitive) => {
+                self.primitive(path, *primitive, field.endian, value)
+            }
+            StructFieldType::Array(array) => {
+                let MessageValue::Array(elements) = value else {
+                    return Err(error(path, "expected an array"));
+                };
+                if array.fixed && elements.len() != array.max_length {
+                    return Err(error(
+                        path,
+                        format!(
+                            "expected exactly {} elements, got {}",
+                            array.max_length,
+                            elements.len()
+                        ),
+                    ));
+                }
+                let count = elements.len().min(array.max_length);
+                self.array(path, array.primitive, field.endian, &elements[..count])
+            }
+            StructFieldType::Nested(nested) => self.fields(nested, &format!("{}.", path), value),
+            StructFieldType::Bytes(length) => {
+                let MessageValue::Array(elements) = value else {
+                    return Err(error(path, "expected an array"));
+                };
+                if elements.len() != *length {
+                    return Err(error(
+                        path,
+                        format!("expected exactly {} bytes, got {}", length, elements.len()),
+                    ));
+                }
+                self.array(path, PrimitiveType::Uint8, field.endian, elements)
+            }
+        }
+    }
+
+    fn array(
+        &mut self,
+        path: &str,
+        primitive: PrimitiveType,
+        endian: Endian,
+        elements: &[MessageValue],
+    ) -> Result<(), CodecError> {
+        let start = self.out.len();
+        for (i, element) in elements.iter().enumerate() {
+            let path = format!("{}[{}]", path, i);
+            write_primitive(&mut self.out, &path, primitive, endian, element)?;
+        }
+        self.layout.push(EncodedField {
+            path: path.to_string(),
+            bytes: start..self.out.len(),
+            value: MessageValue::Array(elements.to_vec()),
+        });
+        Ok(())
+    }
+
+    fn primitive(
+        &mut self,
+        path: &str,
+        primitive: PrimitiveType,
+        endian: Endian,
+        value: &MessageValue,
+    ) -> Result<(), CodecError> {
+        let start = self.out.len();
+        write_primitive(&mut self.out, path, primitive, endian, value)?;
+        self.layout.push(EncodedField {
+            path: path.to_string(),
+            bytes: start..self.out.len(),
+            value: value.clone(),
+        });
+        Ok(())
+    }
+}
+
+/// Appends one primitive value, mirroring `primitive_encode_stmt`.
+fn write_primitive(
+    out: &mut Vec<u8>,
+    path: &str,
+    primitive: PrimitiveType,
+    endian: Endian,
+    value: &MessageValue,
+) -> Result<(), CodecError> {
+    let width = primitive.byte_len();
+    let bits: u64 = match (primitive, value) {
+        (PrimitiveType::Bool, MessageValue::Bool(flag)) => u64::from(*flag),
+        (PrimitiveType::Char, MessageValue::Char(byte)) => u64::from(*byte),
+        (PrimitiveType::Float16, MessageValue::Float(v)) => u64::from(f32_to_f16_bits(*v as f32)),
+        (PrimitiveType::Float16, MessageValue::Int(v)) => u64::from(f32_to_f16_bits(*v as f32)),
+        (PrimitiveType::Float32, MessageValue::Float(v)) => u64::from((*v as f32).to_bits()),
+        (PrimitiveType::Float32, MessageValue::Int(v)) => u64::from((*v as f32).to_bits()),
+        (PrimitiveType::Float64, MessageValue::Float(v)) => v.to_bits(),
+        (PrimitiveType::Float64, MessageValue::Int(v)) => (*v as f64).to_bits(),
+        (_, MessageValue::Int(v)) => match primitive.integer_range() {
+            Some((min, max)) if (min..=max).contains(v) => *v as u64,
+            Some((min, max)) => {
+                return Err(error(
+                    path,
+                    format!(
+                        "{} does not fit {} ({} to {})",
+                        v,
+                        primitive.wire_type(),
+                        min,
+                        max
+                    ),
+                ));
+            }
+            None => {
+                return Err(error(
+                    path,
+                    format!("expected a value of type {}", primitive.wire_type()),
+                ));
+            }
+        },
+        _ => {
+            return Err(error(
+                path,
+                format!("expected a value of type {}", primitive.wire_type()),
+            ));
+        }
+    };
+    let bytes = bits.to_le_bytes();
+    match endian {
+        Endian::Little => out.extend_from_slice(&bytes[..width]),
+        Endian::Big => out.extend(bytes[..width].iter().rev()),
+    }
+    Ok(())
+}
+
+/// Decodes a payload of `msg` the way its generated C decoder does,
+/// including its length and `min`/`max` checks.
+///
+/// Variable-length arrays are greedy: each takes as many elements as the
+/// bytes beyond the fixed-size fields allow, up to `max_length`, leaving
+/// the rest to the arrays after it. A struct with several arrays therefore
+/// only round-trips when every array but the last one is full.
+pub fn decode_message(msg: &MessageDefinition, data: &[u8]) -> Result<MessageValue, CodecError> {
+    match &msg.body {
+        MessageBody::Scalar(spec) => {
+            let len = spec.primitive.byte_len();
+            if data.len() != len {
+                return Err(length_error(len, data.len()));
+            }
+            let value = read_primitive(data, spec.primitive, spec.endian);
+            check_range("value", &value, spec.primitive, spec.range)?;
+            Ok(value)
+        }
+        MessageBody::Array(spec) => {
+            let elem_size = spec.primitive.byte_len();
+            if !data.len().is_multiple_of(elem_size) {
+                return Err(error(
+                    "payload",
+                    format!(
+                        "{} bytes are not a whole number of {}-byte elements",
+                        data.len(),
+                        elem_size
+                    ),
+                ));
+            }
+            let count = data.len() / elem_size;
+            if count > spec.max_length {
+                return Err(error(
+                    "payload",
+                    format!("{} elements exceed max_length {}", count, spec.max_length),
+                ));
+            }
+            Ok(MessageValue::Array(
+                data.chunks(elem_size)
+                    .map(|chunk| read_primitive(chunk, spec.primitive, spec.endian))
+                    .collect(),
+            ))
+        }
+        MessageBody::Struct(spec) => decode_struct(spec, data, 0, ""),
+        MessageBody::Union(spec) => {
+            let tag_len = spec.tag.byte_len();
+            if data.len() < tag_len {
+                return Err(error(
+                    "payload",
+                    format!("expected at least {} bytes, got {}", tag_len, data.len()),
+                ));
+            }
+            let tag = read_primitive(data, spec.tag, spec.endian);
+            let variant = match tag {
+                MessageValue::Int(tag) => spec.variant(tag),
+                _ => None,
+            }
+            .ok_or_else(|| error("tag", format!("{} is not the tag of a variant", tag)))?;
+            let fields =
+                decode_struct(&variant.spec, data, tag_len, &format!("{}.", variant.name))?;
+            Ok(MessageValue::Struct(vec![
+                ("tag".to_string(), tag),
+                (variant.name.clone(), fields),
+            ]))
+        }
+    }
+}
+
+/// Decodes the struct `spec` from `data[start..]`, which it must fill.
+fn decode_struct(
+    spec: &StructSpec,
+    data: &[u8],
+    start: usize,
+    prefix: &str,
+) -> Result<MessageValue, CodecError> {
+    let (min, max) = (
+        start + struct_min_byte_len(spec),
+        start + struct_byte_len(spec),
+    );
+    if data.len() < min || data.len() > max {
+        return Err(if min == max {
+            length_error(max, data.len())
+        } else {
+            error(
+                "payload",
+                format!("expected {} to {} bytes, got {}", min, max, data.len()),
+            )
+        });
+    }
+    let mut decoder = Decoder {
+        data,
+        offset: start,
+        remaining: data.len() - min,
+    };
+    let value = decoder.fields(spec, prefix)?;
+    // The last array takes whole elements, up to its maximum
+    if decoder.remaining != 0 {
+        return Err(error(
+            "payload",
+            format!(
+                "{} byte(s) left over after the last array",
+                decoder.remaining
+            ),
+        ));
+    }
+    Ok(value)
+}
+
+fn length_error(expected: usize, found: usize) -> CodecError {
+    error(
+        "payload",
+        format!("expected {} bytes, got {}", expected, found),
+    )
+}
+
+struct Decoder<'a> {
+    data: &'a [u8],
+    offset: usize,
+    /// Bytes beyond the fixed-size fields not yet taken by an array
+    remaining: usize,
+}
+
+impl Decoder<'_> {
+    fn fields(&mut self, spec: &StructSpec, prefix: &str) -> Result<MessageValue, CodecError> {
+        let mut values = Vec::new();
+        for field in &spec.fields {
+            if let (true, StructFieldType::Bytes(length)) = (field.reserved, &field.field_type) {
+                self.offset += length;
+                continue;
+            }
+            let path = format!("{}{}", prefix, field.name);
+            let value = match &field.field_type {
+                StructFieldType::Primitive(primitive) => {
+                    let value = self.primitive(*primitive, field.endian);
+                    check_range(&path, &value, *primitive, field.range)?;
+                    value
+                }
+                StructFieldType::Array(array) if array.fixed => MessageValue::Array(
+                    (0..array.max_length)
+                        .map(|_| self.primitive(array.primitive, field.endian))
+                        .collect(),
+                ),
+                StructFieldType::Array(array) => {
+                    let elem_size = array.primitive.byte_len();
+                    let count = (self.remaining / elem_size).min(array.max_length);
+                    self.remaining -= count * elem_size;
+                    MessageValue::Array(
+                        (0..count)
+                            .map(|_| self.primitive(array.primitive, field.endian))
+                            .collect(),
+                    )
+                }
+                StructFieldType::Nested(nested) => self.fields(nested, &format!("{}.", path))?,
+                StructFieldType::Bytes(length) => MessageValue::Array(
+                    (0..*length)
+                        .map(|_| self.primitive(PrimitiveType::Uint8, field.endian))
+                        .collect(),
+                ),
+            };
+            values.push((field.name.clone(), value));
+        }
+        Ok(MessageValue::Struct(values))
+    }
+
+    fn primitive(&mut self, primitive: PrimitiveType, endian: Endian) -> MessageValue {
+        let end = self.offset + primitive.byte_len();
+        let value = read_primitive(&self.data[self.offset..end], primitive, endian);
+        self.offset = end;
+        value
+    }
+}
+
+/// Reads one primitive value from the start of `data`, mirroring
+/// `primitive_decode_stmt`.
+fn read_primitive(data: &[u8], primitive: PrimitiveType, endian: Endian) -> MessageValue {
+    let width = primitive.byte_len();
+    let mut bytes = [0u8; 8];
+    bytes[..width].copy_from_slice(&data[..width]);
+    if endian == Endian::Big {
+        bytes[..width].reverse();
+    }
+    let bits = u64::from_le_bytes(bytes);
+    match primitive {
+        PrimitiveType::Bool => MessageValue::Bool(bits != 0),
+        PrimitiveType::Char => MessageValue::Char(bits as u8),
+        PrimitiveType::Int8 => MessageValue::Int(i128::from(bits as u8 as i8)),
+        PrimitiveType::Int16 => MessageValue::Int(i128::from(bits as u16 as i16)),
+        // Shift the sign bit of the 24-bit value into place and back
+        PrimitiveType::Int24 => MessageValue::Int(i128::from(((bits as u32) << 8) as i32 >> 8)),
+        PrimitiveType::Int32 => MessageValue::Int(i128::from(bits as u32 as i32)),
+        PrimitiveType::Int64 => MessageValue::Int(i128::from(bits as i64)),
+        PrimitiveType::Uint8
+        | PrimitiveType::Uint16
+        | PrimitiveType::Uint24
+        | PrimitiveType::Uint32
+        | PrimitiveType::Uint64 => MessageValue::Int(i128::from(bits)),
+        PrimitiveType::Float16 => MessageValue::Float(f64::from(f16_bits_to_f32(bits as u16))),
+        PrimitiveType::Float32 => MessageValue::Float(f64::from(f32::from_bits(bits as u32))),
+        PrimitiveType::Float64 => MessageValue::Float(f64::from_bits(bits)),
+    }
+}
+
+/// Rounds `value` to the nearest half precision value, ties to even, as
+/// the `write_f16` helpers of the C code do.
+pub(crate) fn f32_to_f16_bits(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = (bits >> 16) & 0x8000;
+    let exponent = (bits >> 23) & 0xFF;
+    let mantissa = bits & 0x7F_FFFF;
+    let round = |half: u32, remainder: u32, halfway: u32| {
+        if remainder > halfway || (remainder == halfway && half & 1 != 0) {
+            half + 1
+        } else {
+            half
+        }
+    };
+    let half = match exponent {
+        // Infinity, or NaN with the quiet bit set so it stays a NaN
+        0xFF if mantissa == 0 => 0x7C00,
+        0xFF => 0x7E00 | (mantissa >> 13),
+        // Too large: infinity
+        143.. => 0x7C00,
+        // Normal
+        113.. => round(
+            ((exponent - 112) << 10) | (mantissa >> 13),
+            mantissa & 0x1FFF,
+            0x1000,
+        ),
+        // Subnormal, including the values that round up to the smallest one
+        102.. => {
+            let shift = 126 - exponent;
+            let mantissa = mantissa | 0x80_0000;
+            round(
+                mantissa >> shift,
+                mantissa & ((1 << shift) - 1),
+                1 << (shift - 1),
+            )
+        }
+        _ => 0,
+    };
+    (sign | half) as u16
+}
+
+/// The `float` value of half precision `bits`, which is always exact.
+pub(crate) fn f16_bits_to_f32(bits: u16) -> f32 {
+    let bits = u32::from(bits);
+    let sign = (bits & 0x8000) << 16;
+    let exponent = (bits >> 10) & 0x1F;
+    let mantissa = bits & 0x3FF;
+    let single = match (exponent, mantissa) {
+        (0x1F, _) => sign | 0x7F80_0000 | (mantissa << 13),
+        (0, 0) => sign,
+        (0, _) => {
+            // Subnormal: normalize into the wider exponent range
+            let shift = mantissa.leading_zeros() - 21;
+            sign | ((113 - shift) << 23) | (((mantissa << shift) & 0x3FF) << 13)
+        }
+        _ => sign | ((exponent + 112) << 23) | (mantissa << 13),
+    };
+    f32::from_bits(single)
+}
+
+/// Rejects a decoded value outside its `min`/`max` range. Like the C
+/// checks, `float` values are compared in single precision and NaN is
+/// never in range.
+fn check_range(
+    path: &str,
+    value: &MessageValue,
+    primitive: PrimitiveType,
+    range: Option<ValueRange>,
+) -> Result<(), CodecError> {
+    let Some(ValueRange { min, max }) = range else {
+        return Ok(());
+    };
+    let in_range = match value {
+        MessageValue::Int(value) => {
+            let bound = |bound: Bound| match bound {
+                Bound::Int(bound) => bound,
+                Bound::Float(bound) => bound as i128,
+            };
+            min.is_none_or(|min| *value >= bound(min)) && max.is_none_or(|max| *value <= bound(max))
+        }
+        MessageValue::Float(value) if primitive.c_type() == "float" => {
+            let value = *value as f32;
+            min.is_none_or(|min| value >= min.as_f64() as f32)
+                && max.is_none_or(|max| value <= max.as_f64() as f32)
+                && !value.is_nan()
+        }
+        MessageValue::Float(value) => {
+            min.is_none_or(|min| *value >= min.as_f64())
+                && max.is_none_or(|max| *value <= max.as_f64())
+                && !value.is_nan()
+        }
+        _ => true,
+    };
+    if in_range {
+        Ok(())
+    } else {
+        Err(error(path, format!("{} is out of range", value)))
+    }
+}
+
+/// Small deterministic value of a message body: integers count up from 1,
+/// floats from 1.5, `bool` is `true`, `char` arrays hold `"AB"` and other
+/// arrays two elements (both cut to `max_length`); `fixed_length` arrays
+/// and `bytes` are full. Values are moved into their `min`/`max` range. A
+/// union holds its first variant.
+pub fn example_value(body: &MessageBody) -> MessageValue {
+    let mut next = 1;
+    match body {
+        MessageBody::Scalar(spec) => example_primitive(spec.primitive, spec.range, &mut next),
+        MessageBody::Array(spec) => example_array(spec.primitive, spec.max_length, &mut next),
+        MessageBody::Struct(spec) => example_struct(spec, &mut next),
+        MessageBody::Union(spec) => example_variant(&spec.variants[0], &mut next),
+    }
+}
+
+/// A union holding `variant`.
+fn example_variant(variant: &UnionVariant, next: &mut i128) -> MessageValue {
+    MessageValue::Struct(vec![
+        ("tag".to_string(), MessageValue::Int(variant.tag)),
+        (variant.name.clone(), example_struct(&variant.spec, next)),
+    ])
+}
+
+fn example_struct(spec: &StructSpec, next: &mut i128) -> MessageValue {
+    MessageValue::Struct(
+        spec.fields
+            .iter()
+            .filter(|field| !field.reserved)
+            .map(|field| {
+                let value = match &field.field_type {
+                    StructFieldType::Primitive(primitive) => {
+                        example_primitive(*primitive, field.range, next)
+                    }
+                    StructFieldType::Array(array) if array.fixed => {
+                        example_fixed(array.primitive, array.max_length, next)
+                    }
+                    StructFieldType::Array(array) => {
+                        example_array(array.primitive, array.max_length, next)
+                    }
+                    StructFieldType::Nested(nested) => example_struct(nested, next),
+                    StructFieldType::Bytes(length) => {
+                        example_fixed(PrimitiveType::Uint8, *length, next)
+                    }
+                };
+                (field.name.clone(), value)
+            })
+            .collect(),
+    )
+}
+
+fn example_array(primitive: PrimitiveType, max_length: usize, next: &mut i128) -> MessageValue {
+    if primitive == PrimitiveType::Char {
+        return MessageValue::Array(
+            b"AB"
+                .iter()
+                .take(max_length)
+                .map(|&byte| MessageValue::Char(byte))
+                .collect(),
+        );
+    }
+    MessageValue::Array(
+        (0..max_length.min(2))
+            .map(|_| example_primitive(primitive, None, next))
+            .collect(),
+    )
+}
+
+/// Every element of a `fixed_length` array or `bytes` field.
+fn example_fixed(primitive: PrimitiveType, length: usize, next: &mut i128) -> MessageValue {
+    MessageValue::Array(
+        (0..length)
+            .map(|_| example_primitive(primitive, None, next))
+            .collect(),
+    )
+}
+
+fn example_primitive(
+    primitive: PrimitiveType,
+    range: Option<ValueRange>,
+    next: &mut i128,
+) -> MessageValue {
+    let n = *next;
+    *next += 1;
+    let range = range.unwrap_or_default();
+    match primitive {
+        PrimitiveType::Bool => MessageValue::Bool(true),
+        PrimitiveType::Char => MessageValue::Char(b'A'),
+        PrimitiveType::Float16 | PrimitiveType::Float32 | PrimitiveType::Float64 => {
+            let mut value = n as f64 + 0.5;
+            if let Some(max) = range.max {
+                value = value.min(max.as_f64());
+            }
+            if let Some(min) = range.min {
+                value = value.max(min.as_f64());
+            }
+            MessageValue::Float(value)
+        }
+        _ => {
+            let (_, type_max) = primitive.integer_range().unwrap_or((0, i128::MAX));
+            let mut value = n.min(type_max);
+            if let Some(Bound::Int(max)) = range.max {
+                value = value.min(max);
+            }
+            if let Some(Bound::Int(min)) = range.min {
+                value = value.max(min);
+            }
+            MessageValue::Int(value)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(json: &str) -> MessageDefinition {
+        let (_, mut messages) = crate::parse_str(json).unwrap();
+        messages.remove(0)
+    }
+
+    #[test]
+    fn test_encode_known_bytes() {
+        let msg = message(
+            r#"{ "motor": { "packet_id": 1, "msg_type": "struct", "fields": {
+                     "enabled": { "type": "bool" },
+                     "speed": { "type": "int16", "endianess": "big" },
+                     "current": { "type": "f32" },
+                     "name": { "type": "char", "array": true, "max_length": 4 },
+                     "crc": { "type": "u32" } } } }"#,
+        );
+        let value = MessageValue::Struct(vec![
+            ("crc".to_string(), MessageValue::Int(0x0102_0304)),
+            ("enabled".to_string(), MessageValue::Bool(true)),
+            ("speed".to_string(), MessageValue::Int(-2)),
+            ("current".to_string(), MessageValue::Float(1.5)),
+            ("name".to_string(), MessageValue::text("ABCDE")),
+        ]);
+        let (bytes, layout) = encode_message_with_layout(&msg, &value).unwrap();
+        assert_eq!(
+            bytes,
+            [
+                0x01, // enabled
+                0xFF, 0xFE, // speed, big-endian
+                0x00, 0x00, 0xC0, 0x3F, // current, little-endian 1.5f
+                b'A', b'B', b'C', b'D', // name, cut to max_length
+                0x04, 0x03, 0x02, 0x01, // crc, little-endian
+            ]
+        );
+        let spans: Vec<_> = layout
+            .iter()
+            .map(|f| (f.path.as_str(), f.bytes.clone()))
+            .collect();
+        assert_eq!(
+            spans,
+            [
+                ("enabled", 0..1),
+                ("speed", 1..3),
+                ("current", 3..7),
+                ("name", 7..11),
+                ("crc", 11..15)
+            ]
+        );
+        assert_eq!(layout[3].value.to_string(), "\"ABCD\"");
+    }
+
+    #[test]
+    fn test_encode_scalar_and_array_messages() {
+        let msg = message(r#"{ "t": { "packet_id": 1, "msg_type": "f64", "endianess": "big" } }"#);
+        assert_eq!(
+            encode_message(&msg, &MessageValue::Float(1.0)).unwrap(),
+            [0x3F, 0xF0, 0, 0, 0, 0, 0, 0]
+        );
+
+        let msg = message(
+            r#"{ "ranges": { "packet_id": 1, "msg_type": "u16", "array": true, "max_length": 2 } }"#,
+        );
+        let value = MessageValue::Array(vec![MessageValue::Int(1), MessageValue::Int(0x0203)]);
+        assert_eq!(encode_message(&msg, &value).unwrap(), [1, 0, 3, 2]);
+        let too_long = MessageValue::Array(vec![MessageValue::Int(1); 3]);
+        assert_eq!(
+            encode_message(&msg, &too_long).unwrap_err().to_string(),
+            "data: 3 elements exceed max_length 2"
+        );
+    }
+
+    #[test]
+    fn test_int24_round_trip_sign_extends() {
+        let msg = message(
+            r#"{ "s": { "packet_id": 1, "msg_type": "i24", "array": true, "max_length": 3 } }"#,
+        );
+        let value = MessageValue::Array(vec![
+            MessageValue::Int(-1),
+            MessageValue::Int(-(1 << 23)),
+            MessageValue::Int(0x12_3456),
+        ]);
+        let wire = [0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x80, 0x56, 0x34, 0x12];
+        assert_eq!(encode_message(&msg, &value).unwrap(), wire);
+        assert_eq!(decode_message(&msg, &wire).unwrap(), value);
+        assert!(decode_message(&msg, &wire[..8]).is_err());
+
+        let msg = message(r#"{ "s": { "packet_id": 1, "msg_type": "u24", "endianess": "big" } }"#);
+        assert_eq!(
+            decode_message(&msg, &[0xFF, 0xFF, 0xFE]).unwrap(),
+            MessageValue::Int(0xFF_FFFE)
+        );
+        assert!(encode_message(&msg, &MessageValue::Int(1 << 24)).is_err());
+    }
+
+    #[test]
+    fn test_bytes_need_their_exact_length() {
+        let msg = message(r#"{ "id": { "packet_id": 1, "msg_type": "bytes", "length": 3 } }"#);
+        let bytes = |values: &[i128]| {
+            MessageValue::Struct(vec![(
+                "value".to_string(),
+                MessageValue::Array(values.iter().map(|&v| MessageValue::Int(v)).collect()),
+            )])
+        };
+        let value = bytes(&[0xDE, 0xAD, 0x01]);
+        assert_eq!(encode_message(&msg, &value).unwrap(), [0xDE, 0xAD, 0x01]);
+        assert_eq!(decode_message(&msg, &[0xDE, 0xAD, 0x01]).unwrap(), value);
+        assert_eq!(
+            encode_message(&msg, &bytes(&[1, 2]))
+                .unwrap_err()
+                .to_string(),
+            "value: expected exactly 3 bytes, got 2"
+        );
+        assert!(decode_message(&msg, &[0xDE, 0xAD]).is_err());
+        assert!(decode_message(&msg, &[0xDE, 0xAD, 0x01, 0x02]).is_err());
+    }
+
+    #[test]
+    fn test_reserved_gaps_are_zeros() {
+        let msg = message(
+            r#"{ "s": { "packet_id": 1, "msg_type": "struct", "fields": {
+                     "a": { "type": "u8" }, "b": { "type": "u8", "at": 3 } } } }"#,
+        );
+        let value = MessageValue::Struct(vec![
+            ("a".to_string(), MessageValue::Int(1)),
+            ("b".to_string(), MessageValue::Int(2)),
+        ]);
+        assert_eq!(encode_message(&msg, &value).unwrap(), [1, 0, 0, 2]);
+        // Whatever the sender put in the gap is ignored
+        assert_eq!(decode_message(&msg, &[1, 7, 7, 2]).unwrap(), value);
+    }
+
+    #[test]
+    fn test_float16_conversion() {
+        let cases: [(u32, u16); 13] = [
+            (0x3F80_0000, 0x3C00), // 1.0
+            (0xC000_0000, 0xC000), // -2.0
+            (0x477F_E000, 0x7BFF), // 65504, the largest half
+            (0x477F_EFFF, 0x7BFF), // just below the tie with infinity
+            (0x477F_F000, 0x7C00), // 65520 rounds to even: infinity
+            (0x3880_0000, 0x0400), // smallest normal
+            (0x387F_C000, 0x03FF), // largest subnormal
+            (0x3380_0000, 0x0001), // smallest subnormal
+            (0x3300_0000, 0x0000), // half of it rounds to even: zero
+            (0x33C0_0000, 0x0002), // 1.5 of it rounds to even: 2
+            (0x3F80_1000, 0x3C00), // tie rounds down to even
+            (0x3F80_3000, 0x3C02), // tie rounds up to even
+            (0xFF80_0000, 0xFC00), // -infinity
+        ];
+        for (single, half) in cases {
+            assert_eq!(
+                f32_to_f16_bits(f32::from_bits(single)),
+                half,
+                "{:08X}",
+                single
+            );
+        }
+        assert_eq!(f16_bits_to_f32(0x0001).to_bits(), 0x3380_0000);
+        assert_eq!(f16_bits_to_f32(0x03FF).to_bits(), 0x387F_C000);
+        assert_eq!(f16_bits_to_f32(0x8000).to_bits(), 0x8000_0000);
+        assert_eq!(f16_bits_to_f32(0x7C00), f32::INFINITY);
+        assert!(f16_bits_to_f32(f32_to_f16_bits(f32::NAN)).is_nan());
+        for half in 0..=u16::MAX {
+            let value = f16_bits_to_f32(half);
+            if !value.is_nan() {
+                assert_eq!(f32_to_f16_bits(value), half);
+            }
+        }
+
+        let msg = message(r#"{ "s": { "packet_id": 1, "msg_type": "f16", "max": 2.5 } }"#);
+        assert_eq!(
+            encode_message(&msg, &MessageValue::Float(-1.5)).unwrap(),
+            [0x00, 0xBE]
+        );
+        assert_eq!(
+            decode_message(&msg, &[0x00, 0x41]).unwrap(),
+            MessageValue::Float(2.5)
+        );
+        assert_eq!(
+            decode_message(&msg, &[0x00, 0x42]).unwrap_err().to_string(),
+            "value: 3.0 is out of range"
+        );
+    }
+
+    #[test]
+    fn test_encode_rejects_values_that_do_not_fit() {
+        let msg = message(
+            r#"{ "s": { "packet_id": 1, "msg_type": "struct", "fields": {
+                     "pose": { "type": "struct", "fields": { "id": { "type": "i8" } } } } } }"#,
+        );
+        let value = |id| {
+            MessageValue::Struct(vec![(
+                "pose".to_string(),
+                MessageValue::Struct(vec![("id".to_string(), id)]),
+            )])
+        };
+        assert_eq!(
+            encode_message(&msg, &value(MessageValue::Int(128)))
+                .unwrap_err()
+                .to_string(),
+            "pose.id: 128 does not fit int8_t (-128 to 127)"
+        );
+        assert_eq!(
+            encode_message(&msg, &value(MessageValue::Bool(true)))
+                .unwrap_err()
+                .to_string(),
+            "pose.id: expected a value of type int8_t"
+        );
+        assert_eq!(
+            encode_message(&msg, &MessageValue::Struct(Vec::new()))
+                .unwrap_err()
+                .to_string(),
+            "pose: missing field"
+        );
+    }
+
+    #[test]
+    fn test_example_values_respect_ranges() {
+        let msg = message(
+            r#"{ "s": { "packet_id": 1, "msg_type": "struct", "fields": {
+                     "mode": { "type": "u8", "min": 5 },
+                     "gain": { "type": "f32", "max": 0.25 },
+                     "label": { "type": "char", "array": true, "max_length": 1 },
+                     "samples": { "type": "i16", "array": true, "max_length": 8 } } } }"#,
+        );
+        let value = example_value(&msg.body);
+        assert_eq!(
+            value.to_string(),
+            "{ mode: 5, gain: 0.25, label: \"A\", samples: [3, 4] }"
+        );
+        assert_eq!(encode_message(&msg, &value).unwrap().len(), 1 + 4 + 1 + 4);
+    }
+
+    #[test]
+    fn test_decode_known_bytes() {
+        let msg = message(
+            r#"{ "scan": { "packet_id": 1, "msg_type": "struct", "fields": {
+                     "mode": { "type": "i8", "min": -3, "max": 3 },
+                     "label": { "type": "char", "array": true, "max_length": 4 },
+                     "speed": { "type": "int16", "endianess": "big" },
+                     "ranges": { "type": "u16", "array": true, "max_length": 2 } } } }"#,
+        );
+        // `label` takes what it can, `ranges` the rest
+        let value =
+            decode_message(&msg, &[0xFD, b'A', b'B', b'C', b'D', 0xFF, 0xFE, 3, 2]).unwrap();
+        assert_eq!(
+            value.to_string(),
+            "{ mode: -3, label: \"ABCD\", speed: -2, ranges: [515] }"
+        );
+        let value = decode_message(&msg, &[1, b'A', b'B', 2, 9]).unwrap();
+        assert_eq!(
+            value.to_string(),
+            "{ mode: 1, label: \"AB\", speed: 521, ranges: [] }"
+        );
+
+        assert_eq!(
+            decode_message(&msg, &[1, 0]).unwrap_err().to_string(),
+            "payload: expected 3 to 11 bytes, got 2"
+        );
+        assert_eq!(
+            decode_message(&msg, &[4, 0, 0]).unwrap_err().to_string(),
+            "mode: 4 is out of range"
+        );
+
+        let msg = message(r#"{ "t": { "packet_id": 1, "msg_type": "f32", "max": 1.5 } }"#);
+        assert_eq!(
+            decode_message(&msg, &[0, 0, 0xC0, 0x3F]).unwrap(),
+            MessageValue::Float(1.5)
+        );
+        let nan = f32::NAN.to_bits().to_le_bytes();
+        assert_eq!(
+            decode_message(&msg, &nan).unwrap_err().to_string(),
+            "value: NaN is out of range"
+        );
+        assert_eq!(
+            decode_message(&msg, &[0; 5]).unwrap_err().to_string(),
+            "payload: expected 4 bytes, got 5"
+        );
+
+        let msg = message(
+            r#"{ "a": { "packet_id": 1, "msg_type": "u16", "array": true, "max_length": 2 } }"#,
+        );
+        assert_eq!(
+            decode_message(&msg, &[1, 2, 3]).unwrap_err().to_string(),
+            "payload: 3 bytes are not a whole number of 2-byte elements"
+        );
+        assert_eq!(
+            decode_message(&msg, &[0; 6]).unwrap_err().to_string(),
+            "payload: 3 elements exceed max_length 2"
+        );
+    }
+
+    /// xorshift64*, enough to vary test values reproducibly.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 >> 12;
+            self.0 ^= self.0 << 25;
+            self.0 ^= self.0 >> 27;
+            self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+        }
+
+        fn below(&mut self, n: u64) -> u64 {
+            self.next() % n
+        }
+    }
+
+    fn random_primitive(
+        rng: &mut Rng,
+        primitive: PrimitiveType,
+        range: Option<ValueRange>,
+    ) -> MessageValue {
+        let range = range.unwrap_or_default();
+        match primitive {
+            PrimitiveType::Bool => MessageValue::Bool(rng.below(2) == 1),
+            PrimitiveType::Char => MessageValue::Char(rng.next() as u8),
+            PrimitiveType::Float16 => loop {
+                let value = f16_bits_to_f32(rng.next() as u16);
+                if !value.is_nan() {
+                    return MessageValue::Float(f64::from(value));
+                }
+            },
+            PrimitiveType::Float32 | PrimitiveType::Float64 => {
+                if range.min.is_some() || range.max.is_some() {
+                    let low = range.min.map_or(-1e3, Bound::as_f64);
+                    let high = range.max.map_or(1e3, Bound::as_f64);
+                    let t = rng.below(1001) as f64 / 1000.0;
+                    let value = low + (high - low) * t;
+                    return MessageValue::Float(if primitive == PrimitiveType::Float32 {
+                        f64::from((value as f32).clamp(low as f32, high as f32))
+                    } else {
+                        value
+                    });
+                }
+                loop {
+                    let value = if primitive == PrimitiveType::Float32 {
+                        f64::from(f32::from_bits(rng.next() as u32))
+                    } else {
+                        f64::from_bits(rng.next())
+                    };
+                    if !value.is_nan() {
+                        return MessageValue::Float(value);
+                    }
+                }
+            }
+            _ => {
+                let (mut low, mut high) = primitive.integer_range().unwrap();
+                if let Some(Bound::Int(min)) = range.min {
+                    low = low.max(min);
+                }
+                if let Some(Bound::Int(max)) = range.max {
+                    high = high.min(max);
+                }
+                let span = (high - low) as u128 + 1;
+                let offset = (u128::from(rng.next()) << 64 | u128::from(rng.next())) % span;
+                MessageValue::Int(low + offset as i128)
+            }
+        }
+    }
+
+    /// A random value of `spec`; every array but the last one in wire
+    /// order is full, so the value survives the greedy decoding.
+    fn random_struct(rng: &mut Rng, spec: &StructSpec, arrays_left: &mut usize) -> MessageValue {
+        MessageValue::Struct(
+            spec.fields
+                .iter()
+                .filter(|field| !field.reserved)
+                .map(|field| {
+                    let value = match &field.field_type {
+                        StructFieldType::Primitive(primitive) => {
+                            random_primitive(rng, *primitive, field.range)
+                        }
+                        StructFieldType::Array(array) if array.fixed => MessageValue::Array(
+                            (0..array.max_length)
+                                .map(|_| random_primitive(rng, array.primitive, None))
+                                .collect(),
+                        ),
+                        StructFieldType::Array(array) => {
+                            *arrays_left -= 1;
+                            let count = if *arrays_left == 0 {
+                                rng.below(array.max_length as u64 + 1) as usize
+                            } else {
+                                array.max_length
+                            };
+                            MessageValue::Array(
+                                (0..count)
+                                    .map(|_| random_primitive(rng, array.primitive, None))
+                                    .collect(),
+                            )
+                        }
+                        StructFieldType::Nested(nested) => random_struct(rng, nested, arrays_left),
+                        StructFieldType::Bytes(length) => MessageValue::Array(
+                            (0..*length)
+                                .map(|_| random_primitive(rng, PrimitiveType::Uint8, None))
+                                .collect(),
+                        ),
+                    };
+                    (field.name.clone(), value)
+                })
+                .collect(),
+        )
+    }
+
+    fn count_arrays(spec: &StructSpec) -> usize {
+        spec.fields
+            .iter()
+            .map(|field| match &field.field_type {
+                StructFieldType::Primitive(_) | StructFieldType::Bytes(_) => 0,
+                StructFieldType::Array(array) => usize::from(!array.fixed),
+                StructFieldType::Nested(nested) => count_arrays(nested),
+            })
+            .sum()
+    }
+
+    fn random_value(rng: &mut Rng, body: &MessageBody) -> MessageValue {
+        match body {
+            MessageBody::Scalar(spec) => random_primitive(rng, spec.primitive, spec.range),
+            MessageBody::Array(spec) => {
+                let count = rng.below(spec.max_length as u64 + 1) as usize;
+                MessageValue::Array(
+                    (0..count)
+                        .map(|_| random_primitive(rng, spec.primitive, None))
+                        .collect(),
+                )
+            }
+            MessageBody::Struct(spec) => random_struct(rng, spec, &mut count_arrays(spec)),
+            MessageBody::Union(spec) => {
+                let variant = &spec.variants[rng.below(spec.variants.len() as u64) as usize];
+                let fields = random_struct(rng, &variant.spec, &mut count_arrays(&variant.spec));
+                MessageValue::Struct(vec![
+                    ("tag".to_string(), MessageValue::Int(variant.tag)),
+                    (variant.name.clone(), fields),
+                ])
+            }
+        }
+    }
+
+    const ROUND_TRIP_MESSAGES: &str = r#"{
+        "flag": { "packet_id": 1, "msg_type": "bool" },
+        "level": { "packet_id": 2, "msg_type": "i8", "min": -10, "max": 10 },
+        "count": { "packet_id": 3, "msg_type": "u64", "endianess": "big" },
+        "offset": { "packet_id": 4, "msg_type": "i64" },
+        "gain": { "packet_id": 5, "msg_type": "f32", "min": -2.5, "max": 0.75 },
+        "scale": { "packet_id": 6, "msg_type": "f64", "endianess": "big" },
+        "accel": { "packet_id": 10, "msg_type": "f16", "array": true, "max_length": 3, "endianess": "big" },
+        "name": { "packet_id": 7, "msg_type": "char", "array": true, "max_length": 12 },
+        "samples": { "packet_id": 8, "msg_type": "i32", "array": true, "max_length": 5, "endianess": "big" },
+        "scan": { "packet_id": 9, "msg_type": "struct", "fields": {
+            "label": { "type": "char", "array": true, "max_length": 3 },
+            "pose": { "type": "struct", "fields": {
+                "x": { "type": "f32", "endianess": "big" },
+                "heading": { "type": "i16", "min": -180, "max": 180 },
+                "valid": { "type": "bool" } } },
+            "ids": { "type": "u32", "array": true, "max_length": 2 },
+            "crc": { "type": "u16", "endianess": "big" },
+            "ranges": { "type": "u16", "array": true, "max_length": 4, "endianess": "big" } } },
+        "sample": { "packet_id": 11, "msg_type": "union", "tag": "i16", "endianess": "big", "variants": {
+            "-1": { "name": "fix", "fields": {
+                "lat": { "type": "f64" },
+                "sats": { "type": "u8", "max": 32 } } },
+            "7": { "name": "odometry", "fields": {
+                "ticks": { "type": "i32", "array": true, "max_length": 3, "endianess": "big" },
+                "label": { "type": "char", "array": true, "max_length": 2 } } } } }
+    }"#;
+
+    #[test]
+    fn test_random_values_round_trip() {
+        let (_, messages) = crate::parse_str(ROUND_TRIP_MESSAGES).unwrap();
+        let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+        for msg in &messages {
+            for _ in 0..200 {
+                let value = random_value(&mut rng, &msg.body);
+                let bytes = encode_message(msg, &value).unwrap();
+                let decoded = decode_message(msg, &bytes)
+                    .unwrap_or_else(|e| panic!("{}: {} from {:02X?}", msg.name, e, bytes));
+                assert_eq!(decoded, value, "{} from {:02X?}", msg.name, bytes);
+                assert_eq!(encode_message(msg, &decoded).unwrap(), bytes);
+            }
+        }
+    }
+
+    #[test]
+    fn test_random_payloads_decode_or_fail_cleanly() {
+        let (_, messages) = crate::parse_str(ROUND_TRIP_MESSAGES).unwrap();
+        let mut rng = Rng(0x2545_F491_4F6C_DD1D);
+        for msg in &messages {
+            for _ in 0..500 {
+                let len = rng.below(40) as usize;
+                let data: Vec<u8> = (0..len).map(|_| rng.next() as u8).collect();
+                // Whatever decodes re-encodes to the same length
+                if let Ok(value) = decode_message(msg, &data) {
+                    assert_eq!(encode_message(msg, &value).unwrap().len(), len);
+                }
+            }
+        }
+    }
+}
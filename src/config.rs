@@ -0,0 +1,381 @@
+//! Project configuration file (`h6xserial.toml`).
+//!
+//! Holds the settings that would otherwise be repeated on every command
+//! line. The file is read from `--config PATH`, or from `h6xserial.toml` in
+//! the current directory when present. Values are merged as
+//! defaults <- configuration file <- command line:
+//!
+//! ```toml
+//! input = "msgs/protocol.json"
+//! languages = ["c", "docs"]
+//! symbol_prefix = "acme"
+//! max_payload_bytes = 512
+//! impl_style = "source"
+//!
+//! [output]
+//! c = "firmware/generated"
+//! docs = "docs"
+//! ```
+//!
+//! Relative paths are resolved against the directory of the file.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use crate::cli::{Cli, Format, InputOptions};
+use crate::emit_c::{CDialect, EncodeRange, ImplStyle, Package, StructLayout};
+use crate::{InputFormat, SourceComment, Target, TargetLanguage};
+
+/// Name of the configuration file looked up in the current directory.
+pub(crate) const CONFIG_FILE: &str = "h6xserial.toml";
+
+/// Settings of a generation run. `None` leaves the built-in default.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct GenerationConfig {
+    /// Input documents (a path or a list of paths), or `-` for stdin
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub(crate) input: Vec<PathBuf>,
+    /// `json`, `jsonc` or `toml`, as `--input-format`
+    #[serde(default, deserialize_with = "value_enum")]
+    pub(crate) input_format: Option<InputFormat>,
+    /// What to generate: `c`, `docs`, `csv`, `ir`, `ksy`, `proto`, `dbc`,
+    /// `mermaid` and/or `dot`
+    pub(crate) languages: Option<Vec<String>>,
+    /// Output directory of each language
+    #[serde(default)]
+    pub(crate) output: OutputConfig,
+    pub(crate) base_name: Option<String>,
+    pub(crate) symbol_prefix: Option<String>,
+    pub(crate) max_payload_bytes: Option<usize>,
+    pub(crate) max_array_length: Option<usize>,
+    pub(crate) max_nesting_depth: Option<usize>,
+    /// `inline` or `source`, as `--impl-style`
+    #[serde(default, deserialize_with = "value_enum")]
+    pub(crate) impl_style: Option<ImplStyle>,
+    /// `c99` or `c89`, as `--std`
+    #[serde(default, deserialize_with = "value_enum")]
+    pub(crate) std: Option<CDialect>,
+    /// MISRA C:2012 oriented code, as `--misra`
+    pub(crate) misra: Option<bool>,
+    pub(crate) doc_comments: Option<bool>,
+    /// `off`, `clamp` or `reject`, as `--encode-range`
+    #[serde(default, deserialize_with = "value_enum")]
+    pub(crate) encode_range: Option<EncodeRange>,
+    /// `natural` or `packed`, as `--struct-layout`
+    #[serde(default, deserialize_with = "value_enum")]
+    pub(crate) struct_layout: Option<StructLayout>,
+    pub(crate) template_dir: Option<PathBuf>,
+    /// `path`, `relative`, `basename` or `none`, as `--source-comment`
+    #[serde(default, deserialize_with = "value_enum")]
+    pub(crate) source_comment: Option<SourceComment>,
+    pub(crate) fast_path: Option<bool>,
+    pub(crate) deterministic_decode: Option<bool>,
+    pub(crate) all_helpers: Option<bool>,
+    pub(crate) emit_tests: Option<bool>,
+    pub(crate) emit_fuzz: Option<bool>,
+    pub(crate) emit_python: Option<bool>,
+    pub(crate) emit_cmake: Option<bool>,
+    pub(crate) emit_send: Option<bool>,
+    pub(crate) emit_skeleton: Option<bool>,
+    /// Largest message sent from a stack buffer, as `--send-stack-limit`
+    pub(crate) send_stack_limit: Option<usize>,
+    /// `arduino`, as `--package`
+    #[serde(default, deserialize_with = "value_enum")]
+    pub(crate) package: Option<Package>,
+    /// Table of contents in the markdown, `--no-toc` turns it off
+    pub(crate) toc: Option<bool>,
+    /// 64-byte CAN FD frames in the DBC instead of 8-byte ones
+    pub(crate) can_fd: Option<bool>,
+    pub(crate) strict: Option<bool>,
+}
+
+/// Reads a single path or a list of paths.
+fn one_or_many<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<PathBuf>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(PathBuf),
+        Many(Vec<PathBuf>),
+    }
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(path) => vec![path],
+        OneOrMany::Many(paths) => paths,
+    })
+}
+
+/// Reads a value of an enum of the command line, with the names and
+/// aliases the option accepts.
+fn value_enum<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: ValueEnum,
+{
+    let value = String::deserialize(deserializer)?;
+    T::from_str(&value, true).map(Some).map_err(|_| {
+        let expected: Vec<String> = T::value_variants()
+            .iter()
+            .filter_map(ValueEnum::to_possible_value)
+            .map(|possible| format!("'{}'", possible.get_name()))
+            .collect();
+        serde::de::Error::custom(format!(
+            "unsupported value '{}', expected {}",
+            value,
+            expected.join(", ")
+        ))
+    })
+}
+
+/// The `[output]` table.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct OutputConfig {
+    pub(crate) c: Option<PathBuf>,
+    pub(crate) docs: Option<PathBuf>,
+}
+
+impl OutputConfig {
+    /// The configured output directory of `target`.
+    pub(crate) fn get(&self, target: Target) -> Option<&PathBuf> {
+        match target {
+            Target::Code(TargetLanguage::C) => self.c.as_ref(),
+            Target::Docs
+            | Target::Csv
+            | Target::Ir
+            | Target::Kaitai
+            | Target::Proto
+            | Target::Dbc
+            | Target::Flow(_) => self.docs.as_ref(),
+        }
+    }
+}
+
+impl GenerationConfig {
+    /// Reads the file given with `--config`, or `h6xserial.toml` in the
+    /// current directory if it exists. Returns the path that was read.
+    pub(crate) fn load(path: Option<&Path>) -> Result<Option<(PathBuf, Self)>> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None if Path::new(CONFIG_FILE).is_file() => PathBuf::from(CONFIG_FILE),
+            None => return Ok(None),
+        };
+        let text = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read configuration {}", path.display()))?;
+        let mut config = Self::from_toml(&text)
+            .with_context(|| format!("invalid configuration {}", path.display()))?;
+        let base = path.parent().unwrap_or(Path::new(""));
+        config.resolve_paths(base);
+        Ok(Some((path, config)))
+    }
+
+    /// Parses the content of a configuration file.
+    pub(crate) fn from_toml(text: &str) -> Result<Self> {
+        Ok(toml::from_str(text)?)
+    }
+
+    /// Makes relative paths relative to `base`, the directory of the file.
+    fn resolve_paths(&mut self, base: &Path) {
+        let paths = self
+            .input
+            .iter_mut()
+            .chain(self.output.c.as_mut())
+            .chain(self.output.docs.as_mut())
+            .chain(self.template_dir.as_mut());
+        for path in paths {
+            if path.is_relative() && path != Path::new(crate::STDIO_PATH) {
+                *path = base.join(&*path);
+            }
+        }
+    }
+
+    /// Overrides the file values with those given on the command line.
+    pub(crate) fn apply_cli(&mut self, cli: &Cli) -> Result<()> {
+        if !cli.inputs.is_empty() {
+            self.input = cli.inputs.iter().map(PathBuf::from).collect();
+        }
+        self.apply_input_options(&cli.input_options);
+        // The export flags exclude each other; each generates one
+        // `languages` entry instead of code (`--export_docs` depending on
+        // `--format`)
+        let export = if cli.export_docs {
+            Some(match cli.format {
+                None | Some(Format::Markdown) => "docs",
+                Some(Format::Csv) => "csv",
+                Some(Format::Mermaid) => "mermaid",
+                Some(Format::Dot) => "dot",
+                Some(format @ (Format::Text | Format::Json)) => bail!(
+                    "unsupported --export_docs format '{}', expected 'markdown', 'csv', 'mermaid' or 'dot'",
+                    format.name()
+                ),
+            })
+        } else {
+            [
+                (cli.export_ir, "ir"),
+                (cli.export_ksy, "ksy"),
+                (cli.export_proto, "proto"),
+                (cli.export_dbc, "dbc"),
+            ]
+            .into_iter()
+            .find_map(|(set, language)| set.then_some(language))
+        };
+        if let Some(language) = export {
+            self.languages = Some(vec![language.to_string()]);
+        } else if let Some(language) = cli.language {
+            self.languages = Some(vec![language.name().to_string()]);
+        }
+        for (value, setting) in [
+            (&cli.base_name, &mut self.base_name),
+            (&cli.symbol_prefix, &mut self.symbol_prefix),
+        ] {
+            if value.is_some() {
+                setting.clone_from(value);
+            }
+        }
+        self.impl_style = cli.impl_style.or(self.impl_style);
+        self.std = cli.std.or(self.std);
+        self.encode_range = cli.encode_range.or(self.encode_range);
+        self.struct_layout = cli.struct_layout.or(self.struct_layout);
+        self.source_comment = cli.source_comment.or(self.source_comment);
+        self.package = cli.package.or(self.package);
+        if let Some(dir) = &cli.template_dir {
+            self.template_dir = Some(PathBuf::from(dir));
+        }
+        self.send_stack_limit = cli.send_stack_limit.or(self.send_stack_limit);
+        if cli.no_toc {
+            self.toc = Some(false);
+        }
+        for (flag, setting) in [
+            (cli.misra, &mut self.misra),
+            (cli.doc_comments, &mut self.doc_comments),
+            (cli.fast_path, &mut self.fast_path),
+            (cli.deterministic_decode, &mut self.deterministic_decode),
+            (cli.all_helpers, &mut self.all_helpers),
+            (cli.emit_tests, &mut self.emit_tests),
+            (cli.emit_fuzz, &mut self.emit_fuzz),
+            (cli.emit_python, &mut self.emit_python),
+            (cli.emit_cmake, &mut self.emit_cmake),
+            (cli.emit_send, &mut self.emit_send),
+            (cli.emit_skeleton, &mut self.emit_skeleton),
+            (cli.can_fd, &mut self.can_fd),
+        ] {
+            if flag {
+                *setting = Some(true);
+            }
+        }
+        if let Some(name) = &self.base_name
+            && (name.is_empty() || name.contains(['/', '\\']))
+        {
+            bail!(
+                "invalid base name '{}', expected a file name without directories",
+                name
+            );
+        }
+        Ok(())
+    }
+
+    /// Overrides the file values with the options on reading the input,
+    /// which `diff` accepts as well.
+    pub(crate) fn apply_input_options(&mut self, options: &InputOptions) {
+        self.input_format = options.input_format.or(self.input_format);
+        for (value, setting) in [
+            (options.max_payload, &mut self.max_payload_bytes),
+            (options.max_array_length, &mut self.max_array_length),
+            (options.max_nesting_depth, &mut self.max_nesting_depth),
+        ] {
+            if value.is_some() {
+                *setting = value;
+            }
+        }
+        if options.strict {
+            self.strict = Some(true);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::{Command, parse};
+
+    fn cli(args: &[&str]) -> Cli {
+        match parse(std::iter::once("h6xserial_idl").chain(args.iter().copied())).unwrap() {
+            Command::Run(cli) => *cli,
+            other => panic!("expected a run, got {:?}", other),
+        }
+    }
+
+    const CONFIG: &str = r#"
+input = "msgs/protocol.json"
+languages = ["c", "docs"]
+symbol_prefix = "acme"
+max_payload_bytes = 512
+impl_style = "source"
+strict = true
+
+[output]
+c = "generated"
+docs = "docs"
+"#;
+
+    #[test]
+    fn test_command_line_overrides_file() {
+        let mut config = GenerationConfig::from_toml(CONFIG).unwrap();
+        config.resolve_paths(Path::new("project"));
+        assert_eq!(config.input, [PathBuf::from("project/msgs/protocol.json")]);
+        assert_eq!(config.output.c, Some(PathBuf::from("project/generated")));
+
+        config
+            .apply_cli(&cli(&[
+                "--max-payload",
+                "1024",
+                "--symbol-prefix=beta",
+                "--export_docs",
+                "--no-toc",
+                "other.json",
+            ]))
+            .unwrap();
+        assert_eq!(config.input, [PathBuf::from("other.json")]);
+        assert_eq!(config.languages, Some(vec!["docs".to_string()]));
+        assert_eq!(config.symbol_prefix.as_deref(), Some("beta"));
+        assert_eq!(config.max_payload_bytes, Some(1024));
+        assert_eq!(config.toc, Some(false));
+        // Untouched by the command line
+        assert_eq!(config.impl_style, Some(ImplStyle::Source));
+        assert_eq!(config.strict, Some(true));
+    }
+
+    #[test]
+    fn test_unknown_keys_are_errors() {
+        let err = GenerationConfig::from_toml("input = \"a.json\"\nsymbol_prefx = \"x\"\n")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("unknown field `symbol_prefx`"), "{}", err);
+
+        let err = GenerationConfig::from_toml("[output]\nrust = \"out\"\n")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("unknown field `rust`"), "{}", err);
+
+        let err = GenerationConfig::from_toml("max_payload_bytes = \"big\"\n")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("invalid type"), "{}", err);
+
+        let err = GenerationConfig::from_toml("std = \"c11\"\n")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("unsupported value 'c11', expected 'c99', 'c89'"),
+            "{}",
+            err
+        );
+        let config = GenerationConfig::from_toml("std = \"ANSI\"\n").unwrap();
+        assert_eq!(config.std, Some(CDialect::C89));
+    }
+}
@@ -0,0 +1,205 @@
+//! Minimal line-based unified diff.
+//!
+//! Used to show what changed between a previously generated file and the
+//! current generator output. Implements Myers' O(ND) algorithm, which is
+//! fast for the small edit distances typical of generated code.
+
+use std::fmt::Write as FmtWrite;
+
+/// Number of unchanged lines shown around each change.
+const CONTEXT_LINES: usize = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Edit {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// Returns the shortest edit script turning `old` into `new`, one entry per
+/// line of output (equal lines appear once).
+fn edit_script(old: &[&str], new: &[&str]) -> Vec<Edit> {
+    let n = old.len() as isize;
+    let m = new.len() as isize;
+    let max = (n + m) as usize;
+    let offset = max as isize + 1;
+    let mut v = vec![0isize; 2 * max + 3];
+    let mut trace = Vec::new();
+
+    'search: for d in 0..=max as isize {
+        trace.push(v.clone());
+        let mut k = -d;
+        while k <= d {
+            let idx = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && v[idx - 1] < v[idx + 1]) {
+                v[idx + 1]
+            } else {
+                v[idx - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[idx] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+            k += 2;
+        }
+    }
+
+    // Walk the trace backwards to recover the path
+    let mut edits = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+        let idx = (k + offset) as usize;
+        let prev_k = if k == -d || (k != d && v[idx - 1] < v[idx + 1]) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = v[(prev_k + offset) as usize];
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            edits.push(Edit::Equal);
+            x -= 1;
+            y -= 1;
+        }
+        if d > 0 {
+            edits.push(if x == prev_x {
+                Edit::Insert
+            } else {
+                Edit::Delete
+            });
+            x = prev_x;
+            y = prev_y;
+        }
+    }
+    edits.reverse();
+    edits
+}
+
+/// Formats a unified diff between `old` and `new`.
+///
+/// Returns an empty string when both texts have identical lines.
+///
+/// # Example
+/// ```
+/// let diff = h6xserial_idl::diff::unified_diff("a\nb\n", "a\nc\n", "old", "new");
+/// assert_eq!(diff, "--- old\n+++ new\n@@ -1,2 +1,2 @@\n a\n-b\n+c\n");
+/// ```
+pub fn unified_diff(old: &str, new: &str, old_label: &str, new_label: &str) -> String {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let edits = edit_script(&old_lines, &new_lines);
+    if edits.iter().all(|e| *e == Edit::Equal) {
+        return String::new();
+    }
+
+    // Line positions in both texts before each edit
+    let mut positions = Vec::with_capacity(edits.len() + 1);
+    let (mut oi, mut ni) = (0, 0);
+    for edit in &edits {
+        positions.push((oi, ni));
+        match edit {
+            Edit::Equal => {
+                oi += 1;
+                ni += 1;
+            }
+            Edit::Delete => oi += 1,
+            Edit::Insert => ni += 1,
+        }
+    }
+
+    let changes: Vec<usize> = edits
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| **e != Edit::Equal)
+        .map(|(i, _)| i)
+        .collect();
+
+    let mut out = String::new();
+    writeln!(&mut out, "--- {}", old_label).unwrap();
+    writeln!(&mut out, "+++ {}", new_label).unwrap();
+
+    let mut next = 0;
+    while next < changes.len() {
+        let first = changes[next];
+        let mut last = first;
+        next += 1;
+        // Merge changes whose context would overlap into one hunk
+        while next < changes.len() && changes[next] - last <= 2 * CONTEXT_LINES {
+            last = changes[next];
+            next += 1;
+        }
+        let start = first.saturating_sub(CONTEXT_LINES);
+        let stop = (last + CONTEXT_LINES + 1).min(edits.len());
+
+        let old_count = edits[start..stop]
+            .iter()
+            .filter(|e| **e != Edit::Insert)
+            .count();
+        let new_count = edits[start..stop]
+            .iter()
+            .filter(|e| **e != Edit::Delete)
+            .count();
+        let (old_pos, new_pos) = positions[start];
+        let hunk_start = |pos: usize, count: usize| if count == 0 { pos } else { pos + 1 };
+        writeln!(
+            &mut out,
+            "@@ -{},{} +{},{} @@",
+            hunk_start(old_pos, old_count),
+            old_count,
+            hunk_start(new_pos, new_count),
+            new_count
+        )
+        .unwrap();
+
+        for (edit, (oi, ni)) in edits[start..stop].iter().zip(&positions[start..stop]) {
+            match edit {
+                Edit::Equal => writeln!(&mut out, " {}", old_lines[*oi]).unwrap(),
+                Edit::Delete => writeln!(&mut out, "-{}", old_lines[*oi]).unwrap(),
+                Edit::Insert => writeln!(&mut out, "+{}", new_lines[*ni]).unwrap(),
+            }
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identical_texts_produce_no_diff() {
+        assert_eq!(unified_diff("a\nb\n", "a\nb\n", "x", "y"), "");
+        assert_eq!(unified_diff("", "", "x", "y"), "");
+    }
+
+    #[test]
+    fn test_separate_hunks_and_context() {
+        let old: String = (1..=20).map(|i| format!("line{}\n", i)).collect();
+        let new = old
+            .replace("line2\n", "line2 changed\n")
+            .replace("line18\n", "");
+        let diff = unified_diff(&old, &new, "old", "new");
+        assert_eq!(
+            diff,
+            "--- old\n+++ new\n\
+             @@ -1,5 +1,5 @@\n line1\n-line2\n+line2 changed\n line3\n line4\n line5\n\
+             @@ -15,6 +15,5 @@\n line15\n line16\n line17\n-line18\n line19\n line20\n"
+        );
+    }
+
+    #[test]
+    fn test_insert_into_empty_text() {
+        assert_eq!(
+            unified_diff("", "new line\n", "old", "new"),
+            "--- old\n+++ new\n@@ -0,0 +1,1 @@\n+new line\n"
+        );
+    }
+}
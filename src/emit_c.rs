@@ -1,17 +1,38 @@
-//! C99 code generator for message definitions.
+//! C99 (or C89, see [`CDialect`]) code generator for message definitions.
 //!
 //! Generates header files with type definitions and encode/decode functions.
+//! By default every function is `static inline` in the header; with
+//! [`ImplStyle::Source`] the headers only carry prototypes and the bodies are
+//! written to companion `.c` files.
 
-use std::collections::HashSet;
+mod arduino;
+mod cmake;
+mod ctypes;
+mod fuzz;
+mod self_test;
+mod skeleton;
+
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fmt::Write as FmtWrite;
-use std::path::Path;
+use std::io;
+use std::path::{Path, PathBuf};
 
 use anyhow::Result;
+use rayon::prelude::*;
+
+pub use cmake::CMakeOptions;
 
+use crate::fingerprint::GENERATOR;
+use crate::template::TemplateContext;
+use crate::validate::pointer_push;
 use crate::{
-    ArraySpec, Endian, MessageBody, MessageDefinition, Metadata, PrimitiveType, RequestType,
-    ScalarSpec, StructField, StructFieldType, StructSpec, TargetLanguage, load_templates,
-    to_macro_ident, to_snake_case,
+    ArraySpec, Bound, ConstValue, Deprecation, Endian, IdlError, MAX_NESTING_DEPTH_CAP,
+    MessageBody, MessageDefinition, Metadata, PrimitiveType, RequestType, STDIN_LABEL, ScalarSpec,
+    Scaling, SourceComment, StructField, StructFieldType, StructSpec, TargetLanguage,
+    TimestampResolution, UnionSpec, UnionVariant, ValueRange, body_structs, const_macro_name,
+    ir_fingerprint, load_optional_template, load_templates, message_body_max_size,
+    message_body_min_size, shared_types, struct_shape, template_override, to_macro_ident,
+    to_snake_case,
 };
 
 /// Determines which functions to generate for a message.
@@ -25,6 +46,166 @@ pub enum FunctionMode {
     Both,
 }
 
+/// Where generated function bodies are placed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ImplStyle {
+    /// Every function is `static inline` inside the header (default)
+    #[default]
+    #[value(alias = "header")]
+    Inline,
+    /// Headers declare prototypes only; bodies go into a companion `.c` file
+    #[value(alias = "c")]
+    Source,
+}
+
+/// C standard the generated code is written for.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum CDialect {
+    /// C99: `stdbool.h`, `static inline` and declarations where first
+    /// used (default)
+    #[default]
+    C99,
+    /// C89/ANSI C for legacy compilers: `bool` is `unsigned char`, inlining
+    /// goes through the `H6XSERIAL_INLINE` macro, locals are declared at the
+    /// top of their block and there is no `_Static_assert`
+    #[value(aliases = ["c90", "ansi"])]
+    C89,
+}
+
+impl CDialect {
+    /// Storage class of the functions defined in headers.
+    fn static_inline(self) -> &'static str {
+        match self {
+            CDialect::C99 => "static inline ",
+            CDialect::C89 => "static H6XSERIAL_INLINE ",
+        }
+    }
+
+    /// Substituted for `{{inline}}` in the helper templates.
+    fn inline_keyword(self) -> &'static str {
+        match self {
+            CDialect::C99 => "inline",
+            CDialect::C89 => "H6XSERIAL_INLINE",
+        }
+    }
+
+    /// `<c_type> <name> = <value>` in C99. C89 only assigns, the local is
+    /// declared at the top of the function by [`NameContext::write_locals`].
+    fn init(self, c_type: &str, name: &str, value: &str) -> String {
+        match self {
+            CDialect::C99 => format!("{} {} = {}", c_type, name, value),
+            CDialect::C89 => format!("{} = {}", name, value),
+        }
+    }
+}
+
+/// Library layout the generated files are packaged in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Package {
+    /// Arduino/PlatformIO library: `library.properties`, the code under
+    /// `src/` and an `examples/Decode` sketch
+    #[value(alias = "platformio")]
+    Arduino,
+}
+
+/// What generated encoders do with values outside their `min`/`max`
+/// range. Decoders always reject them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum EncodeRange {
+    /// Encode values unchecked (default)
+    #[default]
+    #[value(alias = "none")]
+    Off,
+    /// Clamp each value into its range
+    Clamp,
+    /// Encode nothing and return 0
+    Reject,
+}
+
+/// In-memory layout of the generated struct typedefs. The wire format is
+/// the same either way.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum StructLayout {
+    /// The compiler's natural alignment, with padding between members
+    /// (default)
+    #[default]
+    Natural,
+    /// No padding ([`PACKED_MACRO`]), so the members of a fixed-size
+    /// struct sit at their wire offsets
+    Packed,
+}
+
+/// Options controlling C code generation.
+#[derive(Clone, Debug, Default)]
+pub struct CGenOptions {
+    /// Header-only inline functions or a `.h`/`.c` pair
+    pub impl_style: ImplStyle,
+    /// C standard of the generated code
+    pub dialect: CDialect,
+    /// Write the code for MISRA C:2012 checkers: explicit casts and
+    /// comparisons, `(void)` discarded results, fixed-width loop counters
+    /// and a comment block listing the remaining deviations
+    pub misra: bool,
+    /// Document the types and functions with Doxygen comment blocks
+    pub doc_comments: bool,
+    /// Emit a single-`memcpy` encode/decode path for eligible structs on
+    /// little-endian hosts (see [`struct_fast_path_eligible`])
+    pub fast_path: bool,
+    /// Zero the unused tail of decoded arrays and the bytes of the other
+    /// union variants, so every byte of a decoded message is defined
+    pub deterministic_decode: bool,
+    /// Also emit a `*_tests.c` encode/decode round-trip self-test
+    pub emit_tests: bool,
+    /// Also emit a `*_fuzz.c` libFuzzer/AFL harness for the decoders
+    pub emit_fuzz: bool,
+    /// Also emit a `*_ctypes.py` Python module binding the functions of
+    /// [`ImplStyle::Source`] output
+    pub emit_python: bool,
+    /// Also emit a `<base>.cmake` module declaring a CMake INTERFACE
+    /// library of the generated files
+    pub cmake: Option<CMakeOptions>,
+    /// Lay the files out as a library of this kind
+    pub package: Option<Package>,
+    /// Emit every byte order helper, not only those the messages call
+    pub all_helpers: bool,
+    /// Also write the [`generate_server_skeleton`] file, without
+    /// overwriting an existing one
+    pub emit_skeleton: bool,
+    /// Also emit `<prefix>_send_<name>()` stubs for the messages a role
+    /// encodes, passing the payload to a transmit callback. Messages whose
+    /// `MAX_SIZE` exceeds this many bytes take a caller-provided buffer
+    /// instead of a stack buffer.
+    pub send_stack_limit: Option<usize>,
+    /// Handling of out-of-range values in encoders
+    pub encode_range: EncodeRange,
+    /// Padding of the generated struct typedefs
+    pub struct_layout: StructLayout,
+    /// Directory whose helper templates replace the embedded ones
+    pub template_dir: Option<PathBuf>,
+    /// Prefix of the byte order helper functions, substituted for
+    /// `{{prefix}}` in the templates (defaults to `h6xserial`)
+    pub symbol_prefix: Option<String>,
+    /// How the `Source:` comment names the input document
+    pub source_comment: SourceComment,
+    /// Further documents merged into the IR, listed after the input path
+    /// in the `Source:` comment
+    pub extra_inputs: Vec<PathBuf>,
+    /// Existing types header that [`generate_for_role`] includes instead of
+    /// generating `<base>_types.h` and the byte order header
+    pub types_header: Option<String>,
+    /// Command line that regenerates the files, given in a "DO NOT EDIT"
+    /// line of their banner
+    pub regenerate_command: Option<String>,
+}
+
+impl CGenOptions {
+    fn helper_prefix(&self) -> &str {
+        self.symbol_prefix
+            .as_deref()
+            .unwrap_or(DEFAULT_SYMBOL_PREFIX)
+    }
+}
+
 /// Output file specification for multi-file generation.
 #[derive(Debug)]
 pub struct OutputFile {
@@ -32,14 +213,55 @@ pub struct OutputFile {
     pub content: String,
 }
 
+/// How a single function is written out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum FnEmit {
+    /// `static inline` definition for header-only output, see
+    /// [`CDialect::static_inline`]
+    Inline,
+    /// External-linkage definition for a `.c` file
+    Definition,
+    /// Prototype only, for headers in source mode
+    Prototype,
+}
+
+impl FnEmit {
+    fn qualifier(self, dialect: CDialect) -> &'static str {
+        match self {
+            FnEmit::Inline => dialect.static_inline(),
+            FnEmit::Definition | FnEmit::Prototype => "",
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 struct NameContext {
     msg_prefix: String,
     macro_prefix: String,
+    /// Prefix of the byte order helpers, see [`CGenOptions::symbol_prefix`]
+    helpers: String,
+    dialect: CDialect,
+    /// See [`CGenOptions::misra`]
+    misra: bool,
+    /// See [`CGenOptions::doc_comments`]
+    doc_comments: bool,
+    /// [`StructLayout::Packed`]
+    packed: bool,
+    /// See [`CGenOptions::deterministic_decode`]
+    deterministic_decode: bool,
+    /// See [`Metadata::packet_id_width`]
+    packet_id_width: u8,
+    /// C identifier of the `group` of each grouped message, by message name
+    groups: HashMap<String, String>,
 }
 
 impl NameContext {
-    fn new(base_name: &str) -> Self {
+    fn new(
+        base_name: &str,
+        options: &CGenOptions,
+        metadata: &Metadata,
+        messages: &[MessageDefinition],
+    ) -> Self {
         let mut msg_prefix = to_snake_case(base_name);
         if msg_prefix.is_empty() {
             msg_prefix = "messages".to_string();
@@ -48,31 +270,560 @@ impl NameContext {
         Self {
             msg_prefix,
             macro_prefix,
+            helpers: options.helper_prefix().to_string(),
+            dialect: options.dialect,
+            misra: options.misra,
+            doc_comments: options.doc_comments,
+            packed: options.struct_layout == StructLayout::Packed,
+            deterministic_decode: options.deterministic_decode,
+            packet_id_width: metadata.packet_id_width,
+            groups: messages
+                .iter()
+                .filter_map(|msg| Some((msg.name.clone(), to_snake_case(msg.group.as_ref()?))))
+                .collect(),
+        }
+    }
+
+    /// The part of the C names of message `name` after the base name:
+    /// `msg_<name>`, or `<group>_msg_<name>` in a group.
+    fn msg_ident(&self, name: &str) -> String {
+        match self.groups.get(name) {
+            Some(group) => format!("{}_msg_{}", group, to_snake_case(name)),
+            None => format!("msg_{}", to_snake_case(name)),
+        }
+    }
+
+    /// Prefix of the C functions of message `name`, e.g. `proto_msg_ping`.
+    fn msg_fn_prefix(&self, name: &str) -> String {
+        format!("{}_{}", self.msg_prefix, self.msg_ident(name))
+    }
+
+    /// Prefix of the macros of message `name`, e.g. `PROTO_MSG_PING`.
+    fn msg_macro_prefix(&self, name: &str) -> String {
+        format!(
+            "{}_{}",
+            self.macro_prefix,
+            self.msg_ident(name).to_ascii_uppercase()
+        )
+    }
+
+    /// Opening of a struct typedef, with the packing attribute if packed.
+    fn typedef_struct(&self) -> &'static str {
+        if self.packed {
+            "typedef struct H6XSERIAL_PACKED {"
+        } else {
+            "typedef struct {"
+        }
+    }
+
+    /// Literal of a size, length or count, `U`-suffixed in MISRA mode so
+    /// that comparisons with `size_t` do not mix signedness.
+    fn uint(&self, value: impl std::fmt::Display) -> String {
+        if self.misra {
+            format!("{}U", value)
+        } else {
+            value.to_string()
+        }
+    }
+
+    /// Type of the `i` loop counters, fixed width in MISRA mode.
+    fn counter_type(&self) -> &'static str {
+        if self.misra { "uint32_t" } else { "size_t" }
+    }
+
+    /// Start of a `for` loop over the `i` counter.
+    fn for_index(&self) -> String {
+        match self.dialect {
+            CDialect::C99 => format!("for ({} i = {}; ", self.counter_type(), self.uint(0)),
+            CDialect::C89 => format!("for (i = {}; ", self.uint(0)),
+        }
+    }
+
+    /// Declares the locals of a function body in C89, where they cannot
+    /// follow statements; C99 declares them in place. `i` is the loop
+    /// counter, the others are `size_t`.
+    fn write_locals(&self, out: &mut String, names: &[&str]) {
+        if self.dialect == CDialect::C89 {
+            for name in names {
+                let c_type = if *name == "i" {
+                    self.counter_type()
+                } else {
+                    "size_t"
+                };
+                writeln!(out, "    {} {};", c_type, name).unwrap();
+            }
+        }
+    }
+
+    /// Writes the early return of a function when one of `pointers` is
+    /// null. MISRA mode compares with `NULL` instead of applying `!` to a
+    /// pointer.
+    fn write_null_guard(&self, out: &mut String, pointers: &[&str], result: &str) {
+        let checks: Vec<String> = if self.misra && pointers.len() > 1 {
+            pointers
+                .iter()
+                .map(|p| format!("({} == NULL)", p))
+                .collect()
+        } else if self.misra {
+            pointers.iter().map(|p| format!("{} == NULL", p)).collect()
+        } else {
+            pointers.iter().map(|p| format!("!{}", p)).collect()
+        };
+        writeln!(
+            out,
+            "    if ({}) {{\n        return {};\n    }}",
+            checks.join(" || "),
+            result
+        )
+        .unwrap();
+    }
+
+    /// Written before calls whose result is unused: `(void)` in MISRA
+    /// mode (Rule 17.7).
+    fn discard(&self) -> &'static str {
+        if self.misra { "(void)" } else { "" }
+    }
+
+    /// C type holding a packet ID.
+    fn packet_id_type(&self) -> &'static str {
+        if self.packet_id_width > 8 {
+            "uint16_t"
+        } else {
+            "uint8_t"
         }
     }
 }
 
-fn name_context_from_path(input_path: &Path) -> NameContext {
+fn name_context_from_path(
+    input_path: &Path,
+    options: &CGenOptions,
+    metadata: &Metadata,
+    messages: &[MessageDefinition],
+) -> NameContext {
     let base_name = input_path
         .file_stem()
         .and_then(|s| s.to_str())
+        .filter(|_| input_path != Path::new(STDIN_LABEL))
         .unwrap_or("messages");
-    NameContext::new(base_name)
+    NameContext::new(base_name, options, metadata, messages)
 }
 
 fn msg_macro_prefix(ctx: &NameContext, msg: &MessageDefinition) -> String {
-    format!("{}_MSG_{}", ctx.macro_prefix, to_macro_ident(&msg.name))
+    ctx.msg_macro_prefix(&msg.name)
+}
+
+/// C type of a shared struct type, e.g. `h6xserial_type_vector3_t`.
+fn shared_type_name(helpers: &str, name: &str) -> String {
+    format!("{}_type_{}_t", helpers, to_snake_case(name))
+}
+
+/// Prefix of the array length macros of a struct's fields: the shared
+/// type's own (`H6XSERIAL_TYPE_VECTOR3`) or `prefix` for a struct defined
+/// in place.
+fn fields_macro_prefix(helpers: &str, spec: &StructSpec, prefix: String) -> String {
+    match &spec.shared {
+        Some(name) => format!("{}_TYPE_{}", to_macro_ident(helpers), to_macro_ident(name)),
+        None => prefix,
+    }
+}
+
+/// Width families of the byte order helpers. Each has a
+/// `helpers_<family>_le.h` and a `helpers_<family>_be.h` template; the
+/// float helpers call the integer ones of the same width and byte order.
+const HELPER_FAMILIES: &[&str] = &["u16", "u24", "u32", "u64", "f16", "f32", "f64"];
+
+/// Template file of a helper family and byte order.
+fn helper_template_file(family: &str, endian: Endian) -> String {
+    format!("helpers_{}_{}.h", family, endian.suffix())
+}
+
+/// Index into [`HELPER_FAMILIES`] of the helpers encoding `primitive`,
+/// or `None` for single-byte types.
+fn helper_family(primitive: PrimitiveType) -> Option<usize> {
+    match primitive {
+        PrimitiveType::Bool | PrimitiveType::Char | PrimitiveType::Int8 | PrimitiveType::Uint8 => {
+            None
+        }
+        PrimitiveType::Int16 | PrimitiveType::Uint16 => Some(0),
+        PrimitiveType::Int24 | PrimitiveType::Uint24 => Some(1),
+        PrimitiveType::Int32 | PrimitiveType::Uint32 => Some(2),
+        PrimitiveType::Int64 | PrimitiveType::Uint64 => Some(3),
+        PrimitiveType::Float16 => Some(4),
+        PrimitiveType::Float32 => Some(5),
+        PrimitiveType::Float64 => Some(6),
+    }
+}
+
+/// Index into [`HELPER_FAMILIES`] of the integer helpers the float helpers
+/// of `family` call, if it is a float family.
+fn float_base_family(family: usize) -> Option<usize> {
+    match HELPER_FAMILIES[family] {
+        "f16" => Some(0),
+        "f32" => Some(2),
+        "f64" => Some(3),
+        _ => None,
+    }
+}
+
+/// Size checks for the float helpers among the template `files`, which
+/// copy a `float` or `double` into a `uint32_t` or `uint64_t`; the half
+/// precision helpers convert from and to a `float`.
+fn float_size_checks(files: &[&str], helpers: &str, dialect: CDialect) -> String {
+    let uses = |family: &str| {
+        files
+            .iter()
+            .any(|file| file.starts_with(&format!("helpers_{}", family)))
+    };
+    let mut checks: Vec<String> = [
+        ("f16", "float", 4),
+        ("f32", "float", 4),
+        ("f64", "double", 8),
+    ]
+    .into_iter()
+    .filter(|(family, _, _)| uses(family))
+    .map(|(_, c_type, size)| {
+        format!(
+            "H6XSERIAL_STATIC_ASSERT(sizeof({c_type}) == {size}, {helpers}_helpers_{c_type}_size)\n"
+        )
+    })
+    .collect();
+    checks.dedup();
+    if checks.is_empty() {
+        return String::new();
+    }
+    let mut out = String::from(
+        "/* The float helpers copy the bits into an integer of the same size with\n * memcpy, which is defined in C and C++ alike, and write the integer byte\n * by byte, independent of the host byte order. */\n",
+    );
+    out.push_str(static_assert_macro(dialect));
+    out.extend(checks);
+    out.push('\n');
+    out
 }
 
-/// Template files containing C helper functions for serialization.
-const TEMPLATE_FILES: &[&str] = &[
-    "helpers_u16.h",
-    "helpers_u32.h",
-    "helpers_u64.h",
-    "helpers_f32.h",
-    "helpers_f64.h",
-];
+/// The helper templates the encoders and decoders of `messages` call, in
+/// [`HELPER_FAMILIES`] order, or all of them with `all`. A
+/// `helpers_<family>.h` file in `template_dir`, from before the templates
+/// were split by byte order, stands in for both halves of its family.
+fn helper_template_files(
+    messages: &[MessageDefinition],
+    all: bool,
+    template_dir: Option<&Path>,
+) -> Vec<String> {
+    // needed[family][0] is little endian, [1] big endian
+    let mut needed = [[all; 2]; HELPER_FAMILIES.len()];
+    let mut mark = |primitive: PrimitiveType, endian: Endian| {
+        if let Some(family) = helper_family(primitive) {
+            let order = usize::from(endian == Endian::Big);
+            needed[family][order] = true;
+            if let Some(base) = float_base_family(family) {
+                needed[base][order] = true;
+            }
+        }
+    };
+    fn visit(spec: &StructSpec, mark: &mut impl FnMut(PrimitiveType, Endian)) {
+        for field in &spec.fields {
+            match &field.field_type {
+                StructFieldType::Primitive(primitive) => mark(*primitive, field.endian),
+                StructFieldType::Array(array) => mark(array.primitive, field.endian),
+                StructFieldType::Nested(nested) => visit(nested, mark),
+                StructFieldType::Bytes(_) => {}
+            }
+        }
+    }
+    for msg in messages {
+        match &msg.body {
+            MessageBody::Scalar(spec) => mark(spec.primitive, spec.endian),
+            MessageBody::Array(spec) => mark(spec.primitive, spec.endian),
+            MessageBody::Struct(spec) => visit(spec, &mut mark),
+            MessageBody::Union(spec) => {
+                mark(spec.tag, spec.endian);
+                for variant in &spec.variants {
+                    visit(&variant.spec, &mut mark);
+                }
+            }
+        }
+    }
+
+    let mut files = Vec::new();
+    for (family, orders) in HELPER_FAMILIES.iter().zip(needed) {
+        if !orders.contains(&true) {
+            continue;
+        }
+        let combined = format!("helpers_{}.h", family);
+        let combined_override = template_dir
+            .and_then(|dir| template_override(TargetLanguage::C, dir, &combined).ok())
+            .flatten();
+        if combined_override.is_some() {
+            files.push(combined);
+            continue;
+        }
+        for (endian, needed) in [Endian::Little, Endian::Big].into_iter().zip(orders) {
+            if needed {
+                files.push(helper_template_file(family, endian));
+            }
+        }
+    }
+    files
+}
 const BYTEORDER_HEADER_FILENAME: &str = "h6x_serial_byteorder.h";
+/// Definitions of the byte order helpers in source style.
+const BYTEORDER_SOURCE_FILENAME: &str = "h6x_serial_byteorder.c";
+const DEFAULT_SYMBOL_PREFIX: &str = "h6xserial";
+
+/// Host byte order detection used to guard the `memcpy` fast path.
+const HOST_ENDIAN_DETECT: &str = "\
+#ifndef H6XSERIAL_HOST_LITTLE_ENDIAN
+#if defined(__BYTE_ORDER__) && defined(__ORDER_LITTLE_ENDIAN__) && (__BYTE_ORDER__ == __ORDER_LITTLE_ENDIAN__)
+#define H6XSERIAL_HOST_LITTLE_ENDIAN 1
+#elif defined(_MSC_VER) || defined(__LITTLE_ENDIAN__)
+#define H6XSERIAL_HOST_LITTLE_ENDIAN 1
+#else
+#define H6XSERIAL_HOST_LITTLE_ENDIAN 0
+#endif
+#endif
+";
+
+/// Portable static assertion: C11 `_Static_assert`, C++11 `static_assert`, or
+/// a negative-array-size typedef on C99. `name` must be a unique identifier.
+/// Defining `H6XSERIAL_NO_STATIC_ASSERT` disables all checks.
+const STATIC_ASSERT_MACRO: &str = "\
+#ifndef H6XSERIAL_STATIC_ASSERT
+#if defined(H6XSERIAL_NO_STATIC_ASSERT)
+#define H6XSERIAL_STATIC_ASSERT(cond, name)
+#elif defined(__cplusplus) && __cplusplus >= 201103L
+#define H6XSERIAL_STATIC_ASSERT(cond, name) static_assert(cond, #name);
+#elif defined(__STDC_VERSION__) && __STDC_VERSION__ >= 201112L
+#define H6XSERIAL_STATIC_ASSERT(cond, name) _Static_assert(cond, #name);
+#else
+#define H6XSERIAL_STATIC_ASSERT(cond, name) typedef char h6xserial_static_assert_##name[(cond) ? 1 : -1];
+#endif
+#endif
+";
+
+/// [`STATIC_ASSERT_MACRO`] for [`CDialect::C89`], which always takes the
+/// typedef.
+const STATIC_ASSERT_MACRO_C89: &str = "\
+#ifndef H6XSERIAL_STATIC_ASSERT
+#if defined(H6XSERIAL_NO_STATIC_ASSERT)
+#define H6XSERIAL_STATIC_ASSERT(cond, name)
+#else
+#define H6XSERIAL_STATIC_ASSERT(cond, name) typedef char h6xserial_static_assert_##name[(cond) ? 1 : -1];
+#endif
+#endif
+";
+
+fn static_assert_macro(dialect: CDialect) -> &'static str {
+    match dialect {
+        CDialect::C99 => STATIC_ASSERT_MACRO,
+        CDialect::C89 => STATIC_ASSERT_MACRO_C89,
+    }
+}
+
+/// Stand-ins for `stdbool.h` and `inline` in [`CDialect::C89`] output.
+/// Defining `H6XSERIAL_INLINE` before the include overrides the keyword.
+const C89_COMPAT: &str = "\
+#if !defined(__cplusplus) && !defined(bool)
+#define bool unsigned char
+#define true 1
+#define false 0
+#endif
+#ifndef H6XSERIAL_INLINE
+#if defined(__cplusplus)
+#define H6XSERIAL_INLINE inline
+#elif defined(__GNUC__)
+#define H6XSERIAL_INLINE __inline__
+#elif defined(_MSC_VER)
+#define H6XSERIAL_INLINE __inline
+#else
+#define H6XSERIAL_INLINE
+#endif
+#endif
+";
+
+/// Writes the standard includes of a header declaring message types.
+fn write_standard_includes(out: &mut String, dialect: CDialect) {
+    match dialect {
+        CDialect::C99 => out.push_str(
+            "#include <stdbool.h>\n#include <stddef.h>\n#include <stdint.h>\n#include <string.h>\n\n",
+        ),
+        CDialect::C89 => {
+            out.push_str("#include <stddef.h>\n#include <stdint.h>\n#include <string.h>\n\n");
+            out.push_str(C89_COMPAT);
+            out.push('\n');
+        }
+    }
+}
+
+/// Deprecation attribute for the functions of deprecated messages: GCC and
+/// Clang `__attribute__`, MSVC `__declspec`, nothing elsewhere. Defining
+/// `H6XSERIAL_NO_DEPRECATED` silences the warnings.
+const DEPRECATED_MACRO: &str = "\
+#ifndef H6XSERIAL_DEPRECATED
+#if defined(H6XSERIAL_NO_DEPRECATED)
+#define H6XSERIAL_DEPRECATED(reason)
+#elif defined(__GNUC__) || defined(__clang__)
+#define H6XSERIAL_DEPRECATED(reason) __attribute__((deprecated(reason)))
+#elif defined(_MSC_VER)
+#define H6XSERIAL_DEPRECATED(reason) __declspec(deprecated(reason))
+#else
+#define H6XSERIAL_DEPRECATED(reason)
+#endif
+#endif
+";
+
+/// Suppresses deprecation warnings around the send stubs and version
+/// decoders of deprecated messages, which call the deprecated encoder or
+/// decoder.
+const ALLOW_DEPRECATED_MACROS: &str = "\
+#ifndef H6XSERIAL_ALLOW_DEPRECATED_BEGIN
+#if defined(__GNUC__) || defined(__clang__)
+#define H6XSERIAL_ALLOW_DEPRECATED_BEGIN _Pragma(\"GCC diagnostic push\") _Pragma(\"GCC diagnostic ignored \\\"-Wdeprecated-declarations\\\"\")
+#define H6XSERIAL_ALLOW_DEPRECATED_END _Pragma(\"GCC diagnostic pop\")
+#elif defined(_MSC_VER)
+#define H6XSERIAL_ALLOW_DEPRECATED_BEGIN __pragma(warning(push)) __pragma(warning(disable : 4996))
+#define H6XSERIAL_ALLOW_DEPRECATED_END __pragma(warning(pop))
+#else
+#define H6XSERIAL_ALLOW_DEPRECATED_BEGIN
+#define H6XSERIAL_ALLOW_DEPRECATED_END
+#endif
+#endif
+";
+
+/// Packing of the typedefs with [`StructLayout::Packed`]: GCC and Clang
+/// take `__attribute__((packed))` on each typedef, other compilers (MSVC,
+/// IAR, Arm Compiler 5) a `#pragma pack` around them. Defining
+/// `H6XSERIAL_PACKED` before the include overrides the attribute and drops
+/// the pragma.
+const PACKED_MACRO: &str = "\
+#ifndef H6XSERIAL_PACKED
+#if defined(__GNUC__) || defined(__clang__)
+#define H6XSERIAL_PACKED __attribute__((packed))
+#else
+#define H6XSERIAL_PACKED
+#define H6XSERIAL_PACK_PRAGMA
+#endif
+#endif
+#ifdef H6XSERIAL_PACK_PRAGMA
+#pragma pack(push, 1)
+#endif
+";
+
+/// Closes the `#pragma pack` of [`PACKED_MACRO`] after the typedefs.
+const PACKED_END: &str = "\
+#ifdef H6XSERIAL_PACK_PRAGMA
+#pragma pack(pop)
+#endif
+";
+
+/// User templates with no embedded default, read from
+/// [`CGenOptions::template_dir`] when present: `prologue.h` is inserted after
+/// the standard includes of every generated header, `epilogue.h` right
+/// before its closing include guard.
+const PROLOGUE_FILE: &str = "prologue.h";
+const EPILOGUE_FILE: &str = "epilogue.h";
+
+/// Template content shared by every generated file.
+struct Templates {
+    /// Byte order helpers plus any option-dependent helper code
+    helpers: String,
+    prologue: Option<String>,
+    epilogue: Option<String>,
+    /// Banner lines naming the input, the IR fingerprint and the command
+    /// that regenerates the file
+    origin: String,
+}
+
+impl Templates {
+    fn load(
+        options: &CGenOptions,
+        metadata: &Metadata,
+        messages: &[MessageDefinition],
+        input_path: &Path,
+    ) -> Result<Self, IdlError> {
+        let dir = options.template_dir.as_deref();
+        let label = options
+            .source_comment
+            .label_all(input_path, &options.extra_inputs);
+        let mut origin = String::new();
+        if let Some(label) = &label {
+            writeln!(origin, " * Source: {}", label).unwrap();
+        }
+        writeln!(
+            origin,
+            " * IR fingerprint: {}",
+            ir_fingerprint(metadata, messages)
+        )
+        .unwrap();
+        if let Some(command) = &options.regenerate_command {
+            writeln!(
+                origin,
+                " * DO NOT EDIT: changes are lost when this file is regenerated with"
+            )
+            .unwrap();
+            writeln!(origin, " *   {}", command.replace("*/", "*\\/")).unwrap();
+        }
+        let source = label.unwrap_or_default();
+        let context = TemplateContext::new(
+            options.helper_prefix(),
+            options.dialect.inline_keyword(),
+            metadata,
+            &source,
+        );
+        let files = helper_template_files(messages, options.all_helpers, dir);
+        let files: Vec<&str> = files.iter().map(String::as_str).collect();
+        let mut helpers = float_size_checks(&files, options.helper_prefix(), options.dialect);
+        helpers.push_str(&load_templates(TargetLanguage::C, &files, dir, &context)?);
+        if options.fast_path {
+            helpers.push_str(HOST_ENDIAN_DETECT);
+            helpers.push('\n');
+        }
+        Ok(Self {
+            helpers,
+            prologue: load_optional_template(TargetLanguage::C, PROLOGUE_FILE, dir, &context)?,
+            epilogue: load_optional_template(TargetLanguage::C, EPILOGUE_FILE, dir, &context)?,
+            origin,
+        })
+    }
+
+    /// Writes the ` * Source:` (unless disabled), ` * IR fingerprint:` and
+    /// (with [`CGenOptions::regenerate_command`]) ` * DO NOT EDIT:` banner
+    /// lines.
+    fn write_origin(&self, out: &mut String) {
+        out.push_str(&self.origin);
+    }
+
+    fn write_prologue(&self, out: &mut String) {
+        write_user_template(out, self.prologue.as_deref());
+    }
+
+    fn write_epilogue(&self, out: &mut String) {
+        write_user_template(out, self.epilogue.as_deref());
+    }
+}
+
+fn write_user_template(out: &mut String, content: Option<&str>) {
+    if let Some(content) = content {
+        out.push_str(content);
+        if !content.ends_with('\n') {
+            out.push('\n');
+        }
+        out.push('\n');
+    }
+}
+
+/// Describes which template files `options` resolves to, e.g. for the CLI
+/// to report that a `--template-dir` override is in effect.
+pub fn describe_templates(options: &CGenOptions) -> Result<String, IdlError> {
+    let mut files = Vec::new();
+    for family in HELPER_FAMILIES {
+        files.push(format!("helpers_{}.h", family));
+        files.push(helper_template_file(family, Endian::Little));
+        files.push(helper_template_file(family, Endian::Big));
+    }
+    files.extend([PROLOGUE_FILE, EPILOGUE_FILE].map(String::from));
+    let files: Vec<&str> = files.iter().map(String::as_str).collect();
+    crate::describe_templates(TargetLanguage::C, &files, options.template_dir.as_deref())
+}
 
 /// Generates multiple C99 header files for server and clients.
 ///
@@ -96,508 +847,3078 @@ pub fn generate_multiple(
     messages: &[MessageDefinition],
     input_path: &Path,
     base_name: &str,
-) -> Result<Vec<OutputFile>> {
-    let helper_block = load_templates(TargetLanguage::C, TEMPLATE_FILES)?;
-    let name_ctx = NameContext::new(base_name);
-    let mut files = Vec::new();
-
-    let byteorder_content = generate_byteorder_header(input_path, &helper_block);
-    files.push(OutputFile {
-        filename: BYTEORDER_HEADER_FILENAME.to_string(),
-        content: byteorder_content,
-    });
+) -> Result<Vec<OutputFile>, IdlError> {
+    generate_multiple_with_options(
+        metadata,
+        messages,
+        input_path,
+        base_name,
+        &CGenOptions::default(),
+    )
+}
 
-    // Collect all unique client IDs
-    let client_ids: HashSet<i32> = messages
-        .iter()
-        .filter(|m| m.target_client_id > 0)
-        .map(|m| m.target_client_id)
+/// Same as [`generate_multiple`], honoring the given generation options.
+///
+/// With [`ImplStyle::Source`] every role header is accompanied by a `.c`
+/// file of the same stem holding the function bodies, and the byte order
+/// helpers are defined in `h6x_serial_byteorder.c`.
+pub fn generate_multiple_with_options(
+    metadata: &Metadata,
+    messages: &[MessageDefinition],
+    input_path: &Path,
+    base_name: &str,
+    options: &CGenOptions,
+) -> Result<Vec<OutputFile>, IdlError> {
+    check_nesting(messages)?;
+    let templates = Templates::load(options, metadata, messages, input_path)?;
+    let name_ctx = NameContext::new(base_name, options, metadata, messages);
+    let mut files = Vec::new();
+    // Every message once, in parallel; the files below only join the parts
+    let blocks: Vec<MessageBlocks> = messages
+        .par_iter()
+        .map(|msg| MessageBlocks::new(msg, &name_ctx, options))
         .collect();
 
-    // Generate types header (common definitions)
     let types_filename = format!("{}_types.h", base_name);
-    let types_content =
-        generate_types_header(metadata, messages, input_path, &types_filename, &name_ctx);
-    files.push(OutputFile {
-        filename: types_filename.clone(),
-        content: types_content,
-    });
-
-    // Generate server header
-    let server_filename = format!("{}_server.h", base_name);
-    let server_content = generate_header_for_role(&HeaderForRoleArgs {
+    let shared = SharedFiles {
         metadata,
         messages,
-        input_path,
-        filename: &server_filename,
-        types_header: &types_filename,
-        role: Role::Server,
-        client_common_header: None,
-        name_ctx: &name_ctx,
-    });
-    files.push(OutputFile {
-        filename: server_filename,
-        content: server_content,
-    });
+        blocks: &blocks,
+        templates: &templates,
+    };
+    let mut sources = Vec::new();
+    sources.extend(shared.push_common(&mut files, &types_filename, &name_ctx, options));
 
-    // Generate client common header (for target_client_id=-1 messages)
-    let client_common_filename = format!("{}_client_common.h", base_name);
-    let client_common_content = generate_header_for_role(&HeaderForRoleArgs {
-        metadata,
-        messages,
-        input_path,
-        filename: &client_common_filename,
-        types_header: &types_filename,
-        role: Role::ClientCommon,
-        client_common_header: None,
-        name_ctx: &name_ctx,
-    });
+    // Generate server, client common (target_client_id=-1) and per-client headers
+    let roles = split_roles(messages);
+    let mut role_headers = Vec::new();
+    let mut decoders = Vec::new();
+    for &role in &roles {
+        let filename = role.header_filename(base_name);
+        role_headers.push(filename.clone());
+        decoders.push((filename, role));
+        sources.extend(shared.push_role(&mut files, role, base_name, &types_filename, options));
+    }
+    let umbrella_filename = format!("{}.h", base_name);
     files.push(OutputFile {
-        filename: client_common_filename.clone(),
-        content: client_common_content,
+        content: generate_umbrella_header(
+            &roles,
+            base_name,
+            &umbrella_filename,
+            &types_filename,
+            &templates,
+        ),
+        filename: umbrella_filename,
     });
 
-    // Generate client headers for each unique client ID
-    for client_id in &client_ids {
-        let client_filename = format!("{}_client_{}.h", base_name, client_id);
-        let client_content = generate_header_for_role(&HeaderForRoleArgs {
+    if options.emit_tests {
+        // Server and client headers together provide both directions
+        files.push(OutputFile {
+            filename: format!("{}_tests.c", base_name),
+            content: self_test::generate_test_source(
+                messages,
+                &role_headers,
+                &name_ctx,
+                &templates,
+            ),
+        });
+    }
+    if options.emit_fuzz {
+        files.push(OutputFile {
+            filename: format!("{}_fuzz.c", base_name),
+            content: fuzz::generate_fuzz_source(
+                metadata,
+                messages,
+                &role_headers,
+                &name_ctx,
+                &templates,
+            ),
+        });
+    }
+    if options.emit_python {
+        files.push(OutputFile {
+            filename: format!("{}_ctypes.py", base_name),
+            content: ctypes::generate_ctypes_module(metadata, messages, &types_filename, &name_ctx),
+        });
+    }
+    if options.package == Some(Package::Arduino) {
+        arduino::package(
+            &mut files,
+            &mut sources,
             metadata,
             messages,
-            input_path,
-            filename: &client_filename,
-            types_header: &types_filename,
-            role: Role::Client(*client_id),
-            client_common_header: Some(&client_common_filename),
-            name_ctx: &name_ctx,
-        });
+            &name_ctx,
+            &role_headers,
+            |msg| {
+                decoders
+                    .iter()
+                    .find(|(_, role)| {
+                        matches!(
+                            role.function_mode(msg),
+                            Some(FunctionMode::DecodeOnly | FunctionMode::Both)
+                        )
+                    })
+                    .map(|(filename, _)| filename.as_str())
+            },
+        );
+    }
+    if let Some(cmake) = &options.cmake {
+        let content = cmake::generate_cmake_module(
+            &files,
+            &sources,
+            &name_ctx,
+            cmake,
+            &ir_fingerprint(metadata, messages),
+        );
         files.push(OutputFile {
-            filename: client_filename,
-            content: client_content,
+            filename: format!("{}.cmake", base_name),
+            content,
         });
     }
 
     Ok(files)
 }
 
-/// Role for which to generate the header.
-#[derive(Clone, Copy, Debug)]
-enum Role {
-    /// Server role: pub->encode, sub->decode
-    Server,
-    /// Client common role: only messages with target_client_id=-1
-    ClientCommon,
-    /// Client role with specific ID: pub->decode, sub->encode (only specific messages)
-    Client(i32),
-}
-
-/// Generates the types header containing common definitions.
-/// This includes:
-/// - Helper functions for serialization
-/// - Type definitions (structs)
-/// - Packet ID macros
-/// - Max length macros
-fn generate_types_header(
+/// Same as [`generate_multiple_with_options`], producing only the files of
+/// one role: its header (and `.c` file in source style), plus the types
+/// and byte order headers (and the byte order `.c` file in source style)
+/// unless [`CGenOptions::types_header`] names an existing one. The extras covering every role (self-test, fuzz harness,
+/// ctypes module, package and CMake module) are left out. The files are
+/// identical to their counterparts of a full generation, so build systems
+/// can regenerate each client separately.
+///
+/// Fails when no message targets the requested client.
+///
+/// # Example
+/// ```
+/// use std::path::Path;
+/// use h6xserial_idl::emit_c::{self, CGenOptions, Role};
+///
+/// let (metadata, messages) = h6xserial_idl::parse_str(
+///     r#"{ "packets": { "ping": { "packet_id": 1, "msg_type": "uint8", "target_client_id": 3 } } }"#,
+/// )
+/// .unwrap();
+/// let options = CGenOptions {
+///     types_header: Some("proto_types.h".to_string()),
+///     ..Default::default()
+/// };
+/// let files = emit_c::generate_for_role(
+///     &metadata,
+///     &messages,
+///     Path::new("proto.json"),
+///     "proto",
+///     &options,
+///     Role::Client(3),
+/// )
+/// .unwrap();
+/// let names: Vec<&str> = files.iter().map(|file| file.filename.as_str()).collect();
+/// assert_eq!(names, ["proto_client_3.h"]);
+/// ```
+pub fn generate_for_role(
     metadata: &Metadata,
     messages: &[MessageDefinition],
     input_path: &Path,
-    filename: &str,
-    name_ctx: &NameContext,
-) -> String {
-    let header_guard = header_guard_name_from_str(filename);
-
-    let mut out = String::new();
-    writeln!(&mut out, "/*").unwrap();
-    writeln!(&mut out, " * Auto-generated by h6xserial_idl.").unwrap();
-    writeln!(&mut out, " * Source: {}", input_path.display()).unwrap();
-    writeln!(&mut out, " * Common type definitions and helper functions").unwrap();
-    if let Some(version) = &metadata.version {
-        writeln!(&mut out, " * Protocol version: {}", version).unwrap();
-    }
-    if let Some(max_address) = metadata.max_address {
-        writeln!(&mut out, " * Max address: {}", max_address).unwrap();
+    base_name: &str,
+    options: &CGenOptions,
+    role: Role,
+) -> Result<Vec<OutputFile>, IdlError> {
+    check_nesting(messages)?;
+    if let Role::Client(id) = role {
+        check_client(messages, id)?;
     }
-    writeln!(&mut out, " */\n").unwrap();
-
-    writeln!(&mut out, "#ifndef {}", header_guard).unwrap();
-    writeln!(&mut out, "#define {}\n", header_guard).unwrap();
+    let templates = Templates::load(options, metadata, messages, input_path)?;
+    let name_ctx = NameContext::new(base_name, options, metadata, messages);
+    let blocks: Vec<MessageBlocks> = messages
+        .par_iter()
+        .map(|msg| MessageBlocks::new(msg, &name_ctx, options))
+        .collect();
+    let shared = SharedFiles {
+        metadata,
+        messages,
+        blocks: &blocks,
+        templates: &templates,
+    };
+    let mut files = Vec::new();
+    let types_filename = match &options.types_header {
+        Some(path) => path.clone(),
+        None => {
+            let filename = format!("{}_types.h", base_name);
+            shared.push_common(&mut files, &filename, &name_ctx, options);
+            filename
+        }
+    };
+    shared.push_role(&mut files, role, base_name, &types_filename, options);
+    Ok(files)
+}
 
-    out.push_str(
-        "#include <stdbool.h>\n#include <stddef.h>\n#include <stdint.h>\n#include <string.h>\n\n",
-    );
+/// Every client ID with messages of its own, in ascending order.
+pub fn client_ids(messages: &[MessageDefinition]) -> BTreeSet<i32> {
+    messages
+        .iter()
+        .filter(|m| m.target_client_id > 0)
+        .map(|m| m.target_client_id)
+        .collect()
+}
 
-    writeln!(&mut out, "#include \"{}\"\n", BYTEORDER_HEADER_FILENAME).unwrap();
-    out.push_str("#ifdef __cplusplus\nextern \"C\" {\n#endif\n\n");
+/// The roles of the headers of [`generate_multiple_with_options`]: the
+/// server, the common client header and every client of [`client_ids`].
+pub fn split_roles(messages: &[MessageDefinition]) -> Vec<Role> {
+    [Role::Server, Role::ClientCommon]
+        .into_iter()
+        .chain(client_ids(messages).into_iter().map(Role::Client))
+        .collect()
+}
 
-    // Generate type definitions only (no functions)
-    for msg in messages {
-        out.push('\n');
-        out.push_str(&generate_message_types_only(msg, name_ctx));
+/// Fails unless some message targets client `id`.
+pub(crate) fn check_client(messages: &[MessageDefinition], id: i32) -> Result<(), IdlError> {
+    let clients = client_ids(messages);
+    if clients.contains(&id) {
+        return Ok(());
     }
-
-    out.push_str("\n#ifdef __cplusplus\n}\n#endif\n\n");
-    writeln!(&mut out, "#endif /* {} */", header_guard).unwrap();
-
-    out
+    let known: Vec<String> = clients.iter().map(i32::to_string).collect();
+    Err(IdlError::invalid_value(
+        "/packets",
+        id,
+        format!(
+            "no message targets client {} (clients: {})",
+            id,
+            if known.is_empty() {
+                "none".to_string()
+            } else {
+                known.join(", ")
+            }
+        ),
+    ))
 }
 
-struct HeaderForRoleArgs<'a> {
+/// What the files of a split generation are built from.
+struct SharedFiles<'a> {
     metadata: &'a Metadata,
     messages: &'a [MessageDefinition],
-    input_path: &'a Path,
-    filename: &'a str,
-    types_header: &'a str,
-    role: Role,
-    client_common_header: Option<&'a str>,
-    name_ctx: &'a NameContext,
+    blocks: &'a [MessageBlocks],
+    templates: &'a Templates,
 }
 
-/// Generates a header file for a specific role (server or client).
-/// This header includes the types header and defines only the functions.
-fn generate_header_for_role(args: &HeaderForRoleArgs<'_>) -> String {
-    let header_guard = header_guard_name_from_str(args.filename);
-
-    let mut out = String::new();
-    writeln!(&mut out, "/*").unwrap();
-    writeln!(&mut out, " * Auto-generated by h6xserial_idl.").unwrap();
-    writeln!(&mut out, " * Source: {}", args.input_path.display()).unwrap();
-    match args.role {
-        Role::Server => writeln!(&mut out, " * Role: Server").unwrap(),
-        Role::ClientCommon => writeln!(&mut out, " * Role: Client (Common)").unwrap(),
-        Role::Client(id) => writeln!(&mut out, " * Role: Client (ID: {})", id).unwrap(),
-    }
-    if let Some(version) = &args.metadata.version {
-        writeln!(&mut out, " * Protocol version: {}", version).unwrap();
-    }
-    if let Some(max_address) = args.metadata.max_address {
-        writeln!(&mut out, " * Max address: {}", max_address).unwrap();
-    }
-    writeln!(&mut out, " */\n").unwrap();
-
-    writeln!(&mut out, "#ifndef {}", header_guard).unwrap();
-    writeln!(&mut out, "#define {}\n", header_guard).unwrap();
-
-    // Include the types header
-    writeln!(&mut out, "#include \"{}\"", args.types_header).unwrap();
-
-    // For specific client headers, include the common client header
-    if let Some(common_header) = args.client_common_header {
-        writeln!(&mut out, "#include \"{}\"", common_header).unwrap();
+impl SharedFiles<'_> {
+    /// Adds the byte order header and the types header (common definitions),
+    /// and in source style the byte order `.c` file, whose name it returns.
+    fn push_common(
+        &self,
+        files: &mut Vec<OutputFile>,
+        types_filename: &str,
+        name_ctx: &NameContext,
+        options: &CGenOptions,
+    ) -> Option<String> {
+        let helpers = match options.impl_style {
+            ImplStyle::Inline => None,
+            ImplStyle::Source => Some(split_helpers(
+                &self.templates.helpers,
+                options.dialect.inline_keyword(),
+            )),
+        };
+        files.push(OutputFile {
+            filename: BYTEORDER_HEADER_FILENAME.to_string(),
+            content: generate_byteorder_header(
+                self.templates,
+                helpers
+                    .as_ref()
+                    .map_or(&self.templates.helpers, |(declarations, _)| declarations),
+                options.dialect,
+            ),
+        });
+        let source = helpers.map(|(_, definitions)| {
+            files.push(OutputFile {
+                filename: BYTEORDER_SOURCE_FILENAME.to_string(),
+                content: generate_byteorder_source(self.templates, &definitions),
+            });
+            BYTEORDER_SOURCE_FILENAME.to_string()
+        });
+        files.push(OutputFile {
+            filename: types_filename.to_string(),
+            content: generate_types_header(
+                self.metadata,
+                self.messages,
+                self.blocks,
+                types_filename,
+                name_ctx,
+                options,
+                self.templates,
+            ),
+        });
+        source
     }
-    out.push('\n');
 
-    if emit_own_device_definitions(&mut out, args.metadata, args.role) {
-        out.push('\n');
+    /// Adds the header of `role`, and its `.c` file in source style.
+    /// Returns the name of the `.c` file.
+    fn push_role(
+        &self,
+        files: &mut Vec<OutputFile>,
+        role: Role,
+        base_name: &str,
+        types_filename: &str,
+        options: &CGenOptions,
+    ) -> Option<String> {
+        let filename = role.header_filename(base_name);
+        let client_common_filename = format!("{}_client_common.h", base_name);
+        let args = HeaderForRoleArgs {
+            metadata: self.metadata,
+            blocks: self.blocks,
+            filename: &filename,
+            types_header: types_filename,
+            role,
+            client_common_header: matches!(role, Role::Client(_))
+                .then_some(client_common_filename.as_str()),
+            templates: self.templates,
+        };
+        let content = generate_header_for_role(&args);
+        let source = (options.impl_style == ImplStyle::Source).then(|| OutputFile {
+            filename: source_filename_for(&filename),
+            content: generate_source_for_role(&args),
+        });
+        files.push(OutputFile { filename, content });
+        source.map(|source| {
+            let filename = source.filename.clone();
+            files.push(source);
+            filename
+        })
     }
+}
 
-    out.push_str("#ifdef __cplusplus\nextern \"C\" {\n#endif\n\n");
+/// Role for which to generate the header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+    /// Server role: pub->encode, sub->decode
+    Server,
+    /// Client common role: only messages with target_client_id=-1
+    ClientCommon,
+    /// Client role with specific ID: pub->decode, sub->encode (only specific messages)
+    Client(i32),
+}
 
-    for msg in args.messages {
-        // Determine if this message applies to the current role
-        let (applies, mode) = match args.role {
-            Role::Server => {
-                // Server: pub->encode, sub->decode
-                let mode = match msg.request_type {
-                    RequestType::Pub => FunctionMode::EncodeOnly,
-                    RequestType::Sub => FunctionMode::DecodeOnly,
+impl Role {
+    /// Returns whether `msg` belongs in this role's header and which functions it needs.
+    pub(crate) fn function_mode(self, msg: &MessageDefinition) -> Option<FunctionMode> {
+        match self {
+            // Server: pub->encode, sub->decode
+            Role::Server => Some(match msg.request_type {
+                RequestType::Pub => FunctionMode::EncodeOnly,
+                RequestType::Sub => FunctionMode::DecodeOnly,
+            }),
+            // ClientCommon: only messages with target_client_id == -1
+            // Client: only messages with specific target_client_id (NOT -1, those are in common)
+            Role::ClientCommon | Role::Client(_) => {
+                let applies = match self {
+                    Role::Client(client_id) => msg.target_client_id == client_id,
+                    _ => msg.target_client_id == -1,
                 };
-                (true, mode)
-            }
-            Role::ClientCommon => {
-                // ClientCommon: only messages with target_client_id == -1
-                let applies = msg.target_client_id == -1;
-                // Client: pub->decode, sub->encode (opposite of server)
-                let mode = match msg.request_type {
-                    RequestType::Pub => FunctionMode::DecodeOnly,
-                    RequestType::Sub => FunctionMode::EncodeOnly,
-                };
-                (applies, mode)
-            }
-            Role::Client(client_id) => {
-                // Client: only messages with specific target_client_id (NOT -1, those are in common)
-                let applies = msg.target_client_id == client_id;
                 // Client: pub->decode, sub->encode (opposite of server)
-                let mode = match msg.request_type {
+                applies.then_some(match msg.request_type {
                     RequestType::Pub => FunctionMode::DecodeOnly,
                     RequestType::Sub => FunctionMode::EncodeOnly,
-                };
-                (applies, mode)
+                })
             }
-        };
-
-        if applies {
-            out.push('\n');
-            out.push_str(&generate_message_functions_only(msg, mode, args.name_ctx));
         }
     }
+}
 
-    out.push_str("\n#ifdef __cplusplus\n}\n#endif\n\n");
-    writeln!(&mut out, "#endif /* {} */", header_guard).unwrap();
+impl Role {
+    /// The role whose header or `.c` file is named `filename` in the files
+    /// of [`generate_multiple_with_options`] for `base_name`.
+    pub(crate) fn of_file(
+        messages: &[MessageDefinition],
+        base_name: &str,
+        filename: &str,
+    ) -> Option<Role> {
+        split_roles(messages).into_iter().find(|role| {
+            let header = role.header_filename(base_name);
+            filename == header || filename == source_filename_for(&header)
+        })
+    }
 
-    out
-}
+    /// Macro that selects this role in the umbrella header `<base>.h`.
+    pub fn macro_name(self) -> String {
+        match self {
+            Role::Server => "H6XSERIAL_ROLE_SERVER".to_string(),
+            Role::ClientCommon => "H6XSERIAL_ROLE_CLIENT_COMMON".to_string(),
+            Role::Client(id) => format!("H6XSERIAL_ROLE_CLIENT_{}", id),
+        }
+    }
 
-/// Legacy generate function for backwards compatibility.
-/// Generates a single header with all encode/decode functions.
-pub fn generate(
-    metadata: &Metadata,
-    messages: &[MessageDefinition],
-    input_path: &Path,
-    output_path: &Path,
-) -> Result<String> {
-    let helper_block = load_templates(TargetLanguage::C, TEMPLATE_FILES)?;
-    let header_guard = header_guard_name(output_path);
-    let name_ctx = name_context_from_path(input_path);
+    /// File name of the header of this role.
+    fn header_filename(self, base_name: &str) -> String {
+        match self {
+            Role::Server => format!("{}_server.h", base_name),
+            Role::ClientCommon => format!("{}_client_common.h", base_name),
+            Role::Client(id) => format!("{}_client_{}.h", base_name, id),
+        }
+    }
 
-    let mut out = String::new();
-    writeln!(&mut out, "/*").unwrap();
-    writeln!(&mut out, " * Auto-generated by h6xserial_idl.").unwrap();
-    writeln!(&mut out, " * Source: {}", input_path.display()).unwrap();
-    if let Some(version) = &metadata.version {
-        writeln!(&mut out, " * Protocol version: {}", version).unwrap();
+    fn is_client(self) -> bool {
+        !matches!(self, Role::Server)
     }
-    if let Some(max_address) = metadata.max_address {
-        writeln!(&mut out, " * Max address: {}", max_address).unwrap();
+
+    /// The client header `msg` belongs to, if any.
+    fn client_of(msg: &MessageDefinition) -> Option<Role> {
+        match msg.target_client_id {
+            -1 => Some(Role::ClientCommon),
+            id if id > 0 => Some(Role::Client(id)),
+            _ => None,
+        }
     }
-    writeln!(&mut out, " */\n").unwrap();
+}
 
-    writeln!(&mut out, "#ifndef {}", header_guard).unwrap();
-    writeln!(&mut out, "#define {}\n", header_guard).unwrap();
+/// Bytes reserved on top of the message code of a generated file, for
+/// its banner, includes, guards and tables.
+const FILE_OVERHEAD_BYTES: usize = 4096;
 
-    out.push_str(
-        "#include <stdbool.h>\n#include <stddef.h>\n#include <stdint.h>\n#include <string.h>\n\n",
-    );
+/// Rough size of the code of one message, used to preallocate the files
+/// that are written in one pass.
+const MESSAGE_BYTES_ESTIMATE: usize = 2048;
 
-    out.push_str("#ifdef __cplusplus\nextern \"C\" {\n#endif\n\n");
-    out.push_str(&helper_block);
+/// The code of one message in a role header and, in source style, in the
+/// `.c` file next to it.
+struct RoleBlock {
+    role: Role,
+    header: String,
+    source: String,
+}
 
-    for msg in messages {
-        out.push('\n');
-        out.push_str(&generate_message_block_with_mode(
-            msg,
-            FunctionMode::Both,
-            &name_ctx,
-        ));
-    }
+/// The code of one message for [`generate_multiple_with_options`],
+/// generated once and then joined into every file that needs it.
+struct MessageBlocks {
+    /// Part of `_types.h`
+    types: String,
+    /// Part of the server header, then of the client header, if any
+    roles: Vec<RoleBlock>,
+}
 
-    out.push_str("\n#ifdef __cplusplus\n}\n#endif\n\n");
-    writeln!(&mut out, "#endif /* {} */", header_guard).unwrap();
+impl MessageBlocks {
+    fn new(msg: &MessageDefinition, name_ctx: &NameContext, options: &CGenOptions) -> Self {
+        let mut types = String::from("\n");
+        types.push_str(&generate_message_types_only(msg, name_ctx, options));
+        types.push_str(&generate_string_accessors(msg, name_ctx, FnEmit::Inline));
+
+        let emit = match options.impl_style {
+            ImplStyle::Inline => FnEmit::Inline,
+            ImplStyle::Source => FnEmit::Prototype,
+        };
+        let roles = std::iter::once(Role::Server)
+            .chain(Role::client_of(msg))
+            .filter_map(|role| {
+                let mode = role.function_mode(msg)?;
+                let source = match options.impl_style {
+                    ImplStyle::Inline => String::new(),
+                    ImplStyle::Source => generate_role_functions(
+                        msg,
+                        role,
+                        mode,
+                        name_ctx,
+                        options,
+                        FnEmit::Definition,
+                    ),
+                };
+                Some(RoleBlock {
+                    role,
+                    header: generate_role_functions(msg, role, mode, name_ctx, options, emit),
+                    source,
+                })
+            })
+            .collect();
+        MessageBlocks { types, roles }
+    }
 
-    Ok(out)
+    fn role(&self, role: Role) -> Option<&RoleBlock> {
+        self.roles.iter().find(|block| block.role == role)
+    }
 }
 
-fn generate_message_block_with_mode(
+/// The functions of `msg` in the header of `role`, or in its `.c` file.
+fn generate_role_functions(
     msg: &MessageDefinition,
+    role: Role,
     mode: FunctionMode,
     name_ctx: &NameContext,
+    options: &CGenOptions,
+    emit: FnEmit,
+) -> String {
+    let mut out = String::from("\n");
+    out.push_str(&generate_message_functions_only(
+        msg, mode, name_ctx, options, emit,
+    ));
+    if role.is_client() {
+        out.push_str(&generate_expected_response_fn(msg, name_ctx, emit));
+    }
+    if mode != FunctionMode::DecodeOnly {
+        out.push_str(&generate_encode_str_fn(msg, name_ctx, emit));
+    }
+    if mode == FunctionMode::EncodeOnly {
+        out.push_str(&generate_send_fn(msg, name_ctx, options, emit));
+    }
+    out
+}
+
+/// Warnings about `response` pairs that travel the same way: the server
+/// encodes `pub` messages and decodes `sub` ones, so a response normally
+/// has the other request type than its request.
+pub fn response_warnings(messages: &[MessageDefinition]) -> Vec<String> {
+    let mut warnings = Vec::new();
+    for msg in messages {
+        let Some(response) = msg
+            .response
+            .as_ref()
+            .and_then(|name| messages.iter().find(|other| other.name == *name))
+        else {
+            continue;
+        };
+        if response.request_type == msg.request_type {
+            warnings.push(format!(
+                "'{}' and its response '{}' are both {}; a response normally goes the other way",
+                msg.name,
+                response.name,
+                msg.request_type.name()
+            ));
+        }
+    }
+    warnings
+}
+
+/// The `<name>_expected_response_id()` function of a message with a
+/// `response`, or nothing.
+fn generate_expected_response_fn(
+    msg: &MessageDefinition,
+    name_ctx: &NameContext,
+    emit: FnEmit,
 ) -> String {
+    if msg.response.is_none() {
+        return String::new();
+    }
+    let signature = format!(
+        "{} {}_expected_response_id(void)",
+        name_ctx.packet_id_type(),
+        name_ctx.msg_fn_prefix(&msg.name)
+    );
+    let qualifiers = fn_qualifiers(msg, emit, name_ctx.dialect);
+    let doc = if name_ctx.doc_comments && emit != FnEmit::Definition {
+        doc_block(&[format!(
+            "@brief Packet ID of the response to '{}', {}_RESPONSE_PACKET_ID.",
+            msg.name,
+            msg_macro_prefix(name_ctx, msg)
+        )])
+    } else {
+        String::new()
+    };
+    if emit == FnEmit::Prototype {
+        return format!("{}{}{};\n\n", doc, qualifiers, signature);
+    }
+    format!(
+        "{}{}{} {{\n    return {}_RESPONSE_PACKET_ID;\n}}\n\n",
+        doc,
+        qualifiers,
+        signature,
+        msg_macro_prefix(name_ctx, msg)
+    )
+}
+
+/// The `<prefix>_response_packet_id_for()` lookup from request to
+/// response packet ID, when any message has a `response`.
+fn generate_response_table(messages: &[MessageDefinition], name_ctx: &NameContext) -> String {
+    if messages.iter().all(|msg| msg.response.is_none()) {
+        return String::new();
+    }
     let mut out = String::new();
-    if let Some(desc) = &msg.description {
-        writeln!(&mut out, "/* {} */", desc).unwrap();
+    if name_ctx.doc_comments {
+        out.push_str(
+            "/** @brief Packet ID of the response to @p request_id, or -1 when it has none. */\n",
+        );
+    } else {
+        out.push_str("/* Packet ID of the response to request_id, or -1 when it has none */\n");
     }
-    let macro_prefix = msg_macro_prefix(name_ctx, msg);
     writeln!(
-        &mut out,
-        "#define {}_PACKET_ID {}",
-        macro_prefix, msg.packet_id
+        out,
+        "{}int {}_response_packet_id_for({} request_id) {{",
+        name_ctx.dialect.static_inline(),
+        name_ctx.msg_prefix,
+        name_ctx.packet_id_type()
     )
     .unwrap();
-
-    match &msg.body {
-        MessageBody::Array(spec) => {
+    out.push_str("    switch (request_id) {\n");
+    for msg in messages.iter().filter(|msg| msg.response.is_some()) {
+        let macro_prefix = msg_macro_prefix(name_ctx, msg);
+        writeln!(out, "    case {}_PACKET_ID:", macro_prefix).unwrap();
+        if name_ctx.misra {
             writeln!(
-                &mut out,
-                "#define {}_MAX_LENGTH {}",
-                macro_prefix, spec.max_length
+                out,
+                "        return (int){}_RESPONSE_PACKET_ID;",
+                macro_prefix
             )
             .unwrap();
-            if let Some(sector) = spec.sector_bytes {
-                writeln!(&mut out, "#define {}_SECTOR_BYTES {}", macro_prefix, sector).unwrap();
-            }
-            out.push('\n');
-            out.push_str(&generate_array_block(msg, spec, mode, name_ctx));
-        }
-        MessageBody::Scalar(spec) => {
-            out.push('\n');
-            out.push_str(&generate_scalar_block(msg, spec, mode, name_ctx));
-        }
-        MessageBody::Struct(spec) => {
-            out.push('\n');
-            out.push_str(&generate_struct_block(msg, spec, mode, name_ctx));
+        } else {
+            writeln!(out, "        return {}_RESPONSE_PACKET_ID;", macro_prefix).unwrap();
         }
     }
-
+    out.push_str("    default:\n        return -1;\n    }\n}\n");
     out
 }
 
-/// Generates only type definitions and macros for a message (for _types.h)
-fn generate_message_types_only(msg: &MessageDefinition, name_ctx: &NameContext) -> String {
+/// Default of [`CGenOptions::send_stack_limit`] for `--emit-send`.
+pub const DEFAULT_SEND_STACK_LIMIT: usize = 256;
+
+/// Declares the transmit callback taken by the send stubs, once per
+/// helper prefix.
+fn write_send_declarations(out: &mut String, options: &CGenOptions) {
+    if options.send_stack_limit.is_none() {
+        return;
+    }
+    write_tx_fn_typedef(out, options.helper_prefix());
+}
+
+/// Writes the guarded `<helpers>_tx_fn` typedef.
+fn write_tx_fn_typedef(out: &mut String, helpers: &str) {
+    let guard = format!("{}_TX_FN_DEFINED", helpers.to_ascii_uppercase());
+    writeln!(out, "#ifndef {}", guard).unwrap();
+    writeln!(out, "#define {}", guard).unwrap();
+    out.push_str("/* Transmits len bytes of data; returns false on failure */\n");
+    writeln!(
+        out,
+        "typedef bool (*{}_tx_fn)(const uint8_t *data, size_t len, void *ctx);",
+        helpers
+    )
+    .unwrap();
+    writeln!(out, "#endif\n").unwrap();
+}
+
+/// The `<prefix>_send_<name>()` stub of a message the role encodes, or
+/// nothing without [`CGenOptions::send_stack_limit`]. It encodes into a
+/// `MAX_SIZE` stack buffer, or into the caller's buffer for messages above
+/// the limit, and hands the payload to the transmit callback.
+fn generate_send_fn(
+    msg: &MessageDefinition,
+    name_ctx: &NameContext,
+    options: &CGenOptions,
+    emit: FnEmit,
+) -> String {
+    let Some(stack_limit) = options.send_stack_limit else {
+        return String::new();
+    };
+    let helpers = options.helper_prefix();
+    let max_size = message_body_max_size(&msg.body);
+    let on_stack = max_size <= stack_limit && max_size > 0;
+    let buffer_params = if on_stack {
+        ""
+    } else {
+        "uint8_t *buf, const size_t buf_len, "
+    };
+    let signature = format!(
+        "bool {}_send_{}(const {} *msg, {}{}_tx_fn tx, void *ctx)",
+        helpers,
+        to_snake_case(&msg.name),
+        type_name(msg, name_ctx),
+        buffer_params,
+        helpers
+    );
+    let qualifiers = fn_qualifiers(msg, emit, name_ctx.dialect);
+    if emit == FnEmit::Prototype {
+        return format!("{}{};\n\n", qualifiers, signature);
+    }
+
     let mut out = String::new();
-    if let Some(desc) = &msg.description {
-        writeln!(&mut out, "/* {} */", desc).unwrap();
+    if msg.deprecated.is_some() {
+        out.push_str("H6XSERIAL_ALLOW_DEPRECATED_BEGIN\n");
     }
-    let macro_prefix = msg_macro_prefix(name_ctx, msg);
+    writeln!(out, "{}{} {{", qualifiers, signature).unwrap();
+    if on_stack {
+        writeln!(
+            out,
+            "    uint8_t buf[{}_MAX_SIZE];",
+            msg_macro_prefix(name_ctx, msg)
+        )
+        .unwrap();
+    }
+    let buf_len = if on_stack { "sizeof(buf)" } else { "buf_len" };
     writeln!(
-        &mut out,
-        "#define {}_PACKET_ID {}",
-        macro_prefix, msg.packet_id
+        out,
+        "    const size_t len = {}(msg, buf, {});",
+        encode_fn_name(msg, name_ctx),
+        buf_len
     )
     .unwrap();
+    // Encoders return 0 on failure, which is also the length of an empty
+    // payload when every array of the message may be empty
+    let failed = if message_body_min_size(&msg.body) > 0 {
+        "len == 0".to_string()
+    } else {
+        let mut lengths = Vec::new();
+        match &msg.body {
+            MessageBody::Array(_) => lengths.push("msg->length".to_string()),
+            MessageBody::Struct(spec) => collect_length_accessors(spec, "msg->", &mut lengths),
+            // The tag is never empty
+            MessageBody::Scalar(_) | MessageBody::Union(_) => {}
+        }
+        let nonempty: Vec<String> = lengths
+            .iter()
+            .map(|length| format!("{} != 0", length))
+            .collect();
+        format!("len == 0 && (!msg || {})", nonempty.join(" || "))
+    };
+    writeln!(out, "    if ({}) {{\n        return false;\n    }}", failed).unwrap();
+    out.push_str("    return tx(buf, len, ctx);\n}\n");
+    if msg.deprecated.is_some() {
+        out.push_str("H6XSERIAL_ALLOW_DEPRECATED_END\n");
+    }
+    out.push('\n');
+    out
+}
+
+/// Length members of the variable-length arrays of `spec`, including
+/// those of nested structs.
+fn collect_length_accessors(spec: &StructSpec, accessor: &str, lengths: &mut Vec<String>) {
+    for field in &spec.fields {
+        let member = format!("{}{}", accessor, to_snake_case(&field.name));
+        match &field.field_type {
+            StructFieldType::Array(arr) if !arr.fixed => lengths.push(format!("{}_length", member)),
+            StructFieldType::Primitive(_)
+            | StructFieldType::Array(_)
+            | StructFieldType::Bytes(_) => {}
+            StructFieldType::Nested(nested) => {
+                collect_length_accessors(nested, &format!("{}.", member), lengths)
+            }
+        }
+    }
+}
+
+/// A `string_semantics` array: its C lvalue through `msg`, the lvalue of
+/// its length member, and the macro of its maximum length.
+struct StringArray {
+    data: String,
+    length: String,
+    max_macro: String,
+}
+
+/// Collects the `string_semantics` arrays of a message body, including
+/// those of nested structs. Unions have none, as only the variant of the
+/// tag may be touched.
+fn collect_string_arrays(msg: &MessageDefinition, name_ctx: &NameContext) -> Vec<StringArray> {
+    fn visit(
+        spec: &StructSpec,
+        accessor: &str,
+        macro_prefix: &str,
+        helpers: &str,
+        out: &mut Vec<StringArray>,
+    ) {
+        for field in &spec.fields {
+            let member = format!("{}{}", accessor, to_snake_case(&field.name));
+            let field_macro = format!("{}_{}", macro_prefix, to_macro_ident(&field.name));
+            match &field.field_type {
+                StructFieldType::Array(arr) if arr.string_semantics => out.push(StringArray {
+                    length: format!("{}_length", member),
+                    data: member,
+                    max_macro: format!("{}_MAX_LENGTH", field_macro),
+                }),
+                StructFieldType::Nested(nested) => visit(
+                    nested,
+                    &format!("{}.", member),
+                    &fields_macro_prefix(helpers, nested, field_macro),
+                    helpers,
+                    out,
+                ),
+                StructFieldType::Primitive(_)
+                | StructFieldType::Array(_)
+                | StructFieldType::Bytes(_) => {}
+            }
+        }
+    }
 
+    let mut out = Vec::new();
     match &msg.body {
-        MessageBody::Array(spec) => {
+        MessageBody::Array(spec) if spec.string_semantics => out.push(StringArray {
+            data: "msg->data".to_string(),
+            length: "msg->length".to_string(),
+            max_macro: format!("{}_MAX_LENGTH", msg_macro_prefix(name_ctx, msg)),
+        }),
+        MessageBody::Struct(spec) => visit(
+            spec,
+            "msg->",
+            &fields_macro_prefix(&name_ctx.helpers, spec, msg_macro_prefix(name_ctx, msg)),
+            &name_ctx.helpers,
+            &mut out,
+        ),
+        MessageBody::Scalar(_) | MessageBody::Array(_) | MessageBody::Union(_) => {}
+    }
+    out
+}
+
+/// Whether [`collect_string_arrays`] finds any array in `body`, so the
+/// message gets an `_encode_str()` function.
+fn has_string_semantics(body: &MessageBody) -> bool {
+    fn visit(spec: &StructSpec) -> bool {
+        spec.fields.iter().any(|field| match &field.field_type {
+            StructFieldType::Array(arr) => arr.string_semantics,
+            StructFieldType::Nested(nested) => visit(nested),
+            StructFieldType::Primitive(_) | StructFieldType::Bytes(_) => false,
+        })
+    }
+
+    match body {
+        MessageBody::Array(spec) => spec.string_semantics,
+        MessageBody::Struct(spec) => visit(spec),
+        MessageBody::Scalar(_) | MessageBody::Union(_) => false,
+    }
+}
+
+/// The `<name>_encode_str()` variant of the encoder for messages with
+/// `string_semantics` arrays, which sets their lengths from the text
+/// first.
+fn generate_encode_str_fn(msg: &MessageDefinition, name_ctx: &NameContext, emit: FnEmit) -> String {
+    let strings = collect_string_arrays(msg, name_ctx);
+    if strings.is_empty() {
+        return String::new();
+    }
+    let encode_fn = encode_fn_name(msg, name_ctx);
+    let signature = format!(
+        "size_t {}_str({} *msg, uint8_t *out_buf, const size_t out_len)",
+        encode_fn,
+        type_name(msg, name_ctx)
+    );
+    let qualifiers = fn_qualifiers(msg, emit, name_ctx.dialect);
+    let mut out = String::new();
+    if emit != FnEmit::Definition {
+        if name_ctx.doc_comments {
+            out.push_str(&doc_block(&[
+                format!(
+                    "@brief Sets the length of each string of @p msg from its text, up to the first '\\0' or its maximum length, then encodes it like {}().",
+                    encode_fn
+                ),
+                "@return Number of bytes written, or 0 on failure.".to_string(),
+            ]));
+        } else {
             writeln!(
-                &mut out,
-                "#define {}_MAX_LENGTH {}",
-                macro_prefix, spec.max_length
+                out,
+                "/* Sets the length of each string from its text, like strnlen(), then encodes like {}() */",
+                encode_fn
+            )
+            .unwrap();
+        }
+    }
+    if emit == FnEmit::Prototype {
+        writeln!(out, "{}{};\n", qualifiers, signature).unwrap();
+        return out;
+    }
+
+    let zero = name_ctx.uint(0);
+    if msg.deprecated.is_some() {
+        out.push_str("H6XSERIAL_ALLOW_DEPRECATED_BEGIN\n");
+    }
+    writeln!(out, "{}{} {{", qualifiers, signature).unwrap();
+    out.push_str("    size_t n;\n");
+    name_ctx.write_null_guard(&mut out, &["msg"], &zero);
+    for string in &strings {
+        writeln!(
+            out,
+            "    n = {zero};\n    while ((n < {max}) && ({data}[n] != '\\0')) {{\n        ++n;\n    }}\n    {length} = n;",
+            zero = zero,
+            max = string.max_macro,
+            data = string.data,
+            length = string.length
+        )
+        .unwrap();
+    }
+    writeln!(out, "    return {}(msg, out_buf, out_len);\n}}", encode_fn).unwrap();
+    if msg.deprecated.is_some() {
+        out.push_str("H6XSERIAL_ALLOW_DEPRECATED_END\n");
+    }
+    out.push('\n');
+    out
+}
+
+/// Emits the active payload limit so firmware can size its buffers.
+/// Writes [`DEPRECATED_MACRO`] when at least one message is deprecated,
+/// and [`ALLOW_DEPRECATED_MACROS`] when one of them has functions calling
+/// its encoder or decoder.
+fn write_deprecated_macro(out: &mut String, messages: &[MessageDefinition], options: &CGenOptions) {
+    if messages.iter().any(|msg| msg.deprecated.is_some()) {
+        out.push_str(
+            "/* Marks deprecated messages (define H6XSERIAL_NO_DEPRECATED to silence) */\n",
+        );
+        out.push_str(DEPRECATED_MACRO);
+        out.push('\n');
+    }
+    if messages.iter().any(|msg| {
+        msg.deprecated.is_some()
+            && (options.send_stack_limit.is_some()
+                || msg.msg_version.is_some()
+                || has_string_semantics(&msg.body))
+    }) {
+        out.push_str(ALLOW_DEPRECATED_MACROS);
+        out.push('\n');
+    }
+}
+
+/// Lets generated test programs call the functions of deprecated messages
+/// without warnings; written before the includes.
+fn write_deprecated_opt_out(out: &mut String, messages: &[MessageDefinition]) {
+    if messages.iter().any(|msg| msg.deprecated.is_some()) {
+        out.push_str(
+            "/* Deprecated messages are exercised too */\n#define H6XSERIAL_NO_DEPRECATED\n\n",
+        );
+    }
+}
+
+fn write_payload_limit(
+    out: &mut String,
+    metadata: &Metadata,
+    messages: &[MessageDefinition],
+    name_ctx: &NameContext,
+) {
+    if name_ctx.misra {
+        out.push_str(MISRA_DEVIATIONS);
+    }
+    out.push_str("/* Payload size limit of the transport, in bytes */\n");
+    writeln!(
+        out,
+        "#define H6XSERIAL_MAX_PAYLOAD_BYTES {}\n",
+        name_ctx.uint(metadata.max_payload_bytes)
+    )
+    .unwrap();
+    out.push_str("/* Width of the packet ID field, in bits */\n");
+    writeln!(
+        out,
+        "#define H6XSERIAL_PACKET_ID_BITS {}\n",
+        name_ctx.uint(metadata.packet_id_width)
+    )
+    .unwrap();
+    // Shorter payloads match no message and can be dropped before decoding
+    let min_payload = messages
+        .iter()
+        .map(|msg| message_body_min_size(&msg.body))
+        .min()
+        .unwrap_or(0);
+    out.push_str("/* Smallest payload of any message, in bytes */\n");
+    writeln!(
+        out,
+        "#define H6XSERIAL_MIN_PAYLOAD_SIZE {}\n",
+        name_ctx.uint(min_payload)
+    )
+    .unwrap();
+}
+
+/// Comment block of `--misra` headers listing the MISRA C:2012 guidelines
+/// the generated code knowingly departs from.
+const MISRA_DEVIATIONS: &str = "/*
+ * MISRA C:2012 deviations of the generated code:
+ *
+ * D1  Rules 10.3, 10.4 and 10.8: signed and character values are converted
+ *     to and from their unsigned wire form with explicit casts, relying on
+ *     two's complement representation. Marked with deviation D1.
+ * D2  Rule 21.15 and Dir 4.6: the float helpers copy a float into an
+ *     integer of the same size with memcpy; the size is checked at compile
+ *     time.
+ * D3  Rule 15.5: functions return early on invalid arguments.
+ * D4  Dir 4.9 and Rule 20.10: the compile-time checks and feature tests are
+ *     function-like macros and paste tokens with ##.
+ * D5  Rule 1.2: compiler extensions (inline keywords, byte order macros)
+ *     are only used behind feature checks.
+ * D6  Dir 4.6: the scaled accessors take and return double, and the
+ *     response lookup returns int, with -1 for no response.
+ */
+
+";
+
+/// Emits the protocol constants as `H6XSERIAL_CONST_<NAME>` macros.
+fn write_constants(out: &mut String, metadata: &Metadata) {
+    if metadata.constants.is_empty() {
+        return;
+    }
+    out.push_str("/* Protocol constants */\n");
+    for (name, value) in &metadata.constants {
+        writeln!(
+            out,
+            "#define {} {}",
+            const_macro_name(name),
+            const_literal(value)
+        )
+        .unwrap();
+    }
+    out.push('\n');
+}
+
+/// C literal of a constant. Negative numbers are parenthesized so the macro
+/// expands safely inside expressions.
+fn const_literal(value: &ConstValue) -> String {
+    match value {
+        // -9223372036854775808 would be the negation of an out of range literal
+        ConstValue::Int(i64::MIN) => "(-9223372036854775807LL - 1)".to_string(),
+        ConstValue::Int(value) if *value < 0 => format!("({})", value),
+        ConstValue::Int(value) => value.to_string(),
+        ConstValue::Float(value) if *value < 0.0 => format!("({:?})", value),
+        ConstValue::Float(value) => format!("{:?}", value),
+        ConstValue::Str(value) => c_string_literal(value),
+    }
+}
+
+/// Quotes `text` as a C string literal. Bytes outside printable ASCII are
+/// written as three-digit octal escapes, which cannot absorb the following
+/// characters the way hex escapes do.
+fn c_string_literal(text: &str) -> String {
+    let mut out = String::from("\"");
+    for byte in text.bytes() {
+        match byte {
+            b'"' => out.push_str("\\\""),
+            b'\\' => out.push_str("\\\\"),
+            b'\n' => out.push_str("\\n"),
+            b'\t' => out.push_str("\\t"),
+            b'\r' => out.push_str("\\r"),
+            // "??" could start a trigraph
+            b'?' => out.push_str("\\?"),
+            0x20..=0x7e => out.push(byte as char),
+            _ => write!(out, "\\{:03o}", byte).unwrap(),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Generates the types header containing common definitions.
+/// This includes:
+/// - Helper functions for serialization (inline style only)
+/// - Type definitions (structs)
+/// - Packet ID macros
+/// - Max length macros
+fn generate_types_header(
+    metadata: &Metadata,
+    messages: &[MessageDefinition],
+    blocks: &[MessageBlocks],
+    filename: &str,
+    name_ctx: &NameContext,
+    options: &CGenOptions,
+    templates: &Templates,
+) -> String {
+    let header_guard = header_guard_name_from_str(filename);
+
+    let mut out = String::with_capacity(
+        FILE_OVERHEAD_BYTES
+            + templates.helpers.len()
+            + blocks.iter().map(|block| block.types.len()).sum::<usize>(),
+    );
+    writeln!(&mut out, "/*").unwrap();
+    writeln!(&mut out, " * Auto-generated by {}.", GENERATOR).unwrap();
+    templates.write_origin(&mut out);
+    writeln!(&mut out, " * Common type definitions and helper functions").unwrap();
+    if let Some(version) = &metadata.version {
+        writeln!(&mut out, " * Protocol version: {}", version).unwrap();
+    }
+    if let Some(max_address) = metadata.max_address {
+        writeln!(&mut out, " * Max address: {}", max_address).unwrap();
+    }
+    if let Some(endian) = metadata.default_endianness {
+        writeln!(&mut out, " * Default endianness: {}", endian).unwrap();
+    }
+    writeln!(&mut out, " */\n").unwrap();
+
+    writeln!(&mut out, "#ifndef {}", header_guard).unwrap();
+    writeln!(&mut out, "#define {}\n", header_guard).unwrap();
+
+    write_standard_includes(&mut out, name_ctx.dialect);
+
+    // In source style the byte order helpers are only needed by the .c files
+    if options.impl_style == ImplStyle::Inline {
+        writeln!(&mut out, "#include \"{}\"\n", BYTEORDER_HEADER_FILENAME).unwrap();
+    }
+    templates.write_prologue(&mut out);
+    out.push_str("#ifdef __cplusplus\nextern \"C\" {\n#endif\n\n");
+    write_payload_limit(&mut out, metadata, messages, name_ctx);
+    write_constants(&mut out, metadata);
+    write_deprecated_macro(&mut out, messages, options);
+    write_send_declarations(&mut out, options);
+
+    if name_ctx.packed {
+        out.push_str(PACKED_MACRO);
+        out.push('\n');
+    }
+    write_timestamp_typedefs(&mut out, metadata, messages, name_ctx);
+    out.push_str(&generate_shared_types(metadata, messages, name_ctx));
+
+    // Generate type definitions only (no functions)
+    for block in blocks {
+        out.push_str(&block.types);
+    }
+    out.push_str(&generate_response_table(messages, name_ctx));
+    if name_ctx.packed {
+        out.push_str(PACKED_END);
+    }
+
+    out.push('\n');
+    out.push_str(&generate_static_asserts(messages, name_ctx));
+
+    out.push_str("\n#ifdef __cplusplus\n}\n#endif\n\n");
+    templates.write_epilogue(&mut out);
+    writeln!(&mut out, "#endif /* {} */", header_guard).unwrap();
+
+    out
+}
+
+struct HeaderForRoleArgs<'a> {
+    metadata: &'a Metadata,
+    blocks: &'a [MessageBlocks],
+    filename: &'a str,
+    types_header: &'a str,
+    role: Role,
+    client_common_header: Option<&'a str>,
+    templates: &'a Templates,
+}
+
+impl HeaderForRoleArgs<'_> {
+    /// The code of the messages in this role's files, in message order.
+    fn parts(&self) -> impl Iterator<Item = &RoleBlock> {
+        self.blocks.iter().filter_map(|block| block.role(self.role))
+    }
+}
+
+fn write_role_banner(out: &mut String, args: &HeaderForRoleArgs<'_>) {
+    writeln!(out, "/*").unwrap();
+    writeln!(out, " * Auto-generated by {}.", GENERATOR).unwrap();
+    args.templates.write_origin(out);
+    match args.role {
+        Role::Server => writeln!(out, " * Role: Server").unwrap(),
+        Role::ClientCommon => writeln!(out, " * Role: Client (Common)").unwrap(),
+        Role::Client(id) => writeln!(out, " * Role: Client (ID: {})", id).unwrap(),
+    }
+    if let Some(version) = &args.metadata.version {
+        writeln!(out, " * Protocol version: {}", version).unwrap();
+    }
+    if let Some(max_address) = args.metadata.max_address {
+        writeln!(out, " * Max address: {}", max_address).unwrap();
+    }
+    if let Some(endian) = args.metadata.default_endianness {
+        writeln!(out, " * Default endianness: {}", endian).unwrap();
+    }
+    writeln!(out, " */\n").unwrap();
+}
+
+/// Generates a header file for a specific role (server or client).
+/// This header includes the types header and defines only the functions
+/// (or declares them, in source style).
+fn generate_header_for_role(args: &HeaderForRoleArgs<'_>) -> String {
+    let header_guard = header_guard_name_from_str(args.filename);
+
+    let mut out = String::with_capacity(
+        FILE_OVERHEAD_BYTES + args.parts().map(|block| block.header.len()).sum::<usize>(),
+    );
+    write_role_banner(&mut out, args);
+
+    writeln!(&mut out, "#ifndef {}", header_guard).unwrap();
+    writeln!(&mut out, "#define {}\n", header_guard).unwrap();
+
+    // Include the types header
+    writeln!(&mut out, "#include \"{}\"", args.types_header).unwrap();
+
+    // For specific client headers, include the common client header
+    if let Some(common_header) = args.client_common_header {
+        writeln!(&mut out, "#include \"{}\"", common_header).unwrap();
+    }
+    out.push('\n');
+    args.templates.write_prologue(&mut out);
+
+    if emit_own_device_definitions(&mut out, args.metadata, args.role) {
+        out.push('\n');
+    }
+
+    out.push_str("#ifdef __cplusplus\nextern \"C\" {\n#endif\n\n");
+
+    for block in args.parts() {
+        out.push_str(&block.header);
+    }
+
+    out.push_str("\n#ifdef __cplusplus\n}\n#endif\n\n");
+    args.templates.write_epilogue(&mut out);
+    writeln!(&mut out, "#endif /* {} */", header_guard).unwrap();
+
+    out
+}
+
+/// Generates `<base>.h`, which includes the types header and the header of
+/// the role whose macro (see [`Role::macro_name`]) is defined, so firmware
+/// needs a single include however the set of role headers changes.
+fn generate_umbrella_header(
+    roles: &[Role],
+    base_name: &str,
+    filename: &str,
+    types_filename: &str,
+    templates: &Templates,
+) -> String {
+    let header_guard = header_guard_name_from_str(filename);
+    let macros: Vec<String> = roles.iter().map(|role| role.macro_name()).collect();
+    let mut out = String::new();
+    writeln!(out, "/*").unwrap();
+    writeln!(out, " * Auto-generated by {}.", GENERATOR).unwrap();
+    templates.write_origin(&mut out);
+    writeln!(
+        out,
+        " * Umbrella header: define one of these macros before including it"
+    )
+    .unwrap();
+    for name in &macros {
+        writeln!(out, " *   {}", name).unwrap();
+    }
+    writeln!(out, " */\n").unwrap();
+
+    writeln!(out, "#ifndef {}", header_guard).unwrap();
+    writeln!(out, "#define {}\n", header_guard).unwrap();
+    writeln!(out, "#include \"{}\"\n", types_filename).unwrap();
+    templates.write_prologue(&mut out);
+    for (i, (role, name)) in roles.iter().zip(&macros).enumerate() {
+        let directive = if i == 0 { "#if" } else { "#elif" };
+        writeln!(out, "{} defined({})", directive, name).unwrap();
+        writeln!(out, "#include \"{}\"", role.header_filename(base_name)).unwrap();
+    }
+    writeln!(out, "#else").unwrap();
+    writeln!(
+        out,
+        "#error \"Define one of {} before including {}\"",
+        macros.join(", "),
+        filename
+    )
+    .unwrap();
+    writeln!(out, "#endif\n").unwrap();
+    templates.write_epilogue(&mut out);
+    writeln!(out, "#endif /* {} */", header_guard).unwrap();
+    out
+}
+
+/// Generates the `.c` implementation file that pairs with a role header in
+/// source style.
+fn generate_source_for_role(args: &HeaderForRoleArgs<'_>) -> String {
+    let mut out = String::with_capacity(
+        FILE_OVERHEAD_BYTES + args.parts().map(|block| block.source.len()).sum::<usize>(),
+    );
+    write_role_banner(&mut out, args);
+
+    writeln!(&mut out, "#include \"{}\"", args.filename).unwrap();
+    writeln!(&mut out, "#include \"{}\"", BYTEORDER_HEADER_FILENAME).unwrap();
+
+    for block in args.parts() {
+        out.push_str(&block.source);
+    }
+
+    out
+}
+
+/// Generates `<base>_server.example.c`, a server starting point to copy
+/// and edit: a handler per message the server decodes and a
+/// `<prefix>_server_poll()` dispatch loop, built against the files of
+/// [`generate_multiple_with_options`]. Unlike those, the file is meant to
+/// be written once and never overwritten.
+pub fn generate_server_skeleton(
+    metadata: &Metadata,
+    messages: &[MessageDefinition],
+    base_name: &str,
+    options: &CGenOptions,
+) -> OutputFile {
+    let name_ctx = NameContext::new(base_name, options, metadata, messages);
+    OutputFile {
+        filename: format!("{}_server{}", base_name, SKELETON_SUFFIX),
+        content: skeleton::generate_skeleton_source(
+            metadata,
+            messages,
+            &format!("{}_server.h", base_name),
+            &name_ctx,
+        ),
+    }
+}
+
+/// File name ending of [`generate_server_skeleton`].
+pub const SKELETON_SUFFIX: &str = ".example.c";
+
+/// Same as [`generate_multiple_with_options`], streaming each file into a
+/// writer instead of returning the contents.
+///
+/// `open` is called with `output_dir` joined with each file name and
+/// returns the writer for it (e.g. `File::create`). Returns the paths
+/// written, in generation order.
+///
+/// # Example
+/// ```
+/// use std::path::Path;
+/// use h6xserial_idl::emit_c::{self, CGenOptions};
+///
+/// let (metadata, messages) = h6xserial_idl::parse_str(
+///     r#"{ "packets": { "ping": { "packet_id": 1, "msg_type": "uint8" } } }"#,
+/// )
+/// .unwrap();
+/// let paths = emit_c::generate_multiple_to_writers(
+///     &metadata,
+///     &messages,
+///     Path::new("proto.json"),
+///     "proto",
+///     &CGenOptions::default(),
+///     Path::new("out"),
+///     |_| Ok(Vec::new()),
+/// )
+/// .unwrap();
+/// assert!(paths.contains(&Path::new("out").join("proto_types.h")));
+/// ```
+pub fn generate_multiple_to_writers<W, F>(
+    metadata: &Metadata,
+    messages: &[MessageDefinition],
+    input_path: &Path,
+    base_name: &str,
+    options: &CGenOptions,
+    output_dir: &Path,
+    mut open: F,
+) -> Result<Vec<PathBuf>, IdlError>
+where
+    W: io::Write,
+    F: FnMut(&Path) -> io::Result<W>,
+{
+    let files = generate_multiple_with_options(metadata, messages, input_path, base_name, options)?;
+    let mut written = Vec::with_capacity(files.len());
+    for file in &files {
+        let path = output_dir.join(&file.filename);
+        open(&path)
+            .and_then(|mut writer| {
+                writer.write_all(file.content.as_bytes())?;
+                writer.flush()
+            })
+            .map_err(|e| IdlError::write(&path, e))?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// Same as [`generate`], writing the header into `writer`. `output_path`
+/// names the header, as in [`generate`], and appears in write errors.
+///
+/// # Example
+/// ```
+/// use std::path::Path;
+/// use h6xserial_idl::emit_c;
+///
+/// let (metadata, messages) = h6xserial_idl::parse_str(
+///     r#"{ "packets": { "ping": { "packet_id": 1, "msg_type": "uint8" } } }"#,
+/// )
+/// .unwrap();
+/// let mut buf = Vec::new();
+/// emit_c::generate_to_writer(
+///     &mut buf,
+///     &metadata,
+///     &messages,
+///     Path::new("proto.json"),
+///     Path::new("proto.h"),
+/// )
+/// .unwrap();
+/// assert!(String::from_utf8(buf).unwrap().contains("PROTO_MSG_PING"));
+/// ```
+pub fn generate_to_writer<W: io::Write>(
+    writer: &mut W,
+    metadata: &Metadata,
+    messages: &[MessageDefinition],
+    input_path: &Path,
+    output_path: &Path,
+) -> Result<(), IdlError> {
+    let header = generate(metadata, messages, input_path, output_path)?;
+    writer
+        .write_all(header.as_bytes())
+        .map_err(|e| IdlError::write(output_path, e))
+}
+
+/// Legacy generate function for backwards compatibility.
+/// Generates a single header with all encode/decode functions.
+pub fn generate(
+    metadata: &Metadata,
+    messages: &[MessageDefinition],
+    input_path: &Path,
+    output_path: &Path,
+) -> Result<String, IdlError> {
+    let options = CGenOptions::default();
+    check_nesting(messages)?;
+    let templates = Templates::load(&options, metadata, messages, input_path)?;
+    Ok(generate_single_header(
+        metadata,
+        messages,
+        input_path,
+        output_path,
+        &templates,
+        &options,
+        FnEmit::Inline,
+    ))
+}
+
+/// Single-file generation honoring the given options.
+///
+/// Returns one header in [`ImplStyle::Inline`] mode (identical to
+/// [`generate`]), or a declarations-only header plus a `.c` file holding the
+/// function bodies and endian helpers in [`ImplStyle::Source`] mode. File
+/// names are derived from `output_path`.
+pub fn generate_files(
+    metadata: &Metadata,
+    messages: &[MessageDefinition],
+    input_path: &Path,
+    output_path: &Path,
+    options: &CGenOptions,
+) -> Result<Vec<OutputFile>, IdlError> {
+    check_nesting(messages)?;
+    let templates = Templates::load(options, metadata, messages, input_path)?;
+    let header_filename = output_path
+        .file_name()
+        .and_then(|s| s.to_str())
+        .unwrap_or("generated_header.h")
+        .to_string();
+
+    let mut files = match options.impl_style {
+        ImplStyle::Inline => vec![OutputFile {
+            content: generate_single_header(
+                metadata,
+                messages,
+                input_path,
+                output_path,
+                &templates,
+                options,
+                FnEmit::Inline,
+            ),
+            filename: header_filename.clone(),
+        }],
+        ImplStyle::Source => {
+            let header = generate_single_header(
+                metadata,
+                messages,
+                input_path,
+                output_path,
+                &templates,
+                options,
+                FnEmit::Prototype,
+            );
+            let source_filename = source_filename_for(&header_filename);
+            let source = generate_single_source(
+                metadata,
+                messages,
+                input_path,
+                &header_filename,
+                &templates,
+                options,
+            );
+            vec![
+                OutputFile {
+                    filename: header_filename.clone(),
+                    content: header,
+                },
+                OutputFile {
+                    filename: source_filename,
+                    content: source,
+                },
+            ]
+        }
+    };
+
+    let stem = header_filename
+        .strip_suffix(".h")
+        .unwrap_or(&header_filename);
+    let headers = std::slice::from_ref(&header_filename);
+    if options.emit_tests {
+        files.push(OutputFile {
+            filename: format!("{}_tests.c", stem),
+            content: self_test::generate_test_source(
+                messages,
+                headers,
+                &name_context_from_path(input_path, options, metadata, messages),
+                &templates,
+            ),
+        });
+    }
+    if options.emit_fuzz {
+        files.push(OutputFile {
+            filename: format!("{}_fuzz.c", stem),
+            content: fuzz::generate_fuzz_source(
+                metadata,
+                messages,
+                headers,
+                &name_context_from_path(input_path, options, metadata, messages),
+                &templates,
+            ),
+        });
+    }
+    if options.emit_python {
+        files.push(OutputFile {
+            filename: format!("{}_ctypes.py", stem),
+            content: ctypes::generate_ctypes_module(
+                metadata,
+                messages,
+                &header_filename,
+                &name_context_from_path(input_path, options, metadata, messages),
+            ),
+        });
+    }
+    // The companion source of the header, in source style
+    let source_filename = source_filename_for(&header_filename);
+    let mut sources: Vec<String> = files
+        .iter()
+        .filter(|file| file.filename == source_filename)
+        .map(|file| file.filename.clone())
+        .collect();
+    if options.package == Some(Package::Arduino) {
+        // The single header declares every function
+        arduino::package(
+            &mut files,
+            &mut sources,
+            metadata,
+            messages,
+            &name_context_from_path(input_path, options, metadata, messages),
+            headers,
+            |_| Some(header_filename.as_str()),
+        );
+    }
+    if let Some(cmake) = &options.cmake {
+        let content = cmake::generate_cmake_module(
+            &files,
+            &sources,
+            &name_context_from_path(input_path, options, metadata, messages),
+            cmake,
+            &ir_fingerprint(metadata, messages),
+        );
+        files.push(OutputFile {
+            filename: format!("{}.cmake", stem),
+            content,
+        });
+    }
+
+    Ok(files)
+}
+
+/// Refuses messages whose structs nest deeper than [`MAX_NESTING_DEPTH_CAP`]
+/// levels. Parsed and built messages never do, but the typedef and
+/// encode/decode generators recurse once per level, so definitions
+/// assembled by hand are stopped here before they can exhaust the stack.
+fn check_nesting(messages: &[MessageDefinition]) -> Result<(), IdlError> {
+    for msg in messages {
+        let (depth, _) = struct_shape(body_structs(&msg.body));
+        if depth > MAX_NESTING_DEPTH_CAP {
+            return Err(IdlError::limit_exceeded(
+                &pointer_push("/packets", &msg.name),
+                depth,
+                MAX_NESTING_DEPTH_CAP,
+                format!(
+                    "message '{}' nests structs {} levels deep, more than the C generator supports ({})",
+                    msg.name, depth, MAX_NESTING_DEPTH_CAP
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn generate_single_header(
+    metadata: &Metadata,
+    messages: &[MessageDefinition],
+    input_path: &Path,
+    output_path: &Path,
+    templates: &Templates,
+    options: &CGenOptions,
+    emit: FnEmit,
+) -> String {
+    let header_guard = header_guard_name(output_path);
+    let name_ctx = name_context_from_path(input_path, options, metadata, messages);
+
+    let mut out = String::with_capacity(
+        FILE_OVERHEAD_BYTES + templates.helpers.len() + messages.len() * MESSAGE_BYTES_ESTIMATE,
+    );
+    writeln!(&mut out, "/*").unwrap();
+    writeln!(&mut out, " * Auto-generated by {}.", GENERATOR).unwrap();
+    templates.write_origin(&mut out);
+    if let Some(version) = &metadata.version {
+        writeln!(&mut out, " * Protocol version: {}", version).unwrap();
+    }
+    if let Some(max_address) = metadata.max_address {
+        writeln!(&mut out, " * Max address: {}", max_address).unwrap();
+    }
+    if let Some(endian) = metadata.default_endianness {
+        writeln!(&mut out, " * Default endianness: {}", endian).unwrap();
+    }
+    writeln!(&mut out, " */\n").unwrap();
+
+    writeln!(&mut out, "#ifndef {}", header_guard).unwrap();
+    writeln!(&mut out, "#define {}\n", header_guard).unwrap();
+
+    write_standard_includes(&mut out, name_ctx.dialect);
+    templates.write_prologue(&mut out);
+
+    out.push_str("#ifdef __cplusplus\nextern \"C\" {\n#endif\n\n");
+    write_payload_limit(&mut out, metadata, messages, &name_ctx);
+    write_constants(&mut out, metadata);
+    write_deprecated_macro(&mut out, messages, options);
+    if emit == FnEmit::Inline {
+        out.push_str(&templates.helpers);
+    }
+    if name_ctx.packed {
+        out.push_str(PACKED_MACRO);
+        out.push('\n');
+    }
+    write_timestamp_typedefs(&mut out, metadata, messages, &name_ctx);
+    out.push_str(&generate_shared_types(metadata, messages, &name_ctx));
+
+    let blocks: Vec<String> = messages
+        .par_iter()
+        .map(|msg| {
+            generate_message_block_with_mode(msg, FunctionMode::Both, &name_ctx, options, emit)
+        })
+        .collect();
+    for block in &blocks {
+        out.push('\n');
+        out.push_str(block);
+    }
+    // After every message, as a response may be defined after its request
+    let trailers: Vec<String> = messages
+        .par_iter()
+        .map(|msg| {
+            let mut trailer = generate_encode_str_fn(msg, &name_ctx, emit);
+            trailer.push_str(&generate_expected_response_fn(msg, &name_ctx, emit));
+            trailer
+        })
+        .collect();
+    out.extend(trailers);
+    out.push_str(&generate_response_table(messages, &name_ctx));
+    if name_ctx.packed {
+        out.push_str(PACKED_END);
+    }
+
+    out.push('\n');
+    out.push_str(&generate_static_asserts(messages, &name_ctx));
+
+    out.push_str("\n#ifdef __cplusplus\n}\n#endif\n\n");
+    templates.write_epilogue(&mut out);
+    writeln!(&mut out, "#endif /* {} */", header_guard).unwrap();
+
+    out
+}
+
+fn generate_single_source(
+    metadata: &Metadata,
+    messages: &[MessageDefinition],
+    input_path: &Path,
+    header_filename: &str,
+    templates: &Templates,
+    options: &CGenOptions,
+) -> String {
+    let name_ctx = name_context_from_path(input_path, options, metadata, messages);
+
+    let mut out = String::with_capacity(
+        FILE_OVERHEAD_BYTES + templates.helpers.len() + messages.len() * MESSAGE_BYTES_ESTIMATE,
+    );
+    writeln!(&mut out, "/*").unwrap();
+    writeln!(&mut out, " * Auto-generated by {}.", GENERATOR).unwrap();
+    templates.write_origin(&mut out);
+    if let Some(version) = &metadata.version {
+        writeln!(&mut out, " * Protocol version: {}", version).unwrap();
+    }
+    writeln!(&mut out, " */\n").unwrap();
+
+    writeln!(&mut out, "#include \"{}\"\n", header_filename).unwrap();
+    out.push_str(&templates.helpers);
+
+    let blocks: Vec<String> = messages
+        .par_iter()
+        .map(|msg| {
+            let mut block = String::from("\n");
+            block.push_str(&generate_message_functions_only(
+                msg,
+                FunctionMode::Both,
+                &name_ctx,
+                options,
+                FnEmit::Definition,
+            ));
+            block.push_str(&generate_string_accessors(
+                msg,
+                &name_ctx,
+                FnEmit::Definition,
+            ));
+            block.push_str(&generate_encode_str_fn(msg, &name_ctx, FnEmit::Definition));
+            block.push_str(&generate_expected_response_fn(
+                msg,
+                &name_ctx,
+                FnEmit::Definition,
+            ));
+            block
+        })
+        .collect();
+    out.extend(blocks);
+
+    out
+}
+
+/// Returns the `.c` file name that pairs with a generated header.
+fn source_filename_for(header_filename: &str) -> String {
+    match header_filename.strip_suffix(".h") {
+        Some(stem) => format!("{}.c", stem),
+        None => format!("{}.c", header_filename),
+    }
+}
+
+fn generate_message_block_with_mode(
+    msg: &MessageDefinition,
+    mode: FunctionMode,
+    name_ctx: &NameContext,
+    options: &CGenOptions,
+    emit: FnEmit,
+) -> String {
+    let mut out = generate_message_types_only(msg, name_ctx, options);
+    out.push_str(&generate_string_accessors(msg, name_ctx, emit));
+    out.push_str(&generate_body_functions(msg, mode, name_ctx, options, emit));
+    out
+}
+
+/// Generates only type definitions and macros for a message (for _types.h)
+fn generate_message_types_only(
+    msg: &MessageDefinition,
+    name_ctx: &NameContext,
+    options: &CGenOptions,
+) -> String {
+    let mut out = String::new();
+    write_message_comments(&mut out, msg, name_ctx);
+    let macro_prefix = msg_macro_prefix(name_ctx, msg);
+    writeln!(
+        &mut out,
+        "#define {}_PACKET_ID {}",
+        macro_prefix,
+        name_ctx.uint(msg.packet_id)
+    )
+    .unwrap();
+    writeln!(
+        &mut out,
+        "#define {}_MIN_SIZE {}",
+        macro_prefix,
+        name_ctx.uint(message_body_min_size(&msg.body))
+    )
+    .unwrap();
+    writeln!(
+        &mut out,
+        "#define {}_MAX_SIZE {}",
+        macro_prefix,
+        name_ctx.uint(message_body_max_size(&msg.body))
+    )
+    .unwrap();
+    if let Some(version) = msg.msg_version {
+        writeln!(
+            &mut out,
+            "#define {}_VERSION {}",
+            macro_prefix,
+            name_ctx.uint(version)
+        )
+        .unwrap();
+    }
+    if let Some(response) = &msg.response {
+        writeln!(
+            &mut out,
+            "#define {}_RESPONSE_PACKET_ID {}_PACKET_ID",
+            macro_prefix,
+            name_ctx.msg_macro_prefix(response)
+        )
+        .unwrap();
+    }
+    write_field_offsets(&mut out, msg, &macro_prefix, name_ctx);
+
+    match &msg.body {
+        MessageBody::Array(spec) => {
+            writeln!(
+                &mut out,
+                "#define {}_MAX_LENGTH {}",
+                macro_prefix,
+                name_ctx.uint(spec.max_length)
             )
             .unwrap();
             if let Some(sector) = spec.sector_bytes {
-                writeln!(&mut out, "#define {}_SECTOR_BYTES {}", macro_prefix, sector).unwrap();
+                writeln!(
+                    &mut out,
+                    "#define {}_SECTOR_BYTES {}",
+                    macro_prefix,
+                    name_ctx.uint(sector)
+                )
+                .unwrap();
+                writeln!(
+                    &mut out,
+                    "#define {}_SECTOR_COUNT {}",
+                    macro_prefix,
+                    name_ctx.uint(sector_count(spec, sector))
+                )
+                .unwrap();
+            }
+            out.push('\n');
+            out.push_str(&generate_array_typedef(msg, spec, name_ctx));
+        }
+        MessageBody::Scalar(spec) => {
+            out.push('\n');
+            out.push_str(&generate_scalar_typedef(msg, spec, name_ctx));
+        }
+        MessageBody::Struct(spec) => {
+            out.push('\n');
+            out.push_str(&generate_struct_typedef_for_types(msg, spec, name_ctx));
+            if options.fast_path && struct_fast_path_eligible(spec) {
+                out.push_str(&generate_wire_mirror_typedef(msg, spec, name_ctx));
+            }
+        }
+        MessageBody::Union(spec) => {
+            for variant in &spec.variants {
+                writeln!(
+                    &mut out,
+                    "#define {}_{}_TAG {}",
+                    macro_prefix,
+                    to_macro_ident(&variant.name),
+                    tag_literal(variant.tag, spec.tag, name_ctx)
+                )
+                .unwrap();
+            }
+            out.push('\n');
+            out.push_str(&generate_union_typedef(msg, spec, name_ctx));
+        }
+    }
+    out.push_str(&generate_scaled_accessors(msg, name_ctx));
+
+    out
+}
+
+/// Writes `<MSG>_<FIELD>_OFFSET` and `<MSG>_<FIELD>_SIZE` with the payload
+/// position of every leaf field of a struct or union message, nested paths
+/// joined by `_`, so a receiver can peek at one field without decoding the
+/// rest. A variable-length array gets its offset only, and the fields after
+/// it none, since they move with its length.
+fn write_field_offsets(
+    out: &mut String,
+    msg: &MessageDefinition,
+    macro_prefix: &str,
+    name_ctx: &NameContext,
+) {
+    let layouts = match &msg.body {
+        MessageBody::Struct(spec) => vec![struct_layout(spec, "", ByteSpan::fixed(0)).0],
+        MessageBody::Union(spec) => spec
+            .variants
+            .iter()
+            .map(|variant| {
+                let prefix = format!("{}.", variant.name);
+                struct_layout(&variant.spec, &prefix, ByteSpan::fixed(spec.tag.byte_len())).0
+            })
+            .collect(),
+        MessageBody::Scalar(_) | MessageBody::Array(_) => return,
+    };
+    for layout in layouts {
+        let mut leaves = layout
+            .into_iter()
+            .filter(|entry| {
+                !entry.field.reserved
+                    && !matches!(entry.field.field_type, StructFieldType::Nested(_))
+            })
+            .peekable();
+        while let Some(entry) = leaves.next() {
+            let name = entry
+                .path
+                .split('.')
+                .map(to_macro_ident)
+                .collect::<Vec<_>>()
+                .join("_");
+            writeln!(
+                out,
+                "#define {}_{}_OFFSET {}",
+                macro_prefix,
+                name,
+                name_ctx.uint(entry.offset.min)
+            )
+            .unwrap();
+            if !entry.size.is_fixed() {
+                if leaves.peek().is_some() {
+                    writeln!(
+                        out,
+                        "/* No offsets after '{}': its length varies, so the fields after it move */",
+                        entry.path
+                    )
+                    .unwrap();
+                }
+                break;
+            }
+            writeln!(
+                out,
+                "#define {}_{}_SIZE {}",
+                macro_prefix,
+                name,
+                name_ctx.uint(entry.size.min)
+            )
+            .unwrap();
+        }
+    }
+}
+
+/// Generates `get_str()`/`set_str()` for a char array message, copying
+/// the text out to a caller buffer and in from a C string. Both truncate
+/// rather than overflow and always leave a terminating '\0'.
+fn generate_string_accessors(
+    msg: &MessageDefinition,
+    name_ctx: &NameContext,
+    emit: FnEmit,
+) -> String {
+    let MessageBody::Array(spec) = &msg.body else {
+        return String::new();
+    };
+    if spec.primitive != PrimitiveType::Char {
+        return String::new();
+    }
+    let type_name = type_name(msg, name_ctx);
+    let fn_prefix = name_ctx.msg_fn_prefix(&msg.name);
+    let max_macro = format!("{}_MAX_LENGTH", msg_macro_prefix(name_ctx, msg));
+    let dialect = name_ctx.dialect;
+    let zero = name_ctx.uint(0);
+    let mut out = String::new();
+
+    // The comments go with the declaration, so a `.c` file has none
+    if emit != FnEmit::Definition {
+        if name_ctx.doc_comments {
+            out.push_str(&doc_block(&[
+                "@brief Copies the text into @p dst as a C string, truncated to @p dst_len - 1 characters.".to_string(),
+                "@return The number of characters copied, or 0 if @p msg or @p dst is NULL or @p dst_len is 0.".to_string(),
+            ]));
+        } else {
+            out.push_str(
+                "/* Copies the text into dst as a C string, truncated to dst_len - 1 characters */\n",
+            );
+        }
+    }
+    write!(
+        &mut out,
+        "{}size_t {}_get_str(const {} *msg, char *dst, size_t dst_len)",
+        emit.qualifier(dialect),
+        fn_prefix,
+        type_name
+    )
+    .unwrap();
+    if emit == FnEmit::Prototype {
+        out.push_str(";\n\n");
+    } else {
+        out.push_str(" {\n");
+        name_ctx.write_locals(&mut out, &["n", "i"]);
+        let guard = if name_ctx.misra {
+            format!("(msg == NULL) || (dst == NULL) || (dst_len == {})", zero)
+        } else {
+            "!msg || !dst || dst_len == 0".to_string()
+        };
+        writeln!(
+            &mut out,
+            "    if ({}) {{\n        return {};\n    }}",
+            guard, zero
+        )
+        .unwrap();
+        writeln!(
+            &mut out,
+            "    {};",
+            dialect.init("size_t", "n", "msg->length")
+        )
+        .unwrap();
+        writeln!(
+            &mut out,
+            "    if (n > {m}) {{\n        n = {m};\n    }}",
+            m = max_macro
+        )
+        .unwrap();
+        writeln!(
+            &mut out,
+            "    if (n > dst_len - {one}) {{\n        n = dst_len - {one};\n    }}",
+            one = name_ctx.uint(1)
+        )
+        .unwrap();
+        writeln!(
+            &mut out,
+            "    {}i < n; ++i) {{\n        dst[i] = msg->data[i];\n    }}",
+            name_ctx.for_index()
+        )
+        .unwrap();
+        out.push_str("    dst[n] = '\\0';\n    return n;\n}\n\n");
+    }
+
+    if emit != FnEmit::Definition {
+        if name_ctx.doc_comments {
+            out.push_str(&doc_block(&[
+                format!(
+                    "@brief Sets the text from the C string @p src, truncated to {} characters.",
+                    max_macro
+                ),
+                "@return The number of characters stored, or 0 if @p msg or @p src is NULL."
+                    .to_string(),
+            ]));
+        } else {
+            writeln!(
+                &mut out,
+                "/* Sets the text from the C string src, truncated to {} characters */",
+                max_macro
+            )
+            .unwrap();
+        }
+    }
+    write!(
+        &mut out,
+        "{}size_t {}_set_str({} *msg, const char *src)",
+        emit.qualifier(dialect),
+        fn_prefix,
+        type_name
+    )
+    .unwrap();
+    if emit == FnEmit::Prototype {
+        out.push_str(";\n\n");
+        return out;
+    }
+    out.push_str(" {\n");
+    name_ctx.write_locals(&mut out, &["n"]);
+    name_ctx.write_null_guard(&mut out, &["msg", "src"], &zero);
+    writeln!(&mut out, "    {};", dialect.init("size_t", "n", &zero)).unwrap();
+    writeln!(
+        &mut out,
+        "    while ((n < {}) && (src[n] != '\\0')) {{\n        msg->data[n] = src[n];\n        ++n;\n    }}",
+        max_macro
+    )
+    .unwrap();
+    out.push_str("    msg->data[n] = '\\0';\n    msg->length = n;\n    return n;\n}\n\n");
+    out
+}
+
+/// A scaled integer of a message: accessor name part, C lvalue through
+/// `msg`, raw type, and whether it is an array indexed by the accessors.
+struct ScaledValue {
+    name: String,
+    lvalue: String,
+    primitive: PrimitiveType,
+    scaling: Scaling,
+    is_array: bool,
+}
+
+/// Collects the scaled fields of `spec` and its nested structs. `access`
+/// is the C expression prefix of the struct's members, e.g. `msg->pose.`.
+fn collect_scaled_fields(spec: &StructSpec, name: &str, access: &str, out: &mut Vec<ScaledValue>) {
+    for field in &spec.fields {
+        let field_name = to_snake_case(&field.name);
+        let name = if name.is_empty() {
+            field_name.clone()
+        } else {
+            format!("{}_{}", name, field_name)
+        };
+        let lvalue = format!("{}{}", access, field_name);
+        match (&field.field_type, field.scaling) {
+            (StructFieldType::Nested(nested), _) => {
+                collect_scaled_fields(nested, &name, &format!("{}.", lvalue), out)
             }
-            out.push('\n');
-            out.push_str(&generate_array_typedef(msg, spec, name_ctx));
+            (StructFieldType::Primitive(primitive), Some(scaling)) => out.push(ScaledValue {
+                name,
+                lvalue,
+                primitive: *primitive,
+                scaling,
+                is_array: false,
+            }),
+            (StructFieldType::Array(arr), Some(scaling)) => out.push(ScaledValue {
+                name,
+                lvalue,
+                primitive: arr.primitive,
+                scaling,
+                is_array: true,
+            }),
+            (StructFieldType::Bytes(_), _) | (_, None) => {}
         }
+    }
+}
+
+/// Generates `get_<field>_scaled()`/`set_<field>_scaled()` helpers for
+/// the scaled integers of a message. The setter rounds to the nearest raw
+/// value and returns false when it is out of range of the raw type.
+fn generate_scaled_accessors(msg: &MessageDefinition, name_ctx: &NameContext) -> String {
+    let mut values = Vec::new();
+    match &msg.body {
         MessageBody::Scalar(spec) => {
-            out.push('\n');
-            out.push_str(&generate_scalar_typedef(msg, spec, name_ctx));
+            if let Some(scaling) = spec.scaling {
+                values.push(ScaledValue {
+                    name: "value".to_string(),
+                    lvalue: "msg->value".to_string(),
+                    primitive: spec.primitive,
+                    scaling,
+                    is_array: false,
+                });
+            }
+        }
+        MessageBody::Array(spec) => {
+            if let Some(scaling) = spec.scaling {
+                values.push(ScaledValue {
+                    name: "data".to_string(),
+                    lvalue: "msg->data".to_string(),
+                    primitive: spec.primitive,
+                    scaling,
+                    is_array: true,
+                });
+            }
+        }
+        MessageBody::Struct(spec) => collect_scaled_fields(spec, "", "msg->", &mut values),
+        MessageBody::Union(spec) => {
+            for variant in &spec.variants {
+                let member = to_snake_case(&variant.name);
+                collect_scaled_fields(
+                    &variant.spec,
+                    &member,
+                    &format!("msg->variant.{}.", member),
+                    &mut values,
+                );
+            }
+        }
+    }
+
+    let type_name = type_name(msg, name_ctx);
+    let fn_prefix = name_ctx.msg_fn_prefix(&msg.name);
+    let mut out = String::new();
+    for value in values {
+        let Some((min, max)) = value.primitive.integer_range() else {
+            continue;
+        };
+        let scale = const_literal(&ConstValue::Float(value.scaling.scale));
+        let offset = const_literal(&ConstValue::Float(value.scaling.offset));
+        let (index_param, element, index_check) = if value.is_array {
+            (
+                ", size_t index",
+                format!("{}[index]", value.lvalue),
+                format!("index >= sizeof({0}) / sizeof({0}[0])", value.lvalue),
+            )
+        } else {
+            ("", value.lvalue.clone(), String::new())
+        };
+        let guard = match (name_ctx.misra, index_check.is_empty()) {
+            (true, true) => "msg == NULL".to_string(),
+            (true, false) => format!("(msg == NULL) || ({})", index_check),
+            (false, true) => "!msg".to_string(),
+            (false, false) => format!("!msg || {}", index_check),
+        };
+        let invalid = if value.is_array {
+            "@p msg is NULL or @p index is out of range"
+        } else {
+            "@p msg is NULL"
+        };
+        if name_ctx.doc_comments {
+            out.push_str(&doc_block(&[
+                format!(
+                    "@brief '{}' in physical units: raw * {} + {}.",
+                    value.name, scale, offset
+                ),
+                format!("@return The value, or 0.0 if {}.", invalid),
+            ]));
+        } else {
+            writeln!(
+                &mut out,
+                "/* '{}' in physical units: raw * {} + {} */",
+                value.name, scale, offset
+            )
+            .unwrap();
+        }
+        writeln!(
+            &mut out,
+            "{}double {}_get_{}_scaled(const {} *msg{}) {{",
+            name_ctx.dialect.static_inline(),
+            fn_prefix,
+            value.name,
+            type_name,
+            index_param
+        )
+        .unwrap();
+        writeln!(&mut out, "    if ({}) {{", guard).unwrap();
+        out.push_str("        return 0.0;\n    }\n");
+        writeln!(
+            &mut out,
+            "    return (double){} * {} + {};",
+            element, scale, offset
+        )
+        .unwrap();
+        out.push_str("}\n\n");
+
+        if name_ctx.doc_comments {
+            out.push_str(&doc_block(&[
+                format!(
+                    "@brief Sets '{}' from a value in physical units, rounded to the nearest raw value.",
+                    value.name
+                ),
+                format!(
+                    "@return false if {} or the raw value does not fit {}.",
+                    invalid,
+                    value.primitive.c_type()
+                ),
+            ]));
+        }
+        writeln!(
+            &mut out,
+            "{}bool {}_set_{}_scaled({} *msg{}, double value) {{",
+            name_ctx.dialect.static_inline(),
+            fn_prefix,
+            value.name,
+            type_name,
+            index_param
+        )
+        .unwrap();
+        if name_ctx.dialect == CDialect::C89 {
+            out.push_str("    double raw;\n");
+        }
+        writeln!(&mut out, "    if ({}) {{", guard).unwrap();
+        out.push_str("        return false;\n    }\n");
+        writeln!(
+            &mut out,
+            "    {};",
+            name_ctx.dialect.init(
+                "double",
+                "raw",
+                &format!("(value - {}) / {}", offset, scale)
+            )
+        )
+        .unwrap();
+        out.push_str("    raw = raw < 0.0 ? raw - 0.5 : raw + 0.5;\n");
+        // Truncation maps (min - 1, max + 1) onto [min, max]; also rejects NaN
+        writeln!(
+            &mut out,
+            "    if (!(raw > {:?} && raw < {:?})) {{",
+            (min - 1) as f64,
+            (max + 1) as f64
+        )
+        .unwrap();
+        out.push_str("        return false;\n    }\n");
+        writeln!(
+            &mut out,
+            "    {} = ({})raw;",
+            element,
+            value.primitive.c_type()
+        )
+        .unwrap();
+        out.push_str("    return true;\n}\n\n");
+    }
+    out
+}
+
+/// Generates only functions for a message (for _server.h and _client_<id>.h)
+fn generate_message_functions_only(
+    msg: &MessageDefinition,
+    mode: FunctionMode,
+    name_ctx: &NameContext,
+    options: &CGenOptions,
+    emit: FnEmit,
+) -> String {
+    let mut out = String::new();
+    write_message_comments(&mut out, msg, name_ctx);
+    out.push_str(&generate_body_functions(msg, mode, name_ctx, options, emit));
+    out
+}
+
+/// Writes the description and deprecation comments above a message.
+/// With Doxygen comments they are part of the blocks of the typedef and
+/// the functions instead.
+fn write_message_comments(out: &mut String, msg: &MessageDefinition, name_ctx: &NameContext) {
+    if name_ctx.doc_comments {
+        return;
+    }
+    if let Some(desc) = &msg.description {
+        writeln!(out, "/* {} */", comment_text(desc)).unwrap();
+    }
+    match &msg.deprecated {
+        Some(Deprecation {
+            reason: Some(reason),
+        }) => writeln!(out, "/* Deprecated: {} */", comment_text(reason)).unwrap(),
+        Some(Deprecation { reason: None }) => writeln!(out, "/* Deprecated */").unwrap(),
+        None => {}
+    }
+}
+
+/// Storage class and attributes written before the signature of a
+/// message function.
+fn fn_qualifiers(msg: &MessageDefinition, emit: FnEmit, dialect: CDialect) -> String {
+    match &msg.deprecated {
+        Some(deprecation) => {
+            let reason = match &deprecation.reason {
+                Some(reason) => reason.clone(),
+                None => format!("message '{}' is deprecated", msg.name),
+            };
+            format!(
+                "H6XSERIAL_DEPRECATED({}) {}",
+                c_string_literal(&reason),
+                emit.qualifier(dialect)
+            )
+        }
+        None => emit.qualifier(dialect).to_string(),
+    }
+}
+
+/// Generates the encode/decode functions (or their prototypes) for a message body.
+fn generate_body_functions(
+    msg: &MessageDefinition,
+    mode: FunctionMode,
+    name_ctx: &NameContext,
+    options: &CGenOptions,
+    emit: FnEmit,
+) -> String {
+    if emit == FnEmit::Prototype {
+        let mut out = String::new();
+        if mode != FunctionMode::DecodeOnly {
+            out.push_str(&function_doc(
+                msg,
+                true,
+                name_ctx,
+                options.encode_range,
+                emit,
+            ));
+            writeln!(
+                &mut out,
+                "{}{};",
+                fn_qualifiers(msg, emit, name_ctx.dialect),
+                encode_signature(msg, name_ctx)
+            )
+            .unwrap();
+        }
+        if mode != FunctionMode::EncodeOnly {
+            out.push_str(&function_doc(
+                msg,
+                false,
+                name_ctx,
+                options.encode_range,
+                emit,
+            ));
+            writeln!(
+                &mut out,
+                "{}{};",
+                fn_qualifiers(msg, emit, name_ctx.dialect),
+                decode_signature(msg, name_ctx)
+            )
+            .unwrap();
+        }
+        if let MessageBody::Array(spec) = &msg.body
+            && spec.sector_bytes.is_some()
+        {
+            out.push_str(&generate_sector_functions(msg, spec, mode, name_ctx, emit));
+        }
+        out.push_str(&generate_version_functions(msg, mode, name_ctx, emit));
+        out.push('\n');
+        return out;
+    }
+
+    let mut out = match &msg.body {
+        MessageBody::Array(spec) => {
+            generate_array_functions(msg, spec, mode, name_ctx, options, emit)
+        }
+        MessageBody::Scalar(spec) => {
+            generate_scalar_functions(msg, spec, mode, name_ctx, options, emit)
         }
         MessageBody::Struct(spec) => {
-            out.push('\n');
-            out.push_str(&generate_struct_typedef_for_types(msg, spec, name_ctx));
+            generate_struct_functions(msg, spec, mode, name_ctx, options, emit)
+        }
+        MessageBody::Union(spec) => {
+            generate_union_functions(msg, spec, mode, name_ctx, options, emit)
+        }
+    };
+    out.push_str(&generate_version_functions(msg, mode, name_ctx, emit));
+    out
+}
+
+/// Generate typedef only for scalar message
+fn generate_scalar_typedef(
+    msg: &MessageDefinition,
+    spec: &ScalarSpec,
+    name_ctx: &NameContext,
+) -> String {
+    let type_name = type_name(msg, name_ctx);
+    format!(
+        "{}{}\n    {} value;{}\n}} {};\n\n",
+        typedef_doc(msg, name_ctx),
+        name_ctx.typedef_struct(),
+        spec.primitive.c_type(),
+        member_comment(
+            spec.description.as_deref(),
+            spec.unit.as_deref(),
+            name_ctx.doc_comments
+        ),
+        type_name
+    )
+}
+
+/// Generate typedef only for array message
+fn generate_array_typedef(
+    msg: &MessageDefinition,
+    spec: &ArraySpec,
+    name_ctx: &NameContext,
+) -> String {
+    let type_name = type_name(msg, name_ctx);
+    let macro_prefix = msg_macro_prefix(name_ctx, msg);
+    let bitmap = match spec.sector_bytes {
+        Some(_) if name_ctx.doc_comments => format!(
+            "\n    uint8_t sectors_received[({}_SECTOR_COUNT + 7) / 8]; /**< Sectors stored by the _decode_sector function, one bit each */",
+            macro_prefix
+        ),
+        Some(_) => format!(
+            "\n    /* Sectors stored by the _decode_sector function, one bit each */\n    uint8_t sectors_received[({}_SECTOR_COUNT + 7) / 8];",
+            macro_prefix
+        ),
+        None => String::new(),
+    };
+    // Strings get room for a terminating '\0' even at full length; the
+    // wire format is the same
+    let capacity = if spec.primitive == PrimitiveType::Char {
+        format!("{}_MAX_LENGTH + {}", macro_prefix, name_ctx.uint(1))
+    } else {
+        format!("{}_MAX_LENGTH", macro_prefix)
+    };
+    format!(
+        "{}{}\n    size_t length;{}\n    {} data[{}];{}{}\n}} {};\n\n",
+        typedef_doc(msg, name_ctx),
+        name_ctx.typedef_struct(),
+        length_comment("data", name_ctx),
+        spec.primitive.c_type(),
+        capacity,
+        member_comment(
+            spec.description.as_deref(),
+            spec.unit.as_deref(),
+            name_ctx.doc_comments
+        ),
+        bitmap,
+        type_name
+    )
+}
+
+/// Number of `sector_bytes` sectors of a full array message.
+fn sector_count(spec: &ArraySpec, sector_bytes: usize) -> usize {
+    (spec.max_length * spec.primitive.byte_len()).div_ceil(sector_bytes)
+}
+
+/// Text made safe for a `/* */` comment: comment delimiters are split and
+/// line breaks become spaces.
+fn comment_text(text: &str) -> String {
+    text.replace("*/", "* /")
+        .replace("/*", "/ *")
+        .replace(['\r', '\n'], " ")
+}
+
+/// Trailing ` /* unit: description */` comment of a struct member, or an
+/// empty string when neither is set. `doc` makes it a Doxygen `/**<`
+/// member comment.
+fn member_comment(description: Option<&str>, unit: Option<&str>, doc: bool) -> String {
+    let text = match (unit, description) {
+        (Some(unit), Some(description)) => format!("{}: {}", unit, description),
+        (Some(text), None) | (None, Some(text)) => text.to_string(),
+        (None, None) => return String::new(),
+    };
+    let open = if doc { "/**<" } else { "/*" };
+    format!(" {} {} */", open, comment_text(&text))
+}
+
+/// Doxygen member comment of the length of the array `member`.
+fn length_comment(member: &str, name_ctx: &NameContext) -> String {
+    if name_ctx.doc_comments {
+        format!(" /**< Number of valid elements in {} */", member)
+    } else {
+        String::new()
+    }
+}
+
+/// Doxygen `/** */` block of `lines`, each made safe like [`comment_text`].
+/// An empty line separates paragraphs.
+fn doc_block(lines: &[String]) -> String {
+    let mut out = String::from("/**\n");
+    for line in lines {
+        if line.is_empty() {
+            out.push_str(" *\n");
+        } else {
+            writeln!(out, " * {}", comment_text(line)).unwrap();
+        }
+    }
+    out.push_str(" */\n");
+    out
+}
+
+/// `@brief` line of a message: its description, or its name.
+fn message_brief(msg: &MessageDefinition) -> String {
+    match &msg.description {
+        Some(desc) => format!("@brief {}", desc),
+        None => format!("@brief '{}' message", msg.name),
+    }
+}
+
+/// `@deprecated` line of a deprecated message.
+fn deprecated_line(msg: &MessageDefinition) -> Option<String> {
+    msg.deprecated
+        .as_ref()
+        .map(|deprecation| match &deprecation.reason {
+            Some(reason) => format!("@deprecated {}", reason),
+            None => "@deprecated".to_string(),
+        })
+}
+
+/// Byte order of the multi-byte values of `body`, or `None` when every
+/// value is a single byte.
+fn byte_order_text(body: &MessageBody) -> Option<&'static str> {
+    fn visit(spec: &StructSpec, out: &mut Vec<Endian>) {
+        for field in &spec.fields {
+            match &field.field_type {
+                StructFieldType::Primitive(prim) if prim.byte_len() > 1 => out.push(field.endian),
+                StructFieldType::Array(arr) if arr.primitive.byte_len() > 1 => {
+                    out.push(field.endian)
+                }
+                StructFieldType::Nested(nested) => visit(nested, out),
+                _ => {}
+            }
+        }
+    }
+
+    let mut endians = Vec::new();
+    match body {
+        MessageBody::Scalar(spec) if spec.primitive.byte_len() > 1 => endians.push(spec.endian),
+        MessageBody::Array(spec) if spec.primitive.byte_len() > 1 => endians.push(spec.endian),
+        MessageBody::Struct(spec) => visit(spec, &mut endians),
+        MessageBody::Union(spec) => {
+            if spec.tag.byte_len() > 1 {
+                endians.push(spec.endian);
+            }
+            for variant in &spec.variants {
+                visit(&variant.spec, &mut endians);
+            }
+        }
+        _ => {}
+    }
+    let first = *endians.first()?;
+    Some(match first {
+        _ if endians.iter().any(|endian| *endian != first) => "byte order per field",
+        Endian::Little => "little-endian",
+        Endian::Big => "big-endian",
+    })
+}
+
+/// `@note` line with the wire size and byte order of a message.
+fn size_note(msg: &MessageDefinition, name_ctx: &NameContext) -> String {
+    let mut note = format!(
+        "@note At most {}_MAX_SIZE ({}) bytes on the wire",
+        msg_macro_prefix(name_ctx, msg),
+        message_body_max_size(&msg.body)
+    );
+    if let Some(order) = byte_order_text(&msg.body) {
+        write!(note, ", {}", order).unwrap();
+    }
+    note.push('.');
+    note
+}
+
+/// `sizeof` of a generated type: with natural alignment on a 32-bit target
+/// (4-byte `size_t`, 64-bit types aligned to 8 as in the Arm EABI), its
+/// alignment there, and packed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct MemorySize {
+    natural: usize,
+    align: usize,
+    packed: usize,
+}
+
+impl MemorySize {
+    const EMPTY: Self = Self {
+        natural: 0,
+        align: 1,
+        packed: 0,
+    };
+
+    /// The C storage of `primitive`, which is wider than the wire encoding
+    /// for 24-bit integers and half floats.
+    fn of_primitive(primitive: PrimitiveType) -> Self {
+        let bytes = match primitive {
+            PrimitiveType::Int24 | PrimitiveType::Uint24 | PrimitiveType::Float16 => 4,
+            _ => primitive.byte_len(),
+        };
+        Self {
+            natural: bytes,
+            align: bytes,
+            packed: bytes,
+        }
+    }
+
+    fn size_t() -> Self {
+        Self::of_primitive(PrimitiveType::Uint32)
+    }
+
+    fn times(self, count: usize) -> Self {
+        Self {
+            natural: self.natural * count,
+            packed: self.packed * count,
+            ..self
+        }
+    }
+
+    /// Appends a struct member after the padding its alignment needs.
+    fn then(self, member: Self) -> Self {
+        Self {
+            natural: self.natural.next_multiple_of(member.align) + member.natural,
+            align: self.align.max(member.align),
+            packed: self.packed + member.packed,
+        }
+    }
+
+    /// Adds a member of a union.
+    fn or(self, member: Self) -> Self {
+        Self {
+            natural: self.natural.max(member.natural),
+            align: self.align.max(member.align),
+            packed: self.packed.max(member.packed),
+        }
+    }
+
+    /// Adds the tail padding that makes arrays of the type aligned.
+    fn close(self) -> Self {
+        Self {
+            natural: self.natural.next_multiple_of(self.align),
+            ..self
+        }
+    }
+}
+
+/// [`MemorySize`] of the typedef [`generate_struct_typedef`] writes.
+fn struct_memory_size(spec: &StructSpec) -> MemorySize {
+    let mut size = MemorySize::EMPTY;
+    for field in &spec.fields {
+        size = match &field.field_type {
+            StructFieldType::Primitive(primitive) => {
+                size.then(MemorySize::of_primitive(*primitive))
+            }
+            StructFieldType::Array(arr) if arr.fixed => {
+                size.then(MemorySize::of_primitive(arr.primitive).times(arr.max_length))
+            }
+            StructFieldType::Array(arr) => size.then(MemorySize::size_t()).then(
+                MemorySize::of_primitive(arr.primitive)
+                    .times(arr.max_length + usize::from(arr.string_semantics)),
+            ),
+            StructFieldType::Bytes(length) => {
+                size.then(MemorySize::of_primitive(PrimitiveType::Uint8).times(*length))
+            }
+            StructFieldType::Nested(nested) => size.then(struct_memory_size(nested)),
+        };
+    }
+    size.close()
+}
+
+/// [`MemorySize`] of the typedef of a message.
+fn message_memory_size(body: &MessageBody) -> MemorySize {
+    match body {
+        MessageBody::Scalar(spec) => MemorySize::EMPTY
+            .then(MemorySize::of_primitive(spec.primitive))
+            .close(),
+        MessageBody::Array(spec) => {
+            let capacity = spec.max_length + usize::from(spec.primitive == PrimitiveType::Char);
+            let bitmap = spec.sector_bytes.map_or(0, |sector_bytes| {
+                sector_count(spec, sector_bytes).div_ceil(8)
+            });
+            MemorySize::EMPTY
+                .then(MemorySize::size_t())
+                .then(MemorySize::of_primitive(spec.primitive).times(capacity))
+                .then(MemorySize::of_primitive(PrimitiveType::Uint8).times(bitmap))
+                .close()
+        }
+        MessageBody::Struct(spec) => struct_memory_size(spec),
+        MessageBody::Union(spec) => {
+            let variants = spec
+                .variants
+                .iter()
+                .map(|variant| struct_memory_size(&variant.spec))
+                .fold(MemorySize::EMPTY, MemorySize::or)
+                .close();
+            MemorySize::EMPTY
+                .then(MemorySize::of_primitive(spec.tag))
+                .then(variants)
+                .close()
         }
     }
+}
 
-    out
+/// Compares the `sizeof` of the typedef of `msg` with its wire size, when
+/// natural alignment pads it.
+fn memory_size_note(msg: &MessageDefinition) -> Option<String> {
+    let size = message_memory_size(&msg.body);
+    let wire = message_body_max_size(&msg.body);
+    (size.natural != size.packed).then(|| {
+        format!(
+            "In memory: sizeof {} with natural alignment on a 32-bit target, {} packed; at most {} bytes on the wire",
+            size.natural, size.packed, wire
+        )
+    })
 }
 
-/// Generates only functions for a message (for _server.h and _client_<id>.h)
-fn generate_message_functions_only(
-    msg: &MessageDefinition,
-    mode: FunctionMode,
-    name_ctx: &NameContext,
-) -> String {
-    let mut out = String::new();
-    if let Some(desc) = &msg.description {
-        writeln!(&mut out, "/* {} */", desc).unwrap();
+/// Doxygen block of the typedef of a message, or without doc comments the
+/// [`memory_size_note`] alone.
+fn typedef_doc(msg: &MessageDefinition, name_ctx: &NameContext) -> String {
+    if !name_ctx.doc_comments {
+        return memory_size_note(msg)
+            .map(|note| format!("/* {} */\n", note))
+            .unwrap_or_default();
     }
-
-    match &msg.body {
-        MessageBody::Array(spec) => {
-            out.push_str(&generate_array_functions(msg, spec, mode, name_ctx));
-        }
-        MessageBody::Scalar(spec) => {
-            out.push_str(&generate_scalar_functions(msg, spec, mode, name_ctx));
-        }
-        MessageBody::Struct(spec) => {
-            out.push_str(&generate_struct_functions(msg, spec, mode, name_ctx));
-        }
+    let macro_prefix = msg_macro_prefix(name_ctx, msg);
+    let id_note = if name_ctx.packet_id_width > 8 {
+        format!(
+            "@note 16-bit packet ID {}_PACKET_ID (0x{:04X}).",
+            macro_prefix, msg.packet_id
+        )
+    } else {
+        format!(
+            "@note Packet ID {}_PACKET_ID ({}).",
+            macro_prefix, msg.packet_id
+        )
+    };
+    let mut lines = vec![message_brief(msg), id_note, size_note(msg, name_ctx)];
+    lines.extend(memory_size_note(msg).map(|note| format!("@note {}.", note)));
+    if let MessageBody::Array(spec) = &msg.body {
+        lines.push(match spec.primitive {
+            PrimitiveType::Char => format!(
+                "@note Holds up to {}_MAX_LENGTH ({}) characters, always followed by a '\\0'.",
+                macro_prefix, spec.max_length
+            ),
+            _ => format!(
+                "@note Holds up to {}_MAX_LENGTH ({}) elements.",
+                macro_prefix, spec.max_length
+            ),
+        });
     }
+    lines.extend(deprecated_line(msg));
+    doc_block(&lines)
+}
 
-    out
+/// Condition under which a range check fails, for the docs.
+fn range_failure(check: &RangeCheck) -> Option<String> {
+    let (min, max) = effective_bounds(check);
+    let nan = if check.primitive.integer_range().is_some() {
+        ""
+    } else {
+        "NaN or "
+    };
+    let bounds = match (min, max) {
+        (Some(min), Some(max)) => format!("outside [{}, {}]", min, max),
+        (Some(min), None) => format!("less than {}", min),
+        (None, Some(max)) => format!("greater than {}", max),
+        (None, None) => return None,
+    };
+    Some(format!("{} is {}{}", check.lvalue, nan, bounds))
 }
 
-/// Generate typedef only for scalar message
-fn generate_scalar_typedef(
+/// Doxygen block of the encode (`encode`) or decode function of a
+/// message, listing the failures the generated checks return.
+fn function_doc(
     msg: &MessageDefinition,
-    spec: &ScalarSpec,
+    encode: bool,
     name_ctx: &NameContext,
+    encode_range: EncodeRange,
+    emit: FnEmit,
 ) -> String {
-    let type_name = type_name(msg, name_ctx);
-    format!(
-        "typedef struct {{\n    {} value;\n}} {};\n\n",
-        spec.primitive.c_type(),
-        type_name
-    )
+    // Source style documents the prototype only
+    if !name_ctx.doc_comments || emit == FnEmit::Definition {
+        return String::new();
+    }
+    let macro_prefix = msg_macro_prefix(name_ctx, msg);
+    let checks = collect_range_checks(&msg.body);
+    let mut failures = Vec::new();
+    let mut lines = vec![message_brief(msg), String::new()];
+    if encode {
+        lines.extend([
+            "Encodes @p msg into @p out_buf.".to_string(),
+            "@param msg Message to encode.".to_string(),
+            "@param out_buf Buffer receiving the wire bytes.".to_string(),
+            "@param out_len Size of @p out_buf in bytes.".to_string(),
+            "@return Number of bytes written, or 0 when:".to_string(),
+        ]);
+        failures.push("@p msg or @p out_buf is NULL".to_string());
+        match &msg.body {
+            MessageBody::Array(spec) => {
+                failures.push(format!("msg->length exceeds {}_MAX_LENGTH", macro_prefix));
+                failures.push(match spec.primitive.byte_len() {
+                    1 => "@p out_len is less than msg->length".to_string(),
+                    size => format!("@p out_len is less than msg->length * {}", size),
+                });
+            }
+            MessageBody::Struct(spec) if struct_has_variable_arrays(spec) => {
+                let prefix = fields_macro_prefix(&name_ctx.helpers, spec, macro_prefix.clone());
+                let arrays = variable_arrays(name_ctx, &spec.fields, "msg->", &prefix);
+                failures.extend(
+                    arrays
+                        .iter()
+                        .map(|array| format!("{} exceeds {}", array.length, array.max_macro)),
+                );
+                failures.push(format!(
+                    "@p out_len is less than {}",
+                    encoded_size_expr(struct_min_byte_len(spec), &arrays, &|n| n.to_string())
+                ));
+            }
+            MessageBody::Union(spec) => {
+                for variant in &spec.variants {
+                    let prefix = fields_macro_prefix(
+                        &name_ctx.helpers,
+                        &variant.spec,
+                        format!("{}_{}", macro_prefix, to_macro_ident(&variant.name)),
+                    );
+                    let arrays = variable_arrays(
+                        name_ctx,
+                        &variant.spec.fields,
+                        &variant_accessor(variant),
+                        &prefix,
+                    );
+                    failures.extend(
+                        arrays
+                            .iter()
+                            .map(|array| format!("{} exceeds {}", array.length, array.max_macro)),
+                    );
+                }
+                failures.push(
+                    "@p out_len is less than the tag plus the fields of the selected variant"
+                        .to_string(),
+                );
+            }
+            body => failures.push(format!(
+                "@p out_len is less than {}",
+                message_body_max_size(body)
+            )),
+        }
+        if let MessageBody::Union(_) = &msg.body {
+            failures.push("msg->tag is not the tag of a variant".to_string());
+        }
+        if encode_range == EncodeRange::Reject {
+            failures.extend(checks.iter().filter_map(range_failure));
+        }
+    } else {
+        lines.extend([
+            "Decodes @p data into @p msg.".to_string(),
+            "@param msg Receives the decoded message.".to_string(),
+            "@param data Received payload.".to_string(),
+            "@param data_len Length of @p data in bytes.".to_string(),
+            "@return true on success, or false when:".to_string(),
+        ]);
+        failures.push("@p msg or @p data is NULL".to_string());
+        match &msg.body {
+            MessageBody::Array(spec) => match spec.primitive.byte_len() {
+                1 => failures.push(format!("@p data_len exceeds {}_MAX_LENGTH", macro_prefix)),
+                size => {
+                    failures.push(format!("@p data_len is not a multiple of {}", size));
+                    failures.push(format!(
+                        "@p data_len / {} exceeds {}_MAX_LENGTH",
+                        size, macro_prefix
+                    ));
+                }
+            },
+            MessageBody::Struct(spec) if struct_has_variable_arrays(spec) => {
+                failures.push(format!(
+                    "@p data_len is less than {} or greater than {}",
+                    struct_min_byte_len(spec),
+                    struct_byte_len(spec)
+                ));
+            }
+            MessageBody::Union(spec) => {
+                failures.push(format!("@p data_len is less than {}", spec.tag.byte_len()));
+                failures.push("the tag is not the tag of a variant".to_string());
+                failures.push("@p data_len does not fit the variant of the tag".to_string());
+            }
+            body => failures.push(format!(
+                "@p data_len is not {}",
+                message_body_max_size(body)
+            )),
+        }
+        failures.extend(checks.iter().filter_map(range_failure));
+    }
+    lines.extend(
+        failures
+            .into_iter()
+            .map(|failure| format!("  - {}", failure)),
+    );
+    if encode
+        && encode_range == EncodeRange::Clamp
+        && checks.iter().any(|check| range_failure(check).is_some())
+    {
+        lines.push("@note Values outside their range are clamped into it.".to_string());
+    }
+    lines.push(size_note(msg, name_ctx));
+    lines.extend(deprecated_line(msg));
+    doc_block(&lines)
 }
 
-/// Generate typedef only for array message
-fn generate_array_typedef(
+/// Generate typedef only for struct message (wrapper for generate_struct_typedef)
+fn generate_struct_typedef_for_types(
     msg: &MessageDefinition,
-    spec: &ArraySpec,
+    spec: &StructSpec,
     name_ctx: &NameContext,
 ) -> String {
+    let mut out = String::new();
     let type_name = type_name(msg, name_ctx);
-    let max_macro = format!("{}_MAX_LENGTH", msg_macro_prefix(name_ctx, msg));
-    format!(
-        "typedef struct {{\n    size_t length;\n    {} data[{}];\n}} {};\n\n",
-        spec.primitive.c_type(),
-        max_macro,
-        type_name
-    )
+    if let Some(shared) = &spec.shared {
+        out.push_str(&typedef_doc(msg, name_ctx));
+        writeln!(
+            &mut out,
+            "typedef {} {};\n",
+            shared_type_name(&name_ctx.helpers, shared),
+            type_name
+        )
+        .unwrap();
+        return out;
+    }
+    let macro_prefix = msg_macro_prefix(name_ctx, msg);
+    generate_struct_typedef(
+        &mut out,
+        &type_name,
+        &macro_prefix,
+        spec,
+        name_ctx,
+        &typedef_doc(msg, name_ctx),
+    );
+    out.push('\n');
+    out
 }
 
-/// Generate typedef only for struct message (wrapper for generate_struct_typedef)
-fn generate_struct_typedef_for_types(
+/// Generates the typedefs of the variants of a union message and the
+/// message type holding the tag and the `variant` union of them.
+fn generate_union_typedef(
     msg: &MessageDefinition,
-    spec: &StructSpec,
+    spec: &UnionSpec,
     name_ctx: &NameContext,
 ) -> String {
     let mut out = String::new();
     let type_name = type_name(msg, name_ctx);
     let macro_prefix = msg_macro_prefix(name_ctx, msg);
-    generate_struct_typedef(&mut out, &type_name, &macro_prefix, spec);
-    out.push('\n');
+    let mut members = Vec::new();
+    for variant in &spec.variants {
+        let comment = member_comment(variant.description.as_deref(), None, name_ctx.doc_comments);
+        let member = to_snake_case(&variant.name);
+        if let Some(shared) = &variant.spec.shared {
+            members.push((shared_type_name(&name_ctx.helpers, shared), member, comment));
+            continue;
+        }
+        let variant_type = nested_struct_type_name(&type_name, &variant.name);
+        let doc = if name_ctx.doc_comments {
+            doc_block(&[format!(
+                "@brief Variant '{}' of {}, tag {}_{}_TAG",
+                variant.name,
+                type_name,
+                macro_prefix,
+                to_macro_ident(&variant.name)
+            )])
+        } else {
+            String::new()
+        };
+        generate_struct_typedef(
+            &mut out,
+            &variant_type,
+            &format!("{}_{}", macro_prefix, to_macro_ident(&variant.name)),
+            &variant.spec,
+            name_ctx,
+            &doc,
+        );
+        members.push((variant_type, member, comment));
+    }
+
+    out.push_str(&typedef_doc(msg, name_ctx));
+    writeln!(out, "{}", name_ctx.typedef_struct()).unwrap();
+    writeln!(
+        out,
+        "    {} tag;{}",
+        spec.tag.c_type(),
+        member_comment(
+            Some("Selects the member of variant"),
+            None,
+            name_ctx.doc_comments
+        )
+    )
+    .unwrap();
+    writeln!(out, "    union {{").unwrap();
+    for (variant_type, member, comment) in members {
+        writeln!(out, "        {} {};{}", variant_type, member, comment).unwrap();
+    }
+    writeln!(out, "    }} variant;\n}} {};\n", type_name).unwrap();
     out
 }
 
+/// Literal of a tag value of a union, parenthesized when negative so the
+/// `_TAG` macro expands safely.
+fn tag_literal(tag: i128, primitive: PrimitiveType, name_ctx: &NameContext) -> String {
+    let literal = bound_literal(Bound::Int(tag), primitive, name_ctx);
+    if tag < 0 {
+        format!("({})", literal)
+    } else {
+        literal
+    }
+}
+
+/// Prefix of the members of a union variant through `msg`, e.g.
+/// `msg->variant.gps.`.
+fn variant_accessor(variant: &UnionVariant) -> String {
+    format!("msg->variant.{}.", to_snake_case(&variant.name))
+}
+
 /// Generate functions only for scalar message (for _server.h/_client.h)
 fn generate_scalar_functions(
     msg: &MessageDefinition,
     spec: &ScalarSpec,
     mode: FunctionMode,
     name_ctx: &NameContext,
+    options: &CGenOptions,
+    emit: FnEmit,
 ) -> String {
     let mut out = String::new();
-    let type_name = type_name(msg, name_ctx);
-    let encode_name = encode_fn_name(msg, name_ctx);
-    let decode_name = decode_fn_name(msg, name_ctx);
     let size = spec.primitive.byte_len();
+    let checks = collect_range_checks(&msg.body);
 
     if mode == FunctionMode::EncodeOnly || mode == FunctionMode::Both {
+        out.push_str(&function_doc(
+            msg,
+            true,
+            name_ctx,
+            options.encode_range,
+            emit,
+        ));
         writeln!(
             &mut out,
-            "static inline size_t {}(const {} *msg, uint8_t *out_buf, const size_t out_len) {{",
-            encode_name, type_name
+            "{}{} {{",
+            fn_qualifiers(msg, emit, name_ctx.dialect),
+            encode_signature(msg, name_ctx)
         )
         .unwrap();
-        out.push_str("    if (!msg || !out_buf) {\n        return 0;\n    }\n");
+        name_ctx.write_null_guard(&mut out, &["msg", "out_buf"], &name_ctx.uint(0));
         writeln!(
             &mut out,
-            "    if (out_len < {}) {{\n        return 0;\n    }}",
-            size
+            "    if (out_len < {}) {{\n        return {};\n    }}",
+            name_ctx.uint(size),
+            name_ctx.uint(0)
         )
         .unwrap();
+        if options.encode_range == EncodeRange::Reject {
+            write_range_checks(&mut out, &checks, "    ", &name_ctx.uint(0), name_ctx);
+        }
+        let source = match checks.first() {
+            Some(check) if options.encode_range == EncodeRange::Clamp => {
+                clamp_expr(check, name_ctx)
+            }
+            _ => "msg->value".to_string(),
+        };
         out.push_str(&primitive_encode_stmt(
+            name_ctx,
             spec.primitive,
             spec.endian,
-            "msg->value",
+            &source,
             "out_buf",
             "    ",
         ));
-        writeln!(&mut out, "    return {};\n}}\n", size).unwrap();
+        writeln!(&mut out, "    return {};\n}}\n", name_ctx.uint(size)).unwrap();
     }
 
     if mode == FunctionMode::DecodeOnly || mode == FunctionMode::Both {
+        out.push_str(&function_doc(
+            msg,
+            false,
+            name_ctx,
+            options.encode_range,
+            emit,
+        ));
         writeln!(
             &mut out,
-            "static inline bool {}({} *msg, const uint8_t *data, const size_t data_len) {{",
-            decode_name, type_name
+            "{}{} {{",
+            fn_qualifiers(msg, emit, name_ctx.dialect),
+            decode_signature(msg, name_ctx)
         )
         .unwrap();
-        out.push_str("    if (!msg || !data) {\n        return false;\n    }\n");
+        name_ctx.write_null_guard(&mut out, &["msg", "data"], "false");
         writeln!(
             &mut out,
             "    if (data_len != {}) {{\n        return false;\n    }}",
-            size
+            name_ctx.uint(size)
         )
         .unwrap();
         out.push_str(&primitive_decode_stmt(
+            name_ctx,
             spec.primitive,
             spec.endian,
             "msg->value",
             "data",
             "    ",
         ));
+        write_range_checks(&mut out, &checks, "    ", "false", name_ctx);
         out.push_str("    return true;\n}\n\n");
     }
 
@@ -610,74 +3931,124 @@ fn generate_array_functions(
     spec: &ArraySpec,
     mode: FunctionMode,
     name_ctx: &NameContext,
+    options: &CGenOptions,
+    emit: FnEmit,
 ) -> String {
     let mut out = String::new();
-    let type_name = type_name(msg, name_ctx);
-    let encode_name = encode_fn_name(msg, name_ctx);
-    let decode_name = decode_fn_name(msg, name_ctx);
     let max_macro = format!("{}_MAX_LENGTH", msg_macro_prefix(name_ctx, msg));
     let elem_size = spec.primitive.byte_len();
+    let dialect = name_ctx.dialect;
 
     if mode == FunctionMode::EncodeOnly || mode == FunctionMode::Both {
+        out.push_str(&function_doc(
+            msg,
+            true,
+            name_ctx,
+            options.encode_range,
+            emit,
+        ));
         writeln!(
             &mut out,
-            "static inline size_t {}(const {} *msg, uint8_t *out_buf, const size_t out_len) {{",
-            encode_name, type_name
+            "{}{} {{",
+            fn_qualifiers(msg, emit, dialect),
+            encode_signature(msg, name_ctx)
         )
         .unwrap();
-        out.push_str("    if (!msg || !out_buf) {\n        return 0;\n    }\n");
+        if elem_size == 1 {
+            name_ctx.write_locals(&mut out, &["required"]);
+        } else {
+            name_ctx.write_locals(&mut out, &["required", "offset", "i"]);
+        }
+        name_ctx.write_null_guard(&mut out, &["msg", "out_buf"], &name_ctx.uint(0));
         writeln!(
             &mut out,
-            "    if (msg->length > {}) {{\n        return 0;\n    }}",
-            max_macro
+            "    if (msg->length > {}) {{\n        return {};\n    }}",
+            max_macro,
+            name_ctx.uint(0)
+        )
+        .unwrap();
+        writeln!(
+            &mut out,
+            "    {};",
+            dialect.init(
+                "size_t",
+                "required",
+                &format!("msg->length * {}", name_ctx.uint(elem_size))
+            )
         )
         .unwrap();
         writeln!(
             &mut out,
-            "    size_t required = msg->length * {};",
-            elem_size
+            "    if (out_len < required) {{\n        return {};\n    }}",
+            name_ctx.uint(0)
         )
         .unwrap();
-        out.push_str("    if (out_len < required) {\n        return 0;\n    }\n");
         if elem_size == 1 {
-            out.push_str(
-                "    if (required > 0) {\n        memcpy(out_buf, msg->data, required);\n    }\n",
-            );
+            writeln!(
+                &mut out,
+                "    if (required > {}) {{\n        {}memcpy(out_buf, msg->data, required);\n    }}",
+                name_ctx.uint(0),
+                name_ctx.discard()
+            )
+            .unwrap();
             out.push_str("    return required;\n}\n\n");
         } else {
-            out.push_str(
-                "    size_t offset = 0;\n    for (size_t i = 0; i < msg->length; ++i) {\n",
-            );
+            writeln!(
+                &mut out,
+                "    {};\n    {}i < msg->length; ++i) {{",
+                dialect.init("size_t", "offset", &name_ctx.uint(0)),
+                name_ctx.for_index()
+            )
+            .unwrap();
             out.push_str(&primitive_encode_stmt(
+                name_ctx,
                 spec.primitive,
                 spec.endian,
                 "msg->data[i]",
                 "out_buf + offset",
                 "        ",
             ));
-            writeln!(&mut out, "        offset += {};", elem_size).unwrap();
+            writeln!(&mut out, "        offset += {};", name_ctx.uint(elem_size)).unwrap();
             out.push_str("    }\n    return offset;\n}\n\n");
         }
     }
 
     if mode == FunctionMode::DecodeOnly || mode == FunctionMode::Both {
+        out.push_str(&function_doc(
+            msg,
+            false,
+            name_ctx,
+            options.encode_range,
+            emit,
+        ));
         writeln!(
             &mut out,
-            "static inline bool {}({} *msg, const uint8_t *data, const size_t data_len) {{",
-            decode_name, type_name
+            "{}{} {{",
+            fn_qualifiers(msg, emit, dialect),
+            decode_signature(msg, name_ctx)
         )
         .unwrap();
-        out.push_str("    if (!msg || !data) {\n        return false;\n    }\n");
+        if elem_size == 1 {
+            name_ctx.write_locals(&mut out, &["element_count"]);
+        } else {
+            name_ctx.write_locals(&mut out, &["element_count", "offset", "i"]);
+        }
+        name_ctx.write_null_guard(&mut out, &["msg", "data"], "false");
         writeln!(
             &mut out,
-            "    if (data_len % {} != 0) {{\n        return false;\n    }}",
-            elem_size
+            "    if (data_len % {} != {}) {{\n        return false;\n    }}",
+            name_ctx.uint(elem_size),
+            name_ctx.uint(0)
         )
         .unwrap();
         writeln!(
             &mut out,
-            "    size_t element_count = data_len / {};",
-            elem_size
+            "    {};",
+            dialect.init(
+                "size_t",
+                "element_count",
+                &format!("data_len / {}", name_ctx.uint(elem_size))
+            )
         )
         .unwrap();
         writeln!(
@@ -687,37 +4058,391 @@ fn generate_array_functions(
         )
         .unwrap();
         out.push_str("    msg->length = element_count;\n");
-        out.push_str("    if (element_count == 0) {\n");
+        if name_ctx.deterministic_decode {
+            write_zero_tail(
+                &mut out,
+                name_ctx,
+                "msg->data",
+                "element_count",
+                &max_macro,
+                "    ",
+            );
+        }
+        writeln!(
+            &mut out,
+            "    if (element_count == {}) {{",
+            name_ctx.uint(0)
+        )
+        .unwrap();
         if spec.primitive == PrimitiveType::Char {
-            out.push_str("        if (");
-            out.push_str(&max_macro);
-            out.push_str(" > 0) {\n            msg->data[0] = '\\0';\n        }\n");
+            out.push_str("        msg->data[0] = '\\0';\n");
         }
         out.push_str("        return true;\n    }\n");
         if elem_size == 1 {
-            out.push_str("    memcpy(msg->data, data, element_count);\n");
+            writeln!(
+                &mut out,
+                "    {}memcpy(msg->data, data, element_count);",
+                name_ctx.discard()
+            )
+            .unwrap();
         } else {
-            out.push_str(
-                "    size_t offset = 0;\n    for (size_t i = 0; i < element_count; ++i) {\n",
-            );
+            writeln!(
+                &mut out,
+                "    {};\n    {}i < element_count; ++i) {{",
+                dialect.init("size_t", "offset", &name_ctx.uint(0)),
+                name_ctx.for_index()
+            )
+            .unwrap();
             out.push_str(&primitive_decode_stmt(
+                name_ctx,
                 spec.primitive,
                 spec.endian,
                 "msg->data[i]",
                 "data + offset",
                 "        ",
             ));
-            writeln!(&mut out, "        offset += {};", elem_size).unwrap();
+            writeln!(&mut out, "        offset += {};", name_ctx.uint(elem_size)).unwrap();
             out.push_str("    }\n");
         }
         if spec.primitive == PrimitiveType::Char {
-            out.push_str("    if (element_count < ");
-            out.push_str(&max_macro);
-            out.push_str(") {\n        msg->data[element_count] = '\\0';\n    }\n");
+            out.push_str("    msg->data[element_count] = '\\0';\n");
         }
         out.push_str("    return true;\n}\n\n");
     }
-
+    if spec.sector_bytes.is_some() {
+        out.push_str(&generate_sector_functions(msg, spec, mode, name_ctx, emit));
+    }
+
+    out
+}
+
+/// Generates the sector transfer functions of an array message with
+/// `sector_bytes`: the encoding side splits the payload into sectors of
+/// `_SECTOR_BYTES` (the last one may be short), the decoding side stores
+/// each sector in place and records it in `sectors_received`.
+fn generate_sector_functions(
+    msg: &MessageDefinition,
+    spec: &ArraySpec,
+    mode: FunctionMode,
+    name_ctx: &NameContext,
+    emit: FnEmit,
+) -> String {
+    let mut out = String::new();
+    let type_name = type_name(msg, name_ctx);
+    let fn_prefix = name_ctx.msg_fn_prefix(&msg.name);
+    let macro_prefix = msg_macro_prefix(name_ctx, msg);
+    let dialect = name_ctx.dialect;
+    let qualifiers = fn_qualifiers(msg, emit, dialect);
+    let elem_size = spec.primitive.byte_len();
+    // Elements per sector; sector_bytes is a multiple of the element size
+    let per_sector = format!(
+        "({}_SECTOR_BYTES / {})",
+        macro_prefix,
+        name_ctx.uint(elem_size)
+    );
+    let sectors_of_length = format!(
+        "(msg->length * {} + {m}_SECTOR_BYTES - {}) / {m}_SECTOR_BYTES",
+        name_ctx.uint(elem_size),
+        name_ctx.uint(1),
+        m = macro_prefix
+    );
+    let documented = name_ctx.doc_comments && emit != FnEmit::Definition;
+    let lines = |lines: &[&str]| {
+        lines
+            .iter()
+            .map(|line| line.to_string())
+            .collect::<Vec<_>>()
+    };
+    let mut function = |doc: Vec<String>, signature: String, body: &dyn Fn(&mut String)| {
+        if documented {
+            out.push_str(&doc_block(&doc));
+        }
+        if emit == FnEmit::Prototype {
+            writeln!(&mut out, "{}{};", qualifiers, signature).unwrap();
+        } else {
+            writeln!(&mut out, "{}{} {{", qualifiers, signature).unwrap();
+            body(&mut out);
+            out.push_str("}\n\n");
+        }
+    };
+
+    if mode != FunctionMode::DecodeOnly {
+        function(
+            lines(&[
+                "@brief Number of sectors needed to send @p msg.",
+                "@return The sector count, or 0 if @p msg is NULL.",
+            ]),
+            format!(
+                "size_t {}_sector_count(const {} *msg)",
+                fn_prefix, type_name
+            ),
+            &|out| {
+                name_ctx.write_null_guard(out, &["msg"], &name_ctx.uint(0));
+                writeln!(out, "    return {};", sectors_of_length).unwrap();
+            },
+        );
+        function(
+            vec![
+                "@brief Encodes sector @p sector_index of @p msg into @p out_buf.".to_string(),
+                "@param msg Message to encode.".to_string(),
+                "@param sector_index Index of the sector, from 0.".to_string(),
+                "@param out_buf Buffer receiving the wire bytes.".to_string(),
+                "@param out_len Size of @p out_buf in bytes.".to_string(),
+                "@return Number of bytes written, or 0 when:".to_string(),
+                "  - @p msg or @p out_buf is NULL".to_string(),
+                format!("  - msg->length exceeds {}_MAX_LENGTH", macro_prefix),
+                "  - @p sector_index is not below the sector count".to_string(),
+                "  - @p out_len is less than the sector".to_string(),
+            ],
+            format!(
+                "size_t {}_encode_sector(const {} *msg, const size_t sector_index, uint8_t *out_buf, const size_t out_len)",
+                fn_prefix, type_name
+            ),
+            &|out| {
+                if elem_size == 1 {
+                    name_ctx.write_locals(out, &["first", "count", "required"]);
+                } else {
+                    name_ctx.write_locals(out, &["first", "count", "required", "i"]);
+                }
+                name_ctx.write_null_guard(out, &["msg", "out_buf"], &name_ctx.uint(0));
+                writeln!(
+                    out,
+                    "    if (msg->length > {}_MAX_LENGTH || sector_index >= {}) {{\n        return {};\n    }}",
+                    macro_prefix,
+                    sectors_of_length,
+                    name_ctx.uint(0)
+                )
+                .unwrap();
+                writeln!(
+                    out,
+                    "    {};",
+                    dialect.init("size_t", "first", &format!("sector_index * {}", per_sector))
+                )
+                .unwrap();
+                writeln!(
+                    out,
+                    "    {};",
+                    dialect.init("size_t", "count", "msg->length - first")
+                )
+                .unwrap();
+                writeln!(
+                    out,
+                    "    if (count > {p}) {{\n        count = {p};\n    }}",
+                    p = per_sector
+                )
+                .unwrap();
+                writeln!(
+                    out,
+                    "    {};",
+                    dialect.init(
+                        "size_t",
+                        "required",
+                        &format!("count * {}", name_ctx.uint(elem_size))
+                    )
+                )
+                .unwrap();
+                writeln!(
+                    out,
+                    "    if (out_len < required) {{\n        return {};\n    }}",
+                    name_ctx.uint(0)
+                )
+                .unwrap();
+                if elem_size == 1 {
+                    writeln!(
+                        out,
+                        "    {}memcpy(out_buf, msg->data + first, required);",
+                        name_ctx.discard()
+                    )
+                    .unwrap();
+                } else {
+                    writeln!(out, "    {}i < count; ++i) {{", name_ctx.for_index()).unwrap();
+                    out.push_str(&primitive_encode_stmt(
+                        name_ctx,
+                        spec.primitive,
+                        spec.endian,
+                        "msg->data[first + i]",
+                        &format!("out_buf + i * {}", name_ctx.uint(elem_size)),
+                        "        ",
+                    ));
+                    out.push_str("    }\n");
+                }
+                out.push_str("    return required;\n");
+            },
+        );
+    }
+
+    if mode != FunctionMode::EncodeOnly {
+        let mut decode_doc = vec![
+            "@brief Stores sector @p sector_index, received in @p data, and marks it in msg->sectors_received.".to_string(),
+            "@param msg Receives the sector.".to_string(),
+            "@param sector_index Index of the sector, from 0.".to_string(),
+            "@param data Received payload.".to_string(),
+            "@param data_len Length of @p data in bytes.".to_string(),
+            "@return true on success, or false when:".to_string(),
+            "  - @p msg or @p data is NULL".to_string(),
+        ];
+        decode_doc.push(match elem_size {
+            1 => format!(
+                "  - @p data_len is 0 or exceeds {}_SECTOR_BYTES",
+                macro_prefix
+            ),
+            size => format!(
+                "  - @p data_len is 0, exceeds {}_SECTOR_BYTES or is not a multiple of {}",
+                macro_prefix, size
+            ),
+        });
+        decode_doc.extend([
+            format!(
+                "  - @p sector_index is not below {}_SECTOR_COUNT",
+                macro_prefix
+            ),
+            format!("  - the sector extends past {}_MAX_LENGTH", macro_prefix),
+            format!(
+                "  - the sector is shorter than {}_SECTOR_BYTES, which makes it the last one, but a later sector was stored",
+                macro_prefix
+            ),
+            "  - a short sector before @p sector_index was stored, so the message already ended".to_string(),
+        ]);
+        function(
+            decode_doc,
+            format!(
+                "bool {}_decode_sector({} *msg, const size_t sector_index, const uint8_t *data, const size_t data_len)",
+                fn_prefix, type_name
+            ),
+            &|out| {
+                if elem_size == 1 {
+                    name_ctx.write_locals(out, &["first", "count"]);
+                } else {
+                    name_ctx.write_locals(out, &["first", "count", "i"]);
+                }
+                name_ctx.write_null_guard(out, &["msg", "data"], "false");
+                writeln!(
+                    out,
+                    "    if (data_len == {z} || data_len > {}_SECTOR_BYTES || data_len % {} != {z}) {{\n        return false;\n    }}",
+                    macro_prefix,
+                    name_ctx.uint(elem_size),
+                    z = name_ctx.uint(0)
+                )
+                .unwrap();
+                writeln!(
+                    out,
+                    "    if (sector_index >= {}_SECTOR_COUNT) {{\n        return false;\n    }}",
+                    macro_prefix
+                )
+                .unwrap();
+                writeln!(
+                    out,
+                    "    {};",
+                    dialect.init("size_t", "first", &format!("sector_index * {}", per_sector))
+                )
+                .unwrap();
+                writeln!(
+                    out,
+                    "    {};",
+                    dialect.init(
+                        "size_t",
+                        "count",
+                        &format!("data_len / {}", name_ctx.uint(elem_size))
+                    )
+                )
+                .unwrap();
+                writeln!(
+                    out,
+                    "    if (count > {}_MAX_LENGTH - first) {{\n        return false;\n    }}",
+                    macro_prefix
+                )
+                .unwrap();
+                // Only the last sector of a message may be short: a short
+                // sector cannot precede one already stored, and a stored
+                // length that ends mid-sector ends the message
+                writeln!(
+                    out,
+                    "    if (count < {p} && msg->length > first + count) {{\n        return false;\n    }}",
+                    p = per_sector
+                )
+                .unwrap();
+                writeln!(
+                    out,
+                    "    if (msg->length % {p} != {z} && msg->length < first) {{\n        return false;\n    }}",
+                    p = per_sector,
+                    z = name_ctx.uint(0)
+                )
+                .unwrap();
+                if elem_size == 1 {
+                    writeln!(
+                        out,
+                        "    {}memcpy(msg->data + first, data, count);",
+                        name_ctx.discard()
+                    )
+                    .unwrap();
+                } else {
+                    writeln!(out, "    {}i < count; ++i) {{", name_ctx.for_index()).unwrap();
+                    out.push_str(&primitive_decode_stmt(
+                        name_ctx,
+                        spec.primitive,
+                        spec.endian,
+                        "msg->data[first + i]",
+                        &format!("data + i * {}", name_ctx.uint(elem_size)),
+                        "        ",
+                    ));
+                    out.push_str("    }\n");
+                }
+                out.push_str(
+                    "    if (first + count > msg->length) {\n        msg->length = first + count;\n    }\n",
+                );
+                if spec.primitive == PrimitiveType::Char {
+                    out.push_str("    msg->data[msg->length] = '\\0';\n");
+                }
+                writeln!(
+                    out,
+                    "    msg->sectors_received[sector_index / {e}] |= (uint8_t)({o} << (sector_index % {e}));",
+                    e = name_ctx.uint(8),
+                    o = if name_ctx.misra { "1U" } else { "1u" }
+                )
+                .unwrap();
+                out.push_str("    return true;\n");
+            },
+        );
+        function(
+            lines(&[
+                "@brief Whether every sector up to msg->length has been received.",
+                "@return false also when @p msg is NULL or msg->length is 0.",
+            ]),
+            format!(
+                "bool {}_sectors_complete(const {} *msg)",
+                fn_prefix, type_name
+            ),
+            &|out| {
+                name_ctx.write_locals(out, &["i"]);
+                if name_ctx.misra {
+                    out.push_str(
+                        "    if ((msg == NULL) || (msg->length == 0U)) {\n        return false;\n    }\n",
+                    );
+                } else {
+                    out.push_str(
+                        "    if (!msg || msg->length == 0) {\n        return false;\n    }\n",
+                    );
+                }
+                writeln!(
+                    out,
+                    "    {}i < {}; ++i) {{",
+                    name_ctx.for_index(),
+                    sectors_of_length
+                )
+                .unwrap();
+                if name_ctx.misra {
+                    out.push_str(
+                        "        if ((msg->sectors_received[i / 8U] & (1U << (i % 8U))) == 0U) {\n            return false;\n        }\n",
+                    );
+                } else {
+                    out.push_str(
+                        "        if (!(msg->sectors_received[i / 8] & (1u << (i % 8)))) {\n            return false;\n        }\n",
+                    );
+                }
+                out.push_str("    }\n    return true;\n");
+            },
+        );
+    }
     out
 }
 
@@ -727,79 +4452,183 @@ fn generate_struct_functions(
     spec: &StructSpec,
     mode: FunctionMode,
     name_ctx: &NameContext,
+    options: &CGenOptions,
+    emit: FnEmit,
 ) -> String {
     let mut out = String::new();
+    let fast_path = options.fast_path && struct_fast_path_eligible(spec);
+    let checks = collect_range_checks(&msg.body);
+    let clamp = options.encode_range == EncodeRange::Clamp;
     let type_name = type_name(msg, name_ctx);
-    let encode_name = encode_fn_name(msg, name_ctx);
-    let decode_name = decode_fn_name(msg, name_ctx);
-    let macro_prefix = msg_macro_prefix(name_ctx, msg);
+    let wire_type = wire_mirror_type_name(&type_name);
+    let macro_prefix =
+        fields_macro_prefix(&name_ctx.helpers, spec, msg_macro_prefix(name_ctx, msg));
 
     let has_variable_arrays = struct_has_variable_arrays(spec);
+    let has_arrays = struct_has_arrays(spec);
     let max_size = struct_byte_len(spec);
     let min_size = struct_min_byte_len(spec);
+    let dialect = name_ctx.dialect;
 
     if mode == FunctionMode::EncodeOnly || mode == FunctionMode::Both {
+        out.push_str(&function_doc(
+            msg,
+            true,
+            name_ctx,
+            options.encode_range,
+            emit,
+        ));
+        writeln!(
+            &mut out,
+            "{}{} {{",
+            fn_qualifiers(msg, emit, dialect),
+            encode_signature(msg, name_ctx)
+        )
+        .unwrap();
+        if has_arrays {
+            name_ctx.write_locals(&mut out, &["offset", "i"]);
+        } else {
+            name_ctx.write_locals(&mut out, &["offset"]);
+        }
+        name_ctx.write_null_guard(&mut out, &["msg", "out_buf"], &name_ctx.uint(0));
+        // The lengths set the encoded size, so they are checked first
+        let arrays = variable_arrays(name_ctx, &spec.fields, "msg->", &macro_prefix);
+        for array in &arrays {
+            writeln!(
+                &mut out,
+                "    if ({} > {}) {{\n        return {};\n    }}",
+                array.length,
+                array.max_macro,
+                name_ctx.uint(0)
+            )
+            .unwrap();
+        }
         writeln!(
             &mut out,
-            "static inline size_t {}(const {} *msg, uint8_t *out_buf, const size_t out_len) {{",
-            encode_name, type_name
+            "    if (out_len < {}) {{\n        return {};\n    }}",
+            encoded_size_expr(min_size, &arrays, &|n| name_ctx.uint(n)),
+            name_ctx.uint(0)
         )
         .unwrap();
-        out.push_str("    if (!msg || !out_buf) {\n        return 0;\n    }\n");
+        if options.encode_range == EncodeRange::Reject {
+            write_range_checks(&mut out, &checks, "    ", &name_ctx.uint(0), name_ctx);
+        }
+        // A memcpy cannot clamp
+        if fast_path && (!clamp || checks.is_empty()) {
+            writeln!(
+                &mut out,
+                "#if H6XSERIAL_HOST_LITTLE_ENDIAN\n    if (sizeof({t}) == sizeof({w})) {{\n        {d}memcpy(out_buf, msg, sizeof({w}));\n        return sizeof({w});\n    }}\n#endif",
+                t = type_name,
+                w = wire_type,
+                d = name_ctx.discard()
+            )
+            .unwrap();
+        }
         writeln!(
             &mut out,
-            "    if (out_len < {}) {{\n        return 0;\n    }}",
-            max_size
+            "    {};",
+            dialect.init("size_t", "offset", &name_ctx.uint(0))
         )
         .unwrap();
-        out.push_str("    size_t offset = 0;\n");
-        generate_field_encode_stmts(&mut out, &spec.fields, "msg->", &macro_prefix, "    ");
+        generate_field_encode_stmts(
+            &mut out,
+            name_ctx,
+            &spec.fields,
+            "msg->",
+            &macro_prefix,
+            "    ",
+            clamp,
+        );
         out.push_str("    return offset;\n}\n\n");
     }
 
     if mode == FunctionMode::DecodeOnly || mode == FunctionMode::Both {
+        out.push_str(&function_doc(
+            msg,
+            false,
+            name_ctx,
+            options.encode_range,
+            emit,
+        ));
         writeln!(
             &mut out,
-            "static inline bool {}({} *msg, const uint8_t *data, const size_t data_len) {{",
-            decode_name, type_name
+            "{}{} {{",
+            fn_qualifiers(msg, emit, dialect),
+            decode_signature(msg, name_ctx)
         )
         .unwrap();
-        out.push_str("    if (!msg || !data) {\n        return false;\n    }\n");
+        if has_variable_arrays {
+            name_ctx.write_locals(&mut out, &["offset", "remaining", "i"]);
+        } else if has_arrays {
+            name_ctx.write_locals(&mut out, &["offset", "i"]);
+        } else {
+            name_ctx.write_locals(&mut out, &["offset"]);
+        }
+        name_ctx.write_null_guard(&mut out, &["msg", "data"], "false");
 
         if has_variable_arrays {
             writeln!(
                 &mut out,
                 "    if (data_len < {}) {{\n        return false;\n    }}",
-                min_size
+                name_ctx.uint(min_size)
             )
             .unwrap();
             writeln!(
                 &mut out,
                 "    if (data_len > {}) {{\n        return false;\n    }}",
-                max_size
+                name_ctx.uint(max_size)
+            )
+            .unwrap();
+            writeln!(
+                &mut out,
+                "    {};",
+                dialect.init("size_t", "offset", &name_ctx.uint(0))
+            )
+            .unwrap();
+            writeln!(
+                &mut out,
+                "    {};",
+                dialect.init("size_t", "remaining", "data_len")
             )
             .unwrap();
-            out.push_str("    size_t offset = 0;\n");
-            out.push_str("    size_t remaining = data_len;\n");
-            writeln!(&mut out, "    remaining -= {};", min_size).unwrap();
+            writeln!(&mut out, "    remaining -= {};", name_ctx.uint(min_size)).unwrap();
             generate_field_decode_stmts(
                 &mut out,
+                name_ctx,
                 &spec.fields,
                 "msg->",
                 &macro_prefix,
                 "    ",
-                Some("remaining"),
+                Some(Remaining::all("remaining", name_ctx.deterministic_decode)),
             );
         } else {
             writeln!(
                 &mut out,
                 "    if (data_len != {}) {{\n        return false;\n    }}",
-                max_size
+                name_ctx.uint(max_size)
+            )
+            .unwrap();
+            if fast_path {
+                writeln!(
+                    &mut out,
+                    "#if H6XSERIAL_HOST_LITTLE_ENDIAN\n    if (sizeof({t}) == sizeof({w})) {{\n        {d}memcpy(msg, data, sizeof({w}));",
+                    t = type_name,
+                    w = wire_type,
+                    d = name_ctx.discard()
+                )
+                .unwrap();
+                write_range_checks(&mut out, &checks, "        ", "false", name_ctx);
+                out.push_str("        return true;\n    }\n#endif\n");
+            }
+            writeln!(
+                &mut out,
+                "    {};",
+                dialect.init("size_t", "offset", &name_ctx.uint(0))
             )
             .unwrap();
-            out.push_str("    size_t offset = 0;\n");
             generate_field_decode_stmts(
                 &mut out,
+                name_ctx,
                 &spec.fields,
                 "msg->",
                 &macro_prefix,
@@ -807,212 +4636,661 @@ fn generate_struct_functions(
                 None,
             );
         }
+        write_range_checks(&mut out, &checks, "    ", "false", name_ctx);
         out.push_str("    return true;\n}\n\n");
     }
 
     out
 }
 
-fn generate_scalar_block(
+/// Generates the encode/decode functions of a union message: the tag,
+/// then the fields of the variant it selects. Unknown tags fail both ways.
+fn generate_union_functions(
     msg: &MessageDefinition,
-    spec: &ScalarSpec,
+    spec: &UnionSpec,
     mode: FunctionMode,
     name_ctx: &NameContext,
+    options: &CGenOptions,
+    emit: FnEmit,
 ) -> String {
     let mut out = String::new();
-    let type_name = type_name(msg, name_ctx);
-    let encode_name = encode_fn_name(msg, name_ctx);
-    let decode_name = decode_fn_name(msg, name_ctx);
-
-    writeln!(
-        &mut out,
-        "typedef struct {{\n    {} value;\n}} {};\n",
-        spec.primitive.c_type(),
-        type_name
-    )
-    .unwrap();
-
-    let size = spec.primitive.byte_len();
+    let clamp = options.encode_range == EncodeRange::Clamp;
+    let macro_prefix = msg_macro_prefix(name_ctx, msg);
+    let dialect = name_ctx.dialect;
+    let tag = [spec.tag_field()];
+    let tag_len = spec.tag.byte_len();
+    let has_arrays = spec
+        .variants
+        .iter()
+        .any(|variant| struct_has_arrays(&variant.spec));
+    let has_variable_arrays = spec
+        .variants
+        .iter()
+        .any(|variant| struct_has_variable_arrays(&variant.spec));
+    // Macro prefix, member accessor and range checks of every variant
+    let variants: Vec<(&UnionVariant, String, String, Vec<RangeCheck>)> = spec
+        .variants
+        .iter()
+        .map(|variant| {
+            let fields_prefix = fields_macro_prefix(
+                &name_ctx.helpers,
+                &variant.spec,
+                format!("{}_{}", macro_prefix, to_macro_ident(&variant.name)),
+            );
+            let accessor = variant_accessor(variant);
+            let checks = collect_struct_range_checks(&variant.spec, &accessor);
+            (variant, fields_prefix, accessor, checks)
+        })
+        .collect();
+    let case_label = |variant: &UnionVariant| {
+        format!(
+            "    case {}_{}_TAG:",
+            macro_prefix,
+            to_macro_ident(&variant.name)
+        )
+    };
 
-    // Generate encode function if needed
     if mode == FunctionMode::EncodeOnly || mode == FunctionMode::Both {
+        out.push_str(&function_doc(
+            msg,
+            true,
+            name_ctx,
+            options.encode_range,
+            emit,
+        ));
         writeln!(
             &mut out,
-            "static inline size_t {}(const {} *msg, uint8_t *out_buf, const size_t out_len) {{",
-            encode_name, type_name
+            "{}{} {{",
+            fn_qualifiers(msg, emit, dialect),
+            encode_signature(msg, name_ctx)
         )
         .unwrap();
-        out.push_str("    if (!msg || !out_buf) {\n        return 0;\n    }\n");
+        if has_arrays {
+            name_ctx.write_locals(&mut out, &["offset", "i"]);
+        } else {
+            name_ctx.write_locals(&mut out, &["offset"]);
+        }
+        name_ctx.write_null_guard(&mut out, &["msg", "out_buf"], &name_ctx.uint(0));
         writeln!(
             &mut out,
-            "    if (out_len < {}) {{\n        return 0;\n    }}",
-            size
+            "    {};",
+            dialect.init("size_t", "offset", &name_ctx.uint(0))
         )
         .unwrap();
-        out.push_str(&primitive_encode_stmt(
-            spec.primitive,
-            spec.endian,
-            "msg->value",
-            "out_buf",
-            "    ",
-        ));
-        writeln!(&mut out, "    return {};\n}}\n", size).unwrap();
+        out.push_str("    switch (msg->tag) {\n");
+        for (variant, fields_prefix, accessor, checks) in &variants {
+            writeln!(&mut out, "{}", case_label(variant)).unwrap();
+            // As for a struct: the lengths of the variant, then the size
+            // they add up to, all before the first byte is written
+            let arrays = variable_arrays(name_ctx, &variant.spec.fields, accessor, fields_prefix);
+            for array in &arrays {
+                writeln!(
+                    &mut out,
+                    "        if ({} > {}) {{\n            return {};\n        }}",
+                    array.length,
+                    array.max_macro,
+                    name_ctx.uint(0)
+                )
+                .unwrap();
+            }
+            writeln!(
+                &mut out,
+                "        if (out_len < {}) {{\n            return {};\n        }}",
+                encoded_size_expr(
+                    tag_len + struct_min_byte_len(&variant.spec),
+                    &arrays,
+                    &|n| name_ctx.uint(n)
+                ),
+                name_ctx.uint(0)
+            )
+            .unwrap();
+            if options.encode_range == EncodeRange::Reject {
+                write_range_checks(&mut out, checks, "        ", &name_ctx.uint(0), name_ctx);
+            }
+            generate_field_encode_stmts(
+                &mut out,
+                name_ctx,
+                &tag,
+                "msg->",
+                &macro_prefix,
+                "        ",
+                false,
+            );
+            generate_field_encode_stmts(
+                &mut out,
+                name_ctx,
+                &variant.spec.fields,
+                accessor,
+                fields_prefix,
+                "        ",
+                clamp,
+            );
+            out.push_str("        break;\n");
+        }
+        writeln!(
+            &mut out,
+            "    default:\n        return {};\n    }}",
+            name_ctx.uint(0)
+        )
+        .unwrap();
+        out.push_str("    return offset;\n}\n\n");
     }
 
-    // Generate decode function if needed
     if mode == FunctionMode::DecodeOnly || mode == FunctionMode::Both {
+        out.push_str(&function_doc(
+            msg,
+            false,
+            name_ctx,
+            options.encode_range,
+            emit,
+        ));
         writeln!(
             &mut out,
-            "static inline bool {}({} *msg, const uint8_t *data, const size_t data_len) {{",
-            decode_name, type_name
+            "{}{} {{",
+            fn_qualifiers(msg, emit, dialect),
+            decode_signature(msg, name_ctx)
         )
         .unwrap();
-        out.push_str("    if (!msg || !data) {\n        return false;\n    }\n");
+        let mut locals = vec!["offset"];
+        if has_variable_arrays {
+            locals.push("remaining");
+        }
+        if has_arrays {
+            locals.push("i");
+        }
+        name_ctx.write_locals(&mut out, &locals);
+        name_ctx.write_null_guard(&mut out, &["msg", "data"], "false");
         writeln!(
             &mut out,
-            "    if (data_len != {}) {{\n        return false;\n    }}",
-            size
+            "    if (data_len < {}) {{\n        return false;\n    }}",
+            name_ctx.uint(tag_len)
         )
         .unwrap();
-        out.push_str(&primitive_decode_stmt(
-            spec.primitive,
-            spec.endian,
-            "msg->value",
-            "data",
+        writeln!(
+            &mut out,
+            "    {};",
+            dialect.init("size_t", "offset", &name_ctx.uint(0))
+        )
+        .unwrap();
+        if has_variable_arrays {
+            writeln!(
+                &mut out,
+                "    {};",
+                dialect.init("size_t", "remaining", "data_len")
+            )
+            .unwrap();
+        }
+        generate_field_decode_stmts(
+            &mut out,
+            name_ctx,
+            &tag,
+            "msg->",
+            &macro_prefix,
             "    ",
-        ));
+            None,
+        );
+        // Leaves no stale bytes of a longer variant or past an array length
+        if name_ctx.deterministic_decode && (variants.len() > 1 || has_variable_arrays) {
+            writeln!(
+                &mut out,
+                "    {}memset(&msg->variant, 0, sizeof(msg->variant));",
+                name_ctx.discard()
+            )
+            .unwrap();
+        }
+        out.push_str("    switch (msg->tag) {\n");
+        for (variant, fields_prefix, accessor, checks) in &variants {
+            writeln!(&mut out, "{}", case_label(variant)).unwrap();
+            let min_size = tag_len + struct_min_byte_len(&variant.spec);
+            let max_size = tag_len + struct_byte_len(&variant.spec);
+            let remaining = if struct_has_variable_arrays(&variant.spec) {
+                // The tag has been checked already
+                if min_size > tag_len {
+                    writeln!(
+                        &mut out,
+                        "        if (data_len < {}) {{\n            return false;\n        }}",
+                        name_ctx.uint(min_size)
+                    )
+                    .unwrap();
+                }
+                writeln!(
+                    &mut out,
+                    "        if (data_len > {}) {{\n            return false;\n        }}",
+                    name_ctx.uint(max_size)
+                )
+                .unwrap();
+                writeln!(
+                    &mut out,
+                    "        remaining -= {};",
+                    name_ctx.uint(min_size)
+                )
+                .unwrap();
+                Some("remaining")
+            } else {
+                writeln!(
+                    &mut out,
+                    "        if (data_len != {}) {{\n            return false;\n        }}",
+                    name_ctx.uint(max_size)
+                )
+                .unwrap();
+                None
+            };
+            generate_field_decode_stmts(
+                &mut out,
+                name_ctx,
+                &variant.spec.fields,
+                accessor,
+                fields_prefix,
+                "        ",
+                // Zeroed with the whole variant above
+                remaining.map(|var| Remaining::all(var, false)),
+            );
+            write_range_checks(&mut out, checks, "        ", "false", name_ctx);
+            out.push_str("        break;\n");
+        }
+        out.push_str("    default:\n        return false;\n    }\n");
         out.push_str("    return true;\n}\n\n");
     }
 
     out
 }
 
-fn generate_array_block(
+/// Generates the decoders of a message with a `msg_version`, which take
+/// the version byte of the frame: `_decode_versioned` rejects any other
+/// version, and `_decode_compat` of a struct message also decodes older
+/// versions, whose fields are a prefix of the current ones, zeroing the
+/// fields appended since.
+fn generate_version_functions(
     msg: &MessageDefinition,
-    spec: &ArraySpec,
     mode: FunctionMode,
     name_ctx: &NameContext,
+    emit: FnEmit,
 ) -> String {
     let mut out = String::new();
+    let Some(version) = msg.msg_version else {
+        return out;
+    };
+    if mode == FunctionMode::EncodeOnly {
+        return out;
+    }
     let type_name = type_name(msg, name_ctx);
-    let encode_name = encode_fn_name(msg, name_ctx);
-    let decode_name = decode_fn_name(msg, name_ctx);
-    let max_macro = format!("{}_MAX_LENGTH", msg_macro_prefix(name_ctx, msg));
-
-    writeln!(
-        &mut out,
-        "typedef struct {{\n    size_t length;\n    {} data[{}];\n}} {};\n",
-        spec.primitive.c_type(),
-        max_macro,
+    let fn_prefix = name_ctx.msg_fn_prefix(&msg.name);
+    let macro_prefix = msg_macro_prefix(name_ctx, msg);
+    let decode = decode_fn_name(msg, name_ctx);
+    let dialect = name_ctx.dialect;
+    let qualifiers = fn_qualifiers(msg, emit, dialect);
+    let documented = name_ctx.doc_comments && emit != FnEmit::Definition;
+    let deprecated = msg.deprecated.is_some() && emit != FnEmit::Prototype;
+    let params = format!(
+        "{} *msg, const uint8_t version, const uint8_t *data, const size_t data_len",
         type_name
-    )
-    .unwrap();
+    );
+    let mut function = |doc: Vec<String>, signature: String, body: &dyn Fn(&mut String)| {
+        // The decoder of a deprecated message is deprecated too
+        if deprecated {
+            out.push_str("H6XSERIAL_ALLOW_DEPRECATED_BEGIN\n");
+        }
+        if documented {
+            out.push_str(&doc_block(&doc));
+        }
+        if emit == FnEmit::Prototype {
+            writeln!(&mut out, "{}{};", qualifiers, signature).unwrap();
+            return;
+        }
+        writeln!(&mut out, "{}{} {{", qualifiers, signature).unwrap();
+        body(&mut out);
+        out.push_str("}\n");
+        if deprecated {
+            out.push_str("H6XSERIAL_ALLOW_DEPRECATED_END\n");
+        }
+        out.push('\n');
+    };
+
+    function(
+        vec![
+            message_brief(msg),
+            String::new(),
+            format!(
+                "Decodes @p data into @p msg when it was sent as version {}_VERSION ({}).",
+                macro_prefix, version
+            ),
+            "@param msg Receives the decoded message.".to_string(),
+            "@param version Message version of the frame.".to_string(),
+            "@param data Received payload.".to_string(),
+            "@param data_len Length of @p data in bytes.".to_string(),
+            format!(
+                "@return false when @p version is not {}_VERSION, otherwise the result of {}().",
+                macro_prefix, decode
+            ),
+        ],
+        format!("bool {}_decode_versioned({})", fn_prefix, params),
+        &|out| {
+            writeln!(
+                out,
+                "    if (version != {}_VERSION) {{\n        return false;\n    }}",
+                macro_prefix
+            )
+            .unwrap();
+            writeln!(out, "    return {}(msg, data, data_len);", decode).unwrap();
+        },
+    );
+
+    let MessageBody::Struct(spec) = &msg.body else {
+        return out;
+    };
+    if version == 1 {
+        return out;
+    }
+    // Older versions by the number of fields they have, the versions with
+    // every field going to the current decoder
+    let mut layouts: Vec<(Vec<u8>, usize)> = Vec::new();
+    for older in 1..version {
+        let count = spec
+            .fields
+            .iter()
+            .take_while(|field| field.since_version.unwrap_or(1) <= older)
+            .count();
+        match layouts.last_mut() {
+            Some((versions, last)) if *last == count => versions.push(older),
+            _ => layouts.push((vec![older], count)),
+        }
+    }
+    let current: Vec<u8> = match layouts.last() {
+        Some((versions, count)) if *count == spec.fields.len() => {
+            let versions = versions.clone();
+            layouts.pop();
+            versions
+        }
+        _ => Vec::new(),
+    };
+    let macro_fields = fields_macro_prefix(&name_ctx.helpers, spec, macro_prefix.clone());
+    let prefixes: Vec<(Vec<u8>, StructSpec)> = layouts
+        .into_iter()
+        .map(|(versions, count)| {
+            let prefix = StructSpec {
+                fields: spec.fields[..count].to_vec(),
+                shared: None,
+            };
+            (versions, prefix)
+        })
+        .collect();
+    function(
+        vec![
+            message_brief(msg),
+            String::new(),
+            "Decodes @p data, sent as version @p version of the message, into @p msg.".to_string(),
+            "Fields appended after @p version are zeroed.".to_string(),
+            "@param msg Receives the decoded message.".to_string(),
+            "@param version Message version of the frame.".to_string(),
+            "@param data Received payload.".to_string(),
+            "@param data_len Length of @p data in bytes.".to_string(),
+            "@return true on success, or false when:".to_string(),
+            "  - @p msg or @p data is NULL".to_string(),
+            format!("  - @p version is 0 or above {}_VERSION", macro_prefix),
+            "  - @p data is not a valid payload of that version".to_string(),
+        ],
+        format!("bool {}_decode_compat({})", fn_prefix, params),
+        &|out| {
+            let mut locals = Vec::new();
+            if !prefixes.is_empty() {
+                locals.push("offset");
+            }
+            if prefixes
+                .iter()
+                .any(|(_, prefix)| struct_has_variable_arrays(prefix))
+            {
+                locals.push("remaining");
+            }
+            if prefixes.iter().any(|(_, prefix)| struct_has_arrays(prefix)) {
+                locals.push("i");
+            }
+            name_ctx.write_locals(out, &locals);
+            name_ctx.write_null_guard(out, &["msg", "data"], "false");
+            out.push_str("    switch (version) {\n");
+            for older in &current {
+                writeln!(out, "    case {}:", name_ctx.uint(older)).unwrap();
+            }
+            writeln!(
+                out,
+                "    case {}_VERSION:\n        return {}(msg, data, data_len);",
+                macro_prefix, decode
+            )
+            .unwrap();
+            for (versions, prefix) in &prefixes {
+                let (last, earlier) = versions.split_last().unwrap();
+                for older in earlier {
+                    writeln!(out, "    case {}:", name_ctx.uint(older)).unwrap();
+                }
+                writeln!(out, "    case {}: {{", name_ctx.uint(last)).unwrap();
+                let min_size = struct_min_byte_len(prefix);
+                let max_size = struct_byte_len(prefix);
+                let remaining = if struct_has_variable_arrays(prefix) {
+                    writeln!(
+                        out,
+                        "        if (data_len < {}) {{\n            return false;\n        }}",
+                        name_ctx.uint(min_size)
+                    )
+                    .unwrap();
+                    writeln!(
+                        out,
+                        "        if (data_len > {}) {{\n            return false;\n        }}",
+                        name_ctx.uint(max_size)
+                    )
+                    .unwrap();
+                    Some("remaining")
+                } else {
+                    writeln!(
+                        out,
+                        "        if (data_len != {}) {{\n            return false;\n        }}",
+                        name_ctx.uint(max_size)
+                    )
+                    .unwrap();
+                    None
+                };
+                writeln!(
+                    out,
+                    "        {}memset(msg, 0, sizeof(*msg));",
+                    name_ctx.discard()
+                )
+                .unwrap();
+                writeln!(
+                    out,
+                    "        {};",
+                    dialect.init("size_t", "offset", &name_ctx.uint(0))
+                )
+                .unwrap();
+                if remaining.is_some() {
+                    writeln!(
+                        out,
+                        "        {};",
+                        dialect.init("size_t", "remaining", "data_len")
+                    )
+                    .unwrap();
+                    writeln!(out, "        remaining -= {};", name_ctx.uint(min_size)).unwrap();
+                }
+                generate_field_decode_stmts(
+                    out,
+                    name_ctx,
+                    &prefix.fields,
+                    "msg->",
+                    &macro_fields,
+                    "        ",
+                    remaining.map(|var| Remaining::all(var, name_ctx.deterministic_decode)),
+                );
+                let checks = collect_range_checks(&MessageBody::Struct(prefix.clone()));
+                write_range_checks(out, &checks, "        ", "false", name_ctx);
+                out.push_str("        break;\n    }\n");
+            }
+            out.push_str("    default:\n        return false;\n    }\n");
+            out.push_str("    return true;\n");
+        },
+    );
+    out
+}
+
+/// A value with a `min`/`max` range: C lvalue through `msg`, type and range.
+struct RangeCheck {
+    lvalue: String,
+    primitive: PrimitiveType,
+    range: ValueRange,
+}
+
+/// Collects the range-checked values of a message body, nested struct
+/// fields and the fields of every union variant included.
+fn collect_range_checks(body: &MessageBody) -> Vec<RangeCheck> {
+    let mut checks = Vec::new();
+    match body {
+        MessageBody::Scalar(spec) => {
+            if let Some(range) = spec.range {
+                checks.push(RangeCheck {
+                    lvalue: "msg->value".to_string(),
+                    primitive: spec.primitive,
+                    range,
+                });
+            }
+        }
+        MessageBody::Array(_) => {}
+        MessageBody::Struct(spec) => return collect_struct_range_checks(spec, "msg->"),
+        MessageBody::Union(spec) => {
+            for variant in &spec.variants {
+                checks.extend(collect_struct_range_checks(
+                    &variant.spec,
+                    &variant_accessor(variant),
+                ));
+            }
+        }
+    }
+    checks.retain(|check| effective_bounds(check) != (None, None));
+    checks
+}
+
+/// Collects the range-checked fields of a struct reached through `access`,
+/// nested struct fields included.
+fn collect_struct_range_checks(spec: &StructSpec, access: &str) -> Vec<RangeCheck> {
+    fn visit(spec: &StructSpec, access: &str, out: &mut Vec<RangeCheck>) {
+        for field in &spec.fields {
+            let lvalue = format!("{}{}", access, to_snake_case(&field.name));
+            match (&field.field_type, field.range) {
+                (StructFieldType::Primitive(primitive), Some(range)) => out.push(RangeCheck {
+                    lvalue,
+                    primitive: *primitive,
+                    range,
+                }),
+                (StructFieldType::Nested(nested), _) => visit(nested, &format!("{}.", lvalue), out),
+                _ => {}
+            }
+        }
+    }
 
-    let elem_size = spec.primitive.byte_len();
+    let mut checks = Vec::new();
+    visit(spec, access, &mut checks);
+    checks.retain(|check| effective_bounds(check) != (None, None));
+    checks
+}
 
-    // Generate encode function if needed
-    if mode == FunctionMode::EncodeOnly || mode == FunctionMode::Both {
-        writeln!(
-            &mut out,
-            "static inline size_t {}(const {} *msg, uint8_t *out_buf, const size_t out_len) {{",
-            encode_name, type_name
-        )
-        .unwrap();
-        out.push_str("    if (!msg || !out_buf) {\n        return 0;\n    }\n");
-        writeln!(
-            &mut out,
-            "    if (msg->length > {}) {{\n        return 0;\n    }}",
-            max_macro
-        )
-        .unwrap();
-        writeln!(
-            &mut out,
-            "    size_t required = msg->length * {};",
-            elem_size
-        )
-        .unwrap();
-        out.push_str("    if (out_len < required) {\n        return 0;\n    }\n");
-        if elem_size == 1 {
-            out.push_str(
-                "    if (required > 0) {\n        memcpy(out_buf, msg->data, required);\n    }\n",
-            );
-            out.push_str("    return required;\n}\n\n");
-        } else {
-            out.push_str(
-                "    size_t offset = 0;\n    for (size_t i = 0; i < msg->length; ++i) {\n",
-            );
-            out.push_str(&primitive_encode_stmt(
-                spec.primitive,
-                spec.endian,
-                "msg->data[i]",
-                "out_buf + offset",
-                "        ",
-            ));
-            writeln!(&mut out, "        offset += {};", elem_size).unwrap();
-            out.push_str("    }\n    return offset;\n}\n\n");
-        }
+/// The bounds of `check` that exclude values of its type. Integer bounds
+/// at the limits of the type would only trigger compiler warnings.
+fn effective_bounds(check: &RangeCheck) -> (Option<Bound>, Option<Bound>) {
+    let ValueRange { min, max } = check.range;
+    match check.primitive.integer_range() {
+        Some((low, high)) => (
+            min.filter(|bound| !matches!(bound, Bound::Int(value) if *value <= low)),
+            max.filter(|bound| !matches!(bound, Bound::Int(value) if *value >= high)),
+        ),
+        None => (min, max),
     }
+}
 
-    // Generate decode function if needed
-    if mode == FunctionMode::DecodeOnly || mode == FunctionMode::Both {
-        writeln!(
-            &mut out,
-            "static inline bool {}({} *msg, const uint8_t *data, const size_t data_len) {{",
-            decode_name, type_name
-        )
-        .unwrap();
-        out.push_str("    if (!msg || !data) {\n        return false;\n    }\n");
-        writeln!(
-            &mut out,
-            "    if (data_len % {} != 0) {{\n        return false;\n    }}",
-            elem_size
-        )
-        .unwrap();
-        writeln!(
-            &mut out,
-            "    size_t element_count = data_len / {};",
-            elem_size
-        )
-        .unwrap();
-        writeln!(
-            &mut out,
-            "    if (element_count > {}) {{\n        return false;\n    }}",
-            max_macro
-        )
-        .unwrap();
-        out.push_str("    msg->length = element_count;\n");
-        out.push_str("    if (element_count == 0) {\n");
-        if spec.primitive == PrimitiveType::Char {
-            out.push_str("        if (");
-            out.push_str(&max_macro);
-            out.push_str(" > 0) {\n            msg->data[0] = '\\0';\n        }\n");
+/// C literal of a bound compared with a value of type `primitive`. C89
+/// has no `long long` literals, so wide bounds go through `UINT64_C` and
+/// `INT64_C` there; MISRA mode suffixes the bounds of unsigned values.
+fn bound_literal(bound: Bound, primitive: PrimitiveType, name_ctx: &NameContext) -> String {
+    let unsigned = primitive.integer_range().is_some_and(|(low, _)| low == 0);
+    match bound {
+        Bound::Int(value) if i32::try_from(value).is_ok() && unsigned => name_ctx.uint(value),
+        Bound::Int(value) if i32::try_from(value).is_ok() => value.to_string(),
+        Bound::Int(value) if unsigned => match name_ctx.dialect {
+            CDialect::C99 => format!("{}ULL", value),
+            CDialect::C89 => format!("UINT64_C({})", value),
+        },
+        Bound::Int(value) => match name_ctx.dialect {
+            CDialect::C99 => format!("{}LL", value),
+            CDialect::C89 => format!("INT64_C({})", value),
+        },
+        // Compare in single precision so a float equal to the bound passes
+        Bound::Float(value) if primitive.c_type() == "float" => {
+            format!("{:?}f", value as f32)
         }
-        out.push_str("        return true;\n    }\n");
-        if elem_size == 1 {
-            out.push_str("    memcpy(msg->data, data, element_count);\n");
+        Bound::Float(value) => const_literal(&ConstValue::Float(value)),
+    }
+}
+
+/// C condition that holds when the value of `check` is out of range, or
+/// `None` when every value of its type is allowed. Floating point checks
+/// are written so that NaN is out of range.
+fn range_violation(check: &RangeCheck, name_ctx: &NameContext) -> Option<String> {
+    let (min, max) = effective_bounds(check);
+    let literal = |bound| bound_literal(bound, check.primitive, name_ctx);
+    // MISRA wants the operands of || and && parenthesized
+    let operand = |lvalue: &str, op: &str, bound: String| {
+        if name_ctx.misra && min.is_some() && max.is_some() {
+            format!("({} {} {})", lvalue, op, bound)
         } else {
-            out.push_str(
-                "    size_t offset = 0;\n    for (size_t i = 0; i < element_count; ++i) {\n",
-            );
-            out.push_str(&primitive_decode_stmt(
-                spec.primitive,
-                spec.endian,
-                "msg->data[i]",
-                "data + offset",
-                "        ",
-            ));
-            writeln!(&mut out, "        offset += {};", elem_size).unwrap();
-            out.push_str("    }\n");
+            format!("{} {} {}", lvalue, op, bound)
         }
-        if spec.primitive == PrimitiveType::Char {
-            out.push_str("    if (element_count < ");
-            out.push_str(&max_macro);
-            out.push_str(") {\n        msg->data[element_count] = '\\0';\n    }\n");
+    };
+    if check.primitive.integer_range().is_some() {
+        let parts: Vec<String> = min
+            .map(|min| operand(&check.lvalue, "<", literal(min)))
+            .into_iter()
+            .chain(max.map(|max| operand(&check.lvalue, ">", literal(max))))
+            .collect();
+        return (!parts.is_empty()).then(|| parts.join(" || "));
+    }
+    let parts: Vec<String> = min
+        .map(|min| operand(&check.lvalue, ">=", literal(min)))
+        .into_iter()
+        .chain(max.map(|max| operand(&check.lvalue, "<=", literal(max))))
+        .collect();
+    (!parts.is_empty()).then(|| format!("!({})", parts.join(" && ")))
+}
+
+/// Writes one `if` per range check that returns `result` when violated.
+fn write_range_checks(
+    out: &mut String,
+    checks: &[RangeCheck],
+    indent: &str,
+    result: &str,
+    name_ctx: &NameContext,
+) {
+    for check in checks {
+        if let Some(violation) = range_violation(check, name_ctx) {
+            writeln!(
+                out,
+                "{i}if ({}) {{\n{i}    return {};\n{i}}}",
+                violation,
+                result,
+                i = indent
+            )
+            .unwrap();
         }
-        out.push_str("    return true;\n}\n\n");
     }
+}
 
-    out
+/// The value of `check` clamped into its range.
+fn clamp_expr(check: &RangeCheck, name_ctx: &NameContext) -> String {
+    let (min, max) = effective_bounds(check);
+    let lvalue = &check.lvalue;
+    let mut expr = lvalue.clone();
+    if let Some(max) = max {
+        let max = bound_literal(max, check.primitive, name_ctx);
+        expr = format!("{} > {} ? {} : {}", lvalue, max, max, expr);
+    }
+    if let Some(min) = min {
+        let min = bound_literal(min, check.primitive, name_ctx);
+        expr = format!("{} < {} ? {} : ({})", lvalue, min, min, expr);
+    }
+    format!("({})", expr)
 }
 
 /// Calculates the total byte size of a struct field (recursively for nested structs).
@@ -1022,35 +5300,396 @@ fn field_byte_len(field: &StructField) -> usize {
         StructFieldType::Primitive(prim) => prim.byte_len(),
         StructFieldType::Array(arr) => arr.max_length * arr.primitive.byte_len(),
         StructFieldType::Nested(nested) => struct_byte_len(nested),
+        StructFieldType::Bytes(length) => *length,
+    }
+}
+
+/// Offset or size that may depend on how many elements variable-length
+/// arrays carry, as the lowest and highest possible byte count.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct ByteSpan {
+    pub(crate) min: usize,
+    pub(crate) max: usize,
+}
+
+impl ByteSpan {
+    pub(crate) fn fixed(len: usize) -> Self {
+        ByteSpan { min: len, max: len }
+    }
+
+    pub(crate) fn add(self, other: ByteSpan) -> Self {
+        ByteSpan {
+            min: self.min + other.min,
+            max: self.max + other.max,
+        }
+    }
+
+    pub(crate) fn is_fixed(self) -> bool {
+        self.min == self.max
+    }
+}
+
+impl std::fmt::Display for ByteSpan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.min == self.max {
+            write!(f, "{}", self.min)
+        } else {
+            write!(f, "{}–{}", self.min, self.max)
+        }
+    }
+}
+
+/// Position of a struct field in the encoded payload.
+pub(crate) struct FieldLayout<'a> {
+    /// Dotted path from the outermost struct, e.g. `pose.x`
+    pub(crate) path: String,
+    pub(crate) field: &'a StructField,
+    pub(crate) offset: ByteSpan,
+    pub(crate) size: ByteSpan,
+}
+
+/// Lays out the fields of `spec` from `start` in wire order, each nested
+/// struct followed by its own fields, with `prefix` in front of every path.
+/// Returns the fields and the offset after the struct.
+///
+/// The offset macros and the Markdown field tables both come from here, so
+/// they cannot disagree.
+pub(crate) fn struct_layout<'a>(
+    spec: &'a StructSpec,
+    prefix: &str,
+    start: ByteSpan,
+) -> (Vec<FieldLayout<'a>>, ByteSpan) {
+    let mut fields = Vec::new();
+    let mut offset = start;
+    for field in &spec.fields {
+        let path = format!("{}{}", prefix, field.name);
+        let size = match &field.field_type {
+            StructFieldType::Array(arr) if !arr.fixed => ByteSpan {
+                min: 0,
+                max: field_byte_len(field),
+            },
+            StructFieldType::Nested(nested) => ByteSpan {
+                min: struct_min_byte_len(nested),
+                max: struct_byte_len(nested),
+            },
+            _ => ByteSpan::fixed(field_byte_len(field)),
+        };
+        fields.push(FieldLayout {
+            path: path.clone(),
+            field,
+            offset,
+            size,
+        });
+        if let StructFieldType::Nested(nested) = &field.field_type {
+            fields.extend(struct_layout(nested, &format!("{}.", path), offset).0);
+        }
+        offset = offset.add(size);
+    }
+    (fields, offset)
+}
+
+/// A variable-length array of a struct, as the encoder sees it.
+struct VariableArray {
+    /// Length member, e.g. `msg->scan.ranges_length`
+    length: String,
+    /// Its `_MAX_LENGTH` macro
+    max_macro: String,
+    elem_size: usize,
+}
+
+/// The variable-length arrays of `fields` in wire order, nested ones
+/// included.
+fn variable_arrays(
+    name_ctx: &NameContext,
+    fields: &[StructField],
+    parent_accessor: &str,
+    macro_prefix: &str,
+) -> Vec<VariableArray> {
+    let mut arrays = Vec::new();
+    for field in fields {
+        let field_ident = to_snake_case(&field.name);
+        match &field.field_type {
+            StructFieldType::Array(arr) if !arr.fixed => arrays.push(VariableArray {
+                length: format!("{}{}_length", parent_accessor, field_ident),
+                max_macro: format!(
+                    "{}_{}_MAX_LENGTH",
+                    macro_prefix,
+                    to_macro_ident(&field.name)
+                ),
+                elem_size: arr.primitive.byte_len(),
+            }),
+            StructFieldType::Nested(nested_spec) => {
+                let nested_macro_prefix = fields_macro_prefix(
+                    &name_ctx.helpers,
+                    nested_spec,
+                    format!("{}_{}", macro_prefix, to_macro_ident(&field.name)),
+                );
+                arrays.extend(variable_arrays(
+                    name_ctx,
+                    &nested_spec.fields,
+                    &format!("{}{}.", parent_accessor, field_ident),
+                    &nested_macro_prefix,
+                ));
+            }
+            _ => {}
+        }
+    }
+    arrays
+}
+
+/// C expression of the encoded size of a struct: the fixed part
+/// `fixed_size` plus the elements of `arrays`, with numbers spelled by
+/// `number`.
+fn encoded_size_expr(
+    fixed_size: usize,
+    arrays: &[VariableArray],
+    number: &dyn Fn(usize) -> String,
+) -> String {
+    let mut expr = number(fixed_size);
+    for array in arrays {
+        match array.elem_size {
+            1 => write!(expr, " + {}", array.length).unwrap(),
+            size => write!(expr, " + {} * {}", array.length, number(size)).unwrap(),
+        }
     }
+    expr
 }
 
 /// Checks if a struct contains any variable-length array fields (recursively).
 fn struct_has_variable_arrays(spec: &StructSpec) -> bool {
+    spec.fields.iter().any(field_has_variable_arrays)
+}
+
+/// Checks if a field is or contains a variable-length array.
+fn field_has_variable_arrays(field: &StructField) -> bool {
+    match &field.field_type {
+        StructFieldType::Array(arr) => !arr.fixed,
+        StructFieldType::Nested(nested) => struct_has_variable_arrays(nested),
+        StructFieldType::Primitive(_) | StructFieldType::Bytes(_) => false,
+    }
+}
+
+/// Checks if a struct contains array fields of either kind (recursively).
+fn struct_has_arrays(spec: &StructSpec) -> bool {
     spec.fields.iter().any(|f| match &f.field_type {
         StructFieldType::Array(_) => true,
-        StructFieldType::Nested(nested) => struct_has_variable_arrays(nested),
-        StructFieldType::Primitive(_) => false,
+        StructFieldType::Nested(nested) => struct_has_arrays(nested),
+        StructFieldType::Primitive(_) | StructFieldType::Bytes(_) => false,
     })
 }
 
-/// Calculates the minimum byte size of a struct (arrays contribute 0 minimum).
-fn struct_min_byte_len(spec: &StructSpec) -> usize {
+/// Calculates the minimum byte size of a struct (variable-length arrays
+/// contribute 0 minimum).
+pub(crate) fn struct_min_byte_len(spec: &StructSpec) -> usize {
     spec.fields
         .iter()
         .map(|f| match &f.field_type {
             StructFieldType::Primitive(prim) => prim.byte_len(),
+            StructFieldType::Array(arr) if arr.fixed => arr.max_length * arr.primitive.byte_len(),
             StructFieldType::Array(_) => 0,
             StructFieldType::Nested(nested) => struct_min_byte_len(nested),
+            StructFieldType::Bytes(length) => *length,
         })
         .sum()
 }
 
 /// Calculates the total byte size of a struct (recursively for nested structs).
-fn struct_byte_len(spec: &StructSpec) -> usize {
+pub(crate) fn struct_byte_len(spec: &StructSpec) -> usize {
     spec.fields.iter().map(field_byte_len).sum()
 }
 
+/// Returns whether a struct qualifies for the `memcpy` fast path.
+///
+/// The in-memory layout must match the wire layout byte for byte: no
+/// variable-length arrays, every field little-endian, and every field at a
+/// naturally aligned offset so the compiler inserts no padding. `bool`
+/// fields are excluded because only 0 and 1 are valid object
+/// representations, so arbitrary wire bytes cannot be copied into them.
+pub(crate) fn struct_fast_path_eligible(spec: &StructSpec) -> bool {
+    fn all_fields_little_non_bool(spec: &StructSpec) -> bool {
+        spec.fields.iter().all(|f| match &f.field_type {
+            StructFieldType::Primitive(prim) => {
+                f.endian == Endian::Little && *prim != PrimitiveType::Bool
+            }
+            StructFieldType::Array(_) | StructFieldType::Bytes(_) => false,
+            StructFieldType::Nested(nested) => all_fields_little_non_bool(nested),
+        })
+    }
+
+    !struct_has_variable_arrays(spec)
+        && all_fields_little_non_bool(spec)
+        && natural_layout(spec).is_some()
+}
+
+/// Computes `(size, alignment)` of a struct under natural C alignment,
+/// returning `None` if the compiler would need to insert any padding.
+fn natural_layout(spec: &StructSpec) -> Option<(usize, usize)> {
+    let mut offset = 0;
+    let mut max_align = 1;
+    for field in &spec.fields {
+        let (size, align) = match &field.field_type {
+            StructFieldType::Primitive(prim) if prim.is_widened() => return None,
+            StructFieldType::Primitive(prim) => (prim.byte_len(), prim.byte_len()),
+            StructFieldType::Array(_) | StructFieldType::Bytes(_) => return None,
+            StructFieldType::Nested(nested) => natural_layout(nested)?,
+        };
+        if offset % align != 0 {
+            return None;
+        }
+        offset += size;
+        max_align = max_align.max(align);
+    }
+    if offset % max_align != 0 {
+        return None;
+    }
+    Some((offset, max_align))
+}
+
+fn wire_mirror_type_name(type_name: &str) -> String {
+    format!("{}_wire_t", type_name.trim_end_matches("_t"))
+}
+
+/// Generates the packed mirror of a struct's wire layout used by the fast path.
+fn generate_wire_mirror_typedef(
+    msg: &MessageDefinition,
+    spec: &StructSpec,
+    name_ctx: &NameContext,
+) -> String {
+    fn collect_leaves(spec: &StructSpec, prefix: &str, out: &mut Vec<(String, PrimitiveType)>) {
+        for field in &spec.fields {
+            let ident = format!("{}{}", prefix, to_snake_case(&field.name));
+            match &field.field_type {
+                StructFieldType::Primitive(prim) => out.push((ident, *prim)),
+                StructFieldType::Nested(nested) => {
+                    collect_leaves(nested, &format!("{}_", ident), out)
+                }
+                StructFieldType::Array(_) | StructFieldType::Bytes(_) => {}
+            }
+        }
+    }
+
+    let mut leaves = Vec::new();
+    collect_leaves(spec, "", &mut leaves);
+
+    let mut out = String::new();
+    writeln!(
+        &mut out,
+        "/* Packed mirror of the wire layout (memcpy fast path) */"
+    )
+    .unwrap();
+    out.push_str("#pragma pack(push, 1)\ntypedef struct {\n");
+    for (ident, prim) in leaves {
+        writeln!(&mut out, "    {} {};", prim.c_type(), ident).unwrap();
+    }
+    writeln!(
+        &mut out,
+        "}} {};\n#pragma pack(pop)\n",
+        wire_mirror_type_name(&type_name(msg, name_ctx))
+    )
+    .unwrap();
+    out
+}
+
+/// C type of `timestamp` fields stored as `storage`.
+fn timestamp_type_name(helpers: &str, storage: PrimitiveType) -> String {
+    match storage {
+        PrimitiveType::Uint32 => format!("{}_timestamp32_t", helpers),
+        _ => format!("{}_timestamp_t", helpers),
+    }
+}
+
+/// Declares the typedef of each `timestamp` storage in use once, ahead of
+/// the shared types and messages using it.
+fn write_timestamp_typedefs(
+    out: &mut String,
+    metadata: &Metadata,
+    messages: &[MessageDefinition],
+    name_ctx: &NameContext,
+) {
+    fn visit(spec: &StructSpec, used: &mut [bool; 2]) {
+        for field in &spec.fields {
+            match &field.field_type {
+                StructFieldType::Primitive(storage) if field.timestamp.is_some() => {
+                    used[usize::from(*storage == PrimitiveType::Uint32)] = true;
+                }
+                StructFieldType::Nested(nested) => visit(nested, used),
+                _ => {}
+            }
+        }
+    }
+    let mut used = [false; 2];
+    for spec in shared_types(metadata, messages) {
+        visit(spec, &mut used);
+    }
+    for msg in messages {
+        match &msg.body {
+            MessageBody::Struct(spec) => visit(spec, &mut used),
+            MessageBody::Union(spec) => {
+                for variant in &spec.variants {
+                    visit(&variant.spec, &mut used);
+                }
+            }
+            MessageBody::Scalar(_) | MessageBody::Array(_) => {}
+        }
+    }
+    if !used.contains(&true) {
+        return;
+    }
+    if !name_ctx.doc_comments {
+        out.push_str("/* Timestamps; each member notes its resolution */\n");
+    }
+    for (storage, used) in [PrimitiveType::Uint64, PrimitiveType::Uint32]
+        .into_iter()
+        .zip(used)
+    {
+        if !used {
+            continue;
+        }
+        if name_ctx.doc_comments {
+            out.push_str(&doc_block(&[format!(
+                "@brief Timestamp stored as {}; each member notes its resolution",
+                storage.c_type()
+            )]));
+        }
+        writeln!(
+            out,
+            "typedef {} {};",
+            storage.c_type(),
+            timestamp_type_name(&name_ctx.helpers, storage)
+        )
+        .unwrap();
+    }
+}
+
+/// Declares every shared type once, ahead of the messages using it.
+fn generate_shared_types(
+    metadata: &Metadata,
+    messages: &[MessageDefinition],
+    name_ctx: &NameContext,
+) -> String {
+    let helpers = &name_ctx.helpers;
+    let mut out = String::new();
+    for spec in shared_types(metadata, messages) {
+        let name = spec.shared.as_deref().unwrap_or_default();
+        let doc = if name_ctx.doc_comments {
+            out.push('\n');
+            doc_block(&[format!("@brief Shared type '{}'", name)])
+        } else {
+            writeln!(&mut out, "\n/* Shared type '{}' */", name).unwrap();
+            String::new()
+        };
+        generate_struct_typedef(
+            &mut out,
+            &shared_type_name(helpers, name),
+            &fields_macro_prefix(helpers, spec, String::new()),
+            spec,
+            name_ctx,
+            &doc,
+        );
+    }
+    out
+}
+
 /// Generates a nested struct type name.
 fn nested_struct_type_name(parent_type_name: &str, field_name: &str) -> String {
     format!(
@@ -1062,58 +5701,149 @@ fn nested_struct_type_name(parent_type_name: &str, field_name: &str) -> String {
 
 /// Generates typedef for a struct, including nested struct typedefs.
 /// Also emits #define macros for array field max lengths.
+/// Shared types are declared once by [`generate_shared_types`] and only
+/// referenced here. `doc` is written right before the typedef.
 fn generate_struct_typedef(
     out: &mut String,
     type_name: &str,
     macro_prefix: &str,
     spec: &StructSpec,
+    name_ctx: &NameContext,
+    doc: &str,
 ) {
+    let helpers = &name_ctx.helpers;
     // First, generate typedefs for any nested structs
     for field in &spec.fields {
-        if let StructFieldType::Nested(nested_spec) = &field.field_type {
+        if let StructFieldType::Nested(nested_spec) = &field.field_type
+            && nested_spec.shared.is_none()
+        {
             let nested_type = nested_struct_type_name(type_name, &field.name);
             let nested_macro_prefix = format!("{}_{}", macro_prefix, to_macro_ident(&field.name));
-            generate_struct_typedef(out, &nested_type, &nested_macro_prefix, nested_spec);
+            let nested_doc = if name_ctx.doc_comments {
+                let brief = match &field.description {
+                    Some(desc) => format!("@brief {}", desc),
+                    None => format!("@brief Field '{}' of {}", field.name, type_name),
+                };
+                doc_block(&[brief])
+            } else {
+                String::new()
+            };
+            generate_struct_typedef(
+                out,
+                &nested_type,
+                &nested_macro_prefix,
+                nested_spec,
+                name_ctx,
+                &nested_doc,
+            );
         }
     }
 
     // Generate #define macros for array field max lengths
     for field in &spec.fields {
-        if let StructFieldType::Array(arr) = &field.field_type {
-            let field_macro = to_macro_ident(&field.name);
-            writeln!(
-                out,
-                "#define {}_{}_MAX_LENGTH {}",
-                macro_prefix, field_macro, arr.max_length
-            )
-            .unwrap();
-        }
+        let (suffix, length) = match &field.field_type {
+            StructFieldType::Array(arr) if arr.fixed => ("LENGTH", arr.max_length),
+            StructFieldType::Array(arr) => ("MAX_LENGTH", arr.max_length),
+            StructFieldType::Bytes(_) if field.reserved => continue,
+            StructFieldType::Bytes(length) => ("LENGTH", *length),
+            StructFieldType::Primitive(_) | StructFieldType::Nested(_) => continue,
+        };
+        writeln!(
+            out,
+            "#define {}_{}_{} {}",
+            macro_prefix,
+            to_macro_ident(&field.name),
+            suffix,
+            name_ctx.uint(length)
+        )
+        .unwrap();
     }
 
     // Then generate this struct's typedef
-    writeln!(out, "typedef struct {{").unwrap();
+    out.push_str(doc);
+    writeln!(out, "{}", name_ctx.typedef_struct()).unwrap();
     for field in &spec.fields {
         let field_ident = to_snake_case(&field.name);
+        // A timestamp without a unit of its own is in its resolution
+        let unit = field
+            .unit
+            .as_deref()
+            .or(field.timestamp.map(TimestampResolution::name));
+        let comment = member_comment(field.description.as_deref(), unit, name_ctx.doc_comments);
         match &field.field_type {
+            StructFieldType::Primitive(prim) if field.timestamp.is_some() => {
+                let c_type = timestamp_type_name(helpers, *prim);
+                writeln!(out, "    {} {};{}", c_type, field_ident, comment).unwrap();
+            }
             StructFieldType::Primitive(prim) => {
-                writeln!(out, "    {} {};", prim.c_type(), field_ident).unwrap();
+                writeln!(out, "    {} {};{}", prim.c_type(), field_ident, comment).unwrap();
+            }
+            StructFieldType::Array(arr) if arr.fixed => {
+                writeln!(
+                    out,
+                    "    {} {}[{}_{}_LENGTH];{}",
+                    arr.primitive.c_type(),
+                    field_ident,
+                    macro_prefix,
+                    to_macro_ident(&field.name),
+                    comment
+                )
+                .unwrap();
             }
             StructFieldType::Array(arr) => {
                 let field_macro = to_macro_ident(&field.name);
-                writeln!(out, "    size_t {}_length;", field_ident).unwrap();
                 writeln!(
                     out,
-                    "    {} {}[{}_{}_MAX_LENGTH];",
+                    "    size_t {}_length;{}",
+                    field_ident,
+                    length_comment(&field_ident, name_ctx)
+                )
+                .unwrap();
+                // Text gets room for its terminating '\0'
+                let terminator = if arr.string_semantics {
+                    format!(" + {}", name_ctx.uint(1))
+                } else {
+                    String::new()
+                };
+                writeln!(
+                    out,
+                    "    {} {}[{}_{}_MAX_LENGTH{}];{}",
                     arr.primitive.c_type(),
                     field_ident,
                     macro_prefix,
-                    field_macro
+                    field_macro,
+                    terminator,
+                    comment
+                )
+                .unwrap();
+            }
+            StructFieldType::Nested(nested_spec) => {
+                let nested_type = match &nested_spec.shared {
+                    Some(shared) => shared_type_name(helpers, shared),
+                    None => nested_struct_type_name(type_name, &field.name),
+                };
+                writeln!(out, "    {} {};{}", nested_type, field_ident, comment).unwrap();
+            }
+            StructFieldType::Bytes(length) if field.reserved => {
+                writeln!(
+                    out,
+                    "    uint8_t {}[{}];{}",
+                    field_ident,
+                    name_ctx.uint(*length),
+                    comment
                 )
                 .unwrap();
             }
-            StructFieldType::Nested(_) => {
-                let nested_type = nested_struct_type_name(type_name, &field.name);
-                writeln!(out, "    {} {};", nested_type, field_ident).unwrap();
+            StructFieldType::Bytes(_) => {
+                writeln!(
+                    out,
+                    "    uint8_t {}[{}_{}_LENGTH];{}",
+                    field_ident,
+                    macro_prefix,
+                    to_macro_ident(&field.name),
+                    comment
+                )
+                .unwrap();
             }
         }
     }
@@ -1123,63 +5853,173 @@ fn generate_struct_typedef(
 /// Generates encode statements for struct fields (recursively for nested structs).
 fn generate_field_encode_stmts(
     out: &mut String,
+    name_ctx: &NameContext,
     fields: &[StructField],
     parent_accessor: &str,
     macro_prefix: &str,
     indent: &str,
+    clamp: bool,
 ) {
     for field in fields {
+        let helpers = &name_ctx.helpers;
         let field_ident = to_snake_case(&field.name);
         let accessor = format!("{}{}", parent_accessor, field_ident);
         match &field.field_type {
             StructFieldType::Primitive(prim) => {
+                let check = field.range.map(|range| RangeCheck {
+                    lvalue: accessor.clone(),
+                    primitive: *prim,
+                    range,
+                });
+                let source = match check {
+                    Some(check) if clamp && effective_bounds(&check) != (None, None) => {
+                        clamp_expr(&check, name_ctx)
+                    }
+                    _ => accessor.clone(),
+                };
                 out.push_str(&primitive_encode_stmt(
+                    name_ctx,
                     *prim,
                     field.endian,
-                    &accessor,
+                    &source,
                     "out_buf + offset",
                     indent,
                 ));
-                writeln!(out, "{}offset += {};", indent, prim.byte_len()).unwrap();
+                writeln!(
+                    out,
+                    "{}offset += {};",
+                    indent,
+                    name_ctx.uint(prim.byte_len())
+                )
+                .unwrap();
             }
             StructFieldType::Array(arr) => {
                 let field_macro = to_macro_ident(&field.name);
-                let max_macro = format!("{}_{}_MAX_LENGTH", macro_prefix, field_macro);
-                let length_accessor = format!("{}{}_length", parent_accessor, field_ident);
                 let elem_size = arr.primitive.byte_len();
 
                 // Encode array elements
-                writeln!(
-                    out,
-                    "{}for (size_t i = 0; i < {} && i < {}; ++i) {{",
-                    indent, length_accessor, max_macro
-                )
-                .unwrap();
+                if arr.fixed {
+                    writeln!(
+                        out,
+                        "{}{}i < {}_{}_LENGTH; ++i) {{",
+                        indent,
+                        name_ctx.for_index(),
+                        macro_prefix,
+                        field_macro
+                    )
+                    .unwrap();
+                } else {
+                    writeln!(
+                        out,
+                        "{}{}i < {}{}_length && i < {}_{}_MAX_LENGTH; ++i) {{",
+                        indent,
+                        name_ctx.for_index(),
+                        parent_accessor,
+                        field_ident,
+                        macro_prefix,
+                        field_macro
+                    )
+                    .unwrap();
+                }
                 let elem_accessor = format!("{}[i]", accessor);
                 let next_indent = format!("{}    ", indent);
                 out.push_str(&primitive_encode_stmt(
+                    name_ctx,
                     arr.primitive,
                     field.endian,
                     &elem_accessor,
                     "out_buf + offset",
                     &next_indent,
                 ));
-                writeln!(out, "{}    offset += {};", indent, elem_size).unwrap();
+                writeln!(out, "{}    offset += {};", indent, name_ctx.uint(elem_size)).unwrap();
                 writeln!(out, "{}}}", indent).unwrap();
             }
             StructFieldType::Nested(nested_spec) => {
                 // Recursively encode nested struct fields
                 let nested_accessor = format!("{}.", accessor);
-                let nested_macro_prefix =
-                    format!("{}_{}", macro_prefix, to_macro_ident(&field.name));
+                let nested_macro_prefix = fields_macro_prefix(
+                    helpers,
+                    nested_spec,
+                    format!("{}_{}", macro_prefix, to_macro_ident(&field.name)),
+                );
                 generate_field_encode_stmts(
                     out,
+                    name_ctx,
                     &nested_spec.fields,
                     &nested_accessor,
                     &nested_macro_prefix,
                     indent,
+                    clamp,
                 );
             }
+            StructFieldType::Bytes(length) if field.reserved => {
+                writeln!(
+                    out,
+                    "{}{}memset(out_buf + offset, 0, {});",
+                    indent,
+                    name_ctx.discard(),
+                    name_ctx.uint(*length)
+                )
+                .unwrap();
+                writeln!(out, "{}offset += {};", indent, name_ctx.uint(*length)).unwrap();
+            }
+            StructFieldType::Bytes(length) => {
+                writeln!(
+                    out,
+                    "{}{}memcpy(out_buf + offset, {}, {});",
+                    indent,
+                    name_ctx.discard(),
+                    accessor,
+                    name_ctx.uint(*length)
+                )
+                .unwrap();
+                writeln!(out, "{}offset += {};", indent, name_ctx.uint(*length)).unwrap();
+            }
+        }
+    }
+}
+
+/// Writes the `--deterministic-decode` statement zeroing the elements of
+/// `array` from index `count` on, including the terminator slot of text.
+fn write_zero_tail(
+    out: &mut String,
+    name_ctx: &NameContext,
+    array: &str,
+    count: &str,
+    max_macro: &str,
+    indent: &str,
+) {
+    writeln!(
+        out,
+        "{i}if ({c} < {m}) {{\n{i}    {d}memset(&{a}[{c}], 0, sizeof({a}) - {c} * sizeof({a}[0]));\n{i}}}",
+        i = indent,
+        c = count,
+        m = max_macro,
+        d = name_ctx.discard(),
+        a = array
+    )
+    .unwrap();
+}
+
+/// The bytes left for the variable-length arrays of a struct being decoded.
+#[derive(Clone, Copy)]
+struct Remaining<'a> {
+    /// C variable holding the count
+    var: &'a str,
+    /// Whether no variable-length array of the message follows the fields
+    /// being decoded, so the last one among them takes up all of the rest
+    trailing: bool,
+    /// Whether to zero the elements of the arrays past their length
+    zero_tail: bool,
+}
+
+impl<'a> Remaining<'a> {
+    /// The remaining bytes of a whole message in `var`.
+    fn all(var: &'a str, zero_tail: bool) -> Self {
+        Self {
+            var,
+            trailing: true,
+            zero_tail,
         }
     }
 }
@@ -1188,25 +6028,68 @@ fn generate_field_encode_stmts(
 /// For structs with variable-length arrays, we need to track remaining bytes.
 fn generate_field_decode_stmts(
     out: &mut String,
+    name_ctx: &NameContext,
     fields: &[StructField],
     parent_accessor: &str,
     macro_prefix: &str,
     indent: &str,
-    remaining_var: Option<&str>,
+    remaining: Option<Remaining<'_>>,
 ) {
-    for field in fields {
+    for (index, field) in fields.iter().enumerate() {
+        // Variable-length arrays of this field end the message
+        let remaining_here = remaining.map(|remaining| Remaining {
+            trailing: remaining.trailing
+                && !fields[index + 1..].iter().any(field_has_variable_arrays),
+            ..remaining
+        });
+        let helpers = &name_ctx.helpers;
         let field_ident = to_snake_case(&field.name);
         let accessor = format!("{}{}", parent_accessor, field_ident);
         match &field.field_type {
             StructFieldType::Primitive(prim) => {
                 out.push_str(&primitive_decode_stmt(
+                    name_ctx,
                     *prim,
                     field.endian,
                     &accessor,
                     "data + offset",
                     indent,
                 ));
-                writeln!(out, "{}offset += {};", indent, prim.byte_len()).unwrap();
+                writeln!(
+                    out,
+                    "{}offset += {};",
+                    indent,
+                    name_ctx.uint(prim.byte_len())
+                )
+                .unwrap();
+            }
+            StructFieldType::Array(arr) if arr.fixed => {
+                // Counted in the fixed size, so no remaining bytes are taken
+                writeln!(
+                    out,
+                    "{}{}i < {}_{}_LENGTH; ++i) {{",
+                    indent,
+                    name_ctx.for_index(),
+                    macro_prefix,
+                    to_macro_ident(&field.name)
+                )
+                .unwrap();
+                out.push_str(&primitive_decode_stmt(
+                    name_ctx,
+                    arr.primitive,
+                    field.endian,
+                    &format!("{}[i]", accessor),
+                    "data + offset",
+                    &format!("{}    ", indent),
+                ));
+                writeln!(
+                    out,
+                    "{}    offset += {};",
+                    indent,
+                    name_ctx.uint(arr.primitive.byte_len())
+                )
+                .unwrap();
+                writeln!(out, "{}}}", indent).unwrap();
             }
             StructFieldType::Array(arr) => {
                 let field_macro = to_macro_ident(&field.name);
@@ -1215,181 +6098,182 @@ fn generate_field_decode_stmts(
                 let elem_size = arr.primitive.byte_len();
 
                 // Calculate how many elements we can decode based on remaining bytes
-                if let Some(rem_var) = remaining_var {
+                if let Some(Remaining {
+                    var: rem_var,
+                    trailing,
+                    zero_tail,
+                }) = remaining_here
+                {
+                    // The last array gets the rest, which must be whole elements
+                    if trailing && elem_size > 1 {
+                        writeln!(
+                            out,
+                            "{}if ({} % {} != {}) {{\n{}    return false;\n{}}}",
+                            indent,
+                            rem_var,
+                            name_ctx.uint(elem_size),
+                            name_ctx.uint(0),
+                            indent,
+                            indent
+                        )
+                        .unwrap();
+                    }
                     writeln!(out, "{}{{", indent).unwrap();
                     writeln!(
                         out,
                         "{}    size_t elem_count = {} / {};",
-                        indent, rem_var, elem_size
+                        indent,
+                        rem_var,
+                        name_ctx.uint(elem_size)
                     )
                     .unwrap();
                     writeln!(out, "{}    if (elem_count > {}) {{", indent, max_macro).unwrap();
                     writeln!(out, "{}        elem_count = {};", indent, max_macro).unwrap();
                     writeln!(out, "{}    }}", indent).unwrap();
+                    // and no more than the maximum
+                    if trailing {
+                        let taken = if elem_size == 1 {
+                            "elem_count".to_string()
+                        } else {
+                            format!("elem_count * {}", name_ctx.uint(elem_size))
+                        };
+                        writeln!(
+                            out,
+                            "{}    if ({} != {}) {{\n{}        return false;\n{}    }}",
+                            indent, taken, rem_var, indent, indent
+                        )
+                        .unwrap();
+                    }
                     writeln!(out, "{}    {} = elem_count;", indent, length_accessor).unwrap();
                     writeln!(
                         out,
-                        "{}    for (size_t i = 0; i < elem_count; ++i) {{",
-                        indent
+                        "{}    {}i < elem_count; ++i) {{",
+                        indent,
+                        name_ctx.for_index()
                     )
                     .unwrap();
                     let elem_accessor = format!("{}[i]", accessor);
                     out.push_str(&primitive_decode_stmt(
+                        name_ctx,
                         arr.primitive,
                         field.endian,
                         &elem_accessor,
                         "data + offset",
                         &format!("{}        ", indent),
                     ));
-                    writeln!(out, "{}        offset += {};", indent, elem_size).unwrap();
+                    writeln!(
+                        out,
+                        "{}        offset += {};",
+                        indent,
+                        name_ctx.uint(elem_size)
+                    )
+                    .unwrap();
                     writeln!(out, "{}    }}", indent).unwrap();
+                    if zero_tail {
+                        write_zero_tail(
+                            out,
+                            name_ctx,
+                            &accessor,
+                            "elem_count",
+                            &max_macro,
+                            &format!("{}    ", indent),
+                        );
+                    }
+                    // Leave the rest to the arrays after this one
+                    if elem_size == 1 {
+                        writeln!(out, "{}    {} -= elem_count;", indent, rem_var).unwrap();
+                    } else {
+                        writeln!(
+                            out,
+                            "{}    {} -= elem_count * {};",
+                            indent,
+                            rem_var,
+                            name_ctx.uint(elem_size)
+                        )
+                        .unwrap();
+                    }
                     writeln!(out, "{}}}", indent).unwrap();
                 } else {
                     // No remaining var tracking - decode max elements
                     writeln!(out, "{}{} = {};", indent, length_accessor, max_macro).unwrap();
                     writeln!(
                         out,
-                        "{}for (size_t i = 0; i < {}; ++i) {{",
-                        indent, max_macro
+                        "{}{}i < {}; ++i) {{",
+                        indent,
+                        name_ctx.for_index(),
+                        max_macro
                     )
                     .unwrap();
                     let elem_accessor = format!("{}[i]", accessor);
                     let next_indent = format!("{}    ", indent);
                     out.push_str(&primitive_decode_stmt(
+                        name_ctx,
                         arr.primitive,
                         field.endian,
                         &elem_accessor,
                         "data + offset",
                         &next_indent,
                     ));
-                    writeln!(out, "{}    offset += {};", indent, elem_size).unwrap();
+                    writeln!(out, "{}    offset += {};", indent, name_ctx.uint(elem_size)).unwrap();
                     writeln!(out, "{}}}", indent).unwrap();
                 }
+                if arr.string_semantics {
+                    writeln!(out, "{}{}[{}] = '\\0';", indent, accessor, length_accessor).unwrap();
+                }
             }
             StructFieldType::Nested(nested_spec) => {
                 // Recursively decode nested struct fields
                 let nested_accessor = format!("{}.", accessor);
-                let nested_macro_prefix =
-                    format!("{}_{}", macro_prefix, to_macro_ident(&field.name));
+                let nested_macro_prefix = fields_macro_prefix(
+                    helpers,
+                    nested_spec,
+                    format!("{}_{}", macro_prefix, to_macro_ident(&field.name)),
+                );
                 generate_field_decode_stmts(
                     out,
+                    name_ctx,
                     &nested_spec.fields,
                     &nested_accessor,
                     &nested_macro_prefix,
                     indent,
-                    remaining_var,
+                    remaining_here,
                 );
             }
+            StructFieldType::Bytes(length) if field.reserved => {
+                writeln!(out, "{}offset += {};", indent, name_ctx.uint(*length)).unwrap();
+            }
+            StructFieldType::Bytes(length) => {
+                writeln!(
+                    out,
+                    "{}{}memcpy({}, data + offset, {});",
+                    indent,
+                    name_ctx.discard(),
+                    accessor,
+                    name_ctx.uint(*length)
+                )
+                .unwrap();
+                writeln!(out, "{}offset += {};", indent, name_ctx.uint(*length)).unwrap();
+            }
         }
     }
 }
 
-fn generate_struct_block(
-    msg: &MessageDefinition,
-    spec: &StructSpec,
-    mode: FunctionMode,
-    name_ctx: &NameContext,
-) -> String {
-    let mut out = String::new();
-    let type_name = type_name(msg, name_ctx);
-    let encode_name = encode_fn_name(msg, name_ctx);
-    let decode_name = decode_fn_name(msg, name_ctx);
-    let macro_prefix = msg_macro_prefix(name_ctx, msg);
-
-    // Generate typedef(s) for struct and nested structs
-    generate_struct_typedef(&mut out, &type_name, &macro_prefix, spec);
-
-    let has_variable_arrays = struct_has_variable_arrays(spec);
-    let max_size = struct_byte_len(spec);
-    let min_size = struct_min_byte_len(spec);
-
-    // Generate encode function if needed
-    if mode == FunctionMode::EncodeOnly || mode == FunctionMode::Both {
-        writeln!(
-            &mut out,
-            "static inline size_t {}(const {} *msg, uint8_t *out_buf, const size_t out_len) {{",
-            encode_name, type_name
-        )
-        .unwrap();
-        out.push_str("    if (!msg || !out_buf) {\n        return 0;\n    }\n");
-        writeln!(
-            &mut out,
-            "    if (out_len < {}) {{\n        return 0;\n    }}",
-            max_size
-        )
-        .unwrap();
-        out.push_str("    size_t offset = 0;\n");
-        generate_field_encode_stmts(&mut out, &spec.fields, "msg->", &macro_prefix, "    ");
-        out.push_str("    return offset;\n}\n\n");
-    }
-
-    // Generate decode function if needed
-    if mode == FunctionMode::DecodeOnly || mode == FunctionMode::Both {
-        writeln!(
-            &mut out,
-            "static inline bool {}({} *msg, const uint8_t *data, const size_t data_len) {{",
-            decode_name, type_name
-        )
-        .unwrap();
-        out.push_str("    if (!msg || !data) {\n        return false;\n    }\n");
-
-        if has_variable_arrays {
-            // For structs with variable-length arrays, check minimum size
-            writeln!(
-                &mut out,
-                "    if (data_len < {}) {{\n        return false;\n    }}",
-                min_size
-            )
-            .unwrap();
-            writeln!(
-                &mut out,
-                "    if (data_len > {}) {{\n        return false;\n    }}",
-                max_size
-            )
-            .unwrap();
-            out.push_str("    size_t offset = 0;\n");
-            out.push_str("    size_t remaining = data_len;\n");
-            // Calculate remaining bytes after fixed fields for the array
-            writeln!(&mut out, "    remaining -= {};", min_size).unwrap();
-            generate_field_decode_stmts(
-                &mut out,
-                &spec.fields,
-                "msg->",
-                &macro_prefix,
-                "    ",
-                Some("remaining"),
-            );
-        } else {
-            writeln!(
-                &mut out,
-                "    if (data_len != {}) {{\n        return false;\n    }}",
-                max_size
-            )
-            .unwrap();
-            out.push_str("    size_t offset = 0;\n");
-            generate_field_decode_stmts(
-                &mut out,
-                &spec.fields,
-                "msg->",
-                &macro_prefix,
-                "    ",
-                None,
-            );
-        }
-        out.push_str("    return true;\n}\n\n");
-    }
-
-    out
-}
-
 fn primitive_encode_stmt(
+    name_ctx: &NameContext,
     primitive: PrimitiveType,
     endian: Endian,
     source: &str,
     dest_ptr: &str,
     indent: &str,
 ) -> String {
-    match primitive {
+    let helpers = &name_ctx.helpers;
+    let stmt = match primitive {
+        PrimitiveType::Bool if name_ctx.misra => format!(
+            "{indent}({dest})[0] = (uint8_t)(({src}) ? 1U : 0U);\n",
+            indent = indent,
+            dest = dest_ptr,
+            src = source
+        ),
         PrimitiveType::Bool => format!(
             "{indent}({dest})[0] = ({src}) ? 1 : 0;\n",
             indent = indent,
@@ -1403,72 +6287,96 @@ fn primitive_encode_stmt(
             src = source
         ),
         PrimitiveType::Int16 => format!(
-            "{indent}h6xserial_write_u16_{suffix}((uint16_t)({src}), {dest});\n",
+            "{indent}{helpers}_write_u16_{suffix}((uint16_t)({src}), {dest});\n",
             indent = indent,
             suffix = endian.suffix(),
             src = source,
             dest = dest_ptr
         ),
         PrimitiveType::Uint16 => format!(
-            "{indent}h6xserial_write_u16_{suffix}((uint16_t)({src}), {dest});\n",
+            "{indent}{helpers}_write_u16_{suffix}((uint16_t)({src}), {dest});\n",
+            indent = indent,
+            suffix = endian.suffix(),
+            src = source,
+            dest = dest_ptr
+        ),
+        // The low 3 bytes of the two's complement value
+        PrimitiveType::Int24 | PrimitiveType::Uint24 => format!(
+            "{indent}{helpers}_write_u24_{suffix}((uint32_t)({src}), {dest});\n",
             indent = indent,
             suffix = endian.suffix(),
             src = source,
             dest = dest_ptr
         ),
         PrimitiveType::Int32 => format!(
-            "{indent}h6xserial_write_u32_{suffix}((uint32_t)({src}), {dest});\n",
+            "{indent}{helpers}_write_u32_{suffix}((uint32_t)({src}), {dest});\n",
             indent = indent,
             suffix = endian.suffix(),
             src = source,
             dest = dest_ptr
         ),
         PrimitiveType::Uint32 => format!(
-            "{indent}h6xserial_write_u32_{suffix}((uint32_t)({src}), {dest});\n",
+            "{indent}{helpers}_write_u32_{suffix}((uint32_t)({src}), {dest});\n",
             indent = indent,
             suffix = endian.suffix(),
             src = source,
             dest = dest_ptr
         ),
         PrimitiveType::Int64 => format!(
-            "{indent}h6xserial_write_u64_{suffix}((uint64_t)({src}), {dest});\n",
+            "{indent}{helpers}_write_u64_{suffix}((uint64_t)({src}), {dest});\n",
             indent = indent,
             suffix = endian.suffix(),
             src = source,
             dest = dest_ptr
         ),
         PrimitiveType::Uint64 => format!(
-            "{indent}h6xserial_write_u64_{suffix}((uint64_t)({src}), {dest});\n",
+            "{indent}{helpers}_write_u64_{suffix}((uint64_t)({src}), {dest});\n",
+            indent = indent,
+            suffix = endian.suffix(),
+            src = source,
+            dest = dest_ptr
+        ),
+        PrimitiveType::Float16 => format!(
+            "{indent}{helpers}_write_f16_{suffix}({src}, {dest});\n",
             indent = indent,
             suffix = endian.suffix(),
             src = source,
             dest = dest_ptr
         ),
         PrimitiveType::Float32 => format!(
-            "{indent}h6xserial_write_f32_{suffix}({src}, {dest});\n",
+            "{indent}{helpers}_write_f32_{suffix}({src}, {dest});\n",
             indent = indent,
             suffix = endian.suffix(),
             src = source,
             dest = dest_ptr
         ),
         PrimitiveType::Float64 => format!(
-            "{indent}h6xserial_write_f64_{suffix}({src}, {dest});\n",
+            "{indent}{helpers}_write_f64_{suffix}({src}, {dest});\n",
             indent = indent,
             suffix = endian.suffix(),
             src = source,
             dest = dest_ptr
         ),
-    }
+    };
+    misra_sign_note(name_ctx, primitive, stmt)
 }
 
 fn primitive_decode_stmt(
+    name_ctx: &NameContext,
     primitive: PrimitiveType,
     endian: Endian,
     dest: &str,
     src_ptr: &str,
     indent: &str,
 ) -> String {
-    match primitive {
+    let helpers = &name_ctx.helpers;
+    let stmt = match primitive {
+        PrimitiveType::Bool if name_ctx.misra => format!(
+            "{indent}{dest} = (({src})[0]) != 0U;\n",
+            indent = indent,
+            dest = dest,
+            src = src_ptr
+        ),
         PrimitiveType::Bool => format!(
             "{indent}{dest} = (({src})[0]) != 0;\n",
             indent = indent,
@@ -1494,81 +6402,207 @@ fn primitive_decode_stmt(
             src = src_ptr
         ),
         PrimitiveType::Int16 => format!(
-            "{indent}{dest} = (int16_t)h6xserial_read_u16_{suffix}({src});\n",
+            "{indent}{dest} = (int16_t){helpers}_read_u16_{suffix}({src});\n",
             indent = indent,
             dest = dest,
             suffix = endian.suffix(),
             src = src_ptr
         ),
         PrimitiveType::Uint16 => format!(
-            "{indent}{dest} = h6xserial_read_u16_{suffix}({src});\n",
+            "{indent}{dest} = {helpers}_read_u16_{suffix}({src});\n",
+            indent = indent,
+            dest = dest,
+            suffix = endian.suffix(),
+            src = src_ptr
+        ),
+        // Flipping the sign bit maps the value onto 0..0xFFFFFF, which
+        // fits int32_t; subtracting the offset then extends the sign
+        PrimitiveType::Int24 => format!(
+            "{indent}{dest} = (int32_t)({helpers}_read_u24_{suffix}({src}) ^ 0x800000u) - (int32_t)0x800000;\n",
+            indent = indent,
+            dest = dest,
+            suffix = endian.suffix(),
+            src = src_ptr
+        ),
+        PrimitiveType::Uint24 => format!(
+            "{indent}{dest} = {helpers}_read_u24_{suffix}({src});\n",
             indent = indent,
             dest = dest,
             suffix = endian.suffix(),
             src = src_ptr
         ),
         PrimitiveType::Int32 => format!(
-            "{indent}{dest} = (int32_t)h6xserial_read_u32_{suffix}({src});\n",
+            "{indent}{dest} = (int32_t){helpers}_read_u32_{suffix}({src});\n",
             indent = indent,
             dest = dest,
             suffix = endian.suffix(),
             src = src_ptr
         ),
         PrimitiveType::Uint32 => format!(
-            "{indent}{dest} = h6xserial_read_u32_{suffix}({src});\n",
+            "{indent}{dest} = {helpers}_read_u32_{suffix}({src});\n",
             indent = indent,
             dest = dest,
             suffix = endian.suffix(),
             src = src_ptr
         ),
         PrimitiveType::Int64 => format!(
-            "{indent}{dest} = (int64_t)h6xserial_read_u64_{suffix}({src});\n",
+            "{indent}{dest} = (int64_t){helpers}_read_u64_{suffix}({src});\n",
             indent = indent,
             dest = dest,
             suffix = endian.suffix(),
             src = src_ptr
         ),
         PrimitiveType::Uint64 => format!(
-            "{indent}{dest} = h6xserial_read_u64_{suffix}({src});\n",
+            "{indent}{dest} = {helpers}_read_u64_{suffix}({src});\n",
+            indent = indent,
+            dest = dest,
+            suffix = endian.suffix(),
+            src = src_ptr
+        ),
+        PrimitiveType::Float16 => format!(
+            "{indent}{dest} = {helpers}_read_f16_{suffix}({src});\n",
             indent = indent,
             dest = dest,
             suffix = endian.suffix(),
             src = src_ptr
         ),
         PrimitiveType::Float32 => format!(
-            "{indent}{dest} = h6xserial_read_f32_{suffix}({src});\n",
+            "{indent}{dest} = {helpers}_read_f32_{suffix}({src});\n",
             indent = indent,
             dest = dest,
             suffix = endian.suffix(),
             src = src_ptr
         ),
         PrimitiveType::Float64 => format!(
-            "{indent}{dest} = h6xserial_read_f64_{suffix}({src});\n",
+            "{indent}{dest} = {helpers}_read_f64_{suffix}({src});\n",
             indent = indent,
             dest = dest,
             suffix = endian.suffix(),
             src = src_ptr
         ),
+    };
+    misra_sign_note(name_ctx, primitive, stmt)
+}
+
+/// In MISRA mode, marks a statement that converts between a signed (or
+/// character) value and its unsigned wire form with the deviation that
+/// covers it.
+fn misra_sign_note(name_ctx: &NameContext, primitive: PrimitiveType, stmt: String) -> String {
+    let signed = matches!(
+        primitive,
+        PrimitiveType::Char
+            | PrimitiveType::Int8
+            | PrimitiveType::Int16
+            | PrimitiveType::Int24
+            | PrimitiveType::Int32
+            | PrimitiveType::Int64
+    );
+    match stmt.strip_suffix(";\n") {
+        Some(stmt) if name_ctx.misra && signed => format!("{}; /* deviation D1 */\n", stmt),
+        _ => stmt,
+    }
+}
+
+fn type_name(msg: &MessageDefinition, name_ctx: &NameContext) -> String {
+    format!("{}_t", name_ctx.msg_fn_prefix(&msg.name))
+}
+
+fn encode_fn_name(msg: &MessageDefinition, name_ctx: &NameContext) -> String {
+    format!("{}_encode", name_ctx.msg_fn_prefix(&msg.name))
+}
+
+fn decode_fn_name(msg: &MessageDefinition, name_ctx: &NameContext) -> String {
+    format!("{}_decode", name_ctx.msg_fn_prefix(&msg.name))
+}
+
+/// Generates compile-time checks tying the `*_MAX_SIZE` macros to the sizes
+/// computed by the generator, plus the target's primitive sizes.
+fn generate_static_asserts(messages: &[MessageDefinition], name_ctx: &NameContext) -> String {
+    fn collect_primitives(spec: &StructSpec, used: &mut Vec<PrimitiveType>) {
+        for field in &spec.fields {
+            match &field.field_type {
+                StructFieldType::Primitive(prim) => used.push(*prim),
+                StructFieldType::Array(arr) => used.push(arr.primitive),
+                StructFieldType::Nested(nested) => collect_primitives(nested, used),
+                StructFieldType::Bytes(_) => {}
+            }
+        }
+    }
+
+    let mut used = Vec::new();
+    for msg in messages {
+        match &msg.body {
+            MessageBody::Scalar(spec) => used.push(spec.primitive),
+            MessageBody::Array(spec) => used.push(spec.primitive),
+            MessageBody::Struct(spec) => collect_primitives(spec, &mut used),
+            MessageBody::Union(spec) => {
+                used.push(spec.tag);
+                for variant in &spec.variants {
+                    collect_primitives(&variant.spec, &mut used);
+                }
+            }
+        }
+    }
+
+    let mut out = String::new();
+    out.push_str("/* Compile-time checks (define H6XSERIAL_NO_STATIC_ASSERT to disable) */\n");
+    out.push_str(static_assert_macro(name_ctx.dialect));
+    out.push('\n');
+
+    // Only primitives the protocol uses: e.g. AVR has a 4-byte double
+    let mut seen = HashSet::new();
+    for prim in [
+        PrimitiveType::Uint16,
+        PrimitiveType::Int16,
+        PrimitiveType::Uint32,
+        PrimitiveType::Int32,
+        PrimitiveType::Uint64,
+        PrimitiveType::Int64,
+        PrimitiveType::Float32,
+        PrimitiveType::Float64,
+    ] {
+        if used.contains(&prim) && seen.insert(prim.c_type()) {
+            writeln!(
+                &mut out,
+                "H6XSERIAL_STATIC_ASSERT(sizeof({}) == {}, {}_{}_size_{})",
+                prim.c_type(),
+                name_ctx.uint(prim.byte_len()),
+                name_ctx.msg_prefix,
+                prim.c_type().trim_end_matches("_t"),
+                prim.byte_len()
+            )
+            .unwrap();
+        }
+    }
+
+    for msg in messages {
+        let macro_prefix = msg_macro_prefix(name_ctx, msg);
+        writeln!(
+            &mut out,
+            "H6XSERIAL_STATIC_ASSERT({}_MAX_SIZE == {}, {}_max_size)",
+            macro_prefix,
+            name_ctx.uint(message_body_max_size(&msg.body)),
+            macro_prefix.to_ascii_lowercase()
+        )
+        .unwrap();
     }
-}
 
-fn type_name(msg: &MessageDefinition, name_ctx: &NameContext) -> String {
-    format!("{}_msg_{}_t", name_ctx.msg_prefix, to_snake_case(&msg.name))
+    out
 }
 
-fn encode_fn_name(msg: &MessageDefinition, name_ctx: &NameContext) -> String {
+fn encode_signature(msg: &MessageDefinition, name_ctx: &NameContext) -> String {
     format!(
-        "{}_msg_{}_encode",
-        name_ctx.msg_prefix,
-        to_snake_case(&msg.name)
+        "size_t {}(const {} *msg, uint8_t *out_buf, const size_t out_len)",
+        encode_fn_name(msg, name_ctx),
+        type_name(msg, name_ctx)
     )
 }
 
-fn decode_fn_name(msg: &MessageDefinition, name_ctx: &NameContext) -> String {
+fn decode_signature(msg: &MessageDefinition, name_ctx: &NameContext) -> String {
     format!(
-        "{}_msg_{}_decode",
-        name_ctx.msg_prefix,
-        to_snake_case(&msg.name)
+        "bool {}({} *msg, const uint8_t *data, const size_t data_len)",
+        decode_fn_name(msg, name_ctx),
+        type_name(msg, name_ctx)
     )
 }
 
@@ -1598,27 +6632,98 @@ fn header_guard_name_from_str(file_name: &str) -> String {
     guard
 }
 
-fn generate_byteorder_header(input_path: &Path, helper_block: &str) -> String {
+/// Splits the byte order helpers for source style into the part for the
+/// header, where each helper function is declared, and their definitions.
+///
+/// A helper function is a `static` definition whose first line ends with
+/// `{` and whose body ends with a `}` line, as in the default templates.
+/// Everything else (macros, size checks, helpers of templates written
+/// differently) stays in the header unchanged.
+fn split_helpers(helpers: &str, inline_keyword: &str) -> (String, String) {
+    let mut declarations = String::new();
+    let mut definitions = String::new();
+    let mut lines = helpers.lines().peekable();
+    while let Some(line) = lines.next() {
+        let signature = line
+            .strip_prefix("static ")
+            .and_then(|rest| rest.strip_suffix('{'))
+            .filter(|signature| signature.contains('('));
+        let Some(signature) = signature else {
+            declarations.push_str(line);
+            declarations.push('\n');
+            continue;
+        };
+        let signature = signature.trim_end();
+        let signature = signature
+            .strip_prefix(inline_keyword)
+            .and_then(|rest| rest.strip_prefix(' '))
+            .unwrap_or(signature);
+        writeln!(declarations, "{};", signature).unwrap();
+        if !definitions.is_empty() {
+            definitions.push('\n');
+        }
+        writeln!(definitions, "{} {{", signature).unwrap();
+        for line in lines.by_ref() {
+            definitions.push_str(line);
+            definitions.push('\n');
+            if line == "}" {
+                break;
+            }
+        }
+        // The blank line between two helpers
+        lines.next_if_eq(&"");
+    }
+    (declarations, definitions)
+}
+
+/// Generates `h6x_serial_byteorder.h`. `helpers` are the helper functions,
+/// or their declarations in source style.
+fn generate_byteorder_header(templates: &Templates, helpers: &str, dialect: CDialect) -> String {
     let header_guard = header_guard_name_from_str(BYTEORDER_HEADER_FILENAME);
     let mut out = String::new();
     writeln!(&mut out, "/*").unwrap();
-    writeln!(&mut out, " * Auto-generated by h6xserial_idl.").unwrap();
-    writeln!(&mut out, " * Source: {}", input_path.display()).unwrap();
+    writeln!(&mut out, " * Auto-generated by {}.", GENERATOR).unwrap();
+    templates.write_origin(&mut out);
     writeln!(&mut out, " * Byte order helper functions").unwrap();
     writeln!(&mut out, " */\n").unwrap();
 
     writeln!(&mut out, "#ifndef {}", header_guard).unwrap();
     writeln!(&mut out, "#define {}\n", header_guard).unwrap();
 
-    out.push_str("#include <stdint.h>\n\n");
+    out.push_str("#include <stdint.h>\n#include <string.h>\n\n");
+    if dialect == CDialect::C89 {
+        out.push_str(C89_COMPAT);
+        out.push('\n');
+    }
+    templates.write_prologue(&mut out);
     out.push_str("#ifdef __cplusplus\nextern \"C\" {\n#endif\n\n");
-    out.push_str(helper_block);
+    out.push_str(helpers);
     out.push_str("\n#ifdef __cplusplus\n}\n#endif\n\n");
+    templates.write_epilogue(&mut out);
     writeln!(&mut out, "#endif /* {} */", header_guard).unwrap();
 
     out
 }
 
+/// Generates `h6x_serial_byteorder.c`, the `definitions` of the helpers
+/// declared by the byte order header in source style.
+fn generate_byteorder_source(templates: &Templates, definitions: &str) -> String {
+    let mut out = String::new();
+    writeln!(&mut out, "/*").unwrap();
+    writeln!(&mut out, " * Auto-generated by {}.", GENERATOR).unwrap();
+    templates.write_origin(&mut out);
+    writeln!(&mut out, " * Byte order helper functions").unwrap();
+    writeln!(
+        &mut out,
+        " */
+"
+    )
+    .unwrap();
+    writeln!(&mut out, "#include \"{}\"\n", BYTEORDER_HEADER_FILENAME).unwrap();
+    out.push_str(definitions);
+    out
+}
+
 fn emit_own_device_definitions(out: &mut String, metadata: &Metadata, role: Role) -> bool {
     let (own_id, own_device) = match role {
         Role::Server => {
@@ -1643,7 +6748,7 @@ fn emit_own_device_definitions(out: &mut String, metadata: &Metadata, role: Role
     writeln!(out, "#define OWN_ID {}", own_id).unwrap();
     if let Some(device) = own_device {
         if let Some(desc) = &device.description {
-            writeln!(out, "/* {} */", desc).unwrap();
+            writeln!(out, "/* {} */", comment_text(desc)).unwrap();
         }
         let name_macro = to_macro_ident(&device.name);
         writeln!(out, "#define {}_ID OWN_ID", name_macro).unwrap();
@@ -1651,7 +6756,7 @@ fn emit_own_device_definitions(out: &mut String, metadata: &Metadata, role: Role
     writeln!(out, "#else").unwrap();
     if let Some(device) = own_device {
         if let Some(desc) = &device.description {
-            writeln!(out, "/* {} */", desc).unwrap();
+            writeln!(out, "/* {} */", comment_text(desc)).unwrap();
         }
         let name_macro = to_macro_ident(&device.name);
         writeln!(out, "#define {}_ID {}", name_macro, own_id).unwrap();
@@ -1660,3 +6765,1050 @@ fn emit_own_device_definitions(out: &mut String, metadata: &Metadata, role: Role
 
     true
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StructFieldArraySpec;
+
+    fn field(name: &str, prim: PrimitiveType, endian: Endian) -> StructField {
+        StructField {
+            name: name.to_string(),
+            field_type: StructFieldType::Primitive(prim),
+            endian,
+            scaling: None,
+            range: None,
+            description: None,
+            unit: None,
+            timestamp: None,
+            since_version: None,
+            at: None,
+            reserved: false,
+        }
+    }
+
+    #[test]
+    fn test_fast_path_eligible_for_aligned_little_endian_struct() {
+        let spec = StructSpec {
+            fields: vec![
+                field("a", PrimitiveType::Uint32, Endian::Little),
+                field("b", PrimitiveType::Uint16, Endian::Little),
+                field("c", PrimitiveType::Uint8, Endian::Little),
+                field("d", PrimitiveType::Int8, Endian::Little),
+            ],
+            shared: None,
+        };
+        assert!(struct_fast_path_eligible(&spec));
+    }
+
+    #[test]
+    fn test_fast_path_rejects_padding_big_endian_bool_and_arrays() {
+        let padded = StructSpec {
+            fields: vec![
+                field("a", PrimitiveType::Uint8, Endian::Little),
+                field("b", PrimitiveType::Float32, Endian::Little),
+            ],
+            shared: None,
+        };
+        assert!(!struct_fast_path_eligible(&padded));
+
+        let big = StructSpec {
+            fields: vec![field("a", PrimitiveType::Uint32, Endian::Big)],
+            shared: None,
+        };
+        assert!(!struct_fast_path_eligible(&big));
+
+        let with_bool = StructSpec {
+            fields: vec![field("a", PrimitiveType::Bool, Endian::Little)],
+            shared: None,
+        };
+        assert!(!struct_fast_path_eligible(&with_bool));
+
+        let with_array = StructSpec {
+            fields: vec![StructField {
+                name: "a".to_string(),
+                field_type: StructFieldType::Array(StructFieldArraySpec {
+                    primitive: PrimitiveType::Uint8,
+                    max_length: 4,
+                    fixed: false,
+                    string_semantics: false,
+                }),
+                endian: Endian::Little,
+                scaling: None,
+                range: None,
+                description: None,
+                unit: None,
+                timestamp: None,
+                since_version: None,
+                at: None,
+                reserved: false,
+            }],
+            shared: None,
+        };
+        assert!(!struct_fast_path_eligible(&with_array));
+
+        // Tail padding: 4-byte alignment but only 5 bytes of data
+        let tail_padded = StructSpec {
+            fields: vec![
+                field("a", PrimitiveType::Uint32, Endian::Little),
+                field("b", PrimitiveType::Uint8, Endian::Little),
+            ],
+            shared: None,
+        };
+        assert!(!struct_fast_path_eligible(&tail_padded));
+    }
+
+    #[test]
+    fn test_static_asserts_cover_used_primitives_and_sizes() {
+        let messages = vec![MessageDefinition {
+            name: "sample".to_string(),
+            packet_id: 3,
+            packet_id_radix: crate::Radix::Decimal,
+            description: None,
+            request_type: RequestType::Pub,
+            body: MessageBody::Struct(StructSpec {
+                fields: vec![
+                    field("a", PrimitiveType::Uint16, Endian::Little),
+                    field("b", PrimitiveType::Float32, Endian::Big),
+                ],
+                shared: None,
+            }),
+            request_type_given: true,
+            target_client_id: -1,
+            target_client_id_given: false,
+            deprecated: None,
+            reserved_ok: false,
+            response: None,
+            msg_version: None,
+            group: None,
+        }];
+        let name_ctx = NameContext::new(
+            "demo",
+            &CGenOptions::default(),
+            &Metadata::default(),
+            &messages,
+        );
+
+        let checks = generate_static_asserts(&messages, &name_ctx);
+        assert!(checks.contains("#define H6XSERIAL_STATIC_ASSERT(cond, name) _Static_assert"));
+        assert!(
+            checks.contains("H6XSERIAL_STATIC_ASSERT(sizeof(uint16_t) == 2, demo_uint16_size_2)")
+        );
+        assert!(checks.contains("H6XSERIAL_STATIC_ASSERT(sizeof(float) == 4, demo_float_size_4)"));
+        assert!(!checks.contains("sizeof(double)"));
+        assert!(checks.contains(
+            "H6XSERIAL_STATIC_ASSERT(DEMO_MSG_SAMPLE_MAX_SIZE == 6, demo_msg_sample_max_size)"
+        ));
+    }
+
+    #[test]
+    fn test_const_literals() {
+        assert_eq!(const_literal(&ConstValue::Int(165)), "165");
+        assert_eq!(const_literal(&ConstValue::Int(-3)), "(-3)");
+        assert_eq!(
+            const_literal(&ConstValue::Int(i64::MIN)),
+            "(-9223372036854775807LL - 1)"
+        );
+        assert_eq!(const_literal(&ConstValue::Float(9.5)), "9.5");
+        assert_eq!(const_literal(&ConstValue::Float(2.0)), "2.0");
+        assert_eq!(const_literal(&ConstValue::Float(-0.25)), "(-0.25)");
+        assert_eq!(
+            const_literal(&ConstValue::Str("v1 \"b\"\\??\n\u{e9}".to_string())),
+            r#""v1 \"b\"\\\?\?\n\303\251""#
+        );
+    }
+
+    #[test]
+    fn test_range_checks_in_decoders_and_encoders() {
+        let (metadata, messages) = crate::parse_str(
+            r#"{ "packets": {
+                 "speed": { "packet_id": 1, "msg_type": "uint16", "min": 10, "max": 1000 },
+                 "trim": { "packet_id": 2, "msg_type": "int8", "min": -5, "max": 127 },
+                 "gain": { "packet_id": 3, "msg_type": "float32", "min": -0.5, "max": 2.5 },
+                 "pose": { "packet_id": 4, "msg_type": "struct", "fields": {
+                     "x": { "type": "i32", "min": -100000 },
+                     "n": { "type": "u64", "max": 5000000000 } } } } }"#,
+        )
+        .unwrap();
+        let render = |encode_range| {
+            let options = CGenOptions {
+                encode_range,
+                ..CGenOptions::default()
+            };
+            generate_files(
+                &metadata,
+                &messages,
+                Path::new("limits.json"),
+                Path::new("limits.h"),
+                &options,
+            )
+            .unwrap()
+            .remove(0)
+            .content
+        };
+
+        let header = render(EncodeRange::Off);
+        assert!(header.contains(
+            "    if (msg->value < 10 || msg->value > 1000) {\n        return false;\n    }"
+        ));
+        // The upper bound of int8_t needs no check
+        assert!(header.contains("    if (msg->value < -5) {\n        return false;\n    }"));
+        assert!(header.contains("    if (!(msg->value >= -0.5f && msg->value <= 2.5f)) {"));
+        assert!(header.contains("    if (msg->x < -100000) {"));
+        assert!(header.contains("    if (msg->n > 5000000000ULL) {"));
+        assert!(!header.contains("return 0;\n    }\n    if (msg->"));
+
+        let header = render(EncodeRange::Reject);
+        assert!(
+            header.contains(
+                "    if (msg->value < 10 || msg->value > 1000) {\n        return 0;\n    }"
+            )
+        );
+
+        let header = render(EncodeRange::Clamp);
+        assert!(header.contains(
+            "h6xserial_write_u16_le((uint16_t)((msg->value < 10 ? 10 : (msg->value > 1000 ? 1000 : msg->value))), out_buf);"
+        ));
+    }
+
+    #[test]
+    fn test_member_comments_cannot_close_early() {
+        assert_eq!(member_comment(None, None, false), "");
+        assert_eq!(member_comment(None, Some("rpm"), false), " /* rpm */");
+        assert_eq!(
+            member_comment(Some("motor speed"), Some("rpm"), false),
+            " /* rpm: motor speed */"
+        );
+        assert_eq!(
+            member_comment(Some("ends */ here\nnext /* line"), None, false),
+            " /* ends * / here next / * line */"
+        );
+        assert_eq!(
+            member_comment(Some("ends */ here"), None, true),
+            " /**< ends * / here */"
+        );
+    }
+
+    #[test]
+    fn test_deprecated_functions_carry_the_attribute() {
+        let (metadata, messages) = crate::parse_str(
+            r#"{ "packets": {
+                 "old": { "packet_id": 1, "msg_type": "u16", "deprecated": "use \"new\"" },
+                 "legacy": { "packet_id": 2, "msg_type": "struct", "deprecated": true,
+                             "fields": { "x": { "type": "u8" } } },
+                 "new": { "packet_id": 3, "msg_type": "u16" } } }"#,
+        )
+        .unwrap();
+        let header = generate(
+            &metadata,
+            &messages,
+            Path::new("demo.json"),
+            Path::new("demo.h"),
+        )
+        .unwrap();
+        assert!(header.contains(DEPRECATED_MACRO));
+        assert!(
+            header.contains("/* Deprecated: use \"new\" */\n#define DEMO_MSG_OLD_PACKET_ID 1\n")
+        );
+        assert!(header.contains(
+            "\nH6XSERIAL_DEPRECATED(\"use \\\"new\\\"\") static inline size_t demo_msg_old_encode("
+        ));
+        assert!(header.contains(
+            "H6XSERIAL_DEPRECATED(\"message 'legacy' is deprecated\") static inline bool demo_msg_legacy_decode("
+        ));
+        assert!(header.contains("\nstatic inline size_t demo_msg_new_encode("));
+
+        let options = CGenOptions {
+            impl_style: ImplStyle::Source,
+            ..CGenOptions::default()
+        };
+        let files = generate_files(
+            &metadata,
+            &messages,
+            Path::new("demo.json"),
+            Path::new("demo.h"),
+            &options,
+        )
+        .unwrap();
+        assert!(files[0].content.contains(
+            "H6XSERIAL_DEPRECATED(\"use \\\"new\\\"\") size_t demo_msg_old_encode(const demo_msg_old_t *msg, uint8_t *out_buf, const size_t out_len);"
+        ));
+
+        let (metadata, messages) =
+            crate::parse_str(r#"{ "new": { "packet_id": 3, "msg_type": "u16" } }"#).unwrap();
+        let header = generate(
+            &metadata,
+            &messages,
+            Path::new("demo.json"),
+            Path::new("demo.h"),
+        )
+        .unwrap();
+        assert!(!header.contains("H6XSERIAL_DEPRECATED"));
+    }
+
+    #[test]
+    fn test_source_style_defines_helpers_in_a_source_file() {
+        let (metadata, messages) = crate::parse_str(
+            r#"{ "level": { "packet_id": 1, "msg_type": "f32" },
+                 "speed": { "packet_id": 2, "msg_type": "u16", "endianess": "big" } }"#,
+        )
+        .unwrap();
+        let options = CGenOptions {
+            impl_style: ImplStyle::Source,
+            dialect: CDialect::C89,
+            fast_path: true,
+            ..Default::default()
+        };
+        let files = generate_multiple_with_options(
+            &metadata,
+            &messages,
+            Path::new("t.json"),
+            "t",
+            &options,
+        )
+        .unwrap();
+        let file = |name: &str| {
+            &files
+                .iter()
+                .find(|file| file.filename == name)
+                .unwrap()
+                .content
+        };
+        let header = file("h6x_serial_byteorder.h");
+        assert!(!header.contains(") {"), "{}", header);
+        assert!(header.contains(
+            "void h6xserial_write_f32_le(float value, uint8_t *out);\nfloat h6xserial_read_f32_le(const uint8_t *in);\n"
+        ));
+        assert!(header.contains("#define H6XSERIAL_HOST_LITTLE_ENDIAN 1\n"));
+        let source = file("h6x_serial_byteorder.c");
+        assert!(
+            source.contains("#include \"h6x_serial_byteorder.h\"\n\nvoid h6xserial_write_u16_be(")
+        );
+        assert!(
+            source
+                .contains("}\n\nuint16_t h6xserial_read_u16_be(const uint8_t *in) {\n    return ")
+        );
+        assert!(!source.contains("static"));
+        assert!(!source.contains("H6XSERIAL_INLINE"));
+    }
+
+    #[test]
+    fn test_generate_for_role_matches_full_generation() {
+        let (metadata, messages) = crate::parse_str(
+            r#"{ "ping": { "packet_id": 1, "msg_type": "u8" },
+                 "temp": { "packet_id": 2, "msg_type": "f32", "target_client_id": 3 },
+                 "speed": { "packet_id": 3, "msg_type": "i16", "target_client_id": 5,
+                            "request_type": "sub" } }"#,
+        )
+        .unwrap();
+        let options = CGenOptions {
+            impl_style: ImplStyle::Source,
+            ..Default::default()
+        };
+        let full = generate_multiple_with_options(
+            &metadata,
+            &messages,
+            Path::new("t.json"),
+            "t",
+            &options,
+        )
+        .unwrap();
+        let for_role = |role: Role, options: &CGenOptions| {
+            generate_for_role(
+                &metadata,
+                &messages,
+                Path::new("t.json"),
+                "t",
+                options,
+                role,
+            )
+        };
+        let names = |files: &[OutputFile]| -> Vec<String> {
+            files.iter().map(|file| file.filename.clone()).collect()
+        };
+
+        for (role, expected) in [
+            (Role::Server, ["t_server.h", "t_server.c"]),
+            (
+                Role::ClientCommon,
+                ["t_client_common.h", "t_client_common.c"],
+            ),
+            (Role::Client(5), ["t_client_5.h", "t_client_5.c"]),
+        ] {
+            let files = for_role(role, &options).unwrap();
+            let mut all = vec![
+                "h6x_serial_byteorder.h".to_string(),
+                "h6x_serial_byteorder.c".to_string(),
+                "t_types.h".to_string(),
+            ];
+            all.extend(expected.map(String::from));
+            assert_eq!(names(&files), all);
+            for file in &files {
+                let same = full.iter().find(|f| f.filename == file.filename).unwrap();
+                assert_eq!(file.content, same.content, "{} differs", file.filename);
+            }
+        }
+
+        let options = CGenOptions {
+            types_header: Some("../include/t_types.h".to_string()),
+            ..Default::default()
+        };
+        let files = for_role(Role::Client(3), &options).unwrap();
+        assert_eq!(names(&files), ["t_client_3.h"]);
+        assert!(
+            files[0]
+                .content
+                .contains("#include \"../include/t_types.h\"\n#include \"t_client_common.h\"\n")
+        );
+
+        let err = for_role(Role::Client(4), &options).unwrap_err();
+        assert_eq!(err.pointer(), Some("/packets"));
+        assert!(
+            err.to_string()
+                .contains("no message targets client 4 (clients: 3, 5)")
+        );
+    }
+
+    #[test]
+    fn test_response_pairs() {
+        let (metadata, messages) = crate::parse_str(
+            r#"{ "get_temp": { "packet_id": 1, "msg_type": "u8", "request_type": "sub",
+                               "response": "temp" },
+                 "temp": { "packet_id": 2, "msg_type": "f32" },
+                 "ping": { "packet_id": 3, "msg_type": "u8", "response": "pong" },
+                 "pong": { "packet_id": 4, "msg_type": "u8" } }"#,
+        )
+        .unwrap();
+        let files = generate_multiple(&metadata, &messages, Path::new("t.json"), "t").unwrap();
+        let file = |name: &str| {
+            &files
+                .iter()
+                .find(|file| file.filename == name)
+                .unwrap()
+                .content
+        };
+        let types = file("t_types.h");
+        assert!(types.contains("#define T_MSG_GET_TEMP_RESPONSE_PACKET_ID T_MSG_TEMP_PACKET_ID\n"));
+        assert!(types.contains(
+            "static inline int t_response_packet_id_for(uint8_t request_id) {\n    switch (request_id) {\n    case T_MSG_GET_TEMP_PACKET_ID:\n        return T_MSG_GET_TEMP_RESPONSE_PACKET_ID;\n"
+        ));
+        assert!(file("t_client_common.h").contains(
+            "static inline uint8_t t_msg_get_temp_expected_response_id(void) {\n    return T_MSG_GET_TEMP_RESPONSE_PACKET_ID;\n}\n"
+        ));
+        assert!(!file("t_server.h").contains("expected_response_id"));
+
+        // The single header defines the response macros before using them
+        let header = generate(&metadata, &messages, Path::new("t.json"), Path::new("t.h")).unwrap();
+        let defined = header.find("#define T_MSG_TEMP_PACKET_ID").unwrap();
+        let used = header.find("t_msg_get_temp_expected_response_id").unwrap();
+        assert!(defined < used);
+
+        // ping and pong are both pub
+        assert_eq!(
+            response_warnings(&messages),
+            vec![
+                "'ping' and its response 'pong' are both pub; a response normally goes the other way"
+                    .to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_send_stubs() {
+        let (metadata, messages) = crate::parse_str(
+            r#"{ "ping": { "packet_id": 1, "msg_type": "u16" },
+                 "blob": { "packet_id": 2, "msg_type": "uint8", "array": true, "max_length": 64 },
+                 "cmd": { "packet_id": 3, "msg_type": "u8", "request_type": "sub" } }"#,
+        )
+        .unwrap();
+        let options = CGenOptions {
+            send_stack_limit: Some(32),
+            symbol_prefix: Some("acme".to_string()),
+            ..Default::default()
+        };
+        let files = generate_multiple_with_options(
+            &metadata,
+            &messages,
+            Path::new("t.json"),
+            "t",
+            &options,
+        )
+        .unwrap();
+        let file = |name: &str| {
+            &files
+                .iter()
+                .find(|file| file.filename == name)
+                .unwrap()
+                .content
+        };
+        assert!(file("t_types.h").contains(
+            "#ifndef ACME_TX_FN_DEFINED\n#define ACME_TX_FN_DEFINED\n/* Transmits len bytes of data; returns false on failure */\ntypedef bool (*acme_tx_fn)(const uint8_t *data, size_t len, void *ctx);\n#endif\n"
+        ));
+        let server = file("t_server.h");
+        assert!(server.contains(
+            "static inline bool acme_send_ping(const t_msg_ping_t *msg, acme_tx_fn tx, void *ctx) {\n    uint8_t buf[T_MSG_PING_MAX_SIZE];\n    const size_t len = t_msg_ping_encode(msg, buf, sizeof(buf));\n    if (len == 0) {\n"
+        ));
+        // 64 bytes exceed the stack limit; an empty array encodes to 0 bytes
+        assert!(server.contains(
+            "static inline bool acme_send_blob(const t_msg_blob_t *msg, uint8_t *buf, const size_t buf_len, acme_tx_fn tx, void *ctx) {\n    const size_t len = t_msg_blob_encode(msg, buf, buf_len);\n    if (len == 0 && (!msg || msg->length != 0)) {\n"
+        ));
+        assert!(!server.contains("acme_send_cmd"));
+        assert!(file("t_client_common.h").contains("static inline bool acme_send_cmd("));
+
+        let files = generate_multiple_with_options(
+            &metadata,
+            &messages,
+            Path::new("t.json"),
+            "t",
+            &CGenOptions {
+                send_stack_limit: Some(32),
+                impl_style: ImplStyle::Source,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let header = &files
+            .iter()
+            .find(|file| file.filename == "t_server.h")
+            .unwrap()
+            .content;
+        assert!(header.contains(
+            "bool h6xserial_send_ping(const t_msg_ping_t *msg, h6xserial_tx_fn tx, void *ctx);\n"
+        ));
+
+        // Off by default
+        let files = generate_multiple(&metadata, &messages, Path::new("t.json"), "t").unwrap();
+        assert!(files.iter().all(|file| !file.content.contains("_send_")));
+    }
+
+    #[test]
+    fn test_only_used_helper_templates_are_loaded() {
+        let (_, messages) = crate::parse_str(
+            r#"{ "temp": { "packet_id": 1, "msg_type": "f32", "endianess": "big" },
+                 "flags": { "packet_id": 2, "msg_type": "u8" },
+                 "pose": { "packet_id": 3, "msg_type": "struct", "fields": {
+                     "at": { "type": "struct", "fields": { "x": { "type": "i16" } } } } } }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            helper_template_files(&messages, false, None),
+            ["helpers_u16_le.h", "helpers_u32_be.h", "helpers_f32_be.h"]
+        );
+        assert_eq!(
+            helper_template_files(&messages[1..2], false, None),
+            Vec::<String>::new()
+        );
+        let all = helper_template_files(&messages[1..2], true, None);
+        assert_eq!(all.len(), 14);
+        assert_eq!(all[0], "helpers_u16_le.h");
+        assert_eq!(all[13], "helpers_f64_be.h");
+    }
+
+    #[test]
+    fn test_c89_dialect() {
+        let (metadata, messages) = crate::parse_str(
+            r#"{ "samples": { "packet_id": 1, "msg_type": "u16", "array": true, "max_length": 8 },
+                 "big": { "packet_id": 2, "msg_type": "u64", "max": 10000000000, "request_type": "sub" },
+                 "level": { "packet_id": 3, "msg_type": "f32" } }"#,
+        )
+        .unwrap();
+        let options = CGenOptions {
+            dialect: CDialect::C89,
+            ..Default::default()
+        };
+        let files = generate_multiple_with_options(
+            &metadata,
+            &messages,
+            Path::new("t.json"),
+            "t",
+            &options,
+        )
+        .unwrap();
+        for file in &files {
+            for construct in [
+                "stdbool.h",
+                "static inline",
+                "_Static_assert",
+                "for (size_t",
+                "//",
+            ] {
+                assert!(
+                    !file.content.contains(construct),
+                    "{} contains {}",
+                    file.filename,
+                    construct
+                );
+            }
+        }
+        let file = |name: &str| {
+            &files
+                .iter()
+                .find(|file| file.filename == name)
+                .unwrap()
+                .content
+        };
+        assert!(file("t_types.h").contains("#define bool unsigned char\n"));
+        assert!(file("h6x_serial_byteorder.h").contains(
+            "static H6XSERIAL_INLINE void h6xserial_write_f32_le(float value, uint8_t *out) {"
+        ));
+        assert!(file("t_server.h").contains(
+            "static H6XSERIAL_INLINE size_t t_msg_samples_encode(const t_msg_samples_t *msg, uint8_t *out_buf, const size_t out_len) {\n    size_t required;\n    size_t offset;\n    size_t i;\n    if (!msg || !out_buf) {"
+        ));
+        assert!(
+            file("t_server.h")
+                .contains("    offset = 0;\n    for (i = 0; i < msg->length; ++i) {\n")
+        );
+        assert!(file("t_server.h").contains("msg->value > UINT64_C(10000000000)"));
+        use clap::ValueEnum;
+        assert_eq!(CDialect::from_str("ANSI", true).unwrap(), CDialect::C89);
+        assert!(CDialect::from_str("c11", true).is_err());
+    }
+
+    #[test]
+    fn test_timestamp_typedefs() {
+        let (metadata, messages) = crate::parse_str(
+            r#"{ "types": { "stamped": { "fields": {
+                     "at": { "type": "timestamp", "storage": "uint32", "resolution": "ms" } } } },
+                 "packets": {
+                     "imu": { "packet_id": 1, "msg_type": "struct", "fields": {
+                         "stamp": { "type": "timestamp", "desc": "capture" },
+                         "inner": { "type": "stamped" } } },
+                     "log": { "packet_id": 2, "msg_type": "struct", "fields": {
+                         "t": { "type": "timestamp", "resolution": "ns", "unit": "ticks" } } } } }"#,
+        )
+        .unwrap();
+        let files = generate_multiple(&metadata, &messages, Path::new("t.json"), "t").unwrap();
+        let types = &files
+            .iter()
+            .find(|file| file.filename == "t_types.h")
+            .unwrap()
+            .content;
+        assert_eq!(
+            types
+                .matches("typedef uint64_t h6xserial_timestamp_t;")
+                .count(),
+            1
+        );
+        assert_eq!(
+            types
+                .matches("typedef uint32_t h6xserial_timestamp32_t;")
+                .count(),
+            1
+        );
+        assert!(
+            types.find("h6xserial_timestamp32_t;").unwrap()
+                < types.find("h6xserial_type_stamped_t;").unwrap()
+        );
+        assert!(types.contains("    h6xserial_timestamp32_t at; /* ms */\n"));
+        assert!(types.contains("    h6xserial_timestamp_t stamp; /* us: capture */\n"));
+        assert!(types.contains("    h6xserial_timestamp_t t; /* ticks */\n"));
+
+        // No typedefs without timestamp fields
+        let (metadata, messages) =
+            crate::parse_str(r#"{ "n": { "packet_id": 1, "msg_type": "u64" } }"#).unwrap();
+        let files = generate_multiple(&metadata, &messages, Path::new("t.json"), "t").unwrap();
+        assert!(files.iter().all(|file| !file.content.contains("timestamp")));
+    }
+
+    #[test]
+    fn test_doc_comments() {
+        let (metadata, messages) = crate::parse_str(
+            r#"{ "level": { "packet_id": 1, "msg_type": "u16", "msg_desc": "Fill level */ raw", "max": 1000,
+                            "endianess": "big" },
+                 "pose": { "packet_id": 2, "msg_type": "struct", "fields": {
+                     "x": { "type": "f32", "desc": "X position", "unit": "m" },
+                     "ids": { "type": "u8", "array": true, "max_length": 4 } } } }"#,
+        )
+        .unwrap();
+        let options = CGenOptions {
+            doc_comments: true,
+            encode_range: EncodeRange::Reject,
+            ..Default::default()
+        };
+        let files = generate_multiple_with_options(
+            &metadata,
+            &messages,
+            Path::new("t.json"),
+            "t",
+            &options,
+        )
+        .unwrap();
+        let file = |name: &str| {
+            &files
+                .iter()
+                .find(|file| file.filename == name)
+                .unwrap()
+                .content
+        };
+        assert!(file("t_types.h").contains(
+            "/**\n * @brief Fill level * / raw\n * @note Packet ID T_MSG_LEVEL_PACKET_ID (1).\n * @note At most T_MSG_LEVEL_MAX_SIZE (2) bytes on the wire, big-endian.\n */\ntypedef struct {"
+        ));
+        assert!(file("t_types.h").contains("    float x; /**< m: X position */\n"));
+        assert!(
+            file("t_types.h")
+                .contains("    size_t ids_length; /**< Number of valid elements in ids */\n")
+        );
+        assert!(file("t_server.h").contains(
+            " * @return Number of bytes written, or 0 when:\n *   - @p msg or @p out_buf is NULL\n *   - @p out_len is less than 2\n *   - msg->value is greater than 1000\n"
+        ));
+        assert!(file("t_client_common.h").contains(
+            " * @return true on success, or false when:\n *   - @p msg or @p data is NULL\n *   - @p data_len is less than 4 or greater than 8\n * @note At most T_MSG_POSE_MAX_SIZE (8) bytes on the wire, little-endian.\n */\nstatic inline bool t_msg_pose_decode("
+        ));
+        assert!(!file("t_types.h").contains("/* Fill level"));
+
+        // Source style documents the prototypes, not the definitions
+        let options = CGenOptions {
+            impl_style: ImplStyle::Source,
+            ..options
+        };
+        let files = generate_multiple_with_options(
+            &metadata,
+            &messages,
+            Path::new("t.json"),
+            "t",
+            &options,
+        )
+        .unwrap();
+        for file in &files {
+            let expected = file.filename.ends_with(".h")
+                && !["h6x_serial_byteorder.h", "t.h"].contains(&file.filename.as_str());
+            assert_eq!(file.content.contains("/**"), expected, "{}", file.filename);
+        }
+    }
+
+    #[test]
+    fn test_misra_mode() {
+        let (metadata, messages) = crate::parse_str(
+            r#"{ "samples": { "packet_id": 1, "msg_type": "i16", "array": true, "max_length": 8 },
+                 "flag": { "packet_id": 2, "msg_type": "bool" },
+                 "level": { "packet_id": 3, "msg_type": "u8", "max": 100 },
+                 "temps": { "packet_id": 4, "msg_type": "i16", "array": true, "max_length": 2, "scale": 0.1 } }"#,
+        )
+        .unwrap();
+        let generate = |misra| {
+            let options = CGenOptions {
+                misra,
+                encode_range: EncodeRange::Reject,
+                ..Default::default()
+            };
+            let files = generate_multiple_with_options(
+                &metadata,
+                &messages,
+                Path::new("t.json"),
+                "t",
+                &options,
+            )
+            .unwrap();
+            let content = |name: &str| {
+                files
+                    .iter()
+                    .find(|file| file.filename == name)
+                    .unwrap()
+                    .content
+                    .clone()
+            };
+            (
+                content("t_types.h"),
+                content("t_server.h") + &content("t_client_common.h"),
+            )
+        };
+
+        let (types, code) = generate(true);
+        assert!(types.contains(" * MISRA C:2012 deviations of the generated code:\n"));
+        assert!(types.contains("#define H6XSERIAL_MAX_PAYLOAD_BYTES 251U\n"));
+        assert!(types.contains("#define T_MSG_SAMPLES_MAX_LENGTH 8U\n"));
+        assert!(
+            code.contains("    if ((msg == NULL) || (out_buf == NULL)) {\n        return 0U;\n")
+        );
+        assert!(code.contains("    for (uint32_t i = 0U; i < msg->length; ++i) {\n"));
+        assert!(code.contains(
+            "        msg->data[i] = (int16_t)h6xserial_read_u16_le(data + offset); /* deviation D1 */\n"
+        ));
+        assert!(code.contains("(out_buf)[0] = (uint8_t)((msg->value) ? 1U : 0U);\n"));
+        assert!(code.contains("msg->value = ((data)[0]) != 0U;\n"));
+        assert!(code.contains("    if (msg->value > 100U) {\n"));
+        assert!(types.contains(
+            "    if ((msg == NULL) || (index >= sizeof(msg->data) / sizeof(msg->data[0]))) {\n        return 0.0;\n"
+        ));
+
+        let (types, code) = generate(false);
+        assert!(!types.contains("MISRA"));
+        assert!(!code.contains("deviation") && !code.contains("0U"));
+    }
+
+    #[test]
+    fn test_field_offset_macros() {
+        let (metadata, messages) = crate::parse_str(
+            r#"{ "types": { "point": { "fields": { "x": { "type": "i16" }, "y": { "type": "i16" } } } },
+                 "frame": { "packet_id": 1, "msg_type": "struct", "fields": {
+                     "seq": { "type": "u32" },
+                     "at": { "type": "point" },
+                     "note": { "type": "struct", "fields": {
+                         "code": { "type": "u8" },
+                         "text": { "type": "char", "array": true, "max_length": 4 } } },
+                     "crc": { "type": "u16" } } },
+                 "event": { "packet_id": 2, "msg_type": "union", "tag": "u16", "variants": {
+                     "1": { "name": "moved", "type": "point" } } } }"#,
+        )
+        .unwrap();
+        let header = generate(&metadata, &messages, Path::new("t.json"), Path::new("t.h")).unwrap();
+        assert!(header.contains(
+            "#define T_MSG_FRAME_SEQ_OFFSET 0\n\
+             #define T_MSG_FRAME_SEQ_SIZE 4\n\
+             #define T_MSG_FRAME_AT_X_OFFSET 4\n\
+             #define T_MSG_FRAME_AT_X_SIZE 2\n\
+             #define T_MSG_FRAME_AT_Y_OFFSET 6\n\
+             #define T_MSG_FRAME_AT_Y_SIZE 2\n\
+             #define T_MSG_FRAME_NOTE_CODE_OFFSET 8\n\
+             #define T_MSG_FRAME_NOTE_CODE_SIZE 1\n\
+             #define T_MSG_FRAME_NOTE_TEXT_OFFSET 9\n\
+             /* No offsets after 'note.text': its length varies, so the fields after it move */\n"
+        ));
+        assert!(!header.contains("T_MSG_FRAME_NOTE_TEXT_SIZE") && !header.contains("CRC_OFFSET"));
+        assert!(header.contains(
+            "#define T_MSG_EVENT_MOVED_X_OFFSET 2\n\
+             #define T_MSG_EVENT_MOVED_X_SIZE 2\n\
+             #define T_MSG_EVENT_MOVED_Y_OFFSET 4\n"
+        ));
+
+        // The Markdown tables show the same offsets
+        let docs =
+            crate::emit_markdown::generate(&metadata, &messages, Path::new("t.json")).unwrap();
+        assert!(docs.contains("| `note.text` | `char[4]` | 9 |"), "{}", docs);
+        assert!(docs.contains("| `crc` | `uint16_t` | 9–13 |"), "{}", docs);
+    }
+
+    #[test]
+    fn test_at_reserves_the_gaps() {
+        let (metadata, messages) = crate::parse_str(
+            r#"{ "status": { "packet_id": 1, "msg_type": "struct", "fields": {
+                     "mode": { "type": "u8" },
+                     "speed": { "type": "u16", "at": 4 },
+                     "flags": { "type": "u8" },
+                     "crc": { "type": "u16", "at": 8 } } } }"#,
+        )
+        .unwrap();
+        let header = generate(&metadata, &messages, Path::new("t.json"), Path::new("t.h")).unwrap();
+        assert!(header.contains(
+            "    uint8_t mode;\n    uint8_t _reserved_0[3]; /* Reserved */\n    uint16_t speed;\n"
+        ));
+        assert!(header.contains("#define T_MSG_STATUS_SPEED_OFFSET 4\n"));
+        assert!(header.contains("#define T_MSG_STATUS_CRC_OFFSET 8\n"));
+        assert!(!header.contains("RESERVED_0"));
+        assert!(header.contains(
+            "    offset += 1;\n    memset(out_buf + offset, 0, 3);\n    offset += 3;\n    h6xserial_write_u16_le("
+        ));
+        assert!(header.contains(
+            "    offset += 1;\n    offset += 3;\n    msg->speed = h6xserial_read_u16_le(data + offset);\n"
+        ));
+
+        let docs =
+            crate::emit_markdown::generate(&metadata, &messages, Path::new("t.json")).unwrap();
+        assert!(docs.contains(
+            "| `_reserved_1` | reserved[1] | 7 | 1 |  | Reserved: sent as zeros, ignored when received |"
+        ));
+    }
+
+    #[test]
+    fn test_group_namespaces_the_names() {
+        let (metadata, messages) = crate::parse_str(
+            r#"{ "speed": { "packet_id": 1, "msg_type": "u16", "group": "Motor",
+                           "response": "ack" },
+                 "ack": { "packet_id": 2, "msg_type": "u8", "group": "status" },
+                 "ping": { "packet_id": 3, "msg_type": "u8" } }"#,
+        )
+        .unwrap();
+        let header = generate(&metadata, &messages, Path::new("t.json"), Path::new("t.h")).unwrap();
+        assert!(header.contains("} t_motor_msg_speed_t;\n"));
+        assert!(header.contains("t_motor_msg_speed_encode("));
+        assert!(header.contains("t_motor_msg_speed_decode("));
+        assert!(header.contains("#define T_MOTOR_MSG_SPEED_PACKET_ID 1\n"));
+        assert!(
+            header.contains(
+                "#define T_MOTOR_MSG_SPEED_RESPONSE_PACKET_ID T_STATUS_MSG_ACK_PACKET_ID\n"
+            )
+        );
+        // Ungrouped messages keep their names
+        assert!(header.contains("} t_msg_ping_t;\n"));
+        assert!(header.contains("#define T_MSG_PING_PACKET_ID 3\n"));
+        assert!(!header.contains("T_MSG_SPEED"));
+    }
+
+    #[test]
+    fn test_min_size_macros() {
+        let (metadata, messages) = crate::parse_str(
+            r#"{ "log": { "packet_id": 1, "msg_type": "struct", "fields": {
+                     "level": { "type": "u8" },
+                     "stamp": { "type": "u32" },
+                     "text": { "type": "char", "array": true, "max_length": 32 } } },
+                 "raw": { "packet_id": 2, "msg_type": "u8", "array": true, "max_length": 8 },
+                 "temp": { "packet_id": 3, "msg_type": "i16" } }"#,
+        )
+        .unwrap();
+        let header = generate(&metadata, &messages, Path::new("t.json"), Path::new("t.h")).unwrap();
+        // level and stamp; text may be empty
+        assert!(header.contains("#define T_MSG_LOG_MIN_SIZE 5\n#define T_MSG_LOG_MAX_SIZE 37\n"));
+        assert!(header.contains("#define T_MSG_RAW_MIN_SIZE 0\n"));
+        assert!(header.contains("#define T_MSG_TEMP_MIN_SIZE 2\n"));
+        assert!(header.contains("#define H6XSERIAL_MIN_PAYLOAD_SIZE 0\n"));
+
+        let files = generate_multiple(&metadata, &messages[..1], Path::new("t.json"), "t").unwrap();
+        let types = &files
+            .iter()
+            .find(|f| f.filename == "t_types.h")
+            .unwrap()
+            .content;
+        assert!(types.contains("#define T_MSG_LOG_MIN_SIZE 5\n"));
+        assert!(types.contains("#define H6XSERIAL_MIN_PAYLOAD_SIZE 5\n"));
+    }
+
+    #[test]
+    fn test_last_array_takes_whole_elements() {
+        let (metadata, messages) = crate::parse_str(
+            r#"{ "trace": { "packet_id": 1, "msg_type": "struct", "fields": {
+                     "id": { "type": "u8" },
+                     "tags": { "type": "u8", "array": true, "max_length": 4 },
+                     "samples": { "type": "f32", "array": true, "max_length": 8 } } } }"#,
+        )
+        .unwrap();
+        let header = generate(&metadata, &messages, Path::new("t.json"), Path::new("t.h")).unwrap();
+        // Only the last array checks the rest; the first one leaves it
+        assert_eq!(header.matches("    if (remaining % 4 != 0) {").count(), 1);
+        assert!(header.contains(
+            "    if (remaining % 4 != 0) {\n        return false;\n    }\n    {\n        size_t elem_count = remaining / 4;\n"
+        ));
+        assert!(header.contains(
+            "            elem_count = T_MSG_TRACE_SAMPLES_MAX_LENGTH;\n        }\n        if (elem_count * 4 != remaining) {\n            return false;\n        }\n"
+        ));
+        assert!(!header.contains("if (elem_count != remaining)"));
+    }
+
+    #[test]
+    fn test_struct_encode_checks_the_lengths() {
+        let (metadata, messages) = crate::parse_str(
+            r#"{ "scan": { "packet_id": 1, "msg_type": "struct", "fields": {
+                     "id": { "type": "u16" },
+                     "pose": { "type": "struct", "fields": {
+                         "points": { "type": "f32", "array": true, "max_length": 40 } } },
+                     "note": { "type": "char", "array": true, "max_length": 16 } } } }"#,
+        )
+        .unwrap();
+        let options = CGenOptions {
+            doc_comments: true,
+            ..Default::default()
+        };
+        let files = generate_multiple_with_options(
+            &metadata,
+            &messages,
+            Path::new("t.json"),
+            "t",
+            &options,
+        )
+        .unwrap();
+        let header = &files
+            .iter()
+            .find(|f| f.filename == "t_server.h")
+            .unwrap()
+            .content;
+        assert!(header.contains(
+            "    if (msg->pose.points_length > T_MSG_SCAN_POSE_POINTS_MAX_LENGTH) {\n        return 0;\n    }\n\
+             \x20   if (msg->note_length > T_MSG_SCAN_NOTE_MAX_LENGTH) {\n        return 0;\n    }\n\
+             \x20   if (out_len < 2 + msg->pose.points_length * 4 + msg->note_length) {\n"
+        ));
+        assert!(header.contains(
+            " *   - msg->note_length exceeds T_MSG_SCAN_NOTE_MAX_LENGTH\n\
+             \x20*   - @p out_len is less than 2 + msg->pose.points_length * 4 + msg->note_length\n"
+        ));
+        assert!(!header.contains("out_len < 178"));
+    }
+
+    #[test]
+    fn test_deterministic_decode_zeroes_the_tails() {
+        let (metadata, messages) = crate::parse_str(
+            r#"{ "raw": { "packet_id": 1, "msg_type": "u16", "array": true, "max_length": 6 },
+                 "pose": { "packet_id": 2, "msg_type": "struct", "fields": {
+                     "x": { "type": "f32" },
+                     "name": { "type": "char", "array": true, "max_length": 8,
+                               "string_semantics": true } } },
+                 "mode": { "packet_id": 3, "msg_type": "union", "tag": "u8", "variants": {
+                     "1": { "name": "idle", "fields": { "a": { "type": "u8" } } },
+                     "2": { "name": "run", "fields": { "speed": { "type": "u16" } } } } },
+                 "fixed": { "packet_id": 4, "msg_type": "struct", "fields": {
+                     "v": { "type": "u8", "fixed_length": 3, "array": true } } } }"#,
+        )
+        .unwrap();
+        let options = CGenOptions {
+            deterministic_decode: true,
+            ..Default::default()
+        };
+        let files = generate_files(
+            &metadata,
+            &messages,
+            Path::new("t.json"),
+            Path::new("t.h"),
+            &options,
+        )
+        .unwrap();
+        let header = &files[0].content;
+        assert!(header.contains(
+            "    msg->length = element_count;\n    if (element_count < T_MSG_RAW_MAX_LENGTH) {\n        memset(&msg->data[element_count], 0, sizeof(msg->data) - element_count * sizeof(msg->data[0]));\n    }\n"
+        ));
+        assert!(header.contains(
+            "        if (elem_count < T_MSG_POSE_NAME_MAX_LENGTH) {\n            memset(&msg->name[elem_count], 0, sizeof(msg->name) - elem_count * sizeof(msg->name[0]));\n        }\n"
+        ));
+        assert!(header.contains(
+            "    memset(&msg->variant, 0, sizeof(msg->variant));\n    switch (msg->tag) {\n"
+        ));
+        assert_eq!(header.matches("memset(&msg->").count(), 3);
+
+        let default =
+            generate(&metadata, &messages, Path::new("t.json"), Path::new("t.h")).unwrap();
+        assert!(!default.contains("memset(&msg->"));
+    }
+
+    #[test]
+    fn test_wide_packet_ids() {
+        let (metadata, messages) = crate::parse_str(
+            r#"{ "packet_id_width": 16,
+                 "get": { "packet_id": 257, "msg_type": "u8", "request_type": "sub",
+                          "response": "value" },
+                 "value": { "packet_id": 4096, "msg_type": "u16" } }"#,
+        )
+        .unwrap();
+        let options = CGenOptions {
+            doc_comments: true,
+            ..Default::default()
+        };
+        let files = generate_multiple_with_options(
+            &metadata,
+            &messages,
+            Path::new("t.json"),
+            "t",
+            &options,
+        )
+        .unwrap();
+        let file = |name: &str| {
+            &files
+                .iter()
+                .find(|file| file.filename == name)
+                .unwrap()
+                .content
+        };
+        let types = file("t_types.h");
+        assert!(types.contains("#define H6XSERIAL_PACKET_ID_BITS 16\n"));
+        assert!(types.contains("#define T_MSG_GET_PACKET_ID 257\n"));
+        assert!(types.contains(" * @note 16-bit packet ID T_MSG_VALUE_PACKET_ID (0x1000).\n"));
+        assert!(types.contains("int t_response_packet_id_for(uint16_t request_id) {\n"));
+        assert!(
+            file("t_client_common.h").contains("uint16_t t_msg_get_expected_response_id(void)")
+        );
+
+        let skeleton = generate_server_skeleton(&metadata, &messages, "t", &options);
+        assert!(
+            skeleton
+                .content
+                .contains("(uint16_t *packet_id, uint8_t *payload,")
+        );
+        assert!(skeleton.content.contains("    uint16_t packet_id;\n"));
+    }
+}
@@ -0,0 +1,362 @@
+//! Generator for the `--package arduino` library layout.
+//!
+//! The Arduino IDE and PlatformIO accept a directory with a
+//! `library.properties` file, the code to compile under `src/`, and
+//! example sketches under `examples/<Name>/<Name>.ino`. The generated
+//! `Decode` example decodes a fixed sample payload of one message, computed
+//! like the wire bytes of the `--emit-tests` self-test, and prints the
+//! fields over `Serial`.
+
+use std::fmt::Write as FmtWrite;
+
+use super::{
+    NameContext, OutputFile, decode_fn_name, self_test, type_name, write_deprecated_opt_out,
+};
+use crate::fingerprint::GENERATOR;
+use crate::{
+    MessageBody, MessageDefinition, Metadata, PrimitiveType, StructFieldType, StructSpec,
+    ir_fingerprint, to_snake_case,
+};
+
+/// Directory of the compiled library sources.
+const SOURCE_DIR: &str = "src";
+
+/// Path of the generated example sketch.
+const DECODE_SKETCH: &str = "examples/Decode/Decode.ino";
+
+/// Lays `files` out as an Arduino library: moves them with
+/// [`move_to_source_dir`] and adds `library.properties` and the `Decode`
+/// sketch. `decoder_header` names the header declaring the decoder of a
+/// message, if any.
+pub(super) fn package<'h>(
+    files: &mut Vec<OutputFile>,
+    sources: &mut [String],
+    metadata: &Metadata,
+    messages: &[MessageDefinition],
+    name_ctx: &NameContext,
+    includes: &[String],
+    decoder_header: impl Fn(&MessageDefinition) -> Option<&'h str>,
+) {
+    let sketch = generate_decode_sketch(metadata, messages, name_ctx, decoder_header);
+    move_to_source_dir(files, sources);
+    files.push(OutputFile {
+        filename: "library.properties".to_string(),
+        content: generate_library_properties(metadata, messages, name_ctx, includes),
+    });
+    if let Some(sketch) = sketch {
+        files.push(OutputFile {
+            filename: DECODE_SKETCH.to_string(),
+            content: sketch,
+        });
+    }
+}
+
+/// Moves the headers and the library `sources` under `src/`, where the
+/// IDE compiles them. Test and fuzz programs have their own `main()` and
+/// stay in the library root with the other extra files.
+fn move_to_source_dir(files: &mut [OutputFile], sources: &mut [String]) {
+    for file in files.iter_mut() {
+        if file.filename.ends_with(".h") || sources.contains(&file.filename) {
+            file.filename = format!("{}/{}", SOURCE_DIR, file.filename);
+        }
+    }
+    for source in sources.iter_mut() {
+        *source = format!("{}/{}", SOURCE_DIR, source);
+    }
+}
+
+/// Generates `library.properties`; `includes` are the headers a sketch
+/// includes, offered by the IDE's "Include Library" menu.
+fn generate_library_properties(
+    metadata: &Metadata,
+    messages: &[MessageDefinition],
+    name_ctx: &NameContext,
+    includes: &[String],
+) -> String {
+    let mut out = String::new();
+    writeln!(out, "name={}", name_ctx.msg_prefix).unwrap();
+    writeln!(
+        out,
+        "version={}",
+        library_version(metadata.version.as_deref())
+    )
+    .unwrap();
+    writeln!(out, "author={}", GENERATOR).unwrap();
+    writeln!(out, "maintainer={}", GENERATOR).unwrap();
+    writeln!(
+        out,
+        "sentence=Encoders and decoders of the {} serial protocol.",
+        name_ctx.msg_prefix
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "paragraph=Generated from an IR with fingerprint {}; regenerate instead of editing.",
+        ir_fingerprint(metadata, messages)
+    )
+    .unwrap();
+    writeln!(out, "category=Communication").unwrap();
+    writeln!(out, "url=https://github.com/Ar-Ray-code/h6xserial_idl").unwrap();
+    writeln!(out, "architectures=*").unwrap();
+    writeln!(out, "includes={}", includes.join(",")).unwrap();
+    out
+}
+
+/// `version` as the `MAJOR.MINOR.PATCH` the library manager expects:
+/// missing numeric components are filled with zeros, anything else is
+/// kept as written.
+fn library_version(version: Option<&str>) -> String {
+    let Some(version) = version else {
+        return "0.0.0".to_string();
+    };
+    let parts: Vec<&str> = version.split('.').collect();
+    let numeric = parts
+        .iter()
+        .all(|part| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit()));
+    if numeric && parts.len() < 3 {
+        let mut parts = parts;
+        parts.resize(3, "0");
+        parts.join(".")
+    } else {
+        version.to_string()
+    }
+}
+
+/// Generates the `Decode` sketch, or `None` when no header decodes any
+/// message.
+fn generate_decode_sketch<'h>(
+    metadata: &Metadata,
+    messages: &[MessageDefinition],
+    name_ctx: &NameContext,
+    decoder_header: impl Fn(&MessageDefinition) -> Option<&'h str>,
+) -> Option<String> {
+    let (msg, header) = messages
+        .iter()
+        .filter_map(|msg| decoder_header(msg).map(|header| (msg, header)))
+        .min_by_key(|(msg, _)| (msg.deprecated.is_some(), sample_rank(&msg.body)))?;
+
+    let mut out = String::new();
+    out.push_str("/*\n");
+    writeln!(out, " * Auto-generated Arduino example by {}.", GENERATOR).unwrap();
+    writeln!(
+        out,
+        " * IR fingerprint: {}",
+        ir_fingerprint(metadata, messages)
+    )
+    .unwrap();
+    out.push_str(" *\n");
+    writeln!(
+        out,
+        " * Decodes a sample '{}' payload (packet ID {}) and prints its fields.",
+        msg.name, msg.packet_id
+    )
+    .unwrap();
+    out.push_str(" */\n\n");
+    write_deprecated_opt_out(&mut out, std::slice::from_ref(msg));
+    writeln!(out, "#include <{}>\n", header).unwrap();
+
+    out.push_str("void setup() {\n");
+    out.push_str("    Serial.begin(115200);\n");
+    out.push_str("    while (!Serial) {\n    }\n\n");
+    let payload = self_test::sample_payload(msg);
+    self_test::write_byte_array(&mut out, "payload", &payload);
+    writeln!(out, "    {} msg;", type_name(msg, name_ctx)).unwrap();
+    writeln!(
+        out,
+        "    if (!{}(&msg, payload, {})) {{",
+        decode_fn_name(msg, name_ctx),
+        payload.len()
+    )
+    .unwrap();
+    out.push_str("        Serial.println(\"decode failed\");\n");
+    out.push_str("        return;\n");
+    out.push_str("    }\n\n");
+    writeln!(
+        out,
+        "    Serial.println(\"{} (packet ID {}):\");",
+        msg.name, msg.packet_id
+    )
+    .unwrap();
+    match &msg.body {
+        MessageBody::Scalar(spec) => write_print(&mut out, "value", "value", spec.primitive),
+        MessageBody::Array(spec) => {
+            write_array_print(&mut out, "data", "msg.length", "data", spec.primitive)
+        }
+        MessageBody::Struct(spec) => write_struct_prints(&mut out, spec, "", ""),
+        MessageBody::Union(spec) => {
+            write_print(&mut out, "tag", "tag", spec.tag);
+            // The sample payload holds the last variant
+            if let Some(variant) = spec.variants.last() {
+                write_struct_prints(
+                    &mut out,
+                    &variant.spec,
+                    &format!("variant.{}.", to_snake_case(&variant.name)),
+                    &format!("{}.", variant.name),
+                );
+            }
+        }
+    }
+    out.push_str("}\n\n");
+    out.push_str("void loop() {\n}\n");
+    Some(out)
+}
+
+/// Preference of a message body as the sample: scalars and flat structs
+/// print best, arrays are the fallback.
+fn sample_rank(body: &MessageBody) -> u8 {
+    match body {
+        MessageBody::Scalar(_) => 0,
+        MessageBody::Struct(spec)
+            if spec
+                .fields
+                .iter()
+                .all(|field| matches!(field.field_type, StructFieldType::Primitive(_))) =>
+        {
+            0
+        }
+        MessageBody::Struct(_) | MessageBody::Union(_) => 1,
+        MessageBody::Array(_) => 2,
+    }
+}
+
+fn write_struct_prints(out: &mut String, spec: &StructSpec, accessor: &str, label: &str) {
+    for field in &spec.fields {
+        let accessor = format!("{}{}", accessor, to_snake_case(&field.name));
+        let label = format!("{}{}", label, field.name);
+        match &field.field_type {
+            StructFieldType::Primitive(primitive) => {
+                write_print(out, &accessor, &label, *primitive)
+            }
+            StructFieldType::Array(array) if array.fixed => write_array_print(
+                out,
+                &accessor,
+                &array.max_length.to_string(),
+                &label,
+                array.primitive,
+            ),
+            StructFieldType::Array(array) => write_array_print(
+                out,
+                &accessor,
+                &format!("msg.{}_length", accessor),
+                &label,
+                array.primitive,
+            ),
+            StructFieldType::Bytes(_) => write_array_print(
+                out,
+                &accessor,
+                &format!("sizeof(msg.{})", accessor),
+                &label,
+                PrimitiveType::Uint8,
+            ),
+            StructFieldType::Nested(nested) => write_struct_prints(
+                out,
+                nested,
+                &format!("{}.", accessor),
+                &format!("{}.", label),
+            ),
+        }
+    }
+}
+
+fn write_print(out: &mut String, accessor: &str, label: &str, primitive: PrimitiveType) {
+    writeln!(out, "    Serial.print(\"  {}: \");", label).unwrap();
+    writeln!(
+        out,
+        "    Serial.println({});",
+        printable(&format!("msg.{}", accessor), primitive)
+    )
+    .unwrap();
+}
+
+fn write_array_print(
+    out: &mut String,
+    accessor: &str,
+    length: &str,
+    label: &str,
+    primitive: PrimitiveType,
+) {
+    writeln!(out, "    Serial.print(\"  {}: \");", label).unwrap();
+    if primitive == PrimitiveType::Char {
+        writeln!(out, "    Serial.write(msg.{}, {});", accessor, length).unwrap();
+        out.push_str("    Serial.println();\n");
+        return;
+    }
+    writeln!(out, "    for (size_t i = 0; i < {}; i++) {{", length).unwrap();
+    writeln!(
+        out,
+        "        Serial.print({});",
+        printable(&format!("msg.{}[i]", accessor), primitive)
+    )
+    .unwrap();
+    out.push_str("        Serial.print(' ');\n");
+    out.push_str("    }\n");
+    out.push_str("    Serial.println();\n");
+}
+
+/// `value` converted to a type `Serial.print` accepts on every core; AVR
+/// has no 64-bit overloads, so those go through `double`.
+fn printable(value: &str, primitive: PrimitiveType) -> String {
+    match primitive {
+        PrimitiveType::Bool => format!("{} ? \"true\" : \"false\"", value),
+        PrimitiveType::Char => value.to_string(),
+        PrimitiveType::Int8
+        | PrimitiveType::Int16
+        | PrimitiveType::Int24
+        | PrimitiveType::Int32 => {
+            format!("(long){}", value)
+        }
+        PrimitiveType::Uint8
+        | PrimitiveType::Uint16
+        | PrimitiveType::Uint24
+        | PrimitiveType::Uint32 => format!("(unsigned long){}", value),
+        PrimitiveType::Int64 | PrimitiveType::Uint64 => format!("(double){}, 0", value),
+        PrimitiveType::Float16 | PrimitiveType::Float32 | PrimitiveType::Float64 => {
+            format!("{}, 6", value)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_library_version_is_padded_to_three_components() {
+        assert_eq!(library_version(None), "0.0.0");
+        assert_eq!(library_version(Some("2")), "2.0.0");
+        assert_eq!(library_version(Some("1.4")), "1.4.0");
+        assert_eq!(library_version(Some("1.4.2")), "1.4.2");
+        assert_eq!(library_version(Some("1.0-beta")), "1.0-beta");
+    }
+
+    #[test]
+    fn test_sketch_prefers_flat_messages_over_arrays() {
+        let (metadata, messages) = crate::parse_str(
+            r#"{ "packets": {
+                 "blob": { "packet_id": 1, "msg_type": "uint8", "array": true, "max_length": 4 },
+                 "pose": { "packet_id": 2, "msg_type": "struct", "fields": {
+                     "ok": { "type": "bool" }, "x": { "type": "i16" } } } } }"#,
+        )
+        .unwrap();
+        let name_ctx = NameContext::new(
+            "robot",
+            &super::super::CGenOptions::default(),
+            &Metadata::default(),
+            &messages,
+        );
+        let sketch =
+            generate_decode_sketch(&metadata, &messages, &name_ctx, |_| Some("robot.h")).unwrap();
+        assert!(sketch.contains("#include <robot.h>"));
+        assert!(sketch.contains("robot_msg_pose_decode(&msg, payload, 3)"));
+        assert!(sketch.contains("    Serial.println(msg.ok ? \"true\" : \"false\");"));
+        assert!(sketch.contains("    Serial.println((long)msg.x);"));
+
+        // Arrays are the fallback
+        let sketch = generate_decode_sketch(&metadata, &messages, &name_ctx, |msg| {
+            (msg.packet_id == 1).then_some("robot.h")
+        })
+        .unwrap();
+        assert!(sketch.contains("for (size_t i = 0; i < msg.length; i++) {"));
+        assert!(generate_decode_sketch(&metadata, &messages, &name_ctx, |_| None).is_none());
+    }
+}
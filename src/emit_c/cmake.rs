@@ -0,0 +1,193 @@
+//! Generator for the `--emit-cmake` module.
+//!
+//! The emitted `<base>.cmake` is meant for `include()` from a project's
+//! `CMakeLists.txt`. It declares an INTERFACE library with the output
+//! directory as include directory and the generated files as sources, a
+//! custom command re-running the generator when an input document
+//! changes, and install rules for the headers. Every path is relative to
+//! the directory of the module, so the tree can be moved or checked in.
+
+use std::fmt::Write as FmtWrite;
+use std::path::{Component, Path, PathBuf};
+
+use super::{NameContext, OutputFile};
+use crate::fingerprint::GENERATOR;
+
+/// What the `--emit-cmake` module needs to re-run the generator.
+#[derive(Clone, Debug, Default)]
+pub struct CMakeOptions {
+    /// Input documents, relative to the output directory
+    pub inputs: Vec<PathBuf>,
+    /// Command line arguments reproducing the generation besides the input
+    /// and output paths, e.g. `["--impl-style", "source"]`
+    pub args: Vec<String>,
+}
+
+/// Generates the module for `files`, the other outputs of the run.
+/// `sources` names the `.c` files that belong to the library; test and
+/// fuzz programs have their own `main()` and are only regenerated.
+pub(super) fn generate_cmake_module(
+    files: &[OutputFile],
+    sources: &[String],
+    name_ctx: &NameContext,
+    options: &CMakeOptions,
+    fingerprint: &str,
+) -> String {
+    let target = format!("{}_idl", name_ctx.msg_prefix);
+    let var = format!("{}_IDL", name_ctx.macro_prefix);
+    let dir = format!("${{{}_DIR}}", var);
+
+    let mut out = String::new();
+    writeln!(out, "# Auto-generated by {}.", GENERATOR).unwrap();
+    writeln!(out, "# IR fingerprint: {}", fingerprint).unwrap();
+    writeln!(out, "#").unwrap();
+    writeln!(
+        out,
+        "# Declares the INTERFACE library `{}` (CMake 3.19 or newer):",
+        target
+    )
+    .unwrap();
+    writeln!(out, "#   include(path/to/this/file.cmake)").unwrap();
+    writeln!(
+        out,
+        "#   target_link_libraries(firmware PRIVATE {})",
+        target
+    )
+    .unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "set({}_DIR \"${{CMAKE_CURRENT_LIST_DIR}}\")", var).unwrap();
+
+    let list = |out: &mut String, name: &str, paths: Vec<String>| {
+        writeln!(out, "set({}_{}", var, name).unwrap();
+        for path in paths {
+            writeln!(out, "    \"{}/{}\"", dir, path).unwrap();
+        }
+        writeln!(out, ")").unwrap();
+    };
+    list(
+        &mut out,
+        "INPUTS",
+        options.inputs.iter().map(|path| cmake_path(path)).collect(),
+    );
+    list(
+        &mut out,
+        "HEADERS",
+        files
+            .iter()
+            .filter(|file| file.filename.ends_with(".h"))
+            .map(|file| file.filename.clone())
+            .collect(),
+    );
+    list(&mut out, "SOURCES", sources.to_vec());
+    list(
+        &mut out,
+        "FILES",
+        files.iter().map(|file| file.filename.clone()).collect(),
+    );
+
+    writeln!(out).unwrap();
+    writeln!(out, "find_program(H6XSERIAL_IDL_EXECUTABLE h6xserial_idl)").unwrap();
+    writeln!(out, "if(H6XSERIAL_IDL_EXECUTABLE)").unwrap();
+    writeln!(out, "    add_custom_command(").unwrap();
+    writeln!(out, "        OUTPUT ${{{}_FILES}}", var).unwrap();
+    write!(out, "        COMMAND \"${{H6XSERIAL_IDL_EXECUTABLE}}\"").unwrap();
+    for arg in &options.args {
+        write!(out, " {}", cmake_argument(arg)).unwrap();
+    }
+    writeln!(out, " ${{{}_INPUTS}} \"{}\"", var, dir).unwrap();
+    writeln!(out, "        DEPENDS ${{{}_INPUTS}}", var).unwrap();
+    // Relative arguments such as --template-dir are relative to the module
+    writeln!(out, "        WORKING_DIRECTORY \"{}\"", dir).unwrap();
+    writeln!(
+        out,
+        "        COMMENT \"Regenerating the {} protocol code\"",
+        name_ctx.msg_prefix
+    )
+    .unwrap();
+    writeln!(out, "        VERBATIM").unwrap();
+    writeln!(out, "    )").unwrap();
+    writeln!(
+        out,
+        "    add_custom_target({}_generate DEPENDS ${{{}_FILES}})",
+        target, var
+    )
+    .unwrap();
+    writeln!(out, "endif()").unwrap();
+
+    writeln!(out).unwrap();
+    writeln!(out, "add_library({} INTERFACE)", target).unwrap();
+    writeln!(
+        out,
+        "target_include_directories({} INTERFACE \"$<BUILD_INTERFACE:{}>\")",
+        target, dir
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "target_sources({} INTERFACE ${{{}_HEADERS}} ${{{}_SOURCES}})",
+        target, var, var
+    )
+    .unwrap();
+    writeln!(out, "if(TARGET {}_generate)", target).unwrap();
+    writeln!(out, "    add_dependencies({} {}_generate)", target, target).unwrap();
+    writeln!(out, "endif()").unwrap();
+
+    writeln!(out).unwrap();
+    writeln!(out, "include(GNUInstallDirs)").unwrap();
+    writeln!(
+        out,
+        "install(FILES ${{{}_HEADERS}} ${{{}_SOURCES}} DESTINATION \"${{CMAKE_INSTALL_INCLUDEDIR}}/{}\")",
+        var, var, name_ctx.msg_prefix
+    )
+    .unwrap();
+    out
+}
+
+/// `path` with `/` separators, as CMake wants on every platform.
+fn cmake_path(path: &Path) -> String {
+    path.components()
+        .map(|component| match component {
+            Component::ParentDir => "..".to_string(),
+            other => other.as_os_str().to_string_lossy().into_owned(),
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// A command argument, quoted when CMake would otherwise split it.
+fn cmake_argument(arg: &str) -> String {
+    if !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=".contains(c))
+    {
+        arg.to_string()
+    } else {
+        format!(
+            "\"{}\"",
+            arg.replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('$', "\\$")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cmake_argument_quotes_only_when_needed() {
+        assert_eq!(cmake_argument("--impl-style"), "--impl-style");
+        assert_eq!(cmake_argument("../templates"), "../templates");
+        assert_eq!(cmake_argument("my dir"), "\"my dir\"");
+        assert_eq!(cmake_argument("${HOME}"), "\"\\${HOME}\"");
+        assert_eq!(cmake_argument(""), "\"\"");
+    }
+
+    #[test]
+    fn test_cmake_path_uses_forward_slashes() {
+        let path: PathBuf = ["..", "proto", "msgs.json"].iter().collect();
+        assert_eq!(cmake_path(&path), "../proto/msgs.json");
+    }
+}
@@ -0,0 +1,321 @@
+//! Generator for the `--emit-python` ctypes bindings.
+//!
+//! The emitted `*_ctypes.py` declares a `ctypes.Structure` per typedef of
+//! the types header, with the same members in the same order (including
+//! the `size_t` array lengths), so ctypes lays them out exactly like the C
+//! compiler; the `variant` union of a union message becomes a
+//! `ctypes.Union` class. Its `Library` class loads a shared library built
+//! from the generated `.c` files and wraps the exported encode and decode
+//! functions; the `static inline` functions of the default style are not
+//! exported, which is why the option needs
+//! [`ImplStyle::Source`](super::ImplStyle).
+
+use std::fmt::Write as FmtWrite;
+
+use super::{
+    NameContext, decode_fn_name, encode_fn_name, nested_struct_type_name, sector_count,
+    shared_type_name, type_name,
+};
+use crate::fingerprint::GENERATOR;
+use crate::{
+    MessageBody, MessageDefinition, Metadata, PrimitiveType, StructFieldType, StructSpec,
+    ir_fingerprint, message_body_max_size, shared_types, to_macro_ident, to_snake_case,
+};
+
+const LIBRARY_CLASS: &str = r#"class Library:
+    """Encode and decode functions of a shared library built from the
+    generated .c files. Each role header only defines the directions its
+    role needs; calling a function the library lacks raises
+    AttributeError."""
+
+    def __init__(self, path):
+        self.lib = ctypes.CDLL(path)
+
+    def _encode(self, symbol, msg, max_size):
+        function = getattr(self.lib, symbol)
+        function.restype = ctypes.c_size_t
+        function.argtypes = [
+            ctypes.POINTER(type(msg)),
+            ctypes.POINTER(ctypes.c_uint8),
+            ctypes.c_size_t,
+        ]
+        buf = (ctypes.c_uint8 * max_size)()
+        written = function(ctypes.byref(msg), buf, max_size)
+        if written == 0 and max_size > 0:
+            raise ValueError(symbol + " rejected the message")
+        return bytes(buf[:written])
+
+    def _decode(self, symbol, msg_type, data):
+        function = getattr(self.lib, symbol)
+        function.restype = ctypes.c_bool
+        function.argtypes = [
+            ctypes.POINTER(msg_type),
+            ctypes.POINTER(ctypes.c_uint8),
+            ctypes.c_size_t,
+        ]
+        buf = (ctypes.c_uint8 * len(data)).from_buffer_copy(bytes(data))
+        msg = msg_type()
+        if not function(ctypes.byref(msg), buf, len(data)):
+            raise ValueError(symbol + " rejected the payload")
+        return msg
+"#;
+
+/// Generates the Python module for `messages`; `header` is the types
+/// header the structures mirror.
+pub(super) fn generate_ctypes_module(
+    metadata: &Metadata,
+    messages: &[MessageDefinition],
+    header: &str,
+    name_ctx: &NameContext,
+) -> String {
+    let mut out = String::new();
+    writeln!(out, "# Auto-generated by {}.", GENERATOR).unwrap();
+    writeln!(
+        out,
+        "# IR fingerprint: {}",
+        ir_fingerprint(metadata, messages)
+    )
+    .unwrap();
+    writeln!(out, "\"\"\"ctypes bindings of the structs in {}.", header).unwrap();
+    out.push_str(
+        "\nLoad a shared library built from the C code generated with\n\
+         --impl-style source, e.g. `cc -shared -fPIC -o libproto.so *.c`,\n\
+         with `Library(\"./libproto.so\")`.\n\"\"\"\n\n",
+    );
+    out.push_str("import ctypes\n\n");
+
+    if let Some(version) = &metadata.version {
+        writeln!(out, "PROTOCOL_VERSION = {}", quote(version)).unwrap();
+    }
+    writeln!(out, "MAX_PAYLOAD_BYTES = {}", metadata.max_payload_bytes).unwrap();
+    out.push('\n');
+    for msg in messages {
+        let name = to_macro_ident(&msg.name);
+        writeln!(out, "PACKET_ID_{} = {}", name, msg.packet_id).unwrap();
+        writeln!(
+            out,
+            "{}_MAX_SIZE = {}",
+            name,
+            message_body_max_size(&msg.body)
+        )
+        .unwrap();
+    }
+
+    for spec in shared_types(metadata, messages) {
+        let name = spec.shared.as_deref().unwrap_or_default();
+        write_structure(
+            &mut out,
+            &shared_type_name(&name_ctx.helpers, name),
+            None,
+            spec,
+            name_ctx,
+        );
+    }
+    for msg in messages {
+        let class = type_name(msg, name_ctx);
+        let doc = msg.description.as_deref();
+        match &msg.body {
+            MessageBody::Scalar(spec) => {
+                write_class(
+                    &mut out,
+                    &class,
+                    "Structure",
+                    doc,
+                    &[("value".to_string(), ctype(spec.primitive).to_string())],
+                    name_ctx,
+                );
+            }
+            MessageBody::Array(spec) => {
+                let mut fields = vec![
+                    ("length".to_string(), "ctypes.c_size_t".to_string()),
+                    (
+                        "data".to_string(),
+                        // Char arrays have room for a terminating '\0'
+                        format!(
+                            "{} * {}",
+                            ctype(spec.primitive),
+                            spec.max_length + usize::from(spec.primitive == PrimitiveType::Char)
+                        ),
+                    ),
+                ];
+                if let Some(sector_bytes) = spec.sector_bytes {
+                    fields.push((
+                        "sectors_received".to_string(),
+                        format!(
+                            "ctypes.c_uint8 * {}",
+                            sector_count(spec, sector_bytes).div_ceil(8)
+                        ),
+                    ));
+                }
+                write_class(&mut out, &class, "Structure", doc, &fields, name_ctx);
+            }
+            MessageBody::Struct(spec) => match &spec.shared {
+                // Mirrors the `typedef <shared> <message>;` of the header
+                Some(shared) => {
+                    writeln!(
+                        out,
+                        "\n\n{} = {}",
+                        class,
+                        shared_type_name(&name_ctx.helpers, shared)
+                    )
+                    .unwrap();
+                }
+                None => write_structure(&mut out, &class, doc, spec, name_ctx),
+            },
+            MessageBody::Union(spec) => {
+                // The anonymous union of the typedef needs a class of its own
+                let union_class = format!("_{}_variant", class);
+                let mut members = Vec::new();
+                for variant in &spec.variants {
+                    let variant_class = match &variant.spec.shared {
+                        Some(shared) => shared_type_name(&name_ctx.helpers, shared),
+                        None => {
+                            let variant_class = nested_struct_type_name(&class, &variant.name);
+                            write_structure(
+                                &mut out,
+                                &variant_class,
+                                variant.description.as_deref(),
+                                &variant.spec,
+                                name_ctx,
+                            );
+                            variant_class
+                        }
+                    };
+                    members.push((to_snake_case(&variant.name), variant_class));
+                }
+                write_class(&mut out, &union_class, "Union", None, &members, name_ctx);
+                write_class(
+                    &mut out,
+                    &class,
+                    "Structure",
+                    doc,
+                    &[
+                        ("tag".to_string(), ctype(spec.tag).to_string()),
+                        ("variant".to_string(), union_class),
+                    ],
+                    name_ctx,
+                );
+            }
+        }
+    }
+
+    out.push_str("\n\n");
+    out.push_str(LIBRARY_CLASS);
+    for msg in messages {
+        let class = type_name(msg, name_ctx);
+        let name = to_snake_case(&msg.name);
+        writeln!(out).unwrap();
+        writeln!(out, "    def {}_encode(self, msg):", name).unwrap();
+        writeln!(
+            out,
+            "        return self._encode({}, msg, {}_MAX_SIZE)",
+            quote(&encode_fn_name(msg, name_ctx)),
+            to_macro_ident(&msg.name)
+        )
+        .unwrap();
+        writeln!(out).unwrap();
+        writeln!(out, "    def {}_decode(self, data):", name).unwrap();
+        writeln!(
+            out,
+            "        return self._decode({}, {}, data)",
+            quote(&decode_fn_name(msg, name_ctx)),
+            class
+        )
+        .unwrap();
+    }
+    out
+}
+
+/// Writes the classes of the nested structs defined in place, then the
+/// class of `spec` itself, as [`super::generate_struct_typedef`] does.
+fn write_structure(
+    out: &mut String,
+    class: &str,
+    doc: Option<&str>,
+    spec: &StructSpec,
+    name_ctx: &NameContext,
+) {
+    for field in &spec.fields {
+        if let StructFieldType::Nested(nested) = &field.field_type
+            && nested.shared.is_none()
+        {
+            let nested_class = nested_struct_type_name(class, &field.name);
+            write_structure(out, &nested_class, None, nested, name_ctx);
+        }
+    }
+    let mut fields = Vec::new();
+    for field in &spec.fields {
+        let ident = to_snake_case(&field.name);
+        match &field.field_type {
+            StructFieldType::Primitive(primitive) => {
+                fields.push((ident, ctype(*primitive).to_string()))
+            }
+            StructFieldType::Array(array) => {
+                if !array.fixed {
+                    fields.push((format!("{}_length", ident), "ctypes.c_size_t".to_string()));
+                }
+                // Room for the '\0' the decoder writes after text
+                let capacity = array.max_length + usize::from(array.string_semantics);
+                fields.push((ident, format!("{} * {}", ctype(array.primitive), capacity)));
+            }
+            StructFieldType::Nested(nested) => {
+                let nested_class = match &nested.shared {
+                    Some(shared) => shared_type_name(&name_ctx.helpers, shared),
+                    None => nested_struct_type_name(class, &field.name),
+                };
+                fields.push((ident, nested_class));
+            }
+            StructFieldType::Bytes(length) => {
+                fields.push((ident, format!("ctypes.c_uint8 * {}", length)))
+            }
+        }
+    }
+    write_class(out, class, "Structure", doc, &fields, name_ctx);
+}
+
+/// Writes a `ctypes.Structure` or `ctypes.Union` (`base`) class, without
+/// padding when the typedefs are packed.
+fn write_class(
+    out: &mut String,
+    class: &str,
+    base: &str,
+    doc: Option<&str>,
+    fields: &[(String, String)],
+    name_ctx: &NameContext,
+) {
+    writeln!(out, "\n\nclass {}(ctypes.{}):", class, base).unwrap();
+    if let Some(doc) = doc {
+        writeln!(out, "    __doc__ = {}", quote(doc)).unwrap();
+    }
+    if name_ctx.packed {
+        writeln!(out, "    _pack_ = 1").unwrap();
+    }
+    writeln!(out, "    _fields_ = [").unwrap();
+    for (name, ctype) in fields {
+        writeln!(out, "        ({}, {}),", quote(name), ctype).unwrap();
+    }
+    writeln!(out, "    ]").unwrap();
+}
+
+/// The ctypes type of a C primitive.
+fn ctype(primitive: PrimitiveType) -> &'static str {
+    match primitive {
+        PrimitiveType::Bool => "ctypes.c_bool",
+        PrimitiveType::Char => "ctypes.c_char",
+        PrimitiveType::Int8 => "ctypes.c_int8",
+        PrimitiveType::Uint8 => "ctypes.c_uint8",
+        PrimitiveType::Int16 => "ctypes.c_int16",
+        PrimitiveType::Uint16 => "ctypes.c_uint16",
+        PrimitiveType::Int24 | PrimitiveType::Int32 => "ctypes.c_int32",
+        PrimitiveType::Uint24 | PrimitiveType::Uint32 => "ctypes.c_uint32",
+        PrimitiveType::Int64 => "ctypes.c_int64",
+        PrimitiveType::Uint64 => "ctypes.c_uint64",
+        PrimitiveType::Float16 | PrimitiveType::Float32 => "ctypes.c_float",
+        PrimitiveType::Float64 => "ctypes.c_double",
+    }
+}
+
+/// A Python string literal; JSON escapes are valid Python escapes.
+fn quote(text: &str) -> String {
+    serde_json::Value::from(text).to_string()
+}
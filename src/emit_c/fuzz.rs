@@ -0,0 +1,230 @@
+//! Generator for the `--emit-fuzz` decoder fuzz harness.
+//!
+//! The emitted `*_fuzz.c` defines `LLVMFuzzerTestOneInput`: the first input
+//! byte selects a packet ID and the rest is handed to that message's decoder.
+//! With `packet_id_width` 16 the selector is the first two bytes, in the
+//! `default_endianness`.
+//! Every successful decode must re-encode into a buffer of exactly
+//! `<MSG>_MAX_SIZE` bytes and keep all array lengths within `MAX_LENGTH`.
+//! Unless `H6XSERIAL_FUZZ_LIBFUZZER` is defined, a plain `main()` feeds the
+//! harness from stdin or from files given on the command line, so the same
+//! file builds for libFuzzer, AFL and toolchains without sanitizer support.
+
+use std::fmt::Write as FmtWrite;
+
+use super::{
+    NameContext, Templates, decode_fn_name, encode_fn_name, fields_macro_prefix, msg_macro_prefix,
+    type_name, write_deprecated_opt_out,
+};
+use crate::fingerprint::GENERATOR;
+use crate::{
+    Endian, MessageBody, MessageDefinition, Metadata, StructFieldType, StructSpec, to_macro_ident,
+    to_snake_case,
+};
+
+const STANDALONE_MAIN: &str = r#"#ifndef H6XSERIAL_FUZZ_LIBFUZZER
+/* Plain driver: runs each file argument, or stdin when none are given. */
+static int h6xserial_fuzz_run_stream(FILE *stream) {
+    static uint8_t input[65536];
+    size_t size = fread(input, 1, sizeof(input), stream);
+    return LLVMFuzzerTestOneInput(input, size);
+}
+
+int main(int argc, char **argv) {
+    if (argc < 2) {
+        return h6xserial_fuzz_run_stream(stdin);
+    }
+    for (int i = 1; i < argc; ++i) {
+        FILE *stream = fopen(argv[i], "rb");
+        if (!stream) {
+            fprintf(stderr, "cannot open %s\n", argv[i]);
+            return 1;
+        }
+        h6xserial_fuzz_run_stream(stream);
+        fclose(stream);
+    }
+    return 0;
+}
+#endif /* H6XSERIAL_FUZZ_LIBFUZZER */
+"#;
+
+/// Collects `(length accessor, max length macro)` for every array in a struct.
+fn struct_array_bounds(
+    spec: &StructSpec,
+    accessor_prefix: &str,
+    macro_prefix: &str,
+    helpers: &str,
+    out: &mut Vec<(String, String)>,
+) {
+    let macro_prefix = fields_macro_prefix(helpers, spec, macro_prefix.to_string());
+    for field in &spec.fields {
+        let ident = to_snake_case(&field.name);
+        let field_macro = format!("{}_{}", macro_prefix, to_macro_ident(&field.name));
+        match &field.field_type {
+            StructFieldType::Primitive(_) | StructFieldType::Bytes(_) => {}
+            StructFieldType::Array(arr) if arr.fixed => {}
+            StructFieldType::Array(_) => out.push((
+                format!("{}{}_length", accessor_prefix, ident),
+                format!("{}_MAX_LENGTH", field_macro),
+            )),
+            StructFieldType::Nested(nested) => struct_array_bounds(
+                nested,
+                &format!("{}{}.", accessor_prefix, ident),
+                &field_macro,
+                helpers,
+                out,
+            ),
+        }
+    }
+}
+
+fn generate_case(out: &mut String, msg: &MessageDefinition, name_ctx: &NameContext) {
+    let macro_prefix = msg_macro_prefix(name_ctx, msg);
+
+    let mut bounds = Vec::new();
+    match &msg.body {
+        MessageBody::Scalar(_) => {}
+        MessageBody::Array(_) => bounds.push((
+            "msg.length".to_string(),
+            format!("{}_MAX_LENGTH", macro_prefix),
+        )),
+        MessageBody::Struct(spec) => {
+            struct_array_bounds(spec, "msg.", &macro_prefix, &name_ctx.helpers, &mut bounds)
+        }
+        MessageBody::Union(_) => {}
+    }
+    let mut checks: Vec<String> = bounds
+        .into_iter()
+        .map(|(length, max)| format!("{} <= {}", length, max))
+        .collect();
+    // Only the lengths of the variant the tag selects were decoded
+    if let MessageBody::Union(spec) = &msg.body {
+        for variant in &spec.variants {
+            let variant_macro = format!("{}_{}", macro_prefix, to_macro_ident(&variant.name));
+            let mut bounds = Vec::new();
+            struct_array_bounds(
+                &variant.spec,
+                &format!("msg.variant.{}.", to_snake_case(&variant.name)),
+                &variant_macro,
+                &name_ctx.helpers,
+                &mut bounds,
+            );
+            checks.extend(bounds.into_iter().map(|(length, max)| {
+                format!("msg.tag != {}_TAG || {} <= {}", variant_macro, length, max)
+            }));
+        }
+    }
+
+    writeln!(out, "    case {}_PACKET_ID: {{", macro_prefix).unwrap();
+    writeln!(out, "        {} msg;", type_name(msg, name_ctx)).unwrap();
+    writeln!(out, "        uint8_t out[{}_MAX_SIZE];", macro_prefix).unwrap();
+    writeln!(
+        out,
+        "        if ({}(&msg, payload, payload_len)) {{",
+        decode_fn_name(msg, name_ctx)
+    )
+    .unwrap();
+    for check in &checks {
+        writeln!(out, "            H6XSERIAL_FUZZ_ASSERT({});", check).unwrap();
+    }
+    writeln!(
+        out,
+        "            size_t len = {}(&msg, out, sizeof(out));",
+        encode_fn_name(msg, name_ctx)
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "            H6XSERIAL_FUZZ_ASSERT(len <= {}_MAX_SIZE);",
+        macro_prefix
+    )
+    .unwrap();
+    out.push_str("        }\n        break;\n    }\n");
+}
+
+/// Generates the fuzz harness translation unit.
+///
+/// `headers` are included in order and must together provide both the
+/// encode and decode function of every message.
+pub(super) fn generate_fuzz_source(
+    metadata: &Metadata,
+    messages: &[MessageDefinition],
+    headers: &[String],
+    name_ctx: &NameContext,
+    templates: &Templates,
+) -> String {
+    let mut out = String::new();
+    out.push_str("/*\n");
+    writeln!(
+        out,
+        " * Auto-generated decoder fuzz harness by {}.",
+        GENERATOR
+    )
+    .unwrap();
+    templates.write_origin(&mut out);
+    out.push_str(" *\n");
+    out.push_str(" * libFuzzer: clang -fsanitize=fuzzer,address -DH6XSERIAL_FUZZ_LIBFUZZER <this file> ...\n");
+    out.push_str(" * AFL/plain: cc <this file> ... && ./a.out < input\n");
+    out.push_str(" */\n\n");
+
+    write_deprecated_opt_out(&mut out, messages);
+    out.push_str("#include <stdio.h>\n#include <stdlib.h>\n\n");
+    for header in headers {
+        writeln!(&mut out, "#include \"{}\"", header).unwrap();
+    }
+    out.push('\n');
+
+    out.push_str(
+        "/* Not assert(): must stay active when NDEBUG is defined */
+#define H6XSERIAL_FUZZ_ASSERT(cond) \\
+    do { \\
+        if (!(cond)) { \\
+            fprintf(stderr, \"%s:%d: fuzz check failed: %s\\n\", __FILE__, __LINE__, #cond); \\
+            abort(); \\
+        } \\
+    } while (0)
+
+int LLVMFuzzerTestOneInput(const uint8_t *data, size_t size);
+
+int LLVMFuzzerTestOneInput(const uint8_t *data, size_t size) {
+",
+    );
+    let (selector_len, guard, selector) = if metadata.packet_id_width > 8 {
+        let (high, low) = match metadata.default_endianness.unwrap_or_default() {
+            Endian::Little => (1, 0),
+            Endian::Big => (0, 1),
+        };
+        (
+            2,
+            "size < 2",
+            format!(
+                "(uint16_t)(((uint16_t)data[{}] << 8) | data[{}])",
+                high, low
+            ),
+        )
+    } else {
+        (1, "size == 0", "data[0]".to_string())
+    };
+    writeln!(
+        &mut out,
+        "    if ({guard}) {{
+        return 0;
+    }}
+    const uint8_t *payload = data + {n};
+    const size_t payload_len = size - {n};
+
+    switch ({selector}) {{",
+        guard = guard,
+        n = selector_len,
+        selector = selector
+    )
+    .unwrap();
+
+    for msg in messages {
+        generate_case(&mut out, msg, name_ctx);
+    }
+    out.push_str("    default:\n        break;\n    }\n    return 0;\n}\n\n");
+    out.push_str(STANDALONE_MAIN);
+
+    out
+}
@@ -0,0 +1,614 @@
+//! Generator for the `--emit-tests` C self-test.
+//!
+//! The emitted `*_tests.c` fills every message with deterministic values
+//! (the first and the last variant of a union), encodes it, checks the
+//! exact wire bytes (computed here on the Rust side), decodes into a fresh
+//! struct and compares field by field. `main()` returns non-zero if any
+//! check fails, so CI only needs to compile and run it.
+
+use std::fmt::Write as FmtWrite;
+
+use super::{
+    NameContext, Templates, decode_fn_name, encode_fn_name, msg_macro_prefix, type_name,
+    write_deprecated_opt_out,
+};
+use crate::codec::{f16_bits_to_f32, f32_to_f16_bits};
+use crate::fingerprint::GENERATOR;
+use crate::{
+    Bound, Endian, FLOAT16_MAX, MessageBody, MessageDefinition, PrimitiveType, StructFieldType,
+    StructSpec, ValueRange, to_snake_case,
+};
+
+/// Which end of the value space a test case exercises.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Case {
+    /// Smallest scalar values and zero-length arrays
+    Min,
+    /// Largest scalar values and arrays filled to their maximum length
+    Max,
+}
+
+impl Case {
+    fn suffix(self) -> &'static str {
+        match self {
+            Case::Min => "min",
+            Case::Max => "max",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Case::Min => "smallest values, empty arrays",
+            Case::Max => "largest values, full arrays",
+        }
+    }
+}
+
+/// A deterministic test value: its C literal and its wire encoding.
+#[derive(Debug)]
+struct TestValue {
+    literal: String,
+    wire: Vec<u8>,
+}
+
+/// Returns the inclusive range of an integer primitive.
+fn int_bounds(primitive: PrimitiveType) -> (i128, i128) {
+    match primitive {
+        PrimitiveType::Int8 => (i8::MIN.into(), i8::MAX.into()),
+        PrimitiveType::Uint8 => (0, u8::MAX.into()),
+        PrimitiveType::Int16 => (i16::MIN.into(), i16::MAX.into()),
+        PrimitiveType::Uint16 => (0, u16::MAX.into()),
+        PrimitiveType::Int24 => (-(1 << 23), (1 << 23) - 1),
+        PrimitiveType::Uint24 => (0, (1 << 24) - 1),
+        PrimitiveType::Int32 => (i32::MIN.into(), i32::MAX.into()),
+        PrimitiveType::Uint32 => (0, u32::MAX.into()),
+        PrimitiveType::Int64 => (i64::MIN.into(), i64::MAX.into()),
+        PrimitiveType::Uint64 => (0, u64::MAX.into()),
+        _ => unreachable!("not an integer primitive"),
+    }
+}
+
+fn ordered_bytes(le_bytes: &[u8], endian: Endian) -> Vec<u8> {
+    let mut bytes = le_bytes.to_vec();
+    if endian == Endian::Big {
+        bytes.reverse();
+    }
+    bytes
+}
+
+/// Computes the test value for a scalar (`index == None`) or for element
+/// `index` of an array. Scalars take the extreme of `case`; array elements
+/// follow a fixed pattern spread across the type's range.
+fn test_value(
+    primitive: PrimitiveType,
+    endian: Endian,
+    case: Case,
+    index: Option<usize>,
+) -> TestValue {
+    match primitive {
+        PrimitiveType::Bool => {
+            let value = match index {
+                Some(i) => i.is_multiple_of(2),
+                None => case == Case::Max,
+            };
+            TestValue {
+                literal: value.to_string(),
+                wire: vec![u8::from(value)],
+            }
+        }
+        PrimitiveType::Char => {
+            let value = match (index, case) {
+                (Some(i), _) => b'A' + (i % 26) as u8,
+                (None, Case::Min) => b'a',
+                (None, Case::Max) => b'z',
+            };
+            TestValue {
+                literal: format!("'{}'", value as char),
+                wire: vec![value],
+            }
+        }
+        PrimitiveType::Float16 => {
+            let value = match (index, case) {
+                (Some(i), _) => float_pattern(i) as f32,
+                (None, Case::Min) => -FLOAT16_MAX as f32,
+                (None, Case::Max) => FLOAT16_MAX as f32,
+            };
+            half_test_value(f32_to_f16_bits(value), endian)
+        }
+        PrimitiveType::Float32 => {
+            let value = match (index, case) {
+                (Some(i), _) => float_pattern(i) as f32,
+                (None, Case::Min) => f32::MIN,
+                (None, Case::Max) => f32::MAX,
+            };
+            TestValue {
+                literal: format!("{:e}f", value),
+                wire: ordered_bytes(&value.to_bits().to_le_bytes(), endian),
+            }
+        }
+        PrimitiveType::Float64 => {
+            let value = match (index, case) {
+                (Some(i), _) => float_pattern(i),
+                (None, Case::Min) => f64::MIN,
+                (None, Case::Max) => f64::MAX,
+            };
+            TestValue {
+                literal: format!("{:e}", value),
+                wire: ordered_bytes(&value.to_bits().to_le_bytes(), endian),
+            }
+        }
+        _ => {
+            let (min, max) = int_bounds(primitive);
+            let value = match (index, case) {
+                (Some(i), _) => min + (i as i128 * 0x9E37_79B9 + 0x55) % (max - min + 1),
+                (None, Case::Min) => min,
+                (None, Case::Max) => max,
+            };
+            int_test_value(primitive, endian, value)
+        }
+    }
+}
+
+fn int_test_value(primitive: PrimitiveType, endian: Endian, value: i128) -> TestValue {
+    let (min, _) = int_bounds(primitive);
+    let macro_base = primitive.c_type().trim_end_matches("_t").to_uppercase();
+    // INTn_MIN cannot be written as a negated literal; the int24 minimum is
+    // not the one of its int32_t storage
+    let literal = if value == min && min < 0 && primitive != PrimitiveType::Int24 {
+        format!("{}_MIN", macro_base)
+    } else {
+        format!("{}_C({})", macro_base, value)
+    };
+    let width = primitive.byte_len();
+    TestValue {
+        literal,
+        wire: ordered_bytes(&(value as u64).to_le_bytes()[..width], endian),
+    }
+}
+
+/// Computes the test value for a scalar with a `min`/`max` range: the bound
+/// on the side of `case`, or the type's extreme where that side is open.
+fn ranged_test_value(
+    primitive: PrimitiveType,
+    endian: Endian,
+    case: Case,
+    range: ValueRange,
+) -> TestValue {
+    let bound = match case {
+        Case::Min => range.min,
+        Case::Max => range.max,
+    };
+    match (bound, primitive) {
+        (None, _) => test_value(primitive, endian, case, None),
+        (Some(bound), PrimitiveType::Float16) => {
+            half_test_value(half_bound(bound.as_f64(), case), endian)
+        }
+        (Some(bound), PrimitiveType::Float32) => {
+            let value = bound.as_f64() as f32;
+            TestValue {
+                literal: format!("{:e}f", value),
+                wire: ordered_bytes(&value.to_bits().to_le_bytes(), endian),
+            }
+        }
+        (Some(bound), PrimitiveType::Float64) => {
+            let value = bound.as_f64();
+            TestValue {
+                literal: format!("{:e}", value),
+                wire: ordered_bytes(&value.to_bits().to_le_bytes(), endian),
+            }
+        }
+        (Some(Bound::Int(value)), _) => int_test_value(primitive, endian, value),
+        (Some(Bound::Float(_)), _) => unreachable!("integer ranges have integer bounds"),
+    }
+}
+
+fn half_test_value(bits: u16, endian: Endian) -> TestValue {
+    TestValue {
+        literal: format!("{:e}f", f16_bits_to_f32(bits)),
+        wire: ordered_bytes(&bits.to_le_bytes(), endian),
+    }
+}
+
+/// The half precision value nearest to `bound` on the inner side of a
+/// `min` (`Case::Min`) or `max` bound, which rounding alone may cross.
+fn half_bound(bound: f64, case: Case) -> u16 {
+    let bits = f32_to_f16_bits(bound as f32);
+    let value = f64::from(f16_bits_to_f32(bits));
+    let negative = bits & 0x8000 != 0;
+    let magnitude = bits & 0x7FFF;
+    match case {
+        Case::Min if value < bound => match (negative, magnitude) {
+            (true, 0) => 0x0001,
+            (true, _) => bits - 1,
+            (false, _) => bits + 1,
+        },
+        Case::Max if value > bound => match (negative, magnitude) {
+            (false, 0) => 0x8001,
+            (false, _) => bits - 1,
+            (true, _) => bits + 1,
+        },
+        _ => bits,
+    }
+}
+
+/// Exactly representable values with alternating sign.
+fn float_pattern(index: usize) -> f64 {
+    let magnitude = (index * 3 + 1) as f64 * 0.5;
+    if index.is_multiple_of(2) {
+        magnitude
+    } else {
+        -magnitude
+    }
+}
+
+/// Statements, expected wire bytes and comparisons for one test case.
+#[derive(Default)]
+struct CaseBody {
+    setup: String,
+    wire: Vec<u8>,
+    checks: Vec<(String, String)>,
+}
+
+impl CaseBody {
+    fn primitive(
+        &mut self,
+        accessor: &str,
+        primitive: PrimitiveType,
+        endian: Endian,
+        case: Case,
+        range: Option<ValueRange>,
+    ) {
+        let value = match range {
+            Some(range) => ranged_test_value(primitive, endian, case, range),
+            None => test_value(primitive, endian, case, None),
+        };
+        writeln!(&mut self.setup, "    msg.{} = {};", accessor, value.literal).unwrap();
+        self.wire.extend(value.wire);
+        self.checks.push((
+            format!("decoded.{a} == msg.{a}", a = accessor),
+            accessor.to_string(),
+        ));
+    }
+
+    fn array(
+        &mut self,
+        data_accessor: &str,
+        length_accessor: &str,
+        primitive: PrimitiveType,
+        endian: Endian,
+        length: usize,
+    ) {
+        writeln!(&mut self.setup, "    msg.{} = {};", length_accessor, length).unwrap();
+        self.fill(data_accessor, primitive, endian, length);
+        self.checks.push((
+            format!("decoded.{l} == msg.{l}", l = length_accessor),
+            length_accessor.to_string(),
+        ));
+        self.checks.push((
+            format!(
+                "memcmp(decoded.{d}, msg.{d}, msg.{l} * sizeof(msg.{d}[0])) == 0",
+                d = data_accessor,
+                l = length_accessor
+            ),
+            data_accessor.to_string(),
+        ));
+    }
+
+    /// A `bytes` field, filled with the same pattern as a `uint8` array.
+    fn bytes(&mut self, accessor: &str, length: usize) {
+        self.fixed_array(accessor, PrimitiveType::Uint8, Endian::Little, length);
+    }
+
+    /// An array of exactly `length` elements, full in every case.
+    fn fixed_array(
+        &mut self,
+        accessor: &str,
+        primitive: PrimitiveType,
+        endian: Endian,
+        length: usize,
+    ) {
+        self.fill(accessor, primitive, endian, length);
+        self.checks.push((
+            format!(
+                "memcmp(decoded.{a}, msg.{a}, sizeof(msg.{a})) == 0",
+                a = accessor
+            ),
+            accessor.to_string(),
+        ));
+    }
+
+    /// Copies `length` patterned elements into the array at `data_accessor`.
+    fn fill(
+        &mut self,
+        data_accessor: &str,
+        primitive: PrimitiveType,
+        endian: Endian,
+        length: usize,
+    ) {
+        if length > 0 {
+            let values_ident = format!("{}_values", data_accessor.replace('.', "_"));
+            let values: Vec<TestValue> = (0..length)
+                .map(|i| test_value(primitive, endian, Case::Max, Some(i)))
+                .collect();
+            writeln!(
+                &mut self.setup,
+                "    static const {} {}[{}] = {{",
+                primitive.c_type(),
+                values_ident,
+                length
+            )
+            .unwrap();
+            for chunk in values.chunks(8) {
+                let line: Vec<&str> = chunk.iter().map(|v| v.literal.as_str()).collect();
+                writeln!(&mut self.setup, "        {},", line.join(", ")).unwrap();
+            }
+            self.setup.push_str("    };\n");
+            writeln!(
+                &mut self.setup,
+                "    memcpy(msg.{}, {v}, sizeof({v}));",
+                data_accessor,
+                v = values_ident
+            )
+            .unwrap();
+            for value in values {
+                self.wire.extend(value.wire);
+            }
+        }
+    }
+
+    fn structure(&mut self, spec: &StructSpec, prefix: &str, case: Case) {
+        for field in &spec.fields {
+            // The padding of an `at` gap is written as zeros and skipped
+            // when decoding, so it has no value to set or compare
+            if let (true, StructFieldType::Bytes(length)) = (field.reserved, &field.field_type) {
+                self.wire.resize(self.wire.len() + length, 0);
+                continue;
+            }
+            let accessor = format!("{}{}", prefix, to_snake_case(&field.name));
+            match &field.field_type {
+                StructFieldType::Primitive(prim) => {
+                    self.primitive(&accessor, *prim, field.endian, case, field.range)
+                }
+                StructFieldType::Array(arr) if arr.fixed => {
+                    self.fixed_array(&accessor, arr.primitive, field.endian, arr.max_length)
+                }
+                StructFieldType::Array(arr) => {
+                    let length = match case {
+                        Case::Min => 0,
+                        Case::Max => arr.max_length,
+                    };
+                    self.array(
+                        &accessor,
+                        &format!("{}_length", accessor),
+                        arr.primitive,
+                        field.endian,
+                        length,
+                    );
+                }
+                StructFieldType::Nested(nested) => {
+                    self.structure(nested, &format!("{}.", accessor), case)
+                }
+                StructFieldType::Bytes(length) => self.bytes(&accessor, *length),
+            }
+        }
+    }
+}
+
+pub(super) fn write_byte_array(out: &mut String, ident: &str, bytes: &[u8]) {
+    if bytes.is_empty() {
+        writeln!(out, "    static const uint8_t {}[1] = {{0x00}};", ident).unwrap();
+        return;
+    }
+    writeln!(
+        out,
+        "    static const uint8_t {}[{}] = {{",
+        ident,
+        bytes.len()
+    )
+    .unwrap();
+    for chunk in bytes.chunks(12) {
+        let line: Vec<String> = chunk.iter().map(|b| format!("0x{:02X}", b)).collect();
+        writeln!(out, "        {},", line.join(", ")).unwrap();
+    }
+    out.push_str("    };\n");
+}
+
+fn test_fn_name(msg: &MessageDefinition, case: Case) -> String {
+    format!("test_{}_{}", to_snake_case(&msg.name), case.suffix())
+}
+
+/// The wire bytes of `msg` with the largest values and full arrays, a
+/// payload its decoder accepts.
+pub(super) fn sample_payload(msg: &MessageDefinition) -> Vec<u8> {
+    case_body(msg, Case::Max).wire
+}
+
+fn case_body(msg: &MessageDefinition, case: Case) -> CaseBody {
+    let mut body = CaseBody::default();
+    match &msg.body {
+        MessageBody::Scalar(spec) => {
+            body.primitive("value", spec.primitive, spec.endian, case, spec.range)
+        }
+        MessageBody::Array(spec) => {
+            let length = match case {
+                Case::Min => 0,
+                Case::Max => spec.max_length,
+            };
+            body.array("data", "length", spec.primitive, spec.endian, length);
+        }
+        MessageBody::Struct(spec) => body.structure(spec, "", case),
+        MessageBody::Union(spec) => {
+            // The first variant in the smallest case, the last in the largest
+            let variant = match case {
+                Case::Min => spec.variants.first(),
+                Case::Max => spec.variants.last(),
+            }
+            .expect("unions have at least one variant");
+            let tag = int_test_value(spec.tag, spec.endian, variant.tag);
+            writeln!(&mut body.setup, "    msg.tag = {};", tag.literal).unwrap();
+            body.wire.extend(tag.wire);
+            body.checks
+                .push(("decoded.tag == msg.tag".to_string(), "tag".to_string()));
+            let prefix = format!("variant.{}.", to_snake_case(&variant.name));
+            body.structure(&variant.spec, &prefix, case);
+        }
+    }
+    body
+}
+
+fn generate_case(out: &mut String, msg: &MessageDefinition, case: Case, name_ctx: &NameContext) {
+    let body = case_body(msg, case);
+    let type_name = type_name(msg, name_ctx);
+    let encode_fn = encode_fn_name(msg, name_ctx);
+    let fn_name = test_fn_name(msg, case);
+
+    writeln!(out, "/* {}: {} */", msg.name, case.description()).unwrap();
+    writeln!(out, "static void {}(void) {{", fn_name).unwrap();
+    writeln!(
+        out,
+        "    static const char name[] = \"{}_{}\";",
+        msg.name,
+        case.suffix()
+    )
+    .unwrap();
+    writeln!(out, "    {} msg;", type_name).unwrap();
+    writeln!(out, "    {} decoded;", type_name).unwrap();
+    writeln!(
+        out,
+        "    uint8_t buf[{}_MAX_SIZE + 1];",
+        msg_macro_prefix(name_ctx, msg)
+    )
+    .unwrap();
+    write_byte_array(out, "expected", &body.wire);
+    writeln!(out, "    const size_t expected_len = {};", body.wire.len()).unwrap();
+    out.push_str("    size_t len;\n\n");
+    out.push_str("    memset(&msg, 0, sizeof(msg));\n");
+    out.push_str("    memset(&decoded, 0xA5, sizeof(decoded));\n");
+    out.push_str(&body.setup);
+    out.push('\n');
+
+    writeln!(out, "    len = {}(&msg, buf, sizeof(buf));", encode_fn).unwrap();
+    out.push_str("    h6xserial_test_check(name, len == expected_len, \"encoded length\");\n");
+    out.push_str(
+        "    h6xserial_test_check(name, memcmp(buf, expected, expected_len) == 0, \"wire bytes\");\n",
+    );
+    if !body.wire.is_empty() {
+        writeln!(
+            out,
+            "    h6xserial_test_check(name, {}(&msg, buf, expected_len - 1) == 0, \"short buffer rejected\");",
+            encode_fn
+        )
+        .unwrap();
+    }
+    writeln!(
+        out,
+        "    h6xserial_test_check(name, {}(&decoded, buf, expected_len), \"decode\");",
+        decode_fn_name(msg, name_ctx)
+    )
+    .unwrap();
+    for (cond, what) in &body.checks {
+        writeln!(
+            out,
+            "    h6xserial_test_check(name, {}, \"{}\");",
+            cond, what
+        )
+        .unwrap();
+    }
+    out.push_str("}\n\n");
+}
+
+/// Generates the self-test translation unit.
+///
+/// `headers` are included in order and must together provide both the
+/// encode and decode function of every message.
+pub(super) fn generate_test_source(
+    messages: &[MessageDefinition],
+    headers: &[String],
+    name_ctx: &NameContext,
+    templates: &Templates,
+) -> String {
+    let mut out = String::new();
+    out.push_str("/*\n");
+    writeln!(
+        out,
+        " * Auto-generated encode/decode self-test by {}.",
+        GENERATOR
+    )
+    .unwrap();
+    templates.write_origin(&mut out);
+    out.push_str(
+        " * Build together with the generated sources and run; exits non-zero on failure.\n",
+    );
+    out.push_str(" */\n\n");
+
+    write_deprecated_opt_out(&mut out, messages);
+    out.push_str("#include <stdio.h>\n#include <string.h>\n\n");
+    for header in headers {
+        writeln!(&mut out, "#include \"{}\"", header).unwrap();
+    }
+    out.push('\n');
+
+    out.push_str(
+        "static int h6xserial_test_checks = 0;
+static int h6xserial_test_failures = 0;
+
+static void h6xserial_test_check(const char *test, bool ok, const char *what) {
+    ++h6xserial_test_checks;
+    if (!ok) {
+        fprintf(stderr, \"FAIL %s: %s\\n\", test, what);
+        ++h6xserial_test_failures;
+    }
+}
+
+",
+    );
+
+    for msg in messages {
+        for case in [Case::Min, Case::Max] {
+            generate_case(&mut out, msg, case, name_ctx);
+        }
+    }
+
+    out.push_str("int main(void) {\n");
+    for msg in messages {
+        for case in [Case::Min, Case::Max] {
+            writeln!(&mut out, "    {}();", test_fn_name(msg, case)).unwrap();
+        }
+    }
+    out.push_str(
+        "
+    if (h6xserial_test_failures != 0) {
+        fprintf(stderr, \"%d of %d check(s) failed\\n\", h6xserial_test_failures, h6xserial_test_checks);
+        return 1;
+    }
+    printf(\"All %d check(s) passed\\n\", h6xserial_test_checks);
+    return 0;
+}
+",
+    );
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_values_follow_endianness_and_extremes() {
+        let value = test_value(PrimitiveType::Uint16, Endian::Big, Case::Max, None);
+        assert_eq!(value.literal, "UINT16_C(65535)");
+        assert_eq!(value.wire, vec![0xFF, 0xFF]);
+
+        let value = test_value(PrimitiveType::Int32, Endian::Little, Case::Min, None);
+        assert_eq!(value.literal, "INT32_MIN");
+        assert_eq!(value.wire, vec![0x00, 0x00, 0x00, 0x80]);
+
+        let value = test_value(PrimitiveType::Float32, Endian::Big, Case::Max, Some(1));
+        assert_eq!(value.literal, "-2e0f");
+        assert_eq!(value.wire, vec![0xC0, 0x00, 0x00, 0x00]);
+
+        let value = test_value(PrimitiveType::Int16, Endian::Little, Case::Max, Some(0));
+        assert_eq!(value.wire, (i16::MIN + 0x55).to_le_bytes().to_vec());
+    }
+}
@@ -0,0 +1,165 @@
+//! Generator for the `--emit-skeleton` server starting point.
+//!
+//! The emitted `<base>_server.example.c` includes the server header, has an
+//! empty handler for every message the server decodes and a
+//! `<prefix>_server_poll()` loop that decodes the packets a read callback
+//! returns and dispatches them by packet ID. Framing is left to the read
+//! callback. The file belongs to the user once written, so it is not part
+//! of the regenerated set; see [`super::generate_server_skeleton`].
+
+use std::fmt::Write as FmtWrite;
+
+use super::{
+    FunctionMode, NameContext, Role, decode_fn_name, msg_macro_prefix, type_name,
+    write_tx_fn_typedef,
+};
+use crate::fingerprint::GENERATOR;
+use crate::{MessageDefinition, Metadata, ir_fingerprint, to_snake_case};
+
+pub(super) fn generate_skeleton_source(
+    metadata: &Metadata,
+    messages: &[MessageDefinition],
+    server_header: &str,
+    name_ctx: &NameContext,
+) -> String {
+    let helpers = &name_ctx.helpers;
+    let handled: Vec<&MessageDefinition> = messages
+        .iter()
+        .filter(|msg| Role::Server.function_mode(msg) == Some(FunctionMode::DecodeOnly))
+        .collect();
+
+    let mut out = String::new();
+    out.push_str("/*\n");
+    writeln!(out, " * Server skeleton generated by {}.", GENERATOR).unwrap();
+    writeln!(
+        out,
+        " * IR fingerprint: {}",
+        ir_fingerprint(metadata, messages)
+    )
+    .unwrap();
+    out.push_str(" *\n");
+    out.push_str(" * A starting point for server firmware: fill in the handlers and call\n");
+    writeln!(
+        out,
+        " * {}_server_poll() from the main loop. This file is yours to edit;",
+        helpers
+    )
+    .unwrap();
+    out.push_str(" * the generator writes a new file instead of overwriting it.\n");
+    out.push_str(" */\n\n");
+    if handled.iter().any(|msg| msg.deprecated.is_some()) {
+        out.push_str(
+            "/* Deprecated messages are still handled */\n#define H6XSERIAL_NO_DEPRECATED\n\n",
+        );
+    }
+    writeln!(out, "#include \"{}\"\n", server_header).unwrap();
+
+    out.push_str(
+        "/* Reads one packet into payload; returns the payload length, or -1 when\n * no packet is available */\n",
+    );
+    writeln!(
+        out,
+        "typedef int (*{}_read_fn)({} *packet_id, uint8_t *payload, size_t capacity, void *ctx);\n",
+        helpers,
+        name_ctx.packet_id_type()
+    )
+    .unwrap();
+    write_tx_fn_typedef(&mut out, helpers);
+
+    for msg in &handled {
+        writeln!(
+            out,
+            "static void handle_{}(const {} *msg, {}_tx_fn write, void *ctx) {{",
+            to_snake_case(&msg.name),
+            type_name(msg, name_ctx),
+            helpers
+        )
+        .unwrap();
+        writeln!(out, "    /* TODO: handle '{}' */", msg.name).unwrap();
+        out.push_str("    (void)msg;\n    (void)write;\n    (void)ctx;\n}\n\n");
+    }
+
+    out.push_str(
+        "/* Dispatches every packet read returns to its handler, which may answer\n * through write; returns the number of packets handled */\n",
+    );
+    writeln!(
+        out,
+        "size_t {h}_server_poll({h}_read_fn read, {h}_tx_fn write, void *ctx) {{",
+        h = helpers
+    )
+    .unwrap();
+    out.push_str("    uint8_t payload[H6XSERIAL_MAX_PAYLOAD_BYTES];\n");
+    writeln!(out, "    {} packet_id;", name_ctx.packet_id_type()).unwrap();
+    out.push_str("    size_t handled = 0;\n");
+    out.push_str("    int len;\n");
+    if handled.is_empty() {
+        out.push_str("    (void)write;\n");
+    }
+    out.push_str("    while ((len = read(&packet_id, payload, sizeof(payload), ctx)) >= 0) {\n");
+    out.push_str("        switch (packet_id) {\n");
+    for msg in &handled {
+        writeln!(
+            out,
+            "        case {}_PACKET_ID: {{",
+            msg_macro_prefix(name_ctx, msg)
+        )
+        .unwrap();
+        writeln!(out, "            {} msg;", type_name(msg, name_ctx)).unwrap();
+        writeln!(
+            out,
+            "            if ({}(&msg, payload, (size_t)len)) {{",
+            decode_fn_name(msg, name_ctx)
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "                handle_{}(&msg, write, ctx);",
+            to_snake_case(&msg.name)
+        )
+        .unwrap();
+        out.push_str("                handled++;\n");
+        out.push_str("            }\n");
+        out.push_str("            break;\n");
+        out.push_str("        }\n");
+    }
+    out.push_str("        default:\n");
+    out.push_str("            break;\n");
+    out.push_str("        }\n");
+    out.push_str("    }\n");
+    out.push_str("    return handled;\n");
+    out.push_str("}\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_handlers_cover_the_messages_the_server_decodes() {
+        let (metadata, messages) = crate::parse_str(
+            r#"{ "set_led": { "packet_id": 1, "msg_type": "bool", "request_type": "sub" },
+                 "status": { "packet_id": 2, "msg_type": "u8" } }"#,
+        )
+        .unwrap();
+        let name_ctx = NameContext::new(
+            "robot",
+            &super::super::CGenOptions::default(),
+            &Metadata::default(),
+            &messages,
+        );
+        let source = generate_skeleton_source(&metadata, &messages, "robot_server.h", &name_ctx);
+        assert!(source.contains("#include \"robot_server.h\"\n"));
+        assert!(source.contains(
+            "static void handle_set_led(const robot_msg_set_led_t *msg, h6xserial_tx_fn write, void *ctx) {\n"
+        ));
+        assert!(source.contains(
+            "        case ROBOT_MSG_SET_LED_PACKET_ID: {\n            robot_msg_set_led_t msg;\n            if (robot_msg_set_led_decode(&msg, payload, (size_t)len)) {\n                handle_set_led(&msg, write, ctx);\n"
+        ));
+        assert!(source.contains(
+            "size_t h6xserial_server_poll(h6xserial_read_fn read, h6xserial_tx_fn write, void *ctx) {\n"
+        ));
+        assert!(!source.contains("handle_status"));
+        assert!(!source.contains("H6XSERIAL_NO_DEPRECATED"));
+    }
+}
@@ -0,0 +1,232 @@
+//! CSV codebook generator for message definitions.
+//!
+//! One row per leaf value, for tracking signals in a spreadsheet. Nested
+//! struct fields are flattened into dotted paths and each array is a single
+//! row; the fields of a union variant are prefixed with its name and start
+//! after the tag. Offsets assume every array holds `max_length` elements,
+//! as in `<MSG>_MAX_SIZE`. Quoting and line endings follow RFC 4180.
+
+use std::fmt::Write as FmtWrite;
+
+use crate::{
+    Endian, IdlError, MessageBody, MessageDefinition, Metadata, PrimitiveType, StructFieldType,
+    StructSpec, ValueRange,
+};
+
+/// Column names of the header row.
+const COLUMNS: [&str; 11] = [
+    "message_name",
+    "packet_id",
+    "field_path",
+    "type",
+    "bytes",
+    "offset",
+    "endianness",
+    "unit",
+    "min",
+    "max",
+    "description",
+];
+
+/// Generates the CSV codebook of `messages`.
+///
+/// # Columns
+/// `message_name`, `packet_id`, `field_path`, `type`, `bytes`, `offset`,
+/// `endianness`, `unit`, `min`, `max` and `description`. Scalar messages
+/// have one row named `value` and array messages one named `data`; arrays
+/// are typed like `uint16_t[max 12]` with the bytes of all elements.
+/// Endianness is left empty for single-byte values.
+pub fn generate(_metadata: &Metadata, messages: &[MessageDefinition]) -> Result<String, IdlError> {
+    let mut out = String::new();
+    write_record(&mut out, COLUMNS.iter().map(|c| c.to_string()));
+    for msg in messages {
+        let mut rows = Vec::new();
+        match &msg.body {
+            MessageBody::Scalar(spec) => rows.push(Row {
+                path: "value".to_string(),
+                ty: spec.primitive.wire_type().to_string(),
+                bytes: spec.primitive.byte_len(),
+                offset: 0,
+                endian: endianness(spec.primitive, spec.endian),
+                unit: spec.unit.clone(),
+                range: spec.range,
+                description: spec.description.clone(),
+            }),
+            MessageBody::Array(spec) => rows.push(Row {
+                path: "data".to_string(),
+                ty: array_type(spec.primitive, spec.max_length, false),
+                bytes: spec.primitive.byte_len() * spec.max_length,
+                offset: 0,
+                endian: endianness(spec.primitive, spec.endian),
+                unit: spec.unit.clone(),
+                range: None,
+                description: spec.description.clone(),
+            }),
+            MessageBody::Struct(spec) => {
+                collect(spec, "", 0, &mut rows);
+            }
+            MessageBody::Union(spec) => {
+                rows.push(Row {
+                    path: "tag".to_string(),
+                    ty: spec.tag.wire_type().to_string(),
+                    bytes: spec.tag.byte_len(),
+                    offset: 0,
+                    endian: endianness(spec.tag, spec.endian),
+                    unit: None,
+                    range: None,
+                    description: None,
+                });
+                // Every variant starts right after the tag
+                for variant in &spec.variants {
+                    collect(
+                        &variant.spec,
+                        &format!("{}.", variant.name),
+                        spec.tag.byte_len(),
+                        &mut rows,
+                    );
+                }
+            }
+        }
+        for row in rows {
+            let range = row.range.unwrap_or_default();
+            write_record(
+                &mut out,
+                [
+                    msg.name.clone(),
+                    msg.packet_id.to_string(),
+                    row.path,
+                    row.ty,
+                    row.bytes.to_string(),
+                    row.offset.to_string(),
+                    row.endian.to_string(),
+                    row.unit.unwrap_or_default(),
+                    range.min.map(|b| b.to_string()).unwrap_or_default(),
+                    range.max.map(|b| b.to_string()).unwrap_or_default(),
+                    row.description.unwrap_or_default(),
+                ],
+            );
+        }
+    }
+    Ok(out)
+}
+
+/// One leaf value of a message.
+struct Row {
+    path: String,
+    ty: String,
+    bytes: usize,
+    offset: usize,
+    endian: &'static str,
+    unit: Option<String>,
+    range: Option<ValueRange>,
+    description: Option<String>,
+}
+
+/// Adds the leaves of `spec` starting at `offset` and returns the offset
+/// after it.
+fn collect(spec: &StructSpec, prefix: &str, mut offset: usize, rows: &mut Vec<Row>) -> usize {
+    for field in &spec.fields {
+        let path = format!("{}{}", prefix, field.name);
+        let (ty, bytes, endian) = match &field.field_type {
+            StructFieldType::Primitive(primitive) => (
+                primitive.wire_type().to_string(),
+                primitive.byte_len(),
+                endianness(*primitive, field.endian),
+            ),
+            StructFieldType::Array(array) => (
+                array_type(array.primitive, array.max_length, array.fixed),
+                array.primitive.byte_len() * array.max_length,
+                endianness(array.primitive, field.endian),
+            ),
+            StructFieldType::Bytes(length) => (format!("bytes[{}]", length), *length, ""),
+            StructFieldType::Nested(nested) => {
+                offset = collect(nested, &format!("{}.", path), offset, rows);
+                continue;
+            }
+        };
+        rows.push(Row {
+            path,
+            ty,
+            bytes,
+            offset,
+            endian,
+            unit: field.unit.clone(),
+            range: field.range,
+            description: field.description.clone(),
+        });
+        offset += bytes;
+    }
+    offset
+}
+
+fn array_type(primitive: PrimitiveType, max_length: usize, fixed: bool) -> String {
+    if fixed {
+        format!("{}[{}]", primitive.wire_type(), max_length)
+    } else {
+        format!("{}[max {}]", primitive.wire_type(), max_length)
+    }
+}
+
+fn endianness(primitive: PrimitiveType, endian: Endian) -> &'static str {
+    match endian {
+        _ if primitive.byte_len() == 1 => "",
+        Endian::Little => "little",
+        Endian::Big => "big",
+    }
+}
+
+/// Writes one record, quoting the fields that need it.
+fn write_record(out: &mut String, fields: impl IntoIterator<Item = String>) {
+    let fields: Vec<String> = fields.into_iter().map(|f| quote(&f)).collect();
+    write!(out, "{}\r\n", fields.join(",")).unwrap();
+}
+
+/// A field as RFC 4180 wants it: in double quotes, with quotes doubled,
+/// when it holds a comma, a quote or a line break.
+fn quote(field: &str) -> String {
+    if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("plain text"), "plain text");
+        assert_eq!(quote("a, b"), "\"a, b\"");
+        assert_eq!(quote("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(quote("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn test_one_row_per_leaf() {
+        let (metadata, messages) = crate::parse_str(
+            r#"{ "speed": { "packet_id": 1, "msg_type": "int16", "endianess": "big",
+                            "unit": "rpm", "min": -100, "max": 100, "desc": "target, signed" },
+                 "scan": { "packet_id": 2, "msg_type": "struct", "fields": {
+                     "id": { "type": "u8" },
+                     "pose": { "type": "struct", "fields": {
+                         "x": { "type": "f32", "unit": "m" },
+                         "y": { "type": "f32", "desc": "the \"y\" axis" } } },
+                     "ranges": { "type": "u16", "array": true, "max_length": 4 },
+                     "crc": { "type": "u16", "endianess": "big" } } } }"#,
+        )
+        .unwrap();
+        let csv = generate(&metadata, &messages).unwrap();
+        assert_eq!(
+            csv,
+            "message_name,packet_id,field_path,type,bytes,offset,endianness,unit,min,max,description\r\n\
+             speed,1,value,int16_t,2,0,big,rpm,-100,100,\"target, signed\"\r\n\
+             scan,2,id,uint8_t,1,0,,,,,\r\n\
+             scan,2,pose.x,float,4,1,little,m,,,\r\n\
+             scan,2,pose.y,float,4,5,little,,,,\"the \"\"y\"\" axis\"\r\n\
+             scan,2,ranges,uint16_t[max 4],8,9,little,,,,\r\n\
+             scan,2,crc,uint16_t,2,17,big,,,,\r\n"
+        );
+    }
+}
@@ -0,0 +1,606 @@
+//! CAN database (`.dbc`) generator for message definitions.
+//!
+//! Every message whose payload fits one CAN frame becomes a `BO_` frame
+//! with the packet ID as CAN ID, and every leaf value a `SG_` signal at its
+//! fixed bit position. A variable-length array is split into one signal per
+//! element, which only has a fixed position when nothing follows the
+//! array. A union is a multiplexed frame with the tag as multiplexor.
+//! Messages that do not fit or have no fixed layout are left out and
+//! reported in [`DbcFile::skipped`].
+
+use std::collections::HashSet;
+use std::fmt::Write as FmtWrite;
+
+use crate::fingerprint::GENERATOR;
+use crate::{
+    Bound, Endian, IdlError, MessageBody, MessageDefinition, Metadata, PrimitiveType, Scaling,
+    StructFieldType, StructSpec, ValueRange, ir_fingerprint, message_body_max_size, to_snake_case,
+};
+
+/// Payload of a classic CAN frame in bytes.
+pub const CAN_PAYLOAD_BYTES: usize = 8;
+/// Payload of a CAN FD frame in bytes.
+pub const CAN_FD_PAYLOAD_BYTES: usize = 64;
+
+/// Longest name most DBC tools accept without extended attributes.
+const MAX_NAME_LEN: usize = 32;
+
+/// Node name DBC uses for an unspecified sender or receiver.
+const NO_NODE: &str = "Vector__XXX";
+
+/// Options for DBC generation.
+#[derive(Clone, Debug, Default)]
+pub struct DbcOptions {
+    /// Describe CAN FD frames of up to 64 bytes instead of classic 8-byte
+    /// frames
+    pub can_fd: bool,
+}
+
+/// A generated DBC file.
+#[derive(Clone, Debug)]
+pub struct DbcFile {
+    pub content: String,
+    /// Messages left out of the file, in packet ID order
+    pub skipped: Vec<SkippedMessage>,
+}
+
+/// A message that has no CAN frame.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SkippedMessage {
+    pub name: String,
+    pub packet_id: u32,
+    /// Why, e.g. `up to 12 bytes, more than the 8-byte CAN payload`
+    pub reason: String,
+}
+
+/// Generates the DBC of `messages`.
+pub fn generate(
+    metadata: &Metadata,
+    messages: &[MessageDefinition],
+    options: &DbcOptions,
+) -> Result<DbcFile, IdlError> {
+    let payload = if options.can_fd {
+        CAN_FD_PAYLOAD_BYTES
+    } else {
+        CAN_PAYLOAD_BYTES
+    };
+    let mut sorted: Vec<&MessageDefinition> = messages.iter().collect();
+    sorted.sort_by_key(|msg| msg.packet_id);
+
+    let mut frames = Vec::new();
+    let mut skipped = Vec::new();
+    let mut frame_names = HashSet::new();
+    for msg in sorted {
+        let max_size = message_body_max_size(&msg.body);
+        if max_size > payload {
+            skipped.push(SkippedMessage {
+                name: msg.name.clone(),
+                packet_id: msg.packet_id,
+                reason: format!(
+                    "up to {} bytes, more than the {}-byte CAN{} payload",
+                    max_size,
+                    payload,
+                    if options.can_fd { " FD" } else { "" }
+                ),
+            });
+            continue;
+        }
+        let signals = match signals(msg) {
+            Ok(signals) => signals,
+            Err(reason) => {
+                skipped.push(SkippedMessage {
+                    name: msg.name.clone(),
+                    packet_id: msg.packet_id,
+                    reason: reason.to_string(),
+                });
+                continue;
+            }
+        };
+        frames.push(Frame {
+            msg,
+            name: unique_name(&msg.name, &mut frame_names),
+            dlc: if options.can_fd {
+                fd_frame_len(max_size)
+            } else {
+                max_size
+            },
+            signals,
+        });
+    }
+
+    let mut out = String::new();
+    writeln!(
+        out,
+        "VERSION \"{}\"",
+        escape(metadata.version.as_deref().unwrap_or_default())
+    )
+    .unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "NS_ :").unwrap();
+    for symbol in ["CM_", "BA_DEF_", "BA_DEF_DEF_", "BA_", "SIG_VALTYPE_"] {
+        writeln!(out, "\t{}", symbol).unwrap();
+    }
+    writeln!(out).unwrap();
+    writeln!(out, "BS_:").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "BU_:").unwrap();
+
+    for frame in &frames {
+        writeln!(out).unwrap();
+        writeln!(
+            out,
+            "BO_ {} {}: {} {}",
+            frame.msg.packet_id, frame.name, frame.dlc, NO_NODE
+        )
+        .unwrap();
+        for signal in &frame.signals {
+            signal.write(&mut out);
+        }
+    }
+
+    writeln!(out).unwrap();
+    writeln!(
+        out,
+        "CM_ \"Auto-generated by {}. IR fingerprint: {}\";",
+        GENERATOR,
+        ir_fingerprint(metadata, messages)
+    )
+    .unwrap();
+    for frame in &frames {
+        if let Some(description) = &frame.msg.description {
+            writeln!(
+                out,
+                "CM_ BO_ {} \"{}\";",
+                frame.msg.packet_id,
+                escape(description)
+            )
+            .unwrap();
+        }
+        for signal in &frame.signals {
+            if let Some(description) = &signal.description {
+                writeln!(
+                    out,
+                    "CM_ SG_ {} {} \"{}\";",
+                    frame.msg.packet_id,
+                    signal.name,
+                    escape(description)
+                )
+                .unwrap();
+            }
+        }
+    }
+
+    if options.can_fd {
+        // Vector's attribute marking frames as CAN FD; the values between
+        // the classic and FD formats are reserved
+        let mut formats = vec!["\"StandardCAN\"", "\"ExtendedCAN\""];
+        formats.extend(["\"reserved\""; 12]);
+        formats.extend(["\"StandardCAN_FD\"", "\"ExtendedCAN_FD\""]);
+        writeln!(
+            out,
+            "BA_DEF_ BO_ \"VFrameFormat\" ENUM {};",
+            formats.join(",")
+        )
+        .unwrap();
+        writeln!(out, "BA_DEF_ \"BusType\" STRING;").unwrap();
+        writeln!(out, "BA_DEF_DEF_ \"VFrameFormat\" \"StandardCAN\";").unwrap();
+        writeln!(out, "BA_DEF_DEF_ \"BusType\" \"CAN FD\";").unwrap();
+        writeln!(out, "BA_ \"BusType\" \"CAN FD\";").unwrap();
+        for frame in &frames {
+            writeln!(out, "BA_ \"VFrameFormat\" BO_ {} 14;", frame.msg.packet_id).unwrap();
+        }
+    }
+
+    for frame in &frames {
+        for signal in &frame.signals {
+            let value_type = match signal.primitive {
+                PrimitiveType::Float32 => 1,
+                PrimitiveType::Float64 => 2,
+                _ => continue,
+            };
+            writeln!(
+                out,
+                "SIG_VALTYPE_ {} {} : {};",
+                frame.msg.packet_id, signal.name, value_type
+            )
+            .unwrap();
+        }
+    }
+
+    Ok(DbcFile {
+        content: out,
+        skipped,
+    })
+}
+
+struct Frame<'a> {
+    msg: &'a MessageDefinition,
+    name: String,
+    /// Frame length in bytes
+    dlc: usize,
+    signals: Vec<Signal>,
+}
+
+/// One `SG_` line.
+struct Signal {
+    name: String,
+    primitive: PrimitiveType,
+    endian: Endian,
+    /// Byte offset in the payload
+    offset: usize,
+    scaling: Option<Scaling>,
+    range: Option<ValueRange>,
+    unit: Option<String>,
+    description: Option<String>,
+    multiplex: Multiplex,
+}
+
+/// Role of a signal in a multiplexed (union) frame.
+#[derive(Clone, Copy)]
+enum Multiplex {
+    None,
+    /// The tag, `M`
+    Switch,
+    /// Present when the tag has this value, `m<value>`
+    Value(u64),
+}
+
+impl Signal {
+    fn write(&self, out: &mut String) {
+        let bits = self.primitive.byte_len() * 8;
+        // Intel signals start at their least significant bit, Motorola ones
+        // at the most significant bit of the first byte
+        let (start, order) = match self.endian {
+            Endian::Big if bits > 8 => (self.offset * 8 + 7, 0),
+            _ => (self.offset * 8, 1),
+        };
+        let signed = matches!(
+            self.primitive,
+            PrimitiveType::Int8
+                | PrimitiveType::Int16
+                | PrimitiveType::Int24
+                | PrimitiveType::Int32
+                | PrimitiveType::Int64
+                | PrimitiveType::Float32
+                | PrimitiveType::Float64
+        );
+        let scaling = self.scaling.unwrap_or(Scaling {
+            scale: 1.0,
+            offset: 0.0,
+        });
+        let (min, max) = self.physical_range(scaling);
+        let multiplex = match self.multiplex {
+            Multiplex::None => String::new(),
+            Multiplex::Switch => " M".to_string(),
+            Multiplex::Value(tag) => format!(" m{}", tag),
+        };
+        writeln!(
+            out,
+            " SG_ {}{} : {}|{}@{}{} ({},{}) [{}|{}] \"{}\" {}",
+            self.name,
+            multiplex,
+            start,
+            bits,
+            order,
+            if signed { '-' } else { '+' },
+            scaling.scale,
+            scaling.offset,
+            min,
+            max,
+            escape(self.unit.as_deref().unwrap_or_default()),
+            NO_NODE
+        )
+        .unwrap();
+    }
+
+    /// The `[min|max]` of the signal in physical units; `[0|0]` leaves an
+    /// unbounded float open, as DBC tools expect.
+    fn physical_range(&self, scaling: Scaling) -> (String, String) {
+        let range = self.range.unwrap_or_default();
+        let raw = |bound: Option<Bound>, fallback: Option<i128>| match bound {
+            Some(bound) => Some(bound.as_f64()),
+            None => fallback.map(|value| value as f64),
+        };
+        let (min, max) = match self.primitive {
+            PrimitiveType::Bool => (Some(0.0), Some(1.0)),
+            PrimitiveType::Char => (Some(0.0), Some(255.0)),
+            // DBC has no half precision type; the signal carries the bits
+            PrimitiveType::Float16 => (Some(0.0), Some(65535.0)),
+            PrimitiveType::Float32 | PrimitiveType::Float64 => {
+                (raw(range.min, None), raw(range.max, None))
+            }
+            integer => {
+                let (low, high) = integer.integer_range().unwrap_or_default();
+                (raw(range.min, Some(low)), raw(range.max, Some(high)))
+            }
+        };
+        match (min, max) {
+            (Some(min), Some(max)) => {
+                let (a, b) = (scaling.apply(min), scaling.apply(max));
+                (a.min(b).to_string(), a.max(b).to_string())
+            }
+            _ => ("0".to_string(), "0".to_string()),
+        }
+    }
+}
+
+/// Why a value follows a variable-length array and has no fixed position.
+const AFTER_ARRAY: &str = "values after a variable-length array have no fixed position";
+
+/// The signals of `msg`, or why it has no fixed layout. The tag of a union
+/// is the multiplexor of the signals of its variants.
+fn signals(msg: &MessageDefinition) -> Result<Vec<Signal>, &'static str> {
+    let mut signals = Vec::new();
+    let mut names = HashSet::new();
+    match &msg.body {
+        MessageBody::Scalar(spec) => signals.push(Signal {
+            name: unique_name("value", &mut names),
+            primitive: spec.primitive,
+            endian: spec.endian,
+            offset: 0,
+            scaling: spec.scaling,
+            range: spec.range,
+            unit: spec.unit.clone(),
+            description: spec.description.clone(),
+            multiplex: Multiplex::None,
+        }),
+        MessageBody::Array(spec) => {
+            for index in 0..spec.max_length {
+                signals.push(Signal {
+                    name: unique_name(&format!("data_{}", index), &mut names),
+                    primitive: spec.primitive,
+                    endian: spec.endian,
+                    offset: index * spec.primitive.byte_len(),
+                    scaling: spec.scaling,
+                    range: None,
+                    unit: spec.unit.clone(),
+                    description: spec.description.clone(),
+                    multiplex: Multiplex::None,
+                });
+            }
+        }
+        MessageBody::Struct(spec) => {
+            let mut walk = Walk {
+                offset: 0,
+                after_array: false,
+                names: &mut names,
+                signals: &mut signals,
+            };
+            walk.fields(spec, "").ok_or(AFTER_ARRAY)?;
+        }
+        MessageBody::Union(spec) => {
+            signals.push(Signal {
+                name: unique_name("tag", &mut names),
+                primitive: spec.tag,
+                endian: spec.endian,
+                offset: 0,
+                scaling: None,
+                range: None,
+                unit: None,
+                description: None,
+                multiplex: Multiplex::Switch,
+            });
+            for variant in &spec.variants {
+                let tag = u64::try_from(variant.tag)
+                    .map_err(|_| "DBC multiplexor values cannot be negative")?;
+                let first = signals.len();
+                let mut walk = Walk {
+                    offset: spec.tag.byte_len(),
+                    after_array: false,
+                    names: &mut names,
+                    signals: &mut signals,
+                };
+                walk.fields(&variant.spec, &format!("{}_", variant.name))
+                    .ok_or(AFTER_ARRAY)?;
+                for signal in &mut signals[first..] {
+                    signal.multiplex = Multiplex::Value(tag);
+                }
+            }
+        }
+    }
+    Ok(signals)
+}
+
+/// Position while laying out the fields of a struct.
+struct Walk<'a> {
+    offset: usize,
+    /// A variable-length array was laid out, so nothing may follow
+    after_array: bool,
+    names: &'a mut HashSet<String>,
+    signals: &'a mut Vec<Signal>,
+}
+
+impl Walk<'_> {
+    fn fields(&mut self, spec: &StructSpec, prefix: &str) -> Option<()> {
+        for field in &spec.fields {
+            let path = format!("{}{}", prefix, field.name);
+            match &field.field_type {
+                StructFieldType::Primitive(primitive) => {
+                    if self.after_array {
+                        return None;
+                    }
+                    self.push(&path, *primitive, field, field.range);
+                }
+                StructFieldType::Array(array) => {
+                    if self.after_array {
+                        return None;
+                    }
+                    for index in 0..array.max_length {
+                        self.push(&format!("{}_{}", path, index), array.primitive, field, None);
+                    }
+                    // Fields after a fixed_length array keep their offsets
+                    self.after_array = !array.fixed;
+                }
+                StructFieldType::Bytes(length) => {
+                    if self.after_array {
+                        return None;
+                    }
+                    if field.reserved {
+                        self.offset += length;
+                        continue;
+                    }
+                    for index in 0..*length {
+                        let name = format!("{}_{}", path, index);
+                        self.push(&name, PrimitiveType::Uint8, field, None);
+                    }
+                }
+                StructFieldType::Nested(nested) => {
+                    self.fields(nested, &format!("{}_", path))?;
+                }
+            }
+        }
+        Some(())
+    }
+
+    fn push(
+        &mut self,
+        path: &str,
+        primitive: PrimitiveType,
+        field: &crate::StructField,
+        range: Option<ValueRange>,
+    ) {
+        self.signals.push(Signal {
+            name: unique_name(path, self.names),
+            primitive,
+            endian: field.endian,
+            offset: self.offset,
+            scaling: field.scaling,
+            range,
+            unit: field.unit.clone(),
+            description: field.description.clone(),
+            multiplex: Multiplex::None,
+        });
+        self.offset += primitive.byte_len();
+    }
+}
+
+/// A DBC identifier made from `name`: a C identifier of at most 32
+/// characters, with a numeric suffix when the name is already `taken`.
+fn unique_name(name: &str, taken: &mut HashSet<String>) -> String {
+    let base = to_snake_case(name);
+    let base = if base.is_empty() {
+        "_".to_string()
+    } else {
+        base
+    };
+    let mut candidate: String = base.chars().take(MAX_NAME_LEN).collect();
+    let mut counter = 2;
+    while taken.contains(&candidate) {
+        let suffix = format!("_{}", counter);
+        candidate = base
+            .chars()
+            .take(MAX_NAME_LEN - suffix.len())
+            .chain(suffix.chars())
+            .collect();
+        counter += 1;
+    }
+    taken.insert(candidate.clone());
+    candidate
+}
+
+/// The shortest CAN FD frame holding `len` bytes.
+fn fd_frame_len(len: usize) -> usize {
+    [8, 12, 16, 20, 24, 32, 48, 64]
+        .into_iter()
+        .find(|&size| len <= size)
+        .map_or(len, |size| if len <= 8 { len } else { size })
+}
+
+/// A DBC string literal body; DBC has no escapes, so quotes become `'`.
+fn escape(text: &str) -> String {
+    text.replace('"', "'")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(json: &str) -> (Metadata, Vec<MessageDefinition>) {
+        crate::parse_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_signal_layout() {
+        let (metadata, messages) = parse(
+            r#"{ "version": "1.2.0", "packets": { "motor": { "packet_id": 16, "msg_type": "struct",
+                 "msg_desc": "Motor \"A\" state", "fields": {
+                   "enabled": { "type": "bool" },
+                   "speed": { "type": "i16", "endianess": "big", "scale": 0.5, "offset": 10,
+                              "unit": "rpm", "desc": "Shaft speed" },
+                   "limits": { "type": "struct", "fields": {
+                       "current": { "type": "u8", "min": 100, "max": 200 } } },
+                   "temp": { "type": "f32" } } } } }"#,
+        );
+        let dbc = generate(&metadata, &messages, &DbcOptions::default()).unwrap();
+        assert!(dbc.skipped.is_empty());
+        assert!(dbc.content.starts_with("VERSION \"1.2.0\"\n"));
+        assert!(dbc.content.contains(
+            "BO_ 16 motor: 8 Vector__XXX\n SG_ enabled : 0|8@1+ (1,0) [0|1] \"\" Vector__XXX\n SG_ speed : 15|16@0- (0.5,10) [-16374|16393.5] \"rpm\" Vector__XXX\n SG_ limits_current : 24|8@1+ (1,0) [100|200] \"\" Vector__XXX\n SG_ temp : 32|32@1- (1,0) [0|0] \"\" Vector__XXX\n"
+        ));
+        assert!(dbc.content.contains("CM_ BO_ 16 \"Motor 'A' state\";\n"));
+        assert!(dbc.content.contains("CM_ SG_ 16 speed \"Shaft speed\";\n"));
+        assert!(dbc.content.contains("SIG_VALTYPE_ 16 temp : 1;\n"));
+        assert!(!dbc.content.contains("VFrameFormat"));
+    }
+
+    #[test]
+    fn test_messages_without_a_frame_are_skipped() {
+        let (metadata, messages) = parse(
+            r#"{ "packets": {
+                 "wide": { "packet_id": 1, "msg_type": "u32", "array": true, "max_length": 4 },
+                 "tail": { "packet_id": 2, "msg_type": "struct", "fields": {
+                     "head": { "type": "u8" },
+                     "bytes": { "type": "u8", "array": true, "max_length": 3 } } },
+                 "gap": { "packet_id": 3, "msg_type": "struct", "fields": {
+                     "bytes": { "type": "u8", "array": true, "max_length": 3 },
+                     "crc": { "type": "u8" } } } } }"#,
+        );
+        let dbc = generate(&metadata, &messages, &DbcOptions::default()).unwrap();
+        let reasons: Vec<(&str, &str)> = dbc
+            .skipped
+            .iter()
+            .map(|s| (s.name.as_str(), s.reason.as_str()))
+            .collect();
+        assert_eq!(
+            reasons,
+            [
+                ("wide", "up to 16 bytes, more than the 8-byte CAN payload"),
+                (
+                    "gap",
+                    "values after a variable-length array have no fixed position"
+                ),
+            ]
+        );
+        assert!(dbc.content.contains(
+            "BO_ 2 tail: 4 Vector__XXX\n SG_ head : 0|8@1+ (1,0) [0|255] \"\" Vector__XXX\n SG_ bytes_0 : 8|8@1+"
+        ));
+        assert!(dbc.content.contains(" SG_ bytes_2 : 24|8@1+"));
+
+        let fd = generate(&metadata, &messages, &DbcOptions { can_fd: true }).unwrap();
+        assert_eq!(fd.skipped.len(), 1);
+        assert!(fd.content.contains("BO_ 1 wide: 16 Vector__XXX\n"));
+        assert!(fd.content.contains("BA_ \"VFrameFormat\" BO_ 1 14;\n"));
+    }
+
+    #[test]
+    fn test_names_are_dbc_identifiers() {
+        let mut taken = HashSet::new();
+        assert_eq!(unique_name("motor-speed", &mut taken), "motor_speed");
+        assert_eq!(unique_name("motor speed", &mut taken), "motor_speed_2");
+        assert_eq!(unique_name("2nd", &mut taken), "_2nd");
+        let long = "a".repeat(40);
+        assert_eq!(unique_name(&long, &mut taken), "a".repeat(32));
+        assert_eq!(
+            unique_name(&long, &mut taken),
+            format!("{}_2", "a".repeat(30))
+        );
+    }
+
+    #[test]
+    fn test_fd_frame_lengths() {
+        assert_eq!(fd_frame_len(5), 5);
+        assert_eq!(fd_frame_len(9), 12);
+        assert_eq!(fd_frame_len(33), 48);
+        assert_eq!(fd_frame_len(64), 64);
+    }
+}
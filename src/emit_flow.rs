@@ -0,0 +1,255 @@
+//! Message flow diagrams (Mermaid `flowchart` or Graphviz DOT).
+//!
+//! The diagram has a node for the server, one per client ID and a single
+//! "All clients" node for broadcast messages (`target_client_id` -1). Each
+//! message is an edge labeled with its name and packet ID, drawn from the
+//! side that sends it: the server for `pub`, the client for `sub`.
+//! Deprecated messages are dashed. Nodes and edges are sorted, so the
+//! output only changes with the messages and diffs cleanly.
+
+use std::collections::BTreeSet;
+use std::fmt::Write as FmtWrite;
+
+use crate::fingerprint::GENERATOR;
+use crate::{IdlError, MessageDefinition, Metadata, RequestType, ir_fingerprint};
+
+/// Syntax of the diagram.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FlowFormat {
+    /// Mermaid `flowchart`, rendered by GitHub and GitLab
+    Mermaid,
+    /// Graphviz DOT, for `dot -Tsvg`
+    Dot,
+}
+
+impl FlowFormat {
+    /// File extension of the diagram.
+    pub fn extension(self) -> &'static str {
+        match self {
+            FlowFormat::Mermaid => "mmd",
+            FlowFormat::Dot => "dot",
+        }
+    }
+}
+
+/// A box of the diagram, in drawing order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Node {
+    Server,
+    AllClients,
+    Client(i32),
+}
+
+impl Node {
+    fn of_target(target_client_id: i32) -> Self {
+        match target_client_id {
+            -1 => Node::AllClients,
+            id => Node::Client(id),
+        }
+    }
+
+    /// Identifier of the node in the diagram source.
+    fn id(self) -> String {
+        match self {
+            Node::Server => "server".to_string(),
+            Node::AllClients => "all_clients".to_string(),
+            Node::Client(id) if id < 0 => format!("client_m{}", id.unsigned_abs()),
+            Node::Client(id) => format!("client_{}", id),
+        }
+    }
+
+    /// Caption of the node, with the device name from `devices` if any.
+    fn label(self, metadata: &Metadata) -> String {
+        let device = |role: &str, id: Option<u32>| {
+            metadata
+                .devices
+                .iter()
+                .find(|device| {
+                    device.role.eq_ignore_ascii_case(role) && (id.is_none() || device.id == id)
+                })
+                .map(|device| device.name.as_str())
+        };
+        let (caption, device) = match self {
+            Node::Server => ("Server".to_string(), device("server", None)),
+            Node::AllClients => ("All clients".to_string(), None),
+            Node::Client(id) => (
+                format!("Client {}", id),
+                u32::try_from(id)
+                    .ok()
+                    .and_then(|id| device("client", Some(id))),
+            ),
+        };
+        match device {
+            Some(name) => format!("{} ({})", caption, name),
+            None => caption,
+        }
+    }
+}
+
+/// One message drawn as an arrow.
+struct Edge<'a> {
+    from: Node,
+    to: Node,
+    msg: &'a MessageDefinition,
+}
+
+impl Edge<'_> {
+    fn label(&self) -> String {
+        format!("{} ({})", self.msg.name, self.msg.packet_id)
+    }
+}
+
+/// Generates the flow diagram of `messages` in `format`.
+pub fn generate(
+    metadata: &Metadata,
+    messages: &[MessageDefinition],
+    format: FlowFormat,
+) -> Result<String, IdlError> {
+    let mut edges: Vec<Edge> = messages
+        .iter()
+        .map(|msg| {
+            let client = Node::of_target(msg.target_client_id);
+            let (from, to) = match msg.request_type {
+                RequestType::Pub => (Node::Server, client),
+                RequestType::Sub => (client, Node::Server),
+            };
+            Edge { from, to, msg }
+        })
+        .collect();
+    edges.sort_by(|a, b| (a.msg.packet_id, &a.msg.name).cmp(&(b.msg.packet_id, &b.msg.name)));
+    let nodes: BTreeSet<Node> = std::iter::once(Node::Server)
+        .chain(edges.iter().flat_map(|edge| [edge.from, edge.to]))
+        .collect();
+    let fingerprint = ir_fingerprint(metadata, messages);
+
+    let mut out = String::new();
+    match format {
+        FlowFormat::Mermaid => {
+            writeln!(out, "%% Auto-generated by {}.", GENERATOR).unwrap();
+            writeln!(out, "%% IR fingerprint: {}", fingerprint).unwrap();
+            writeln!(out, "flowchart LR").unwrap();
+            for node in &nodes {
+                writeln!(
+                    out,
+                    "    {}[\"{}\"]",
+                    node.id(),
+                    mermaid_text(&node.label(metadata))
+                )
+                .unwrap();
+            }
+            for edge in &edges {
+                let arrow = if edge.msg.deprecated.is_some() {
+                    "-.->"
+                } else {
+                    "-->"
+                };
+                writeln!(
+                    out,
+                    "    {} {}|\"{}\"| {}",
+                    edge.from.id(),
+                    arrow,
+                    mermaid_text(&edge.label()),
+                    edge.to.id()
+                )
+                .unwrap();
+            }
+        }
+        FlowFormat::Dot => {
+            writeln!(out, "// Auto-generated by {}.", GENERATOR).unwrap();
+            writeln!(out, "// IR fingerprint: {}", fingerprint).unwrap();
+            writeln!(out, "digraph messages {{").unwrap();
+            writeln!(out, "    rankdir=LR;").unwrap();
+            writeln!(out, "    node [shape=box];").unwrap();
+            for node in &nodes {
+                writeln!(
+                    out,
+                    "    {} [label=\"{}\"];",
+                    node.id(),
+                    dot_text(&node.label(metadata))
+                )
+                .unwrap();
+            }
+            for edge in &edges {
+                let style = if edge.msg.deprecated.is_some() {
+                    ", style=dashed"
+                } else {
+                    ""
+                };
+                writeln!(
+                    out,
+                    "    {} -> {} [label=\"{}\"{}];",
+                    edge.from.id(),
+                    edge.to.id(),
+                    dot_text(&edge.label()),
+                    style
+                )
+                .unwrap();
+            }
+            writeln!(out, "}}").unwrap();
+        }
+    }
+    Ok(out)
+}
+
+/// Text inside a quoted Mermaid label.
+fn mermaid_text(text: &str) -> String {
+    text.replace('"', "#quot;")
+}
+
+/// Text inside a quoted DOT string.
+fn dot_text(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUT: &str = r#"{
+        "devices": {
+            "hub": { "role": "server" },
+            "probe": { "role": "client", "id": 3 }
+        },
+        "packets": {
+            "reset": { "packet_id": 9, "msg_type": "u8", "request_type": "sub",
+                       "target_client_id": 3 },
+            "ping": { "packet_id": 1, "msg_type": "u8" },
+            "temp": { "packet_id": 4, "msg_type": "f32", "target_client_id": 3 },
+            "old": { "packet_id": 5, "msg_type": "u8", "target_client_id": 2,
+                     "deprecated": true }
+        }
+    }"#;
+
+    #[test]
+    fn test_mermaid_flowchart() {
+        let (metadata, messages) = crate::parse_str(INPUT).unwrap();
+        let diagram = generate(&metadata, &messages, FlowFormat::Mermaid).unwrap();
+        let body = diagram.split_once("flowchart LR\n").unwrap().1;
+        assert_eq!(
+            body,
+            concat!(
+                "    server[\"Server (hub)\"]\n",
+                "    all_clients[\"All clients\"]\n",
+                "    client_2[\"Client 2\"]\n",
+                "    client_3[\"Client 3 (probe)\"]\n",
+                "    server -->|\"ping (1)\"| all_clients\n",
+                "    server -->|\"temp (4)\"| client_3\n",
+                "    server -.->|\"old (5)\"| client_2\n",
+                "    client_3 -->|\"reset (9)\"| server\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_dot_digraph() {
+        let (metadata, messages) = crate::parse_str(INPUT).unwrap();
+        let diagram = generate(&metadata, &messages, FlowFormat::Dot).unwrap();
+        assert!(diagram.contains("digraph messages {\n    rankdir=LR;\n"));
+        assert!(diagram.contains("    client_3 [label=\"Client 3 (probe)\"];\n"));
+        assert!(diagram.contains("    server -> all_clients [label=\"ping (1)\"];\n"));
+        assert!(diagram.contains("    server -> client_2 [label=\"old (5)\", style=dashed];\n"));
+        assert!(diagram.contains("    client_3 -> server [label=\"reset (9)\"];\n"));
+        assert!(diagram.ends_with("}\n"));
+        assert_eq!(dot_text(r#"a "b" \c"#), r#"a \"b\" \\c"#);
+    }
+}
@@ -0,0 +1,477 @@
+//! Canonical JSON export of the parsed IR.
+//!
+//! The export is itself a valid input document: parsing it again yields the
+//! same IR. Unlike hand-written input it spells every resolved default out
+//! (`endianess`, `request_type`, `target_client_id`, ...), uses one spelling
+//! per key, lists the messages in packet ID order and adds the computed
+//! payload sizes. Keys always come in the same order, so the output is
+//! stable under `diff`.
+//!
+//! # Schema
+//! The top level holds `ir_version` ([`IR_VERSION`]), the metadata keys
+//! that are set, every shared type the messages use under `types`, and the
+//! messages under `packets`. Each message has `packet_id`, `msg_type`,
+//! `group` when set, `request_type`, `target_client_id`, `reserved_ok`, `deprecated`,
+//! `response` and `msg_version` when set, the keys of its shape (`tag`,
+//! `endianess` and `variants` for a union), and `min_size`/`max_size` in
+//! bytes. The parser accepts the size keys and ignores them; they are
+//! recomputed on load.
+
+use serde_json::{Map, Value, json};
+
+use crate::raw::parse_int_literal;
+use crate::{
+    ConstValue, IdlError, MessageBody, MessageDefinition, Metadata, Scaling, StructFieldType,
+    StructSpec, UnionSpec, ValueRange, message_body_max_size, message_body_min_size,
+};
+
+/// Version of the export format, bumped on incompatible schema changes.
+pub const IR_VERSION: u64 = 1;
+
+/// Generates the canonical JSON of the IR, pretty-printed with a trailing
+/// newline.
+pub fn generate(metadata: &Metadata, messages: &[MessageDefinition]) -> Result<String, IdlError> {
+    let mut out = serde_json::to_string_pretty(&export(metadata, messages))
+        .expect("serializing a JSON value cannot fail");
+    out.push('\n');
+    Ok(out)
+}
+
+/// The IR as a JSON document in the input format.
+pub fn export(metadata: &Metadata, messages: &[MessageDefinition]) -> Value {
+    let mut doc = Map::new();
+    doc.insert("ir_version".into(), IR_VERSION.into());
+    if let Some(version) = &metadata.version {
+        doc.insert("version".into(), version.as_str().into());
+    }
+    if let Some(max_address) = metadata.max_address {
+        doc.insert("max_address".into(), max_address.into());
+    }
+    // Only a widened ID field, so existing exports stay the same
+    if metadata.packet_id_width != Metadata::default().packet_id_width {
+        doc.insert("packet_id_width".into(), metadata.packet_id_width.into());
+    }
+    if let Some(endian) = metadata.default_endianness {
+        doc.insert("default_endianness".into(), endian.name().into());
+    }
+    doc.insert(
+        "max_payload_bytes".into(),
+        metadata.max_payload_bytes.into(),
+    );
+    doc.insert("max_array_length".into(), metadata.max_array_length.into());
+    // Only a raised or lowered limit, so existing exports stay the same
+    if metadata.max_nesting_depth != Metadata::default().max_nesting_depth {
+        doc.insert(
+            "max_nesting_depth".into(),
+            metadata.max_nesting_depth.into(),
+        );
+    }
+    if !metadata.devices.is_empty() {
+        let mut devices = Map::new();
+        for device in &metadata.devices {
+            let mut entry = Map::new();
+            entry.insert("role".into(), device.role.as_str().into());
+            if let Some(id) = device.id {
+                entry.insert("id".into(), id.into());
+            }
+            if let Some(description) = &device.description {
+                entry.insert("description".into(), description.as_str().into());
+            }
+            devices.insert(device.name.clone(), entry.into());
+        }
+        doc.insert("devices".into(), devices.into());
+    }
+    if !metadata.reserved_ids.is_empty() {
+        let ids = metadata
+            .reserved_ids
+            .iter()
+            .map(|&(first, last)| id_range(first, last))
+            .collect();
+        doc.insert("reserved_ids".into(), Value::Array(ids));
+    }
+    if !metadata.command_groups.is_empty() {
+        let groups = metadata
+            .command_groups
+            .iter()
+            .map(|group| json!({ "name": group.name, "range": [group.first, group.last] }))
+            .collect();
+        doc.insert("command_groups".into(), Value::Array(groups));
+    }
+    if !metadata.constants.is_empty() {
+        let constants = metadata
+            .constants
+            .iter()
+            .map(|(name, value)| {
+                let value = match value {
+                    ConstValue::Int(value) => (*value).into(),
+                    ConstValue::Float(value) => (*value).into(),
+                    // Kept a string on the way back in
+                    ConstValue::Str(value) if parse_int_literal(value).is_ok() => {
+                        json!({ "string": value })
+                    }
+                    ConstValue::Str(value) => value.as_str().into(),
+                };
+                (name.clone(), value)
+            })
+            .collect();
+        doc.insert("constants".into(), Value::Object(constants));
+    }
+
+    // Types of included documents are not in `metadata.types`, and the
+    // export has no includes, so every type in use is written out
+    let mut shared: Vec<&StructSpec> = Vec::new();
+    for spec in &metadata.types {
+        collect_shared(spec, &mut shared);
+    }
+    for msg in messages {
+        match &msg.body {
+            MessageBody::Struct(spec) => collect_shared(spec, &mut shared),
+            MessageBody::Union(spec) => {
+                for variant in &spec.variants {
+                    collect_shared(&variant.spec, &mut shared);
+                }
+            }
+            MessageBody::Scalar(_) | MessageBody::Array(_) => {}
+        }
+    }
+    if !shared.is_empty() {
+        let types = shared
+            .into_iter()
+            .map(|spec| {
+                let name = spec.shared.clone().unwrap_or_default();
+                (name, json!({ "type": "struct", "fields": fields(spec) }))
+            })
+            .collect();
+        doc.insert("types".into(), Value::Object(types));
+    }
+
+    let mut sorted: Vec<&MessageDefinition> = messages.iter().collect();
+    sorted.sort_by_key(|msg| msg.packet_id);
+    let packets = sorted
+        .into_iter()
+        .map(|msg| (msg.name.clone(), message(msg)))
+        .collect();
+    doc.insert("packets".into(), Value::Object(packets));
+    Value::Object(doc)
+}
+
+/// Adds the shared types within `spec` and then `spec` itself, if shared,
+/// so every type comes after the types it uses.
+pub(crate) fn collect_shared<'a>(spec: &'a StructSpec, out: &mut Vec<&'a StructSpec>) {
+    for field in &spec.fields {
+        if let StructFieldType::Nested(nested) = &field.field_type {
+            collect_shared(nested, out);
+        }
+    }
+    if spec.shared.is_some() && !out.iter().any(|known| known.shared == spec.shared) {
+        out.push(spec);
+    }
+}
+
+fn message(msg: &MessageDefinition) -> Value {
+    let mut out = Map::new();
+    out.insert("packet_id".into(), msg.packet_id.into());
+    let msg_type = match &msg.body {
+        MessageBody::Scalar(spec) => spec.primitive.name(),
+        MessageBody::Array(spec) => spec.primitive.name(),
+        MessageBody::Struct(spec) => spec.shared.as_deref().unwrap_or("struct"),
+        MessageBody::Union(_) => "union",
+    };
+    out.insert("msg_type".into(), msg_type.into());
+    if let Some(description) = &msg.description {
+        out.insert("msg_desc".into(), description.as_str().into());
+    }
+    if let Some(group) = &msg.group {
+        out.insert("group".into(), group.as_str().into());
+    }
+    out.insert("request_type".into(), msg.request_type.name().into());
+    out.insert("target_client_id".into(), msg.target_client_id.into());
+    out.insert("reserved_ok".into(), msg.reserved_ok.into());
+    let deprecated = match &msg.deprecated {
+        None => false.into(),
+        Some(deprecation) => match &deprecation.reason {
+            Some(reason) => reason.as_str().into(),
+            None => true.into(),
+        },
+    };
+    out.insert("deprecated".into(), deprecated);
+    if let Some(response) = &msg.response {
+        out.insert("response".into(), response.as_str().into());
+    }
+    if let Some(msg_version) = msg.msg_version {
+        out.insert("msg_version".into(), msg_version.into());
+    }
+
+    match &msg.body {
+        MessageBody::Scalar(spec) => {
+            out.insert("array".into(), false.into());
+            out.insert("endianess".into(), spec.endian.name().into());
+            value_keys(&mut out, spec.scaling, spec.range);
+            doc_keys(&mut out, &spec.description, &spec.unit);
+        }
+        MessageBody::Array(spec) => {
+            out.insert("array".into(), true.into());
+            out.insert("max_length".into(), spec.max_length.into());
+            if let Some(sector_bytes) = spec.sector_bytes {
+                out.insert("sector_bytes".into(), sector_bytes.into());
+            }
+            if spec.string_semantics {
+                out.insert("string_semantics".into(), true.into());
+            }
+            out.insert("endianess".into(), spec.endian.name().into());
+            value_keys(&mut out, spec.scaling, None);
+            doc_keys(&mut out, &spec.description, &spec.unit);
+        }
+        // A shared type brings its own fields
+        MessageBody::Struct(spec) if spec.shared.is_some() => {}
+        MessageBody::Struct(spec) => {
+            out.insert("fields".into(), fields(spec));
+        }
+        MessageBody::Union(spec) => {
+            out.insert("tag".into(), spec.tag.name().into());
+            out.insert("endianess".into(), spec.endian.name().into());
+            out.insert("variants".into(), variants(spec));
+        }
+    }
+    out.insert("min_size".into(), message_body_min_size(&msg.body).into());
+    out.insert("max_size".into(), message_body_max_size(&msg.body).into());
+    Value::Object(out)
+}
+
+fn fields(spec: &StructSpec) -> Value {
+    let mut out = Map::new();
+    // Reserved gaps come back from the `at` of the field after them
+    for field in spec.fields.iter().filter(|field| !field.reserved) {
+        let mut entry = Map::new();
+        match &field.field_type {
+            StructFieldType::Primitive(primitive) => match field.timestamp {
+                Some(resolution) => {
+                    entry.insert("type".into(), "timestamp".into());
+                    entry.insert("storage".into(), primitive.name().into());
+                    entry.insert("resolution".into(), resolution.name().into());
+                }
+                None => {
+                    entry.insert("type".into(), primitive.name().into());
+                    entry.insert("array".into(), false.into());
+                }
+            },
+            StructFieldType::Array(array) => {
+                entry.insert("type".into(), array.primitive.name().into());
+                entry.insert("array".into(), true.into());
+                let key = if array.fixed {
+                    "fixed_length"
+                } else {
+                    "max_length"
+                };
+                entry.insert(key.into(), array.max_length.into());
+                if array.string_semantics {
+                    entry.insert("string_semantics".into(), true.into());
+                }
+            }
+            StructFieldType::Bytes(length) => {
+                entry.insert("type".into(), "bytes".into());
+                entry.insert("length".into(), (*length).into());
+            }
+            StructFieldType::Nested(nested) => match &nested.shared {
+                Some(name) => {
+                    entry.insert("type".into(), name.as_str().into());
+                }
+                None => {
+                    entry.insert("type".into(), "struct".into());
+                }
+            },
+        }
+        // Nested structs take the byte order of their own fields
+        if !matches!(field.field_type, StructFieldType::Nested(_)) {
+            entry.insert("endianess".into(), field.endian.name().into());
+        }
+        value_keys(&mut entry, field.scaling, field.range);
+        doc_keys(&mut entry, &field.description, &field.unit);
+        if let Some(at) = field.at {
+            entry.insert("at".into(), at.into());
+        }
+        if let Some(since_version) = field.since_version {
+            entry.insert("since_version".into(), since_version.into());
+        }
+        if let StructFieldType::Nested(nested) = &field.field_type
+            && nested.shared.is_none()
+        {
+            entry.insert("fields".into(), fields(nested));
+        }
+        out.insert(field.name.clone(), entry.into());
+    }
+    Value::Object(out)
+}
+
+/// The `variants` object of a union, keyed by tag value.
+fn variants(spec: &UnionSpec) -> Value {
+    let mut out = Map::new();
+    for variant in &spec.variants {
+        let mut entry = Map::new();
+        entry.insert("name".into(), variant.name.as_str().into());
+        match &variant.spec.shared {
+            Some(name) => {
+                entry.insert("type".into(), name.as_str().into());
+            }
+            None => {
+                entry.insert("fields".into(), fields(&variant.spec));
+            }
+        }
+        if let Some(description) = &variant.description {
+            entry.insert("desc".into(), description.as_str().into());
+        }
+        out.insert(variant.tag.to_string(), entry.into());
+    }
+    Value::Object(out)
+}
+
+/// Adds `scale`/`offset` and the `min`/`max` bounds that are set.
+fn value_keys(out: &mut Map<String, Value>, scaling: Option<Scaling>, range: Option<ValueRange>) {
+    if let Some(scaling) = scaling {
+        out.insert("scale".into(), scaling.scale.into());
+        out.insert("offset".into(), scaling.offset.into());
+    }
+    let range = range.unwrap_or_default();
+    for (key, bound) in [("min", range.min), ("max", range.max)] {
+        if let Some(bound) = bound {
+            out.insert(key.into(), bound.to_json());
+        }
+    }
+}
+
+fn doc_keys(out: &mut Map<String, Value>, description: &Option<String>, unit: &Option<String>) {
+    if let Some(description) = description {
+        out.insert("desc".into(), description.as_str().into());
+    }
+    if let Some(unit) = unit {
+        out.insert("unit".into(), unit.as_str().into());
+    }
+}
+
+/// A `reserved_ids` entry: the ID, or `[first, last]` for a range.
+fn id_range(first: u32, last: u32) -> Value {
+    if first == last {
+        first.into()
+    } else {
+        json!([first, last])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUT: &str = r#"{
+        "version": "2.1",
+        "max_address": 255,
+        "devices": { "board": { "id": 1, "description": "main board" }, "pc": { "role": "server" } },
+        "reserved_ids": [0, [250, 255]],
+        "command_groups": [{ "name": "Motion", "range": [10, 19] }],
+        "constants": { "MAX_SPEED": 1500, "GAIN": 0.5, "NAME": "rover", "REV": { "string": "0x0102" } },
+        "types": {
+            "Vec2": { "type": "struct", "fields": {
+                "x": { "type": "f32", "unit": "m" }, "y": { "type": "f32" } } },
+            "Pose": { "type": "struct", "fields": {
+                "pos": { "type": "Vec2" }, "yaw": { "type": "i16", "scale": 0.01 } } }
+        },
+        "packets": {
+            "pose": { "packet_id": 12, "msg_type": "Pose", "request_type": "sub" },
+            "speed": { "packet_id": 10, "msg_type": "i16", "endianness": "BE", "min": -1500,
+                       "max": 1500, "unit": "rpm", "msg_desc": "Target speed" },
+            "log": { "packet_id": 11, "msg_type": "char", "array": true, "max_length": 32,
+                     "deprecated": "use events", "target_client_id": 1 },
+            "status": { "packet_id": 13, "msg_type": "struct", "fields": {
+                "flags": { "msg_type": "u8", "msg_desc": "bit set" },
+                "stamp": { "type": "timestamp", "storage": "u32", "resolution": "ms" },
+                "home": { "type": "Vec2" },
+                "inner": { "type": "struct", "fields": {
+                    "temp": { "type": "float64", "min": -40.0, "max": 125.5 } } },
+                "ranges": { "type": "u16", "array": true, "max_length": 4, "endianess": "big" } } },
+            "boot": { "packet_id": 250, "msg_type": "bool", "reserved_ok": true }
+        }
+    }"#;
+
+    #[test]
+    fn test_export_spells_out_defaults() {
+        let (metadata, messages) = crate::parse_str(INPUT).unwrap();
+        let ir = export(&metadata, &messages);
+        let keys: Vec<&str> = ir.as_object().unwrap().keys().map(String::as_str).collect();
+        assert_eq!(
+            keys,
+            [
+                "ir_version",
+                "version",
+                "max_address",
+                "max_payload_bytes",
+                "max_array_length",
+                "devices",
+                "reserved_ids",
+                "command_groups",
+                "constants",
+                "types",
+                "packets"
+            ]
+        );
+        assert_eq!(ir["devices"]["board"]["role"], "client");
+        assert_eq!(ir["reserved_ids"], json!([0, [250, 255]]));
+        let packets: Vec<&String> = ir["packets"].as_object().unwrap().keys().collect();
+        assert_eq!(packets, ["speed", "log", "pose", "status", "boot"]);
+        assert_eq!(
+            ir["packets"]["speed"],
+            json!({
+                "packet_id": 10, "msg_type": "int16", "msg_desc": "Target speed",
+                "request_type": "pub", "target_client_id": -1, "reserved_ok": false,
+                "deprecated": false, "array": false, "endianess": "big",
+                "min": -1500, "max": 1500, "unit": "rpm", "min_size": 2, "max_size": 2
+            })
+        );
+        assert_eq!(ir["packets"]["log"]["deprecated"], "use events");
+        assert_eq!(ir["packets"]["pose"]["msg_type"], "Pose");
+        assert_eq!(
+            ir["packets"]["status"]["fields"]["flags"]["desc"],
+            "bit set"
+        );
+        assert_eq!(ir["packets"]["status"]["fields"]["home"]["type"], "Vec2");
+        assert_eq!(
+            ir["packets"]["status"]["fields"]["stamp"],
+            json!({
+                "type": "timestamp", "storage": "uint32", "resolution": "ms",
+                "endianess": "little"
+            })
+        );
+        assert_eq!(ir["packets"]["status"]["min_size"], 21);
+        assert_eq!(ir["packets"]["status"]["max_size"], 29);
+        let types: Vec<&String> = ir["types"].as_object().unwrap().keys().collect();
+        assert_eq!(types, ["Vec2", "Pose"]);
+    }
+
+    #[test]
+    fn test_export_round_trips() {
+        let (metadata, messages) = crate::parse_str(INPUT).unwrap();
+        let exported = generate(&metadata, &messages).unwrap();
+        let options = crate::ParseOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let json: Value = serde_json::from_str(&exported).unwrap();
+        let (metadata2, messages2) =
+            crate::parse_messages_with_options(json.as_object().unwrap(), &options).unwrap();
+        assert_eq!(generate(&metadata2, &messages2).unwrap(), exported);
+        assert_eq!(
+            crate::ir_fingerprint(&metadata2, &messages2),
+            crate::ir_fingerprint(&metadata, &messages)
+        );
+    }
+
+    #[test]
+    fn test_newer_ir_version_is_rejected() {
+        let err = crate::parse_str(
+            r#"{ "ir_version": 2, "packets": { "ping": { "packet_id": 1, "msg_type": "u8" } } }"#,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "/ir_version: ir_version 2 is newer than the supported version 1"
+        );
+    }
+}
@@ -0,0 +1,601 @@
+//! Kaitai Struct (`.ksy`) generator for message definitions.
+//!
+//! The top-level type reads one packet: the packet ID followed by the
+//! payload, which is parsed with the type of the message that ID belongs
+//! to. The ID is one byte, or two in the `default_endianness` with
+//! `packet_id_width` 16. Each message type can also parse a bare payload on its own. A union
+//! switches on its tag to one type per variant, which reads the rest of
+//! the payload.
+//!
+//! Variable-length arrays carry no length prefix on the wire; the decoders
+//! hand the spare bytes to them in order (see `codec`). The `.ksy` does the
+//! same: every message type computes an element count per array from
+//! `_io.size` as a `len_*` instance, and passes the counts to the nested
+//! types holding the arrays as parameters.
+
+use std::collections::HashMap;
+use std::fmt::Write as FmtWrite;
+
+use crate::emit_c::struct_min_byte_len;
+use crate::emit_ir::collect_shared;
+use crate::fingerprint::GENERATOR;
+use crate::{
+    Bound, Endian, IdlError, MessageBody, MessageDefinition, Metadata, PrimitiveType,
+    StructFieldType, StructSpec, ValueRange, ir_fingerprint, to_snake_case,
+};
+
+/// Generates the `.ksy` of `messages`; `id` becomes `meta/id` and should
+/// match the file name (it is normalized with [`ident`]).
+pub fn generate(
+    metadata: &Metadata,
+    messages: &[MessageDefinition],
+    id: &str,
+) -> Result<String, IdlError> {
+    let mut out = String::new();
+    writeln!(out, "# Auto-generated by {}.", GENERATOR).unwrap();
+    writeln!(
+        out,
+        "# IR fingerprint: {}",
+        ir_fingerprint(metadata, messages)
+    )
+    .unwrap();
+    writeln!(out, "meta:").unwrap();
+    writeln!(out, "  id: {}", ident(id)).unwrap();
+    if let Some(version) = &metadata.version {
+        writeln!(out, "  title: {}", quote(&format!("Protocol {}", version))).unwrap();
+    }
+    writeln!(out, "  ks-version: 0.9").unwrap();
+    let id_name = if metadata.packet_id_width > 8 {
+        "the 16-bit packet ID"
+    } else {
+        "the packet ID byte"
+    };
+    writeln!(
+        out,
+        "doc: {}",
+        quote(&format!("One packet: {} followed by the payload.", id_name))
+    )
+    .unwrap();
+
+    let mut sorted: Vec<&MessageDefinition> = messages.iter().collect();
+    sorted.sort_by_key(|msg| msg.packet_id);
+    let names = TypeNames::new(&sorted);
+
+    writeln!(out, "seq:").unwrap();
+    writeln!(out, "  - id: packet_id").unwrap();
+    let id_type = if metadata.packet_id_width > 8 {
+        primitive_type(
+            PrimitiveType::Uint16,
+            metadata.default_endianness.unwrap_or_default(),
+        )
+    } else {
+        primitive_type(PrimitiveType::Uint8, Endian::Little)
+    };
+    writeln!(out, "    type: {}", id_type).unwrap();
+    writeln!(out, "    enum: packet_id").unwrap();
+    writeln!(out, "  - id: body").unwrap();
+    writeln!(out, "    size-eos: true").unwrap();
+    writeln!(out, "    type:").unwrap();
+    writeln!(out, "      switch-on: packet_id").unwrap();
+    writeln!(out, "      cases:").unwrap();
+    for msg in &sorted {
+        let name = names.message(&msg.name);
+        writeln!(out, "        'packet_id::{}': {}", name, name).unwrap();
+    }
+
+    let mut types = Vec::new();
+    for msg in &sorted {
+        let name = names.message(&msg.name);
+        match &msg.body {
+            MessageBody::Scalar(spec) => types.push(KType {
+                name,
+                doc: msg.description.clone(),
+                params: Vec::new(),
+                seq: vec![primitive_entry(
+                    "value".to_string(),
+                    spec.primitive,
+                    spec.endian,
+                    spec.range,
+                    doc_text(&spec.description, &spec.unit),
+                )],
+                instances: Vec::new(),
+            }),
+            MessageBody::Array(spec) => {
+                let mut entry = Entry {
+                    id: "data".to_string(),
+                    keys: Vec::new(),
+                    doc: doc_text(&spec.description, &spec.unit),
+                };
+                if spec.primitive == PrimitiveType::Char {
+                    entry.keys.push(("type", "str".to_string()));
+                    entry.keys.push(("size-eos", "true".to_string()));
+                    entry.keys.push(("encoding", "ASCII".to_string()));
+                } else {
+                    entry
+                        .keys
+                        .push(("type", primitive_type(spec.primitive, spec.endian)));
+                    entry.keys.push(("repeat", "eos".to_string()));
+                }
+                types.push(KType {
+                    name,
+                    doc: msg.description.clone(),
+                    params: Vec::new(),
+                    seq: vec![entry],
+                    instances: Vec::new(),
+                });
+            }
+            // The message type holds the fields even for a shared type,
+            // because the counts of its arrays are computed there
+            MessageBody::Struct(spec) => struct_types(
+                spec,
+                name,
+                msg.description.clone(),
+                true,
+                &names,
+                &mut types,
+            ),
+            // The tag picks the variant type, which reads the rest of the
+            // payload as a root type of its own
+            MessageBody::Union(spec) => {
+                let cases: Vec<String> = spec
+                    .variants
+                    .iter()
+                    .map(|variant| format!("{}: {}_{}", variant.tag, name, ident(&variant.name)))
+                    .collect();
+                let seq = vec![
+                    primitive_entry("tag".to_string(), spec.tag, spec.endian, None, None),
+                    Entry {
+                        id: "variant".to_string(),
+                        keys: vec![
+                            ("size-eos", "true".to_string()),
+                            (
+                                "type",
+                                format!("{{ switch-on: tag, cases: {{ {} }} }}", cases.join(", ")),
+                            ),
+                        ],
+                        doc: None,
+                    },
+                ];
+                let mut variant_types = Vec::new();
+                for variant in &spec.variants {
+                    struct_types(
+                        &variant.spec,
+                        format!("{}_{}", name, ident(&variant.name)),
+                        variant.description.clone(),
+                        true,
+                        &names,
+                        &mut variant_types,
+                    );
+                }
+                types.push(KType {
+                    name,
+                    doc: msg.description.clone(),
+                    params: Vec::new(),
+                    seq,
+                    instances: Vec::new(),
+                });
+                types.extend(variant_types);
+            }
+        }
+    }
+    for spec in &names.shared_specs {
+        let name = names.shared(spec);
+        struct_types(spec, name, None, false, &names, &mut types);
+    }
+
+    if !types.is_empty() {
+        writeln!(out, "types:").unwrap();
+        for ktype in &types {
+            ktype.write(&mut out);
+        }
+    }
+    writeln!(out, "enums:").unwrap();
+    writeln!(out, "  packet_id:").unwrap();
+    for msg in &sorted {
+        writeln!(out, "    {}: {}", msg.packet_id, names.message(&msg.name)).unwrap();
+    }
+    Ok(out)
+}
+
+/// Kaitai identifiers of the message and shared types; shared types whose
+/// name a message already has get a `_type` suffix.
+struct TypeNames<'a> {
+    messages: HashMap<&'a str, String>,
+    shared: HashMap<&'a str, String>,
+    /// Every shared type in use, each after the types it contains
+    shared_specs: Vec<&'a StructSpec>,
+}
+
+impl<'a> TypeNames<'a> {
+    fn new(messages: &[&'a MessageDefinition]) -> Self {
+        let mut names = TypeNames {
+            messages: HashMap::new(),
+            shared: HashMap::new(),
+            shared_specs: Vec::new(),
+        };
+        for msg in messages {
+            names.messages.insert(msg.name.as_str(), ident(&msg.name));
+            // A shared type as a whole message is written out as the message
+            // type, so only nested uses need a type of their own
+            let specs: Vec<&StructSpec> = match &msg.body {
+                MessageBody::Struct(spec) => vec![spec],
+                MessageBody::Union(spec) => spec.variants.iter().map(|v| &v.spec).collect(),
+                MessageBody::Scalar(_) | MessageBody::Array(_) => Vec::new(),
+            };
+            for spec in specs {
+                for field in &spec.fields {
+                    if let StructFieldType::Nested(nested) = &field.field_type {
+                        collect_shared(nested, &mut names.shared_specs);
+                    }
+                }
+            }
+        }
+        for spec in &names.shared_specs {
+            let shared = spec.shared.as_deref().unwrap_or_default();
+            let mut name = ident(shared);
+            if names.messages.values().any(|taken| *taken == name) {
+                name.push_str("_type");
+            }
+            names.shared.insert(shared, name);
+        }
+        names
+    }
+
+    fn message(&self, name: &str) -> String {
+        self.messages[name].clone()
+    }
+
+    fn shared(&self, spec: &StructSpec) -> String {
+        self.shared[spec.shared.as_deref().unwrap_or_default()].clone()
+    }
+}
+
+/// One entry of `types`.
+struct KType {
+    name: String,
+    doc: Option<String>,
+    params: Vec<String>,
+    seq: Vec<Entry>,
+    /// `(name, expression)` of the computed array lengths
+    instances: Vec<(String, String)>,
+}
+
+impl KType {
+    fn write(&self, out: &mut String) {
+        writeln!(out, "  {}:", self.name).unwrap();
+        if let Some(doc) = &self.doc {
+            writeln!(out, "    doc: {}", quote(doc)).unwrap();
+        }
+        if !self.params.is_empty() {
+            writeln!(out, "    params:").unwrap();
+            for param in &self.params {
+                writeln!(out, "      - id: {}", param).unwrap();
+                writeln!(out, "        type: u4").unwrap();
+            }
+        }
+        writeln!(out, "    seq:").unwrap();
+        for entry in &self.seq {
+            writeln!(out, "      - id: {}", entry.id).unwrap();
+            for (key, value) in &entry.keys {
+                writeln!(out, "        {}: {}", key, value).unwrap();
+            }
+            if let Some(doc) = &entry.doc {
+                writeln!(out, "        doc: {}", quote(doc)).unwrap();
+            }
+        }
+        if !self.instances.is_empty() {
+            writeln!(out, "    instances:").unwrap();
+            for (name, expr) in &self.instances {
+                writeln!(out, "      {}:", name).unwrap();
+                writeln!(out, "        value: {}", quote(expr)).unwrap();
+            }
+        }
+    }
+}
+
+/// One entry of a `seq`. Values in `keys` are written as given.
+struct Entry {
+    id: String,
+    keys: Vec<(&'static str, String)>,
+    doc: Option<String>,
+}
+
+/// Adds the type of `spec` named `name`, followed by the types of its
+/// nested structs defined in place. The `root` type of a message computes
+/// the array lengths; the others take them as parameters.
+fn struct_types(
+    spec: &StructSpec,
+    name: String,
+    doc: Option<String>,
+    root: bool,
+    names: &TypeNames,
+    out: &mut Vec<KType>,
+) {
+    let arrays = variable_arrays(spec);
+    let mut ktype = KType {
+        name,
+        doc,
+        params: Vec::new(),
+        seq: Vec::new(),
+        instances: Vec::new(),
+    };
+    if root {
+        ktype.instances = length_instances(struct_min_byte_len(spec), &arrays);
+    } else {
+        ktype.params = arrays.iter().map(|a| format!("len_{}", a.path)).collect();
+    }
+
+    let mut nested_types = Vec::new();
+    for field in &spec.fields {
+        let id = ident(&field.name);
+        let doc = doc_text(&field.description, &field.unit);
+        let entry = match &field.field_type {
+            StructFieldType::Primitive(primitive) => {
+                primitive_entry(id, *primitive, field.endian, field.range, doc)
+            }
+            StructFieldType::Array(array) => {
+                let count = if array.fixed {
+                    array.max_length.to_string()
+                } else {
+                    format!("len_{}", id)
+                };
+                let keys = if array.primitive == PrimitiveType::Char {
+                    vec![
+                        ("type", "str".to_string()),
+                        ("size", count),
+                        ("encoding", "ASCII".to_string()),
+                    ]
+                } else {
+                    vec![
+                        ("type", primitive_type(array.primitive, field.endian)),
+                        ("repeat", "expr".to_string()),
+                        ("repeat-expr", count),
+                    ]
+                };
+                Entry { id, keys, doc }
+            }
+            StructFieldType::Bytes(length) => Entry {
+                id,
+                keys: vec![("size", length.to_string())],
+                doc,
+            },
+            StructFieldType::Nested(nested) => {
+                let type_name = match &nested.shared {
+                    Some(_) => names.shared(nested),
+                    None => {
+                        let type_name = format!("{}_{}", ktype.name, id);
+                        struct_types(
+                            nested,
+                            type_name.clone(),
+                            None,
+                            false,
+                            names,
+                            &mut nested_types,
+                        );
+                        type_name
+                    }
+                };
+                let args: Vec<String> = variable_arrays(nested)
+                    .iter()
+                    .map(|a| format!("len_{}_{}", id, a.path))
+                    .collect();
+                let type_ref = if args.is_empty() {
+                    type_name
+                } else {
+                    format!("{}({})", type_name, args.join(", "))
+                };
+                Entry {
+                    id,
+                    keys: vec![("type", type_ref)],
+                    doc,
+                }
+            }
+        };
+        ktype.seq.push(entry);
+    }
+    out.push(ktype);
+    out.extend(nested_types);
+}
+
+/// A variable-length array of a struct, nested ones included.
+struct VarArray {
+    /// Field names from the struct down to the array, joined with `_`
+    path: String,
+    elem_size: usize,
+    max_length: usize,
+}
+
+/// The variable-length arrays of `spec` in wire order.
+fn variable_arrays(spec: &StructSpec) -> Vec<VarArray> {
+    let mut arrays = Vec::new();
+    for field in &spec.fields {
+        let id = ident(&field.name);
+        match &field.field_type {
+            StructFieldType::Primitive(_) | StructFieldType::Bytes(_) => {}
+            StructFieldType::Array(array) if array.fixed => {}
+            StructFieldType::Array(array) => arrays.push(VarArray {
+                path: id,
+                elem_size: array.primitive.byte_len(),
+                max_length: array.max_length,
+            }),
+            StructFieldType::Nested(nested) => {
+                arrays.extend(variable_arrays(nested).into_iter().map(|a| VarArray {
+                    path: format!("{}_{}", id, a.path),
+                    ..a
+                }));
+            }
+        }
+    }
+    arrays
+}
+
+/// The `len_*` instances of a message with fixed fields of `min_size`
+/// bytes: each array takes as many whole elements of the bytes left over
+/// as fit, up to its `max_length`.
+fn length_instances(min_size: usize, arrays: &[VarArray]) -> Vec<(String, String)> {
+    let mut instances = Vec::new();
+    let mut remaining = if min_size == 0 {
+        "_io.size".to_string()
+    } else {
+        format!("_io.size - {}", min_size)
+    };
+    for array in arrays {
+        let name = format!("len_{}", array.path);
+        let count = if array.elem_size == 1 {
+            remaining.clone()
+        } else {
+            format!("({}) / {}", remaining, array.elem_size)
+        };
+        instances.push((
+            name.clone(),
+            format!(
+                "{} < {} ? {} : {}",
+                count, array.max_length, count, array.max_length
+            ),
+        ));
+        if array.elem_size == 1 {
+            write!(remaining, " - {}", name).unwrap();
+        } else {
+            write!(remaining, " - {} * {}", name, array.elem_size).unwrap();
+        }
+    }
+    instances
+}
+
+fn primitive_entry(
+    id: String,
+    primitive: PrimitiveType,
+    endian: Endian,
+    range: Option<ValueRange>,
+    doc: Option<String>,
+) -> Entry {
+    let mut keys = vec![("type", primitive_type(primitive, endian))];
+    let range = range.unwrap_or_default();
+    if range.min.is_some() || range.max.is_some() {
+        let mut valid = String::from("{ ");
+        let bounds: Vec<String> = [("min", range.min), ("max", range.max)]
+            .into_iter()
+            .filter_map(|(key, bound)| bound.map(|b| format!("{}: {}", key, bound_text(b))))
+            .collect();
+        valid.push_str(&bounds.join(", "));
+        valid.push_str(" }");
+        keys.push(("valid", valid));
+    }
+    Entry { id, keys, doc }
+}
+
+fn bound_text(bound: Bound) -> String {
+    match bound {
+        Bound::Int(value) => value.to_string(),
+        Bound::Float(value) => format!("{:?}", value),
+    }
+}
+
+/// Kaitai type of a primitive, e.g. `s2be`. `bool` and `char` are bytes.
+/// Kaitai has no 3-byte integers, so 24-bit values are read as the
+/// unsigned bit-sized integer `b24`, and half precision floats, which it
+/// lacks too, as their raw bits.
+fn primitive_type(primitive: PrimitiveType, endian: Endian) -> String {
+    let base = match primitive {
+        PrimitiveType::Bool | PrimitiveType::Char | PrimitiveType::Uint8 => return "u1".into(),
+        PrimitiveType::Int8 => return "s1".into(),
+        PrimitiveType::Int24 | PrimitiveType::Uint24 => "b24",
+        PrimitiveType::Int16 => "s2",
+        PrimitiveType::Uint16 | PrimitiveType::Float16 => "u2",
+        PrimitiveType::Int32 => "s4",
+        PrimitiveType::Uint32 => "u4",
+        PrimitiveType::Int64 => "s8",
+        PrimitiveType::Uint64 => "u8",
+        PrimitiveType::Float32 => "f4",
+        PrimitiveType::Float64 => "f8",
+    };
+    format!("{}{}", base, endian.suffix())
+}
+
+/// The description with the unit in brackets, e.g. `motor speed [rpm]`.
+fn doc_text(description: &Option<String>, unit: &Option<String>) -> Option<String> {
+    match (description, unit) {
+        (Some(description), Some(unit)) => Some(format!("{} [{}]", description, unit)),
+        (Some(description), None) => Some(description.clone()),
+        (None, Some(unit)) => Some(format!("[{}]", unit)),
+        (None, None) => None,
+    }
+}
+
+/// A Kaitai identifier: lowercase letters, digits and `_`, starting with a
+/// letter.
+pub(crate) fn ident(name: &str) -> String {
+    let snake = to_snake_case(name);
+    match snake.chars().next() {
+        Some(c) if c.is_ascii_lowercase() => snake,
+        _ => format!("n_{}", snake.trim_start_matches('_')),
+    }
+}
+
+/// A YAML double-quoted scalar; its escapes are a superset of JSON's.
+fn quote(text: &str) -> String {
+    serde_json::Value::from(text).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_primitive_types() {
+        assert_eq!(primitive_type(PrimitiveType::Bool, Endian::Big), "u1");
+        assert_eq!(primitive_type(PrimitiveType::Int8, Endian::Little), "s1");
+        assert_eq!(primitive_type(PrimitiveType::Int16, Endian::Big), "s2be");
+        assert_eq!(
+            primitive_type(PrimitiveType::Float64, Endian::Little),
+            "f8le"
+        );
+    }
+
+    #[test]
+    fn test_ident() {
+        assert_eq!(ident("Motor-Speed"), "motor_speed");
+        assert_eq!(ident("2nd-stage"), "n_2nd_stage");
+    }
+
+    #[test]
+    fn test_array_lengths_follow_greedy_decoding() {
+        let (metadata, messages) = crate::parse_str(
+            r#"{ "packets": { "scan": { "packet_id": 40, "msg_type": "struct", "fields": {
+                   "label": { "type": "char", "array": true, "max_length": 8 },
+                   "frame": { "type": "struct", "fields": {
+                       "sequence": { "type": "u32" },
+                       "ranges": { "type": "u16", "array": true, "max_length": 12 } } },
+                   "crc": { "type": "u16", "endianess": "big", "min": 1 } } } } }"#,
+        )
+        .unwrap();
+        let ksy = generate(&metadata, &messages, "scan").unwrap();
+        assert!(ksy.contains(
+            "  scan:\n    seq:\n      - id: label\n        type: str\n        size: len_label\n        encoding: ASCII\n"
+        ));
+        assert!(ksy.contains("      - id: frame\n        type: scan_frame(len_frame_ranges)\n"));
+        assert!(ksy.contains("      - id: crc\n        type: u2be\n        valid: { min: 1 }\n"));
+        assert!(ksy.contains(
+            "      len_label:\n        value: \"_io.size - 6 < 8 ? _io.size - 6 : 8\"\n"
+        ));
+        assert!(ksy.contains(
+            "      len_frame_ranges:\n        value: \"(_io.size - 6 - len_label) / 2 < 12 ? (_io.size - 6 - len_label) / 2 : 12\"\n"
+        ));
+        assert!(ksy.contains(
+            "  scan_frame:\n    params:\n      - id: len_ranges\n        type: u4\n    seq:\n      - id: sequence\n        type: u4le\n      - id: ranges\n        type: u2le\n        repeat: expr\n        repeat-expr: len_ranges\n"
+        ));
+        assert!(ksy.ends_with("enums:\n  packet_id:\n    40: scan\n"));
+    }
+
+    #[test]
+    fn test_wide_packet_ids() {
+        let (metadata, messages) = crate::parse_str(
+            r#"{ "packet_id_width": 16, "default_endianness": "big",
+                 "value": { "packet_id": 4096, "msg_type": "u8" } }"#,
+        )
+        .unwrap();
+        let ksy = generate(&metadata, &messages, "wide").unwrap();
+        assert!(ksy.contains("  - id: packet_id\n    type: u2be\n    enum: packet_id\n"));
+        assert!(ksy.ends_with("enums:\n  packet_id:\n    4096: value\n"));
+    }
+}
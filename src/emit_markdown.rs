@@ -2,17 +2,40 @@
 //!
 //! Generates protocol documentation in Markdown format similar to concept/protocol.md
 
+use std::collections::{HashMap, HashSet};
 use std::fmt::Write as FmtWrite;
-use std::path::Path;
+use std::io;
+use std::path::{Path, PathBuf};
 
-use anyhow::Result;
+use crate::codec;
+use crate::emit_c::{self, ByteSpan, FunctionMode, Role, struct_layout};
+use crate::fingerprint::GENERATOR;
+use crate::{
+    Bound, Deprecation, Endian, IdlError, MessageBody, MessageDefinition, Metadata, PrimitiveType,
+    Radix, RequestType, Scaling, SourceComment, StructField, StructFieldType, StructSpec,
+    ValueRange, const_macro_name, format_id_range, format_packet_id, ir_fingerprint,
+    message_body_max_size, message_body_min_size, shared_types,
+};
 
-use crate::{MessageDefinition, Metadata};
+/// Options of the Markdown generator.
+#[derive(Clone, Debug, Default)]
+pub struct MarkdownOptions {
+    /// How the `Auto-generated from:` line names the input document
+    pub source_comment: SourceComment,
+    /// Further documents merged into the IR, listed after the input path
+    pub extra_inputs: Vec<PathBuf>,
+    /// Leave out the table of contents, e.g. when the file is embedded in
+    /// another document
+    pub no_toc: bool,
+    /// Document only the commands of this client (its own and those for
+    /// every client), with a Direction column seen from the client
+    pub client_id: Option<i32>,
+}
 
 /// Generates Markdown documentation for command definitions.
 ///
 /// # Arguments
-/// * `metadata` - Protocol metadata (version, max_address)
+/// * `metadata` - Protocol metadata (version, max_address, default_endianness)
 /// * `messages` - List of message definitions to document
 /// * `input_path` - Path to input JSON file (for documentation)
 ///
@@ -28,44 +51,256 @@ pub fn generate(
     metadata: &Metadata,
     messages: &[MessageDefinition],
     input_path: &Path,
-) -> Result<String> {
+) -> Result<String, IdlError> {
+    generate_with_options(metadata, messages, input_path, &MarkdownOptions::default())
+}
+
+/// Same as [`generate`], honoring the given options.
+pub fn generate_with_options(
+    metadata: &Metadata,
+    messages: &[MessageDefinition],
+    input_path: &Path,
+    options: &MarkdownOptions,
+) -> Result<String, IdlError> {
+    // The fingerprint identifies the whole protocol, not the client's share
+    let fingerprint = ir_fingerprint(metadata, messages);
+    let client_messages: Vec<MessageDefinition>;
+    let messages = match options.client_id {
+        Some(id) => {
+            emit_c::check_client(messages, id)?;
+            client_messages = messages
+                .iter()
+                .filter(|m| for_client(m, id))
+                .cloned()
+                .collect();
+            &client_messages[..]
+        }
+        None => messages,
+    };
     let mut out = String::new();
 
     // Generate header
     writeln!(&mut out, "# Command Definitions").unwrap();
     writeln!(&mut out).unwrap();
-    writeln!(&mut out, "Auto-generated from: `{}`", input_path.display()).unwrap();
+    if let Some(label) = options
+        .source_comment
+        .label_all(input_path, &options.extra_inputs)
+    {
+        writeln!(&mut out, "Auto-generated from: `{}`", label).unwrap();
+    }
+    writeln!(
+        &mut out,
+        "Generated by {} (IR fingerprint `{}`)",
+        GENERATOR, fingerprint
+    )
+    .unwrap();
 
     if let Some(version) = &metadata.version {
         writeln!(&mut out, "Protocol version: {}", version).unwrap();
     }
     if let Some(max_address) = metadata.max_address {
-        writeln!(&mut out, "Max address: {}", max_address).unwrap();
+        writeln!(
+            &mut out,
+            "Max address: {}",
+            format_packet_id(max_address, metadata.packet_id_width)
+        )
+        .unwrap();
+    }
+    if metadata.packet_id_width != Metadata::default().packet_id_width {
+        writeln!(
+            &mut out,
+            "Packet ID width: {} bits",
+            metadata.packet_id_width
+        )
+        .unwrap();
+    }
+    if let Some(endian) = metadata.default_endianness {
+        writeln!(&mut out, "Default endianness: {}", endian).unwrap();
+    }
+    let reserved: Vec<String> = metadata
+        .reserved_ids
+        .iter()
+        .map(|&(first, last)| format_id_range(first, last))
+        .collect();
+    if !reserved.is_empty() {
+        writeln!(&mut out, "Reserved packet IDs: {}", reserved.join(", ")).unwrap();
+    }
+    if let Some(id) = options.client_id {
+        writeln!(
+            &mut out,
+            "Client: {} (its own commands and those for every client; Direction is seen from the client)",
+            id
+        )
+        .unwrap();
     }
     writeln!(&mut out).unwrap();
 
-    // Group commands by ranges
-    let base_commands: Vec<_> = messages.iter().filter(|m| m.packet_id < 20).collect();
-    let custom_commands: Vec<_> = messages.iter().filter(|m| m.packet_id >= 20).collect();
+    // Command tables, one per section
+    let mut sections: Vec<(String, Vec<&MessageDefinition>)> = Vec::new();
+    if !metadata.command_groups.is_empty() {
+        for group in &metadata.command_groups {
+            let commands: Vec<_> = messages
+                .iter()
+                .filter(|m| (group.first..=group.last).contains(&m.packet_id))
+                .collect();
+            let title = format!(
+                "{} ({})",
+                group.name,
+                format_id_range(group.first, group.last)
+            );
+            sections.push((title, commands));
+        }
+        // Messages outside every group must not disappear
+        let ungrouped: Vec<_> = messages
+            .iter()
+            .filter(|m| {
+                !metadata
+                    .command_groups
+                    .iter()
+                    .any(|g| (g.first..=g.last).contains(&m.packet_id))
+            })
+            .collect();
+        if !ungrouped.is_empty() {
+            sections.push(("Ungrouped".to_string(), ungrouped));
+        }
+    } else if messages.iter().any(|m| m.group.is_some()) {
+        // One section per message group, in order of first appearance
+        for msg in messages {
+            let Some(group) = &msg.group else {
+                continue;
+            };
+            match sections.iter_mut().find(|(title, _)| title == group) {
+                Some((_, commands)) => commands.push(msg),
+                None => sections.push((group.clone(), vec![msg])),
+            }
+        }
+        let ungrouped: Vec<_> = messages.iter().filter(|m| m.group.is_none()).collect();
+        if !ungrouped.is_empty() {
+            sections.push(("Ungrouped".to_string(), ungrouped));
+        }
+    } else if reserved.is_empty() {
+        if !messages.is_empty() {
+            sections.push(("Commands".to_string(), messages.iter().collect()));
+        }
+    } else {
+        // Group commands by the reserved ranges
+        let (base_commands, custom_commands): (Vec<_>, Vec<_>) = messages.iter().partition(|m| {
+            metadata
+                .reserved_ids
+                .iter()
+                .any(|&(first, last)| (first..=last).contains(&m.packet_id))
+        });
+
+        // Base Commands section
+        if !base_commands.is_empty() {
+            let title = format!("Base Commands ({})", reserved.join(", "));
+            sections.push((title, base_commands));
+        }
 
-    // Generate Base Commands section
-    if !base_commands.is_empty() {
-        generate_command_section(&mut out, "Base Commands (0~19)", &base_commands)?;
+        // Custom Commands section
+        if !custom_commands.is_empty() {
+            sections.push(("Custom Commands".to_string(), custom_commands));
+        }
     }
 
-    // Generate Custom Commands section
-    if !custom_commands.is_empty() {
-        generate_command_section(&mut out, "Custom Commands (20+)", &custom_commands)?;
+    // Everything after the command tables
+    let mut rest = String::new();
+    // Generate Message Details section
+    generate_message_details_section(&mut rest, messages, metadata.packet_id_width);
+
+    // Generate Scaled Values section
+    generate_scaled_values_section(&mut rest, messages);
+
+    // Generate Value Ranges section
+    generate_value_ranges_section(&mut rest, messages);
+
+    // Generate Constants section
+    if !metadata.constants.is_empty() {
+        generate_constants_section(&mut rest, metadata);
     }
 
+    // Generate Shared Types section
+    let types = shared_types(metadata, messages);
+    if !types.is_empty() {
+        generate_shared_types_section(&mut rest, &types);
+    }
+
+    // Anchors are assigned in document order, like GitHub does
+    let mut slugger = Slugger::default();
+    slugger.slug("Command Definitions");
+    if !options.no_toc {
+        slugger.slug("Contents");
+    }
+    let section_slugs: Vec<String> = sections
+        .iter()
+        .map(|(title, _)| slugger.slug(title))
+        .collect();
+    let rest_headings: Vec<(usize, &str, String)> = headings(&rest)
+        .into_iter()
+        .map(|(level, text)| (level, text, slugger.slug(text)))
+        .collect();
+    // A command links to its detail section, the first heading naming it
+    let mut anchors = HashMap::new();
+    for (_, text, slug) in &rest_headings {
+        anchors.entry(*text).or_insert(slug.as_str());
+    }
+
+    let has_headings = !sections.is_empty() || !rest_headings.is_empty();
+    if !options.no_toc && has_headings {
+        writeln!(&mut out, "## Contents").unwrap();
+        writeln!(&mut out).unwrap();
+        for ((title, _), slug) in sections.iter().zip(&section_slugs) {
+            writeln!(&mut out, "- [{}](#{})", title, slug).unwrap();
+        }
+        for (level, text, slug) in &rest_headings {
+            let indent = "  ".repeat(level - 2);
+            writeln!(&mut out, "{}- [{}](#{})", indent, text, slug).unwrap();
+        }
+        writeln!(&mut out).unwrap();
+    }
+
+    // The column only appears once the protocol pairs requests and responses
+    let responses = messages.iter().any(|msg| msg.response.is_some());
+    for (title, commands) in &sections {
+        generate_command_section(
+            &mut out,
+            title,
+            commands,
+            &anchors,
+            responses,
+            options.client_id,
+            metadata.packet_id_width,
+        )?;
+    }
+    out.push_str(&rest);
+
     Ok(out)
 }
 
+/// Same as [`generate`], writing the document into `writer`. `output_path`
+/// only names the destination in write errors.
+pub fn generate_to_writer<W: io::Write>(
+    writer: &mut W,
+    metadata: &Metadata,
+    messages: &[MessageDefinition],
+    input_path: &Path,
+    output_path: &Path,
+) -> Result<(), IdlError> {
+    let document = generate(metadata, messages, input_path)?;
+    writer
+        .write_all(document.as_bytes())
+        .map_err(|e| IdlError::write(output_path, e))
+}
+
 fn generate_command_section(
     out: &mut String,
     title: &str,
     commands: &[&MessageDefinition],
-) -> Result<()> {
+    anchors: &HashMap<&str, &str>,
+    responses: bool,
+    client_id: Option<i32>,
+    packet_id_width: u8,
+) -> Result<(), IdlError> {
     writeln!(out, "## {}", title).unwrap();
     writeln!(out).unwrap();
 
@@ -76,26 +311,667 @@ fn generate_command_section(
     }
 
     // Generate table header
-    writeln!(out, "| Command | Value | Description |").unwrap();
-    writeln!(out, "|---------|-------|-------------|").unwrap();
+    out.push_str("| Command | Value | Direction | Target | Description |");
+    if responses {
+        out.push_str(" Responds with |");
+    }
+    out.push_str("\n|---------|-------|-----------|--------|-------------|");
+    if responses {
+        out.push_str("---------------|");
+    }
+    out.push('\n');
 
     // Generate table rows
     for msg in commands {
-        let command_name = format_command_name(&msg.name);
-        let description = msg.description.as_deref().unwrap_or("No description");
+        let mut description = msg
+            .description
+            .as_deref()
+            .unwrap_or("No description")
+            .to_string();
+        match &msg.deprecated {
+            Some(Deprecation {
+                reason: Some(reason),
+            }) => write!(description, " (**deprecated**: {})", reason).unwrap(),
+            Some(Deprecation { reason: None }) => description.push_str(" (**deprecated**)"),
+            None => {}
+        }
 
-        writeln!(
+        let link = |name: &str| {
+            let heading = format!("`{}`", format_command_name(name));
+            match anchors.get(heading.as_str()) {
+                Some(anchor) => format!("[{}](#{})", heading, anchor),
+                None => heading,
+            }
+        };
+        let direction = match client_id {
+            Some(id) => client_direction(msg, id),
+            None => direction(msg),
+        };
+        write!(
             out,
-            "| `{}` | {} | {} |",
-            command_name, msg.packet_id, description
+            "| {} | {} | {} | {} | {} |",
+            link(&msg.name),
+            packet_id_text(msg, packet_id_width),
+            direction,
+            target(msg),
+            description
         )
         .unwrap();
+        if responses {
+            let response = msg.response.as_deref().map(link).unwrap_or_default();
+            write!(out, " {} |", response).unwrap();
+        }
+        writeln!(out).unwrap();
     }
 
     writeln!(out).unwrap();
     Ok(())
 }
 
+/// Packet ID of `msg` in the radix the input wrote it in, followed by the
+/// decimal value for hex and binary; 16-bit IDs always show hex.
+fn packet_id_text(msg: &MessageDefinition, packet_id_width: u8) -> String {
+    let id = msg.packet_id;
+    match msg.packet_id_radix {
+        Radix::Hex => format!(
+            "0x{:0digits$X} ({})",
+            id,
+            id,
+            digits = usize::from(packet_id_width / 4)
+        ),
+        Radix::Binary => format!("0b{:b} ({})", id, id),
+        Radix::Decimal => format_packet_id(id, packet_id_width),
+    }
+}
+
+/// Which side sends `msg`, from its `request_type`.
+fn direction(msg: &MessageDefinition) -> &'static str {
+    match (msg.request_type_given, msg.request_type) {
+        (false, _) => "unspecified",
+        (true, RequestType::Pub) => "server → client",
+        (true, RequestType::Sub) => "client → server",
+    }
+}
+
+/// The client `msg` is addressed to, from its `target_client_id`.
+fn target(msg: &MessageDefinition) -> String {
+    match (msg.target_client_id_given, msg.target_client_id) {
+        (false, _) => "unspecified".to_string(),
+        (true, -1) => "broadcast".to_string(),
+        (true, id) => id.to_string(),
+    }
+}
+
+/// Whether the documentation of client `id` lists `msg`: its own messages
+/// and those for every client.
+pub(crate) fn for_client(msg: &MessageDefinition, id: i32) -> bool {
+    msg.target_client_id == id || msg.target_client_id == -1
+}
+
+/// Whether client `id` receives or sends `msg`, as in its C header.
+fn client_direction(msg: &MessageDefinition, id: i32) -> &'static str {
+    let mode = Role::Client(id)
+        .function_mode(msg)
+        .or_else(|| Role::ClientCommon.function_mode(msg));
+    match mode {
+        Some(FunctionMode::DecodeOnly) => "receive",
+        Some(FunctionMode::EncodeOnly) => "send",
+        Some(FunctionMode::Both) | None => "",
+    }
+}
+
+/// Size column of a variable-length array: the byte range, then the
+/// element size and how many elements fit.
+fn array_size_cell(primitive: PrimitiveType, max_length: usize) -> String {
+    let elem = primitive.byte_len();
+    format!(
+        "{} ({} × up to {})",
+        ByteSpan {
+            min: 0,
+            max: elem * max_length
+        },
+        elem,
+        max_length
+    )
+}
+
+/// One row of a message detail table.
+struct DetailRow {
+    name: String,
+    ty: String,
+    offset: ByteSpan,
+    size: String,
+    unit: Option<String>,
+    description: Option<String>,
+}
+
+fn generate_message_details_section(
+    out: &mut String,
+    messages: &[MessageDefinition],
+    packet_id_width: u8,
+) {
+    /// Adds the rows of `spec` starting at `offset` and returns the offset
+    /// after it. Multi-byte values are recorded in `endians`.
+    fn collect(
+        spec: &StructSpec,
+        prefix: &str,
+        offset: ByteSpan,
+        rows: &mut Vec<DetailRow>,
+        endians: &mut Vec<(String, Endian)>,
+    ) -> ByteSpan {
+        let (layout, end) = struct_layout(spec, prefix, offset);
+        for entry in layout {
+            let field = entry.field;
+            let size_cell = match &field.field_type {
+                StructFieldType::Array(array) if array.fixed => format!(
+                    "{} ({} × {})",
+                    entry.size,
+                    array.primitive.byte_len(),
+                    array.max_length
+                ),
+                StructFieldType::Array(array) => array_size_cell(array.primitive, array.max_length),
+                _ => entry.size.to_string(),
+            };
+            rows.push(DetailRow {
+                name: entry.path.clone(),
+                ty: field_type_cell(field),
+                offset: entry.offset,
+                size: size_cell,
+                unit: field.unit.clone().or(field
+                    .timestamp
+                    .map(|resolution| resolution.name().to_string())),
+                description: match (field.since_version, &field.description) {
+                    _ if field.reserved => {
+                        Some("Reserved: sent as zeros, ignored when received".to_string())
+                    }
+                    (Some(since), Some(description)) => {
+                        Some(format!("{} (since version {})", description, since))
+                    }
+                    (Some(since), None) => Some(format!("Since version {}", since)),
+                    (None, description) => description.clone(),
+                },
+            });
+            match &field.field_type {
+                StructFieldType::Primitive(primitive) if primitive.byte_len() > 1 => {
+                    endians.push((entry.path, field.endian))
+                }
+                StructFieldType::Array(array) if array.primitive.byte_len() > 1 => {
+                    endians.push((entry.path, field.endian))
+                }
+                _ => {}
+            }
+        }
+        end
+    }
+
+    if messages.is_empty() {
+        return;
+    }
+    writeln!(out, "## Message Details").unwrap();
+    writeln!(out).unwrap();
+    for msg in messages {
+        let mut rows = Vec::new();
+        let mut endians = Vec::new();
+        // `(heading, rows)` of each variant of a union
+        let mut variant_tables = Vec::new();
+        let payload = match &msg.body {
+            MessageBody::Scalar(spec) => {
+                let len = spec.primitive.byte_len();
+                rows.push(DetailRow {
+                    name: "value".to_string(),
+                    ty: format!("`{}`", spec.primitive.wire_type()),
+                    offset: ByteSpan::fixed(0),
+                    size: len.to_string(),
+                    unit: spec.unit.clone(),
+                    description: spec.description.clone(),
+                });
+                if len > 1 {
+                    endians.push(("value".to_string(), spec.endian));
+                }
+                ByteSpan::fixed(len)
+            }
+            MessageBody::Array(spec) => {
+                rows.push(DetailRow {
+                    name: "data".to_string(),
+                    ty: format!("`{}[{}]`", spec.primitive.wire_type(), spec.max_length),
+                    offset: ByteSpan::fixed(0),
+                    size: array_size_cell(spec.primitive, spec.max_length),
+                    unit: spec.unit.clone(),
+                    description: spec.description.clone(),
+                });
+                if spec.primitive.byte_len() > 1 {
+                    endians.push(("data".to_string(), spec.endian));
+                }
+                ByteSpan {
+                    min: 0,
+                    max: message_body_max_size(&msg.body),
+                }
+            }
+            MessageBody::Struct(spec) => {
+                collect(spec, "", ByteSpan::fixed(0), &mut rows, &mut endians)
+            }
+            MessageBody::Union(spec) => {
+                let tag_len = spec.tag.byte_len();
+                rows.push(DetailRow {
+                    name: "tag".to_string(),
+                    ty: format!("`{}`", spec.tag.wire_type()),
+                    offset: ByteSpan::fixed(0),
+                    size: tag_len.to_string(),
+                    unit: None,
+                    description: Some("Selects the variant".to_string()),
+                });
+                if tag_len > 1 {
+                    endians.push(("tag".to_string(), spec.endian));
+                }
+                for variant in &spec.variants {
+                    let mut variant_rows = Vec::new();
+                    let end = collect(
+                        &variant.spec,
+                        &format!("{}.", variant.name),
+                        ByteSpan::fixed(tag_len),
+                        &mut variant_rows,
+                        &mut endians,
+                    );
+                    let unit = if end.max == 1 { "byte" } else { "bytes" };
+                    let mut heading = format!(
+                        "**Variant `{}`** (tag {}, {} {})",
+                        variant.name, variant.tag, end, unit
+                    );
+                    if let Some(description) = &variant.description {
+                        write!(heading, ": {}", description).unwrap();
+                    }
+                    variant_tables.push((heading, variant_rows));
+                }
+                ByteSpan {
+                    min: message_body_min_size(&msg.body),
+                    max: message_body_max_size(&msg.body),
+                }
+            }
+        };
+
+        writeln!(out, "### `{}`", format_command_name(&msg.name)).unwrap();
+        writeln!(out).unwrap();
+        writeln!(out, "- Packet ID: {}", packet_id_text(msg, packet_id_width)).unwrap();
+        if let Some(msg_version) = msg.msg_version {
+            writeln!(out, "- Message version: {}", msg_version).unwrap();
+        }
+        let unit = if payload.max == 1 { "byte" } else { "bytes" };
+        writeln!(out, "- Payload size: {} {}", payload, unit).unwrap();
+        writeln!(out, "- Endianness: {}", endianness_text(&endians)).unwrap();
+        writeln!(out).unwrap();
+        write_detail_table(out, rows);
+        for (heading, rows) in variant_tables {
+            writeln!(out, "{}", heading).unwrap();
+            writeln!(out).unwrap();
+            write_detail_table(out, rows);
+        }
+        generate_example_payload(out, msg);
+    }
+}
+
+fn write_detail_table(out: &mut String, rows: Vec<DetailRow>) {
+    writeln!(out, "| Field | Type | Offset | Size | Unit | Description |").unwrap();
+    writeln!(out, "|-------|------|--------|------|------|-------------|").unwrap();
+    for row in rows {
+        let cell = |text: Option<String>| text.map_or_else(String::new, |t| table_text(&t));
+        writeln!(
+            out,
+            "| `{}` | {} | {} | {} | {} | {} |",
+            row.name,
+            row.ty,
+            row.offset,
+            row.size,
+            cell(row.unit),
+            cell(row.description)
+        )
+        .unwrap();
+    }
+    writeln!(out).unwrap();
+}
+
+/// Example payload of a message, encoded from [`codec::example_value`] and
+/// annotated with the bytes of each value.
+fn generate_example_payload(out: &mut String, msg: &MessageDefinition) {
+    let value = codec::example_value(&msg.body);
+    let Ok((bytes, layout)) = codec::encode_message_with_layout(msg, &value) else {
+        return;
+    };
+    let hex = |bytes: &[u8]| {
+        bytes
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let rows: Vec<(String, String, String)> = layout
+        .iter()
+        .map(|field| {
+            (
+                field.bytes.start.to_string(),
+                hex(&bytes[field.bytes.clone()]),
+                format!("{} = {}", field.path, field.value),
+            )
+        })
+        .collect();
+    let offset_width = rows.iter().map(|r| r.0.len()).max().unwrap_or(0).max(6);
+    let bytes_width = rows.iter().map(|r| r.1.len()).max().unwrap_or(0).max(5);
+
+    let unit = if bytes.len() == 1 { "byte" } else { "bytes" };
+    writeln!(out, "Example payload ({} {}):", bytes.len(), unit).unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "```text").unwrap();
+    writeln!(out, "{}", hex(&bytes)).unwrap();
+    writeln!(out).unwrap();
+    writeln!(
+        out,
+        "{:<ow$}  {:<bw$}  value",
+        "offset",
+        "bytes",
+        ow = offset_width,
+        bw = bytes_width
+    )
+    .unwrap();
+    for (offset, bytes, value) in rows {
+        writeln!(
+            out,
+            "{:<ow$}  {:<bw$}  {}",
+            offset,
+            bytes,
+            value,
+            ow = offset_width,
+            bw = bytes_width
+        )
+        .unwrap();
+    }
+    writeln!(out, "```").unwrap();
+    writeln!(out).unwrap();
+}
+
+/// Byte order of the multi-byte values of a message; when they disagree
+/// the big-endian ones are named, little-endian being the default.
+fn endianness_text(endians: &[(String, Endian)]) -> String {
+    let big: Vec<String> = endians
+        .iter()
+        .filter(|(_, endian)| *endian == Endian::Big)
+        .map(|(name, _)| format!("`{}`", name))
+        .collect();
+    if endians.is_empty() {
+        "n/a (single-byte values only)".to_string()
+    } else if big.is_empty() {
+        "little".to_string()
+    } else if big.len() == endians.len() {
+        "big".to_string()
+    } else {
+        format!("mixed (big-endian: {})", big.join(", "))
+    }
+}
+
+/// Heading anchors as GitHub generates them: lowercase, punctuation
+/// dropped, spaces turned into `-`, and `-1`, `-2`, ... appended to
+/// repeated headings.
+#[derive(Default)]
+struct Slugger {
+    seen: HashSet<String>,
+}
+
+impl Slugger {
+    fn slug(&mut self, heading: &str) -> String {
+        let mut base = String::new();
+        for c in heading.chars() {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                base.extend(c.to_lowercase());
+            } else if c == ' ' {
+                base.push('-');
+            }
+        }
+        let mut slug = base.clone();
+        let mut count = 0;
+        while self.seen.contains(&slug) {
+            count += 1;
+            slug = format!("{}-{}", base, count);
+        }
+        self.seen.insert(slug.clone());
+        slug
+    }
+}
+
+/// `(level, text)` of the `##` and `###` headings of a generated section.
+fn headings(markdown: &str) -> Vec<(usize, &str)> {
+    markdown
+        .lines()
+        .filter_map(|line| {
+            if let Some(text) = line.strip_prefix("### ") {
+                Some((3, text))
+            } else {
+                line.strip_prefix("## ").map(|text| (2, text))
+            }
+        })
+        .collect()
+}
+
+/// Text made safe for a table cell.
+fn table_text(text: &str) -> String {
+    text.replace('|', "\\|").replace(['\r', '\n'], " ")
+}
+
+/// Type column of a struct field.
+fn field_type_cell(field: &StructField) -> String {
+    match &field.field_type {
+        StructFieldType::Primitive(primitive) => match field.timestamp {
+            Some(resolution) => format!(
+                "timestamp (`{}`, {})",
+                primitive.wire_type(),
+                resolution.name()
+            ),
+            None => format!("`{}`", primitive.wire_type()),
+        },
+        StructFieldType::Array(array) => {
+            format!("`{}[{}]`", array.primitive.wire_type(), array.max_length)
+        }
+        StructFieldType::Bytes(length) if field.reserved => format!("reserved[{}]", length),
+        StructFieldType::Bytes(length) => format!("`bytes[{}]`", length),
+        StructFieldType::Nested(nested) => match &nested.shared {
+            Some(shared) => format!("`{}`", shared),
+            None => "struct".to_string(),
+        },
+    }
+}
+
+fn generate_scaled_values_section(out: &mut String, messages: &[MessageDefinition]) {
+    fn collect(spec: &StructSpec, prefix: &str, rows: &mut Vec<(String, PrimitiveType, Scaling)>) {
+        for field in &spec.fields {
+            let name = format!("{}{}", prefix, field.name);
+            match (&field.field_type, field.scaling) {
+                (StructFieldType::Nested(nested), _) => {
+                    collect(nested, &format!("{}.", name), rows)
+                }
+                (StructFieldType::Primitive(primitive), Some(scaling)) => {
+                    rows.push((name, *primitive, scaling))
+                }
+                (StructFieldType::Array(array), Some(scaling)) => {
+                    rows.push((format!("{}[]", name), array.primitive, scaling))
+                }
+                (StructFieldType::Bytes(_), _) | (_, None) => {}
+            }
+        }
+    }
+
+    let mut rows = Vec::new();
+    for msg in messages {
+        let mut fields = Vec::new();
+        match &msg.body {
+            MessageBody::Scalar(spec) => {
+                if let Some(scaling) = spec.scaling {
+                    fields.push(("value".to_string(), spec.primitive, scaling));
+                }
+            }
+            MessageBody::Array(spec) => {
+                if let Some(scaling) = spec.scaling {
+                    fields.push(("data[]".to_string(), spec.primitive, scaling));
+                }
+            }
+            MessageBody::Struct(spec) => collect(spec, "", &mut fields),
+            MessageBody::Union(spec) => {
+                for variant in &spec.variants {
+                    collect(&variant.spec, &format!("{}.", variant.name), &mut fields);
+                }
+            }
+        }
+        rows.extend(fields.into_iter().map(|field| (msg, field)));
+    }
+    if rows.is_empty() {
+        return;
+    }
+
+    writeln!(out, "## Scaled Values").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "Physical value = raw * scale + offset.").unwrap();
+    writeln!(out).unwrap();
+    writeln!(
+        out,
+        "| Command | Field | Raw type | Scale | Offset | Physical range |"
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "|---------|-------|----------|-------|--------|----------------|"
+    )
+    .unwrap();
+    for (msg, (field, primitive, scaling)) in rows {
+        let range = match scaling.range(primitive) {
+            Some((low, high)) => format!("{} to {}", format_number(low), format_number(high)),
+            None => "-".to_string(),
+        };
+        writeln!(
+            out,
+            "| `{}` | `{}` | `{}` | {} | {} | {} |",
+            format_command_name(&msg.name),
+            field,
+            primitive.wire_type(),
+            scaling.scale,
+            scaling.offset,
+            range
+        )
+        .unwrap();
+    }
+    writeln!(out).unwrap();
+}
+
+fn generate_value_ranges_section(out: &mut String, messages: &[MessageDefinition]) {
+    fn collect(
+        spec: &StructSpec,
+        prefix: &str,
+        rows: &mut Vec<(String, PrimitiveType, ValueRange)>,
+    ) {
+        for field in &spec.fields {
+            let name = format!("{}{}", prefix, field.name);
+            match (&field.field_type, field.range) {
+                (StructFieldType::Nested(nested), _) => {
+                    collect(nested, &format!("{}.", name), rows)
+                }
+                (StructFieldType::Primitive(primitive), Some(range)) => {
+                    rows.push((name, *primitive, range))
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut rows = Vec::new();
+    for msg in messages {
+        let mut fields = Vec::new();
+        match &msg.body {
+            MessageBody::Scalar(spec) => {
+                if let Some(range) = spec.range {
+                    fields.push(("value".to_string(), spec.primitive, range));
+                }
+            }
+            MessageBody::Array(_) => {}
+            MessageBody::Struct(spec) => collect(spec, "", &mut fields),
+            MessageBody::Union(spec) => {
+                for variant in &spec.variants {
+                    collect(&variant.spec, &format!("{}.", variant.name), &mut fields);
+                }
+            }
+        }
+        rows.extend(fields.into_iter().map(|field| (msg, field)));
+    }
+    if rows.is_empty() {
+        return;
+    }
+
+    writeln!(out, "## Value Ranges").unwrap();
+    writeln!(out).unwrap();
+    writeln!(
+        out,
+        "Decoders reject raw values outside these inclusive bounds."
+    )
+    .unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "| Command | Field | Type | Min | Max |").unwrap();
+    writeln!(out, "|---------|-------|------|-----|-----|").unwrap();
+    for (msg, (field, primitive, range)) in rows {
+        let bound = |bound: Option<Bound>| bound.map_or_else(|| "-".to_string(), |b| b.to_string());
+        writeln!(
+            out,
+            "| `{}` | `{}` | `{}` | {} | {} |",
+            format_command_name(&msg.name),
+            field,
+            primitive.wire_type(),
+            bound(range.min),
+            bound(range.max)
+        )
+        .unwrap();
+    }
+    writeln!(out).unwrap();
+}
+
+/// Formats a computed value with at most six decimals, hiding the
+/// rounding noise of `raw * scale + offset`.
+fn format_number(value: f64) -> String {
+    let rounded = (value * 1e6).round() / 1e6;
+    if rounded.is_finite() {
+        // Avoid "-0"
+        format!("{}", rounded + 0.0)
+    } else {
+        format!("{}", value)
+    }
+}
+
+fn generate_constants_section(out: &mut String, metadata: &Metadata) {
+    writeln!(out, "## Constants").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "| Constant | Value |").unwrap();
+    writeln!(out, "|----------|-------|").unwrap();
+    for (name, value) in &metadata.constants {
+        writeln!(
+            out,
+            "| `{}` | `{}` |",
+            const_macro_name(name),
+            value.to_string().replace('|', "\\|")
+        )
+        .unwrap();
+    }
+    writeln!(out).unwrap();
+}
+
+fn generate_shared_types_section(out: &mut String, types: &[&StructSpec]) {
+    writeln!(out, "## Shared Types").unwrap();
+    writeln!(out).unwrap();
+
+    for spec in types {
+        let name = spec.shared.as_deref().unwrap_or_default();
+        writeln!(out, "### `{}`", name).unwrap();
+        writeln!(out).unwrap();
+        writeln!(out, "| Field | Type |").unwrap();
+        writeln!(out, "|-------|------|").unwrap();
+        for field in &spec.fields {
+            writeln!(out, "| `{}` | {} |", field.name, field_type_cell(field)).unwrap();
+        }
+        writeln!(out).unwrap();
+    }
+}
+
 fn format_command_name(name: &str) -> String {
     // Convert to SCREAMING_SNAKE_CASE for command names
     let mut result = String::new();
@@ -151,4 +1027,287 @@ mod tests {
             "CMD_FIRMWARE_VERSION"
         );
     }
+
+    #[test]
+    fn test_command_groups_build_the_sections() {
+        let (metadata, messages) = crate::parse_str(
+            r#"{ "command_groups": [
+                     { "name": "System", "range": [0, 63] },
+                     { "name": "Diagnostics", "range": [100, 110] },
+                     { "name": "Application", "range": [64, 99] } ],
+                 "ping": { "packet_id": 1, "msg_type": "u8" },
+                 "led": { "packet_id": 70, "msg_type": "bool" },
+                 "trace": { "packet_id": 200, "msg_type": "u8" } }"#,
+        )
+        .unwrap();
+        let doc = generate(&metadata, &messages, Path::new("p.json")).unwrap();
+        let system = doc.find("## System (0-63)\n").unwrap();
+        let diagnostics = doc.find("## Diagnostics (100-110)\n").unwrap();
+        let application = doc.find("## Application (64-99)\n").unwrap();
+        let ungrouped = doc.find("## Ungrouped\n").unwrap();
+        assert!(system < diagnostics && diagnostics < application && application < ungrouped);
+        assert!(doc[system..diagnostics].contains("| [`CMD_PING`](#cmd_ping) | 1 |"));
+        assert!(doc[diagnostics..application].contains("*No commands defined in this range.*"));
+        assert!(doc[application..ungrouped].contains("| [`CMD_LED`](#cmd_led) | 70 |"));
+        assert!(doc[ungrouped..].contains("| [`CMD_TRACE`](#cmd_trace) | 200 |"));
+        assert!(!doc.contains("Base Commands"));
+    }
+
+    #[test]
+    fn test_message_details_give_the_wire_layout() {
+        let (metadata, messages) = crate::parse_str(
+            r#"{ "scan": { "packet_id": 40, "msg_type": "struct", "fields": {
+                     "id": { "type": "u8" },
+                     "pose": { "type": "struct", "fields": {
+                         "x": { "type": "f32", "endianess": "big", "unit": "m" },
+                         "y": { "type": "f32", "endianess": "big" } } },
+                     "ranges": { "type": "u16", "array": true, "max_length": 12, "desc": "a | b" },
+                     "crc": { "type": "u16" } } },
+                 "ping": { "packet_id": 1, "msg_type": "u8" } }"#,
+        )
+        .unwrap();
+        let doc = generate(&metadata, &messages, Path::new("p.json")).unwrap();
+        assert!(doc.contains("| [`CMD_SCAN`](#cmd_scan) | 40 |"));
+        let scan = doc.find("### `CMD_SCAN`\n").unwrap();
+        let details = &doc[scan..];
+        assert!(details.contains("- Packet ID: 40\n"));
+        assert!(details.contains("- Payload size: 11–35 bytes\n"));
+        assert!(details.contains("- Endianness: mixed (big-endian: `pose.x`, `pose.y`)\n"));
+        assert!(details.contains("| `id` | `uint8_t` | 0 | 1 |  |  |\n"));
+        assert!(details.contains("| `pose` | struct | 1 | 8 |  |  |\n"));
+        assert!(details.contains("| `pose.x` | `float` | 1 | 4 | m |  |\n"));
+        assert!(details.contains("| `pose.y` | `float` | 5 | 4 |  |  |\n"));
+        assert!(
+            details
+                .contains("| `ranges` | `uint16_t[12]` | 9 | 0–24 (2 × up to 12) |  | a \\| b |\n")
+        );
+        assert!(details.contains("| `crc` | `uint16_t` | 9–33 | 2 |  |  |\n"));
+
+        assert!(details.contains(
+            "Example payload (15 bytes):\n\n```text\n\
+             01 40 20 00 00 40 60 00 00 04 00 05 00 06 00\n\n\
+             offset  bytes        value\n\
+             0       01           id = 1\n\
+             1       40 20 00 00  pose.x = 2.5\n\
+             5       40 60 00 00  pose.y = 3.5\n\
+             9       04 00 05 00  ranges = [4, 5]\n\
+             13      06 00        crc = 6\n```\n"
+        ));
+
+        let ping = doc.find("### `CMD_PING`\n").unwrap();
+        assert!(doc[ping..].contains("- Payload size: 1 byte\n"));
+        assert!(doc[ping..].contains("- Endianness: n/a (single-byte values only)\n"));
+    }
+
+    #[test]
+    fn test_timestamp_fields_show_their_resolution() {
+        let (metadata, messages) = crate::parse_str(
+            r#"{ "imu": { "packet_id": 1, "msg_type": "struct", "fields": {
+                     "stamp": { "type": "timestamp", "storage": "u32", "resolution": "ms" },
+                     "t": { "type": "timestamp", "unit": "ticks" } } } }"#,
+        )
+        .unwrap();
+        let doc = generate(&metadata, &messages, Path::new("p.json")).unwrap();
+        assert!(doc.contains("| `stamp` | timestamp (`uint32_t`, ms) | 0 | 4 | ms |  |\n"));
+        assert!(doc.contains("| `t` | timestamp (`uint64_t`, us) | 4 | 8 | ticks |  |\n"));
+    }
+
+    #[test]
+    fn test_slugs_follow_github() {
+        let mut slugger = Slugger::default();
+        assert_eq!(
+            slugger.slug("Base Commands (0-19, 250)"),
+            "base-commands-0-19-250"
+        );
+        assert_eq!(slugger.slug("`CMD_PING`"), "cmd_ping");
+        assert_eq!(slugger.slug("Größe"), "größe");
+        assert_eq!(slugger.slug("`CMD_PING`"), "cmd_ping-1");
+        assert_eq!(slugger.slug("cmd_ping-1"), "cmd_ping-1-1");
+        assert_eq!(slugger.slug("CMD_PING"), "cmd_ping-2");
+    }
+
+    #[test]
+    fn test_table_of_contents() {
+        let (metadata, messages) = crate::parse_str(
+            r#"{ "types": { "CMD_POSE": { "fields": { "x": { "type": "f32" } } } },
+                 "pose": { "packet_id": 1, "msg_type": "struct",
+                           "fields": { "p": { "type": "CMD_POSE" } } } }"#,
+        )
+        .unwrap();
+        let doc = generate(&metadata, &messages, Path::new("p.json")).unwrap();
+        assert!(doc.contains(
+            "## Contents\n\n\
+             - [Commands](#commands)\n\
+             - [Message Details](#message-details)\n\
+             \x20\x20- [`CMD_POSE`](#cmd_pose)\n\
+             - [Shared Types](#shared-types)\n\
+             \x20\x20- [`CMD_POSE`](#cmd_pose-1)\n\n## Commands\n"
+        ));
+        // The command links to its details, not to the shared type
+        assert!(doc.contains("| [`CMD_POSE`](#cmd_pose) | 1 |"));
+
+        let options = MarkdownOptions {
+            no_toc: true,
+            ..Default::default()
+        };
+        let doc =
+            generate_with_options(&metadata, &messages, Path::new("p.json"), &options).unwrap();
+        assert!(!doc.contains("## Contents"));
+        assert!(doc.contains("| [`CMD_POSE`](#cmd_pose) | 1 |"));
+    }
+
+    #[test]
+    fn test_client_documentation() {
+        let (metadata, messages) = crate::parse_str(
+            r#"{ "ping": { "packet_id": 1, "msg_type": "u8" },
+                 "temp": { "packet_id": 2, "msg_type": "f32", "target_client_id": 3 },
+                 "speed": { "packet_id": 3, "msg_type": "i16", "target_client_id": 3,
+                            "request_type": "sub" },
+                 "other": { "packet_id": 4, "msg_type": "u8", "target_client_id": 5 } }"#,
+        )
+        .unwrap();
+        let options = MarkdownOptions {
+            client_id: Some(3),
+            ..Default::default()
+        };
+        let doc =
+            generate_with_options(&metadata, &messages, Path::new("p.json"), &options).unwrap();
+        assert!(doc.contains("Client: 3 ("));
+        assert!(doc.contains(
+            "| [`CMD_PING`](#cmd_ping) | 1 | receive | unspecified | No description |\n"
+        ));
+        assert!(doc.contains("| [`CMD_TEMP`](#cmd_temp) | 2 | receive | 3 | No description |\n"));
+        assert!(doc.contains("| [`CMD_SPEED`](#cmd_speed) | 3 | send | 3 | No description |\n"));
+        assert!(!doc.contains("CMD_OTHER"));
+        // Still the fingerprint of the whole protocol
+        assert!(doc.contains(&ir_fingerprint(&metadata, &messages)));
+
+        let full = generate(&metadata, &messages, Path::new("p.json")).unwrap();
+        assert!(full.contains("| [`CMD_SPEED`](#cmd_speed) | 3 | client → server | 3 |"));
+
+        let options = MarkdownOptions {
+            client_id: Some(4),
+            ..Default::default()
+        };
+        let err =
+            generate_with_options(&metadata, &messages, Path::new("p.json"), &options).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("no message targets client 4 (clients: 3, 5)")
+        );
+    }
+
+    #[test]
+    fn test_direction_and_target_columns() {
+        let (metadata, messages) = crate::parse_str(
+            r#"{ "status": { "packet_id": 1, "msg_type": "u8", "request_type": "pub",
+                             "target_client_id": -1 },
+                 "cmd": { "packet_id": 2, "msg_type": "u8", "request_type": "sub",
+                          "target_client_id": 7 },
+                 "legacy": { "packet_id": 3, "msg_type": "u8" } }"#,
+        )
+        .unwrap();
+        let doc = generate(&metadata, &messages, Path::new("p.json")).unwrap();
+        assert!(doc.contains(
+            "| Command | Value | Direction | Target | Description |\n|---------|-------|-----------|--------|-------------|\n"
+        ));
+        assert!(doc.contains(
+            "| [`CMD_STATUS`](#cmd_status) | 1 | server → client | broadcast | No description |\n"
+        ));
+        assert!(
+            doc.contains("| [`CMD_CMD`](#cmd_cmd) | 2 | client → server | 7 | No description |\n")
+        );
+        assert!(doc.contains(
+            "| [`CMD_LEGACY`](#cmd_legacy) | 3 | unspecified | unspecified | No description |\n"
+        ));
+
+        // Messages built in Rust are unspecified until the setters are called
+        let built = [
+            MessageDefinition::scalar("a", 1, PrimitiveType::Uint8)
+                .build()
+                .unwrap(),
+            MessageDefinition::scalar("b", 2, PrimitiveType::Uint8)
+                .request_type(RequestType::Sub)
+                .target_client_id(-1)
+                .build()
+                .unwrap(),
+        ];
+        let doc = generate(&Metadata::default(), &built, Path::new("p.json")).unwrap();
+        assert!(doc.contains("| [`CMD_A`](#cmd_a) | 1 | unspecified | unspecified |"));
+        assert!(doc.contains("| [`CMD_B`](#cmd_b) | 2 | client → server | broadcast |"));
+    }
+
+    #[test]
+    fn test_responds_with_column() {
+        let (metadata, messages) = crate::parse_str(
+            r#"{ "get": { "packet_id": 1, "msg_type": "u8", "response": "reply" },
+                 "reply": { "packet_id": 2, "msg_type": "u8" } }"#,
+        )
+        .unwrap();
+        let doc = generate(&metadata, &messages, Path::new("p.json")).unwrap();
+        assert!(
+            doc.contains(
+                "| Command | Value | Direction | Target | Description | Responds with |\n"
+            )
+        );
+        assert!(doc.contains(
+            "| [`CMD_GET`](#cmd_get) | 1 | unspecified | unspecified | No description | [`CMD_REPLY`](#cmd_reply) |\n"
+        ));
+        assert!(doc.contains(
+            "| [`CMD_REPLY`](#cmd_reply) | 2 | unspecified | unspecified | No description |  |\n"
+        ));
+
+        let (metadata, messages) =
+            crate::parse_str(r#"{ "get": { "packet_id": 1, "msg_type": "u8" } }"#).unwrap();
+        let doc = generate(&metadata, &messages, Path::new("p.json")).unwrap();
+        assert!(!doc.contains("Responds with"));
+    }
+
+    #[test]
+    fn test_message_groups_build_the_sections() {
+        let (metadata, messages) = crate::parse_str(
+            r#"{ "ping": { "packet_id": 1, "msg_type": "u8" },
+                 "speed": { "packet_id": 2, "msg_type": "u16", "group": "motor" },
+                 "led": { "packet_id": 3, "msg_type": "bool", "group": "io" },
+                 "stop": { "packet_id": 4, "msg_type": "u8", "group": "motor" } }"#,
+        )
+        .unwrap();
+        let doc = generate(&metadata, &messages, Path::new("p.json")).unwrap();
+        let motor = doc.find("## motor\n").unwrap();
+        let io = doc.find("## io\n").unwrap();
+        let ungrouped = doc.find("## Ungrouped\n").unwrap();
+        assert!(motor < io && io < ungrouped);
+        assert!(doc[motor..io].contains("| 2 |"));
+        assert!(doc[motor..io].contains("| 4 |"));
+        assert!(doc[io..ungrouped].contains("| 3 |"));
+        assert!(doc[ungrouped..].contains("| 1 |"));
+    }
+
+    #[test]
+    fn test_wide_packet_ids_show_hex() {
+        let (metadata, messages) = crate::parse_str(
+            r#"{ "packet_id_width": 16, "max_address": 4096,
+                 "value": { "packet_id": 257, "msg_type": "u16" } }"#,
+        )
+        .unwrap();
+        let doc = generate(&metadata, &messages, Path::new("p.json")).unwrap();
+        assert!(doc.contains("Max address: 0x1000 (4096)\nPacket ID width: 16 bits\n"));
+        assert!(doc.contains("| [`CMD_VALUE`](#cmd_value) | 0x0101 (257) | "));
+        assert!(doc.contains("- Packet ID: 0x0101 (257)\n"));
+    }
+
+    #[test]
+    fn test_packet_ids_keep_their_radix() {
+        let (metadata, messages) = crate::parse_str(
+            r#"{ "hex": { "packet_id": "0x2a", "msg_type": "u8" },
+                 "bin": { "packet_id": "0b101", "msg_type": "u8" },
+                 "dec": { "packet_id": "7", "msg_type": "u8" } }"#,
+        )
+        .unwrap();
+        let doc = generate(&metadata, &messages, Path::new("p.json")).unwrap();
+        assert!(doc.contains("| [`CMD_HEX`](#cmd_hex) | 0x2A (42) | "));
+        assert!(doc.contains("| [`CMD_BIN`](#cmd_bin) | 0b101 (5) | "));
+        assert!(doc.contains("| [`CMD_DEC`](#cmd_dec) | 7 | "));
+        assert!(doc.contains("- Packet ID: 0x2A (42)\n"));
+    }
 }
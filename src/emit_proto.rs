@@ -0,0 +1,478 @@
+//! Protocol Buffers (`.proto`, proto3) generator for message definitions.
+//!
+//! The schema describes the same messages for tools that speak protobuf;
+//! it does not describe the serial wire format. Every message becomes a
+//! `message` with its fields numbered in declaration order, and the packet
+//! IDs become the `PacketId` enum. Protobuf has no 8 or 16-bit integers and
+//! no byte order, so a comment on each field records the original type,
+//! size and endianness. A union becomes a `oneof` of its variants.
+
+use std::collections::HashMap;
+use std::fmt::Write as FmtWrite;
+
+use crate::emit_c::{struct_byte_len, struct_min_byte_len};
+use crate::emit_ir::collect_shared;
+use crate::fingerprint::GENERATOR;
+use crate::{
+    Endian, IdlError, MessageBody, MessageDefinition, Metadata, PrimitiveType, Scaling,
+    StructFieldType, StructSpec, UnionSpec, ValueRange, ir_fingerprint, message_body_max_size,
+    message_body_min_size, to_pascal_case, to_snake_case,
+};
+
+/// Generates the `.proto` of `messages` in protobuf package `package`.
+pub fn generate(
+    metadata: &Metadata,
+    messages: &[MessageDefinition],
+    package: &str,
+) -> Result<String, IdlError> {
+    let mut out = String::new();
+    writeln!(out, "// Auto-generated by {}.", GENERATOR).unwrap();
+    writeln!(
+        out,
+        "// IR fingerprint: {}",
+        ir_fingerprint(metadata, messages)
+    )
+    .unwrap();
+    if let Some(version) = &metadata.version {
+        writeln!(out, "// Protocol version {}", version).unwrap();
+    }
+    writeln!(out).unwrap();
+    writeln!(out, "syntax = \"proto3\";").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "package {};", field_name(package)).unwrap();
+
+    let mut sorted: Vec<&MessageDefinition> = messages.iter().collect();
+    sorted.sort_by_key(|msg| msg.packet_id);
+    let names = MessageNames::new(&sorted);
+
+    writeln!(out).unwrap();
+    writeln!(out, "// Packet ID of each message").unwrap();
+    writeln!(out, "enum PacketId {{").unwrap();
+    // proto3 enums must start with a zero value
+    if sorted.first().is_none_or(|msg| msg.packet_id != 0) {
+        writeln!(out, "  PACKET_ID_UNSPECIFIED = 0;").unwrap();
+    }
+    for msg in &sorted {
+        writeln!(out, "  {} = {};", packet_id_value(&msg.name), msg.packet_id).unwrap();
+    }
+    writeln!(out, "}}").unwrap();
+
+    for msg in &sorted {
+        writeln!(out).unwrap();
+        if let Some(description) = &msg.description {
+            writeln!(out, "// {}", description).unwrap();
+        }
+        writeln!(
+            out,
+            "// packet_id {}, {}, {}",
+            msg.packet_id,
+            msg.request_type.name(),
+            size_text(
+                message_body_min_size(&msg.body),
+                message_body_max_size(&msg.body)
+            )
+        )
+        .unwrap();
+        writeln!(out, "message {} {{", names.message(&msg.name)).unwrap();
+        if msg.deprecated.is_some() {
+            writeln!(out, "  option deprecated = true;").unwrap();
+        }
+        match &msg.body {
+            MessageBody::Scalar(spec) => {
+                let comment =
+                    primitive_comment(spec.primitive, spec.endian, spec.scaling, spec.range);
+                write_field(
+                    &mut out,
+                    1,
+                    &doc_text(&spec.description, &spec.unit),
+                    &format!("{} value = 1;", scalar_type(spec.primitive)),
+                    &comment,
+                );
+            }
+            MessageBody::Array(spec) => {
+                let mut comment =
+                    array_comment(spec.primitive, spec.endian, spec.max_length, false);
+                if let Some(scaling) = spec.scaling {
+                    comment.push_str(&scaling_text(scaling));
+                }
+                write_field(
+                    &mut out,
+                    1,
+                    &doc_text(&spec.description, &spec.unit),
+                    &format!("{} data = 1;", array_type(spec.primitive)),
+                    &comment,
+                );
+            }
+            MessageBody::Struct(spec) => write_fields(&mut out, spec, 1, &names),
+            MessageBody::Union(spec) => write_variants(&mut out, spec, &names),
+        }
+        writeln!(out, "}}").unwrap();
+    }
+
+    for spec in &names.shared_specs {
+        writeln!(out).unwrap();
+        writeln!(
+            out,
+            "// Shared type {}, {}",
+            spec.shared.as_deref().unwrap_or_default(),
+            size_text(struct_min_byte_len(spec), struct_byte_len(spec))
+        )
+        .unwrap();
+        writeln!(out, "message {} {{", names.shared(spec)).unwrap();
+        write_fields(&mut out, spec, 1, &names);
+        writeln!(out, "}}").unwrap();
+    }
+    Ok(out)
+}
+
+/// Protobuf names of the message and shared types; shared types whose name
+/// a message already has get a `Type` suffix.
+struct MessageNames<'a> {
+    messages: HashMap<&'a str, String>,
+    shared: HashMap<&'a str, String>,
+    /// Every shared type used as a field, each after the types it contains
+    shared_specs: Vec<&'a StructSpec>,
+}
+
+impl<'a> MessageNames<'a> {
+    fn new(messages: &[&'a MessageDefinition]) -> Self {
+        let mut names = MessageNames {
+            messages: HashMap::new(),
+            shared: HashMap::new(),
+            shared_specs: Vec::new(),
+        };
+        for msg in messages {
+            names
+                .messages
+                .insert(msg.name.as_str(), to_pascal_case(&msg.name));
+            match &msg.body {
+                MessageBody::Struct(spec) => {
+                    for field in &spec.fields {
+                        if let StructFieldType::Nested(nested) = &field.field_type {
+                            collect_shared(nested, &mut names.shared_specs);
+                        }
+                    }
+                }
+                // A shared type as a variant is a field of the `oneof`
+                MessageBody::Union(spec) => {
+                    for variant in &spec.variants {
+                        collect_shared(&variant.spec, &mut names.shared_specs);
+                    }
+                }
+                MessageBody::Scalar(_) | MessageBody::Array(_) => {}
+            }
+        }
+        for spec in &names.shared_specs {
+            let shared = spec.shared.as_deref().unwrap_or_default();
+            let mut name = to_pascal_case(shared);
+            if names.messages.values().any(|taken| *taken == name) {
+                name.push_str("Type");
+            }
+            names.shared.insert(shared, name);
+        }
+        names
+    }
+
+    fn message(&self, name: &str) -> String {
+        self.messages[name].clone()
+    }
+
+    fn shared(&self, spec: &StructSpec) -> String {
+        self.shared[spec.shared.as_deref().unwrap_or_default()].clone()
+    }
+}
+
+/// Writes the fields of `spec` numbered from 1 in declaration order, with
+/// the nested structs defined in place as nested messages first. Reserved
+/// gaps are not fields and take no number.
+fn write_fields(out: &mut String, spec: &StructSpec, depth: usize, names: &MessageNames) {
+    let indent = "  ".repeat(depth);
+    for field in &spec.fields {
+        if let StructFieldType::Nested(nested) = &field.field_type
+            && nested.shared.is_none()
+        {
+            writeln!(out, "{}message {} {{", indent, to_pascal_case(&field.name)).unwrap();
+            write_fields(out, nested, depth + 1, names);
+            writeln!(out, "{}}}", indent).unwrap();
+        }
+    }
+    let fields = spec.fields.iter().filter(|field| !field.reserved);
+    for (index, field) in fields.enumerate() {
+        let number = index + 1;
+        let name = field_name(&field.name);
+        let (declaration, comment) = match &field.field_type {
+            StructFieldType::Primitive(primitive) => (
+                format!("{} {} = {};", scalar_type(*primitive), name, number),
+                primitive_comment(*primitive, field.endian, field.scaling, field.range),
+            ),
+            StructFieldType::Array(array) => {
+                let mut comment =
+                    array_comment(array.primitive, field.endian, array.max_length, array.fixed);
+                if let Some(scaling) = field.scaling {
+                    comment.push_str(&scaling_text(scaling));
+                }
+                (
+                    format!("{} {} = {};", array_type(array.primitive), name, number),
+                    comment,
+                )
+            }
+            StructFieldType::Bytes(length) => (
+                format!("bytes {} = {};", name, number),
+                format!("bytes[{}], {}", length, size_text(*length, *length)),
+            ),
+            StructFieldType::Nested(nested) => {
+                let type_name = match &nested.shared {
+                    Some(_) => names.shared(nested),
+                    None => to_pascal_case(&field.name),
+                };
+                let mut comment = format!(
+                    "struct, {}",
+                    size_text(struct_min_byte_len(nested), struct_byte_len(nested))
+                );
+                if let Some(shared) = &nested.shared {
+                    write!(comment, ", shared type {}", shared).unwrap();
+                }
+                (format!("{} {} = {};", type_name, name, number), comment)
+            }
+        };
+        write_field(
+            out,
+            depth,
+            &doc_text(&field.description, &field.unit),
+            &declaration,
+            &comment,
+        );
+    }
+}
+
+/// Writes the variants of a union as a `oneof`, numbered from 1 in
+/// declaration order, with the variants defined in place as nested
+/// messages first. The tag itself is implied by the `oneof` case.
+fn write_variants(out: &mut String, spec: &UnionSpec, names: &MessageNames) {
+    for variant in &spec.variants {
+        if variant.spec.shared.is_none() {
+            writeln!(out, "  message {} {{", to_pascal_case(&variant.name)).unwrap();
+            write_fields(out, &variant.spec, 2, names);
+            writeln!(out, "  }}").unwrap();
+        }
+    }
+    writeln!(
+        out,
+        "  // Tag: {}",
+        primitive_comment(spec.tag, spec.endian, None, None)
+    )
+    .unwrap();
+    writeln!(out, "  oneof variant {{").unwrap();
+    for (index, variant) in spec.variants.iter().enumerate() {
+        let type_name = match &variant.spec.shared {
+            Some(_) => names.shared(&variant.spec),
+            None => to_pascal_case(&variant.name),
+        };
+        let mut comment = format!(
+            "tag {}, {}",
+            variant.tag,
+            size_text(
+                struct_min_byte_len(&variant.spec),
+                struct_byte_len(&variant.spec)
+            )
+        );
+        if let Some(shared) = &variant.spec.shared {
+            write!(comment, ", shared type {}", shared).unwrap();
+        }
+        write_field(
+            out,
+            2,
+            &variant.description,
+            &format!(
+                "{} {} = {};",
+                type_name,
+                field_name(&variant.name),
+                index + 1
+            ),
+            &comment,
+        );
+    }
+    writeln!(out, "  }}").unwrap();
+}
+
+fn write_field(
+    out: &mut String,
+    depth: usize,
+    doc: &Option<String>,
+    declaration: &str,
+    comment: &str,
+) {
+    let indent = "  ".repeat(depth);
+    if let Some(doc) = doc {
+        writeln!(out, "{}// {}", indent, doc).unwrap();
+    }
+    writeln!(out, "{}{} // {}", indent, declaration, comment).unwrap();
+}
+
+/// Protobuf type of a single value: the narrowest 32 or 64-bit type that
+/// holds every value of `primitive`.
+fn scalar_type(primitive: PrimitiveType) -> &'static str {
+    match primitive {
+        PrimitiveType::Bool => "bool",
+        PrimitiveType::Char
+        | PrimitiveType::Uint8
+        | PrimitiveType::Uint16
+        | PrimitiveType::Uint24
+        | PrimitiveType::Uint32 => "uint32",
+        PrimitiveType::Int8
+        | PrimitiveType::Int16
+        | PrimitiveType::Int24
+        | PrimitiveType::Int32 => "int32",
+        PrimitiveType::Uint64 => "uint64",
+        PrimitiveType::Int64 => "int64",
+        PrimitiveType::Float16 | PrimitiveType::Float32 => "float",
+        PrimitiveType::Float64 => "double",
+    }
+}
+
+/// Protobuf type of an array: text for `char`, raw bytes for `uint8`, and a
+/// repeated field otherwise.
+fn array_type(primitive: PrimitiveType) -> String {
+    match primitive {
+        PrimitiveType::Char => "string".to_string(),
+        PrimitiveType::Uint8 => "bytes".to_string(),
+        other => format!("repeated {}", scalar_type(other)),
+    }
+}
+
+/// The wire type of a single value, e.g. `int16, 2 bytes, big endian`.
+fn primitive_comment(
+    primitive: PrimitiveType,
+    endian: Endian,
+    scaling: Option<Scaling>,
+    range: Option<ValueRange>,
+) -> String {
+    let mut comment = format!(
+        "{}, {}",
+        primitive.name(),
+        size_text(primitive.byte_len(), primitive.byte_len())
+    );
+    if primitive.byte_len() > 1 {
+        write!(comment, ", {} endian", endian.name()).unwrap();
+    }
+    if let Some(scaling) = scaling {
+        comment.push_str(&scaling_text(scaling));
+    }
+    let range = range.unwrap_or_default();
+    if let Some(min) = range.min {
+        write!(comment, ", min {}", min).unwrap();
+    }
+    if let Some(max) = range.max {
+        write!(comment, ", max {}", max).unwrap();
+    }
+    comment
+}
+
+/// The wire type of an array, e.g. `uint16[12], up to 24 bytes, little
+/// endian`.
+fn array_comment(
+    primitive: PrimitiveType,
+    endian: Endian,
+    max_length: usize,
+    fixed: bool,
+) -> String {
+    let max_size = max_length * primitive.byte_len();
+    let mut comment = format!(
+        "{}[{}], {}",
+        primitive.name(),
+        max_length,
+        size_text(if fixed { max_size } else { 0 }, max_size)
+    );
+    if primitive.byte_len() > 1 {
+        write!(comment, ", {} endian", endian.name()).unwrap();
+    }
+    comment
+}
+
+fn scaling_text(scaling: Scaling) -> String {
+    format!(", scale {:?}, offset {:?}", scaling.scale, scaling.offset)
+}
+
+fn size_text(min: usize, max: usize) -> String {
+    match (min, max) {
+        (1, 1) => "1 byte".to_string(),
+        (min, max) if min == max => format!("{} bytes", max),
+        (0, max) => format!("up to {} bytes", max),
+        (min, max) => format!("{}-{} bytes", min, max),
+    }
+}
+
+/// The description with the unit in brackets, e.g. `motor speed [rpm]`.
+fn doc_text(description: &Option<String>, unit: &Option<String>) -> Option<String> {
+    match (description, unit) {
+        (Some(description), Some(unit)) => Some(format!("{} [{}]", description, unit)),
+        (Some(description), None) => Some(description.clone()),
+        (None, Some(unit)) => Some(format!("[{}]", unit)),
+        (None, None) => None,
+    }
+}
+
+/// A field or package name: snake case starting with a letter.
+pub(crate) fn field_name(name: &str) -> String {
+    let snake = to_snake_case(name);
+    match snake.chars().next() {
+        Some(c) if c.is_ascii_alphabetic() => snake,
+        _ => format!("n_{}", snake.trim_start_matches('_')),
+    }
+}
+
+/// The `PacketId` value of a message, e.g. `PACKET_ID_MOTOR_SPEED`.
+fn packet_id_value(name: &str) -> String {
+    format!(
+        "PACKET_ID_{}",
+        to_snake_case(name)
+            .trim_start_matches('_')
+            .to_ascii_uppercase()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_types_keep_the_wire_format_in_comments() {
+        let (metadata, messages) = crate::parse_str(
+            r#"{ "version": "2.1.0", "packets": {
+                 "motor-status": { "packet_id": 7, "msg_type": "struct", "fields": {
+                     "speed": { "type": "i16", "endianess": "big", "unit": "rpm", "min": -100 },
+                     "label": { "type": "char", "array": true, "max_length": 8 },
+                     "limits": { "type": "struct", "fields": {
+                         "current": { "type": "f32" },
+                         "samples": { "type": "u16", "array": true, "max_length": 4 } } } } },
+                 "raw": { "packet_id": 9, "msg_type": "u8", "array": true, "max_length": 16 } } }"#,
+        )
+        .unwrap();
+        let proto = generate(&metadata, &messages, "commands").unwrap();
+        assert!(proto.contains("syntax = \"proto3\";\n\npackage commands;\n"));
+        assert!(proto.contains(
+            "enum PacketId {\n  PACKET_ID_UNSPECIFIED = 0;\n  PACKET_ID_MOTOR_STATUS = 7;\n  PACKET_ID_RAW = 9;\n}\n"
+        ));
+        assert!(proto.contains(
+            "message MotorStatus {\n  message Limits {\n    float current = 1; // float32, 4 bytes, little endian\n    repeated uint32 samples = 2; // uint16[4], up to 8 bytes, little endian\n  }\n"
+        ));
+        assert!(proto.contains(
+            "  // [rpm]\n  int32 speed = 1; // int16, 2 bytes, big endian, min -100\n  string label = 2; // char[8], up to 8 bytes\n  Limits limits = 3; // struct, 4-12 bytes\n}\n"
+        ));
+        assert!(proto.contains(
+            "// packet_id 9, pub, up to 16 bytes\nmessage Raw {\n  bytes data = 1; // uint8[16], up to 16 bytes\n}\n"
+        ));
+    }
+
+    #[test]
+    fn test_packet_id_zero_is_the_enum_default() {
+        let (metadata, messages) = crate::parse_str(
+            r#"{ "packets": { "ping": { "packet_id": 0, "msg_type": "bool" } } }"#,
+        )
+        .unwrap();
+        let proto = generate(&metadata, &messages, "2nd").unwrap();
+        assert!(proto.contains("package n_2nd;\n"));
+        assert!(proto.contains("enum PacketId {\n  PACKET_ID_PING = 0;\n}\n"));
+        assert!(proto.contains("  bool value = 1; // bool, 1 byte\n"));
+    }
+}
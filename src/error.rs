@@ -0,0 +1,356 @@
+//! Structured errors returned by the parser and the generators.
+//!
+//! Every input problem carries the JSON pointer (RFC 6901) of the offending
+//! node plus the structured data a tool needs to react to it, while
+//! `Display` keeps the human readable wording printed by the CLI.
+
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+/// An error found in the input document or while generating code.
+///
+/// Errors about the input print as `<pointer>: <message>`, or the bare
+/// message when the pointer is the document root.
+#[derive(Debug, thiserror::Error)]
+pub enum IdlError {
+    /// A required key is absent from the object at `path`.
+    #[error("{}", pointed(path, message))]
+    MissingField {
+        path: String,
+        field: String,
+        message: String,
+    },
+    /// The value at `path` has the wrong JSON type or names an unknown type.
+    #[error("{}", pointed(path, message))]
+    InvalidType {
+        path: String,
+        found: String,
+        message: String,
+    },
+    /// The value at `path` has the right type but is not allowed.
+    #[error("{}", pointed(path, message))]
+    InvalidValue {
+        path: String,
+        found: String,
+        message: String,
+    },
+    /// A numeric value at `path` is above its limit.
+    #[error("{}", pointed(path, message))]
+    LimitExceeded {
+        path: String,
+        value: u64,
+        max: u64,
+        message: String,
+    },
+    /// A key that is not part of the format (typo or misplaced definition).
+    #[error("{}", pointed(path, message))]
+    UnknownKey {
+        path: String,
+        key: String,
+        message: String,
+    },
+    /// Keys that must not appear together.
+    #[error("{}", pointed(path, message))]
+    ConflictingKeys {
+        path: String,
+        keys: Vec<String>,
+        message: String,
+    },
+    /// The input is not well-formed JSON (or TOML).
+    #[error("failed to parse intermediate representation: {message}")]
+    Syntax {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    /// An input file could not be read.
+    #[error("failed to read input {}: {source}", path.display())]
+    Read { path: PathBuf, source: io::Error },
+    /// Following the `include` at `path` would load a file that is already
+    /// being loaded. `chain` runs from that file back to itself.
+    #[error("{}", pointed(path, &include_cycle(chain)))]
+    IncludeCycle { path: String, chain: Vec<PathBuf> },
+    /// An error in an included file rather than in the file being loaded.
+    /// The file comes before the pointer into it.
+    #[error("{}: {error}", file.display())]
+    InFile {
+        file: PathBuf,
+        #[source]
+        error: Box<IdlError>,
+    },
+    /// A template file could not be read.
+    #[error("failed to read template {}: {source}", path.display())]
+    Io { path: PathBuf, source: io::Error },
+    /// Generated output could not be written.
+    #[error("failed to write output to {}: {source}", path.display())]
+    Write { path: PathBuf, source: io::Error },
+    /// A template contains an unknown or malformed placeholder.
+    #[error("template {file}: {message}")]
+    Template { file: String, message: String },
+    /// The template directory given as an override does not exist.
+    #[error("template directory '{dir}' for language {language} does not exist")]
+    TemplateNotFound { dir: String, language: String },
+    /// Several input problems, in document order.
+    #[error("{}", list_errors(.0))]
+    Multiple(Vec<IdlError>),
+}
+
+impl IdlError {
+    /// JSON pointer of the offending node, for errors about the input.
+    ///
+    /// An empty pointer refers to the whole document.
+    pub fn pointer(&self) -> Option<&str> {
+        match self {
+            IdlError::MissingField { path, .. }
+            | IdlError::InvalidType { path, .. }
+            | IdlError::InvalidValue { path, .. }
+            | IdlError::LimitExceeded { path, .. }
+            | IdlError::UnknownKey { path, .. }
+            | IdlError::ConflictingKeys { path, .. }
+            | IdlError::IncludeCycle { path, .. } => Some(path),
+            IdlError::InFile { error, .. } => error.pointer(),
+            IdlError::Syntax { .. }
+            | IdlError::Read { .. }
+            | IdlError::Io { .. }
+            | IdlError::Write { .. }
+            | IdlError::Template { .. }
+            | IdlError::TemplateNotFound { .. }
+            | IdlError::Multiple(_) => None,
+        }
+    }
+
+    /// Human readable description without the pointer.
+    pub fn message(&self) -> String {
+        match self {
+            IdlError::MissingField { message, .. }
+            | IdlError::InvalidType { message, .. }
+            | IdlError::InvalidValue { message, .. }
+            | IdlError::LimitExceeded { message, .. }
+            | IdlError::UnknownKey { message, .. }
+            | IdlError::ConflictingKeys { message, .. } => message.clone(),
+            IdlError::IncludeCycle { chain, .. } => include_cycle(chain),
+            IdlError::InFile { file, error } => format!("{}: {}", file.display(), error.message()),
+            // Errors without a pointer display as their bare message
+            other => other.to_string(),
+        }
+    }
+
+    /// Individual errors: the inner list of [`IdlError::Multiple`], or `self`.
+    pub fn errors(&self) -> &[IdlError] {
+        match self {
+            IdlError::Multiple(errors) => errors,
+            other => std::slice::from_ref(other),
+        }
+    }
+
+    /// Combines collected errors, unwrapping a single one.
+    pub(crate) fn from_list(mut errors: Vec<IdlError>) -> Self {
+        if errors.len() == 1 {
+            errors.remove(0)
+        } else {
+            IdlError::Multiple(errors)
+        }
+    }
+
+    /// Marks an error as found in the included `file`. Errors already
+    /// marked keep the innermost file.
+    pub(crate) fn in_file(self, file: &Path) -> Self {
+        match self {
+            IdlError::InFile { .. } => self,
+            error => IdlError::InFile {
+                file: file.to_path_buf(),
+                error: Box::new(error),
+            },
+        }
+    }
+
+    pub(crate) fn syntax(error: &serde_json::Error) -> Self {
+        IdlError::Syntax {
+            line: error.line(),
+            column: error.column(),
+            message: error.to_string(),
+        }
+    }
+
+    /// A TOML syntax error in `input`, with the 1-based position of its span.
+    pub(crate) fn toml_syntax(input: &str, error: &toml::de::Error) -> Self {
+        let offset = error.span().map_or(0, |span| span.start).min(input.len());
+        let before = &input[..input.floor_char_boundary(offset)];
+        let line = before.matches('\n').count() + 1;
+        let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+        IdlError::Syntax {
+            line,
+            column,
+            message: format!(
+                "{} at line {} column {}",
+                error.message().trim_end(),
+                line,
+                column
+            ),
+        }
+    }
+
+    pub(crate) fn write(path: &Path, source: io::Error) -> Self {
+        IdlError::Write {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+
+    pub(crate) fn missing_field(path: &str, field: &str, message: impl Into<String>) -> Self {
+        IdlError::MissingField {
+            path: path.to_string(),
+            field: field.to_string(),
+            message: message.into(),
+        }
+    }
+
+    pub(crate) fn invalid_type(path: &str, found: &Value, message: impl Into<String>) -> Self {
+        IdlError::InvalidType {
+            path: path.to_string(),
+            found: describe_value(found),
+            message: message.into(),
+        }
+    }
+
+    pub(crate) fn unknown_type_name(path: &str, name: &str, message: impl Into<String>) -> Self {
+        IdlError::InvalidType {
+            path: path.to_string(),
+            found: name.to_string(),
+            message: message.into(),
+        }
+    }
+
+    pub(crate) fn invalid_value(
+        path: &str,
+        found: impl fmt::Display,
+        message: impl Into<String>,
+    ) -> Self {
+        IdlError::InvalidValue {
+            path: path.to_string(),
+            found: found.to_string(),
+            message: message.into(),
+        }
+    }
+
+    pub(crate) fn limit_exceeded(
+        path: &str,
+        value: usize,
+        max: usize,
+        message: impl Into<String>,
+    ) -> Self {
+        IdlError::LimitExceeded {
+            path: path.to_string(),
+            value: value as u64,
+            max: max as u64,
+            message: message.into(),
+        }
+    }
+
+    pub(crate) fn unknown_key(path: &str, key: &str, message: impl Into<String>) -> Self {
+        IdlError::UnknownKey {
+            path: path.to_string(),
+            key: key.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+/// `message` prefixed with the pointer unless it refers to the whole document.
+fn pointed(path: &str, message: &str) -> String {
+    if path.is_empty() {
+        message.to_string()
+    } else {
+        format!("{}: {}", path, message)
+    }
+}
+
+fn include_cycle(chain: &[PathBuf]) -> String {
+    let files: Vec<String> = chain.iter().map(|f| f.display().to_string()).collect();
+    format!("include cycle: {}", files.join(" -> "))
+}
+
+fn list_errors(errors: &[IdlError]) -> String {
+    let lines: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+    format!(
+        "{} errors in message definitions:\n  {}",
+        errors.len(),
+        lines.join("\n  ")
+    )
+}
+
+/// Short description of a JSON value for error reports: scalars verbatim,
+/// containers by type.
+fn describe_value(value: &Value) -> String {
+    match value {
+        Value::Null => "null".to_string(),
+        Value::Array(_) => "array".to_string(),
+        Value::Object(_) => "object".to_string(),
+        Value::Bool(_) | Value::Number(_) | Value::String(_) => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_display_prefixes_pointer() {
+        let error = IdlError::missing_field("/packets/ping", "packet_id", "missing 'packet_id'");
+        assert_eq!(error.to_string(), "/packets/ping: missing 'packet_id'");
+
+        let root = IdlError::missing_field("", "packets", "missing 'packets'");
+        assert_eq!(root.to_string(), "missing 'packets'");
+        assert_eq!(root.pointer(), Some(""));
+    }
+
+    #[test]
+    fn test_sources_chain_to_the_inner_error() {
+        use std::error::Error;
+
+        let inner = IdlError::missing_field("/packets/ping", "packet_id", "missing 'packet_id'");
+        let error = inner.in_file(Path::new("common.json"));
+        assert_eq!(
+            error.to_string(),
+            "common.json: /packets/ping: missing 'packet_id'"
+        );
+        assert_eq!(error.message(), "common.json: missing 'packet_id'");
+        assert!(
+            error
+                .source()
+                .is_some_and(|s| s.to_string().starts_with("/packets/ping"))
+        );
+
+        let write = IdlError::write(Path::new("out.h"), io::Error::other("disk full"));
+        assert_eq!(
+            write.to_string(),
+            "failed to write output to out.h: disk full"
+        );
+        assert!(write.source().is_some());
+    }
+
+    #[test]
+    fn test_multiple_lists_every_error() {
+        let error = IdlError::from_list(vec![
+            IdlError::invalid_type("/a", &json!([1]), "bad a"),
+            IdlError::limit_exceeded("/b", 300, 255, "bad b"),
+        ]);
+        assert_eq!(error.errors().len(), 2);
+        assert_eq!(error.pointer(), None);
+        assert_eq!(
+            error.to_string(),
+            "2 errors in message definitions:\n  /a: bad a\n  /b: bad b"
+        );
+        assert!(matches!(
+            &error.errors()[0],
+            IdlError::InvalidType { found, .. } if found == "array"
+        ));
+
+        let single = IdlError::from_list(vec![IdlError::invalid_value("/c", 0, "bad c")]);
+        assert!(matches!(single, IdlError::InvalidValue { .. }));
+    }
+}
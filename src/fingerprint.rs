@@ -0,0 +1,367 @@
+//! Content hash of the parsed IR, written into every generated file.
+//!
+//! The hash covers the normalized message definitions rather than the raw
+//! JSON text, so reformatting the input or reordering its keys leaves it
+//! unchanged. Messages are hashed in packet ID order; struct fields keep
+//! their declared order because it defines the wire layout.
+
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    Bound, ConstValue, DeviceInfo, MessageBody, MessageDefinition, Metadata, RequestType, Scaling,
+    StructFieldType, StructSpec, ValueRange,
+};
+
+/// Number of hex digits of the SHA-256 digest kept in the fingerprint.
+const FINGERPRINT_LEN: usize = 16;
+
+/// Generator name and version written into generated files.
+pub(crate) const GENERATOR: &str = concat!(env!("CARGO_PKG_NAME"), " ", env!("CARGO_PKG_VERSION"));
+
+/// Short, stable hash of the IR: the first 16 hex digits of the SHA-256 of
+/// its canonical form.
+///
+/// # Example
+/// ```
+/// use h6xserial_idl::{ir_fingerprint, parse_str};
+///
+/// let (metadata, messages) = parse_str(
+///     r#"{ "packets": { "ping": { "packet_id": 1, "msg_type": "uint8" } } }"#,
+/// )
+/// .unwrap();
+/// let (metadata2, messages2) = parse_str(
+///     r#"{"packets":{"ping":{"msg_type":"uint8","packet_id":1}}}"#,
+/// )
+/// .unwrap();
+/// let fingerprint = ir_fingerprint(&metadata, &messages);
+/// assert_eq!(fingerprint.len(), 16);
+/// assert_eq!(fingerprint, ir_fingerprint(&metadata2, &messages2));
+/// ```
+pub fn ir_fingerprint(metadata: &Metadata, messages: &[MessageDefinition]) -> String {
+    let canonical = canonical_ir(metadata, messages).to_string();
+    let mut hex = sha256_hex(canonical.as_bytes());
+    hex.truncate(FINGERPRINT_LEN);
+    hex
+}
+
+/// SHA-256 of `data` as 64 lowercase hex digits.
+pub(crate) fn sha256_hex(data: &[u8]) -> String {
+    let mut hex = String::with_capacity(64);
+    for byte in Sha256::digest(data) {
+        hex.push_str(&format!("{:02x}", byte));
+    }
+    hex
+}
+
+/// How the canonical form spells a request type. The capitalized names
+/// are part of every fingerprint issued so far and must not change.
+fn request_type_key(request_type: RequestType) -> &'static str {
+    match request_type {
+        RequestType::Pub => "Pub",
+        RequestType::Sub => "Sub",
+    }
+}
+
+/// The IR as a JSON value with a fixed key order and sorted collections.
+fn canonical_ir(metadata: &Metadata, messages: &[MessageDefinition]) -> Value {
+    let mut devices: Vec<&DeviceInfo> = metadata.devices.iter().collect();
+    devices.sort_by(|a, b| (a.id, &a.name, &a.role).cmp(&(b.id, &b.name, &b.role)));
+    let devices: Vec<Value> = devices
+        .into_iter()
+        .map(|device| {
+            json!({
+                "name": device.name,
+                "role": device.role,
+                "id": device.id,
+                "description": device.description,
+            })
+        })
+        .collect();
+
+    let mut sorted: Vec<&MessageDefinition> = messages.iter().collect();
+    sorted.sort_by(|a, b| (a.packet_id, &a.name).cmp(&(b.packet_id, &b.name)));
+    let messages: Vec<Value> = sorted
+        .into_iter()
+        .map(|message| {
+            let mut value = json!({
+                "name": message.name,
+                "packet_id": message.packet_id,
+                "description": message.description,
+                "request_type": request_type_key(message.request_type),
+                "target_client_id": message.target_client_id,
+                "body": canonical_body(&message.body),
+            });
+            if message.reserved_ok {
+                value["reserved_ok"] = true.into();
+            }
+            if let Some(group) = &message.group {
+                value["group"] = group.as_str().into();
+            }
+            if let Some(response) = &message.response {
+                value["response"] = response.as_str().into();
+            }
+            if let Some(msg_version) = message.msg_version {
+                value["msg_version"] = msg_version.into();
+            }
+            if let Some(deprecation) = &message.deprecated {
+                value["deprecated"] = match &deprecation.reason {
+                    Some(reason) => reason.as_str().into(),
+                    None => true.into(),
+                };
+            }
+            value
+        })
+        .collect();
+
+    let mut ir = json!({
+        "version": metadata.version,
+        "max_address": metadata.max_address,
+        "max_payload_bytes": metadata.max_payload_bytes,
+        "max_array_length": metadata.max_array_length,
+        "devices": devices,
+        "messages": messages,
+    });
+    // Only present when used, so fingerprints of older IRs stay the same
+    if metadata.packet_id_width != Metadata::default().packet_id_width {
+        ir["packet_id_width"] = metadata.packet_id_width.into();
+    }
+    if !metadata.types.is_empty() {
+        let mut types: Vec<&StructSpec> = metadata.types.iter().collect();
+        types.sort_by(|a, b| a.shared.cmp(&b.shared));
+        ir["types"] = types.into_iter().map(canonical_struct).collect();
+    }
+    if !metadata.reserved_ids.is_empty() {
+        ir["reserved_ids"] = metadata
+            .reserved_ids
+            .iter()
+            .map(|&(first, last)| json!([first, last]))
+            .collect();
+    }
+    if !metadata.command_groups.is_empty() {
+        ir["command_groups"] = metadata
+            .command_groups
+            .iter()
+            .map(|group| json!([group.name, group.first, group.last]))
+            .collect();
+    }
+    if !metadata.constants.is_empty() {
+        let mut constants: Vec<&(String, ConstValue)> = metadata.constants.iter().collect();
+        constants.sort_by(|a, b| a.0.cmp(&b.0));
+        ir["constants"] = constants
+            .into_iter()
+            .map(|(name, value)| json!([name, value.to_string()]))
+            .collect();
+    }
+    ir
+}
+
+fn canonical_body(body: &MessageBody) -> Value {
+    match body {
+        MessageBody::Scalar(spec) => with_docs(
+            with_range(
+                with_scaling(
+                    json!({
+                        "kind": "scalar",
+                        "type": spec.primitive.wire_type(),
+                        "endian": spec.endian.suffix(),
+                    }),
+                    spec.scaling,
+                ),
+                spec.range,
+            ),
+            &spec.description,
+            &spec.unit,
+        ),
+        MessageBody::Array(spec) => with_string_semantics(
+            with_docs(
+                with_scaling(
+                    json!({
+                        "kind": "array",
+                        "type": spec.primitive.wire_type(),
+                        "endian": spec.endian.suffix(),
+                        "max_length": spec.max_length,
+                        "sector_bytes": spec.sector_bytes,
+                    }),
+                    spec.scaling,
+                ),
+                &spec.description,
+                &spec.unit,
+            ),
+            spec.string_semantics,
+        ),
+        MessageBody::Struct(spec) => canonical_struct(spec),
+        MessageBody::Union(spec) => {
+            let variants: Vec<Value> = spec
+                .variants
+                .iter()
+                .map(|variant| {
+                    let mut variant_json = json!({
+                        "name": variant.name,
+                        "tag": Bound::Int(variant.tag).to_json(),
+                        "body": canonical_struct(&variant.spec),
+                    });
+                    if let Some(description) = &variant.description {
+                        variant_json["description"] = description.as_str().into();
+                    }
+                    variant_json
+                })
+                .collect();
+            json!({
+                "kind": "union",
+                "tag": spec.tag.wire_type(),
+                "endian": spec.endian.suffix(),
+                "variants": variants,
+            })
+        }
+    }
+}
+
+/// Adds the scaling of a value, leaving unscaled values as they were.
+fn with_scaling(mut value: Value, scaling: Option<Scaling>) -> Value {
+    if let Some(scaling) = scaling {
+        value["scale"] = scaling.scale.into();
+        value["offset"] = scaling.offset.into();
+    }
+    value
+}
+
+/// Adds the `min`/`max` bounds that are set, leaving the rest as it was.
+fn with_range(mut value: Value, range: Option<ValueRange>) -> Value {
+    let Some(range) = range else {
+        return value;
+    };
+    for (key, bound) in [("min", range.min), ("max", range.max)] {
+        if let Some(bound) = bound {
+            value[key] = bound.to_json();
+        }
+    }
+    value
+}
+
+/// Adds the description and unit of a value when set.
+fn with_docs(mut value: Value, description: &Option<String>, unit: &Option<String>) -> Value {
+    if let Some(description) = description {
+        value["description"] = description.as_str().into();
+    }
+    if let Some(unit) = unit {
+        value["unit"] = unit.as_str().into();
+    }
+    value
+}
+
+/// Marks a `char` array with `string_semantics`. Left out otherwise, so
+/// the fingerprints of other arrays stay as they were.
+fn with_string_semantics(mut value: Value, string_semantics: bool) -> Value {
+    if string_semantics {
+        value["string_semantics"] = true.into();
+    }
+    value
+}
+
+fn canonical_struct(spec: &StructSpec) -> Value {
+    let fields: Vec<Value> = spec
+        .fields
+        .iter()
+        .map(|field| {
+            let field_type = match &field.field_type {
+                StructFieldType::Primitive(prim) => match field.timestamp {
+                    Some(resolution) => json!({
+                        "type": prim.wire_type(),
+                        "timestamp": resolution.name(),
+                    }),
+                    None => json!({ "type": prim.wire_type() }),
+                },
+                StructFieldType::Array(arr) if arr.fixed => json!({
+                    "type": arr.primitive.wire_type(),
+                    "fixed_length": arr.max_length,
+                }),
+                StructFieldType::Array(arr) => with_string_semantics(
+                    json!({
+                        "type": arr.primitive.wire_type(),
+                        "max_length": arr.max_length,
+                    }),
+                    arr.string_semantics,
+                ),
+                StructFieldType::Bytes(length) => json!({ "type": "bytes", "length": length }),
+                StructFieldType::Nested(nested) => canonical_struct(nested),
+            };
+            let mut value = with_docs(
+                with_range(
+                    with_scaling(
+                        json!({
+                            "name": field.name,
+                            "endian": field.endian.suffix(),
+                            "field_type": field_type,
+                        }),
+                        field.scaling,
+                    ),
+                    field.range,
+                ),
+                &field.description,
+                &field.unit,
+            );
+            if let Some(since_version) = field.since_version {
+                value["since_version"] = since_version.into();
+            }
+            value
+        })
+        .collect();
+    let mut spec_json = json!({ "kind": "struct", "fields": fields });
+    if let Some(shared) = &spec.shared {
+        spec_json["shared"] = shared.as_str().into();
+    }
+    spec_json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+
+    #[test]
+    fn test_sha256_known_vectors() {
+        assert_eq!(
+            sha256_hex(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        // Two blocks of padding
+        assert_eq!(
+            sha256_hex(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+    }
+
+    #[test]
+    fn test_fingerprint_ignores_formatting_but_not_layout() {
+        let fingerprint = |json: &str| {
+            let (metadata, messages) = parse_str(json).unwrap();
+            ir_fingerprint(&metadata, &messages)
+        };
+        let base = fingerprint(
+            r#"{ "version": "1.0", "packets": {
+                "ping": { "packet_id": 1, "msg_type": "uint8" },
+                "pose": { "packet_id": 2, "msg_type": "struct",
+                          "fields": { "x": { "type": "int16" }, "y": { "type": "int16" } } } } }"#,
+        );
+        // Other key and message order, other whitespace
+        let reordered = fingerprint(
+            r#"{"packets":{"pose":{"msg_type":"struct","fields":{"x":{"type":"int16"},"y":{"type":"int16"}},"packet_id":2},
+                "ping":{"msg_type":"u8","packet_id":1}},"version":"1.0"}"#,
+        );
+        assert_eq!(base, reordered);
+
+        // Swapping struct fields changes the wire layout
+        let swapped = fingerprint(
+            r#"{ "version": "1.0", "packets": {
+                "ping": { "packet_id": 1, "msg_type": "uint8" },
+                "pose": { "packet_id": 2, "msg_type": "struct",
+                          "fields": { "y": { "type": "int16" }, "x": { "type": "int16" } } } } }"#,
+        );
+        assert_ne!(base, swapped);
+    }
+}
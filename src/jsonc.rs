@@ -0,0 +1,107 @@
+//! JSON with comments (JSONC) input.
+//!
+//! `//` and `/* */` comments and trailing commas are blanked out with
+//! spaces before the text goes to `serde_json`. Newlines and byte offsets
+//! are kept, so syntax errors point at the right line and column of the
+//! original file.
+
+/// Returns `input` with comments and trailing commas replaced by spaces.
+/// An unterminated block comment is left in place for the JSON parser to
+/// report.
+pub(crate) fn strip(input: &str) -> String {
+    let mut bytes = input.as_bytes().to_vec();
+    let mut pos = 0;
+    // Positions of commas outside strings, checked for trailing ones below
+    let mut commas = Vec::new();
+    while pos < bytes.len() {
+        match bytes[pos] {
+            b'"' => pos = skip_string(&bytes, pos),
+            b'/' if bytes.get(pos + 1) == Some(&b'/') => {
+                while pos < bytes.len() && bytes[pos] != b'\n' {
+                    blank(&mut bytes[pos]);
+                    pos += 1;
+                }
+            }
+            b'/' if bytes.get(pos + 1) == Some(&b'*') => {
+                let Some(len) = find(&bytes[pos + 2..], b"*/") else {
+                    break;
+                };
+                let end = pos + 2 + len + 2;
+                bytes[pos..end].iter_mut().for_each(blank);
+                pos = end;
+            }
+            b',' => {
+                commas.push(pos);
+                pos += 1;
+            }
+            _ => pos += 1,
+        }
+    }
+    for comma in commas {
+        let next = bytes[comma + 1..].iter().find(|b| !b.is_ascii_whitespace());
+        if matches!(next, Some(b'}' | b']')) {
+            bytes[comma] = b' ';
+        }
+    }
+    // Only ASCII bytes and whole comments were replaced, so this is UTF-8
+    String::from_utf8(bytes).expect("blanking keeps UTF-8 valid")
+}
+
+/// Position after the string literal starting at `start`.
+fn skip_string(bytes: &[u8], start: usize) -> usize {
+    let mut pos = start + 1;
+    while pos < bytes.len() {
+        match bytes[pos] {
+            b'\\' => pos += 2,
+            b'"' => return pos + 1,
+            _ => pos += 1,
+        }
+    }
+    pos
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+/// Replaces a comment byte with a space, keeping line breaks.
+fn blank(byte: &mut u8) {
+    if *byte != b'\n' && *byte != b'\r' {
+        *byte = b' ';
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_keeps_positions() {
+        let input =
+            "{\n  // note: \"quoted\"\n  \"a\": \"x // y\", /* b */\n  \"b\": [1, 2,],\n}\n";
+        let stripped = strip(input);
+        assert_eq!(stripped.len(), input.len());
+        assert_eq!(stripped.lines().count(), input.lines().count());
+        let value: serde_json::Value = serde_json::from_str(&stripped).unwrap();
+        assert_eq!(value["a"], "x // y");
+        assert_eq!(value["b"], serde_json::json!([1, 2]));
+
+        // Multi-byte characters in comments and strings survive
+        let stripped = strip("{ \"é\": 1 /* ü\n */ }");
+        assert_eq!(stripped, "{ \"é\": 1      \n    }");
+
+        // An error after a comment reports the original line and column
+        let err = serde_json::from_str::<serde_json::Value>(&strip(
+            "{\n  /* one\n     two */ \"a\": ?\n}",
+        ))
+        .unwrap_err();
+        assert_eq!((err.line(), err.column()), (3, 18));
+    }
+
+    #[test]
+    fn test_unterminated_comment_is_left_for_the_parser() {
+        let input = "{ \"a\": 1 /* open";
+        assert_eq!(strip(input), input);
+        assert_eq!(strip("[\"a,]\", \"\\\",]\"]"), "[\"a,]\", \"\\\",]\"]");
+    }
+}
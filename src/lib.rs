@@ -3,21 +3,98 @@
 //! This library reads JSON intermediate representations and generates
 //! language-specific serializer/deserializer code for structured messages.
 
+pub mod builder;
+pub mod changes;
+mod cli;
+pub mod codec;
+mod config;
+pub mod diff;
 pub mod emit_c;
+pub mod emit_csv;
+pub mod emit_dbc;
+pub mod emit_flow;
+pub mod emit_ir;
+pub mod emit_kaitai;
 pub mod emit_markdown;
-
+pub mod emit_proto;
+pub mod error;
+mod fingerprint;
+mod jsonc;
+mod list;
+mod manifest;
+mod raw;
+mod reformat;
+mod resolve;
+mod template;
+pub mod validate;
+mod watch;
+
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::env;
+use std::fmt;
 use std::fs;
-use std::path::PathBuf;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
 
 use anyhow::{Context, Result, bail};
-use serde_json::{Map, Value};
-
-/// Maximum supported array length for safety
-const MAX_ARRAY_LENGTH: usize = 1024;
-
-/// Maximum payload size for serial packets (protocol constraint)
-const MAX_PAYLOAD_SIZE: usize = 251;
+use serde::Deserialize;
+use serde_json::{Map, Number, Value};
+
+pub use changes::{DiffReport, semantic_diff};
+pub use error::IdlError;
+pub use fingerprint::ir_fingerprint;
+use raw::{
+    Loose, RawDeprecated, RawDevice, RawField, RawMessage, RawMetadata, RawVariant,
+    parse_int_literal, typed, typed_int,
+};
+pub use resolve::{Resolver, parse_file};
+
+/// Default maximum array length (metadata `max_array_length` overrides it)
+const DEFAULT_MAX_ARRAY_LENGTH: usize = 1024;
+
+/// Default limit on struct nesting within one message (metadata
+/// `max_nesting_depth` overrides it)
+const DEFAULT_MAX_NESTING_DEPTH: usize = 8;
+
+/// Highest accepted `max_nesting_depth`. The C generator recurses once per
+/// level and refuses anything deeper.
+pub(crate) const MAX_NESTING_DEPTH_CAP: usize = 64;
+
+/// Most fields one message or shared type may have, counting the fields of
+/// its nested structs and of every union variant
+const MAX_MESSAGE_FIELDS: usize = 1024;
+
+/// Top-level keys holding protocol metadata rather than messages
+const METADATA_KEYS: &[&str] = &[
+    "ir_version",
+    "version",
+    "max_address",
+    "packet_id_width",
+    "devices",
+    "max_payload_bytes",
+    "max_array_length",
+    "max_nesting_depth",
+    "default_endianness",
+    "include",
+    "reserved_ids",
+    "command_groups",
+    "types",
+    "constants",
+];
+
+/// Top-level keys whose object holds the message definitions
+pub(crate) const MESSAGE_WRAPPER_KEYS: &[&str] = &["packets", "messages"];
+
+/// Default width of the packet ID field, in bits
+const DEFAULT_PACKET_ID_WIDTH: u8 = 8;
+
+/// Default maximum payload size for serial packets (one-byte length frame)
+const DEFAULT_MAX_PAYLOAD_BYTES: usize = 251;
+
+/// Sanity cap for a configured payload limit
+const MAX_PAYLOAD_BYTES_CAP: usize = DEFAULT_MAX_ARRAY_LENGTH * 8;
 
 /// Runs the code generator with command-line arguments.
 ///
@@ -25,1272 +102,8420 @@ const MAX_PAYLOAD_SIZE: usize = 251;
 /// * `Ok(())` - Generation succeeded
 /// * `Err(...)` - Error with context about what failed
 pub fn run() -> Result<()> {
-    let mut args: Vec<String> = env::args().skip(1).collect();
+    // clap prints --help, --version and usage errors itself
+    let cli = match cli::parse(env::args_os()).unwrap_or_else(|err| err.exit()) {
+        cli::Command::Run(cli) => *cli,
+        cli::Command::Diff(args) => return run_diff(&args),
+    };
+    if cli.positional_language {
+        eprintln!("warning: giving the language as the first argument is deprecated, use --lang");
+    }
 
-    // Check for --export_docs flag
-    let export_docs = parse_export_docs(&mut args);
+    // Defaults <- configuration file <- command line
+    let mut config = load_config(&cli.input_options)?;
+    config.apply_cli(&cli)?;
+
+    let check = cli.check;
+    let targets = match &config.languages {
+        Some(languages) => {
+            let mut targets = Vec::new();
+            for language in languages {
+                let target = Target::parse(language)?;
+                if !targets.contains(&target) {
+                    targets.push(target);
+                }
+            }
+            if targets.is_empty() {
+                bail!("no languages to generate");
+            }
+            targets
+        }
+        None => vec![Target::Code(TargetLanguage::C)],
+    };
 
-    let language = parse_language(&mut args)?;
+    let mut c_options = emit_c::CGenOptions {
+        impl_style: config.impl_style.unwrap_or_default(),
+        dialect: config.std.unwrap_or_default(),
+        encode_range: config.encode_range.unwrap_or_default(),
+        struct_layout: config.struct_layout.unwrap_or_default(),
+        package: config.package,
+        source_comment: config.source_comment.unwrap_or_default(),
+        ..Default::default()
+    };
+    c_options.misra = config.misra.unwrap_or(false);
+    c_options.doc_comments = config.doc_comments.unwrap_or(false);
+    c_options.fast_path = config.fast_path.unwrap_or(false);
+    c_options.deterministic_decode = config.deterministic_decode.unwrap_or(false);
+    c_options.all_helpers = config.all_helpers.unwrap_or(false);
+    c_options.emit_tests = config.emit_tests.unwrap_or(false);
+    c_options.emit_fuzz = config.emit_fuzz.unwrap_or(false);
+    c_options.emit_python = config.emit_python.unwrap_or(false);
+    c_options.emit_skeleton = config.emit_skeleton.unwrap_or(false);
+    // The checks below involve values the configuration file can set, so
+    // clap cannot make them
+    if c_options.emit_python && c_options.impl_style != emit_c::ImplStyle::Source {
+        bail!("--emit-python binds exported functions and needs --impl-style source");
+    }
+    if config.emit_send == Some(true) {
+        c_options.send_stack_limit = Some(
+            config
+                .send_stack_limit
+                .unwrap_or(emit_c::DEFAULT_SEND_STACK_LIMIT),
+        );
+    } else if config.send_stack_limit.is_some() {
+        bail!("--send-stack-limit only applies with --emit-send");
+    }
+    c_options.template_dir = config.template_dir.clone();
+    let mut markdown_options = emit_markdown::MarkdownOptions {
+        source_comment: c_options.source_comment,
+        no_toc: !config.toc.unwrap_or(true),
+        ..Default::default()
+    };
+    let dbc_options = emit_dbc::DbcOptions {
+        can_fd: config.can_fd.unwrap_or(false),
+    };
+    if let Some(prefix) = &config.symbol_prefix {
+        let mut chars = prefix.chars();
+        let valid = chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid {
+            bail!(
+                "invalid --symbol-prefix '{}', expected a C identifier",
+                prefix
+            );
+        }
+        c_options.symbol_prefix = Some(prefix.clone());
+    }
 
-    let input_path = if !args.is_empty() {
-        PathBuf::from(args.remove(0))
-    } else {
-        resolve_default_path(
-            "msgs/intermediate_msg.json",
-            "../msgs/intermediate_msg.json",
-        )
+    let role = match (cli.role, cli.client_id) {
+        (None, None) => None,
+        (None | Some(cli::RoleKind::Client), Some(id)) => Some(emit_c::Role::Client(id)),
+        (Some(cli::RoleKind::Server), None) => Some(emit_c::Role::Server),
+        (Some(cli::RoleKind::ClientCommon), None) => Some(emit_c::Role::ClientCommon),
+        (Some(cli::RoleKind::Client), None) => unreachable!("clap requires --client-id"),
+        (Some(_), Some(_)) => bail!("--client-id only applies to --role client"),
     };
+    if role.is_some()
+        && (c_options.emit_tests
+            || c_options.emit_fuzz
+            || c_options.emit_python
+            || config.emit_cmake == Some(true)
+            || c_options.package.is_some())
+    {
+        bail!(
+            "--role and --client-id generate the files of one role; --emit-tests, --emit-fuzz, --emit-python, --emit-cmake and --package need every role"
+        );
+    }
+    let all_clients = cli.all_clients;
+    if role.is_some() || all_clients {
+        if targets
+            .iter()
+            .any(|target| !matches!(target, Target::Code(_) | Target::Docs))
+        {
+            bail!(
+                "--role, --client-id and --all-clients apply to C output and the Markdown documentation"
+            );
+        }
+        if targets.contains(&Target::Docs) {
+            markdown_options.client_id = match role {
+                Some(emit_c::Role::Client(id)) => Some(id),
+                None => None,
+                Some(_) => bail!(
+                    "the documentation is split by client; use --client-id N or --all-clients with --export_docs"
+                ),
+            };
+        }
+    }
+    if all_clients && !targets.contains(&Target::Docs) {
+        bail!("--all-clients writes the documentation of every client and needs --export_docs");
+    }
+    c_options.types_header = cli.types_header.as_deref().map(str::to_string);
+    let readonly = cli.readonly_output;
+    if readonly {
+        c_options.regenerate_command = Some(regenerate_command(env::args().skip(1)));
+    }
 
-    // For C generation, the output path is a directory where multiple files will be placed
-    let output_dir = if !args.is_empty() {
-        PathBuf::from(args.remove(0))
-    } else if export_docs {
-        resolve_default_path("docs", "../docs")
+    let from_stdin = config
+        .input
+        .iter()
+        .any(|path| path == Path::new(STDIO_PATH));
+    if from_stdin && config.input.len() > 1 {
+        bail!("'-' (stdin) cannot be merged with other inputs");
+    }
+    let paths = if from_stdin {
+        vec![PathBuf::from(STDIN_LABEL)]
+    } else if config.input.is_empty() {
+        vec![resolve_default_path(
+            "msgs/intermediate_msg.json",
+            "../msgs/intermediate_msg.json",
+        )]
     } else {
-        resolve_default_path("generated_c", "../generated_c")
+        config.input.clone()
     };
-
-    let raw = fs::read_to_string(&input_path)
-        .with_context(|| format!("failed to read input JSON: {}", input_path.display()))?;
-    let json: Value =
-        serde_json::from_str(&raw).context("failed to parse intermediate representation JSON")?;
-    let obj = json
-        .as_object()
-        .context("top-level JSON must be an object")?;
-
-    let (metadata, mut messages) = parse_messages(obj)?;
-    if messages.is_empty() {
-        bail!("no message definitions found in {}", input_path.display());
+    let inputs = Inputs {
+        paths,
+        from_stdin,
+        format: config.input_format,
+        options: parse_options(&config, &cli.input_options),
+    };
+    let input_path = inputs.paths[0].clone();
+    // The Source: comments name every input as given
+    c_options.extra_inputs = inputs.paths[1..].to_vec();
+    markdown_options.extra_inputs = c_options.extra_inputs.clone();
+
+    if cli.validate {
+        let format = match cli.format {
+            None | Some(cli::Format::Text) => ReportFormat::Text,
+            Some(cli::Format::Json) => ReportFormat::Json,
+            Some(other) => bail!(
+                "unsupported --validate format '{}', expected 'text' or 'json'",
+                other.name()
+            ),
+        };
+        return inputs.validate(format);
     }
-    messages.sort_by_key(|m| m.packet_id);
-
-    if export_docs {
-        let output_path = output_dir.join("COMMANDS.md");
-        let source = emit_markdown::generate(&metadata, &messages, &input_path)?;
-        if let Some(parent) = output_path.parent() {
-            fs::create_dir_all(parent).with_context(|| {
-                format!("failed to create output directory {}", parent.display())
-            })?;
+    if cli.fmt || cli.fmt_check {
+        if from_stdin {
+            bail!("--fmt rewrites the input files and cannot read stdin");
         }
-        fs::write(&output_path, source)
-            .with_context(|| format!("failed to write output to {}", output_path.display()))?;
-        println!(
-            "Generated documentation at {} for {} command(s).",
-            output_path.display(),
-            messages.len()
+        if cli.output.is_some() {
+            bail!("--fmt rewrites the input files in place and takes no output path");
+        }
+        return inputs.reformat(cli.fmt_check);
+    }
+    let list = cli.list;
+    let sort = cli.sort.unwrap_or_default();
+
+    // An output path on the command line names the directory of the one
+    // language generated; otherwise each language has its own
+    let explicit_output = match (cli.out_dir.as_deref(), &cli.output) {
+        (Some(dir), Some(positional)) => bail!(
+            "both an output path ('{}') and --out-dir ('{}') were given; use only one",
+            positional,
+            dir
+        ),
+        (Some(dir), None) => Some(PathBuf::from(dir)),
+        (None, Some(positional)) => Some(PathBuf::from(positional)),
+        (None, None) => None,
+    };
+    let manifest_path = cli.manifest.as_deref().map(PathBuf::from);
+    if manifest_path.is_some() && from_stdin {
+        bail!("--manifest hashes the input files and cannot read stdin");
+    }
+    if explicit_output.is_some() && targets.len() > 1 {
+        bail!(
+            "an output path applies to a single language; set [output] directories in {} to generate several",
+            config::CONFIG_FILE
         );
-    } else {
-        // Get the base name from the input file
-        let base_name = input_path
+    }
+
+    // Get the base name from the input file
+    let base_name = config.base_name.clone().unwrap_or_else(|| {
+        input_path
             .file_stem()
             .and_then(|s| s.to_str())
-            .unwrap_or("messages");
-
-        match language {
-            TargetLanguage::C => {
-                let files =
-                    emit_c::generate_multiple(&metadata, &messages, &input_path, base_name)?;
-
-                // Ensure output directory exists
-                fs::create_dir_all(&output_dir).with_context(|| {
-                    format!("failed to create output directory {}", output_dir.display())
-                })?;
+            .filter(|_| !from_stdin)
+            .unwrap_or("messages")
+            .to_string()
+    });
+    let docs_stem = config
+        .base_name
+        .clone()
+        .unwrap_or_else(|| "COMMANDS".to_string());
+    let output_dir_of = |target: Target| {
+        explicit_output
+            .clone()
+            .or_else(|| config.output.get(target).cloned())
+            .unwrap_or_else(|| target.default_output())
+    };
+    if config.emit_cmake.unwrap_or(false) {
+        if from_stdin {
+            bail!("--emit-cmake re-runs the generator on the input files and cannot read stdin");
+        }
+        let output_dir = output_dir_of(Target::Code(TargetLanguage::C));
+        c_options.cmake = Some(cmake_options(
+            &config,
+            &inputs.paths,
+            &base_name,
+            &output_dir,
+        )?);
+    }
+    let mut generations = Vec::new();
+    for &target in &targets {
+        let output_dir = output_dir_of(target);
+        if output_dir == Path::new(STDIO_PATH) && check {
+            bail!("--check compares against files on disk and cannot be used with '-' output");
+        }
+        if output_dir == Path::new(STDIO_PATH) {
+            if target == Target::Code(TargetLanguage::C) && role.is_some() {
+                bail!("'-' output writes a single header and cannot be combined with --role");
+            }
+            if all_clients {
+                bail!("--all-clients writes one file per client and needs an output directory");
+            }
+            if manifest_path.is_some() {
+                bail!("'-' output writes no files for --manifest to list");
+            }
+        }
+        generations.push(Generation {
+            target,
+            input_path: &input_path,
+            output_dir,
+            base_name: &base_name,
+            docs_stem: docs_stem.clone(),
+            c_options: &c_options,
+            markdown_options: &markdown_options,
+            dbc_options: &dbc_options,
+            force: cli.force,
+            readonly,
+            role,
+            all_clients,
+        });
+    }
 
-                // Write each generated file
-                for file in &files {
-                    let file_path = output_dir.join(&file.filename);
-                    fs::write(&file_path, &file.content).with_context(|| {
-                        format!("failed to write output to {}", file_path.display())
-                    })?;
-                    println!("Generated: {}", file_path.display());
+    if cli.watch {
+        let to_stdout = generations.iter().any(Generation::to_stdout);
+        if from_stdin || to_stdout {
+            bail!("--watch needs an input file and an output directory");
+        }
+        let mut paths = inputs.paths.clone();
+        paths.extend(c_options.template_dir.clone());
+        return watch::watch(&paths, |read| {
+            // The templates may be what changed
+            clear_template_cache();
+            let (metadata, messages) = inputs.load_tracked(read)?;
+            let mut outputs = Vec::new();
+            for generation in &generations {
+                outputs.extend(generation.manifest_entries(&metadata, &messages)?);
+            }
+            let mut changed = 0;
+            for output in &outputs {
+                if write_output(&output.path, &output.content, false, readonly)? {
+                    changed += 1;
                 }
-
-                println!(
-                    "\nGenerated {} {} file(s) for {} message definition(s).",
-                    files.len(),
-                    language.display_name(),
-                    messages.len()
-                );
             }
+            if let Some(path) = &manifest_path {
+                write_manifest(path, read, &metadata, &messages, &outputs)?;
+            }
+            Ok(match changed {
+                0 => format!("{} file(s) up to date, output unchanged", outputs.len()),
+                n => format!("regenerated, {} of {} file(s) changed", n, outputs.len()),
+            })
+        });
+    }
+
+    let mut read = Vec::new();
+    let (metadata, messages) = inputs.load_tracked(&mut read)?;
+
+    if list {
+        print!(
+            "{}",
+            list::render_table(&messages, sort, cli.filter.as_deref())
+        );
+        if cli.free_ids {
+            print!("\n{}", list::render_id_space(&metadata, &messages));
         }
+        return Ok(());
     }
 
+    if check {
+        let mut outputs = Vec::new();
+        for generation in &generations {
+            outputs.extend(generation.outputs(&metadata, &messages)?);
+        }
+        return check_outputs(&outputs);
+    }
+    let mut outputs = Vec::new();
+    for generation in &generations {
+        outputs.extend(generation.write(&metadata, &messages)?);
+    }
+    if let Some(path) = &manifest_path {
+        write_manifest(path, &read, &metadata, &messages, &outputs)?;
+        println!("Manifest: {}", display_path(path));
+    }
     Ok(())
 }
 
-fn parse_export_docs(args: &mut Vec<String>) -> bool {
-    let mut index = 0;
-    while index < args.len() {
-        if args[index] == "--export_docs" {
-            args.remove(index);
-            return true;
-        }
-        index += 1;
+/// Writes the `--manifest` of a run atomically, so an interrupted run
+/// leaves either the previous manifest or the new one.
+fn write_manifest(
+    path: &Path,
+    inputs: &[PathBuf],
+    metadata: &Metadata,
+    messages: &[MessageDefinition],
+    outputs: &[manifest::OutputEntry],
+) -> Result<()> {
+    let content = manifest::render(inputs, &ir_fingerprint(metadata, messages), outputs, path)?;
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create directory {}", parent.display()))?;
     }
-    false
+    write_atomic(path, &content)
 }
 
-fn parse_language(args: &mut Vec<String>) -> Result<TargetLanguage> {
-    if let Some(first) = args.first().cloned()
-        && let Some(lang) = TargetLanguage::try_from_str(&first)
-    {
-        args.remove(0);
-        return Ok(lang);
+/// Loads the `--config` file, or the default one if present.
+fn load_config(options: &cli::InputOptions) -> Result<config::GenerationConfig> {
+    Ok(
+        match config::GenerationConfig::load(options.config.as_deref().map(Path::new))? {
+            Some((_, config)) => config,
+            None => config::GenerationConfig::default(),
+        },
+    )
+}
+
+/// Limits on reading the input, from the configuration with the command
+/// line applied.
+fn parse_options(config: &config::GenerationConfig, options: &cli::InputOptions) -> ParseOptions {
+    ParseOptions {
+        max_payload_bytes: config.max_payload_bytes,
+        max_array_length: config.max_array_length,
+        max_nesting_depth: config.max_nesting_depth,
+        strict: config.strict.unwrap_or(false),
+        fail_fast: options.fail_fast,
+        format: InputFormat::default(),
     }
+}
 
-    let mut index = 0;
-    while index < args.len() {
-        if args[index] == "--lang" || args[index] == "-l" {
-            if index + 1 >= args.len() {
-                bail!("--lang requires a value (c)");
-            }
-            let value = args.remove(index + 1);
-            args.remove(index);
-            return TargetLanguage::parse(&value);
+/// Compares the two inputs of `diff OLD NEW` and prints the report. Fails
+/// on breaking changes unless `--allow-breaking` is given.
+fn run_diff(args: &cli::DiffArgs) -> Result<()> {
+    let mut config = load_config(&args.input_options)?;
+    config.apply_input_options(&args.input_options);
+    let mut sets = Vec::with_capacity(2);
+    for path in [&args.old, &args.new] {
+        if path == STDIO_PATH {
+            bail!("diff reads OLD and NEW from files, not stdin");
         }
-        if let Some(value) = args[index].strip_prefix("--lang=") {
-            let value = value.to_string();
-            args.remove(index);
-            return TargetLanguage::parse(&value);
-        }
-        index += 1;
+        let inputs = Inputs {
+            paths: vec![PathBuf::from(path)],
+            from_stdin: false,
+            format: config.input_format,
+            options: parse_options(&config, &args.input_options),
+        };
+        let (_, messages) = inputs.load()?;
+        sets.push(messages);
     }
 
-    Ok(TargetLanguage::C)
+    let report = semantic_diff(&sets[0], &sets[1]);
+    if args.format.unwrap_or_default() == cli::DiffFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&report.to_json())?);
+    } else {
+        print!("{}", report.to_markdown());
+    }
+    if report.breaking() > 0 && !args.allow_breaking {
+        bail!(
+            "{} breaking change(s); pass --allow-breaking to accept them",
+            report.breaking()
+        );
+    }
+    Ok(())
 }
 
+/// What a run generates: code in a target language, or the documentation.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub(crate) enum TargetLanguage {
-    C,
+pub(crate) enum Target {
+    Code(TargetLanguage),
+    Docs,
+    /// CSV codebook, written next to the documentation
+    Csv,
+    /// Canonical JSON of the IR, written next to the documentation
+    Ir,
+    /// Kaitai Struct description, written next to the documentation
+    Kaitai,
+    /// Protocol Buffers schema, written next to the documentation
+    Proto,
+    /// CAN database, written next to the documentation
+    Dbc,
+    /// Message flow diagram, written next to the documentation
+    Flow(emit_flow::FlowFormat),
 }
 
-impl TargetLanguage {
-    fn try_from_str(value: &str) -> Option<Self> {
+impl Target {
+    /// Parses a `languages` entry of the configuration file.
+    fn parse(value: &str) -> Result<Self> {
         match value.to_ascii_lowercase().as_str() {
-            "c" | "c99" => Some(Self::C),
-            _ => None,
+            "docs" | "markdown" | "md" => Ok(Target::Docs),
+            "csv" => Ok(Target::Csv),
+            "ir" => Ok(Target::Ir),
+            "ksy" | "kaitai" => Ok(Target::Kaitai),
+            "proto" | "protobuf" => Ok(Target::Proto),
+            "dbc" => Ok(Target::Dbc),
+            "mermaid" | "mmd" => Ok(Target::Flow(emit_flow::FlowFormat::Mermaid)),
+            "dot" | "graphviz" => Ok(Target::Flow(emit_flow::FlowFormat::Dot)),
+            other => match TargetLanguage::try_from_str(other) {
+                Some(language) => Ok(Target::Code(language)),
+                None => bail!(
+                    "unsupported language '{}', expected 'c', 'docs', 'csv', 'ir', 'ksy', 'proto', 'dbc', 'mermaid' or 'dot'",
+                    value
+                ),
+            },
         }
     }
 
-    fn parse(value: &str) -> Result<Self> {
-        Self::try_from_str(value)
-            .ok_or_else(|| anyhow::anyhow!("unsupported language '{}', expected 'c'", value))
-    }
-
-    fn display_name(self) -> &'static str {
+    /// Name of the target in `languages` and in the `--manifest`.
+    fn name(self) -> &'static str {
         match self {
-            TargetLanguage::C => "C99",
+            Target::Code(language) => language.name(),
+            Target::Docs => "docs",
+            Target::Csv => "csv",
+            Target::Ir => "ir",
+            Target::Kaitai => "ksy",
+            Target::Proto => "proto",
+            Target::Dbc => "dbc",
+            Target::Flow(emit_flow::FlowFormat::Mermaid) => "mermaid",
+            Target::Flow(emit_flow::FlowFormat::Dot) => "dot",
         }
     }
 
-    fn template_subdir(self) -> &'static str {
+    fn default_output(self) -> PathBuf {
         match self {
-            TargetLanguage::C => "c",
+            Target::Code(TargetLanguage::C) => {
+                resolve_default_path("generated_c", "../generated_c")
+            }
+            Target::Docs
+            | Target::Csv
+            | Target::Ir
+            | Target::Kaitai
+            | Target::Proto
+            | Target::Dbc
+            | Target::Flow(_) => resolve_default_path("docs", "../docs"),
         }
     }
 }
 
-#[derive(Default, Debug, Clone)]
-pub struct DeviceInfo {
-    pub name: String,
-    pub role: String,
-    pub id: Option<u32>,
-    pub description: Option<String>,
+/// Where and how the generated files of one target are written.
+struct Generation<'a> {
+    target: Target,
+    input_path: &'a Path,
+    output_dir: PathBuf,
+    base_name: &'a str,
+    /// File name of the documentation without extension
+    docs_stem: String,
+    c_options: &'a emit_c::CGenOptions,
+    markdown_options: &'a emit_markdown::MarkdownOptions,
+    dbc_options: &'a emit_dbc::DbcOptions,
+    /// Write every file, even when the one on disk already matches
+    force: bool,
+    /// Clear the write permission of the files (`--readonly-output`)
+    readonly: bool,
+    /// Only the C files of this role
+    role: Option<emit_c::Role>,
+    /// One document per client instead of one for the protocol
+    all_clients: bool,
 }
 
-#[derive(Default, Debug)]
-pub struct Metadata {
-    pub version: Option<String>,
-    pub max_address: Option<u32>,
-    pub devices: Vec<DeviceInfo>,
-}
+impl Generation<'_> {
+    /// `-` output: the generated text goes to stdout.
+    fn to_stdout(&self) -> bool {
+        self.output_dir == Path::new(STDIO_PATH)
+    }
 
-/// Request type for pub/sub semantics.
-/// - Pub: Server publishes (sends) to client(s)
-/// - Sub: Server subscribes (receives) from client(s)
-#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
-pub enum RequestType {
-    #[default]
-    Pub,
-    Sub,
-}
+    /// Every generated file with its destination path, for the modes that
+    /// need the content before touching the disk (`--check`, `--watch`).
+    fn outputs(
+        &self,
+        metadata: &Metadata,
+        messages: &[MessageDefinition],
+    ) -> Result<Vec<(PathBuf, String)>> {
+        match self.target {
+            Target::Docs
+            | Target::Csv
+            | Target::Ir
+            | Target::Kaitai
+            | Target::Proto
+            | Target::Dbc
+            | Target::Flow(_) => Ok(self
+                .documents(metadata, messages)?
+                .into_iter()
+                .map(|(_, path, content)| (path, content))
+                .collect()),
+            Target::Code(TargetLanguage::C) => {
+                let files = match self.role {
+                    Some(role) => emit_c::generate_for_role(
+                        metadata,
+                        messages,
+                        self.input_path,
+                        self.base_name,
+                        self.c_options,
+                        role,
+                    )?,
+                    None => emit_c::generate_multiple_with_options(
+                        metadata,
+                        messages,
+                        self.input_path,
+                        self.base_name,
+                        self.c_options,
+                    )?,
+                };
+                Ok(files
+                    .into_iter()
+                    .map(|file| (self.output_dir.join(&file.filename), file.content))
+                    .collect())
+            }
+        }
+    }
 
-impl RequestType {
-    pub(crate) fn from_str(value: &str) -> Result<Self> {
-        match value.to_ascii_lowercase().as_str() {
-            "pub" | "publish" => Ok(RequestType::Pub),
-            "sub" | "subscribe" => Ok(RequestType::Sub),
-            other => bail!(
-                "unsupported request_type '{}', expected 'pub' or 'sub'",
-                other
+    /// The files of the documentation targets with their paths: one, or
+    /// one per client with `--all-clients`, each with the client it covers.
+    fn documents(
+        &self,
+        metadata: &Metadata,
+        messages: &[MessageDefinition],
+    ) -> Result<Vec<(Option<i32>, PathBuf, String)>> {
+        if self.target == Target::Docs && self.all_clients {
+            return emit_c::client_ids(messages)
+                .into_iter()
+                .map(|id| {
+                    let options = emit_markdown::MarkdownOptions {
+                        client_id: Some(id),
+                        ..self.markdown_options.clone()
+                    };
+                    let (path, content) = self.markdown(metadata, messages, &options)?;
+                    Ok((Some(id), path, content))
+                })
+                .collect();
+        }
+        let (path, content) = self.document(metadata, messages)?;
+        Ok(vec![(self.markdown_options.client_id, path, content)])
+    }
+
+    /// The Markdown documentation, named after the client it covers.
+    fn markdown(
+        &self,
+        metadata: &Metadata,
+        messages: &[MessageDefinition],
+        options: &emit_markdown::MarkdownOptions,
+    ) -> Result<(PathBuf, String)> {
+        let content =
+            emit_markdown::generate_with_options(metadata, messages, self.input_path, options)?;
+        let file = match options.client_id {
+            Some(id) => format!("{}_client_{}.md", self.docs_stem, id),
+            None => format!("{}.md", self.docs_stem),
+        };
+        Ok((self.output_dir.join(file), content))
+    }
+
+    /// The one file of a documentation target, with its path.
+    fn document(
+        &self,
+        metadata: &Metadata,
+        messages: &[MessageDefinition],
+    ) -> Result<(PathBuf, String)> {
+        let (extension, content) = match self.target {
+            Target::Docs => return self.markdown(metadata, messages, self.markdown_options),
+            Target::Csv => ("csv", emit_csv::generate(metadata, messages)?),
+            Target::Ir => ("ir.json", emit_ir::generate(metadata, messages)?),
+            Target::Dbc => {
+                let file = emit_dbc::generate(metadata, messages, self.dbc_options)?;
+                for skipped in &file.skipped {
+                    eprintln!(
+                        "warning: '{}' (packet ID {}) is not in the DBC: {}",
+                        skipped.name, skipped.packet_id, skipped.reason
+                    );
+                }
+                ("dbc", file.content)
+            }
+            Target::Flow(format) => (
+                format.extension(),
+                emit_flow::generate(metadata, messages, format)?,
             ),
+            // Kaitai wants `meta/id` to match the file name, in lowercase
+            Target::Kaitai => {
+                let id = emit_kaitai::ident(&self.docs_stem);
+                let content = emit_kaitai::generate(metadata, messages, &id)?;
+                return Ok((self.output_dir.join(format!("{}.ksy", id)), content));
+            }
+            // The package is named after the file, as protobuf style asks
+            Target::Proto => {
+                let package = emit_proto::field_name(&self.docs_stem);
+                let content = emit_proto::generate(metadata, messages, &package)?;
+                return Ok((self.output_dir.join(format!("{}.proto", package)), content));
+            }
+            Target::Code(_) => unreachable!("code targets generate several files"),
+        };
+        let file = format!("{}.{}", self.docs_stem, extension);
+        Ok((self.output_dir.join(file), content))
+    }
+
+    /// The manifest entry of a generated file; the C files of a role and
+    /// the per-client documents name their role.
+    fn manifest_entry(
+        &self,
+        messages: &[MessageDefinition],
+        path: &Path,
+        content: &str,
+        client_id: Option<i32>,
+    ) -> manifest::OutputEntry {
+        let role = match self.target {
+            Target::Code(_) => path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| emit_c::Role::of_file(messages, self.base_name, name)),
+            _ => client_id.map(emit_c::Role::Client),
+        };
+        manifest::OutputEntry {
+            path: path.to_path_buf(),
+            content: content.to_string(),
+            target: self.target.name(),
+            role,
         }
     }
+
+    /// [`Generation::outputs`] as manifest entries, for `--watch`.
+    fn manifest_entries(
+        &self,
+        metadata: &Metadata,
+        messages: &[MessageDefinition],
+    ) -> Result<Vec<manifest::OutputEntry>> {
+        let files = match self.target {
+            Target::Code(_) => self
+                .outputs(metadata, messages)?
+                .into_iter()
+                .map(|(path, content)| (None, path, content))
+                .collect(),
+            _ => self.documents(metadata, messages)?,
+        };
+        Ok(files
+            .iter()
+            .map(|(client_id, path, content)| {
+                self.manifest_entry(messages, path, content, *client_id)
+            })
+            .collect())
+    }
+
+    /// Generates the files, or writes them to stdout for `-` output.
+    /// Returns the manifest entries of the files written or kept.
+    fn write(
+        &self,
+        metadata: &Metadata,
+        messages: &[MessageDefinition],
+    ) -> Result<Vec<manifest::OutputEntry>> {
+        // With `-` the output stream must stay clean, so progress goes to stderr
+        let to_stdout = self.to_stdout();
+        let info = |line: String| {
+            if to_stdout {
+                eprintln!("{}", line);
+            } else {
+                println!("{}", line);
+            }
+        };
+
+        let mut entries = Vec::new();
+        match self.target {
+            Target::Docs
+            | Target::Csv
+            | Target::Ir
+            | Target::Kaitai
+            | Target::Proto
+            | Target::Dbc
+            | Target::Flow(_) => {
+                for (client_id, output_path, document) in self.documents(metadata, messages)? {
+                    if to_stdout {
+                        io::stdout()
+                            .lock()
+                            .write_all(document.as_bytes())
+                            .map_err(|e| IdlError::write(Path::new(STDOUT_LABEL), e))?;
+                        continue;
+                    }
+                    let changed = write_output(&output_path, &document, self.force, self.readonly)?;
+                    entries.push(self.manifest_entry(messages, &output_path, &document, client_id));
+                    let commands = match client_id {
+                        Some(id) => messages
+                            .iter()
+                            .filter(|msg| emit_markdown::for_client(msg, id))
+                            .count(),
+                        None => messages.len(),
+                    };
+                    println!(
+                        "Generated {} at {} for {} command(s){}.",
+                        match self.target {
+                            Target::Csv => "codebook",
+                            Target::Ir => "IR",
+                            Target::Kaitai => "Kaitai Struct description",
+                            Target::Proto => "protobuf schema",
+                            Target::Dbc => "CAN database",
+                            Target::Flow(_) => "message flow diagram",
+                            _ => "documentation",
+                        },
+                        display_path(&output_path),
+                        commands,
+                        if changed { "" } else { " (unchanged)" }
+                    );
+                }
+            }
+            Target::Code(language @ TargetLanguage::C) => {
+                for warning in emit_c::response_warnings(messages) {
+                    eprintln!("warning: {}", warning);
+                }
+                if self.c_options.template_dir.is_some() {
+                    info(format!(
+                        "Templates: {}",
+                        emit_c::describe_templates(self.c_options)?
+                    ));
+                }
+                if to_stdout {
+                    write_single_header_to_stdout(
+                        metadata,
+                        messages,
+                        self.input_path,
+                        self.base_name,
+                        self.c_options,
+                    )?;
+                    return Ok(entries);
+                }
+                let outputs = self.outputs(metadata, messages)?;
+                fs::create_dir_all(&self.output_dir).with_context(|| {
+                    format!(
+                        "failed to create output directory {}",
+                        self.output_dir.display()
+                    )
+                })?;
+                let mut unchanged = 0;
+                for (path, content) in &outputs {
+                    if write_output(path, content, self.force, self.readonly)? {
+                        info(format!("Generated: {}", display_path(path)));
+                    } else {
+                        unchanged += 1;
+                        info(format!("Generated: {} (unchanged)", display_path(path)));
+                    }
+                    entries.push(self.manifest_entry(messages, path, content, None));
+                }
+                if self.c_options.emit_skeleton {
+                    let skeleton = emit_c::generate_server_skeleton(
+                        metadata,
+                        messages,
+                        self.base_name,
+                        self.c_options,
+                    );
+                    let path = self.output_dir.join(&skeleton.filename);
+                    match write_skeleton(&path, &skeleton.content)? {
+                        Some(written) if written == path => {
+                            info(format!("Generated: {}", display_path(&written)))
+                        }
+                        Some(written) => info(format!(
+                            "Generated: {} ({} exists and is kept)",
+                            display_path(&written),
+                            display_path(&path)
+                        )),
+                        None => info(format!("Skeleton unchanged: {}", display_path(&path))),
+                    }
+                }
+
+                let language_name = match self.c_options.dialect {
+                    emit_c::CDialect::C89 => "C89",
+                    emit_c::CDialect::C99 => language.display_name(),
+                };
+                info(format!(
+                    "\nGenerated {} {} file(s) for {} message definition(s){}.",
+                    outputs.len(),
+                    language_name,
+                    messages.len(),
+                    match unchanged {
+                        0 => String::new(),
+                        n => format!(", {} unchanged", n),
+                    }
+                ));
+                if self.role.is_none() {
+                    let macros: Vec<String> = emit_c::split_roles(messages)
+                        .into_iter()
+                        .map(emit_c::Role::macro_name)
+                        .collect();
+                    info(format!(
+                        "Include {}.h after defining one of: {}",
+                        self.base_name,
+                        macros.join(", ")
+                    ));
+                }
+            }
+        }
+        Ok(entries)
+    }
 }
 
-#[derive(Debug)]
-pub struct MessageDefinition {
-    pub name: String,
-    pub packet_id: u32,
-    pub description: Option<String>,
-    pub body: MessageBody,
-    pub request_type: RequestType,
-    /// Target client ID. -1 means all clients.
-    pub target_client_id: i32,
+/// Writes the server skeleton to the first free path of `<stem>.example.c`,
+/// `<stem>.example.1.c`, `<stem>.example.2.c`, ... so edited copies are
+/// never overwritten. Returns the path written, or `None` when one of the
+/// existing copies already holds exactly `content`.
+fn write_skeleton(path: &Path, content: &str) -> Result<Option<PathBuf>> {
+    let file_name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    let stem = file_name
+        .strip_suffix(emit_c::SKELETON_SUFFIX)
+        .unwrap_or(file_name);
+    let mut candidate = path.to_path_buf();
+    for n in 1.. {
+        match fs::read(&candidate) {
+            Ok(existing) if existing == content.as_bytes() => return Ok(None),
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                write_atomic(&candidate, content)?;
+                return Ok(Some(candidate));
+            }
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("failed to read {}", display_path(&candidate)));
+            }
+        }
+        candidate = path.with_file_name(format!("{}.example.{}.c", stem, n));
+    }
+    unreachable!("ran out of skeleton file names")
 }
 
-#[derive(Debug)]
-pub enum MessageBody {
-    Scalar(ScalarSpec),
-    Array(ArraySpec),
-    Struct(StructSpec),
+/// Writes `content` to `path` unless the file already holds exactly that
+/// (and `force` is off), so unchanged outputs keep their modification time
+/// for make-based builds. With `readonly` the file, written or not, ends
+/// up without write permission. Returns whether the file was written.
+fn write_output(path: &Path, content: &str, force: bool, readonly: bool) -> Result<bool> {
+    let changed = force || fs::read(path).map_or(true, |existing| existing != content.as_bytes());
+    if changed {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).with_context(|| {
+                format!("failed to create output directory {}", parent.display())
+            })?;
+        }
+        write_atomic(path, content)?;
+    }
+    if readonly {
+        set_writable(path, false).map_err(|e| IdlError::write(path, e))?;
+    }
+    Ok(changed)
 }
 
-#[derive(Debug)]
-pub struct ScalarSpec {
-    pub primitive: PrimitiveType,
-    pub endian: Endian,
+/// Writes `content` to a temporary file next to `path` and renames it over
+/// `path`, so a killed run leaves the old file or the new one but never a
+/// truncated one. The file keeps the permissions of the one it replaces.
+///
+/// Where the rename cannot replace an existing file (some network and FUSE
+/// filesystems, or Windows while another process has the file open), the
+/// file is written in place instead, with a warning, and is not atomic.
+fn write_atomic(path: &Path, content: &str) -> Result<()> {
+    let temp = temp_path(path);
+    let permissions = fs::metadata(path)
+        .ok()
+        .map(|existing| existing.permissions());
+    let written = fs::write(&temp, content).and_then(|()| match &permissions {
+        Some(permissions) => fs::set_permissions(&temp, permissions.clone()),
+        None => Ok(()),
+    });
+    if let Err(e) = written {
+        let _ = fs::remove_file(&temp);
+        return Err(IdlError::write(path, e).into());
+    }
+    // Windows refuses to replace a read-only file, as `--readonly-output`
+    // leaves them; the new file carries the permissions again
+    if permissions.as_ref().is_some_and(fs::Permissions::readonly) {
+        let _ = set_writable(path, true);
+    }
+    if let Err(e) = fs::rename(&temp, path) {
+        let _ = fs::remove_file(&temp);
+        eprintln!(
+            "warning: cannot replace {} atomically ({}), writing it in place",
+            display_path(path),
+            e
+        );
+        let written = fs::write(path, content);
+        if let Some(permissions) = permissions {
+            let _ = fs::set_permissions(path, permissions);
+        }
+        written.map_err(|e| IdlError::write(path, e))?;
+    }
+    Ok(())
 }
 
-#[derive(Debug)]
-pub struct ArraySpec {
-    pub primitive: PrimitiveType,
-    pub endian: Endian,
-    pub max_length: usize,
-    pub sector_bytes: Option<usize>,
+/// Clears every write permission of `path`, or gives the owner back the
+/// permission to write. Does nothing where the platform has no file
+/// permissions.
+fn set_writable(path: &Path, writable: bool) -> io::Result<()> {
+    let mut permissions = fs::metadata(path)?.permissions();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = permissions.mode();
+        permissions.set_mode(if writable {
+            mode | 0o200
+        } else {
+            mode & !0o222
+        });
+    }
+    #[cfg(not(unix))]
+    permissions.set_readonly(!writable);
+    match fs::set_permissions(path, permissions) {
+        Err(e) if e.kind() == io::ErrorKind::Unsupported => Ok(()),
+        result => result,
+    }
 }
 
-#[derive(Debug)]
-pub struct StructSpec {
-    pub fields: Vec<StructField>,
+/// The temporary file [`write_atomic`] writes before renaming it to `path`:
+/// hidden, in the same directory so the rename stays on one filesystem, and
+/// unique per process.
+fn temp_path(path: &Path) -> PathBuf {
+    let file_name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("output");
+    path.with_file_name(format!(".{}.{}.tmp", file_name, std::process::id()))
 }
 
-#[derive(Debug)]
-pub struct StructField {
-    pub name: String,
-    pub field_type: StructFieldType,
-    pub endian: Endian,
+/// Reads the input document from stdin or `input_path`.
+fn read_input(from_stdin: bool, input_path: &Path) -> Result<String> {
+    if from_stdin {
+        let mut raw = String::new();
+        io::stdin()
+            .read_to_string(&mut raw)
+            .context("failed to read input JSON from stdin")?;
+        Ok(raw)
+    } else {
+        fs::read_to_string(input_path)
+            .with_context(|| format!("failed to read input JSON: {}", input_path.display()))
+    }
 }
 
-#[derive(Debug)]
-pub struct StructFieldArraySpec {
-    pub primitive: PrimitiveType,
-    pub max_length: usize,
+/// Output format of `--validate`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ReportFormat {
+    Text,
+    Json,
 }
 
-#[derive(Debug)]
-pub enum StructFieldType {
-    Primitive(PrimitiveType),
-    Array(StructFieldArraySpec),
-    Nested(StructSpec),
+/// The input documents of a run and how to read them.
+struct Inputs {
+    /// As given; a directory stands for the `*.json` files in it
+    paths: Vec<PathBuf>,
+    from_stdin: bool,
+    /// `--input-format`; otherwise the extension of each file decides
+    format: Option<InputFormat>,
+    options: ParseOptions,
 }
 
-#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
-pub enum Endian {
-    #[default]
-    Little,
-    Big,
-}
-
-impl Endian {
-    pub(crate) fn from_str(value: &str) -> Result<Self> {
-        match value.to_ascii_lowercase().as_str() {
-            "little" | "le" => Ok(Endian::Little),
-            "big" | "be" => Ok(Endian::Big),
-            other => bail!("unsupported endian value '{}'", other),
+impl Inputs {
+    /// The files to read, with directories expanded to the `*.json` files
+    /// directly in them, sorted by name.
+    fn files(&self) -> Result<Vec<PathBuf>> {
+        if self.from_stdin {
+            return Ok(self.paths.clone());
         }
+        let mut files: Vec<PathBuf> = Vec::new();
+        for path in &self.paths {
+            let mut found = if path.is_dir() {
+                let mut found: Vec<PathBuf> = fs::read_dir(path)
+                    .with_context(|| format!("failed to read input directory {}", path.display()))?
+                    .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                    .filter(|file| {
+                        file.is_file() && file.extension().is_some_and(|ext| ext == "json")
+                    })
+                    .collect();
+                if found.is_empty() {
+                    bail!("no *.json files in input directory {}", path.display());
+                }
+                found.sort();
+                found
+            } else {
+                vec![path.clone()]
+            };
+            found.retain(|file| !files.contains(file));
+            files.extend(found);
+        }
+        Ok(files)
     }
 
-    pub(crate) fn suffix(self) -> &'static str {
-        match self {
-            Endian::Little => "le",
-            Endian::Big => "be",
+    fn options_for(&self, file: &Path) -> ParseOptions {
+        ParseOptions {
+            format: self.format.unwrap_or_else(|| InputFormat::from_path(file)),
+            ..self.options.clone()
         }
     }
-}
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub enum PrimitiveType {
-    Bool,
-    Char,
-    Int8,
-    Uint8,
-    Int16,
-    Uint16,
-    Int32,
-    Uint32,
-    Int64,
-    Uint64,
-    Float32,
-    Float64,
-}
+    /// Reads and parses every input and merges them into one message set,
+    /// printing warnings and errors to stderr.
+    fn load(&self) -> Result<(Metadata, Vec<MessageDefinition>)> {
+        self.load_tracked(&mut Vec::new())
+    }
 
-impl PrimitiveType {
-    pub(crate) fn from_str(value: &str) -> Result<Self> {
-        match value.to_ascii_lowercase().as_str() {
-            "bool" | "boolean" => Ok(PrimitiveType::Bool),
-            "char" => Ok(PrimitiveType::Char),
-            "int8" | "i8" => Ok(PrimitiveType::Int8),
-            "uint8" | "u8" => Ok(PrimitiveType::Uint8),
-            "int16" | "i16" => Ok(PrimitiveType::Int16),
-            "uint16" | "u16" => Ok(PrimitiveType::Uint16),
-            "int32" | "i32" => Ok(PrimitiveType::Int32),
-            "uint32" | "u32" => Ok(PrimitiveType::Uint32),
-            "int64" | "i64" => Ok(PrimitiveType::Int64),
-            "uint64" | "u64" => Ok(PrimitiveType::Uint64),
-            "float32" | "f32" => Ok(PrimitiveType::Float32),
-            "float64" | "f64" | "double" => Ok(PrimitiveType::Float64),
-            other => bail!("unsupported primitive type '{}'", other),
+    /// Same as [`Inputs::load`], also collecting every file read, includes
+    /// too, into `read`; on failure the files read up to that point.
+    fn load_tracked(&self, read: &mut Vec<PathBuf>) -> Result<(Metadata, Vec<MessageDefinition>)> {
+        let files = self.files()?;
+        let mut resolver = Resolver::new(self.options.clone());
+        let mut sets = Vec::with_capacity(files.len());
+        for file in &files {
+            let raw = read_input(self.from_stdin, file)?;
+            let loaded = resolver.load_str(&raw, file, self.options_for(file).format);
+            *read = resolver.files().to_vec();
+            for warning in take_warnings(&mut resolver, file) {
+                eprintln!("warning: {}", warning_text(&warning));
+            }
+            match loaded {
+                Ok(set) => sets.extend(set),
+                Err(errors) => {
+                    for error in &errors {
+                        eprintln!("error: {}", error);
+                    }
+                    bail!("{} error(s) in {}", errors.len(), file.display());
+                }
+            }
         }
+        let (metadata, messages) = if sets.len() == 1 {
+            sets.remove(0)
+        } else {
+            merge_message_sets(sets).or_else(|error| {
+                for error in error.errors() {
+                    eprintln!("error: {}", error);
+                }
+                bail!(
+                    "{} conflict(s) between the merged inputs",
+                    error.errors().len()
+                )
+            })?
+        };
+        if messages.is_empty() {
+            bail!("no message definitions found in {}", self.describe(&files));
+        }
+        Ok((metadata, messages))
     }
 
-    pub(crate) fn c_type(self) -> &'static str {
-        match self {
-            PrimitiveType::Bool => "bool",
-            PrimitiveType::Char => "char",
-            PrimitiveType::Int8 => "int8_t",
-            PrimitiveType::Uint8 => "uint8_t",
-            PrimitiveType::Int16 => "int16_t",
-            PrimitiveType::Uint16 => "uint16_t",
-            PrimitiveType::Int32 => "int32_t",
-            PrimitiveType::Uint32 => "uint32_t",
-            PrimitiveType::Int64 => "int64_t",
-            PrimitiveType::Uint64 => "uint64_t",
-            PrimitiveType::Float32 => "float",
-            PrimitiveType::Float64 => "double",
+    /// Runs every parser check for `--validate` and reports the problems
+    /// without writing any file. Several inputs are checked one by one,
+    /// then for conflicts between them. Fails when there are errors.
+    /// `--fmt`: validates the inputs, then rewrites each file that is not in
+    /// the canonical style, or with `check` only shows how it would change.
+    /// Includes are left alone.
+    fn reformat(&self, check: bool) -> Result<()> {
+        self.load()?;
+        let files = self.files()?;
+        let mut unformatted = 0;
+        for file in &files {
+            let format = self.options_for(file).format;
+            if format != InputFormat::Json {
+                bail!(
+                    "--fmt only rewrites JSON inputs, and {} is {:?}",
+                    file.display(),
+                    format
+                );
+            }
+            let raw = read_input(false, file)?;
+            let doc: Map<String, Value> = serde_json::from_str(&raw)
+                .with_context(|| format!("failed to parse {}", file.display()))?;
+            let formatted = reformat::format_document(&doc);
+            if formatted == raw {
+                continue;
+            }
+            unformatted += 1;
+            let label = display_path(file);
+            if check {
+                print!(
+                    "{}",
+                    diff::unified_diff(&raw, &formatted, &label, &format!("{} (formatted)", label))
+                );
+            } else {
+                write_atomic(file, &formatted)?;
+                println!("Formatted {}", label);
+            }
+        }
+        if check && unformatted > 0 {
+            bail!(
+                "{} of {} input file(s) are not formatted; run --fmt to rewrite them",
+                unformatted,
+                files.len()
+            );
         }
+        if check || unformatted == 0 {
+            println!("{} input file(s) are formatted.", files.len());
+        }
+        Ok(())
     }
 
-    pub(crate) fn byte_len(self) -> usize {
-        match self {
-            PrimitiveType::Bool
-            | PrimitiveType::Char
-            | PrimitiveType::Int8
-            | PrimitiveType::Uint8 => 1,
-            PrimitiveType::Int16 | PrimitiveType::Uint16 => 2,
-            PrimitiveType::Int32 | PrimitiveType::Uint32 | PrimitiveType::Float32 => 4,
-            PrimitiveType::Int64 | PrimitiveType::Uint64 | PrimitiveType::Float64 => 8,
+    fn validate(&self, format: ReportFormat) -> Result<()> {
+        let files = self.files()?;
+        let mut resolver = Resolver::new(self.options.clone());
+        let mut reports = Vec::with_capacity(files.len() + 1);
+        let mut sets = Vec::with_capacity(files.len());
+        let mut failed = false;
+        for file in &files {
+            let raw = read_input(self.from_stdin, file)?;
+            let options = self.options_for(file);
+            let loaded = resolver.load_str(&raw, file, options.format);
+            let warnings = take_warnings(&mut resolver, file);
+            let (messages, errors) = match loaded {
+                // Already part of the set through an include
+                Ok(None) => continue,
+                Ok(Some((metadata, messages))) => {
+                    let count = messages.len();
+                    sets.push((metadata, messages));
+                    (count, Vec::new())
+                }
+                // Still report how many messages the document declares
+                Err(errors) => {
+                    failed = true;
+                    let declared = document_value(&raw, options.format)
+                        .ok()
+                        .and_then(|json| {
+                            json.as_object()
+                                .and_then(|obj| collect_message_maps(obj).ok().map(|e| e.len()))
+                        })
+                        .unwrap_or(0);
+                    (declared, errors)
+                }
+            };
+            reports.push(Validation {
+                input: display_path(file),
+                messages,
+                errors,
+                warnings,
+            });
         }
-    }
-}
 
-/// Parses JSON message definitions into internal structures.
-///
-/// # Arguments
-/// * `map` - JSON object containing metadata and message definitions
-///
-/// # Returns
-/// * `Ok((metadata, messages))` - Parsed metadata and list of message definitions
-/// * `Err(...)` - Parse error with detailed context
-///
-/// # Example
-/// ```
-/// use serde_json::json;
-/// use h6xserial_idl::parse_messages;
-///
-/// let json = json!({
-///     "version": "1.0.0",
-///     "packets": {
-///         "ping": {
-///             "packet_id": 0,
-///             "msg_type": "uint8",
-///             "array": false
-///         }
-///     }
-/// });
-/// let obj = json.as_object().unwrap();
-/// let (metadata, messages) = parse_messages(obj).unwrap();
-/// assert_eq!(messages.len(), 1);
-/// ```
-pub fn parse_messages(map: &Map<String, Value>) -> Result<(Metadata, Vec<MessageDefinition>)> {
-    let mut metadata = Metadata::default();
-    let mut messages = Vec::new();
+        if !failed {
+            let total: usize = sets.iter().map(|(_, messages)| messages.len()).sum();
+            let mut errors = Vec::new();
+            if sets.len() > 1 {
+                if let Err(error) = merge_message_sets(sets) {
+                    errors = match error {
+                        IdlError::Multiple(errors) => errors,
+                        error => vec![error],
+                    };
+                }
+                reports.push(Validation {
+                    input: files
+                        .iter()
+                        .map(|file| display_path(file))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                    messages: total,
+                    errors: Vec::new(),
+                    warnings: Vec::new(),
+                });
+            }
+            if total == 0 {
+                errors.push(IdlError::missing_field(
+                    "",
+                    "packets",
+                    "no message definitions found",
+                ));
+            }
+            reports
+                .last_mut()
+                .expect("at least one input")
+                .errors
+                .extend(errors);
+        }
 
-    // Parse metadata fields
-    if let Some(version) = map.get("version") {
-        metadata.version = version.as_str().map(|s| s.to_string());
-    }
-    if let Some(max_address) = map.get("max_address") {
-        metadata.max_address = max_address.as_u64().map(|v| v as u32);
+        match format {
+            ReportFormat::Text => reports.iter().for_each(Validation::print_text),
+            ReportFormat::Json => {
+                let json = match reports.as_slice() {
+                    [report] => report.to_json(),
+                    reports => Value::Array(reports.iter().map(Validation::to_json).collect()),
+                };
+                println!("{}", serde_json::to_string_pretty(&json)?);
+            }
+        }
+        if reports.iter().any(|report| !report.errors.is_empty()) {
+            bail!("validation of {} failed", self.describe(&files));
+        }
+        Ok(())
     }
-    if let Some(devices_obj) = map.get("devices").and_then(|v| v.as_object()) {
-        metadata.devices = parse_devices(devices_obj)?;
+
+    /// The input files for messages, separated by commas.
+    fn describe(&self, files: &[PathBuf]) -> String {
+        let names: Vec<String> = files.iter().map(|f| f.display().to_string()).collect();
+        names.join(", ")
     }
+}
 
-    // Parse packets from "packets" section
-    let packets_map = map
-        .get("packets")
-        .and_then(|v| v.as_object())
-        .with_context(|| "missing required 'packets' object")?;
+/// A warning paired with the included file it is in, or `None` for the
+/// input itself.
+type Warning = (Option<PathBuf>, validate::ValidationWarning);
+
+/// Pairs the warnings of `resolver` with their file, as in [`Warning`].
+fn take_warnings(resolver: &mut Resolver, input: &Path) -> Vec<Warning> {
+    resolver
+        .take_warnings()
+        .into_iter()
+        .map(|(source, warning)| ((source != input).then_some(source), warning))
+        .collect()
+}
 
-    for (key, value) in packets_map {
-        let msg_map = value
-            .as_object()
-            .with_context(|| format!("message '{}' must be an object", key))?;
-        let definition = parse_message_definition(key, msg_map)?;
-        messages.push(definition);
+/// Text of a [`Warning`], with the included file in front like an
+/// [`IdlError::InFile`].
+fn warning_text((source, warning): &Warning) -> String {
+    match source {
+        Some(source) => format!("{}: {}", source.display(), warning),
+        None => warning.to_string(),
     }
+}
 
-    Ok((metadata, messages))
+/// `--validate` result of one input, or of merging several.
+struct Validation {
+    input: String,
+    messages: usize,
+    errors: Vec<IdlError>,
+    warnings: Vec<Warning>,
 }
 
-/// Parses devices section from JSON.
-fn parse_devices(devices_obj: &Map<String, Value>) -> Result<Vec<DeviceInfo>> {
-    let mut devices = Vec::new();
-    for (name, value) in devices_obj {
-        let device_map = value
-            .as_object()
-            .with_context(|| format!("device '{}' must be an object", name))?;
-
-        let role = device_map
-            .get("role")
-            .and_then(|v| v.as_str())
-            .unwrap_or("client")
-            .to_string();
+impl Validation {
+    fn print_text(&self) {
+        for error in &self.errors {
+            println!("error: {}", error);
+        }
+        for warning in &self.warnings {
+            println!("warning: {}", warning_text(warning));
+        }
+        println!(
+            "{}: {} messages, {} errors, {} warnings",
+            self.input,
+            self.messages,
+            self.errors.len(),
+            self.warnings.len()
+        );
+    }
 
-        let id = device_map
-            .get("id")
-            .and_then(|v| v.as_u64())
-            .map(|v| v as u32);
+    fn to_json(&self) -> Value {
+        let mut diagnostics: Vec<Value> = self
+            .errors
+            .iter()
+            .map(|error| {
+                let mut entry = serde_json::json!({
+                    "path": error.pointer(),
+                    "severity": "error",
+                    "message": error.message(),
+                });
+                if let IdlError::Syntax { line, column, .. } = error {
+                    entry["line"] = (*line).into();
+                    entry["column"] = (*column).into();
+                }
+                entry
+            })
+            .collect();
+        diagnostics.extend(self.warnings.iter().map(|(source, warning)| {
+            let message = match source {
+                Some(source) => format!("{}: {}", source.display(), warning.message),
+                None => warning.message.clone(),
+            };
+            serde_json::json!({
+                "path": warning.path,
+                "severity": "warning",
+                "message": message,
+            })
+        }));
+        serde_json::json!({
+            "input": self.input,
+            "messages": self.messages,
+            "errors": self.errors.len(),
+            "warnings": self.warnings.len(),
+            "diagnostics": diagnostics,
+        })
+    }
+}
 
-        let description = device_map
-            .get("description")
-            .and_then(|v| v.as_str())
-            .map(|s| s.to_string());
+/// Writes the single-header layout for `-` output. Options that add files
+/// (`.c` sources, self-tests, fuzz harnesses) are rejected since only one
+/// stream is available.
+fn write_single_header_to_stdout(
+    metadata: &Metadata,
+    messages: &[MessageDefinition],
+    input_path: &Path,
+    base_name: &str,
+    options: &emit_c::CGenOptions,
+) -> Result<()> {
+    if options.impl_style == emit_c::ImplStyle::Source
+        || options.emit_tests
+        || options.emit_fuzz
+        || options.cmake.is_some()
+        || options.package.is_some()
+        || options.send_stack_limit.is_some()
+        || options.emit_skeleton
+    {
+        bail!(
+            "'-' output writes a single header; --impl-style source, --emit-tests, --emit-fuzz, --emit-cmake, --package, --emit-send and --emit-skeleton generate role headers or several files and need an output directory"
+        );
+    }
+    let header_path = PathBuf::from(format!("{}.h", base_name));
+    let files = emit_c::generate_files(metadata, messages, input_path, &header_path, options)?;
+    let mut stdout = io::stdout().lock();
+    for file in &files {
+        stdout
+            .write_all(file.content.as_bytes())
+            .map_err(|e| IdlError::write(Path::new(STDOUT_LABEL), e))?;
+    }
+    Ok(())
+}
 
-        devices.push(DeviceInfo {
-            name: name.clone(),
-            role,
-            id,
-            description,
-        });
+/// Shows `path` relative to the current directory when it lies below it.
+/// The `--emit-cmake` settings: the inputs relative to `output_dir`, and
+/// the arguments that repeat the C generation of `config`.
+/// The command line of this run for the "DO NOT EDIT" banner of
+/// `--readonly-output`, without the options that leave the generated files
+/// alone, so a `--check` run expects the same banner.
+fn regenerate_command(args: impl IntoIterator<Item = String>) -> String {
+    let mut words = vec![env!("CARGO_PKG_NAME").to_string()];
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--check" | "--force" | "--watch" => {}
+            "--manifest" => {
+                args.next();
+            }
+            _ if arg.starts_with("--manifest=") => {}
+            _ => words.push(shell_word(&arg)),
+        }
     }
-    Ok(devices)
+    words.join(" ")
 }
 
-/// Calculates the maximum byte size of a message body.
-fn message_body_max_size(body: &MessageBody) -> usize {
-    match body {
-        MessageBody::Scalar(spec) => spec.primitive.byte_len(),
-        MessageBody::Array(spec) => spec.max_length * spec.primitive.byte_len(),
-        MessageBody::Struct(spec) => struct_spec_max_size(spec),
+/// `word` quoted for a POSIX shell where needed.
+fn shell_word(word: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c);
+    if !word.is_empty() && word.chars().all(plain) {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', "'\\''"))
     }
 }
 
-/// Calculates the maximum byte size of a struct spec (recursively).
-fn struct_spec_max_size(spec: &StructSpec) -> usize {
-    spec.fields
-        .iter()
-        .map(|f| match &f.field_type {
-            StructFieldType::Primitive(prim) => prim.byte_len(),
-            StructFieldType::Array(arr) => arr.max_length * arr.primitive.byte_len(),
-            StructFieldType::Nested(nested) => struct_spec_max_size(nested),
-        })
-        .sum()
+fn cmake_options(
+    config: &config::GenerationConfig,
+    inputs: &[PathBuf],
+    base_name: &str,
+    output_dir: &Path,
+) -> Result<emit_c::CMakeOptions> {
+    let cwd = env::current_dir().context("failed to read the current directory")?;
+    let output_dir = cwd.join(output_dir);
+    let relative = |path: &Path| relative_path(&cwd.join(path), &output_dir);
+    let mut args = vec!["--base-name".to_string(), base_name.to_string()];
+    let template_dir = config
+        .template_dir
+        .as_deref()
+        .map(|dir| relative(dir).display().to_string());
+    // The command line name of a value
+    fn name<T: clap::ValueEnum>(value: Option<T>) -> Option<String> {
+        value
+            .and_then(|value| value.to_possible_value())
+            .map(|value| value.get_name().to_string())
+    }
+    for (option, value) in [
+        ("input-format", name(config.input_format)),
+        ("symbol-prefix", config.symbol_prefix.clone()),
+        ("impl-style", name(config.impl_style)),
+        ("std", name(config.std)),
+        ("encode-range", name(config.encode_range)),
+        ("struct-layout", name(config.struct_layout)),
+        ("source-comment", name(config.source_comment)),
+        ("package", name(config.package)),
+        ("template-dir", template_dir),
+        (
+            "max-payload",
+            config.max_payload_bytes.map(|n| n.to_string()),
+        ),
+        (
+            "max-array-length",
+            config.max_array_length.map(|n| n.to_string()),
+        ),
+        (
+            "max-nesting-depth",
+            config.max_nesting_depth.map(|n| n.to_string()),
+        ),
+        (
+            "send-stack-limit",
+            config.send_stack_limit.map(|n| n.to_string()),
+        ),
+    ] {
+        if let Some(value) = value {
+            args.push(format!("--{}", option));
+            args.push(value);
+        }
+    }
+    for (flag, setting) in [
+        ("misra", config.misra),
+        ("doc-comments", config.doc_comments),
+        ("fast-path", config.fast_path),
+        ("deterministic-decode", config.deterministic_decode),
+        ("all-helpers", config.all_helpers),
+        ("emit-tests", config.emit_tests),
+        ("emit-fuzz", config.emit_fuzz),
+        ("emit-python", config.emit_python),
+        ("emit-cmake", config.emit_cmake),
+        ("emit-send", config.emit_send),
+        ("strict", config.strict),
+    ] {
+        if setting == Some(true) {
+            args.push(format!("--{}", flag));
+        }
+    }
+    Ok(emit_c::CMakeOptions {
+        inputs: inputs.iter().map(|input| relative(input)).collect(),
+        args,
+    })
 }
 
-/// Parses a single message definition from JSON.
-///
-/// # Arguments
-/// * `name` - Message name from JSON key
-/// * `map` - JSON object for this message
-///
-/// # Returns
-/// * `Ok(MessageDefinition)` - Parsed message
-/// * `Err(...)` - Parse error with context
-fn parse_message_definition(name: &str, map: &Map<String, Value>) -> Result<MessageDefinition> {
-    let packet_id = map
-        .get("packet_id")
-        .and_then(|v| v.as_u64())
-        .with_context(|| {
-            format!(
-                "message '{}' is missing required field 'packet_id' (must be 0-255)",
-                name
-            )
-        })? as u32;
+fn display_path(path: &Path) -> String {
+    env::current_dir()
+        .ok()
+        .and_then(|cwd| path.strip_prefix(cwd).ok())
+        .unwrap_or(path)
+        .display()
+        .to_string()
+}
 
-    if packet_id > 255 {
+/// Compares generated `outputs` with the files on disk for `--check`,
+/// printing a unified diff for each stale or missing one.
+fn check_outputs(outputs: &[(PathBuf, String)]) -> Result<()> {
+    let mut stale = 0;
+    for (path, generated) in outputs {
+        let label = display_path(path);
+        match fs::read(path) {
+            Ok(existing) if existing == generated.as_bytes() => continue,
+            Ok(existing) => {
+                let existing = String::from_utf8_lossy(&existing);
+                let diff = diff::unified_diff(
+                    &existing,
+                    generated,
+                    &label,
+                    &format!("{} (generated)", label),
+                );
+                if diff.is_empty() {
+                    println!("{}: differs in line endings or trailing newline", label);
+                } else {
+                    print!("{}", diff);
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                println!("{}: missing", label);
+                print!("{}", diff::unified_diff("", generated, "/dev/null", &label));
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("failed to read {}", label));
+            }
+        }
+        stale += 1;
+    }
+    if stale > 0 {
         bail!(
-            "message '{}' has packet_id {} which exceeds maximum of 255",
-            name,
-            packet_id
+            "{} of {} generated file(s) are out of date; rerun without --check to update them",
+            stale,
+            outputs.len()
         );
     }
+    println!("{} generated file(s) are up to date.", outputs.len());
+    Ok(())
+}
 
-    let description = map
-        .get("msg_desc")
-        .and_then(|v| v.as_str())
-        .map(|s| s.to_string());
-
-    // Parse request_type (pub or sub), defaults to pub
-    let request_type = if let Some(rt_value) = map.get("request_type") {
-        let rt_str = rt_value.as_str().with_context(|| {
-            format!(
-                "message '{}' has invalid 'request_type' (must be a string)",
-                name
-            )
-        })?;
-        RequestType::from_str(rt_str)?
-    } else {
-        RequestType::default()
-    };
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, clap::ValueEnum)]
+pub(crate) enum TargetLanguage {
+    #[value(alias = "c99")]
+    C,
+}
 
-    // Parse target_client_id, defaults to -1 (all clients)
-    let target_client_id = map
-        .get("target_client_id")
-        .and_then(|v| v.as_i64())
-        .map(|v| v as i32)
-        .unwrap_or(-1);
-
-    let msg_type = map
-        .get("msg_type")
-        .and_then(|v| v.as_str())
-        .with_context(|| {
-            format!(
-                "message '{}' is missing required field 'msg_type' (e.g., 'uint8', 'float32', 'struct')",
-                name
-            )
-        })?;
+impl TargetLanguage {
+    pub(crate) fn try_from_str(value: &str) -> Option<Self> {
+        <Self as clap::ValueEnum>::from_str(value, true).ok()
+    }
 
-    if msg_type.eq_ignore_ascii_case("struct") {
-        let fields_obj = map
-            .get("fields")
-            .and_then(|v| v.as_object())
-            .with_context(|| {
-                format!(
-                    "struct message '{}' requires a 'fields' object containing field definitions",
-                    name
-                )
-            })?;
+    /// Name used on the command line and in the configuration file.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            TargetLanguage::C => "c",
+        }
+    }
 
-        if fields_obj.is_empty() {
-            bail!(
-                "struct message '{}' must define at least one field in 'fields' object",
-                name
-            );
+    fn display_name(self) -> &'static str {
+        match self {
+            TargetLanguage::C => "C99",
         }
-        let fields = parse_struct_fields(fields_obj, name)?;
-        let body = MessageBody::Struct(StructSpec { fields });
-        let max_size = message_body_max_size(&body);
-        if max_size > MAX_PAYLOAD_SIZE {
-            bail!(
-                "struct message '{}' has maximum size {} bytes which exceeds protocol limit of {} bytes",
-                name,
-                max_size,
-                MAX_PAYLOAD_SIZE
-            );
+    }
+
+    fn template_subdir(self) -> &'static str {
+        match self {
+            TargetLanguage::C => "c",
         }
-        Ok(MessageDefinition {
-            name: name.to_string(),
-            packet_id,
-            description,
-            body,
-            request_type,
-            target_client_id,
-        })
-    } else {
-        let primitive = PrimitiveType::from_str(msg_type).with_context(|| {
-            format!(
-                "unsupported 'msg_type' '{}' for message '{}'",
-                msg_type, name
-            )
-        })?;
-        let endian = get_optional_endian(map)?.unwrap_or_default();
-        let is_array = map.get("array").and_then(|v| v.as_bool()).unwrap_or(false);
-        if is_array {
-            let max_length = map
-                .get("max_length")
-                .and_then(|v| v.as_u64())
-                .with_context(|| {
-                    format!(
-                        "array message '{}' requires 'max_length' field (1-{})",
-                        name, MAX_ARRAY_LENGTH
-                    )
-                })? as usize;
-
-            if max_length == 0 {
-                bail!(
-                    "array message '{}' has max_length of 0, must be at least 1",
-                    name
-                );
-            }
-
-            if max_length > MAX_ARRAY_LENGTH {
-                bail!(
-                    "array message '{}' has max_length {} which exceeds maximum of {}",
-                    name,
-                    max_length,
-                    MAX_ARRAY_LENGTH
-                );
-            }
-
-            // Check payload size constraint
-            let payload_size = max_length * primitive.byte_len();
-            if payload_size > MAX_PAYLOAD_SIZE {
-                bail!(
-                    "array message '{}' has maximum payload size {} bytes ({}*{}) which exceeds protocol limit of {} bytes",
-                    name,
-                    payload_size,
-                    max_length,
-                    primitive.byte_len(),
-                    MAX_PAYLOAD_SIZE
-                );
-            }
+    }
 
-            let sector_bytes = map
-                .get("sector_bytes")
-                .and_then(|v| v.as_u64())
-                .map(|v| v as usize);
-            Ok(MessageDefinition {
-                name: name.to_string(),
-                packet_id,
-                description,
-                body: MessageBody::Array(ArraySpec {
-                    primitive,
-                    endian,
-                    max_length,
-                    sector_bytes,
-                }),
-                request_type,
-                target_client_id,
-            })
-        } else {
-            Ok(MessageDefinition {
-                name: name.to_string(),
-                packet_id,
-                description,
-                body: MessageBody::Scalar(ScalarSpec { primitive, endian }),
-                request_type,
-                target_client_id,
-            })
+    /// Templates compiled into the binary, by file name.
+    fn embedded_templates(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            TargetLanguage::C => &[
+                (
+                    "helpers_u16_le.h",
+                    include_str!("msg_template/c/helpers_u16_le.h"),
+                ),
+                (
+                    "helpers_u16_be.h",
+                    include_str!("msg_template/c/helpers_u16_be.h"),
+                ),
+                (
+                    "helpers_u24_le.h",
+                    include_str!("msg_template/c/helpers_u24_le.h"),
+                ),
+                (
+                    "helpers_u24_be.h",
+                    include_str!("msg_template/c/helpers_u24_be.h"),
+                ),
+                (
+                    "helpers_u32_le.h",
+                    include_str!("msg_template/c/helpers_u32_le.h"),
+                ),
+                (
+                    "helpers_u32_be.h",
+                    include_str!("msg_template/c/helpers_u32_be.h"),
+                ),
+                (
+                    "helpers_u64_le.h",
+                    include_str!("msg_template/c/helpers_u64_le.h"),
+                ),
+                (
+                    "helpers_u64_be.h",
+                    include_str!("msg_template/c/helpers_u64_be.h"),
+                ),
+                (
+                    "helpers_f16_le.h",
+                    include_str!("msg_template/c/helpers_f16_le.h"),
+                ),
+                (
+                    "helpers_f16_be.h",
+                    include_str!("msg_template/c/helpers_f16_be.h"),
+                ),
+                (
+                    "helpers_f32_le.h",
+                    include_str!("msg_template/c/helpers_f32_le.h"),
+                ),
+                (
+                    "helpers_f32_be.h",
+                    include_str!("msg_template/c/helpers_f32_be.h"),
+                ),
+                (
+                    "helpers_f64_le.h",
+                    include_str!("msg_template/c/helpers_f64_le.h"),
+                ),
+                (
+                    "helpers_f64_be.h",
+                    include_str!("msg_template/c/helpers_f64_be.h"),
+                ),
+            ],
         }
     }
 }
 
-/// Parses struct fields recursively, supporting nested structs.
-fn parse_struct_fields(
-    fields_obj: &Map<String, Value>,
-    parent_name: &str,
-) -> Result<Vec<StructField>> {
-    let mut fields = Vec::new();
-    for (field_name, field_value) in fields_obj {
-        let field_map = field_value.as_object().with_context(|| {
-            format!(
-                "field '{}' in '{}' must be an object",
-                field_name, parent_name
-            )
-        })?;
-
-        // Support both "type" and "msg_type" for field type specification
-        let type_str = field_map
-            .get("type")
-            .or_else(|| field_map.get("msg_type"))
-            .and_then(|v| v.as_str())
-            .with_context(|| {
-                format!(
-                    "field '{}' in '{}' is missing 'type' or 'msg_type'",
-                    field_name, parent_name
-                )
-            })?;
+#[derive(Default, Debug, Clone)]
+pub struct DeviceInfo {
+    pub name: String,
+    pub role: String,
+    pub id: Option<u32>,
+    pub description: Option<String>,
+}
 
-        let endian = get_optional_endian(field_map)?.unwrap_or_default();
+#[derive(Debug)]
+pub struct Metadata {
+    pub version: Option<String>,
+    pub max_address: Option<u32>,
+    /// Bits of the packet ID field (`packet_id_width`): 8, or 16 for
+    /// packet IDs up to 65535
+    pub packet_id_width: u8,
+    pub devices: Vec<DeviceInfo>,
+    /// Active payload size limit in bytes (metadata `max_payload_bytes`,
+    /// overridable with `--max-payload`)
+    pub max_payload_bytes: usize,
+    /// Active array length limit (metadata `max_array_length`, overridable
+    /// with `--max-array-length`)
+    pub max_array_length: usize,
+    /// Active struct nesting limit (metadata `max_nesting_depth`,
+    /// overridable with `--max-nesting-depth`)
+    pub max_nesting_depth: usize,
+    /// Shared struct types from the top-level `types` object, each after
+    /// the shared types it contains
+    pub types: Vec<StructSpec>,
+    /// Named values from the top-level `constants` object, in document order
+    pub constants: Vec<(String, ConstValue)>,
+    /// Inclusive packet ID ranges of the top-level `reserved_ids` list, in
+    /// document order. Only messages with `reserved_ok` may use them.
+    pub reserved_ids: Vec<(u32, u32)>,
+    /// Sections of the Markdown command list from the top-level
+    /// `command_groups` list, in document order; never overlapping
+    pub command_groups: Vec<CommandGroup>,
+    /// Byte order of the values that set no `endianess` of their own
+    /// (`default_endianness`); little-endian when unset
+    pub default_endianness: Option<Endian>,
+}
 
-        // Check if this is a nested struct
-        if type_str.eq_ignore_ascii_case("struct") {
-            let nested_fields_obj = field_map
-                .get("fields")
-                .and_then(|v| v.as_object())
-                .with_context(|| {
-                    format!(
-                        "nested struct field '{}' in '{}' requires a 'fields' object",
-                        field_name, parent_name
-                    )
-                })?;
+/// A named, inclusive packet ID range grouping commands in the docs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CommandGroup {
+    pub name: String,
+    pub first: u32,
+    pub last: u32,
+}
 
-            if nested_fields_obj.is_empty() {
-                bail!(
-                    "nested struct field '{}' in '{}' must define at least one field",
-                    field_name,
-                    parent_name
-                );
-            }
+/// Value of a named protocol constant.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConstValue {
+    Int(i64),
+    Float(f64),
+    Str(String),
+}
 
-            let nested_path = format!("{}.{}", parent_name, field_name);
-            let nested_fields = parse_struct_fields(nested_fields_obj, &nested_path)?;
-            fields.push(StructField {
-                name: field_name.clone(),
-                field_type: StructFieldType::Nested(StructSpec {
-                    fields: nested_fields,
-                }),
-                endian,
-            });
-        } else {
-            let primitive = PrimitiveType::from_str(type_str).with_context(|| {
-                format!(
-                    "unsupported type '{}' for field '{}' in '{}'",
-                    type_str, field_name, parent_name
-                )
-            })?;
+impl fmt::Display for ConstValue {
+    /// Formats the value as written in JSON, strings quoted.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConstValue::Int(value) => write!(f, "{}", value),
+            ConstValue::Float(value) => write!(f, "{:?}", value),
+            ConstValue::Str(value) => write!(f, "{}", Value::from(value.as_str())),
+        }
+    }
+}
 
-            // Check if this field is an array
-            let is_array = field_map
-                .get("array")
-                .and_then(|v| v.as_bool())
-                .unwrap_or(false);
-            if is_array {
-                let max_length = field_map
-                    .get("max_length")
-                    .and_then(|v| v.as_u64())
-                    .with_context(|| {
-                        format!(
-                            "array field '{}' in '{}' requires 'max_length' field (1-{})",
-                            field_name, parent_name, MAX_ARRAY_LENGTH
-                        )
-                    })? as usize;
+impl Default for Metadata {
+    fn default() -> Self {
+        Self {
+            version: None,
+            max_address: None,
+            packet_id_width: DEFAULT_PACKET_ID_WIDTH,
+            devices: Vec::new(),
+            max_payload_bytes: DEFAULT_MAX_PAYLOAD_BYTES,
+            max_array_length: DEFAULT_MAX_ARRAY_LENGTH,
+            max_nesting_depth: DEFAULT_MAX_NESTING_DEPTH,
+            types: Vec::new(),
+            constants: Vec::new(),
+            reserved_ids: Vec::new(),
+            command_groups: Vec::new(),
+            default_endianness: None,
+        }
+    }
+}
 
-                if max_length == 0 {
-                    bail!(
-                        "array field '{}' in '{}' has max_length of 0, must be at least 1",
-                        field_name,
-                        parent_name
-                    );
-                }
+/// Input or output path meaning stdin or stdout on the command line.
+const STDIO_PATH: &str = "-";
+/// Shown instead of a path for input read from stdin, e.g. in the
+/// `Source:` comment of generated headers.
+pub(crate) const STDIN_LABEL: &str = "<stdin>";
+const STDOUT_LABEL: &str = "<stdout>";
 
-                if max_length > MAX_ARRAY_LENGTH {
-                    bail!(
-                        "array field '{}' in '{}' has max_length {} which exceeds maximum of {}",
-                        field_name,
-                        parent_name,
-                        max_length,
-                        MAX_ARRAY_LENGTH
-                    );
-                }
+/// How generated files name their input document in the `Source:` comment.
+///
+/// The default writes the path as given, which differs between checkouts
+/// when it is absolute; the other modes keep the output reproducible.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum SourceComment {
+    /// The input path as given on the command line
+    #[default]
+    Path,
+    /// The input path relative to the current directory
+    Relative,
+    /// Only the file name of the input
+    Basename,
+    /// No `Source:` comment, so the output only depends on the IR
+    None,
+}
 
-                fields.push(StructField {
-                    name: field_name.clone(),
-                    field_type: StructFieldType::Array(StructFieldArraySpec {
-                        primitive,
-                        max_length,
-                    }),
-                    endian,
-                });
-            } else {
-                fields.push(StructField {
-                    name: field_name.clone(),
-                    field_type: StructFieldType::Primitive(primitive),
-                    endian,
-                });
+impl SourceComment {
+    /// How `input_path` is shown, or `None` when the comment is left out.
+    ///
+    /// # Example
+    /// ```
+    /// use std::path::Path;
+    /// use h6xserial_idl::SourceComment;
+    ///
+    /// let input = Path::new("/home/ci/proto/msgs/protocol.json");
+    /// assert_eq!(SourceComment::Basename.label(input).as_deref(), Some("protocol.json"));
+    /// assert_eq!(SourceComment::None.label(input), None);
+    /// ```
+    pub fn label(self, input_path: &Path) -> Option<String> {
+        if input_path == Path::new(STDIN_LABEL) {
+            return (self != SourceComment::None).then(|| STDIN_LABEL.to_string());
+        }
+        match self {
+            SourceComment::Path => Some(input_path.display().to_string()),
+            SourceComment::Relative => {
+                let relative = env::current_dir()
+                    .map(|cwd| relative_path(input_path, &cwd))
+                    .unwrap_or_else(|_| input_path.to_path_buf());
+                // Forward slashes so the text is the same on every host
+                let parts: Vec<String> = relative
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                    .collect();
+                Some(parts.join("/"))
             }
+            SourceComment::Basename => Some(input_path.file_name().map_or_else(
+                || input_path.display().to_string(),
+                |name| name.to_string_lossy().into_owned(),
+            )),
+            SourceComment::None => None,
         }
     }
-    Ok(fields)
+
+    /// Like [`label`](Self::label) for several merged inputs, joined with
+    /// `, `.
+    pub fn label_all(self, input_path: &Path, extra_inputs: &[PathBuf]) -> Option<String> {
+        let labels: Option<Vec<String>> = std::iter::once(input_path)
+            .chain(extra_inputs.iter().map(PathBuf::as_path))
+            .map(|path| self.label(path))
+            .collect();
+        labels.map(|labels| labels.join(", "))
+    }
 }
 
-fn get_optional_endian(map: &Map<String, Value>) -> Result<Option<Endian>> {
-    for key in ["endianess", "endianness"] {
-        if let Some(value) = map.get(key) {
-            let text = value
-                .as_str()
-                .with_context(|| format!("'{}' must be a string", key))?;
-            return Ok(Some(Endian::from_str(text)?));
+/// `path` relative to the directory `base`, both taken lexically (relative
+/// paths are relative to `base` already).
+fn relative_path(path: &Path, base: &Path) -> PathBuf {
+    use std::path::Component;
+
+    fn normalize(path: &Path) -> Vec<Component<'_>> {
+        let mut parts: Vec<Component<'_>> = Vec::new();
+        for component in path.components() {
+            match component {
+                Component::CurDir => {}
+                Component::ParentDir if matches!(parts.last(), Some(Component::Normal(_))) => {
+                    parts.pop();
+                }
+                other => parts.push(other),
+            }
         }
+        parts
+    }
+
+    if path.is_relative() {
+        return normalize(path).iter().collect();
+    }
+    let path = normalize(path);
+    let base = normalize(base);
+    let common = path.iter().zip(&base).take_while(|(a, b)| a == b).count();
+    if common == 0 {
+        // Different roots (e.g. drives); nothing to be relative to
+        return path.iter().collect();
+    }
+    let mut relative = PathBuf::new();
+    for _ in common..base.len() {
+        relative.push("..");
     }
-    Ok(None)
+    relative.extend(&path[common..]);
+    relative
 }
 
-pub(crate) fn load_templates(language: TargetLanguage, files: &[&str]) -> Result<String> {
-    let template_dir = resolve_template_dir(language)?;
-    let mut combined = String::new();
+/// Syntax of the input document.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum InputFormat {
+    #[default]
+    Json,
+    /// JSON with `//` and `/* */` comments and trailing commas
+    Jsonc,
+    /// TOML with the same keys as the JSON format: a `[packets.NAME]` table
+    /// per message, struct fields as `[packets.NAME.fields.FIELD]` tables or
+    /// as `[[packets.NAME.fields]]` entries with a `name` key
+    Toml,
+}
 
-    for file_name in files {
-        let path = template_dir.join(file_name);
-        let content = fs::read_to_string(&path)
-            .with_context(|| format!("failed to read template {}", path.display()))?;
-        combined.push_str(&content);
-        if !content.ends_with('\n') {
-            combined.push('\n');
+impl InputFormat {
+    /// The format implied by the file extension: TOML for `.toml`, JSONC
+    /// for `.jsonc`, JSON for anything else.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("toml") => InputFormat::Toml,
+            Some(ext) if ext.eq_ignore_ascii_case("jsonc") => InputFormat::Jsonc,
+            _ => InputFormat::Json,
         }
-        combined.push('\n');
     }
+}
 
-    Ok(combined)
+/// Overrides applied while parsing, typically from command line flags.
+#[derive(Clone, Debug, Default)]
+pub struct ParseOptions {
+    /// Replaces the metadata `max_payload_bytes` value when set
+    pub max_payload_bytes: Option<usize>,
+    /// Replaces the metadata `max_array_length` value when set
+    pub max_array_length: Option<usize>,
+    /// Replaces the metadata `max_nesting_depth` value when set
+    pub max_nesting_depth: Option<usize>,
+    /// Treat unknown keys (see [`validate::unknown_key_warnings`]) as errors
+    pub strict: bool,
+    /// Stop at the first error instead of collecting every problem
+    pub fail_fast: bool,
+    /// Syntax of the input text
+    pub format: InputFormat,
 }
 
-fn resolve_template_dir(language: TargetLanguage) -> Result<PathBuf> {
-    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-    let subdir = language.template_subdir();
-    let relative_candidates = [
-        format!("src/msg_template/{}", subdir),
-        format!("msg_template/{}", subdir),
-        format!("../src/msg_template/{}", subdir),
-        format!("../msg_template/{}", subdir),
-    ];
+type ParseResult<T> = std::result::Result<T, IdlError>;
+
+/// Accumulates parse errors, optionally keeping only the first one.
+struct Diagnostics {
+    errors: Vec<IdlError>,
+    fail_fast: bool,
+}
 
-    let mut candidates: Vec<PathBuf> = Vec::new();
-    for rel in &relative_candidates {
-        candidates.push(PathBuf::from(rel));
+impl Diagnostics {
+    fn push(&mut self, error: IdlError) {
+        if !self.stop() {
+            self.errors.push(error);
+        }
     }
-    for rel in &relative_candidates {
-        candidates.push(manifest_dir.join(rel));
+
+    /// Records the error of `result`, if any, and returns its value.
+    fn check<T>(&mut self, result: ParseResult<T>) -> Option<T> {
+        result.map_err(|error| self.push(error)).ok()
     }
 
-    for candidate in candidates {
-        if candidate.is_dir() {
-            return Ok(candidate);
-        }
+    /// Whether parsing should stop because fail-fast mode hit an error.
+    fn stop(&self) -> bool {
+        self.fail_fast && !self.errors.is_empty()
     }
+}
 
-    bail!(
-        "could not locate 'msg_template/{}' directory for language {}",
-        subdir,
-        language.display_name()
-    )
+/// Where an active limit came from, reported in limit errors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum LimitSource {
+    Default,
+    Metadata,
+    CommandLine,
 }
 
-fn resolve_default_path(primary: &str, fallback: &str) -> PathBuf {
-    let primary_path = PathBuf::from(primary);
-    if primary_path.exists() {
-        primary_path
-    } else {
-        PathBuf::from(fallback)
+impl LimitSource {
+    fn describe(self, metadata_key: &str, flag: &str) -> String {
+        match self {
+            LimitSource::Default => "default".to_string(),
+            LimitSource::Metadata => format!("metadata '{}'", metadata_key),
+            LimitSource::CommandLine => flag.to_string(),
+        }
     }
 }
 
-pub(crate) fn to_snake_case(name: &str) -> String {
-    let mut result = String::new();
-    let mut last_was_underscore = false;
-    for ch in name.chars() {
-        if ch.is_ascii_alphanumeric() {
-            let lower = ch.to_ascii_lowercase();
-            if result.is_empty() && lower.is_ascii_digit() {
-                result.push('_');
-            }
-            result.push(lower);
-            last_was_underscore = false;
-        } else if !last_was_underscore {
-            result.push('_');
-            last_was_underscore = true;
+/// Size limits and defaults applied while parsing message definitions.
+#[derive(Clone, Debug)]
+pub(crate) struct Limits {
+    max_payload_bytes: usize,
+    max_array_length: usize,
+    /// Human readable origin of `max_array_length`
+    array_length_source: String,
+    max_nesting_depth: usize,
+    /// Human readable origin of `max_nesting_depth`
+    nesting_depth_source: String,
+    /// Byte order of values without an endianness key
+    default_endian: Endian,
+    packet_id_width: u8,
+    /// Human readable origin of `packet_id_width`
+    packet_id_width_source: String,
+}
+
+impl Limits {
+    /// The limits of `metadata`, as applied to programmatically built messages.
+    pub(crate) fn of(metadata: &Metadata) -> Self {
+        Self {
+            max_payload_bytes: metadata.max_payload_bytes,
+            max_array_length: metadata.max_array_length,
+            array_length_source: "configured".to_string(),
+            max_nesting_depth: metadata.max_nesting_depth,
+            nesting_depth_source: "configured".to_string(),
+            default_endian: metadata.default_endianness.unwrap_or_default(),
+            packet_id_width: metadata.packet_id_width,
+            packet_id_width_source: "configured".to_string(),
         }
     }
-    if result.ends_with('_') {
-        result.pop();
-    }
-    if result.is_empty() {
-        result.push_str("msg");
-    }
-    result
 }
 
-pub(crate) fn to_macro_ident(name: &str) -> String {
-    let mut result = String::new();
-    let mut last_was_underscore = false;
-    for ch in name.chars() {
-        if ch.is_ascii_alphanumeric() {
-            let upper = ch.to_ascii_uppercase();
-            if result.is_empty() && upper.is_ascii_digit() {
-                result.push('_');
-            }
-            result.push(upper);
-            last_was_underscore = false;
-        } else if !last_was_underscore {
-            result.push('_');
-            last_was_underscore = true;
+/// Request type for pub/sub semantics.
+/// - Pub: Server publishes (sends) to client(s)
+/// - Sub: Server subscribes (receives) from client(s)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RequestType {
+    #[default]
+    Pub,
+    Sub,
+}
+
+impl RequestType {
+    pub(crate) fn from_str(value: &str) -> Result<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "pub" | "publish" => Ok(RequestType::Pub),
+            "sub" | "subscribe" => Ok(RequestType::Sub),
+            other => bail!(
+                "unsupported request_type '{}', expected 'pub' or 'sub'",
+                other
+            ),
         }
     }
-    if result.ends_with('_') {
-        result.pop();
+    /// Value of the `request_type` key.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            RequestType::Pub => "pub",
+            RequestType::Sub => "sub",
+        }
     }
-    if result.is_empty() {
-        result.push_str("MSG");
+}
+
+#[derive(Clone, Debug)]
+pub struct MessageDefinition {
+    pub name: String,
+    pub packet_id: u32,
+    /// Radix the input wrote `packet_id` in, which the Markdown docs keep
+    pub packet_id_radix: Radix,
+    pub description: Option<String>,
+    pub body: MessageBody,
+    pub request_type: RequestType,
+    /// Whether the input gave `request_type`, rather than the default
+    pub request_type_given: bool,
+    /// Target client ID. -1 means all clients.
+    pub target_client_id: i32,
+    /// Whether the input gave `target_client_id`, rather than the default
+    pub target_client_id_given: bool,
+    pub deprecated: Option<Deprecation>,
+    /// Allowed to use a packet ID of [`Metadata::reserved_ids`]
+    pub reserved_ok: bool,
+    /// Name of the message that answers this one (`response` key)
+    pub response: Option<String>,
+    /// Version of the payload layout (`msg_version`, 1-255), for receivers
+    /// that get the version byte of the frame alongside the packet ID
+    pub msg_version: Option<u8>,
+    /// Family of the message (`group`), e.g. `motor`: its C identifier goes
+    /// between the base name and `msg` in the generated C names, and the
+    /// Markdown lists each group in a section of its own
+    pub group: Option<String>,
+}
+
+/// Radix an integer was written in: a JSON number or decimal string, or a
+/// `0x` hex or `0b` binary string.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Radix {
+    #[default]
+    Decimal,
+    Hex,
+    Binary,
+}
+
+/// Marks a message that new code should stop using (`"deprecated": true`
+/// or a reason string). Deprecated messages are generated as usual.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Deprecation {
+    pub reason: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub enum MessageBody {
+    Scalar(ScalarSpec),
+    Array(ArraySpec),
+    Struct(StructSpec),
+    Union(UnionSpec),
+}
+
+impl MessageBody {
+    /// Shape name used in messages: `scalar`, `array`, `struct` or `union`.
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            MessageBody::Scalar(_) => "scalar",
+            MessageBody::Array(_) => "array",
+            MessageBody::Struct(_) => "struct",
+            MessageBody::Union(_) => "union",
+        }
     }
-    result
 }
 
-#[allow(dead_code)]
-pub(crate) fn to_pascal_case(name: &str) -> String {
-    let mut result = String::new();
-    let mut capitalize = true;
-    for ch in name.chars() {
-        if ch.is_ascii_alphanumeric() {
-            if result.is_empty() && ch.is_ascii_digit() {
-                result.push('M');
-            }
-            if capitalize {
-                result.push(ch.to_ascii_uppercase());
-            } else {
-                result.push(ch.to_ascii_lowercase());
-            }
-            capitalize = false;
-        } else {
-            capitalize = true;
+/// A leading integer tag followed by the struct of the variant it selects
+/// (`msg_type: "union"`).
+#[derive(Clone, Debug, PartialEq)]
+pub struct UnionSpec {
+    pub tag: PrimitiveType,
+    /// Byte order of the tag; the variants carry their own
+    pub endian: Endian,
+    /// In the order of the `variants` object
+    pub variants: Vec<UnionVariant>,
+}
+
+impl UnionSpec {
+    /// The tag as the struct field `tag` it is written as, ahead of the
+    /// variant.
+    pub(crate) fn tag_field(&self) -> StructField {
+        StructField {
+            name: "tag".to_string(),
+            field_type: StructFieldType::Primitive(self.tag),
+            endian: self.endian,
+            scaling: None,
+            range: None,
+            description: None,
+            unit: None,
+            timestamp: None,
+            since_version: None,
+            at: None,
+            reserved: false,
         }
     }
-    if result.is_empty() {
-        result.push_str("Msg");
+
+    /// The variant selected by the tag value `tag`.
+    pub(crate) fn variant(&self, tag: i128) -> Option<&UnionVariant> {
+        self.variants.iter().find(|variant| variant.tag == tag)
     }
-    result
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use serde_json::json;
-
+/// One entry of the `variants` object of a union message.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UnionVariant {
+    pub name: String,
+    /// Tag value selecting this variant
+    pub tag: i128,
+    pub spec: StructSpec,
+    pub description: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ScalarSpec {
+    pub primitive: PrimitiveType,
+    pub endian: Endian,
+    pub scaling: Option<Scaling>,
+    pub range: Option<ValueRange>,
+    /// `desc` of the value, as opposed to `msg_desc` of the message
+    pub description: Option<String>,
+    pub unit: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ArraySpec {
+    pub primitive: PrimitiveType,
+    pub endian: Endian,
+    pub max_length: usize,
+    pub sector_bytes: Option<usize>,
+    /// Applies to every element
+    pub scaling: Option<Scaling>,
+    /// `desc` of the elements, as opposed to `msg_desc` of the message
+    pub description: Option<String>,
+    pub unit: Option<String>,
+    /// `string_semantics`: a `char` array holding text, which also gets an
+    /// encoder that takes its length from the text
+    pub string_semantics: bool,
+}
+
+/// Allowed raw values of a scalar message or struct field (`min` and
+/// `max` keys), both ends inclusive.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ValueRange {
+    pub min: Option<Bound>,
+    pub max: Option<Bound>,
+}
+
+/// One end of a [`ValueRange`]; integer bounds are exact for every
+/// integer type.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Bound {
+    Int(i128),
+    Float(f64),
+}
+
+impl Bound {
+    pub(crate) fn as_f64(self) -> f64 {
+        match self {
+            Bound::Int(value) => value as f64,
+            Bound::Float(value) => value,
+        }
+    }
+
+    /// The bound as a JSON number, as written in the `min`/`max` keys.
+    pub(crate) fn to_json(self) -> Value {
+        match self {
+            Bound::Int(value) => match i64::try_from(value) {
+                Ok(value) => value.into(),
+                Err(_) => (value as u64).into(),
+            },
+            Bound::Float(value) => value.into(),
+        }
+    }
+}
+
+impl fmt::Display for Bound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Bound::Int(value) => write!(f, "{}", value),
+            Bound::Float(value) => write!(f, "{:?}", value),
+        }
+    }
+}
+
+/// Fixed-point encoding of an integer value: the physical value is
+/// `raw * scale + offset`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Scaling {
+    pub scale: f64,
+    pub offset: f64,
+}
+
+impl Scaling {
+    /// Physical value of a raw integer.
+    pub fn apply(self, raw: f64) -> f64 {
+        raw * self.scale + self.offset
+    }
+
+    /// Physical range covered by the raw integer type, lowest first.
+    pub fn range(self, primitive: PrimitiveType) -> Option<(f64, f64)> {
+        let (min, max) = primitive.integer_range()?;
+        let (a, b) = (self.apply(min as f64), self.apply(max as f64));
+        Some((a.min(b), a.max(b)))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StructSpec {
+    pub fields: Vec<StructField>,
+    /// Name of the shared type (top-level `types`) this struct is, or
+    /// `None` for a struct defined in place
+    pub shared: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StructField {
+    pub name: String,
+    pub field_type: StructFieldType,
+    pub endian: Endian,
+    /// Only on integer fields and integer array fields
+    pub scaling: Option<Scaling>,
+    /// Only on numeric fields that are not arrays
+    pub range: Option<ValueRange>,
+    pub description: Option<String>,
+    pub unit: Option<String>,
+    /// Set on `timestamp` fields, whose storage primitive is `field_type`
+    pub timestamp: Option<TimestampResolution>,
+    /// Message version that appended the field (`since_version`); only on
+    /// the top-level fields of a versioned struct message
+    pub since_version: Option<u8>,
+    /// Byte offset of the field in its struct, when given with `at`
+    pub at: Option<usize>,
+    /// Padding the parser inserted before a field whose `at` leaves a gap:
+    /// a `Bytes` field named `_reserved_<n>`, encoded as zeros and skipped
+    /// when decoding
+    pub reserved: bool,
+}
+
+/// Unit of a `timestamp` field.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TimestampResolution {
+    Ms,
+    #[default]
+    Us,
+    Ns,
+}
+
+impl TimestampResolution {
+    pub(crate) fn from_str(value: &str) -> Option<Self> {
+        match value {
+            "ms" => Some(TimestampResolution::Ms),
+            "us" => Some(TimestampResolution::Us),
+            "ns" => Some(TimestampResolution::Ns),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            TimestampResolution::Ms => "ms",
+            TimestampResolution::Us => "us",
+            TimestampResolution::Ns => "ns",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StructFieldArraySpec {
+    pub primitive: PrimitiveType,
+    pub max_length: usize,
+    /// Always exactly `max_length` elements (`fixed_length`), so there is
+    /// no length member and the array takes no spare bytes when decoding
+    pub fixed: bool,
+    /// `string_semantics`: a `char` array holding text, with room for a
+    /// terminating `'\0'` in C
+    pub string_semantics: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum StructFieldType {
+    Primitive(PrimitiveType),
+    Array(StructFieldArraySpec),
+    Nested(StructSpec),
+    /// Blob of exactly this many bytes, e.g. a UUID; `uint8_t[N]` in C
+    Bytes(usize),
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Endian {
+    #[default]
+    Little,
+    Big,
+}
+
+impl Endian {
+    pub(crate) fn from_str(value: &str) -> Result<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "little" | "le" => Ok(Endian::Little),
+            "big" | "be" => Ok(Endian::Big),
+            other => bail!("unsupported endian value '{}'", other),
+        }
+    }
+
+    pub(crate) fn suffix(self) -> &'static str {
+        match self {
+            Endian::Little => "le",
+            Endian::Big => "be",
+        }
+    }
+
+    /// Value of the `endianess` key.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Endian::Little => "little",
+            Endian::Big => "big",
+        }
+    }
+}
+
+impl fmt::Display for Endian {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PrimitiveType {
+    Bool,
+    Char,
+    Int8,
+    Uint8,
+    Int16,
+    Uint16,
+    /// 3 bytes on the wire, `int32_t` in C
+    Int24,
+    /// 3 bytes on the wire, `uint32_t` in C
+    Uint24,
+    Int32,
+    Uint32,
+    Int64,
+    Uint64,
+    /// IEEE 754 half precision: 2 bytes on the wire, `float` in C
+    Float16,
+    Float32,
+    Float64,
+}
+
+impl PrimitiveType {
+    pub(crate) fn from_str(value: &str) -> Result<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "bool" | "boolean" => Ok(PrimitiveType::Bool),
+            "char" => Ok(PrimitiveType::Char),
+            "int8" | "i8" => Ok(PrimitiveType::Int8),
+            "uint8" | "u8" => Ok(PrimitiveType::Uint8),
+            "int16" | "i16" => Ok(PrimitiveType::Int16),
+            "uint16" | "u16" => Ok(PrimitiveType::Uint16),
+            "int24" | "i24" => Ok(PrimitiveType::Int24),
+            "uint24" | "u24" => Ok(PrimitiveType::Uint24),
+            "int32" | "i32" => Ok(PrimitiveType::Int32),
+            "uint32" | "u32" => Ok(PrimitiveType::Uint32),
+            "int64" | "i64" => Ok(PrimitiveType::Int64),
+            "uint64" | "u64" => Ok(PrimitiveType::Uint64),
+            "float16" | "f16" => Ok(PrimitiveType::Float16),
+            "float32" | "f32" => Ok(PrimitiveType::Float32),
+            "float64" | "f64" | "double" => Ok(PrimitiveType::Float64),
+            other => bail!("unsupported primitive type '{}'", other),
+        }
+    }
+
+    /// Canonical spelling in the input format, e.g. `uint16`.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            PrimitiveType::Bool => "bool",
+            PrimitiveType::Char => "char",
+            PrimitiveType::Int8 => "int8",
+            PrimitiveType::Uint8 => "uint8",
+            PrimitiveType::Int16 => "int16",
+            PrimitiveType::Uint16 => "uint16",
+            PrimitiveType::Int24 => "int24",
+            PrimitiveType::Uint24 => "uint24",
+            PrimitiveType::Int32 => "int32",
+            PrimitiveType::Uint32 => "uint32",
+            PrimitiveType::Int64 => "int64",
+            PrimitiveType::Uint64 => "uint64",
+            PrimitiveType::Float16 => "float16",
+            PrimitiveType::Float32 => "float32",
+            PrimitiveType::Float64 => "float64",
+        }
+    }
+
+    pub(crate) fn c_type(self) -> &'static str {
+        match self {
+            PrimitiveType::Bool => "bool",
+            PrimitiveType::Char => "char",
+            PrimitiveType::Int8 => "int8_t",
+            PrimitiveType::Uint8 => "uint8_t",
+            PrimitiveType::Int16 => "int16_t",
+            PrimitiveType::Uint16 => "uint16_t",
+            PrimitiveType::Int24 => "int32_t",
+            PrimitiveType::Uint24 => "uint32_t",
+            PrimitiveType::Int32 => "int32_t",
+            PrimitiveType::Uint32 => "uint32_t",
+            PrimitiveType::Int64 => "int64_t",
+            PrimitiveType::Uint64 => "uint64_t",
+            PrimitiveType::Float16 | PrimitiveType::Float32 => "float",
+            PrimitiveType::Float64 => "double",
+        }
+    }
+
+    /// The type named in documentation, change reports and fingerprints:
+    /// [`c_type`](Self::c_type), except for the types whose C storage is
+    /// wider than their wire encoding.
+    pub(crate) fn wire_type(self) -> &'static str {
+        match self {
+            PrimitiveType::Int24 => "int24_t",
+            PrimitiveType::Uint24 => "uint24_t",
+            PrimitiveType::Float16 => "float16",
+            _ => self.c_type(),
+        }
+    }
+
+    /// Whether the C type takes more bytes than the wire encoding, so that
+    /// the struct cannot be copied as is.
+    pub(crate) fn is_widened(self) -> bool {
+        self.wire_type() != self.c_type()
+    }
+
+    pub(crate) fn is_float(self) -> bool {
+        matches!(
+            self,
+            PrimitiveType::Float16 | PrimitiveType::Float32 | PrimitiveType::Float64
+        )
+    }
+
+    /// Lowest and highest value of an integer type, `None` for `bool`,
+    /// `char` and floating point types.
+    pub(crate) fn integer_range(self) -> Option<(i128, i128)> {
+        Some(match self {
+            PrimitiveType::Int8 => (i8::MIN.into(), i8::MAX.into()),
+            PrimitiveType::Uint8 => (u8::MIN.into(), u8::MAX.into()),
+            PrimitiveType::Int16 => (i16::MIN.into(), i16::MAX.into()),
+            PrimitiveType::Uint16 => (u16::MIN.into(), u16::MAX.into()),
+            PrimitiveType::Int24 => (-(1 << 23), (1 << 23) - 1),
+            PrimitiveType::Uint24 => (0, (1 << 24) - 1),
+            PrimitiveType::Int32 => (i32::MIN.into(), i32::MAX.into()),
+            PrimitiveType::Uint32 => (u32::MIN.into(), u32::MAX.into()),
+            PrimitiveType::Int64 => (i64::MIN.into(), i64::MAX.into()),
+            PrimitiveType::Uint64 => (u64::MIN.into(), u64::MAX.into()),
+            PrimitiveType::Bool
+            | PrimitiveType::Char
+            | PrimitiveType::Float16
+            | PrimitiveType::Float32
+            | PrimitiveType::Float64 => return None,
+        })
+    }
+
+    pub(crate) fn byte_len(self) -> usize {
+        match self {
+            PrimitiveType::Bool
+            | PrimitiveType::Char
+            | PrimitiveType::Int8
+            | PrimitiveType::Uint8 => 1,
+            PrimitiveType::Int16 | PrimitiveType::Uint16 | PrimitiveType::Float16 => 2,
+            PrimitiveType::Int24 | PrimitiveType::Uint24 => 3,
+            PrimitiveType::Int32 | PrimitiveType::Uint32 | PrimitiveType::Float32 => 4,
+            PrimitiveType::Int64 | PrimitiveType::Uint64 | PrimitiveType::Float64 => 8,
+        }
+    }
+}
+
+/// Parses an IR document from JSON text: syntax check, message extraction,
+/// validation, and ordering by `packet_id`, as done by the CLI.
+///
+/// # Example
+/// ```
+/// let input = r#"{
+///     "packets": {
+///         "pong": { "packet_id": 2, "msg_type": "uint8" },
+///         "ping": { "packet_id": 1, "msg_type": "uint8" }
+///     }
+/// }"#;
+/// let (_, messages) = h6xserial_idl::parse_str(input).unwrap();
+/// let names: Vec<&str> = messages.iter().map(|m| m.name.as_str()).collect();
+/// assert_eq!(names, ["ping", "pong"]);
+///
+/// let err = h6xserial_idl::parse_str("{ \"packets\": ").unwrap_err();
+/// assert!(matches!(err, h6xserial_idl::IdlError::Syntax { line: 1, .. }));
+/// ```
+pub fn parse_str(input: &str) -> std::result::Result<(Metadata, Vec<MessageDefinition>), IdlError> {
+    parse_str_with_options(input, &ParseOptions::default())
+}
+
+/// Same as [`parse_str`], applying the given overrides.
+pub fn parse_str_with_options(
+    input: &str,
+    options: &ParseOptions,
+) -> std::result::Result<(Metadata, Vec<MessageDefinition>), IdlError> {
+    parse_str_with_diagnostics(input, options).map_err(IdlError::from_list)
+}
+
+/// Same as [`parse_str`], returning every error found (see
+/// [`parse_messages_with_diagnostics`]).
+pub fn parse_str_with_diagnostics(
+    input: &str,
+    options: &ParseOptions,
+) -> std::result::Result<(Metadata, Vec<MessageDefinition>), Vec<IdlError>> {
+    parse_source(input, options, |_| {})
+}
+
+/// Merges message sets parsed from several documents into one, sorted by
+/// `packet_id`.
+///
+/// The metadata must agree: `version`, `max_address` and
+/// `default_endianness` given in more than one document must be equal, and a device or shared type defined in
+/// several documents must be defined the same way. A value given in only
+/// one document applies to all, as do `command_groups`, which must be
+/// identical where repeated. Reserved packet ID ranges are combined and
+/// apply to the messages of every set, as does `max_address`. The payload, array length and nesting limits become
+/// the largest of the sets. Message names, the C identifiers derived from them and packet IDs
+/// must be unique across all sets. Every conflict is reported, several as
+/// [`IdlError::Multiple`].
+///
+/// # Example
+/// ```
+/// use h6xserial_idl::{merge_message_sets, parse_str};
+///
+/// let base = parse_str(r#"{ "version": "1.0", "packets": { "ping": { "packet_id": 1, "msg_type": "uint8" } } }"#).unwrap();
+/// let app = parse_str(r#"{ "packets": { "led": { "packet_id": 40, "msg_type": "bool" } } }"#).unwrap();
+/// let (metadata, messages) = merge_message_sets(vec![base, app]).unwrap();
+/// assert_eq!(metadata.version.as_deref(), Some("1.0"));
+/// assert_eq!(messages.len(), 2);
+///
+/// let clash = parse_str(r#"{ "packets": { "pong": { "packet_id": 1, "msg_type": "uint8" } } }"#).unwrap();
+/// let base = parse_str(r#"{ "packets": { "ping": { "packet_id": 1, "msg_type": "uint8" } } }"#).unwrap();
+/// assert!(merge_message_sets(vec![base, clash]).is_err());
+/// ```
+pub fn merge_message_sets(
+    sets: Vec<(Metadata, Vec<MessageDefinition>)>,
+) -> std::result::Result<(Metadata, Vec<MessageDefinition>), IdlError> {
+    fn agree<T: PartialEq + std::fmt::Display>(
+        merged: &mut Option<T>,
+        value: Option<T>,
+        key: &str,
+        errors: &mut Vec<IdlError>,
+    ) {
+        match (merged.as_ref(), value) {
+            (Some(first), Some(value)) if *first != value => {
+                errors.push(IdlError::invalid_value(
+                    &validate::pointer_push("", key),
+                    &value,
+                    format!(
+                        "conflicting '{}' values '{}' and '{}' in the merged inputs",
+                        key, first, value
+                    ),
+                ));
+            }
+            (None, Some(value)) => *merged = Some(value),
+            _ => {}
+        }
+    }
+
+    let mut merged = Metadata::default();
+    let mut messages: Vec<MessageDefinition> = Vec::new();
+    let mut errors = Vec::new();
+    let mut ids: HashMap<u32, String> = HashMap::new();
+    let mut idents: HashMap<String, String> = HashMap::new();
+    for (index, (metadata, set)) in sets.into_iter().enumerate() {
+        agree(
+            &mut merged.version,
+            metadata.version,
+            "version",
+            &mut errors,
+        );
+        agree(
+            &mut merged.max_address,
+            metadata.max_address,
+            "max_address",
+            &mut errors,
+        );
+        agree(
+            &mut merged.default_endianness,
+            metadata.default_endianness,
+            "default_endianness",
+            &mut errors,
+        );
+        merged.packet_id_width = merged.packet_id_width.max(metadata.packet_id_width);
+        if index == 0 {
+            merged.max_payload_bytes = metadata.max_payload_bytes;
+            merged.max_array_length = metadata.max_array_length;
+            merged.max_nesting_depth = metadata.max_nesting_depth;
+        } else {
+            merged.max_payload_bytes = merged.max_payload_bytes.max(metadata.max_payload_bytes);
+            merged.max_array_length = merged.max_array_length.max(metadata.max_array_length);
+            merged.max_nesting_depth = merged.max_nesting_depth.max(metadata.max_nesting_depth);
+        }
+        for device in metadata.devices {
+            match merged.devices.iter().find(|d| d.name == device.name) {
+                Some(existing)
+                    if (&existing.role, existing.id, &existing.description)
+                        != (&device.role, device.id, &device.description) =>
+                {
+                    errors.push(IdlError::invalid_value(
+                        &validate::pointer_push("/devices", &device.name),
+                        &device.name,
+                        format!(
+                            "device '{}' is defined differently in the merged inputs",
+                            device.name
+                        ),
+                    ));
+                }
+                Some(_) => {}
+                None => merged.devices.push(device),
+            }
+        }
+        for spec in metadata.types {
+            let name = spec.shared.clone().unwrap_or_default();
+            match merged.types.iter().find(|t| t.shared == spec.shared) {
+                Some(existing) if *existing != spec => {
+                    errors.push(IdlError::invalid_value(
+                        &validate::pointer_push("/types", &name),
+                        &name,
+                        format!(
+                            "shared type '{}' is defined differently in the merged inputs",
+                            name
+                        ),
+                    ));
+                }
+                Some(_) => {}
+                None => merged.types.push(spec),
+            }
+        }
+        for (name, value) in metadata.constants {
+            let ident = to_macro_ident(&name);
+            match merged
+                .constants
+                .iter()
+                .find(|(known, _)| to_macro_ident(known) == ident)
+            {
+                Some((known, known_value)) if *known != name || *known_value != value => {
+                    let message = if *known == name {
+                        format!(
+                            "constant '{}' is defined differently in the merged inputs",
+                            name
+                        )
+                    } else {
+                        format!(
+                            "constant '{}' maps to the same macro name '{}' as constant '{}'",
+                            name,
+                            const_macro_name(&name),
+                            known
+                        )
+                    };
+                    errors.push(IdlError::invalid_value(
+                        &validate::pointer_push("/constants", &name),
+                        &name,
+                        message,
+                    ));
+                }
+                Some(_) => {}
+                None => merged.constants.push((name, value)),
+            }
+        }
+        if merged.command_groups.is_empty() {
+            merged.command_groups = metadata.command_groups;
+        } else if !metadata.command_groups.is_empty()
+            && merged.command_groups != metadata.command_groups
+        {
+            errors.push(IdlError::invalid_value(
+                "/command_groups",
+                "command_groups",
+                "conflicting 'command_groups' in the merged inputs",
+            ));
+        }
+        for range in metadata.reserved_ids {
+            if !merged.reserved_ids.contains(&range) {
+                merged.reserved_ids.push(range);
+            }
+        }
+        for message in set {
+            if let Some(first) = ids.get(&message.packet_id) {
+                errors.push(IdlError::invalid_value(
+                    "",
+                    message.packet_id,
+                    format!(
+                        "message '{}' has packet_id {} which is already used by message '{}'",
+                        message.name, message.packet_id, first
+                    ),
+                ));
+            } else {
+                ids.insert(message.packet_id, message.name.clone());
+            }
+            let ident = to_snake_case(&message.name);
+            match idents.get(&ident) {
+                Some(first) if *first == message.name => errors.push(IdlError::invalid_value(
+                    "",
+                    &message.name,
+                    format!(
+                        "message '{}' is defined in more than one input",
+                        message.name
+                    ),
+                )),
+                Some(first) => errors.push(IdlError::invalid_value(
+                    "",
+                    &message.name,
+                    format!(
+                        "message '{}' maps to the same C identifier '{}' as message '{}'",
+                        message.name, ident, first
+                    ),
+                )),
+                None => {
+                    idents.insert(ident, message.name.clone());
+                }
+            }
+            messages.push(message);
+        }
+    }
+    let mut groups = HashMap::new();
+    for message in &messages {
+        if let Err(error) = check_group_spelling(message, &mut groups, "") {
+            errors.push(error);
+        }
+    }
+    // Reserved ranges and the address space of one input apply to the
+    // messages of all
+    for message in &messages {
+        if let Err(error) = check_reserved_id(&merged.reserved_ids, message, "") {
+            errors.push(error);
+        }
+        if let Err(error) =
+            check_max_address(merged.max_address, merged.packet_id_width, message, "")
+        {
+            errors.push(error);
+        }
+    }
+    if !errors.is_empty() {
+        return Err(IdlError::from_list(errors));
+    }
+    messages.sort_by_key(|m| m.packet_id);
+    Ok((merged, messages))
+}
+
+/// Shared by [`parse_str_with_diagnostics`] and the CLI, which also wants
+/// the unknown-key warnings reported outside strict mode.
+fn parse_source(
+    input: &str,
+    options: &ParseOptions,
+    on_warning: impl FnMut(validate::ValidationWarning),
+) -> std::result::Result<(Metadata, Vec<MessageDefinition>), Vec<IdlError>> {
+    let json = document_value(input, options.format).map_err(|e| vec![e])?;
+    parse_value(&json, options, &[], on_warning)
+}
+
+/// [`parse_source`] after the text has been read into a JSON value, with
+/// the shared types of included documents in scope.
+fn parse_value(
+    json: &Value,
+    options: &ParseOptions,
+    inherited: &[StructSpec],
+    mut on_warning: impl FnMut(validate::ValidationWarning),
+) -> std::result::Result<(Metadata, Vec<MessageDefinition>), Vec<IdlError>> {
+    let obj = json.as_object().ok_or_else(|| {
+        vec![IdlError::invalid_type(
+            "",
+            json,
+            "top-level JSON must be an object",
+        )]
+    })?;
+    if !options.strict {
+        validate::unknown_key_warnings(obj)
+            .into_iter()
+            .for_each(&mut on_warning);
+    }
+    let (metadata, mut messages) = parse_with_types(obj, options, inherited)?;
+    messages.sort_by_key(|m| m.packet_id);
+    Ok((metadata, messages))
+}
+
+/// Reads the input text into the JSON value the parser works on.
+fn document_value(input: &str, format: InputFormat) -> ParseResult<Value> {
+    match format {
+        InputFormat::Json => serde_json::from_str(input).map_err(|e| IdlError::syntax(&e)),
+        InputFormat::Jsonc => {
+            serde_json::from_str(&jsonc::strip(input)).map_err(|e| IdlError::syntax(&e))
+        }
+        InputFormat::Toml => {
+            let table: toml::Table = input
+                .parse()
+                .map_err(|e| IdlError::toml_syntax(input, &e))?;
+            let mut json = toml_to_json(toml::Value::Table(table), "")?;
+            named_entries_to_maps(&mut json, "")?;
+            Ok(json)
+        }
+    }
+}
+
+/// Converts a parsed TOML document to JSON. Tables keep their declaration
+/// order; date-times and non-finite floats have no JSON form.
+fn toml_to_json(value: toml::Value, pointer: &str) -> ParseResult<Value> {
+    Ok(match value {
+        toml::Value::String(text) => Value::String(text),
+        toml::Value::Integer(number) => Value::from(number),
+        toml::Value::Float(number) => match serde_json::Number::from_f64(number) {
+            Some(number) => Value::Number(number),
+            None => {
+                return Err(IdlError::invalid_value(
+                    pointer,
+                    number,
+                    "non-finite floats are not supported",
+                ));
+            }
+        },
+        toml::Value::Boolean(flag) => Value::Bool(flag),
+        toml::Value::Datetime(datetime) => {
+            return Err(IdlError::invalid_value(
+                pointer,
+                datetime,
+                "date-time values are not supported",
+            ));
+        }
+        toml::Value::Array(items) => Value::Array(
+            items
+                .into_iter()
+                .enumerate()
+                .map(|(index, item)| {
+                    toml_to_json(item, &validate::pointer_push(pointer, &index.to_string()))
+                })
+                .collect::<ParseResult<_>>()?,
+        ),
+        toml::Value::Table(table) => Value::Object(
+            table
+                .into_iter()
+                .map(|(key, item)| {
+                    let item = toml_to_json(item, &validate::pointer_push(pointer, &key))?;
+                    Ok((key, item))
+                })
+                .collect::<ParseResult<_>>()?,
+        ),
+    })
+}
+
+/// Keys whose entries may be written as a TOML array of tables.
+const NAMED_ENTRY_KEYS: [&str; 4] = ["packets", "messages", "fields", "types"];
+
+/// Turns `[[fields]]`-style arrays of tables with a `name` key into the
+/// name-keyed objects of the JSON format, keeping their order.
+fn named_entries_to_maps(value: &mut Value, pointer: &str) -> ParseResult<()> {
+    let Value::Object(map) = value else {
+        return Ok(());
+    };
+    for (key, child) in map.iter_mut() {
+        let child_pointer = validate::pointer_push(pointer, key);
+        if NAMED_ENTRY_KEYS.contains(&key.as_str())
+            && let Value::Array(entries) = child
+        {
+            let mut named = Map::new();
+            for (index, entry) in std::mem::take(entries).into_iter().enumerate() {
+                let entry_pointer = validate::pointer_push(&child_pointer, &index.to_string());
+                let Value::Object(mut table) = entry else {
+                    return Err(IdlError::invalid_type(
+                        &entry_pointer,
+                        &entry,
+                        format!("entries of '{}' must be tables with a 'name' key", key),
+                    ));
+                };
+                let name = match table.remove("name") {
+                    Some(Value::String(name)) => name,
+                    Some(other) => {
+                        return Err(IdlError::invalid_type(
+                            &validate::pointer_push(&entry_pointer, "name"),
+                            &other,
+                            "'name' must be a string",
+                        ));
+                    }
+                    None => {
+                        return Err(IdlError::missing_field(
+                            &entry_pointer,
+                            "name",
+                            format!("entry of '{}' is missing 'name'", key),
+                        ));
+                    }
+                };
+                if named.contains_key(&name) {
+                    return Err(IdlError::invalid_value(
+                        &validate::pointer_push(&entry_pointer, "name"),
+                        &name,
+                        format!("duplicate name '{}' in '{}'", name, key),
+                    ));
+                }
+                named.insert(name, Value::Object(table));
+            }
+            *child = Value::Object(named);
+        }
+        if let Value::Object(children) = child {
+            for (name, grandchild) in children.iter_mut() {
+                named_entries_to_maps(grandchild, &validate::pointer_push(&child_pointer, name))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Parses JSON message definitions into internal structures.
+///
+/// # Arguments
+/// * `map` - JSON object containing metadata and message definitions
+///
+/// # Returns
+/// * `Ok((metadata, messages))` - Parsed metadata and list of message definitions
+/// * `Err(...)` - Parse error with detailed context
+///
+/// # Example
+/// ```
+/// use serde_json::json;
+/// use h6xserial_idl::parse_messages;
+///
+/// let json = json!({
+///     "version": "1.0.0",
+///     "packets": {
+///         "ping": {
+///             "packet_id": 0,
+///             "msg_type": "uint8",
+///             "array": false
+///         }
+///     }
+/// });
+/// let obj = json.as_object().unwrap();
+/// let (metadata, messages) = parse_messages(obj).unwrap();
+/// assert_eq!(messages.len(), 1);
+/// ```
+pub fn parse_messages(
+    map: &Map<String, Value>,
+) -> std::result::Result<(Metadata, Vec<MessageDefinition>), IdlError> {
+    parse_messages_with_options(map, &ParseOptions::default())
+}
+
+/// Same as [`parse_messages`], applying the given overrides.
+///
+/// Every problem in the input is reported: a single error as is, several
+/// as [`IdlError::Multiple`]. See [`parse_messages_with_diagnostics`] for
+/// the plain list.
+///
+/// # Example
+/// ```
+/// use serde_json::json;
+/// use h6xserial_idl::{ParseOptions, parse_messages_with_options};
+///
+/// let json = json!({
+///     "packets": {
+///         "blob": { "packet_id": 1, "msg_type": "uint8", "array": true, "max_length": 1000 }
+///     }
+/// });
+/// let options = ParseOptions { max_payload_bytes: Some(1024), ..Default::default() };
+/// let (metadata, _) = parse_messages_with_options(json.as_object().unwrap(), &options).unwrap();
+/// assert_eq!(metadata.max_payload_bytes, 1024);
+/// ```
+pub fn parse_messages_with_options(
+    map: &Map<String, Value>,
+    options: &ParseOptions,
+) -> std::result::Result<(Metadata, Vec<MessageDefinition>), IdlError> {
+    parse_messages_with_diagnostics(map, options).map_err(IdlError::from_list)
+}
+
+/// Parses message definitions, collecting every error instead of stopping
+/// at the first one (unless [`ParseOptions::fail_fast`] is set).
+///
+/// # Returns
+/// * `Ok((metadata, messages))` - The input has no errors
+/// * `Err(errors)` - All problems found, in document order
+///
+/// # Example
+/// ```
+/// use serde_json::json;
+/// use h6xserial_idl::{ParseOptions, parse_messages_with_diagnostics};
+///
+/// let json = json!({
+///     "packets": {
+///         "ping": { "packet_id": 300, "msg_type": "uint8" },
+///         "pong": { "packet_id": 1, "msg_type": "uint9" }
+///     }
+/// });
+/// let errors = parse_messages_with_diagnostics(json.as_object().unwrap(), &ParseOptions::default())
+///     .unwrap_err();
+/// let paths: Vec<&str> = errors.iter().filter_map(|e| e.pointer()).collect();
+/// assert_eq!(paths, ["/packets/ping/packet_id", "/packets/pong/msg_type"]);
+/// ```
+pub fn parse_messages_with_diagnostics(
+    map: &Map<String, Value>,
+    options: &ParseOptions,
+) -> std::result::Result<(Metadata, Vec<MessageDefinition>), Vec<IdlError>> {
+    parse_with_types(map, options, &[])
+}
+
+/// Same as [`parse_messages_with_diagnostics`], with the shared types of
+/// included documents in scope.
+fn parse_with_types(
+    map: &Map<String, Value>,
+    options: &ParseOptions,
+    inherited: &[StructSpec],
+) -> std::result::Result<(Metadata, Vec<MessageDefinition>), Vec<IdlError>> {
+    let mut diag = Diagnostics {
+        errors: Vec::new(),
+        fail_fast: options.fail_fast,
+    };
+    match parse_document(map, options, inherited, &mut diag) {
+        Some(parsed) if diag.errors.is_empty() => Ok(parsed),
+        _ => Err(diag.errors),
+    }
+}
+
+/// Parses a whole document, recording problems in `diag`.
+///
+/// Returns `None` when parsing had to stop before reaching the messages.
+fn parse_document(
+    map: &Map<String, Value>,
+    options: &ParseOptions,
+    inherited: &[StructSpec],
+    diag: &mut Diagnostics,
+) -> Option<(Metadata, Vec<MessageDefinition>)> {
+    let mut metadata = Metadata::default();
+
+    if options.strict {
+        for warning in validate::unknown_key_warnings(map) {
+            diag.push(IdlError::unknown_key(
+                &warning.path,
+                &warning.key,
+                warning.message,
+            ));
+        }
+    }
+
+    // All metadata keys are optional, so this only fails on non-objects
+    let raw = RawMetadata::deserialize(map).unwrap_or_default();
+    metadata.version = raw.version.as_ref().and_then(Loose::valid).cloned();
+    if let Some(width) = diag.check(parse_packet_id_width(&raw)).flatten() {
+        metadata.packet_id_width = width;
+    }
+    let max_id = max_packet_id(metadata.packet_id_width);
+    let max_address = typed_int(&raw.max_address, "", "max_address", || {
+        "'max_address' must be a non-negative integer".to_string()
+    });
+    if let Some((max_address, _)) = diag.check(max_address).flatten() {
+        if max_address > u64::from(max_id) {
+            diag.push(IdlError::limit_exceeded(
+                "/max_address",
+                max_address as usize,
+                max_id as usize,
+                format!(
+                    "max_address {} exceeds the largest {}-bit packet ID {}",
+                    max_address,
+                    metadata.packet_id_width,
+                    format_packet_id(max_id, metadata.packet_id_width)
+                ),
+            ));
+        }
+        metadata.max_address = Some(max_address.min(u64::from(max_id)) as u32);
+    }
+    if let Some(devices_obj) = raw.devices.as_ref().and_then(Loose::valid) {
+        metadata.devices = parse_devices(devices_obj, diag);
+    }
+    // Written by `--export_ir`; older exports stay readable
+    if let Some(ir_version) = diag
+        .check(typed(&raw.ir_version, "", "ir_version", || {
+            "'ir_version' must be a non-negative integer".to_string()
+        }))
+        .flatten()
+        && *ir_version > emit_ir::IR_VERSION
+    {
+        diag.push(IdlError::invalid_value(
+            "/ir_version",
+            ir_version,
+            format!(
+                "ir_version {} is newer than the supported version {}",
+                ir_version,
+                emit_ir::IR_VERSION
+            ),
+        ));
+    }
+    // Included files are loaded by `resolve::Resolver`; only the shape of
+    // the list is checked here
+    diag.check(typed(&raw.include, "", "include", || {
+        "'include' must be an array of file paths".to_string()
+    }));
+    metadata.default_endianness = diag
+        .check(parse_endian(
+            raw.default_endianness
+                .as_ref()
+                .map(|value| ("default_endianness", value)),
+            "",
+        ))
+        .flatten();
+    if let Some(value) = map.get("constants") {
+        metadata.constants = parse_constants(value, diag);
+    }
+    if let Some(value) = map.get("reserved_ids") {
+        metadata.reserved_ids = parse_reserved_ids(value, max_id, diag);
+    }
+    if let Some(value) = map.get("command_groups") {
+        metadata.command_groups = parse_command_groups(value, max_id, diag);
+    }
+    let limits = diag.check(parse_limits(&raw, options, &mut metadata))?;
+    let (types, own_types) = parse_shared_types(map, inherited, &limits, diag);
+    metadata.types = own_types;
+
+    let entries = match collect_message_maps(map) {
+        Ok(entries) => entries,
+        Err(errors) => {
+            for error in errors {
+                diag.push(error);
+            }
+            return None;
+        }
+    };
+
+    let mut messages = Vec::new();
+    let mut pointers = Vec::new();
+    for (pointer, key, msg_map) in entries {
+        if diag.stop() {
+            break;
+        }
+        if let Some(definition) =
+            parse_message_definition(key, msg_map, &pointer, &limits, &types, diag)
+        {
+            messages.push(definition);
+            pointers.push(pointer);
+        }
+    }
+    check_message_conflicts(&messages, &pointers, diag);
+    check_responses(&messages, &pointers, diag);
+    for (message, pointer) in messages.iter().zip(&pointers) {
+        if let Err(error) = check_reserved_id(&metadata.reserved_ids, message, pointer) {
+            diag.push(error);
+        }
+        if let Err(error) = check_max_address(
+            metadata.max_address,
+            metadata.packet_id_width,
+            message,
+            pointer,
+        ) {
+            diag.push(error);
+        }
+    }
+
+    Some((metadata, messages))
+}
+
+/// Parses `packet_id_width`: the bits of the packet ID field, 8 or 16.
+fn parse_packet_id_width(raw: &RawMetadata) -> ParseResult<Option<u8>> {
+    let Some(&width) = typed(&raw.packet_id_width, "", "packet_id_width", || {
+        "'packet_id_width' must be 8 or 16".to_string()
+    })?
+    else {
+        return Ok(None);
+    };
+    match width {
+        8 | 16 => Ok(Some(width as u8)),
+        _ => Err(IdlError::invalid_value(
+            "/packet_id_width",
+            width,
+            format!(
+                "packet_id_width {} is not supported, expected 8 or 16",
+                width
+            ),
+        )),
+    }
+}
+
+/// Parses `reserved_ids`: a list of packet IDs and `[first, last]` ranges.
+/// `max_id` is the largest packet ID of the configured width.
+fn parse_reserved_ids(value: &Value, max_id: u32, diag: &mut Diagnostics) -> Vec<(u32, u32)> {
+    let Some(items) = value.as_array() else {
+        diag.push(IdlError::invalid_type(
+            "/reserved_ids",
+            value,
+            "'reserved_ids' must be an array of packet IDs and [first, last] ranges",
+        ));
+        return Vec::new();
+    };
+    let mut ranges = Vec::new();
+    for (index, item) in items.iter().enumerate() {
+        let pointer = validate::pointer_push("/reserved_ids", &index.to_string());
+        if let Some(range) = diag.check(parse_id_range(item, &pointer, "reserved", max_id)) {
+            ranges.push(range);
+        }
+    }
+    ranges
+}
+
+/// Parses a packet ID or an inclusive `[first, last]` range of them.
+/// `what` names the list in messages, e.g. `reserved`.
+fn parse_id_range(item: &Value, pointer: &str, what: &str, max_id: u32) -> ParseResult<(u32, u32)> {
+    let packet_id = |value: &Value| {
+        let literal = || Some(parse_int_literal(value.as_str()?).ok()?.0);
+        value
+            .as_u64()
+            .or_else(literal)
+            .filter(|id| *id <= u64::from(max_id))
+            .map(|id| id as u32)
+    };
+    let range = match item {
+        Value::Array(pair) if pair.len() == 2 => packet_id(&pair[0]).zip(packet_id(&pair[1])),
+        Value::Array(_) => None,
+        single => packet_id(single).map(|id| (id, id)),
+    };
+    match range {
+        Some((first, last)) if first > last => Err(IdlError::invalid_value(
+            pointer,
+            item,
+            format!("{} range [{}, {}] ends before it starts", what, first, last),
+        )),
+        Some(range) => Ok(range),
+        None => Err(IdlError::invalid_value(
+            pointer,
+            item,
+            format!(
+                "{} IDs must be packet IDs 0-{} or [first, last] ranges of them",
+                what, max_id
+            ),
+        )),
+    }
+}
+
+/// Parses `command_groups`: a list of `{ "name": ..., "range": [first,
+/// last] }` objects whose ranges must not overlap.
+fn parse_command_groups(value: &Value, max_id: u32, diag: &mut Diagnostics) -> Vec<CommandGroup> {
+    let Some(items) = value.as_array() else {
+        diag.push(IdlError::invalid_type(
+            "/command_groups",
+            value,
+            "'command_groups' must be an array of { \"name\": ..., \"range\": [first, last] } objects",
+        ));
+        return Vec::new();
+    };
+    let mut groups: Vec<CommandGroup> = Vec::new();
+    for (index, item) in items.iter().enumerate() {
+        let pointer = validate::pointer_push("/command_groups", &index.to_string());
+        let Some(object) = item.as_object() else {
+            diag.push(IdlError::invalid_type(
+                &pointer,
+                item,
+                "a command group must be an object with 'name' and 'range'",
+            ));
+            continue;
+        };
+        let name = match object.get("name") {
+            Some(Value::String(name)) if !name.trim().is_empty() => Some(name.clone()),
+            Some(found) => {
+                diag.push(IdlError::invalid_type(
+                    &validate::pointer_push(&pointer, "name"),
+                    found,
+                    "command group 'name' must be a non-empty string",
+                ));
+                None
+            }
+            None => {
+                diag.push(IdlError::missing_field(
+                    &pointer,
+                    "name",
+                    "command group is missing 'name'",
+                ));
+                None
+            }
+        };
+        let range = match object.get("range") {
+            Some(range) => diag.check(parse_id_range(
+                range,
+                &validate::pointer_push(&pointer, "range"),
+                "command group",
+                max_id,
+            )),
+            None => {
+                diag.push(IdlError::missing_field(
+                    &pointer,
+                    "range",
+                    "command group is missing 'range'",
+                ));
+                None
+            }
+        };
+        let (Some(name), Some((first, last))) = (name, range) else {
+            continue;
+        };
+        if let Some(other) = groups.iter().find(|g| first <= g.last && g.first <= last) {
+            diag.push(IdlError::invalid_value(
+                &validate::pointer_push(&pointer, "range"),
+                format_id_range(first, last),
+                format!(
+                    "command group '{}' ({}) overlaps command group '{}' ({})",
+                    name,
+                    format_id_range(first, last),
+                    other.name,
+                    format_id_range(other.first, other.last)
+                ),
+            ));
+            continue;
+        }
+        groups.push(CommandGroup { name, first, last });
+    }
+    groups
+}
+
+/// Fails when `message` uses a reserved packet ID without `reserved_ok`.
+fn check_reserved_id(
+    reserved_ids: &[(u32, u32)],
+    message: &MessageDefinition,
+    pointer: &str,
+) -> ParseResult<()> {
+    let id = message.packet_id;
+    match reserved_ids
+        .iter()
+        .find(|(first, last)| (*first..=*last).contains(&id))
+    {
+        Some((first, last)) if !message.reserved_ok => Err(IdlError::invalid_value(
+            &validate::pointer_push(pointer, "packet_id"),
+            id,
+            format!(
+                "message '{}' uses packet_id {} from the reserved range {}; set \"reserved_ok\": true if it belongs there",
+                message.name,
+                id,
+                format_id_range(*first, *last)
+            ),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Checks that the packet ID of `message` lies within `max_address`.
+fn check_max_address(
+    max_address: Option<u32>,
+    packet_id_width: u8,
+    message: &MessageDefinition,
+    pointer: &str,
+) -> ParseResult<()> {
+    match max_address {
+        Some(max_address) if message.packet_id > max_address => Err(IdlError::limit_exceeded(
+            &validate::pointer_push(pointer, "packet_id"),
+            message.packet_id as usize,
+            max_address as usize,
+            format!(
+                "message '{}' has packet_id {} above max_address {}",
+                message.name,
+                format_packet_id(message.packet_id, packet_id_width),
+                format_packet_id(max_address, packet_id_width)
+            ),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Largest packet ID of a `width`-bit packet ID field.
+pub(crate) fn max_packet_id(width: u8) -> u32 {
+    (1u32 << width) - 1
+}
+
+/// A packet ID as the docs show it: decimal for 8-bit IDs, hex and decimal
+/// for wider ones, e.g. `0x0101 (257)`.
+pub(crate) fn format_packet_id(id: u32, width: u8) -> String {
+    if width > 8 {
+        format!("0x{:04X} ({})", id, id)
+    } else {
+        id.to_string()
+    }
+}
+
+/// `first-last`, or a single ID.
+pub(crate) fn format_id_range(first: u32, last: u32) -> String {
+    if first == last {
+        first.to_string()
+    } else {
+        format!("{}-{}", first, last)
+    }
+}
+
+/// Reports messages that share a packet ID, or whose names map to the same
+/// C identifier (e.g. `motor-speed` and `motor_speed`).
+fn check_message_conflicts(
+    messages: &[MessageDefinition],
+    pointers: &[String],
+    diag: &mut Diagnostics,
+) {
+    let mut ids: HashMap<u32, &str> = HashMap::new();
+    let mut idents: HashMap<String, &str> = HashMap::new();
+    let mut groups: HashMap<String, &str> = HashMap::new();
+    for (message, pointer) in messages.iter().zip(pointers) {
+        diag.check(check_group_spelling(
+            message,
+            &mut groups,
+            &validate::pointer_push(pointer, "group"),
+        ));
+        if let Some(first) = ids.get(&message.packet_id) {
+            diag.push(IdlError::invalid_value(
+                &validate::pointer_push(pointer, "packet_id"),
+                message.packet_id,
+                format!(
+                    "message '{}' has packet_id {} which is already used by message '{}'",
+                    message.name, message.packet_id, first
+                ),
+            ));
+        } else {
+            ids.insert(message.packet_id, &message.name);
+        }
+        let ident = to_snake_case(&message.name);
+        if let Some(first) = idents.get(&ident) {
+            diag.push(IdlError::invalid_value(
+                pointer,
+                &message.name,
+                format!(
+                    "message '{}' maps to the same C identifier '{}' as message '{}'",
+                    message.name, ident, first
+                ),
+            ));
+        } else {
+            idents.insert(ident, &message.name);
+        }
+    }
+}
+
+/// Checks that every `response` names another message of the document.
+fn check_responses(messages: &[MessageDefinition], pointers: &[String], diag: &mut Diagnostics) {
+    for (message, pointer) in messages.iter().zip(pointers) {
+        let Some(response) = &message.response else {
+            continue;
+        };
+        let pointer = validate::pointer_push(pointer, "response");
+        if *response == message.name {
+            diag.push(IdlError::invalid_value(
+                &pointer,
+                response,
+                format!("message '{}' cannot be its own response", message.name),
+            ));
+        } else if !messages.iter().any(|other| other.name == *response) {
+            diag.push(IdlError::invalid_value(
+                &pointer,
+                response,
+                format!(
+                    "message '{}' has response '{}', which is not a message",
+                    message.name, response
+                ),
+            ));
+        }
+    }
+}
+
+/// Resolves the payload and array length limits from metadata and options.
+fn parse_limits(
+    raw: &RawMetadata,
+    options: &ParseOptions,
+    metadata: &mut Metadata,
+) -> ParseResult<Limits> {
+    if let Some(value) = typed(&raw.max_payload_bytes, "", "max_payload_bytes", || {
+        "'max_payload_bytes' must be a non-negative integer".to_string()
+    })? {
+        metadata.max_payload_bytes = *value as usize;
+    }
+    if let Some(limit) = options.max_payload_bytes {
+        metadata.max_payload_bytes = limit;
+    }
+    let out_of_range = format!(
+        "max_payload_bytes {} is out of range, expected 1-{}",
+        metadata.max_payload_bytes, MAX_PAYLOAD_BYTES_CAP
+    );
+    if metadata.max_payload_bytes == 0 {
+        return Err(IdlError::invalid_value(
+            "/max_payload_bytes",
+            0,
+            out_of_range,
+        ));
+    }
+    if metadata.max_payload_bytes > MAX_PAYLOAD_BYTES_CAP {
+        return Err(IdlError::limit_exceeded(
+            "/max_payload_bytes",
+            metadata.max_payload_bytes,
+            MAX_PAYLOAD_BYTES_CAP,
+            out_of_range,
+        ));
+    }
+
+    let mut array_length_source = LimitSource::Default;
+    if let Some(value) = typed(&raw.max_array_length, "", "max_array_length", || {
+        "'max_array_length' must be a non-negative integer".to_string()
+    })? {
+        metadata.max_array_length = *value as usize;
+        array_length_source = LimitSource::Metadata;
+    }
+    if let Some(limit) = options.max_array_length {
+        metadata.max_array_length = limit;
+        array_length_source = LimitSource::CommandLine;
+    }
+    let array_length_source =
+        array_length_source.describe("max_array_length", "--max-array-length");
+    if metadata.max_array_length == 0 {
+        return Err(IdlError::invalid_value(
+            "/max_array_length",
+            0,
+            format!(
+                "max_array_length must be at least 1 (from {})",
+                array_length_source
+            ),
+        ));
+    }
+    // Elements take at least one byte, so a raised cap needs a payload limit to match
+    if metadata.max_array_length > DEFAULT_MAX_ARRAY_LENGTH
+        && metadata.max_array_length > metadata.max_payload_bytes
+    {
+        return Err(IdlError::limit_exceeded(
+            "/max_array_length",
+            metadata.max_array_length,
+            metadata.max_payload_bytes,
+            format!(
+                "max_array_length {} (from {}) exceeds max_payload_bytes {}; raise the payload limit first",
+                metadata.max_array_length, array_length_source, metadata.max_payload_bytes
+            ),
+        ));
+    }
+
+    let mut nesting_depth_source = LimitSource::Default;
+    if let Some(value) = typed(&raw.max_nesting_depth, "", "max_nesting_depth", || {
+        "'max_nesting_depth' must be a non-negative integer".to_string()
+    })? {
+        metadata.max_nesting_depth = *value as usize;
+        nesting_depth_source = LimitSource::Metadata;
+    }
+    if let Some(limit) = options.max_nesting_depth {
+        metadata.max_nesting_depth = limit;
+        nesting_depth_source = LimitSource::CommandLine;
+    }
+    let nesting_depth_source =
+        nesting_depth_source.describe("max_nesting_depth", "--max-nesting-depth");
+    if metadata.max_nesting_depth > MAX_NESTING_DEPTH_CAP {
+        return Err(IdlError::limit_exceeded(
+            "/max_nesting_depth",
+            metadata.max_nesting_depth,
+            MAX_NESTING_DEPTH_CAP,
+            format!(
+                "max_nesting_depth {} (from {}) is out of range, expected 0-{}",
+                metadata.max_nesting_depth, nesting_depth_source, MAX_NESTING_DEPTH_CAP
+            ),
+        ));
+    }
+    let packet_id_width_source = match raw.packet_id_width {
+        Some(Loose::Valid(_)) => LimitSource::Metadata,
+        _ => LimitSource::Default,
+    }
+    .describe("packet_id_width", "");
+    Ok(Limits {
+        max_payload_bytes: metadata.max_payload_bytes,
+        max_array_length: metadata.max_array_length,
+        array_length_source,
+        max_nesting_depth: metadata.max_nesting_depth,
+        nesting_depth_source,
+        default_endian: metadata.default_endianness.unwrap_or_default(),
+        packet_id_width: metadata.packet_id_width,
+        packet_id_width_source,
+    })
+}
+
+/// A message definition object with its JSON pointer and name.
+pub(crate) type MessageEntry<'a> = (String, &'a String, &'a Map<String, Value>);
+
+/// Returns the message definition objects of an input document, each with
+/// the JSON pointer it was found at.
+///
+/// Messages normally live in a `"packets"` (or `"messages"`) object. Without
+/// such a wrapper the legacy flat layout applies, where every top-level
+/// object that is not metadata is a message. Any other top-level key is
+/// rejected rather than silently ignored or parsed as a message.
+pub(crate) fn collect_message_maps(
+    map: &Map<String, Value>,
+) -> std::result::Result<Vec<MessageEntry<'_>>, Vec<IdlError>> {
+    let wrappers: Vec<&str> = MESSAGE_WRAPPER_KEYS
+        .iter()
+        .copied()
+        .filter(|key| map.contains_key(*key))
+        .collect();
+    if wrappers.len() > 1 {
+        return Err(vec![IdlError::ConflictingKeys {
+            path: String::new(),
+            keys: wrappers.iter().map(|key| key.to_string()).collect(),
+            message: "use either 'packets' or 'messages' for message definitions, not both"
+                .to_string(),
+        }]);
+    }
+
+    let mut entries = Vec::new();
+    let mut errors = Vec::new();
+    if let Some(wrapper) = wrappers.first() {
+        let wrapper_pointer = validate::pointer_push("", wrapper);
+        let Some(packets_map) = map[*wrapper].as_object() else {
+            return Err(vec![IdlError::invalid_type(
+                &wrapper_pointer,
+                &map[*wrapper],
+                format!("'{}' must be an object", wrapper),
+            )]);
+        };
+        for (key, value) in packets_map {
+            let pointer = validate::pointer_push(&wrapper_pointer, key);
+            match value.as_object() {
+                Some(msg_map) => entries.push((pointer, key, msg_map)),
+                None => errors.push(IdlError::invalid_type(
+                    &pointer,
+                    value,
+                    format!("message '{}' must be an object", key),
+                )),
+            }
+        }
+    }
+
+    for (key, value) in map {
+        if METADATA_KEYS.contains(&key.as_str()) || MESSAGE_WRAPPER_KEYS.contains(&key.as_str()) {
+            continue;
+        }
+        let pointer = validate::pointer_push("", key);
+        match (wrappers.first(), value.as_object()) {
+            (None, Some(msg_map)) => entries.push((pointer, key, msg_map)),
+            (Some(wrapper), Some(_)) => errors.push(IdlError::unknown_key(
+                &pointer,
+                key,
+                format!(
+                    "unexpected top-level object '{}'; message definitions belong inside '{}'",
+                    key, wrapper
+                ),
+            )),
+            (_, None) => errors.push(IdlError::unknown_key(
+                &pointer,
+                key,
+                format!(
+                    "unknown top-level key '{}' (expected one of {})",
+                    key,
+                    METADATA_KEYS.join(", ")
+                ),
+            )),
+        }
+    }
+
+    // A document may consist of includes only
+    if wrappers.is_empty()
+        && entries.is_empty()
+        && errors.is_empty()
+        && !map.contains_key("include")
+    {
+        errors.push(IdlError::missing_field(
+            "",
+            "packets",
+            "missing required 'packets' object (or top-level message definitions)",
+        ));
+    }
+    if errors.is_empty() {
+        Ok(entries)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Every shared type of the IR, the unused ones in `metadata` included,
+/// each after the shared types it contains.
+pub(crate) fn shared_types<'a>(
+    metadata: &'a Metadata,
+    messages: &'a [MessageDefinition],
+) -> Vec<&'a StructSpec> {
+    fn visit<'a>(spec: &'a StructSpec, out: &mut Vec<&'a StructSpec>) {
+        for field in &spec.fields {
+            if let StructFieldType::Nested(nested) = &field.field_type {
+                visit(nested, out);
+            }
+        }
+        if spec.shared.is_some() && !out.iter().any(|known| known.shared == spec.shared) {
+            out.push(spec);
+        }
+    }
+
+    let mut out = Vec::new();
+    for spec in &metadata.types {
+        visit(spec, &mut out);
+    }
+    for msg in messages {
+        match &msg.body {
+            MessageBody::Struct(spec) => visit(spec, &mut out),
+            MessageBody::Union(spec) => {
+                for variant in &spec.variants {
+                    visit(&variant.spec, &mut out);
+                }
+            }
+            MessageBody::Scalar(_) | MessageBody::Array(_) => {}
+        }
+    }
+    out
+}
+
+/// Shared struct types in scope while parsing a document: its own `types`
+/// and those of the documents it includes.
+#[derive(Default)]
+struct SharedTypes {
+    specs: HashMap<String, StructSpec>,
+    /// Defined but invalid; references to them are not reported again
+    broken: HashSet<String>,
+}
+
+impl SharedTypes {
+    /// Whether `name` refers to a shared type rather than a built-in one.
+    fn defines(&self, name: &str) -> bool {
+        self.specs.contains_key(name) || self.broken.contains(name)
+    }
+
+    /// The shared type `name`, unless it failed to parse.
+    fn get(&self, name: &str) -> Option<&StructSpec> {
+        self.specs.get(name)
+    }
+}
+
+/// Parses the top-level `types` object. Returns the types in scope and the
+/// document's own types, each after the shared types it contains.
+fn parse_shared_types(
+    map: &Map<String, Value>,
+    inherited: &[StructSpec],
+    limits: &Limits,
+    diag: &mut Diagnostics,
+) -> (SharedTypes, Vec<StructSpec>) {
+    let mut types = SharedTypes::default();
+    for spec in inherited {
+        if let Some(name) = &spec.shared {
+            types.specs.insert(name.clone(), spec.clone());
+        }
+    }
+    let mut own = Vec::new();
+    let Some(value) = map.get("types") else {
+        return (types, own);
+    };
+    let Some(defs) = value.as_object() else {
+        diag.push(IdlError::invalid_type(
+            "/types",
+            value,
+            "'types' must be an object of named struct definitions",
+        ));
+        return (types, own);
+    };
+
+    // Check the names first so references can be told from unknown types
+    let mut names = Vec::new();
+    let mut idents: HashMap<String, &str> = HashMap::new();
+    for name in defs.keys() {
+        let pointer = validate::pointer_push("/types", name);
+        let ident = to_snake_case(name);
+        if PrimitiveType::from_str(name).is_ok()
+            || ["struct", "bytes", "timestamp", "union"]
+                .iter()
+                .any(|builtin| name.eq_ignore_ascii_case(builtin))
+        {
+            diag.push(IdlError::invalid_value(
+                &pointer,
+                name,
+                format!("shared type '{}' has the name of a built-in type", name),
+            ));
+        } else if ident.is_empty() {
+            diag.push(IdlError::invalid_value(
+                &pointer,
+                name,
+                format!(
+                    "shared type name '{}' contains no C identifier characters",
+                    name
+                ),
+            ));
+        } else if let Some(first) = idents.get(&ident) {
+            diag.push(IdlError::invalid_value(
+                &pointer,
+                name,
+                format!(
+                    "shared type '{}' maps to the same C identifier '{}' as shared type '{}'",
+                    name, ident, first
+                ),
+            ));
+            types.broken.insert(name.clone());
+        } else {
+            idents.insert(ident, name);
+            names.push(name.as_str());
+        }
+    }
+
+    for name in shared_type_order(defs, &names, &mut types.broken, diag) {
+        if types.broken.contains(name) || diag.stop() {
+            continue;
+        }
+        let pointer = validate::pointer_push("/types", name);
+        match parse_shared_type(name, &defs[name], &pointer, limits, &types, diag) {
+            Some(spec) => {
+                types.specs.insert(name.to_string(), spec.clone());
+                own.push(spec);
+            }
+            None => {
+                types.broken.insert(name.to_string());
+            }
+        }
+    }
+    (types, own)
+}
+
+/// Orders shared types so that each comes after the shared types it
+/// contains. Types containing themselves are reported and marked broken.
+fn shared_type_order<'a>(
+    defs: &'a Map<String, Value>,
+    names: &[&'a str],
+    broken: &mut HashSet<String>,
+    diag: &mut Diagnostics,
+) -> Vec<&'a str> {
+    /// Type names used by the fields of a definition, nested ones included.
+    fn references<'a>(def: &'a Value, out: &mut Vec<&'a str>) {
+        let Some(fields) = def.get("fields").and_then(Value::as_object) else {
+            return;
+        };
+        for field in fields.values() {
+            if let Some(name) = ["type", "msg_type"]
+                .iter()
+                .find_map(|key| field.get(*key).and_then(Value::as_str))
+            {
+                out.push(name);
+            }
+            references(field, out);
+        }
+    }
+
+    fn visit<'a>(
+        name: &'a str,
+        defs: &'a Map<String, Value>,
+        names: &[&'a str],
+        stack: &mut Vec<&'a str>,
+        order: &mut Vec<&'a str>,
+        broken: &mut HashSet<String>,
+        diag: &mut Diagnostics,
+    ) {
+        if order.contains(&name) {
+            return;
+        }
+        if let Some(start) = stack.iter().position(|open| *open == name) {
+            let mut chain = stack[start..].to_vec();
+            chain.push(name);
+            diag.push(IdlError::invalid_value(
+                &validate::pointer_push("/types", name),
+                name,
+                format!(
+                    "shared type '{}' contains itself ({})",
+                    name,
+                    chain.join(" -> ")
+                ),
+            ));
+            broken.extend(stack[start..].iter().map(|n| n.to_string()));
+            return;
+        }
+        stack.push(name);
+        let mut used = Vec::new();
+        references(&defs[name], &mut used);
+        for dependency in used {
+            if let Some(dependency) = names.iter().find(|n| **n == dependency) {
+                visit(dependency, defs, names, stack, order, broken, diag);
+            }
+        }
+        stack.pop();
+        order.push(name);
+    }
+
+    let mut order = Vec::new();
+    for name in names {
+        visit(name, defs, names, &mut Vec::new(), &mut order, broken, diag);
+    }
+    order
+}
+
+/// Parses one entry of the `types` object: a struct given by its `fields`,
+/// optionally marked `"type": "struct"`.
+fn parse_shared_type(
+    name: &str,
+    value: &Value,
+    pointer: &str,
+    limits: &Limits,
+    types: &SharedTypes,
+    diag: &mut Diagnostics,
+) -> Option<StructSpec> {
+    let Some(def) = value.as_object() else {
+        diag.push(IdlError::invalid_type(
+            pointer,
+            value,
+            format!("shared type '{}' must be an object", name),
+        ));
+        return None;
+    };
+    let raw = RawField::deserialize(def).unwrap_or_default();
+    match raw.type_key() {
+        Some((_, Loose::Valid(kind))) if kind.eq_ignore_ascii_case("struct") => {}
+        None => {}
+        Some((key, Loose::Valid(kind))) => {
+            diag.push(IdlError::invalid_value(
+                &validate::pointer_push(pointer, key),
+                kind,
+                format!("shared type '{}' must be a struct", name),
+            ));
+            return None;
+        }
+        Some((key, Loose::Invalid(found))) => {
+            diag.push(IdlError::invalid_type(
+                &validate::pointer_push(pointer, key),
+                found,
+                format!(
+                    "shared type '{}' has invalid '{}' (must be a string)",
+                    name, key
+                ),
+            ));
+            return None;
+        }
+    }
+    let fields_obj = struct_fields_object(
+        &raw.fields,
+        &raw.field_order,
+        pointer,
+        || format!("shared type '{}' requires a 'fields' object", name),
+        || format!("shared type '{}' must define at least one field", name),
+        diag,
+    )?;
+    let fields = parse_struct_fields(
+        &fields_obj,
+        name,
+        &validate::pointer_push(pointer, "fields"),
+        0,
+        limits,
+        types,
+        diag,
+    )?;
+    let spec = StructSpec {
+        fields,
+        shared: Some(name.to_string()),
+    };
+    diag.check(check_struct_shape(
+        &format!("shared type '{}'", name),
+        [&spec],
+        pointer,
+        limits,
+    ))?;
+    diag.check(reject_since_version(
+        &spec,
+        &format!("shared type '{}'", name),
+        &validate::pointer_push(pointer, "fields"),
+    ))?;
+    Some(spec)
+}
+
+/// Parses devices section from JSON.
+/// Parses the top-level `constants` object into named scalar values.
+fn parse_constants(value: &Value, diag: &mut Diagnostics) -> Vec<(String, ConstValue)> {
+    let mut constants = Vec::new();
+    let Some(defs) = value.as_object() else {
+        diag.push(IdlError::invalid_type(
+            "/constants",
+            value,
+            "'constants' must be an object of named integer, float or string values",
+        ));
+        return constants;
+    };
+    let mut idents: HashMap<String, &str> = HashMap::new();
+    for (name, value) in defs {
+        let pointer = validate::pointer_push("/constants", name);
+        let ident = to_macro_ident(name);
+        if let Some(first) = idents.get(&ident) {
+            diag.push(IdlError::invalid_value(
+                &pointer,
+                name,
+                format!(
+                    "constant '{}' maps to the same macro name '{}' as constant '{}'",
+                    name,
+                    const_macro_name(name),
+                    first
+                ),
+            ));
+            continue;
+        }
+        idents.insert(ident, name);
+        let parsed = match value {
+            Value::Number(number) => match (number.as_i64(), number.as_f64()) {
+                (Some(int), _) => Some(ConstValue::Int(int)),
+                (None, Some(float)) if number.is_f64() => Some(ConstValue::Float(float)),
+                _ => {
+                    diag.push(IdlError::invalid_value(
+                        &pointer,
+                        value,
+                        format!(
+                            "constant '{}' is out of range, expected a 64-bit signed integer",
+                            name
+                        ),
+                    ));
+                    continue;
+                }
+            },
+            // Integer literals such as "0xA5" are integers like the other
+            // integer keys; `{ "string": "0xA5" }` keeps one a string
+            Value::String(text) => match parse_int_literal(text) {
+                Ok((int, _)) => match i64::try_from(int) {
+                    Ok(int) => Some(ConstValue::Int(int)),
+                    Err(_) => {
+                        diag.push(IdlError::invalid_value(
+                            &pointer,
+                            value,
+                            format!(
+                                "constant '{}' is out of range, expected a 64-bit signed integer",
+                                name
+                            ),
+                        ));
+                        continue;
+                    }
+                },
+                Err(_) => Some(ConstValue::Str(text.clone())),
+            },
+            Value::Object(forced) if forced.len() == 1 => forced
+                .get("string")
+                .and_then(Value::as_str)
+                .map(|text| ConstValue::Str(text.to_string())),
+            _ => None,
+        };
+        match parsed {
+            Some(parsed) => constants.push((name.clone(), parsed)),
+            None => diag.push(IdlError::invalid_type(
+                &pointer,
+                value,
+                format!(
+                    "constant '{}' must be an integer, float, string or {{ \"string\": \"...\" }}",
+                    name
+                ),
+            )),
+        }
+    }
+    constants
+}
+
+/// Name of the macro emitted for the constant `name`.
+pub(crate) fn const_macro_name(name: &str) -> String {
+    format!("H6XSERIAL_CONST_{}", to_macro_ident(name))
+}
+
+fn parse_devices(devices_obj: &Map<String, Value>, diag: &mut Diagnostics) -> Vec<DeviceInfo> {
+    let mut devices = Vec::new();
+    for (name, value) in devices_obj {
+        let Some(device_map) = value.as_object() else {
+            diag.push(IdlError::invalid_type(
+                &validate::pointer_push("/devices", name),
+                value,
+                format!("device '{}' must be an object", name),
+            ));
+            continue;
+        };
+        let raw = RawDevice::deserialize(device_map).unwrap_or_default();
+
+        devices.push(DeviceInfo {
+            name: name.clone(),
+            role: raw
+                .role
+                .as_ref()
+                .and_then(Loose::valid)
+                .map_or("client", |s| s.as_str())
+                .to_string(),
+            id: raw.id.as_ref().and_then(Loose::valid).map(|v| *v as u32),
+            description: raw.description.as_ref().and_then(Loose::valid).cloned(),
+        });
+    }
+    devices
+}
+
+/// Calculates the minimum byte size of a message body; variable-length
+/// arrays may be empty.
+pub(crate) fn message_body_min_size(body: &MessageBody) -> usize {
+    match body {
+        MessageBody::Scalar(spec) => spec.primitive.byte_len(),
+        MessageBody::Array(_) => 0,
+        MessageBody::Struct(spec) => emit_c::struct_min_byte_len(spec),
+        MessageBody::Union(spec) => {
+            spec.tag.byte_len()
+                + spec
+                    .variants
+                    .iter()
+                    .map(|variant| emit_c::struct_min_byte_len(&variant.spec))
+                    .min()
+                    .unwrap_or(0)
+        }
+    }
+}
+
+/// Calculates the maximum byte size of a message body.
+pub(crate) fn message_body_max_size(body: &MessageBody) -> usize {
+    match body {
+        MessageBody::Scalar(spec) => spec.primitive.byte_len(),
+        MessageBody::Array(spec) => spec.max_length * spec.primitive.byte_len(),
+        MessageBody::Struct(spec) => struct_spec_max_size(spec),
+        MessageBody::Union(spec) => {
+            spec.tag.byte_len()
+                + spec
+                    .variants
+                    .iter()
+                    .map(|variant| struct_spec_max_size(&variant.spec))
+                    .max()
+                    .unwrap_or(0)
+        }
+    }
+}
+
+/// Calculates the maximum byte size of a struct spec (recursively).
+fn struct_spec_max_size(spec: &StructSpec) -> usize {
+    spec.fields
+        .iter()
+        .map(|f| match &f.field_type {
+            StructFieldType::Primitive(prim) => prim.byte_len(),
+            StructFieldType::Array(arr) => arr.max_length * arr.primitive.byte_len(),
+            StructFieldType::Nested(nested) => struct_spec_max_size(nested),
+            StructFieldType::Bytes(length) => *length,
+        })
+        .sum()
+}
+
+/// Parses a single message definition from JSON.
+///
+/// Independent problems (packet ID, request type, body) are all recorded in
+/// `diag`; the message is only returned when it has none.
+///
+/// # Arguments
+/// * `name` - Message name from JSON key
+/// * `map` - JSON object for this message
+/// * `pointer` - JSON pointer of the message object
+/// * `limits` - Payload and array length limits in effect
+/// * `diag` - Collector for parse errors
+fn parse_message_definition(
+    name: &str,
+    map: &Map<String, Value>,
+    pointer: &str,
+    limits: &Limits,
+    types: &SharedTypes,
+    diag: &mut Diagnostics,
+) -> Option<MessageDefinition> {
+    // Every key is optional at this level, so this only fails on non-objects
+    let raw = RawMessage::deserialize(map).unwrap_or_default();
+
+    let packet_id = diag.check(parse_packet_id(name, &raw, limits, pointer));
+
+    let description = raw.msg_desc.as_ref().and_then(Loose::valid).cloned();
+
+    // Parse request_type (pub or sub), defaults to pub
+    let request_type = diag.check(parse_request_type(name, &raw, pointer));
+
+    let reserved_ok = diag.check(
+        typed(&raw.reserved_ok, pointer, "reserved_ok", || {
+            format!(
+                "message '{}' has invalid 'reserved_ok' (must be true or false)",
+                name
+            )
+        })
+        .map(|reserved_ok| reserved_ok.copied().unwrap_or(false)),
+    );
+
+    let deprecated = diag.check(
+        typed(&raw.deprecated, pointer, "deprecated", || {
+            format!(
+                "message '{}' has invalid 'deprecated' (must be true, false or a reason string)",
+                name
+            )
+        })
+        .map(|deprecated| match deprecated {
+            None | Some(RawDeprecated::Flag(false)) => None,
+            Some(RawDeprecated::Flag(true)) => Some(Deprecation::default()),
+            Some(RawDeprecated::Reason(reason)) => Some(Deprecation {
+                reason: Some(reason.clone()),
+            }),
+        }),
+    );
+
+    let response = diag.check(typed(&raw.response, pointer, "response", || {
+        format!(
+            "message '{}' has invalid 'response' (must be a message name)",
+            name
+        )
+    }));
+
+    let group = diag.check(
+        typed(&raw.group, pointer, "group", || {
+            format!("message '{}' has invalid 'group' (must be a string)", name)
+        })
+        .and_then(|group| {
+            if let Some(group) = group {
+                check_group(name, group, &validate::pointer_push(pointer, "group"))?;
+            }
+            Ok(group.cloned())
+        }),
+    );
+
+    let msg_version = diag.check(parse_version(
+        &raw.msg_version,
+        "msg_version",
+        &format!("message '{}'", name),
+        pointer,
+    ));
+
+    // Parse target_client_id, defaults to -1 (all clients)
+    let target_client_id = raw
+        .target_client_id
+        .as_ref()
+        .and_then(Loose::valid)
+        .map_or(-1, |v| *v as i32);
+
+    let msg_type = diag.check(typed(&raw.msg_type, pointer, "msg_type", || {
+        format!(
+            "message '{}' has invalid 'msg_type' (must be a string)",
+            name
+        )
+    }));
+    let is_struct = msg_type.flatten().is_some_and(|msg_type| {
+        ["struct", "union"]
+            .iter()
+            .any(|kind| msg_type.eq_ignore_ascii_case(kind))
+            || types.defines(msg_type)
+    });
+    if is_struct {
+        for (key, value) in [("desc", &raw.desc), ("unit", &raw.unit)] {
+            if value.is_some() {
+                diag.push(IdlError::invalid_value(
+                    &validate::pointer_push(pointer, key),
+                    "struct",
+                    format!(
+                        "'{}' describes the value of a scalar or array message; put it on the fields of struct message '{}'",
+                        key, name
+                    ),
+                ));
+            }
+        }
+    }
+    let is_bytes = msg_type
+        .flatten()
+        .is_some_and(|msg_type| msg_type.eq_ignore_ascii_case("bytes"));
+    if !is_bytes && raw.length.is_some() {
+        diag.push(length_without_bytes(
+            &format!("message '{}'", name),
+            pointer,
+        ));
+    }
+    let is_array = raw.array.as_ref().and_then(Loose::valid) == Some(&true);
+    if raw.sector_bytes.is_some() && (is_struct || is_bytes || !is_array) {
+        diag.push(IdlError::invalid_value(
+            &validate::pointer_push(pointer, "sector_bytes"),
+            "sector_bytes",
+            format!(
+                "'sector_bytes' only applies to array messages, and message '{}' is not one",
+                name
+            ),
+        ));
+    }
+    let is_union = msg_type
+        .flatten()
+        .is_some_and(|msg_type| msg_type.eq_ignore_ascii_case("union"));
+    if !is_union {
+        for (key, value) in [
+            ("tag", raw.tag.is_some()),
+            ("variants", raw.variants.is_some()),
+        ] {
+            if value {
+                diag.push(IdlError::invalid_value(
+                    &validate::pointer_push(pointer, key),
+                    key,
+                    format!(
+                        "'{}' only applies to union messages, and message '{}' is not one (set 'msg_type': 'union')",
+                        key, name
+                    ),
+                ));
+            }
+        }
+    }
+    let body = match msg_type {
+        Some(Some(msg_type)) if msg_type.eq_ignore_ascii_case("struct") => {
+            parse_struct_body(name, &raw, pointer, limits, types, diag)
+        }
+        Some(Some(msg_type)) if msg_type.eq_ignore_ascii_case("union") => {
+            parse_union_body(name, &raw, pointer, limits, types, diag)
+        }
+        Some(Some(msg_type)) if msg_type.eq_ignore_ascii_case("bytes") => {
+            diag.check(parse_bytes_body(name, &raw, pointer, limits))
+        }
+        Some(Some(msg_type)) if types.defines(msg_type) => {
+            // Broken shared types have been reported already
+            let spec = types.get(msg_type)?.clone();
+            let body = MessageBody::Struct(spec);
+            diag.check(check_body_shape(name, &body, pointer, limits))?;
+            diag.check(check_body_size(name, &body, pointer, limits))?;
+            Some(body)
+        }
+        Some(Some(msg_type)) => {
+            diag.check(parse_primitive_body(name, msg_type, &raw, pointer, limits))
+        }
+        Some(None) => {
+            diag.push(IdlError::missing_field(
+                pointer,
+                "msg_type",
+                format!(
+                    "message '{}' is missing required field 'msg_type' (e.g., 'uint8', 'float32', 'struct')",
+                    name
+                ),
+            ));
+            None
+        }
+        None => None,
+    };
+    if let (Some(body), Some(msg_version)) = (&body, msg_version) {
+        diag.check(check_versions(name, msg_version, body, pointer));
+    }
+
+    let (packet_id, packet_id_radix) = packet_id?;
+    Some(MessageDefinition {
+        name: name.to_string(),
+        packet_id,
+        packet_id_radix,
+        description,
+        body: body?,
+        request_type: request_type?,
+        request_type_given: raw.request_type.is_some(),
+        target_client_id,
+        target_client_id_given: raw.target_client_id.is_some(),
+        deprecated: deprecated?,
+        reserved_ok: reserved_ok?,
+        response: response?.cloned(),
+        msg_version: msg_version?,
+        group: group?,
+    })
+}
+
+/// Checks that the `group` of message `name` makes a C identifier.
+pub(crate) fn check_group(name: &str, group: &str, pointer: &str) -> ParseResult<()> {
+    if !group.chars().any(|c| c.is_ascii_alphanumeric()) {
+        return Err(IdlError::invalid_value(
+            pointer,
+            group,
+            format!(
+                "group '{}' of message '{}' contains no C identifier characters",
+                group, name
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Records the C identifier of the group of `message` in `groups`, failing
+/// when another spelling of the group already took it.
+fn check_group_spelling<'a>(
+    message: &'a MessageDefinition,
+    groups: &mut HashMap<String, &'a str>,
+    pointer: &str,
+) -> ParseResult<()> {
+    let Some(group) = &message.group else {
+        return Ok(());
+    };
+    let ident = to_snake_case(group);
+    match groups.get(&ident) {
+        Some(first) if first != group => Err(IdlError::invalid_value(
+            pointer,
+            group,
+            format!(
+                "group '{}' of message '{}' maps to the same C identifier '{}' as group '{}'",
+                group, message.name, ident, first
+            ),
+        )),
+        Some(_) => Ok(()),
+        None => {
+            groups.insert(ident, group);
+            Ok(())
+        }
+    }
+}
+
+fn parse_packet_id(
+    name: &str,
+    raw: &RawMessage,
+    limits: &Limits,
+    pointer: &str,
+) -> ParseResult<(u32, Radix)> {
+    let max_id = max_packet_id(limits.packet_id_width);
+    let (packet_id, radix) = typed_int(&raw.packet_id, pointer, "packet_id", || {
+        format!(
+            "message '{}' has invalid 'packet_id' (must be an integer 0-{})",
+            name, max_id
+        )
+    })?
+    .ok_or_else(|| {
+        IdlError::missing_field(
+            pointer,
+            "packet_id",
+            format!(
+                "message '{}' is missing required field 'packet_id' (must be 0-{})",
+                name, max_id
+            ),
+        )
+    })?;
+
+    check_packet_id(name, packet_id, limits, pointer)?;
+    Ok((packet_id as u32, radix))
+}
+
+/// Checks that a packet ID fits the packet ID field of `limits`.
+pub(crate) fn check_packet_id(
+    name: &str,
+    packet_id: u64,
+    limits: &Limits,
+    pointer: &str,
+) -> ParseResult<()> {
+    let max_id = max_packet_id(limits.packet_id_width);
+    if packet_id > u64::from(max_id) {
+        return Err(IdlError::limit_exceeded(
+            &validate::pointer_push(pointer, "packet_id"),
+            packet_id as usize,
+            max_id as usize,
+            format!(
+                "message '{}' has packet_id {} which exceeds maximum of {} for {}-bit packet IDs (from {})",
+                name, packet_id, max_id, limits.packet_id_width, limits.packet_id_width_source
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Checks an array length against the active cap. `subject` names the array
+/// in the message, e.g. `array message 'log'`; `pointer` is the array itself.
+pub(crate) fn check_max_length(
+    subject: &str,
+    max_length: usize,
+    pointer: &str,
+    limits: &Limits,
+) -> ParseResult<()> {
+    check_array_length(subject, "max_length", max_length, pointer, limits)
+}
+
+/// [`check_max_length`] for the length under `key`, which is
+/// `max_length` or `fixed_length`.
+pub(crate) fn check_array_length(
+    subject: &str,
+    key: &str,
+    length: usize,
+    pointer: &str,
+    limits: &Limits,
+) -> ParseResult<()> {
+    let length_pointer = validate::pointer_push(pointer, key);
+    if length == 0 {
+        return Err(IdlError::invalid_value(
+            &length_pointer,
+            0,
+            format!("{} has {} of 0, must be at least 1", subject, key),
+        ));
+    }
+
+    if length > limits.max_array_length {
+        return Err(IdlError::limit_exceeded(
+            &length_pointer,
+            length,
+            limits.max_array_length,
+            format!(
+                "{} has {} {} which exceeds maximum of {} ({} limit)",
+                subject, key, length, limits.max_array_length, limits.array_length_source
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Reads the element count of an array message or field: `max_length`
+/// for up to that many elements or `fixed_length` for exactly that many.
+/// Returns the count and whether it is fixed.
+fn parse_array_length(
+    max_length: &Option<Loose<u64>>,
+    fixed_length: &Option<Loose<u64>>,
+    subject: &str,
+    pointer: &str,
+    limits: &Limits,
+) -> ParseResult<(usize, bool)> {
+    let length = |value, key| {
+        typed_int(value, pointer, key, || {
+            format!(
+                "{} has invalid '{}' (must be a positive integer)",
+                subject, key
+            )
+        })
+        .map(|length| length.map(|(length, _)| length))
+    };
+    let max_length = length(max_length, "max_length")?;
+    let fixed_length = length(fixed_length, "fixed_length")?;
+    let (key, length, fixed) = match (max_length, fixed_length) {
+        (Some(_), Some(_)) => {
+            return Err(IdlError::invalid_value(
+                &validate::pointer_push(pointer, "fixed_length"),
+                "fixed_length",
+                format!(
+                    "{} has both 'max_length' and 'fixed_length'; use only one",
+                    subject
+                ),
+            ));
+        }
+        (Some(length), None) => ("max_length", length, false),
+        (None, Some(length)) => ("fixed_length", length, true),
+        (None, None) => {
+            return Err(IdlError::missing_field(
+                pointer,
+                "max_length",
+                format!(
+                    "{} requires 'max_length' field (1-{})",
+                    subject, limits.max_array_length
+                ),
+            ));
+        }
+    };
+    let length = length as usize;
+    check_array_length(subject, key, length, pointer, limits)?;
+    Ok((length, fixed))
+}
+
+/// Error for `fixed_length` on something that is not an array.
+fn fixed_length_without_array(what: &str, pointer: &str) -> IdlError {
+    IdlError::invalid_value(
+        &validate::pointer_push(pointer, "fixed_length"),
+        "fixed_length",
+        format!(
+            "'fixed_length' only applies to arrays, and {} is not one (set 'array': true)",
+            what
+        ),
+    )
+}
+
+/// Reads `string_semantics`, which only applies to `char` arrays with a
+/// `max_length`: a `fixed_length` array has no length to derive.
+fn parse_string_semantics(
+    value: &Option<Loose<bool>>,
+    primitive: PrimitiveType,
+    variable_array: bool,
+    what: &str,
+    pointer: &str,
+) -> ParseResult<bool> {
+    let string_semantics = typed(value, pointer, "string_semantics", || {
+        format!(
+            "{} has invalid 'string_semantics' (must be true or false)",
+            what
+        )
+    })?
+    .copied()
+    .unwrap_or(false);
+    if string_semantics && !(variable_array && primitive == PrimitiveType::Char) {
+        return Err(IdlError::invalid_value(
+            &validate::pointer_push(pointer, "string_semantics"),
+            "true",
+            format!(
+                "'string_semantics' only applies to char arrays with 'max_length', and {} is not one",
+                what
+            ),
+        ));
+    }
+    Ok(string_semantics)
+}
+
+/// Checks the `since_version` of the fields of a message against its
+/// `msg_version`. Only the top-level fields of a struct message carry one,
+/// none above the message version and none below that of an earlier field,
+/// so the fields of every older version are a prefix of the current ones.
+pub(crate) fn check_versions(
+    name: &str,
+    msg_version: Option<u8>,
+    body: &MessageBody,
+    pointer: &str,
+) -> ParseResult<()> {
+    let what = format!("message '{}'", name);
+    if msg_version == Some(0) {
+        return Err(IdlError::invalid_value(
+            &validate::pointer_push(pointer, "msg_version"),
+            0,
+            format!("'msg_version' of {} is 0, which is not 1-255", what),
+        ));
+    }
+    let spec = match body {
+        MessageBody::Struct(spec) if spec.shared.is_none() => spec,
+        // Shared types have been checked where they are defined
+        MessageBody::Union(spec) => {
+            let variants_pointer = validate::pointer_push(pointer, "variants");
+            for variant in spec.variants.iter().filter(|v| v.spec.shared.is_none()) {
+                let variant_pointer =
+                    validate::pointer_push(&variants_pointer, &variant.tag.to_string());
+                reject_since_version(
+                    &variant.spec,
+                    &what,
+                    &validate::pointer_push(&variant_pointer, "fields"),
+                )?;
+            }
+            return Ok(());
+        }
+        _ => return Ok(()),
+    };
+    let fields_pointer = validate::pointer_push(pointer, "fields");
+    let mut previous: Option<(&str, u8)> = None;
+    // The padding of a gap is reported through the field after it
+    for field in spec.fields.iter().filter(|field| !field.reserved) {
+        let field_pointer = validate::pointer_push(&fields_pointer, &field.name);
+        if let StructFieldType::Nested(nested) = &field.field_type
+            && nested.shared.is_none()
+        {
+            reject_since_version(
+                nested,
+                &what,
+                &validate::pointer_push(&field_pointer, "fields"),
+            )?;
+        }
+        let since = field.since_version.unwrap_or(1);
+        let since_pointer = validate::pointer_push(&field_pointer, "since_version");
+        if field.since_version.is_some() && msg_version.is_none_or(|version| since > version) {
+            let message = match msg_version {
+                Some(version) => format!(
+                    "field '{}' of {} has since_version {}, above the msg_version {} of the message",
+                    field.name, what, since, version
+                ),
+                None => format!(
+                    "field '{}' of {} has 'since_version' but the message has no 'msg_version'",
+                    field.name, what
+                ),
+            };
+            return Err(IdlError::invalid_value(&since_pointer, since, message));
+        }
+        if let Some((earlier, earlier_since)) = previous
+            && since < earlier_since
+        {
+            return Err(IdlError::invalid_value(
+                if field.since_version.is_some() {
+                    &since_pointer
+                } else {
+                    &field_pointer
+                },
+                since,
+                format!(
+                    "field '{}' of {} is from version {} but follows field '{}' from version {}; fields can only be appended",
+                    field.name, what, since, earlier, earlier_since
+                ),
+            ));
+        }
+        previous = Some((&field.name, since));
+    }
+    Ok(())
+}
+
+/// Rejects `since_version` on the fields of `spec`, a struct that is not
+/// the body of a message, and on its nested structs.
+fn reject_since_version(spec: &StructSpec, what: &str, pointer: &str) -> ParseResult<()> {
+    for field in spec.fields.iter().filter(|field| !field.reserved) {
+        let field_pointer = validate::pointer_push(pointer, &field.name);
+        if let Some(since) = field.since_version {
+            return Err(IdlError::invalid_value(
+                &validate::pointer_push(&field_pointer, "since_version"),
+                since,
+                format!(
+                    "field '{}' of {} has 'since_version', which only applies to the top-level fields of a struct message",
+                    field.name, what
+                ),
+            ));
+        }
+        if let StructFieldType::Nested(nested) = &field.field_type
+            && nested.shared.is_none()
+        {
+            reject_since_version(
+                nested,
+                what,
+                &validate::pointer_push(&field_pointer, "fields"),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Checks that the sectors of an array message hold whole elements and
+/// fit in one payload, and that a sector is no larger than the full array.
+pub(crate) fn check_sector_bytes(
+    name: &str,
+    spec: &ArraySpec,
+    pointer: &str,
+    limits: &Limits,
+) -> ParseResult<()> {
+    let Some(sector_bytes) = spec.sector_bytes else {
+        return Ok(());
+    };
+    let sector_pointer = validate::pointer_push(pointer, "sector_bytes");
+    let elem_size = spec.primitive.byte_len();
+    if sector_bytes == 0 || !sector_bytes.is_multiple_of(elem_size) {
+        return Err(IdlError::invalid_value(
+            &sector_pointer,
+            sector_bytes,
+            format!(
+                "array message '{}' has sector_bytes {}, which must be a positive multiple of its {}-byte elements",
+                name, sector_bytes, elem_size
+            ),
+        ));
+    }
+    if sector_bytes > limits.max_payload_bytes {
+        return Err(IdlError::limit_exceeded(
+            &sector_pointer,
+            sector_bytes,
+            limits.max_payload_bytes,
+            format!(
+                "array message '{}' has sector_bytes {} which exceeds protocol limit of {} bytes",
+                name, sector_bytes, limits.max_payload_bytes
+            ),
+        ));
+    }
+    let array_bytes = spec.max_length * elem_size;
+    if sector_bytes > array_bytes {
+        return Err(IdlError::limit_exceeded(
+            &sector_pointer,
+            sector_bytes,
+            array_bytes,
+            format!(
+                "array message '{}' has sector_bytes {}, more than the {} bytes of its {} {}-byte elements",
+                name, sector_bytes, array_bytes, spec.max_length, elem_size
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Nesting depth and total field count of the structs in `specs`: 0 levels
+/// for a struct of plain fields, one more for each struct nested inside.
+/// Walks the structs without recursion, so no definition can overflow the
+/// stack.
+pub(crate) fn struct_shape<'a>(specs: impl IntoIterator<Item = &'a StructSpec>) -> (usize, usize) {
+    let mut pending: Vec<(&StructSpec, usize)> = specs.into_iter().map(|spec| (spec, 0)).collect();
+    let (mut depth, mut fields) = (0, 0);
+    while let Some((spec, level)) = pending.pop() {
+        depth = depth.max(level);
+        fields += spec.fields.len();
+        for field in &spec.fields {
+            if let StructFieldType::Nested(nested) = &field.field_type {
+                pending.push((nested, level + 1));
+            }
+        }
+    }
+    (depth, fields)
+}
+
+/// The structs of a message body: its own, or one per union variant.
+pub(crate) fn body_structs(body: &MessageBody) -> Vec<&StructSpec> {
+    match body {
+        MessageBody::Struct(spec) => vec![spec],
+        MessageBody::Union(spec) => spec.variants.iter().map(|v| &v.spec).collect(),
+        MessageBody::Scalar(_) | MessageBody::Array(_) => Vec::new(),
+    }
+}
+
+/// Checks that the struct at nesting level `depth`, named by the dotted
+/// path `path`, stays within `max_nesting_depth`.
+fn check_nesting_depth(
+    depth: usize,
+    path: &str,
+    pointer: &str,
+    limits: &Limits,
+) -> ParseResult<()> {
+    if depth <= limits.max_nesting_depth {
+        return Ok(());
+    }
+    Err(IdlError::limit_exceeded(
+        pointer,
+        depth,
+        limits.max_nesting_depth,
+        format!(
+            "struct field '{}' is nested {} levels deep, more than max_nesting_depth {} (from {})",
+            path, depth, limits.max_nesting_depth, limits.nesting_depth_source
+        ),
+    ))
+}
+
+/// Checks the nesting depth and the total field count of `specs`, the
+/// structs of `subject` (e.g. "message 'imu'").
+pub(crate) fn check_struct_shape<'a>(
+    subject: &str,
+    specs: impl IntoIterator<Item = &'a StructSpec>,
+    pointer: &str,
+    limits: &Limits,
+) -> ParseResult<()> {
+    let (depth, fields) = struct_shape(specs);
+    if depth > limits.max_nesting_depth {
+        return Err(IdlError::limit_exceeded(
+            pointer,
+            depth,
+            limits.max_nesting_depth,
+            format!(
+                "{} nests structs {} levels deep, more than max_nesting_depth {} (from {})",
+                subject, depth, limits.max_nesting_depth, limits.nesting_depth_source
+            ),
+        ));
+    }
+    if fields > MAX_MESSAGE_FIELDS {
+        return Err(IdlError::limit_exceeded(
+            pointer,
+            fields,
+            MAX_MESSAGE_FIELDS,
+            format!(
+                "{} has {} fields counting those of nested structs, more than the limit of {}",
+                subject, fields, MAX_MESSAGE_FIELDS
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// [`check_struct_shape`] for the structs of a message body.
+pub(crate) fn check_body_shape(
+    name: &str,
+    body: &MessageBody,
+    pointer: &str,
+    limits: &Limits,
+) -> ParseResult<()> {
+    check_struct_shape(
+        &format!("message '{}'", name),
+        body_structs(body),
+        pointer,
+        limits,
+    )
+}
+
+/// Checks the encoded size of a message body against the payload limit.
+pub(crate) fn check_body_size(
+    name: &str,
+    body: &MessageBody,
+    pointer: &str,
+    limits: &Limits,
+) -> ParseResult<()> {
+    let max_size = message_body_max_size(body);
+    if max_size <= limits.max_payload_bytes {
+        return Ok(());
+    }
+    match body {
+        MessageBody::Array(spec) => Err(IdlError::limit_exceeded(
+            &validate::pointer_push(pointer, "max_length"),
+            max_size,
+            limits.max_payload_bytes,
+            format!(
+                "array message '{}' has maximum payload size {} bytes ({}*{}) which exceeds protocol limit of {} bytes",
+                name,
+                max_size,
+                spec.max_length,
+                spec.primitive.byte_len(),
+                limits.max_payload_bytes
+            ),
+        )),
+        _ => Err(IdlError::limit_exceeded(
+            pointer,
+            max_size,
+            limits.max_payload_bytes,
+            format!(
+                "{} message '{}' has maximum size {} bytes which exceeds protocol limit of {} bytes",
+                body.kind(),
+                name,
+                max_size,
+                limits.max_payload_bytes
+            ),
+        )),
+    }
+}
+
+fn parse_request_type(name: &str, raw: &RawMessage, pointer: &str) -> ParseResult<RequestType> {
+    let Some(rt_str) = typed(&raw.request_type, pointer, "request_type", || {
+        format!(
+            "message '{}' has invalid 'request_type' (must be a string)",
+            name
+        )
+    })?
+    else {
+        return Ok(RequestType::default());
+    };
+    RequestType::from_str(rt_str).map_err(|e| {
+        IdlError::invalid_value(
+            &validate::pointer_push(pointer, "request_type"),
+            rt_str,
+            e.to_string(),
+        )
+    })
+}
+
+/// Returns the non-empty `fields` object of a struct message or field, in
+/// the order of its `field_order` if given, or records why there is none.
+fn struct_fields_object<'a>(
+    fields: &'a Option<Loose<Map<String, Value>>>,
+    field_order: &Option<Loose<Vec<String>>>,
+    pointer: &str,
+    missing: impl FnOnce() -> String,
+    empty: impl FnOnce() -> String,
+    diag: &mut Diagnostics,
+) -> Option<Cow<'a, Map<String, Value>>> {
+    let Some(fields_obj) = fields.as_ref().and_then(Loose::valid) else {
+        diag.push(IdlError::missing_field(pointer, "fields", missing()));
+        return None;
+    };
+    if fields_obj.is_empty() {
+        diag.push(IdlError::invalid_value(
+            &validate::pointer_push(pointer, "fields"),
+            "{}",
+            empty(),
+        ));
+        return None;
+    }
+    let order_pointer = validate::pointer_push(pointer, "field_order");
+    let order = match field_order {
+        None => return Some(Cow::Borrowed(fields_obj)),
+        Some(Loose::Valid(order)) => order,
+        Some(Loose::Invalid(found)) => {
+            diag.push(IdlError::invalid_type(
+                &order_pointer,
+                found,
+                "'field_order' must be an array of field names",
+            ));
+            return None;
+        }
+    };
+    let mut ordered = Map::new();
+    let mut complete = true;
+    for (index, name) in order.iter().enumerate() {
+        let entry_pointer = validate::pointer_push(&order_pointer, &index.to_string());
+        if ordered.contains_key(name) {
+            diag.push(IdlError::invalid_value(
+                &entry_pointer,
+                name,
+                format!("field '{}' is listed twice in 'field_order'", name),
+            ));
+            complete = false;
+        } else if let Some(value) = fields_obj.get(name) {
+            ordered.insert(name.clone(), value.clone());
+        } else {
+            diag.push(IdlError::invalid_value(
+                &entry_pointer,
+                name,
+                format!("'field_order' lists '{}', which is not in 'fields'", name),
+            ));
+            complete = false;
+        }
+    }
+    let missing: Vec<String> = fields_obj
+        .keys()
+        .filter(|name| !order.contains(name))
+        .map(|name| format!("'{}'", name))
+        .collect();
+    if !missing.is_empty() {
+        diag.push(IdlError::invalid_value(
+            &order_pointer,
+            missing.join(", "),
+            format!("'field_order' leaves out {}", missing.join(", ")),
+        ));
+        complete = false;
+    }
+    complete.then_some(Cow::Owned(ordered))
+}
+
+fn parse_struct_body(
+    name: &str,
+    raw: &RawMessage,
+    pointer: &str,
+    limits: &Limits,
+    types: &SharedTypes,
+    diag: &mut Diagnostics,
+) -> Option<MessageBody> {
+    let fields_obj = struct_fields_object(
+        &raw.fields,
+        &raw.field_order,
+        pointer,
+        || {
+            format!(
+                "struct message '{}' requires a 'fields' object containing field definitions",
+                name
+            )
+        },
+        || {
+            format!(
+                "struct message '{}' must define at least one field in 'fields' object",
+                name
+            )
+        },
+        diag,
+    )?;
+    let fields_pointer = validate::pointer_push(pointer, "fields");
+    let fields = parse_struct_fields(&fields_obj, name, &fields_pointer, 0, limits, types, diag)?;
+    let body = MessageBody::Struct(StructSpec {
+        fields,
+        shared: None,
+    });
+    diag.check(check_body_shape(name, &body, pointer, limits))?;
+    diag.check(check_body_size(name, &body, pointer, limits))?;
+    Some(body)
+}
+
+/// Parses a `union` message: the `tag` type and the struct of every entry
+/// of `variants`, keyed by its tag value.
+fn parse_union_body(
+    name: &str,
+    raw: &RawMessage,
+    pointer: &str,
+    limits: &Limits,
+    types: &SharedTypes,
+    diag: &mut Diagnostics,
+) -> Option<MessageBody> {
+    let tag = diag.check(parse_union_tag(name, raw, pointer));
+    let endian = diag.check(parse_endian(raw.endian(), pointer));
+    let variants_pointer = validate::pointer_push(pointer, "variants");
+    let defs = match &raw.variants {
+        Some(Loose::Valid(defs)) if !defs.is_empty() => defs,
+        Some(Loose::Valid(_)) => {
+            diag.push(IdlError::invalid_value(
+                &variants_pointer,
+                "{}",
+                format!(
+                    "union message '{}' must define at least one variant in 'variants' object",
+                    name
+                ),
+            ));
+            return None;
+        }
+        Some(Loose::Invalid(found)) => {
+            diag.push(IdlError::invalid_type(
+                &variants_pointer,
+                found,
+                format!(
+                    "union message '{}' has invalid 'variants' (must be an object keyed by tag value)",
+                    name
+                ),
+            ));
+            return None;
+        }
+        None => {
+            diag.push(IdlError::missing_field(
+                pointer,
+                "variants",
+                format!(
+                    "union message '{}' requires a 'variants' object mapping tag values to structs",
+                    name
+                ),
+            ));
+            return None;
+        }
+    };
+
+    let mut variants: Vec<UnionVariant> = Vec::new();
+    let mut complete = true;
+    for (key, value) in defs {
+        if diag.stop() {
+            return None;
+        }
+        let variant_pointer = validate::pointer_push(&variants_pointer, key);
+        let Some(variant) =
+            parse_union_variant(name, key, value, &variant_pointer, limits, types, diag)
+        else {
+            complete = false;
+            continue;
+        };
+        if let Some(tag) = tag
+            && let Some((min, max)) = tag.integer_range()
+            && !(min..=max).contains(&variant.tag)
+        {
+            diag.push(IdlError::invalid_value(
+                &variant_pointer,
+                key,
+                format!(
+                    "variant '{}' of union message '{}' has tag value {} outside the range of {} ({} to {})",
+                    variant.name,
+                    name,
+                    variant.tag,
+                    tag.name(),
+                    min,
+                    max
+                ),
+            ));
+            complete = false;
+        }
+        if let Some(other) = variants.iter().find(|other| other.tag == variant.tag) {
+            diag.push(IdlError::invalid_value(
+                &variant_pointer,
+                key,
+                format!(
+                    "variants '{}' and '{}' of union message '{}' have the same tag value {}",
+                    other.name, variant.name, name, variant.tag
+                ),
+            ));
+            complete = false;
+        } else if let Some(other) = variants
+            .iter()
+            .find(|other| to_snake_case(&other.name) == to_snake_case(&variant.name))
+        {
+            diag.push(IdlError::invalid_value(
+                &validate::pointer_push(&variant_pointer, "name"),
+                &variant.name,
+                format!(
+                    "variant '{}' of union message '{}' maps to the same C identifier as variant '{}'",
+                    variant.name, name, other.name
+                ),
+            ));
+            complete = false;
+        }
+        variants.push(variant);
+    }
+    if !complete {
+        return None;
+    }
+    let body = MessageBody::Union(UnionSpec {
+        tag: tag?,
+        endian: endian?.unwrap_or(limits.default_endian),
+        variants,
+    });
+    diag.check(check_body_shape(name, &body, pointer, limits))?;
+    diag.check(check_body_size(name, &body, pointer, limits))?;
+    Some(body)
+}
+
+/// Reads the required integer `tag` type of a union message.
+fn parse_union_tag(name: &str, raw: &RawMessage, pointer: &str) -> ParseResult<PrimitiveType> {
+    let tag_pointer = validate::pointer_push(pointer, "tag");
+    let tag = typed(&raw.tag, pointer, "tag", || {
+        format!(
+            "union message '{}' has invalid 'tag' (must be a type name string)",
+            name
+        )
+    })?
+    .ok_or_else(|| {
+        IdlError::missing_field(
+            pointer,
+            "tag",
+            format!(
+                "union message '{}' is missing required field 'tag' (e.g., 'uint8')",
+                name
+            ),
+        )
+    })?;
+    match PrimitiveType::from_str(tag) {
+        Ok(primitive) if primitive.integer_range().is_some() => Ok(primitive),
+        Ok(_) => Err(IdlError::invalid_value(
+            &tag_pointer,
+            tag,
+            format!(
+                "union message '{}' has 'tag' type '{}', which is not an integer type",
+                name, tag
+            ),
+        )),
+        Err(_) => Err(IdlError::unknown_type_name(
+            &tag_pointer,
+            tag,
+            format!(
+                "unsupported 'tag' type '{}' for union message '{}'",
+                tag, name
+            ),
+        )),
+    }
+}
+
+/// Parses one entry of the `variants` object of union message `union`.
+fn parse_union_variant(
+    union: &str,
+    key: &str,
+    value: &Value,
+    pointer: &str,
+    limits: &Limits,
+    types: &SharedTypes,
+    diag: &mut Diagnostics,
+) -> Option<UnionVariant> {
+    let tag = match parse_int_literal(key.trim()) {
+        Ok((tag, Radix::Hex | Radix::Binary)) => Ok(i128::from(tag)),
+        _ => key.trim().parse::<i128>(),
+    };
+    let Ok(tag) = tag else {
+        diag.push(IdlError::invalid_value(
+            pointer,
+            key,
+            format!(
+                "variant key '{}' of union message '{}' is not an integer tag value",
+                key, union
+            ),
+        ));
+        return None;
+    };
+    let Some(variant_map) = value.as_object() else {
+        diag.push(IdlError::invalid_type(
+            pointer,
+            value,
+            format!(
+                "variant {} of union message '{}' must be an object with 'name' and 'fields'",
+                key, union
+            ),
+        ));
+        return None;
+    };
+    let raw = RawVariant::deserialize(variant_map).unwrap_or_default();
+    let what = format!("variant {} of union message '{}'", key, union);
+    let name = diag.check(
+        typed(&raw.name, pointer, "name", || {
+            format!("'name' of {} must be a string", what)
+        })
+        .and_then(|name| {
+            name.ok_or_else(|| {
+                IdlError::missing_field(
+                    pointer,
+                    "name",
+                    format!("{} is missing required field 'name'", what),
+                )
+            })
+        })
+        .and_then(|name| {
+            if to_snake_case(name).is_empty() {
+                Err(IdlError::invalid_value(
+                    &validate::pointer_push(pointer, "name"),
+                    name,
+                    format!(
+                        "{} has name '{}', which contains no C identifier characters",
+                        what, name
+                    ),
+                ))
+            } else {
+                Ok(name.clone())
+            }
+        }),
+    );
+    let description = diag.check(parse_doc_string(&raw.desc, "desc", &what, pointer));
+    let variant_type = diag.check(typed(&raw.variant_type, pointer, "type", || {
+        format!("'type' of {} must be a shared type name", what)
+    }))?;
+
+    let spec = match variant_type {
+        Some(shared) if !shared.eq_ignore_ascii_case("struct") => {
+            if raw.fields.is_some() {
+                diag.push(IdlError::invalid_value(
+                    &validate::pointer_push(pointer, "fields"),
+                    "fields",
+                    format!(
+                        "{} has both a shared 'type' and 'fields'; use only one",
+                        what
+                    ),
+                ));
+                return None;
+            }
+            if !types.defines(shared) {
+                diag.push(IdlError::unknown_type_name(
+                    &validate::pointer_push(pointer, "type"),
+                    shared,
+                    format!("{} refers to unknown shared type '{}'", what, shared),
+                ));
+                return None;
+            }
+            // Broken shared types have been reported already
+            types.get(shared)?.clone()
+        }
+        _ => {
+            let fields_obj = struct_fields_object(
+                &raw.fields,
+                &raw.field_order,
+                pointer,
+                || format!("{} requires a 'fields' object or a shared 'type'", what),
+                || format!("{} must define at least one field in 'fields' object", what),
+                diag,
+            )?;
+            let parent = format!("{}.{}", union, name.as_deref().unwrap_or(key));
+            let fields = parse_struct_fields(
+                &fields_obj,
+                &parent,
+                &validate::pointer_push(pointer, "fields"),
+                0,
+                limits,
+                types,
+                diag,
+            )?;
+            StructSpec {
+                fields,
+                shared: None,
+            }
+        }
+    };
+    Some(UnionVariant {
+        name: name?,
+        tag,
+        spec,
+        description: description?,
+    })
+}
+
+/// Parses a `bytes` message into a struct with the single member `value`.
+fn parse_bytes_body(
+    name: &str,
+    raw: &RawMessage,
+    pointer: &str,
+    limits: &Limits,
+) -> ParseResult<MessageBody> {
+    let what = format!("bytes message '{}'", name);
+    let length = parse_bytes_length(&raw.length, &raw.array, &what, pointer, limits)?;
+    if length > limits.max_payload_bytes {
+        return Err(IdlError::limit_exceeded(
+            &validate::pointer_push(pointer, "length"),
+            length,
+            limits.max_payload_bytes,
+            format!(
+                "{} has length {} which exceeds protocol limit of {} bytes",
+                what, length, limits.max_payload_bytes
+            ),
+        ));
+    }
+    Ok(MessageBody::Struct(StructSpec {
+        fields: vec![StructField {
+            name: "value".to_string(),
+            field_type: StructFieldType::Bytes(length),
+            endian: Endian::default(),
+            scaling: None,
+            range: None,
+            description: parse_doc_string(&raw.desc, "desc", &what, pointer)?,
+            unit: parse_doc_string(&raw.unit, "unit", &what, pointer)?,
+            timestamp: None,
+            since_version: None,
+            at: None,
+            reserved: false,
+        }],
+        shared: None,
+    }))
+}
+
+/// Reads the required `length` of a `bytes` message or field.
+fn parse_bytes_length(
+    length: &Option<Loose<u64>>,
+    array: &Option<Loose<bool>>,
+    what: &str,
+    pointer: &str,
+    limits: &Limits,
+) -> ParseResult<usize> {
+    if array.as_ref().and_then(Loose::valid) == Some(&true) {
+        return Err(IdlError::invalid_value(
+            &validate::pointer_push(pointer, "array"),
+            true,
+            format!("{} cannot be an array; use 'length' instead", what),
+        ));
+    }
+    let length = typed_int(length, pointer, "length", || {
+        format!("'length' of {} must be a positive integer", what)
+    })?
+    .map(|(length, _)| length)
+    .ok_or_else(|| {
+        IdlError::missing_field(
+            pointer,
+            "length",
+            format!(
+                "{} requires 'length' field (1-{})",
+                what, limits.max_payload_bytes
+            ),
+        )
+    })? as usize;
+    if length == 0 {
+        return Err(IdlError::invalid_value(
+            &validate::pointer_push(pointer, "length"),
+            0,
+            format!("{} has length of 0, must be at least 1", what),
+        ));
+    }
+    Ok(length)
+}
+
+fn length_without_bytes(what: &str, pointer: &str) -> IdlError {
+    IdlError::invalid_value(
+        &validate::pointer_push(pointer, "length"),
+        "length",
+        format!(
+            "'length' only applies to bytes types, and {} is not one; arrays use 'max_length'",
+            what
+        ),
+    )
+}
+
+fn parse_primitive_body(
+    name: &str,
+    msg_type: &str,
+    raw: &RawMessage,
+    pointer: &str,
+    limits: &Limits,
+) -> ParseResult<MessageBody> {
+    let primitive = PrimitiveType::from_str(msg_type).map_err(|_| {
+        IdlError::unknown_type_name(
+            &validate::pointer_push(pointer, "msg_type"),
+            msg_type,
+            format!(
+                "unsupported 'msg_type' '{}' for message '{}'",
+                msg_type, name
+            ),
+        )
+    })?;
+    let endian = parse_endian(raw.endian(), pointer)?.unwrap_or(limits.default_endian);
+    let is_array = raw
+        .array
+        .as_ref()
+        .and_then(Loose::valid)
+        .copied()
+        .unwrap_or(false);
+    let scaling = parse_scaling(
+        &raw.scale,
+        &raw.offset,
+        Some(primitive),
+        || format!("message '{}'", name),
+        pointer,
+    )?;
+    let range = parse_range(
+        &raw.min,
+        &raw.max,
+        Some(primitive),
+        is_array,
+        || format!("message '{}'", name),
+        pointer,
+    )?;
+    let what = format!("message '{}'", name);
+    let description = parse_doc_string(&raw.desc, "desc", &what, pointer)?;
+    let unit = parse_doc_string(&raw.unit, "unit", &what, pointer)?;
+    let string_semantics = parse_string_semantics(
+        &raw.string_semantics,
+        primitive,
+        is_array && raw.fixed_length.is_none(),
+        &what,
+        pointer,
+    )?;
+    if !is_array {
+        if raw.fixed_length.is_some() {
+            return Err(fixed_length_without_array(&what, pointer));
+        }
+        return Ok(MessageBody::Scalar(ScalarSpec {
+            primitive,
+            endian,
+            scaling,
+            range,
+            description,
+            unit,
+        }));
+    }
+
+    let subject = format!("array message '{}'", name);
+    let (max_length, fixed) = parse_array_length(
+        &raw.max_length,
+        &raw.fixed_length,
+        &subject,
+        pointer,
+        limits,
+    )?;
+    if fixed {
+        return parse_fixed_array_body(
+            &subject,
+            StructField {
+                name: "data".to_string(),
+                field_type: StructFieldType::Array(StructFieldArraySpec {
+                    primitive,
+                    max_length,
+                    fixed,
+                    string_semantics,
+                }),
+                endian,
+                scaling,
+                range: None,
+                description,
+                unit,
+                timestamp: None,
+                since_version: None,
+                at: None,
+                reserved: false,
+            },
+            raw,
+            pointer,
+            limits,
+        );
+    }
+
+    let sector_bytes = typed_int(&raw.sector_bytes, pointer, "sector_bytes", || {
+        format!(
+            "array message '{}' has invalid 'sector_bytes' (must be a positive integer)",
+            name
+        )
+    })?
+    .map(|(v, _)| v as usize);
+    let body = MessageBody::Array(ArraySpec {
+        primitive,
+        endian,
+        max_length,
+        sector_bytes,
+        scaling,
+        description,
+        unit,
+        string_semantics,
+    });
+    // Check payload size constraint
+    check_body_size(name, &body, pointer, limits)?;
+    if let MessageBody::Array(spec) = &body {
+        check_sector_bytes(name, spec, pointer, limits)?;
+    }
+    Ok(body)
+}
+
+/// Turns a `fixed_length` array message into a struct with the single
+/// member `data`, which has no length member to go with it.
+fn parse_fixed_array_body(
+    subject: &str,
+    data: StructField,
+    raw: &RawMessage,
+    pointer: &str,
+    limits: &Limits,
+) -> ParseResult<MessageBody> {
+    if raw.sector_bytes.is_some() {
+        return Err(IdlError::invalid_value(
+            &validate::pointer_push(pointer, "sector_bytes"),
+            "sector_bytes",
+            format!(
+                "{} has 'fixed_length', which cannot be split into sectors; use 'max_length'",
+                subject
+            ),
+        ));
+    }
+    let body = MessageBody::Struct(StructSpec {
+        fields: vec![data],
+        shared: None,
+    });
+    let size = message_body_max_size(&body);
+    if size > limits.max_payload_bytes {
+        return Err(IdlError::limit_exceeded(
+            &validate::pointer_push(pointer, "fixed_length"),
+            size,
+            limits.max_payload_bytes,
+            format!(
+                "{} has payload size {} bytes which exceeds protocol limit of {} bytes",
+                subject, size, limits.max_payload_bytes
+            ),
+        ));
+    }
+    Ok(body)
+}
+
+/// Parses struct fields recursively, supporting nested structs. `depth` is
+/// the nesting level of the struct owning the fields, 0 for a message.
+///
+/// Every field is checked; the list is only returned when all of them parsed.
+fn parse_struct_fields(
+    fields_obj: &Map<String, Value>,
+    parent_name: &str,
+    pointer: &str,
+    depth: usize,
+    limits: &Limits,
+    types: &SharedTypes,
+    diag: &mut Diagnostics,
+) -> Option<Vec<StructField>> {
+    let mut fields = Vec::new();
+    let mut complete = true;
+    // Offset of the next field while every field before it has a fixed size
+    let mut position = Some(0);
+    let mut gaps = 0;
+    for (field_name, field_value) in fields_obj {
+        if diag.stop() {
+            return None;
+        }
+        let field_pointer = validate::pointer_push(pointer, field_name);
+        let Some(field) = parse_struct_field(
+            field_name,
+            field_value,
+            Parent {
+                name: parent_name,
+                depth,
+            },
+            &field_pointer,
+            limits,
+            types,
+            diag,
+        ) else {
+            complete = false;
+            position = None;
+            continue;
+        };
+        if let Some(at) = field.at {
+            let at_pointer = validate::pointer_push(&field_pointer, "at");
+            match position {
+                None if !complete => {}
+                None => {
+                    diag.push(IdlError::invalid_value(
+                        &at_pointer,
+                        at,
+                        format!(
+                            "field '{}' in '{}' has 'at' {} but a variable-length field comes before it",
+                            field_name, parent_name, at
+                        ),
+                    ));
+                    complete = false;
+                }
+                Some(end) if at < end => {
+                    diag.push(IdlError::invalid_value(
+                        &at_pointer,
+                        at,
+                        format!(
+                            "field '{}' in '{}' has 'at' {}, which overlaps the fields before it (they end at byte {})",
+                            field_name, parent_name, at, end
+                        ),
+                    ));
+                    complete = false;
+                }
+                Some(end) if at > end => {
+                    let name = format!("_reserved_{}", gaps);
+                    gaps += 1;
+                    if fields_obj.contains_key(&name) {
+                        diag.push(IdlError::invalid_value(
+                            &at_pointer,
+                            at,
+                            format!(
+                                "the gap before field '{}' in '{}' needs the name '{}', which is taken",
+                                field_name, parent_name, name
+                            ),
+                        ));
+                        complete = false;
+                    }
+                    fields.push(reserved_field(name, at - end, field.since_version));
+                }
+                Some(_) => {}
+            }
+            position = Some(at);
+        }
+        position = position
+            .zip(struct_field_fixed_size(&field))
+            .map(|(position, size)| position + size);
+        fields.push(field);
+    }
+    complete.then_some(fields)
+}
+
+/// The `_reserved_<n>` padding of `length` bytes before a field with `at`.
+fn reserved_field(name: String, length: usize, since_version: Option<u8>) -> StructField {
+    StructField {
+        name,
+        field_type: StructFieldType::Bytes(length),
+        endian: Endian::Little,
+        scaling: None,
+        range: None,
+        description: Some("Reserved".to_string()),
+        unit: None,
+        timestamp: None,
+        since_version,
+        at: None,
+        reserved: true,
+    }
+}
+
+/// Size of a field on the wire, unless it holds a variable-length array.
+fn struct_field_fixed_size(field: &StructField) -> Option<usize> {
+    match &field.field_type {
+        StructFieldType::Primitive(prim) => Some(prim.byte_len()),
+        StructFieldType::Array(arr) if arr.fixed => Some(arr.max_length * arr.primitive.byte_len()),
+        StructFieldType::Array(_) => None,
+        StructFieldType::Nested(nested) => nested.fields.iter().map(struct_field_fixed_size).sum(),
+        StructFieldType::Bytes(length) => Some(*length),
+    }
+}
+
+/// The struct a field belongs to: its dotted name and nesting level.
+#[derive(Clone, Copy)]
+struct Parent<'a> {
+    name: &'a str,
+    depth: usize,
+}
+
+fn parse_struct_field(
+    field_name: &str,
+    field_value: &Value,
+    parent: Parent<'_>,
+    pointer: &str,
+    limits: &Limits,
+    types: &SharedTypes,
+    diag: &mut Diagnostics,
+) -> Option<StructField> {
+    let Parent {
+        name: parent_name,
+        depth,
+    } = parent;
+    let Some(field_map) = field_value.as_object() else {
+        diag.push(IdlError::invalid_type(
+            pointer,
+            field_value,
+            format!(
+                "field '{}' in '{}' must be an object",
+                field_name, parent_name
+            ),
+        ));
+        return None;
+    };
+    let raw = RawField::deserialize(field_map).unwrap_or_default();
+
+    // Support both "type" and "msg_type" for field type specification
+    let type_str = match raw.type_key() {
+        Some((_, Loose::Valid(type_str))) => type_str,
+        Some((key, Loose::Invalid(found))) => {
+            diag.push(IdlError::invalid_type(
+                &validate::pointer_push(pointer, key),
+                found,
+                format!(
+                    "field '{}' in '{}' has invalid '{}' (must be a string)",
+                    field_name, parent_name, key
+                ),
+            ));
+            return None;
+        }
+        None => {
+            diag.push(IdlError::missing_field(
+                pointer,
+                "type",
+                format!(
+                    "field '{}' in '{}' is missing 'type' or 'msg_type'",
+                    field_name, parent_name
+                ),
+            ));
+            return None;
+        }
+    };
+
+    let endian = diag.check(parse_endian(raw.endian(), pointer));
+    let what = || format!("field '{}' in '{}'", field_name, parent_name);
+    let timestamp = diag.check(parse_timestamp(
+        &raw,
+        type_str.eq_ignore_ascii_case("timestamp"),
+        &what(),
+        pointer,
+    ))?;
+
+    // Check if this is a nested struct
+    let is_bytes = type_str.eq_ignore_ascii_case("bytes");
+    if !is_bytes && raw.length.is_some() {
+        diag.push(length_without_bytes(&what(), pointer));
+        return None;
+    }
+
+    let field_type = if let Some((storage, _)) = timestamp {
+        StructFieldType::Primitive(storage)
+    } else if is_bytes {
+        StructFieldType::Bytes(diag.check(parse_bytes_length(
+            &raw.length,
+            &raw.array,
+            &what(),
+            pointer,
+            limits,
+        ))?)
+    } else if type_str.eq_ignore_ascii_case("struct") {
+        let nested_fields_obj = struct_fields_object(
+            &raw.fields,
+            &raw.field_order,
+            pointer,
+            || {
+                format!(
+                    "nested struct field '{}' in '{}' requires a 'fields' object",
+                    field_name, parent_name
+                )
+            },
+            || {
+                format!(
+                    "nested struct field '{}' in '{}' must define at least one field",
+                    field_name, parent_name
+                )
+            },
+            diag,
+        )?;
+
+        let nested_path = format!("{}.{}", parent_name, field_name);
+        // Checked before descending, so no input can recurse without bound
+        diag.check(check_nesting_depth(
+            depth + 1,
+            &nested_path,
+            pointer,
+            limits,
+        ))?;
+        let nested_fields = parse_struct_fields(
+            &nested_fields_obj,
+            &nested_path,
+            &validate::pointer_push(pointer, "fields"),
+            depth + 1,
+            limits,
+            types,
+            diag,
+        )?;
+        StructFieldType::Nested(StructSpec {
+            fields: nested_fields,
+            shared: None,
+        })
+    } else if types.defines(type_str) {
+        if raw.array.as_ref().and_then(Loose::valid) == Some(&true) {
+            diag.push(IdlError::invalid_value(
+                &validate::pointer_push(pointer, "array"),
+                true,
+                format!(
+                    "field '{}' in '{}' cannot be an array of shared type '{}'",
+                    field_name, parent_name, type_str
+                ),
+            ));
+            return None;
+        }
+        let spec = types.get(type_str)?;
+        let (levels, _) = struct_shape([spec]);
+        diag.check(check_nesting_depth(
+            depth + 1 + levels,
+            &format!("{}.{}", parent_name, field_name),
+            pointer,
+            limits,
+        ))?;
+        StructFieldType::Nested(spec.clone())
+    } else {
+        diag.check(parse_primitive_field_type(
+            field_name,
+            type_str,
+            &raw,
+            parent_name,
+            pointer,
+            limits,
+        ))?
+    };
+
+    let primitive = match &field_type {
+        StructFieldType::Primitive(primitive) => Some(*primitive),
+        StructFieldType::Array(array) => Some(array.primitive),
+        StructFieldType::Nested(_) | StructFieldType::Bytes(_) => None,
+    };
+    let scaling = diag.check(parse_scaling(
+        &raw.scale,
+        &raw.offset,
+        primitive,
+        what,
+        pointer,
+    ));
+    let range = diag.check(parse_range(
+        &raw.min,
+        &raw.max,
+        primitive,
+        matches!(field_type, StructFieldType::Array(_)),
+        what,
+        pointer,
+    ));
+    // `msg_desc` is the older spelling of `desc` on fields
+    let (desc_key, desc) = match (&raw.desc, &raw.msg_desc) {
+        (None, Some(_)) => ("msg_desc", &raw.msg_desc),
+        _ => ("desc", &raw.desc),
+    };
+    let description = diag.check(parse_doc_string(desc, desc_key, &what(), pointer));
+    let unit = diag.check(parse_doc_string(&raw.unit, "unit", &what(), pointer));
+    let since_version = diag.check(parse_version(
+        &raw.since_version,
+        "since_version",
+        &what(),
+        pointer,
+    ));
+    let at = diag.check(typed(&raw.at, pointer, "at", || {
+        format!("'at' of {} must be a byte offset", what())
+    }));
+
+    Some(StructField {
+        name: field_name.to_string(),
+        field_type,
+        endian: endian?.unwrap_or(limits.default_endian),
+        scaling: scaling?,
+        range: range?,
+        description: description?,
+        unit: unit?,
+        timestamp: timestamp.map(|(_, resolution)| resolution),
+        since_version: since_version?,
+        at: at?.map(|&at| usize::try_from(at).unwrap_or(usize::MAX)),
+        reserved: false,
+    })
+}
+
+/// Reads a `msg_version` or `since_version`: a version byte, from 1.
+fn parse_version(
+    value: &Option<Loose<u64>>,
+    key: &str,
+    what: &str,
+    pointer: &str,
+) -> ParseResult<Option<u8>> {
+    let Some(version) = typed(value, pointer, key, || {
+        format!("'{}' of {} must be an integer 1-255", key, what)
+    })?
+    else {
+        return Ok(None);
+    };
+    match u8::try_from(*version) {
+        Ok(version) if version > 0 => Ok(Some(version)),
+        _ => Err(IdlError::invalid_value(
+            &validate::pointer_push(pointer, key),
+            *version,
+            format!("'{}' of {} is {}, which is not 1-255", key, what, version),
+        )),
+    }
+}
+
+/// Reads the storage primitive and resolution of a `timestamp` field, or
+/// `None` for other fields, which must not set `storage` or `resolution`.
+fn parse_timestamp(
+    raw: &RawField,
+    is_timestamp: bool,
+    what: &str,
+    pointer: &str,
+) -> ParseResult<Option<(PrimitiveType, TimestampResolution)>> {
+    let storage = typed(&raw.storage, pointer, "storage", || {
+        format!("'storage' of {} must be a string", what)
+    })?;
+    let resolution = typed(&raw.resolution, pointer, "resolution", || {
+        format!("'resolution' of {} must be a string", what)
+    })?;
+    if !is_timestamp {
+        let key = match (storage, resolution) {
+            (None, None) => return Ok(None),
+            (Some(_), _) => "storage",
+            (None, Some(_)) => "resolution",
+        };
+        return Err(IdlError::invalid_value(
+            &validate::pointer_push(pointer, key),
+            storage.or(resolution).map_or("", String::as_str),
+            format!(
+                "'{}' only applies to timestamp fields, and {} is not one",
+                key, what
+            ),
+        ));
+    }
+    if raw.array.as_ref().and_then(Loose::valid) == Some(&true) {
+        return Err(IdlError::invalid_value(
+            &validate::pointer_push(pointer, "array"),
+            true,
+            format!("{} cannot be an array of timestamps", what),
+        ));
+    }
+    let storage = match storage {
+        None => PrimitiveType::Uint64,
+        Some(text) => match PrimitiveType::from_str(text) {
+            Ok(primitive @ (PrimitiveType::Uint32 | PrimitiveType::Uint64)) => primitive,
+            _ => {
+                return Err(IdlError::invalid_value(
+                    &validate::pointer_push(pointer, "storage"),
+                    text,
+                    format!("'storage' of {} must be uint32 or uint64", what),
+                ));
+            }
+        },
+    };
+    let resolution = match resolution {
+        None => TimestampResolution::default(),
+        Some(text) => TimestampResolution::from_str(text).ok_or_else(|| {
+            IdlError::invalid_value(
+                &validate::pointer_push(pointer, "resolution"),
+                text,
+                format!("'resolution' of {} must be ms, us or ns", what),
+            )
+        })?,
+    };
+    Ok(Some((storage, resolution)))
+}
+
+/// Reads an optional free-form string that only ends up in comments and
+/// docs.
+fn parse_doc_string(
+    value: &Option<Loose<String>>,
+    key: &str,
+    what: &str,
+    pointer: &str,
+) -> ParseResult<Option<String>> {
+    typed(value, pointer, key, || {
+        format!("'{}' of {} must be a string", key, what)
+    })
+    .map(Option::<&String>::cloned)
+}
+
+fn parse_primitive_field_type(
+    field_name: &str,
+    type_str: &str,
+    raw: &RawField,
+    parent_name: &str,
+    pointer: &str,
+    limits: &Limits,
+) -> ParseResult<StructFieldType> {
+    let type_key = raw.type_key().map_or("type", |(key, _)| key);
+    let primitive = PrimitiveType::from_str(type_str).map_err(|_| {
+        IdlError::unknown_type_name(
+            &validate::pointer_push(pointer, type_key),
+            type_str,
+            format!(
+                "unsupported type '{}' for field '{}' in '{}'",
+                type_str, field_name, parent_name
+            ),
+        )
+    })?;
+
+    // Check if this field is an array
+    let is_array = raw
+        .array
+        .as_ref()
+        .and_then(Loose::valid)
+        .copied()
+        .unwrap_or(false);
+    let what = format!("field '{}' in '{}'", field_name, parent_name);
+    let string_semantics = parse_string_semantics(
+        &raw.string_semantics,
+        primitive,
+        is_array && raw.fixed_length.is_none(),
+        &what,
+        pointer,
+    )?;
+    if !is_array {
+        if raw.fixed_length.is_some() {
+            return Err(fixed_length_without_array(&what, pointer));
+        }
+        return Ok(StructFieldType::Primitive(primitive));
+    }
+
+    let (max_length, fixed) = parse_array_length(
+        &raw.max_length,
+        &raw.fixed_length,
+        &format!("array field '{}' in '{}'", field_name, parent_name),
+        pointer,
+        limits,
+    )?;
+
+    Ok(StructFieldType::Array(StructFieldArraySpec {
+        primitive,
+        max_length,
+        fixed,
+        string_semantics,
+    }))
+}
+
+/// Reads the `scale` and `offset` keys of a message or field whose type is
+/// `primitive` (`None` for structs). Both are optional; `scale` defaults to
+/// 1 and `offset` to 0, and only integer types can be scaled.
+fn parse_scaling(
+    scale: &Option<Loose<f64>>,
+    offset: &Option<Loose<f64>>,
+    primitive: Option<PrimitiveType>,
+    what: impl FnOnce() -> String,
+    pointer: &str,
+) -> ParseResult<Option<Scaling>> {
+    let scale_value = typed(scale, pointer, "scale", || {
+        "'scale' must be a number".to_string()
+    })?;
+    let offset_value = typed(offset, pointer, "offset", || {
+        "'offset' must be a number".to_string()
+    })?;
+    let key = match (scale_value, offset_value) {
+        (None, None) => return Ok(None),
+        (Some(_), _) => "scale",
+        (None, Some(_)) => "offset",
+    };
+    let scaling = Scaling {
+        scale: scale_value.copied().unwrap_or(1.0),
+        offset: offset_value.copied().unwrap_or(0.0),
+    };
+    check_scaling(scaling, key, primitive, &what(), pointer)?;
+    Ok(Some(scaling))
+}
+
+/// Checks that `scaling` applies to an integer type and has a non-zero
+/// scale. `key` names the offending key in errors about the type.
+pub(crate) fn check_scaling(
+    scaling: Scaling,
+    key: &str,
+    primitive: Option<PrimitiveType>,
+    what: &str,
+    pointer: &str,
+) -> ParseResult<()> {
+    if primitive.and_then(PrimitiveType::integer_range).is_none() {
+        let type_name = primitive.map_or("struct", PrimitiveType::c_type);
+        return Err(IdlError::invalid_value(
+            &validate::pointer_push(pointer, key),
+            type_name,
+            format!(
+                "'{}' needs an integer type, but {} is {}",
+                key, what, type_name
+            ),
+        ));
+    }
+    if scaling.scale == 0.0 {
+        return Err(IdlError::invalid_value(
+            &validate::pointer_push(pointer, "scale"),
+            0,
+            format!("'scale' of {} must not be zero", what),
+        ));
+    }
+    Ok(())
+}
+
+/// Reads the `min` and `max` keys of a message or field whose type is
+/// `primitive` (`None` for structs).
+fn parse_range(
+    min: &Option<Loose<Number>>,
+    max: &Option<Loose<Number>>,
+    primitive: Option<PrimitiveType>,
+    is_array: bool,
+    what: impl FnOnce() -> String,
+    pointer: &str,
+) -> ParseResult<Option<ValueRange>> {
+    let bound = |value: &Option<Loose<Number>>, key: &str| {
+        typed(value, pointer, key, || {
+            format!("'{}' must be a number", key)
+        })
+        .map(|number| {
+            number.map(|number| match (number.as_i64(), number.as_u64()) {
+                (Some(value), _) => Bound::Int(value.into()),
+                (None, Some(value)) => Bound::Int(value.into()),
+                (None, None) => Bound::Float(number.as_f64().unwrap_or(f64::NAN)),
+            })
+        })
+    };
+    let range = ValueRange {
+        min: bound(min, "min")?,
+        max: bound(max, "max")?,
+    };
+    if range == ValueRange::default() {
+        return Ok(None);
+    }
+    check_range(range, primitive, is_array, &what(), pointer).map(Some)
+}
+
+/// Largest finite IEEE 754 half precision value.
+pub(crate) const FLOAT16_MAX: f64 = 65504.0;
+
+/// Checks that `range` applies to a numeric scalar and that its bounds are
+/// ordered and representable in the type. Returns the range with bounds of
+/// floating point types converted to floats.
+pub(crate) fn check_range(
+    range: ValueRange,
+    primitive: Option<PrimitiveType>,
+    is_array: bool,
+    what: &str,
+    pointer: &str,
+) -> ParseResult<ValueRange> {
+    let key = if range.min.is_some() { "min" } else { "max" };
+    let key_pointer = validate::pointer_push(pointer, key);
+    let numeric = primitive.filter(|p| p.integer_range().is_some() || p.is_float());
+    let primitive = match numeric {
+        Some(primitive) if !is_array => primitive,
+        _ => {
+            let found = match primitive {
+                _ if is_array => "array",
+                Some(primitive) => primitive.c_type(),
+                None => "struct",
+            };
+            return Err(IdlError::invalid_value(
+                &key_pointer,
+                found,
+                format!(
+                    "'{}' needs a numeric scalar type, but {} is {}",
+                    key, what, found
+                ),
+            ));
+        }
+    };
+
+    let mut checked = range;
+    for (key, bound) in [("min", &mut checked.min), ("max", &mut checked.max)] {
+        let Some(value) = *bound else {
+            continue;
+        };
+        let key_pointer = validate::pointer_push(pointer, key);
+        match (primitive.integer_range(), value) {
+            (None, value)
+                if primitive == PrimitiveType::Float32
+                    && value.as_f64().abs() > f32::MAX.into() =>
+            {
+                return Err(IdlError::invalid_value(
+                    &key_pointer,
+                    value,
+                    format!(
+                        "'{}' {} of {} is outside the range of float ({:e} to {:e})",
+                        key,
+                        value,
+                        what,
+                        f32::MIN,
+                        f32::MAX
+                    ),
+                ));
+            }
+            (None, value)
+                if primitive == PrimitiveType::Float16 && value.as_f64().abs() > FLOAT16_MAX =>
+            {
+                return Err(IdlError::invalid_value(
+                    &key_pointer,
+                    value,
+                    format!(
+                        "'{}' {} of {} is outside the range of float16 ({} to {})",
+                        key, value, what, -FLOAT16_MAX, FLOAT16_MAX
+                    ),
+                ));
+            }
+            (None, value) => *bound = Some(Bound::Float(value.as_f64())),
+            (Some((low, high)), Bound::Int(value)) if value < low || value > high => {
+                return Err(IdlError::invalid_value(
+                    &key_pointer,
+                    value,
+                    format!(
+                        "'{}' {} of {} is outside the range of {} ({} to {})",
+                        key,
+                        value,
+                        what,
+                        primitive.wire_type(),
+                        low,
+                        high
+                    ),
+                ));
+            }
+            (Some(_), Bound::Int(_)) => {}
+            (Some(_), Bound::Float(value)) => {
+                return Err(IdlError::invalid_value(
+                    &key_pointer,
+                    value,
+                    format!(
+                        "'{}' of {} must be an integer, as {} is an integer type",
+                        key,
+                        what,
+                        primitive.c_type()
+                    ),
+                ));
+            }
+        }
+    }
+    if let (Some(min), Some(max)) = (checked.min, checked.max) {
+        let greater = match (min, max) {
+            (Bound::Int(min), Bound::Int(max)) => min > max,
+            _ => min.as_f64() > max.as_f64(),
+        };
+        if greater {
+            return Err(IdlError::invalid_value(
+                &validate::pointer_push(pointer, "min"),
+                min.to_string(),
+                format!("'min' {} of {} is greater than 'max' {}", min, what, max),
+            ));
+        }
+    }
+    Ok(checked)
+}
+
+/// Converts the endianness key returned by `RawMessage::endian` or
+/// `RawField::endian`.
+fn parse_endian(
+    endian: Option<(&'static str, &Loose<String>)>,
+    pointer: &str,
+) -> ParseResult<Option<Endian>> {
+    let Some((key, value)) = endian else {
+        return Ok(None);
+    };
+    let key_pointer = validate::pointer_push(pointer, key);
+    match value {
+        Loose::Valid(text) => Endian::from_str(text)
+            .map(Some)
+            .map_err(|e| IdlError::invalid_value(&key_pointer, text, e.to_string())),
+        Loose::Invalid(found) => Err(IdlError::invalid_type(
+            &key_pointer,
+            found,
+            format!("'{}' must be a string", key),
+        )),
+    }
+}
+
+/// Concatenates the helper templates `files` for `language`, with their
+/// placeholders substituted from `context` (see [`template`]).
+///
+/// Templates are embedded in the binary. With `template_dir`, a file of the
+/// same name in that directory replaces the embedded one; files it lacks
+/// keep the embedded content.
+pub(crate) fn load_templates(
+    language: TargetLanguage,
+    files: &[&str],
+    template_dir: Option<&Path>,
+    context: &template::TemplateContext,
+) -> std::result::Result<String, IdlError> {
+    let mut combined = String::new();
+
+    for file_name in files {
+        let override_content = template_dir
+            .map(|dir| template_override(language, dir, file_name))
+            .transpose()?
+            .flatten();
+        let content = match &override_content {
+            Some(content) => content,
+            None => embedded_template(language, file_name)?,
+        };
+        let content = template::render(file_name, content, context)?;
+        combined.push_str(&content);
+        if !content.ends_with('\n') {
+            combined.push('\n');
+        }
+        combined.push('\n');
+    }
+
+    Ok(combined)
+}
+
+/// Reads an optional template with no embedded default, such as
+/// `prologue.h`. Returns `None` without an override directory or when the
+/// directory lacks the file.
+pub(crate) fn load_optional_template(
+    language: TargetLanguage,
+    file_name: &str,
+    template_dir: Option<&Path>,
+    context: &template::TemplateContext,
+) -> std::result::Result<Option<String>, IdlError> {
+    let Some(dir) = template_dir else {
+        return Ok(None);
+    };
+    match template_override(language, dir, file_name)? {
+        Some(content) => template::render(file_name, &content, context).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Override templates by language, directory and file name, with `None`
+/// for a file the directory lacks. A process generating code for many
+/// protocols thus reads each template directory once.
+type TemplateCache = HashMap<(TargetLanguage, PathBuf, String), Option<Arc<str>>>;
+
+static TEMPLATE_CACHE: OnceLock<Mutex<TemplateCache>> = OnceLock::new();
+
+/// The content of `file_name` in the template directory `dir`, or `None`
+/// when the directory has no such file. Files are read once and then
+/// served from memory until [`clear_template_cache`]; a directory that
+/// does not exist is an error unless all its files are cached.
+pub(crate) fn template_override(
+    language: TargetLanguage,
+    dir: &Path,
+    file_name: &str,
+) -> std::result::Result<Option<Arc<str>>, IdlError> {
+    let cache = TEMPLATE_CACHE.get_or_init(Mutex::default);
+    let key = (language, dir.to_path_buf(), file_name.to_string());
+    if let Some(content) = cache
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(&key)
+    {
+        return Ok(content.clone());
+    }
+
+    let path = resolve_template_dir(language, dir)?.join(file_name);
+    let content = if path.is_file() {
+        let content = fs::read_to_string(&path).map_err(|source| IdlError::Io {
+            path: path.clone(),
+            source,
+        })?;
+        Some(Arc::from(content))
+    } else {
+        None
+    };
+    cache
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(key, content.clone());
+    Ok(content)
+}
+
+/// Forgets the template files read so far, so the next generation reads
+/// its template directory again, e.g. after the templates were edited.
+pub fn clear_template_cache() {
+    if let Some(cache) = TEMPLATE_CACHE.get() {
+        cache.lock().unwrap_or_else(PoisonError::into_inner).clear();
+    }
+}
+
+/// Describes where the templates `files` come from, for the CLI to print:
+/// the override directory and the files taken from it, or the embedded set.
+pub(crate) fn describe_templates(
+    language: TargetLanguage,
+    files: &[&str],
+    template_dir: Option<&Path>,
+) -> std::result::Result<String, IdlError> {
+    let Some(dir) = template_dir else {
+        return Ok("embedded templates".to_string());
+    };
+    let dir = resolve_template_dir(language, dir)?;
+    let found: Vec<&str> = files
+        .iter()
+        .copied()
+        .filter(|file_name| dir.join(file_name).is_file())
+        .collect();
+    Ok(if found.is_empty() {
+        format!(
+            "no templates found in {}, using embedded templates",
+            dir.display()
+        )
+    } else {
+        format!(
+            "{} ({}), embedded templates for the rest",
+            dir.display(),
+            found.join(", ")
+        )
+    })
+}
+
+fn embedded_template(
+    language: TargetLanguage,
+    file_name: &str,
+) -> std::result::Result<&'static str, IdlError> {
+    language
+        .embedded_templates()
+        .iter()
+        .find(|(name, _)| *name == file_name)
+        .map(|(_, content)| *content)
+        .ok_or_else(|| IdlError::Io {
+            path: PathBuf::from("msg_template")
+                .join(language.template_subdir())
+                .join(file_name),
+            source: io::Error::new(io::ErrorKind::NotFound, "no embedded template"),
+        })
+}
+
+/// Checks that an explicit `--template-dir` override exists.
+fn resolve_template_dir(
+    language: TargetLanguage,
+    dir: &Path,
+) -> std::result::Result<&Path, IdlError> {
+    if dir.is_dir() {
+        Ok(dir)
+    } else {
+        Err(IdlError::TemplateNotFound {
+            dir: dir.display().to_string(),
+            language: language.display_name().to_string(),
+        })
+    }
+}
+
+fn resolve_default_path(primary: &str, fallback: &str) -> PathBuf {
+    let primary_path = PathBuf::from(primary);
+    if primary_path.exists() {
+        primary_path
+    } else {
+        PathBuf::from(fallback)
+    }
+}
+
+pub(crate) fn to_snake_case(name: &str) -> String {
+    let mut result = String::new();
+    let mut last_was_underscore = false;
+    for ch in name.chars() {
+        if ch.is_ascii_alphanumeric() {
+            let lower = ch.to_ascii_lowercase();
+            if result.is_empty() && lower.is_ascii_digit() {
+                result.push('_');
+            }
+            result.push(lower);
+            last_was_underscore = false;
+        } else if !last_was_underscore {
+            result.push('_');
+            last_was_underscore = true;
+        }
+    }
+    if result.ends_with('_') {
+        result.pop();
+    }
+    if result.is_empty() {
+        result.push_str("msg");
+    }
+    result
+}
+
+pub(crate) fn to_macro_ident(name: &str) -> String {
+    let mut result = String::new();
+    let mut last_was_underscore = false;
+    for ch in name.chars() {
+        if ch.is_ascii_alphanumeric() {
+            let upper = ch.to_ascii_uppercase();
+            if result.is_empty() && upper.is_ascii_digit() {
+                result.push('_');
+            }
+            result.push(upper);
+            last_was_underscore = false;
+        } else if !last_was_underscore {
+            result.push('_');
+            last_was_underscore = true;
+        }
+    }
+    if result.ends_with('_') {
+        result.pop();
+    }
+    if result.is_empty() {
+        result.push_str("MSG");
+    }
+    result
+}
+
+#[allow(dead_code)]
+pub(crate) fn to_pascal_case(name: &str) -> String {
+    let mut result = String::new();
+    let mut capitalize = true;
+    for ch in name.chars() {
+        if ch.is_ascii_alphanumeric() {
+            if result.is_empty() && ch.is_ascii_digit() {
+                result.push('M');
+            }
+            if capitalize {
+                result.push(ch.to_ascii_uppercase());
+            } else {
+                result.push(ch.to_ascii_lowercase());
+            }
+            capitalize = false;
+        } else {
+            capitalize = true;
+        }
+    }
+    if result.is_empty() {
+        result.push_str("Msg");
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_to_snake_case() {
+        // Note: to_snake_case converts to lowercase but doesn't detect camelCase boundaries
+        assert_eq!(to_snake_case("HelloWorld"), "helloworld");
+        assert_eq!(to_snake_case("get_temperatures"), "get_temperatures");
+        assert_eq!(to_snake_case("LED Control"), "led_control");
+        assert_eq!(to_snake_case("CO2Level"), "co2level");
+        assert_eq!(to_snake_case("firmware_version"), "firmware_version");
+        assert_eq!(to_snake_case("123test"), "_123test");
+        assert_eq!(to_snake_case(""), "msg");
+    }
+
+    #[test]
+    fn test_to_macro_ident() {
+        // Note: to_macro_ident converts to uppercase but doesn't detect camelCase boundaries
+        assert_eq!(to_macro_ident("HelloWorld"), "HELLOWORLD");
+        assert_eq!(to_macro_ident("get_temperatures"), "GET_TEMPERATURES");
+        assert_eq!(to_macro_ident("LED Control"), "LED_CONTROL");
+        assert_eq!(to_macro_ident("CO2Level"), "CO2LEVEL");
+        assert_eq!(to_macro_ident("firmware_version"), "FIRMWARE_VERSION");
+        assert_eq!(to_macro_ident("123test"), "_123TEST");
+        assert_eq!(to_macro_ident(""), "MSG");
+    }
+
+    #[test]
+    fn test_to_pascal_case() {
+        assert_eq!(to_pascal_case("hello_world"), "HelloWorld");
+        assert_eq!(to_pascal_case("get_temperatures"), "GetTemperatures");
+        assert_eq!(to_pascal_case("LED Control"), "LedControl");
+        assert_eq!(to_pascal_case("CO2Level"), "Co2level");
+        assert_eq!(to_pascal_case("firmware_version"), "FirmwareVersion");
+        assert_eq!(to_pascal_case("123test"), "M123test");
+        assert_eq!(to_pascal_case(""), "Msg");
+    }
+
+    #[test]
+    fn test_primitive_type_from_str() {
+        assert_eq!(
+            PrimitiveType::from_str("char").unwrap(),
+            PrimitiveType::Char
+        );
+        assert_eq!(
+            PrimitiveType::from_str("uint8").unwrap(),
+            PrimitiveType::Uint8
+        );
+        assert_eq!(
+            PrimitiveType::from_str("int16").unwrap(),
+            PrimitiveType::Int16
+        );
+        assert_eq!(
+            PrimitiveType::from_str("float32").unwrap(),
+            PrimitiveType::Float32
+        );
+        assert_eq!(
+            PrimitiveType::from_str("f64").unwrap(),
+            PrimitiveType::Float64
+        );
+        assert!(PrimitiveType::from_str("invalid").is_err());
+    }
+
+    #[test]
+    fn test_primitive_type_c_type() {
+        assert_eq!(PrimitiveType::Char.c_type(), "char");
+        assert_eq!(PrimitiveType::Uint8.c_type(), "uint8_t");
+        assert_eq!(PrimitiveType::Int16.c_type(), "int16_t");
+        assert_eq!(PrimitiveType::Float32.c_type(), "float");
+        assert_eq!(PrimitiveType::Float64.c_type(), "double");
+    }
+
+    #[test]
+    fn test_primitive_type_byte_len() {
+        assert_eq!(PrimitiveType::Char.byte_len(), 1);
+        assert_eq!(PrimitiveType::Uint8.byte_len(), 1);
+        assert_eq!(PrimitiveType::Int16.byte_len(), 2);
+        assert_eq!(PrimitiveType::Uint32.byte_len(), 4);
+        assert_eq!(PrimitiveType::Float32.byte_len(), 4);
+        assert_eq!(PrimitiveType::Float64.byte_len(), 8);
+    }
+
+    #[test]
+    fn test_endian_from_str() {
+        assert_eq!(Endian::from_str("little").unwrap(), Endian::Little);
+        assert_eq!(Endian::from_str("big").unwrap(), Endian::Big);
+        assert_eq!(Endian::from_str("le").unwrap(), Endian::Little);
+        assert_eq!(Endian::from_str("be").unwrap(), Endian::Big);
+        assert!(Endian::from_str("invalid").is_err());
+    }
+
+    #[test]
+    fn test_endian_suffix() {
+        assert_eq!(Endian::Little.suffix(), "le");
+        assert_eq!(Endian::Big.suffix(), "be");
+    }
+
+    #[test]
+    fn test_target_language_parse() {
+        assert_eq!(TargetLanguage::try_from_str("c"), Some(TargetLanguage::C));
+        assert_eq!(TargetLanguage::try_from_str("C99"), Some(TargetLanguage::C));
+        assert_eq!(TargetLanguage::try_from_str("python"), None);
+    }
+
+    #[test]
+    fn test_source_comment_label() {
+        let input = Path::new("/work/proto/msgs/protocol.json");
+        assert_eq!(
+            SourceComment::Path.label(input).as_deref(),
+            Some("/work/proto/msgs/protocol.json")
+        );
+        assert_eq!(
+            SourceComment::Basename.label(input).as_deref(),
+            Some("protocol.json")
+        );
+        assert_eq!(SourceComment::None.label(input), None);
+        assert_eq!(
+            SourceComment::Basename
+                .label(Path::new(STDIN_LABEL))
+                .as_deref(),
+            Some(STDIN_LABEL)
+        );
+        assert_eq!(SourceComment::None.label(Path::new(STDIN_LABEL)), None);
+
+        assert_eq!(
+            relative_path(input, Path::new("/work/proto")),
+            Path::new("msgs/protocol.json")
+        );
+        assert_eq!(
+            relative_path(input, Path::new("/work/build/out")),
+            Path::new("../../proto/msgs/protocol.json")
+        );
+        assert_eq!(
+            relative_path(Path::new("./msgs/../protocol.json"), Path::new("/work")),
+            Path::new("protocol.json")
+        );
+
+        use clap::ValueEnum;
+        assert_eq!(
+            SourceComment::from_str("BaseName", true).unwrap(),
+            SourceComment::Basename
+        );
+        assert!(SourceComment::from_str("full", true).is_err());
+    }
+
+    #[test]
+    fn test_parse_scalar_message() {
+        let json = json!({
+            "version": "1.0.0",
+            "packets": {
+                "ping": {
+                    "packet_id": 0,
+                    "msg_type": "uint8",
+                    "array": false,
+                    "msg_desc": "Ping command"
+                }
+            }
+        });
+
+        let obj = json.as_object().unwrap();
+        let (metadata, messages) = parse_messages(obj).unwrap();
+
+        assert_eq!(metadata.version, Some("1.0.0".to_string()));
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].name, "ping");
+        assert_eq!(messages[0].packet_id, 0);
+        assert_eq!(messages[0].description, Some("Ping command".to_string()));
+
+        match &messages[0].body {
+            MessageBody::Scalar(spec) => {
+                assert_eq!(spec.primitive, PrimitiveType::Uint8);
+                assert_eq!(spec.endian, Endian::Little);
+            }
+            _ => panic!("Expected scalar message"),
+        }
+    }
+
+    #[test]
+    fn test_parse_array_message() {
+        let json = json!({
+            "packets": {
+                "temperatures": {
+                    "packet_id": 20,
+                    "msg_type": "float32",
+                    "array": true,
+                    "endianess": "big",
+                    "max_length": 8,
+                    "msg_desc": "Temperature array"
+                }
+            }
+        });
+
+        let obj = json.as_object().unwrap();
+        let (_, messages) = parse_messages(obj).unwrap();
+
+        assert_eq!(messages.len(), 1);
+        match &messages[0].body {
+            MessageBody::Array(spec) => {
+                assert_eq!(spec.primitive, PrimitiveType::Float32);
+                assert_eq!(spec.endian, Endian::Big);
+                assert_eq!(spec.max_length, 8);
+            }
+            _ => panic!("Expected array message"),
+        }
+    }
+
+    #[test]
+    fn test_parse_struct_message() {
+        let json = json!({
+            "packets": {
+                "sensor_data": {
+                    "packet_id": 30,
+                    "msg_type": "struct",
+                    "fields": {
+                        "temperature": {
+                            "type": "float32",
+                            "endianess": "big"
+                        },
+                        "humidity": {
+                            "type": "uint8"
+                        }
+                    },
+                    "msg_desc": "Sensor readings"
+                }
+            }
+        });
+
+        let obj = json.as_object().unwrap();
+        let (_, messages) = parse_messages(obj).unwrap();
+
+        assert_eq!(messages.len(), 1);
+        match &messages[0].body {
+            MessageBody::Struct(spec) => {
+                assert_eq!(spec.fields.len(), 2);
+                // Note: JSON object field order is not guaranteed, so check both fields exist
+                let temp_field = spec.fields.iter().find(|f| f.name == "temperature");
+                let hum_field = spec.fields.iter().find(|f| f.name == "humidity");
+
+                assert!(temp_field.is_some(), "temperature field should exist");
+                let temp_field = temp_field.unwrap();
+                match &temp_field.field_type {
+                    StructFieldType::Primitive(prim) => {
+                        assert_eq!(*prim, PrimitiveType::Float32);
+                    }
+                    _ => panic!("Expected primitive field"),
+                }
+                assert_eq!(temp_field.endian, Endian::Big);
+
+                assert!(hum_field.is_some(), "humidity field should exist");
+                let hum_field = hum_field.unwrap();
+                match &hum_field.field_type {
+                    StructFieldType::Primitive(prim) => {
+                        assert_eq!(*prim, PrimitiveType::Uint8);
+                    }
+                    _ => panic!("Expected primitive field"),
+                }
+            }
+            _ => panic!("Expected struct message"),
+        }
+    }
+
+    #[test]
+    fn test_parse_messages_sorted_by_packet_id() {
+        let json = json!({
+            "version": "1.0.0",
+            "max_address": 255,
+            "packets": {
+                "msg_c": {
+                    "packet_id": 30,
+                    "msg_type": "uint8",
+                    "array": false
+                },
+                "msg_a": {
+                    "packet_id": 10,
+                    "msg_type": "uint8",
+                    "array": false
+                },
+                "msg_b": {
+                    "packet_id": 20,
+                    "msg_type": "uint8",
+                    "array": false
+                }
+            }
+        });
+
+        let obj = json.as_object().unwrap();
+        let (metadata, mut messages) = parse_messages(obj).unwrap();
+
+        assert_eq!(metadata.version, Some("1.0.0".to_string()));
+        assert_eq!(metadata.max_address, Some(255));
+        assert_eq!(messages.len(), 3);
+
+        messages.sort_by_key(|m| m.packet_id);
+        assert_eq!(messages[0].name, "msg_a");
+        assert_eq!(messages[0].packet_id, 10);
+        assert_eq!(messages[1].name, "msg_b");
+        assert_eq!(messages[1].packet_id, 20);
+        assert_eq!(messages[2].name, "msg_c");
+        assert_eq!(messages[2].packet_id, 30);
+    }
+
+    #[test]
+    fn test_array_without_max_length_fails() {
+        let json = json!({
+            "packets": {
+                "temperatures": {
+                    "packet_id": 20,
+                    "msg_type": "float32",
+                    "array": true
+                }
+            }
+        });
+
+        let obj = json.as_object().unwrap();
+        let result = parse_messages(obj);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_response_names_another_message() {
+        let (_, messages) = parse_str(
+            r#"{ "get": { "packet_id": 1, "msg_type": "u8", "response": "reply" },
+                 "reply": { "packet_id": 2, "msg_type": "u8" } }"#,
+        )
+        .unwrap();
+        assert_eq!(messages[0].response.as_deref(), Some("reply"));
+        assert_eq!(messages[1].response, None);
+
+        let err = parse_str(
+            r#"{ "get": { "packet_id": 1, "msg_type": "u8", "response": "missing" },
+                 "loop": { "packet_id": 2, "msg_type": "u8", "response": "loop" },
+                 "bad": { "packet_id": 3, "msg_type": "u8", "response": 4 } }"#,
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(
+            err.contains(
+                "/get/response: message 'get' has response 'missing', which is not a message"
+            ),
+            "{}",
+            err
+        );
+        assert!(err.contains("/loop/response: message 'loop' cannot be its own response"));
+        assert!(err.contains("message 'bad' has invalid 'response' (must be a message name)"));
+    }
+
+    #[test]
+    fn test_sector_bytes_must_hold_whole_elements_within_the_payload() {
+        let sectored = |sector_bytes: u64| {
+            parse_messages(
+                json!({
+                    "max_payload_bytes": 64,
+                    "packets": { "image": {
+                        "packet_id": 1, "msg_type": "uint32", "array": true,
+                        "max_length": 16, "sector_bytes": sector_bytes
+                    } }
+                })
+                .as_object()
+                .unwrap(),
+            )
+        };
+        assert!(sectored(32).is_ok());
+        let err = sectored(6).unwrap_err().to_string();
+        assert!(err.contains("multiple of its 4-byte elements"), "{}", err);
+        let err = sectored(128).unwrap_err().to_string();
+        assert!(
+            err.contains("exceeds protocol limit of 64 bytes"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_sector_bytes_rejections() {
+        let json = json!({ "packets": {
+            "zero": { "packet_id": 1, "msg_type": "u16", "array": true, "max_length": 8, "sector_bytes": 0 },
+            "small": { "packet_id": 2, "msg_type": "u32", "array": true, "max_length": 8, "sector_bytes": 2 },
+            "odd": { "packet_id": 3, "msg_type": "u16", "array": true, "max_length": 8, "sector_bytes": 5 },
+            "whole": { "packet_id": 4, "msg_type": "u16", "array": true, "max_length": 8, "sector_bytes": 18 },
+            "text": { "packet_id": 5, "msg_type": "u8", "array": true, "max_length": 8, "sector_bytes": "four" },
+            "scalar": { "packet_id": 6, "msg_type": "u16", "sector_bytes": 2 },
+            "pose": { "packet_id": 7, "msg_type": "struct", "sector_bytes": 2, "fields": {
+                "x": { "type": "u16" } } },
+            "ok": { "packet_id": 8, "msg_type": "u16", "array": true, "max_length": 8, "sector_bytes": 16 }
+        } });
+        let messages: Vec<String> =
+            parse_messages_with_diagnostics(json.as_object().unwrap(), &ParseOptions::default())
+                .unwrap_err()
+                .iter()
+                .map(|e| e.to_string())
+                .collect();
+        assert_eq!(
+            messages,
+            [
+                "/packets/zero/sector_bytes: array message 'zero' has sector_bytes 0, which must be a positive multiple of its 2-byte elements",
+                "/packets/small/sector_bytes: array message 'small' has sector_bytes 2, which must be a positive multiple of its 4-byte elements",
+                "/packets/odd/sector_bytes: array message 'odd' has sector_bytes 5, which must be a positive multiple of its 2-byte elements",
+                "/packets/whole/sector_bytes: array message 'whole' has sector_bytes 18, more than the 16 bytes of its 8 2-byte elements",
+                "/packets/text/sector_bytes: 'sector_bytes' \"four\" is not an integer literal (expected decimal digits, or 0x hex or 0b binary digits)",
+                "/packets/scalar/sector_bytes: 'sector_bytes' only applies to array messages, and message 'scalar' is not one",
+                "/packets/pose/sector_bytes: 'sector_bytes' only applies to array messages, and message 'pose' is not one",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_struct_without_fields_fails() {
+        let json = json!({
+            "packets": {
+                "sensor_data": {
+                    "packet_id": 30,
+                    "msg_type": "struct"
+                }
+            }
+        });
+
+        let obj = json.as_object().unwrap();
+        let result = parse_messages(obj);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_struct_with_empty_fields_fails() {
+        let json = json!({
+            "packets": {
+                "sensor_data": {
+                    "packet_id": 30,
+                    "msg_type": "struct",
+                    "fields": {}
+                }
+            }
+        });
+
+        let obj = json.as_object().unwrap();
+        let result = parse_messages(obj);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_devices() {
+        let json = json!({
+            "version": "1.0.0",
+            "devices": {
+                "device A": {
+                    "role": "server"
+                },
+                "device B": {
+                    "role": "client",
+                    "id": 1
+                }
+            },
+            "packets": {
+                "ping": {
+                    "packet_id": 0,
+                    "msg_type": "uint8",
+                    "array": false
+                }
+            }
+        });
+
+        let obj = json.as_object().unwrap();
+        let (metadata, messages) = parse_messages(obj).unwrap();
+
+        assert_eq!(metadata.version, Some("1.0.0".to_string()));
+        assert_eq!(metadata.devices.len(), 2);
+        assert_eq!(messages.len(), 1);
+
+        let server = metadata.devices.iter().find(|d| d.role == "server");
+        assert!(server.is_some());
+        assert_eq!(server.unwrap().name, "device A");
+        assert_eq!(server.unwrap().id, None);
+
+        let client = metadata.devices.iter().find(|d| d.role == "client");
+        assert!(client.is_some());
+        assert_eq!(client.unwrap().name, "device B");
+        assert_eq!(client.unwrap().id, Some(1));
+    }
+
     #[test]
-    fn test_to_snake_case() {
-        // Note: to_snake_case converts to lowercase but doesn't detect camelCase boundaries
-        assert_eq!(to_snake_case("HelloWorld"), "helloworld");
-        assert_eq!(to_snake_case("get_temperatures"), "get_temperatures");
-        assert_eq!(to_snake_case("LED Control"), "led_control");
-        assert_eq!(to_snake_case("CO2Level"), "co2level");
-        assert_eq!(to_snake_case("firmware_version"), "firmware_version");
-        assert_eq!(to_snake_case("123test"), "_123test");
-        assert_eq!(to_snake_case(""), "msg");
+    fn test_missing_packets_fails() {
+        let json = json!({
+            "version": "1.0.0"
+        });
+
+        let obj = json.as_object().unwrap();
+        let result = parse_messages(obj);
+        assert!(result.is_err());
     }
 
     #[test]
-    fn test_to_macro_ident() {
-        // Note: to_macro_ident converts to uppercase but doesn't detect camelCase boundaries
-        assert_eq!(to_macro_ident("HelloWorld"), "HELLOWORLD");
-        assert_eq!(to_macro_ident("get_temperatures"), "GET_TEMPERATURES");
-        assert_eq!(to_macro_ident("LED Control"), "LED_CONTROL");
-        assert_eq!(to_macro_ident("CO2Level"), "CO2LEVEL");
-        assert_eq!(to_macro_ident("firmware_version"), "FIRMWARE_VERSION");
-        assert_eq!(to_macro_ident("123test"), "_123TEST");
-        assert_eq!(to_macro_ident(""), "MSG");
+    fn test_messages_wrapper_and_flat_layout() {
+        let wrapped = json!({
+            "version": "1.0.0",
+            "messages": {
+                "ping": { "packet_id": 0, "msg_type": "uint8" }
+            }
+        });
+        let (_, messages) = parse_messages(wrapped.as_object().unwrap()).unwrap();
+        assert_eq!(messages[0].name, "ping");
+
+        let flat = json!({
+            "version": "1.0.0",
+            "max_address": 16,
+            "ping": { "packet_id": 0, "msg_type": "uint8" },
+            "status": { "packet_id": 1, "msg_type": "uint16" }
+        });
+        let (metadata, messages) = parse_messages(flat.as_object().unwrap()).unwrap();
+        assert_eq!(metadata.max_address, Some(16));
+        assert_eq!(messages.len(), 2);
     }
 
     #[test]
-    fn test_to_pascal_case() {
-        assert_eq!(to_pascal_case("hello_world"), "HelloWorld");
-        assert_eq!(to_pascal_case("get_temperatures"), "GetTemperatures");
-        assert_eq!(to_pascal_case("LED Control"), "LedControl");
-        assert_eq!(to_pascal_case("CO2Level"), "Co2level");
-        assert_eq!(to_pascal_case("firmware_version"), "FirmwareVersion");
-        assert_eq!(to_pascal_case("123test"), "M123test");
-        assert_eq!(to_pascal_case(""), "Msg");
+    fn test_stray_top_level_keys_rejected() {
+        let stray_scalar = json!({
+            "verison": "1.0.0",
+            "packets": { "ping": { "packet_id": 0, "msg_type": "uint8" } }
+        });
+        let err = parse_messages(stray_scalar.as_object().unwrap()).unwrap_err();
+        assert!(err.to_string().contains("unknown top-level key 'verison'"));
+
+        let stray_object = json!({
+            "packets": { "ping": { "packet_id": 0, "msg_type": "uint8" } },
+            "pong": { "packet_id": 1, "msg_type": "uint8" }
+        });
+        let err = parse_messages(stray_object.as_object().unwrap()).unwrap_err();
+        assert!(err.to_string().contains("belong inside 'packets'"));
+
+        let both = json!({ "packets": {}, "messages": {} });
+        assert!(parse_messages(both.as_object().unwrap()).is_err());
     }
 
     #[test]
-    fn test_primitive_type_from_str() {
+    fn test_all_errors_collected_with_paths() {
+        let json = json!({
+            "packets": {
+                "ping": { "packet_id": 0, "msg_type": "uint8" },
+                "bad_id": { "packet_id": 256, "msg_type": "uint8" },
+                "sensor_data": {
+                    "packet_id": 2,
+                    "msg_type": "struct",
+                    "request_type": "push",
+                    "fields": {
+                        "temp": { "type": "float33" },
+                        "pos": {
+                            "type": "struct",
+                            "fields": { "x": { "type": "int16", "array": true } }
+                        }
+                    }
+                },
+                "blob": { "packet_id": 3, "msg_type": "uint8", "array": true, "max_length": 0 }
+            }
+        });
+        let errors =
+            parse_messages_with_diagnostics(json.as_object().unwrap(), &ParseOptions::default())
+                .unwrap_err();
+        let paths: Vec<&str> = errors.iter().filter_map(|e| e.pointer()).collect();
         assert_eq!(
-            PrimitiveType::from_str("char").unwrap(),
-            PrimitiveType::Char
+            paths,
+            vec![
+                "/packets/bad_id/packet_id",
+                "/packets/sensor_data/request_type",
+                "/packets/sensor_data/fields/temp/type",
+                "/packets/sensor_data/fields/pos/fields/x",
+                "/packets/blob/max_length",
+            ]
+        );
+        assert!(errors[3].message().contains("requires 'max_length'"));
+
+        let combined = parse_messages(json.as_object().unwrap())
+            .unwrap_err()
+            .to_string();
+        assert!(combined.starts_with("5 errors in message definitions:\n"));
+        assert!(combined.contains("\n  /packets/blob/max_length: array message 'blob'"));
+    }
+
+    #[test]
+    fn test_error_variant_for_each_failure_mode() {
+        fn first_error(json: Value) -> IdlError {
+            let errors = parse_messages_with_diagnostics(
+                json.as_object().unwrap(),
+                &ParseOptions::default(),
+            )
+            .unwrap_err();
+            errors.into_iter().next().unwrap()
+        }
+
+        let err = first_error(json!({ "packets": { "a": { "msg_type": "uint8" } } }));
+        assert!(
+            matches!(&err, IdlError::MissingField { path, field, .. } if path == "/packets/a" && field == "packet_id")
+        );
+
+        let err =
+            first_error(json!({ "packets": { "a": { "packet_id": true, "msg_type": "uint8" } } }));
+        assert!(
+            matches!(&err, IdlError::InvalidType { path, found, .. } if path == "/packets/a/packet_id" && found == "true")
         );
+
+        let err =
+            first_error(json!({ "packets": { "a": { "packet_id": 1, "msg_type": "uint9" } } }));
+        assert!(matches!(&err, IdlError::InvalidType { found, .. } if found == "uint9"));
+
+        let err =
+            first_error(json!({ "packets": { "a": { "packet_id": 256, "msg_type": "uint8" } } }));
+        assert!(matches!(
+            &err,
+            IdlError::LimitExceeded {
+                value: 256,
+                max: 255,
+                ..
+            }
+        ));
+
+        let err = first_error(json!({
+            "packets": { "a": { "packet_id": 1, "msg_type": "uint16", "array": true, "max_length": 200 } }
+        }));
+        assert!(matches!(
+            &err,
+            IdlError::LimitExceeded { path, value: 400, max: 251, .. } if path == "/packets/a/max_length"
+        ));
+
+        let err = first_error(json!({
+            "packets": { "a": { "packet_id": 1, "msg_type": "uint8", "endianess": "middle" } }
+        }));
+        assert!(matches!(&err, IdlError::InvalidValue { found, .. } if found == "middle"));
+
+        let err = first_error(json!({ "packets": {}, "extra": 1 }));
+        assert!(matches!(&err, IdlError::UnknownKey { key, .. } if key == "extra"));
+
+        let err = first_error(json!({ "packets": {}, "messages": {} }));
+        assert!(matches!(&err, IdlError::ConflictingKeys { keys, .. } if keys.len() == 2));
+
+        let err = first_error(json!({ "version": "1.0.0" }));
+        assert!(matches!(&err, IdlError::MissingField { field, .. } if field == "packets"));
+
+        // parse_messages wraps several errors and keeps a single one as is
+        let multiple = parse_messages(
+            json!({ "packets": { "a": { "packet_id": 1 }, "b": { "packet_id": 2 } } })
+                .as_object()
+                .unwrap(),
+        )
+        .unwrap_err();
+        assert!(matches!(&multiple, IdlError::Multiple(errors) if errors.len() == 2));
+    }
+
+    #[test]
+    fn test_duplicate_packet_ids_and_colliding_names_rejected() {
+        let json = json!({
+            "packets": {
+                "motor_speed": { "packet_id": 1, "msg_type": "uint8" },
+                "motor-speed": { "packet_id": 2, "msg_type": "uint8" },
+                "status": { "packet_id": 1, "msg_type": "uint8" }
+            }
+        });
+        let errors =
+            parse_messages_with_diagnostics(json.as_object().unwrap(), &ParseOptions::default())
+                .unwrap_err();
+        let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
         assert_eq!(
-            PrimitiveType::from_str("uint8").unwrap(),
-            PrimitiveType::Uint8
+            messages,
+            vec![
+                "/packets/motor-speed: message 'motor-speed' maps to the same C identifier 'motor_speed' as message 'motor_speed'",
+                "/packets/status/packet_id: message 'status' has packet_id 1 which is already used by message 'motor_speed'",
+            ]
         );
+    }
+
+    #[test]
+    fn test_fail_fast_reports_first_error_only() {
+        let json = json!({
+            "packets": {
+                "a": { "packet_id": 0 },
+                "b": { "packet_id": 1, "msg_type": "bogus" }
+            }
+        });
+        let options = ParseOptions {
+            fail_fast: true,
+            ..Default::default()
+        };
+        let errors =
+            parse_messages_with_diagnostics(json.as_object().unwrap(), &options).unwrap_err();
+        assert_eq!(errors.len(), 1);
         assert_eq!(
-            PrimitiveType::from_str("int16").unwrap(),
-            PrimitiveType::Int16
+            errors[0].to_string(),
+            "/packets/a: message 'a' is missing required field 'msg_type' (e.g., 'uint8', 'float32', 'struct')"
+        );
+
+        let both = json!({ "packets": {}, "messages": {} });
+        let errors =
+            parse_messages_with_diagnostics(both.as_object().unwrap(), &options).unwrap_err();
+        assert_eq!(errors[0].pointer(), Some(""));
+    }
+
+    #[test]
+    fn test_toml_input_with_field_arrays() {
+        let toml = ParseOptions {
+            format: InputFormat::Toml,
+            ..Default::default()
+        };
+        let (_, messages) = parse_str_with_options(
+            r#"
+[packets.pose]
+packet_id = 3
+msg_type = "struct"
+
+[[packets.pose.fields]]
+name = "y"
+type = "int16"
+
+[[packets.pose.fields]]
+name = "x"
+type = "int16"
+endianness = "big"
+
+[packets.ping]
+packet_id = 1
+msg_type = "uint8"
+"#,
+            &toml,
+        )
+        .unwrap();
+        assert_eq!(messages[0].name, "ping");
+        let MessageBody::Struct(spec) = &messages[1].body else {
+            panic!("expected a struct");
+        };
+        // Declaration order is the wire order
+        let names: Vec<&str> = spec.fields.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["y", "x"]);
+        assert_eq!(spec.fields[1].endian, Endian::Big);
+
+        let err = parse_str_with_options(
+            "[packets.a]\npacket_id = 1\nmsg_type = \"struct\"\n[[packets.a.fields]]\ntype = \"u8\"\n",
+            &toml,
+        )
+        .unwrap_err();
+        assert_eq!(err.pointer(), Some("/packets/a/fields/0"));
+        let err = parse_str_with_options(
+            "[packets.a]\npacket_id = 1\nmsg_type = \"struct\"\nfields = [{ name = \"v\", type = \"u8\" }, { name = \"v\", type = \"u8\" }]\n",
+            &toml,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "/packets/a/fields/1/name: duplicate name 'v' in 'fields'"
         );
+        let err = parse_str_with_options("[packets]\nping = \n", &toml).unwrap_err();
+        assert!(matches!(err, IdlError::Syntax { line: 2, .. }), "{}", err);
+        // Deep nesting is a syntax error, not a stack overflow
+        let depth = 200_000;
+        for deep in [
+            format!("x = {}1{}\n", "{a = ".repeat(depth), "}".repeat(depth)),
+            format!("x = {}1{}\n", "[".repeat(depth), "]".repeat(depth)),
+        ] {
+            let err = parse_str_with_options(&deep, &toml).unwrap_err();
+            assert!(matches!(err, IdlError::Syntax { line: 1, .. }), "{}", err);
+        }
+        let err = parse_str_with_options(
+            "[packets.a]\npacket_id = 1979-05-27\nmsg_type = \"u8\"\n",
+            &toml,
+        )
+        .unwrap_err();
         assert_eq!(
-            PrimitiveType::from_str("float32").unwrap(),
-            PrimitiveType::Float32
+            err.to_string(),
+            "/packets/a/packet_id: date-time values are not supported"
         );
+
         assert_eq!(
-            PrimitiveType::from_str("f64").unwrap(),
-            PrimitiveType::Float64
+            InputFormat::from_path(Path::new("a/proto.TOML")),
+            InputFormat::Toml
         );
-        assert!(PrimitiveType::from_str("invalid").is_err());
+        assert_eq!(
+            InputFormat::from_path(Path::new("proto.json")),
+            InputFormat::Json
+        );
+        assert!(<InputFormat as clap::ValueEnum>::from_str("yaml", true).is_err());
     }
 
     #[test]
-    fn test_primitive_type_c_type() {
-        assert_eq!(PrimitiveType::Char.c_type(), "char");
-        assert_eq!(PrimitiveType::Uint8.c_type(), "uint8_t");
-        assert_eq!(PrimitiveType::Int16.c_type(), "int16_t");
-        assert_eq!(PrimitiveType::Float32.c_type(), "float");
-        assert_eq!(PrimitiveType::Float64.c_type(), "double");
+    fn test_merge_message_sets_combines_and_sorts() {
+        let base = parse_str(
+            r#"{ "version": "1.0", "max_payload_bytes": 300,
+                 "devices": { "board": { "role": "server" } },
+                 "packets": { "status": { "packet_id": 5, "msg_type": "uint8" } } }"#,
+        )
+        .unwrap();
+        let app = parse_str(
+            r#"{ "version": "1.0", "max_address": 16,
+                 "devices": { "board": { "role": "server" }, "panel": { "role": "client", "id": 1 } },
+                 "packets": { "ping": { "packet_id": 1, "msg_type": "uint8" } } }"#,
+        )
+        .unwrap();
+        let (metadata, messages) = merge_message_sets(vec![base, app]).unwrap();
+        let names: Vec<&str> = messages.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["ping", "status"]);
+        assert_eq!(metadata.version.as_deref(), Some("1.0"));
+        assert_eq!(metadata.max_address, Some(16));
+        assert_eq!(metadata.max_payload_bytes, 300);
+        assert_eq!(metadata.devices.len(), 2);
     }
 
     #[test]
-    fn test_primitive_type_byte_len() {
-        assert_eq!(PrimitiveType::Char.byte_len(), 1);
-        assert_eq!(PrimitiveType::Uint8.byte_len(), 1);
-        assert_eq!(PrimitiveType::Int16.byte_len(), 2);
-        assert_eq!(PrimitiveType::Uint32.byte_len(), 4);
-        assert_eq!(PrimitiveType::Float32.byte_len(), 4);
-        assert_eq!(PrimitiveType::Float64.byte_len(), 8);
+    fn test_merge_message_sets_reports_conflicts() {
+        let set = |json: &str| parse_str(json).unwrap();
+        let err = merge_message_sets(vec![
+            set(
+                r#"{ "version": "1.0", "devices": { "board": { "role": "server" } },
+                     "packets": { "motor_speed": { "packet_id": 1, "msg_type": "uint8" } } }"#,
+            ),
+            set(
+                r#"{ "version": "2.0", "devices": { "board": { "role": "client", "id": 2 } },
+                     "packets": { "motor_speed": { "packet_id": 2, "msg_type": "uint8" },
+                                  "status": { "packet_id": 1, "msg_type": "uint8" } } }"#,
+            ),
+            set(r#"{ "packets": { "motor-speed": { "packet_id": 3, "msg_type": "uint8" } } }"#),
+        ])
+        .unwrap_err();
+        let messages: Vec<String> = err.errors().iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            messages,
+            [
+                "/version: conflicting 'version' values '1.0' and '2.0' in the merged inputs",
+                "/devices/board: device 'board' is defined differently in the merged inputs",
+                "message 'status' has packet_id 1 which is already used by message 'motor_speed'",
+                "message 'motor_speed' is defined in more than one input",
+                "message 'motor-speed' maps to the same C identifier 'motor_speed' as message 'motor_speed'",
+            ]
+        );
+
+        let single = merge_message_sets(vec![
+            set(r#"{ "max_address": 16, "packets": { "a": { "packet_id": 1, "msg_type": "u8" } } }"#),
+            set(r#"{ "max_address": 32, "packets": { "b": { "packet_id": 2, "msg_type": "u8" } } }"#),
+        ])
+        .unwrap_err();
+        assert_eq!(single.pointer(), Some("/max_address"));
     }
 
     #[test]
-    fn test_endian_from_str() {
-        assert_eq!(Endian::from_str("little").unwrap(), Endian::Little);
-        assert_eq!(Endian::from_str("big").unwrap(), Endian::Big);
-        assert_eq!(Endian::from_str("le").unwrap(), Endian::Little);
-        assert_eq!(Endian::from_str("be").unwrap(), Endian::Big);
-        assert!(Endian::from_str("invalid").is_err());
+    fn test_shared_types_resolved_by_name() {
+        let (metadata, messages) = parse_str(
+            r#"{ "types": {
+                     "vector3": { "fields": { "x": { "type": "f32" }, "y": { "type": "f32" }, "z": { "type": "f32" } } },
+                     "pose": { "type": "struct", "fields": { "position": { "type": "vector3" }, "heading": { "type": "f32" } } }
+                 },
+                 "packets": {
+                     "target": { "packet_id": 1, "msg_type": "vector3" },
+                     "odometry": { "packet_id": 2, "msg_type": "struct",
+                                   "fields": { "pose": { "type": "pose" }, "seq": { "type": "u16" } } }
+                 } }"#,
+        )
+        .unwrap();
+        let names: Vec<&str> = metadata
+            .types
+            .iter()
+            .filter_map(|t| t.shared.as_deref())
+            .collect();
+        assert_eq!(names, ["vector3", "pose"]);
+
+        let MessageBody::Struct(target) = &messages[0].body else {
+            panic!("expected struct body");
+        };
+        assert_eq!(target.shared.as_deref(), Some("vector3"));
+        assert_eq!(target.fields.len(), 3);
+
+        let MessageBody::Struct(odometry) = &messages[1].body else {
+            panic!("expected struct body");
+        };
+        let StructFieldType::Nested(pose) = &odometry.fields[0].field_type else {
+            panic!("expected nested field");
+        };
+        assert_eq!(pose.shared.as_deref(), Some("pose"));
+        let used: Vec<&str> = shared_types(&metadata, &messages)
+            .iter()
+            .filter_map(|t| t.shared.as_deref())
+            .collect();
+        assert_eq!(used, ["vector3", "pose"]);
     }
 
     #[test]
-    fn test_endian_suffix() {
-        assert_eq!(Endian::Little.suffix(), "le");
-        assert_eq!(Endian::Big.suffix(), "be");
+    fn test_shared_type_errors() {
+        fn messages_of(json: Value) -> Vec<String> {
+            parse_messages_with_diagnostics(json.as_object().unwrap(), &ParseOptions::default())
+                .unwrap_err()
+                .iter()
+                .map(|e| e.to_string())
+                .collect()
+        }
+
+        let unknown = messages_of(json!({
+            "packets": { "pose": { "packet_id": 1, "msg_type": "struct",
+                                   "fields": { "position": { "type": "vector4" } } } }
+        }));
+        assert_eq!(unknown.len(), 1);
+        assert!(unknown[0].starts_with("/packets/pose/fields/position/type: "));
+        assert!(unknown[0].contains("'vector4'"));
+
+        let cyclic = messages_of(json!({
+            "types": {
+                "a": { "fields": { "b": { "type": "b" } } },
+                "b": { "fields": { "a": { "type": "a" } } }
+            },
+            "packets": { "p": { "packet_id": 1, "msg_type": "a" } }
+        }));
+        assert_eq!(
+            cyclic[0],
+            "/types/a: shared type 'a' contains itself (a -> b -> a)"
+        );
+
+        let builtin = messages_of(json!({
+            "types": { "u8": { "fields": { "x": { "type": "u8" } } } },
+            "packets": { "p": { "packet_id": 1, "msg_type": "u8" } }
+        }));
+        assert_eq!(
+            builtin,
+            ["/types/u8: shared type 'u8' has the name of a built-in type"]
+        );
+
+        let array = messages_of(json!({
+            "types": { "vector3": { "fields": { "x": { "type": "f32" } } } },
+            "packets": { "path": { "packet_id": 1, "msg_type": "struct",
+                                   "fields": { "points": { "type": "vector3", "array": true, "max_length": 4 } } } }
+        }));
+        assert_eq!(
+            array,
+            [
+                "/packets/path/fields/points/array: field 'points' in 'path' cannot be an array of shared type 'vector3'"
+            ]
+        );
     }
 
     #[test]
-    fn test_target_language_parse() {
-        assert_eq!(TargetLanguage::parse("c").unwrap(), TargetLanguage::C);
-        assert_eq!(TargetLanguage::parse("C99").unwrap(), TargetLanguage::C);
-        assert!(TargetLanguage::parse("python").is_err());
+    fn test_merge_message_sets_unions_shared_types() {
+        let set = |json: &str| parse_str(json).unwrap();
+        let (metadata, _) = merge_message_sets(vec![
+            set(
+                r#"{ "types": { "vector3": { "fields": { "x": { "type": "f32" } } } },
+                     "packets": { "a": { "packet_id": 1, "msg_type": "vector3" } } }"#,
+            ),
+            set(
+                r#"{ "types": { "vector3": { "fields": { "x": { "type": "f32" } } } },
+                     "packets": { "b": { "packet_id": 2, "msg_type": "vector3" } } }"#,
+            ),
+        ])
+        .unwrap();
+        assert_eq!(metadata.types.len(), 1);
+
+        let err = merge_message_sets(vec![
+            set(
+                r#"{ "types": { "vector3": { "fields": { "x": { "type": "f32" } } } },
+                     "packets": { "a": { "packet_id": 1, "msg_type": "vector3" } } }"#,
+            ),
+            set(
+                r#"{ "types": { "vector3": { "fields": { "x": { "type": "f64" } } } },
+                     "packets": { "b": { "packet_id": 2, "msg_type": "vector3" } } }"#,
+            ),
+        ])
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "/types/vector3: shared type 'vector3' is defined differently in the merged inputs"
+        );
     }
 
     #[test]
-    fn test_parse_scalar_message() {
+    fn test_parse_constants() {
+        let (metadata, _) = parse_str(
+            r#"{ "constants": { "start_byte": 165, "gravity": 9.81, "offset": -3, "firmware": "v1.2",
+                                "sync": "0xA5", "mask": "0b1010", "revision": { "string": "0x0102" } },
+                 "packets": { "ping": { "packet_id": 1, "msg_type": "u8" } } }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            metadata.constants,
+            [
+                ("start_byte".to_string(), ConstValue::Int(165)),
+                ("gravity".to_string(), ConstValue::Float(9.81)),
+                ("offset".to_string(), ConstValue::Int(-3)),
+                ("firmware".to_string(), ConstValue::Str("v1.2".to_string())),
+                ("sync".to_string(), ConstValue::Int(0xA5)),
+                ("mask".to_string(), ConstValue::Int(10)),
+                (
+                    "revision".to_string(),
+                    ConstValue::Str("0x0102".to_string())
+                ),
+            ]
+        );
+
         let json = json!({
-            "version": "1.0.0",
-            "packets": {
-                "ping": {
-                    "packet_id": 0,
-                    "msg_type": "uint8",
-                    "array": false,
-                    "msg_desc": "Ping command"
-                }
-            }
+            "constants": { "a-b": 1, "a_b": 2, "list": [1], "flag": true, "big": 18446744073709551615u64,
+                           "huge": "0xFFFFFFFFFFFFFFFF", "text": { "string": 1 } },
+            "packets": {}
         });
+        let messages: Vec<String> =
+            parse_messages_with_diagnostics(json.as_object().unwrap(), &ParseOptions::default())
+                .unwrap_err()
+                .iter()
+                .map(|e| e.to_string())
+                .collect();
+        assert_eq!(
+            messages,
+            [
+                "/constants/a_b: constant 'a_b' maps to the same macro name 'H6XSERIAL_CONST_A_B' as constant 'a-b'",
+                "/constants/list: constant 'list' must be an integer, float, string or { \"string\": \"...\" }",
+                "/constants/flag: constant 'flag' must be an integer, float, string or { \"string\": \"...\" }",
+                "/constants/big: constant 'big' is out of range, expected a 64-bit signed integer",
+                "/constants/huge: constant 'huge' is out of range, expected a 64-bit signed integer",
+                "/constants/text: constant 'text' must be an integer, float, string or { \"string\": \"...\" }",
+            ]
+        );
 
-        let obj = json.as_object().unwrap();
-        let (metadata, messages) = parse_messages(obj).unwrap();
+        let set = |json: &str| parse_str(json).unwrap();
+        let err = merge_message_sets(vec![
+            set(r#"{ "constants": { "start_byte": 165 }, "packets": {} }"#),
+            set(r#"{ "constants": { "start_byte": 90 }, "packets": {} }"#),
+            set(r#"{ "constants": { "start-byte": 165 }, "packets": {} }"#),
+        ])
+        .unwrap_err();
+        let messages: Vec<String> = err.errors().iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            messages,
+            [
+                "/constants/start_byte: constant 'start_byte' is defined differently in the merged inputs",
+                "/constants/start-byte: constant 'start-byte' maps to the same macro name 'H6XSERIAL_CONST_START_BYTE' as constant 'start_byte'",
+            ]
+        );
+    }
 
-        assert_eq!(metadata.version, Some("1.0.0".to_string()));
-        assert_eq!(messages.len(), 1);
-        assert_eq!(messages[0].name, "ping");
-        assert_eq!(messages[0].packet_id, 0);
-        assert_eq!(messages[0].description, Some("Ping command".to_string()));
+    #[test]
+    fn test_parse_scale_and_offset() {
+        let (_, messages) = parse_str(
+            r#"{ "packets": {
+                 "temp": { "packet_id": 1, "msg_type": "int16", "scale": 0.01, "offset": -40 },
+                 "env": { "packet_id": 2, "msg_type": "struct", "fields": {
+                     "humidity": { "type": "u8", "offset": 10 },
+                     "raw": { "type": "u16", "array": true, "max_length": 4, "scale": 2 } } } } }"#,
+        )
+        .unwrap();
+        let MessageBody::Scalar(temp) = &messages[0].body else {
+            panic!("expected scalar body");
+        };
+        let scaling = temp.scaling.unwrap();
+        assert_eq!((scaling.scale, scaling.offset), (0.01, -40.0));
+        let (low, high) = scaling.range(PrimitiveType::Int16).unwrap();
+        assert!((low + 367.68).abs() < 1e-9 && (high - 287.67).abs() < 1e-9);
+
+        let MessageBody::Struct(env) = &messages[1].body else {
+            panic!("expected struct body");
+        };
+        assert_eq!(
+            env.fields[0].scaling,
+            Some(Scaling {
+                scale: 1.0,
+                offset: 10.0
+            })
+        );
+        assert_eq!(env.fields[1].scaling.map(|s| s.scale), Some(2.0));
+
+        let json = json!({ "packets": {
+            "speed": { "packet_id": 1, "msg_type": "float32", "scale": 0.5 },
+            "flags": { "packet_id": 2, "msg_type": "bool", "array": true, "max_length": 2, "offset": 1 },
+            "pose": { "packet_id": 3, "msg_type": "struct", "fields": {
+                "pos": { "type": "struct", "scale": 2, "fields": { "x": { "type": "i8" } } },
+                "name": { "type": "char", "array": true, "max_length": 8, "scale": 1 },
+                "count": { "type": "u8", "scale": 0 },
+                "gain": { "type": "u8", "scale": "high" } } }
+        } });
+        let messages: Vec<String> =
+            parse_messages_with_diagnostics(json.as_object().unwrap(), &ParseOptions::default())
+                .unwrap_err()
+                .iter()
+                .map(|e| e.to_string())
+                .collect();
+        assert_eq!(
+            messages,
+            [
+                "/packets/speed/scale: 'scale' needs an integer type, but message 'speed' is float",
+                "/packets/flags/offset: 'offset' needs an integer type, but message 'flags' is bool",
+                "/packets/pose/fields/pos/scale: 'scale' needs an integer type, but field 'pos' in 'pose' is struct",
+                "/packets/pose/fields/name/scale: 'scale' needs an integer type, but field 'name' in 'pose' is char",
+                "/packets/pose/fields/count/scale: 'scale' of field 'count' in 'pose' must not be zero",
+                "/packets/pose/fields/gain/scale: 'scale' must be a number",
+            ]
+        );
+    }
 
-        match &messages[0].body {
-            MessageBody::Scalar(spec) => {
-                assert_eq!(spec.primitive, PrimitiveType::Uint8);
-                assert_eq!(spec.endian, Endian::Little);
-            }
-            _ => panic!("Expected scalar message"),
-        }
+    #[test]
+    fn test_parse_min_and_max() {
+        let (_, messages) = parse_str(
+            r#"{ "packets": {
+                 "speed": { "packet_id": 1, "msg_type": "uint16", "min": 10, "max": 1000 },
+                 "gain": { "packet_id": 2, "msg_type": "float32", "min": -1 },
+                 "pose": { "packet_id": 3, "msg_type": "struct", "fields": {
+                     "x": { "type": "i8", "max": 100 } } } } }"#,
+        )
+        .unwrap();
+        let MessageBody::Scalar(speed) = &messages[0].body else {
+            panic!("expected scalar body");
+        };
+        assert_eq!(
+            speed.range,
+            Some(ValueRange {
+                min: Some(Bound::Int(10)),
+                max: Some(Bound::Int(1000))
+            })
+        );
+        let MessageBody::Scalar(gain) = &messages[1].body else {
+            panic!("expected scalar body");
+        };
+        assert_eq!(gain.range.unwrap().min, Some(Bound::Float(-1.0)));
+        let MessageBody::Struct(pose) = &messages[2].body else {
+            panic!("expected struct body");
+        };
+        assert_eq!(pose.fields[0].range.unwrap().max, Some(Bound::Int(100)));
+
+        let json = json!({ "packets": {
+            "levels": { "packet_id": 1, "msg_type": "u8", "array": true, "max_length": 2, "max": 5 },
+            "flag": { "packet_id": 2, "msg_type": "bool", "min": 0 },
+            "count": { "packet_id": 3, "msg_type": "uint8", "min": -1 },
+            "pose": { "packet_id": 4, "msg_type": "struct", "fields": {
+                "pos": { "type": "struct", "max": 2, "fields": { "x": { "type": "i8" } } },
+                "x": { "type": "i16", "min": 0.5 },
+                "y": { "type": "i16", "min": 5, "max": 4 },
+                "z": { "type": "f32", "max": 1e39 },
+                "w": { "type": "u8", "min": "low" } } }
+        } });
+        let messages: Vec<String> =
+            parse_messages_with_diagnostics(json.as_object().unwrap(), &ParseOptions::default())
+                .unwrap_err()
+                .iter()
+                .map(|e| e.to_string())
+                .collect();
+        assert_eq!(
+            messages,
+            [
+                "/packets/levels/max: 'max' needs a numeric scalar type, but message 'levels' is array",
+                "/packets/flag/min: 'min' needs a numeric scalar type, but message 'flag' is bool",
+                "/packets/count/min: 'min' -1 of message 'count' is outside the range of uint8_t (0 to 255)",
+                "/packets/pose/fields/pos/max: 'max' needs a numeric scalar type, but field 'pos' in 'pose' is struct",
+                "/packets/pose/fields/x/min: 'min' of field 'x' in 'pose' must be an integer, as int16_t is an integer type",
+                "/packets/pose/fields/y/min: 'min' 5 of field 'y' in 'pose' is greater than 'max' 4",
+                "/packets/pose/fields/z/max: 'max' 1e39 of field 'z' in 'pose' is outside the range of float (-3.4028235e38 to 3.4028235e38)",
+                "/packets/pose/fields/w/min: 'min' must be a number",
+            ]
+        );
     }
 
     #[test]
-    fn test_parse_array_message() {
-        let json = json!({
-            "packets": {
-                "temperatures": {
-                    "packet_id": 20,
-                    "msg_type": "float32",
-                    "array": true,
-                    "endianess": "big",
-                    "max_length": 8,
-                    "msg_desc": "Temperature array"
-                }
-            }
-        });
+    fn test_parse_desc_and_unit() {
+        let (_, messages) = parse_str(
+            r#"{ "packets": {
+                 "speed": { "packet_id": 1, "msg_type": "int16", "msg_desc": "Motor", "unit": "rpm", "desc": "setpoint" },
+                 "pose": { "packet_id": 2, "msg_type": "struct", "fields": {
+                     "x": { "type": "f32", "unit": "m" },
+                     "y": { "type": "f32", "msg_desc": "legacy" } } } } }"#,
+        )
+        .unwrap();
+        let MessageBody::Scalar(speed) = &messages[0].body else {
+            panic!("expected scalar body");
+        };
+        assert_eq!(messages[0].description.as_deref(), Some("Motor"));
+        assert_eq!(speed.unit.as_deref(), Some("rpm"));
+        assert_eq!(speed.description.as_deref(), Some("setpoint"));
+        let MessageBody::Struct(pose) = &messages[1].body else {
+            panic!("expected struct body");
+        };
+        assert_eq!(pose.fields[0].unit.as_deref(), Some("m"));
+        assert_eq!(pose.fields[0].description, None);
+        assert_eq!(pose.fields[1].description.as_deref(), Some("legacy"));
+
+        let json = json!({ "packets": {
+            "speed": { "packet_id": 1, "msg_type": "int16", "unit": 5 },
+            "pose": { "packet_id": 2, "msg_type": "struct", "desc": "x", "fields": {
+                "x": { "type": "f32", "desc": ["m"] } } }
+        } });
+        let messages: Vec<String> =
+            parse_messages_with_diagnostics(json.as_object().unwrap(), &ParseOptions::default())
+                .unwrap_err()
+                .iter()
+                .map(|e| e.to_string())
+                .collect();
+        assert_eq!(
+            messages,
+            [
+                "/packets/speed/unit: 'unit' of message 'speed' must be a string",
+                "/packets/pose/desc: 'desc' describes the value of a scalar or array message; put it on the fields of struct message 'pose'",
+                "/packets/pose/fields/x/desc: 'desc' of field 'x' in 'pose' must be a string",
+            ]
+        );
+    }
 
-        let obj = json.as_object().unwrap();
-        let (_, messages) = parse_messages(obj).unwrap();
+    #[test]
+    fn test_parse_timestamp_fields() {
+        let (_, messages) = parse_str(
+            r#"{ "packets": {
+                 "imu": { "packet_id": 1, "msg_type": "struct", "fields": {
+                     "stamp": { "type": "timestamp" },
+                     "boot": { "type": "timestamp", "storage": "u32", "resolution": "ms" },
+                     "count": { "type": "u64" } } } } }"#,
+        )
+        .unwrap();
+        let MessageBody::Struct(imu) = &messages[0].body else {
+            panic!("expected struct body");
+        };
+        let typed = |field: &StructField| (field.field_type.clone(), field.timestamp);
+        assert_eq!(
+            typed(&imu.fields[0]),
+            (
+                StructFieldType::Primitive(PrimitiveType::Uint64),
+                Some(TimestampResolution::Us)
+            )
+        );
+        assert_eq!(
+            typed(&imu.fields[1]),
+            (
+                StructFieldType::Primitive(PrimitiveType::Uint32),
+                Some(TimestampResolution::Ms)
+            )
+        );
+        assert_eq!(typed(&imu.fields[2]).1, None);
+
+        let json = json!({ "packets": {
+            "imu": { "packet_id": 1, "msg_type": "struct", "fields": {
+                "a": { "type": "timestamp", "storage": "int16" },
+                "b": { "type": "timestamp", "resolution": "s" },
+                "c": { "type": "timestamp", "array": true, "max_length": 2 },
+                "d": { "type": "u32", "resolution": "ms" } } }
+        } });
+        let messages: Vec<String> =
+            parse_messages_with_diagnostics(json.as_object().unwrap(), &ParseOptions::default())
+                .unwrap_err()
+                .iter()
+                .map(|e| e.to_string())
+                .collect();
+        assert_eq!(
+            messages,
+            [
+                "/packets/imu/fields/a/storage: 'storage' of field 'a' in 'imu' must be uint32 or uint64",
+                "/packets/imu/fields/b/resolution: 'resolution' of field 'b' in 'imu' must be ms, us or ns",
+                "/packets/imu/fields/c/array: field 'c' in 'imu' cannot be an array of timestamps",
+                "/packets/imu/fields/d/resolution: 'resolution' only applies to timestamp fields, and field 'd' in 'imu' is not one",
+            ]
+        );
+    }
 
-        assert_eq!(messages.len(), 1);
-        match &messages[0].body {
-            MessageBody::Array(spec) => {
-                assert_eq!(spec.primitive, PrimitiveType::Float32);
-                assert_eq!(spec.endian, Endian::Big);
-                assert_eq!(spec.max_length, 8);
-            }
-            _ => panic!("Expected array message"),
-        }
+    #[test]
+    fn test_parse_bytes_types() {
+        let (_, messages) = parse_str(
+            r#"{ "packets": {
+                 "uuid": { "packet_id": 1, "msg_type": "bytes", "length": 16, "desc": "device" },
+                 "beacon": { "packet_id": 2, "msg_type": "struct", "fields": {
+                     "mac": { "type": "bytes", "length": 6 },
+                     "rssi": { "type": "i8" } } } } }"#,
+        )
+        .unwrap();
+        let MessageBody::Struct(uuid) = &messages[0].body else {
+            panic!("expected struct body");
+        };
+        assert_eq!(uuid.fields[0].name, "value");
+        assert_eq!(uuid.fields[0].field_type, StructFieldType::Bytes(16));
+        assert_eq!(uuid.fields[0].description.as_deref(), Some("device"));
+        assert_eq!(message_body_min_size(&messages[0].body), 16);
+        assert_eq!(message_body_max_size(&messages[1].body), 7);
+        assert_eq!(message_body_min_size(&messages[1].body), 7);
+
+        let json = json!({ "packets": {
+            "a": { "packet_id": 1, "msg_type": "bytes" },
+            "b": { "packet_id": 2, "msg_type": "bytes", "length": 300 },
+            "c": { "packet_id": 3, "msg_type": "u8", "length": 4 },
+            "d": { "packet_id": 4, "msg_type": "struct", "fields": {
+                "e": { "type": "bytes", "length": 0 },
+                "f": { "type": "bytes", "length": 2, "array": true, "max_length": 2 } } }
+        } });
+        let messages: Vec<String> =
+            parse_messages_with_diagnostics(json.as_object().unwrap(), &ParseOptions::default())
+                .unwrap_err()
+                .iter()
+                .map(|e| e.to_string())
+                .collect();
+        assert_eq!(
+            messages,
+            [
+                "/packets/a: bytes message 'a' requires 'length' field (1-251)",
+                "/packets/b/length: bytes message 'b' has length 300 which exceeds protocol limit of 251 bytes",
+                "/packets/c/length: 'length' only applies to bytes types, and message 'c' is not one; arrays use 'max_length'",
+                "/packets/d/fields/e/length: field 'e' in 'd' has length of 0, must be at least 1",
+                "/packets/d/fields/f/array: field 'f' in 'd' cannot be an array; use 'length' instead",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_fixed_length_arrays() {
+        let (_, messages) = parse_str(
+            r#"{ "packets": {
+                 "calib": { "packet_id": 1, "msg_type": "f32", "array": true, "fixed_length": 12 },
+                 "frame": { "packet_id": 2, "msg_type": "struct", "fields": {
+                     "gains": { "type": "i16", "array": true, "fixed_length": 3 },
+                     "tail": { "type": "u8", "array": true, "max_length": 4 } } } } }"#,
+        )
+        .unwrap();
+        let MessageBody::Struct(calib) = &messages[0].body else {
+            panic!("expected struct body");
+        };
+        assert_eq!(calib.fields[0].name, "data");
+        assert_eq!(
+            calib.fields[0].field_type,
+            StructFieldType::Array(StructFieldArraySpec {
+                primitive: PrimitiveType::Float32,
+                max_length: 12,
+                fixed: true,
+                string_semantics: false,
+            })
+        );
+        assert_eq!(message_body_min_size(&messages[0].body), 48);
+        assert_eq!(message_body_min_size(&messages[1].body), 6);
+        assert_eq!(message_body_max_size(&messages[1].body), 10);
+
+        let json = json!({ "packets": {
+            "a": { "packet_id": 1, "msg_type": "u8", "array": true, "max_length": 4, "fixed_length": 4 },
+            "b": { "packet_id": 2, "msg_type": "u8", "fixed_length": 4 },
+            "c": { "packet_id": 3, "msg_type": "u16", "array": true, "fixed_length": 4, "sector_bytes": 4 },
+            "d": { "packet_id": 4, "msg_type": "u32", "array": true, "fixed_length": 100 },
+            "e": { "packet_id": 5, "msg_type": "struct", "fields": {
+                "f": { "type": "u8", "array": true, "fixed_length": 0 } } }
+        } });
+        let messages: Vec<String> =
+            parse_messages_with_diagnostics(json.as_object().unwrap(), &ParseOptions::default())
+                .unwrap_err()
+                .iter()
+                .map(|e| e.to_string())
+                .collect();
+        assert_eq!(
+            messages,
+            [
+                "/packets/a/fixed_length: array message 'a' has both 'max_length' and 'fixed_length'; use only one",
+                "/packets/b/fixed_length: 'fixed_length' only applies to arrays, and message 'b' is not one (set 'array': true)",
+                "/packets/c/sector_bytes: array message 'c' has 'fixed_length', which cannot be split into sectors; use 'max_length'",
+                "/packets/d/fixed_length: array message 'd' has payload size 400 bytes which exceeds protocol limit of 251 bytes",
+                "/packets/e/fields/f/fixed_length: array field 'f' in 'e' has fixed_length of 0, must be at least 1",
+            ]
+        );
     }
 
     #[test]
-    fn test_parse_struct_message() {
-        let json = json!({
-            "packets": {
-                "sensor_data": {
-                    "packet_id": 30,
-                    "msg_type": "struct",
-                    "fields": {
-                        "temperature": {
-                            "type": "float32",
-                            "endianess": "big"
-                        },
-                        "humidity": {
-                            "type": "uint8"
-                        }
-                    },
-                    "msg_desc": "Sensor readings"
-                }
-            }
-        });
+    fn test_parse_union_messages() {
+        let (_, messages) = parse_str(
+            r#"{ "types": { "Odom": { "fields": {
+                     "ticks": { "type": "i32", "array": true, "max_length": 2 } } } },
+                 "packets": {
+                 "sample": { "packet_id": 1, "msg_type": "union", "tag": "u16", "endianess": "big",
+                     "variants": {
+                         "7": { "name": "gps", "desc": "Position fix", "fields": {
+                             "lat": { "type": "f64" }, "sats": { "type": "u8" } } },
+                         "3": { "name": "odometry", "type": "Odom" } } } } }"#,
+        )
+        .unwrap();
+        let MessageBody::Union(spec) = &messages[0].body else {
+            panic!("expected union body");
+        };
+        assert_eq!(spec.tag, PrimitiveType::Uint16);
+        assert_eq!(spec.endian, Endian::Big);
+        let names: Vec<(&str, i128)> = spec
+            .variants
+            .iter()
+            .map(|variant| (variant.name.as_str(), variant.tag))
+            .collect();
+        assert_eq!(names, [("gps", 7), ("odometry", 3)]);
+        assert_eq!(
+            spec.variants[0].description.as_deref(),
+            Some("Position fix")
+        );
+        assert_eq!(spec.variants[1].spec.shared.as_deref(), Some("Odom"));
+        assert_eq!(spec.variant(3).unwrap().name, "odometry");
+        assert_eq!(message_body_min_size(&messages[0].body), 2);
+        assert_eq!(message_body_max_size(&messages[0].body), 11);
+
+        let json = json!({ "packets": {
+            "a": { "packet_id": 1, "msg_type": "union", "variants": {
+                "1": { "name": "x", "fields": { "v": { "type": "u8" } } } } },
+            "b": { "packet_id": 2, "msg_type": "union", "tag": "f32", "variants": {
+                "1": { "name": "x", "fields": { "v": { "type": "u8" } } } } },
+            "c": { "packet_id": 3, "msg_type": "union", "tag": "u8", "variants": {
+                "256": { "name": "x", "fields": { "v": { "type": "u8" } } },
+                "one": { "name": "y", "fields": { "v": { "type": "u8" } } },
+                "2": { "name": "z", "fields": { "v": { "type": "u8" } } },
+                "02": { "name": "w", "fields": { "v": { "type": "u8" } } },
+                "3": { "name": "Z", "fields": { "v": { "type": "u8" } } } } },
+            "d": { "packet_id": 4, "msg_type": "union", "tag": "u8", "variants": {} },
+            "e": { "packet_id": 5, "msg_type": "u8", "tag": "u8" }
+        } });
+        let messages: Vec<String> =
+            parse_messages_with_diagnostics(json.as_object().unwrap(), &ParseOptions::default())
+                .unwrap_err()
+                .iter()
+                .map(|e| e.to_string())
+                .collect();
+        assert_eq!(
+            messages,
+            [
+                "/packets/a: union message 'a' is missing required field 'tag' (e.g., 'uint8')",
+                "/packets/b/tag: union message 'b' has 'tag' type 'f32', which is not an integer type",
+                "/packets/c/variants/256: variant 'x' of union message 'c' has tag value 256 outside the range of uint8 (0 to 255)",
+                "/packets/c/variants/one: variant key 'one' of union message 'c' is not an integer tag value",
+                "/packets/c/variants/02: variants 'z' and 'w' of union message 'c' have the same tag value 2",
+                "/packets/c/variants/3/name: variant 'Z' of union message 'c' maps to the same C identifier as variant 'z'",
+                "/packets/d/variants: union message 'd' must define at least one variant in 'variants' object",
+                "/packets/e/tag: 'tag' only applies to union messages, and message 'e' is not one (set 'msg_type': 'union')",
+            ]
+        );
+    }
 
-        let obj = json.as_object().unwrap();
-        let (_, messages) = parse_messages(obj).unwrap();
+    #[test]
+    fn test_default_endianness_applies_to_values_without_their_own() {
+        let (metadata, messages) = parse_str(
+            r#"{ "default_endianness": "big", "packets": {
+                 "speed": { "packet_id": 1, "msg_type": "u16" },
+                 "log": { "packet_id": 2, "msg_type": "i32", "array": true, "max_length": 4 },
+                 "pose": { "packet_id": 3, "msg_type": "struct", "fields": {
+                     "x": { "type": "f32" },
+                     "y": { "type": "f32", "endianess": "little" } } } } }"#,
+        )
+        .unwrap();
+        assert_eq!(metadata.default_endianness, Some(Endian::Big));
+        let MessageBody::Scalar(speed) = &messages[0].body else {
+            panic!("expected scalar body");
+        };
+        assert_eq!(speed.endian, Endian::Big);
+        let MessageBody::Array(log) = &messages[1].body else {
+            panic!("expected array body");
+        };
+        assert_eq!(log.endian, Endian::Big);
+        let MessageBody::Struct(pose) = &messages[2].body else {
+            panic!("expected struct body");
+        };
+        let endians: Vec<Endian> = pose.fields.iter().map(|f| f.endian).collect();
+        assert_eq!(endians, [Endian::Big, Endian::Little]);
+
+        let err = parse_str(r#"{ "default_endianness": "middle", "packets": {} }"#).unwrap_err();
+        assert_eq!(err.pointer(), Some("/default_endianness"));
+        let set = |json: &str| parse_str(json).unwrap();
+        let err = merge_message_sets(vec![
+            set(r#"{ "default_endianness": "big", "packets": { "a": { "packet_id": 1, "msg_type": "u8" } } }"#),
+            set(r#"{ "default_endianness": "le", "packets": { "b": { "packet_id": 2, "msg_type": "u8" } } }"#),
+        ])
+        .unwrap_err();
+        assert_eq!(err.pointer(), Some("/default_endianness"));
+    }
 
-        assert_eq!(messages.len(), 1);
-        match &messages[0].body {
-            MessageBody::Struct(spec) => {
-                assert_eq!(spec.fields.len(), 2);
-                // Note: JSON object field order is not guaranteed, so check both fields exist
-                let temp_field = spec.fields.iter().find(|f| f.name == "temperature");
-                let hum_field = spec.fields.iter().find(|f| f.name == "humidity");
+    #[test]
+    fn test_parse_message_versions() {
+        let (_, messages) = parse_str(
+            r#"{ "packets": {
+                 "pose": { "packet_id": 1, "msg_type": "struct", "msg_version": 2, "fields": {
+                     "x": { "type": "i16" },
+                     "heading": { "type": "f32", "since_version": 2 } } },
+                 "ping": { "packet_id": 2, "msg_type": "u8" } } }"#,
+        )
+        .unwrap();
+        assert_eq!(messages[0].msg_version, Some(2));
+        assert_eq!(messages[1].msg_version, None);
+        let MessageBody::Struct(spec) = &messages[0].body else {
+            panic!("expected struct body");
+        };
+        let since: Vec<Option<u8>> = spec.fields.iter().map(|f| f.since_version).collect();
+        assert_eq!(since, [None, Some(2)]);
+
+        let json = json!({ "packets": {
+            "a": { "packet_id": 1, "msg_type": "u8", "msg_version": 0 },
+            "b": { "packet_id": 2, "msg_type": "struct", "fields": {
+                "v": { "type": "u8", "since_version": 2 } } },
+            "c": { "packet_id": 3, "msg_type": "struct", "msg_version": 2, "fields": {
+                "v": { "type": "u8", "since_version": 3 } } },
+            "d": { "packet_id": 4, "msg_type": "struct", "msg_version": 2, "fields": {
+                "v": { "type": "u8", "since_version": 2 },
+                "w": { "type": "u8" } } },
+            "e": { "packet_id": 5, "msg_type": "struct", "msg_version": 2, "fields": {
+                "inner": { "type": "struct", "fields": {
+                    "v": { "type": "u8", "since_version": 2 } } } } }
+        } });
+        let messages: Vec<String> =
+            parse_messages_with_diagnostics(json.as_object().unwrap(), &ParseOptions::default())
+                .unwrap_err()
+                .iter()
+                .map(|e| e.to_string())
+                .collect();
+        assert_eq!(
+            messages,
+            [
+                "/packets/a/msg_version: 'msg_version' of message 'a' is 0, which is not 1-255",
+                "/packets/b/fields/v/since_version: field 'v' of message 'b' has 'since_version' but the message has no 'msg_version'",
+                "/packets/c/fields/v/since_version: field 'v' of message 'c' has since_version 3, above the msg_version 2 of the message",
+                "/packets/d/fields/w: field 'w' of message 'd' is from version 1 but follows field 'v' from version 2; fields can only be appended",
+                "/packets/e/fields/inner/fields/v/since_version: field 'v' of message 'e' has 'since_version', which only applies to the top-level fields of a struct message",
+            ]
+        );
+    }
 
-                assert!(temp_field.is_some(), "temperature field should exist");
-                let temp_field = temp_field.unwrap();
-                match &temp_field.field_type {
-                    StructFieldType::Primitive(prim) => {
-                        assert_eq!(*prim, PrimitiveType::Float32);
-                    }
-                    _ => panic!("Expected primitive field"),
-                }
-                assert_eq!(temp_field.endian, Endian::Big);
+    #[test]
+    fn test_parse_deprecated() {
+        let (_, messages) = parse_str(
+            r#"{ "packets": {
+                 "a": { "packet_id": 1, "msg_type": "u8", "deprecated": true },
+                 "b": { "packet_id": 2, "msg_type": "u8", "deprecated": "use 'c'" },
+                 "c": { "packet_id": 3, "msg_type": "u8", "deprecated": false },
+                 "d": { "packet_id": 4, "msg_type": "u8" } } }"#,
+        )
+        .unwrap();
+        let deprecated: Vec<_> = messages.iter().map(|m| m.deprecated.clone()).collect();
+        assert_eq!(
+            deprecated,
+            [
+                Some(Deprecation::default()),
+                Some(Deprecation {
+                    reason: Some("use 'c'".to_string())
+                }),
+                None,
+                None,
+            ]
+        );
 
-                assert!(hum_field.is_some(), "humidity field should exist");
-                let hum_field = hum_field.unwrap();
-                match &hum_field.field_type {
-                    StructFieldType::Primitive(prim) => {
-                        assert_eq!(*prim, PrimitiveType::Uint8);
-                    }
-                    _ => panic!("Expected primitive field"),
-                }
-            }
-            _ => panic!("Expected struct message"),
-        }
+        let err = parse_str(r#"{ "a": { "packet_id": 1, "msg_type": "u8", "deprecated": 1 } }"#)
+            .unwrap_err();
+        assert_eq!(err.pointer(), Some("/a/deprecated"));
     }
 
     #[test]
-    fn test_parse_messages_sorted_by_packet_id() {
+    fn test_reserved_ids() {
+        let (metadata, messages) = parse_str(
+            r#"{ "reserved_ids": [[0, 19], 250],
+                 "packets": {
+                     "ping": { "packet_id": 0, "msg_type": "u8", "reserved_ok": true },
+                     "led": { "packet_id": 20, "msg_type": "bool" } } }"#,
+        )
+        .unwrap();
+        assert_eq!(metadata.reserved_ids, [(0, 19), (250, 250)]);
+        assert!(messages[0].reserved_ok && !messages[1].reserved_ok);
+
         let json = json!({
-            "version": "1.0.0",
-            "max_address": 255,
+            "reserved_ids": [[0, 19], [30, 25], "x", [1, 2, 3], 300],
             "packets": {
-                "msg_c": {
-                    "packet_id": 30,
-                    "msg_type": "uint8",
-                    "array": false
-                },
-                "msg_a": {
-                    "packet_id": 10,
-                    "msg_type": "uint8",
-                    "array": false
-                },
-                "msg_b": {
-                    "packet_id": 20,
-                    "msg_type": "uint8",
-                    "array": false
-                }
-            }
+                "custom": { "packet_id": 7, "msg_type": "u8" },
+                "flagged": { "packet_id": 8, "msg_type": "u8", "reserved_ok": "yes" } }
         });
+        let messages: Vec<String> =
+            parse_messages_with_diagnostics(json.as_object().unwrap(), &ParseOptions::default())
+                .unwrap_err()
+                .iter()
+                .map(|e| e.to_string())
+                .collect();
+        assert_eq!(
+            messages,
+            [
+                "/reserved_ids/1: reserved range [30, 25] ends before it starts",
+                "/reserved_ids/2: reserved IDs must be packet IDs 0-255 or [first, last] ranges of them",
+                "/reserved_ids/3: reserved IDs must be packet IDs 0-255 or [first, last] ranges of them",
+                "/reserved_ids/4: reserved IDs must be packet IDs 0-255 or [first, last] ranges of them",
+                "/packets/flagged/reserved_ok: message 'flagged' has invalid 'reserved_ok' (must be true or false)",
+                "/packets/custom/packet_id: message 'custom' uses packet_id 7 from the reserved range 0-19; set \"reserved_ok\": true if it belongs there",
+            ]
+        );
 
-        let obj = json.as_object().unwrap();
-        let (metadata, mut messages) = parse_messages(obj).unwrap();
+        // The ranges of one input apply to the messages of another
+        let base = parse_str(
+            r#"{ "reserved_ids": [[0, 9]],
+                 "ping": { "packet_id": 0, "msg_type": "u8", "reserved_ok": true } }"#,
+        )
+        .unwrap();
+        let app = parse_str(r#"{ "packets": { "led": { "packet_id": 5, "msg_type": "bool" } } }"#)
+            .unwrap();
+        let err = merge_message_sets(vec![base, app]).unwrap_err();
+        assert!(err.to_string().contains("reserved range 0-9"), "{}", err);
+    }
 
-        assert_eq!(metadata.version, Some("1.0.0".to_string()));
-        assert_eq!(metadata.max_address, Some(255));
-        assert_eq!(messages.len(), 3);
+    #[test]
+    fn test_max_address_bounds_packet_ids() {
+        let (metadata, _) =
+            parse_str(r#"{ "max_address": 16, "a": { "packet_id": 16, "msg_type": "u8" } }"#)
+                .unwrap();
+        assert_eq!(metadata.max_address, Some(16));
+
+        let err = parse_str(
+            r#"{ "max_address": 16, "packets": { "a": { "packet_id": 17, "msg_type": "u8" } } }"#,
+        )
+        .unwrap_err();
+        assert_eq!(err.pointer(), Some("/packets/a/packet_id"));
+        assert_eq!(
+            err.to_string(),
+            "/packets/a/packet_id: message 'a' has packet_id 17 above max_address 16"
+        );
 
-        messages.sort_by_key(|m| m.packet_id);
-        assert_eq!(messages[0].name, "msg_a");
-        assert_eq!(messages[0].packet_id, 10);
-        assert_eq!(messages[1].name, "msg_b");
-        assert_eq!(messages[1].packet_id, 20);
-        assert_eq!(messages[2].name, "msg_c");
-        assert_eq!(messages[2].packet_id, 30);
+        // The max_address of one input bounds the messages of another
+        let base =
+            parse_str(r#"{ "max_address": 31, "ping": { "packet_id": 0, "msg_type": "u8" } }"#)
+                .unwrap();
+        let app = parse_str(r#"{ "led": { "packet_id": 40, "msg_type": "bool" } }"#).unwrap();
+        let err = merge_message_sets(vec![base, app]).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("message 'led' has packet_id 40 above max_address 31"),
+            "{}",
+            err
+        );
     }
 
     #[test]
-    fn test_array_without_max_length_fails() {
-        let json = json!({
-            "packets": {
-                "temperatures": {
-                    "packet_id": 20,
-                    "msg_type": "float32",
-                    "array": true
-                }
-            }
-        });
+    fn test_packet_id_width() {
+        let err = parse_str(r#"{ "a": { "packet_id": 300, "msg_type": "u8" } }"#).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "/a/packet_id: message 'a' has packet_id 300 which exceeds maximum of 255 for 8-bit packet IDs (from default)"
+        );
 
-        let obj = json.as_object().unwrap();
-        let result = parse_messages(obj);
-        assert!(result.is_err());
+        let (metadata, messages) = parse_str(
+            r#"{ "packet_id_width": 16, "max_address": 4096, "reserved_ids": [[256, 511]],
+                 "a": { "packet_id": 4000, "msg_type": "u8" } }"#,
+        )
+        .unwrap();
+        assert_eq!(metadata.packet_id_width, 16);
+        assert_eq!(metadata.reserved_ids, [(256, 511)]);
+        assert_eq!(messages[0].packet_id, 4000);
+
+        let err = parse_str(
+            r#"{ "packet_id_width": 16, "a": { "packet_id": 70000, "msg_type": "u8" } }"#,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "/a/packet_id: message 'a' has packet_id 70000 which exceeds maximum of 65535 for 16-bit packet IDs (from metadata 'packet_id_width')"
+        );
+        let err = parse_str(
+            r#"{ "packet_id_width": 16, "max_address": 511,
+                 "a": { "packet_id": 600, "msg_type": "u8" } }"#,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "/a/packet_id: message 'a' has packet_id 0x0258 (600) above max_address 0x01FF (511)"
+        );
+
+        let messages_of = |input: &str| {
+            parse_messages_with_diagnostics(
+                serde_json::from_str::<Value>(input)
+                    .unwrap()
+                    .as_object()
+                    .unwrap(),
+                &ParseOptions::default(),
+            )
+            .unwrap_err()
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            messages_of(
+                r#"{ "packet_id_width": 12, "max_address": 300, "reserved_ids": [300],
+                     "a": { "packet_id": 1, "msg_type": "u8" } }"#
+            ),
+            [
+                "/packet_id_width: packet_id_width 12 is not supported, expected 8 or 16",
+                "/max_address: max_address 300 exceeds the largest 8-bit packet ID 255",
+                "/reserved_ids/0: reserved IDs must be packet IDs 0-255 or [first, last] ranges of them",
+            ]
+        );
     }
 
     #[test]
-    fn test_struct_without_fields_fails() {
-        let json = json!({
-            "packets": {
-                "sensor_data": {
-                    "packet_id": 30,
-                    "msg_type": "struct"
-                }
-            }
-        });
-
-        let obj = json.as_object().unwrap();
-        let result = parse_messages(obj);
-        assert!(result.is_err());
+    fn test_integer_literals() {
+        let (metadata, messages) = parse_str(
+            r#"{ "max_address": "0x7F", "reserved_ids": ["0x70", ["0b1111000", "127"]],
+                 "packets": {
+                     "hex": { "packet_id": "0x2A", "msg_type": "u8", "array": true,
+                              "max_length": "0x10", "sector_bytes": "0b100" },
+                     "bin": { "packet_id": "0b101", "msg_type": "struct", "fields": {
+                         "data": { "type": "u8", "array": true, "fixed_length": "0X8" } } },
+                     "dec": { "packet_id": "7", "msg_type": "bytes", "length": "12" },
+                     "num": { "packet_id": 9, "msg_type": "union", "tag": "u8", "variants": {
+                         "0x02": { "name": "two", "fields": { "a": { "type": "u8" } } } } } } }"#,
+        )
+        .unwrap();
+        assert_eq!(metadata.max_address, Some(127));
+        assert_eq!(metadata.reserved_ids, [(112, 112), (120, 127)]);
+        let radixes: Vec<(u32, Radix)> = messages
+            .iter()
+            .map(|m| (m.packet_id, m.packet_id_radix))
+            .collect();
+        assert_eq!(
+            radixes,
+            [
+                (5, Radix::Binary),
+                (7, Radix::Decimal),
+                (9, Radix::Decimal),
+                (42, Radix::Hex)
+            ]
+        );
+        let MessageBody::Array(array) = &messages[3].body else {
+            panic!("expected an array");
+        };
+        assert_eq!((array.max_length, array.sector_bytes), (16, Some(4)));
+        let MessageBody::Union(union) = &messages[2].body else {
+            panic!("expected a union");
+        };
+        assert_eq!(union.variants[0].tag, 2);
+
+        let errors = |input: &str| -> Vec<String> {
+            parse_messages_with_diagnostics(
+                serde_json::from_str::<Value>(input)
+                    .unwrap()
+                    .as_object()
+                    .unwrap(),
+                &ParseOptions::default(),
+            )
+            .unwrap_err()
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+        };
+        assert_eq!(
+            errors(
+                r#"{ "a": { "packet_id": "0x2G", "msg_type": "u8" },
+                     "b": { "packet_id": "forty", "msg_type": "u8" },
+                     "c": { "packet_id": "0x", "msg_type": "u8" },
+                     "d": { "packet_id": "+5", "msg_type": "u8" },
+                     "e": { "packet_id": "0x10000000000000000", "msg_type": "u8" },
+                     "f": { "packet_id": "0x100", "msg_type": "u8" },
+                     "g": { "packet_id": 1, "msg_type": "u8", "array": true, "max_length": "0b2" } }"#
+            ),
+            [
+                "/a/packet_id: 'packet_id' \"0x2G\" is not an integer literal (expected decimal digits, or 0x hex or 0b binary digits)",
+                "/b/packet_id: 'packet_id' \"forty\" is not an integer literal (expected decimal digits, or 0x hex or 0b binary digits)",
+                "/c/packet_id: 'packet_id' \"0x\" is not an integer literal (expected decimal digits, or 0x hex or 0b binary digits)",
+                "/d/packet_id: 'packet_id' \"+5\" is not an integer literal",
+                "/e/packet_id: 'packet_id' \"0x10000000000000000\" does not fit in 64 bits",
+                "/f/packet_id: message 'f' has packet_id 256 which exceeds maximum of 255 for 8-bit packet IDs (from default)",
+                "/g/max_length: 'max_length' \"0b2\" is not an integer literal (expected decimal digits, or 0x hex or 0b binary digits)",
+            ]
+        );
     }
 
     #[test]
-    fn test_struct_with_empty_fields_fails() {
+    fn test_parse_command_groups() {
+        let (metadata, _) = parse_str(
+            r#"{ "command_groups": [
+                     { "name": "System", "range": [0, 63] },
+                     { "name": "Debug", "range": 255 } ],
+                 "ping": { "packet_id": 0, "msg_type": "u8" } }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            metadata.command_groups,
+            [
+                CommandGroup {
+                    name: "System".to_string(),
+                    first: 0,
+                    last: 63
+                },
+                CommandGroup {
+                    name: "Debug".to_string(),
+                    first: 255,
+                    last: 255
+                },
+            ]
+        );
+
         let json = json!({
-            "packets": {
-                "sensor_data": {
-                    "packet_id": 30,
-                    "msg_type": "struct",
-                    "fields": {}
-                }
-            }
+            "command_groups": [
+                { "name": "System", "range": [0, 63] },
+                { "name": "Application", "range": [60, 255] },
+                { "range": [64, 70] },
+                { "name": "", "range": [71, 72] },
+                { "name": "Late", "range": [90, 80] },
+                "Misc"
+            ],
+            "ping": { "packet_id": 0, "msg_type": "u8" }
         });
-
-        let obj = json.as_object().unwrap();
-        let result = parse_messages(obj);
-        assert!(result.is_err());
+        let messages: Vec<String> =
+            parse_messages_with_diagnostics(json.as_object().unwrap(), &ParseOptions::default())
+                .unwrap_err()
+                .iter()
+                .map(|e| e.to_string())
+                .collect();
+        assert_eq!(
+            messages,
+            [
+                "/command_groups/1/range: command group 'Application' (60-255) overlaps command group 'System' (0-63)",
+                "/command_groups/2: command group is missing 'name'",
+                "/command_groups/3/name: command group 'name' must be a non-empty string",
+                "/command_groups/4/range: command group range [90, 80] ends before it starts",
+                "/command_groups/5: a command group must be an object with 'name' and 'range'",
+            ]
+        );
     }
 
     #[test]
-    fn test_parse_devices() {
-        let json = json!({
-            "version": "1.0.0",
-            "devices": {
-                "device A": {
-                    "role": "server"
-                },
-                "device B": {
-                    "role": "client",
-                    "id": 1
-                }
-            },
-            "packets": {
-                "ping": {
-                    "packet_id": 0,
-                    "msg_type": "uint8",
-                    "array": false
-                }
-            }
-        });
+    fn test_write_atomic_replaces_the_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("proto.h");
+        write_atomic(&path, "old").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+        }
+        write_atomic(&path, "new").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o640);
+        }
+        assert!(!temp_path(&path).exists());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
 
-        let obj = json.as_object().unwrap();
-        let (metadata, messages) = parse_messages(obj).unwrap();
+    #[test]
+    fn test_regenerate_command() {
+        let args = [
+            "--readonly-output",
+            "--force",
+            "--manifest",
+            "out/manifest.json",
+            "--check",
+            "my msgs.json",
+            "out",
+        ]
+        .map(String::from);
+        assert_eq!(
+            regenerate_command(args),
+            "h6xserial_idl --readonly-output 'my msgs.json' out"
+        );
+        assert_eq!(shell_word("it's"), "'it'\\''s'");
+    }
 
-        assert_eq!(metadata.version, Some("1.0.0".to_string()));
-        assert_eq!(metadata.devices.len(), 2);
-        assert_eq!(messages.len(), 1);
+    #[test]
+    fn test_fields_keep_declaration_order() {
+        // Without serde_json's `preserve_order` these would come out sorted
+        let order = |input: &str| {
+            let (metadata, messages) = parse_str(input).unwrap();
+            let header = emit_c::generate(
+                &metadata,
+                &messages,
+                Path::new("pose.json"),
+                Path::new("pose.h"),
+            )
+            .unwrap();
+            let encode = &header[header.find("offset = 0").unwrap()..];
+            let mut names = ["zeta", "alpha", "mid"];
+            names.sort_by_key(|name| encode.find(&format!("msg->{}", name)).unwrap());
+            names
+        };
+        let fields = r#""fields": { "zeta": { "type": "u16" }, "alpha": { "type": "u8" },
+                                    "mid": { "type": "f32" } }"#;
+        assert_eq!(
+            order(&format!(
+                r#"{{ "packets": {{ "pose": {{ "packet_id": 1, "msg_type": "struct", {} }} }} }}"#,
+                fields
+            )),
+            ["zeta", "alpha", "mid"]
+        );
+        assert_eq!(
+            order(&format!(
+                r#"{{ "packets": {{ "pose": {{ "packet_id": 1, "msg_type": "struct", {},
+                     "field_order": ["mid", "zeta", "alpha"] }} }} }}"#,
+                fields
+            )),
+            ["mid", "zeta", "alpha"]
+        );
+    }
 
-        let server = metadata.devices.iter().find(|d| d.role == "server");
-        assert!(server.is_some());
-        assert_eq!(server.unwrap().name, "device A");
-        assert_eq!(server.unwrap().id, None);
+    #[test]
+    fn test_field_order_errors() {
+        let json = json!({ "packets": {
+            "pose": { "packet_id": 1, "msg_type": "struct",
+                      "fields": { "x": { "type": "u8" }, "y": { "type": "u8" },
+                                  "z": { "type": "u8" } },
+                      "field_order": ["y", "w", "y"] },
+            "wrap": { "packet_id": 2, "msg_type": "struct",
+                      "fields": { "a": { "type": "u8" } },
+                      "field_order": "a" }
+        }});
+        let messages: Vec<String> =
+            parse_messages_with_diagnostics(json.as_object().unwrap(), &ParseOptions::default())
+                .unwrap_err()
+                .iter()
+                .map(|e| e.to_string())
+                .collect();
+        assert_eq!(
+            messages,
+            [
+                "/packets/pose/field_order/1: 'field_order' lists 'w', which is not in 'fields'",
+                "/packets/pose/field_order/2: field 'y' is listed twice in 'field_order'",
+                "/packets/pose/field_order: 'field_order' leaves out 'x', 'z'",
+                "/packets/wrap/field_order: 'field_order' must be an array of field names",
+            ]
+        );
+    }
 
-        let client = metadata.devices.iter().find(|d| d.role == "client");
-        assert!(client.is_some());
-        assert_eq!(client.unwrap().name, "device B");
-        assert_eq!(client.unwrap().id, Some(1));
+    #[test]
+    fn test_at_inserts_reserved_gaps() {
+        let (_, messages) = parse_str(
+            r#"{ "status": { "packet_id": 1, "msg_type": "struct", "fields": {
+                     "mode": { "type": "u8" },
+                     "speed": { "type": "u16", "at": 4 },
+                     "flags": { "type": "u8", "at": 6 } } } }"#,
+        )
+        .unwrap();
+        let MessageBody::Struct(spec) = &messages[0].body else {
+            panic!("expected a struct");
+        };
+        let fields: Vec<_> = spec
+            .fields
+            .iter()
+            .map(|f| (f.name.as_str(), f.at, f.reserved))
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                ("mode", None, false),
+                ("_reserved_0", None, true),
+                ("speed", Some(4), false),
+                ("flags", Some(6), false),
+            ]
+        );
+        assert!(matches!(
+            spec.fields[1].field_type,
+            StructFieldType::Bytes(3)
+        ));
+        assert_eq!(emit_c::struct_byte_len(spec), 7);
     }
 
     #[test]
-    fn test_missing_packets_fails() {
-        let json = json!({
-            "version": "1.0.0"
-        });
+    fn test_at_errors() {
+        let json = json!({ "packets": {
+            "overlap": { "packet_id": 1, "msg_type": "struct",
+                         "fields": { "a": { "type": "u32" }, "b": { "type": "u8", "at": 2 } } },
+            "after_array": { "packet_id": 2, "msg_type": "struct",
+                             "fields": { "n": { "type": "u8", "array": true, "max_length": 4 },
+                                         "b": { "type": "u8", "at": 8 } } },
+            "taken": { "packet_id": 3, "msg_type": "struct",
+                       "fields": { "_reserved_0": { "type": "u8" },
+                                   "b": { "type": "u8", "at": 4 } } },
+            "negative": { "packet_id": 4, "msg_type": "struct",
+                          "fields": { "b": { "type": "u8", "at": -1 } } }
+        }});
+        let messages: Vec<String> =
+            parse_messages_with_diagnostics(json.as_object().unwrap(), &ParseOptions::default())
+                .unwrap_err()
+                .iter()
+                .map(|e| e.to_string())
+                .collect();
+        assert_eq!(
+            messages,
+            [
+                "/packets/overlap/fields/b/at: field 'b' in 'overlap' has 'at' 2, which overlaps the fields before it (they end at byte 4)",
+                "/packets/after_array/fields/b/at: field 'b' in 'after_array' has 'at' 8 but a variable-length field comes before it",
+                "/packets/taken/fields/b/at: the gap before field 'b' in 'taken' needs the name '_reserved_0', which is taken",
+                "/packets/negative/fields/b/at: 'at' of field 'b' in 'negative' must be a byte offset",
+            ]
+        );
+    }
 
-        let obj = json.as_object().unwrap();
-        let result = parse_messages(obj);
-        assert!(result.is_err());
+    #[test]
+    fn test_group_errors() {
+        let err = parse_str(r#"{ "ping": { "packet_id": 1, "msg_type": "u8", "group": "--" } }"#)
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            err,
+            "/ping/group: group '--' of message 'ping' contains no C identifier characters"
+        );
+        let err = parse_str(
+            r#"{ "ping": { "packet_id": 1, "msg_type": "u8", "group": "Motor" },
+                 "stop": { "packet_id": 2, "msg_type": "u8", "group": "motor" } }"#,
+        )
+        .unwrap_err()
+        .to_string();
+        assert_eq!(
+            err,
+            "/stop/group: group 'motor' of message 'stop' maps to the same C identifier 'motor' as group 'Motor'"
+        );
+        let err = parse_str(r#"{ "ping": { "packet_id": 1, "msg_type": "u8", "group": 3 } }"#)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("message 'ping' has invalid 'group' (must be a string)"));
     }
 }
@@ -0,0 +1,281 @@
+//! Message table printed by `--list`.
+//!
+//! One row per message with its packet ID, shape, wire size and byte
+//! order, so the IDs in use can be checked without reading the JSON.
+
+use crate::emit_c::{struct_byte_len, struct_min_byte_len};
+use crate::{
+    Endian, MessageBody, MessageDefinition, Metadata, StructFieldType, StructSpec, format_id_range,
+    max_packet_id, message_body_max_size, message_body_min_size,
+};
+
+/// Row order of the table.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub(crate) enum SortKey {
+    #[default]
+    #[value(alias = "packet_id")]
+    Id,
+    Name,
+    Size,
+}
+
+/// Minimum and maximum encoded size of a message body in bytes.
+fn wire_size(body: &MessageBody) -> (usize, usize) {
+    match body {
+        MessageBody::Scalar(spec) => (spec.primitive.byte_len(), spec.primitive.byte_len()),
+        MessageBody::Array(spec) => (0, spec.max_length * spec.primitive.byte_len()),
+        MessageBody::Struct(spec) => (struct_min_byte_len(spec), struct_byte_len(spec)),
+        MessageBody::Union(_) => (message_body_min_size(body), message_body_max_size(body)),
+    }
+}
+
+/// `little`, `big`, `mixed`, or `-` when every value is a single byte.
+fn endian_summary(body: &MessageBody) -> &'static str {
+    fn collect(spec: &StructSpec, out: &mut Vec<Endian>) {
+        for field in &spec.fields {
+            match &field.field_type {
+                StructFieldType::Primitive(prim) if prim.byte_len() > 1 => out.push(field.endian),
+                StructFieldType::Array(arr) if arr.primitive.byte_len() > 1 => {
+                    out.push(field.endian)
+                }
+                StructFieldType::Nested(nested) => collect(nested, out),
+                _ => {}
+            }
+        }
+    }
+
+    let mut endians = Vec::new();
+    match body {
+        MessageBody::Scalar(spec) if spec.primitive.byte_len() > 1 => endians.push(spec.endian),
+        MessageBody::Array(spec) if spec.primitive.byte_len() > 1 => endians.push(spec.endian),
+        MessageBody::Struct(spec) => collect(spec, &mut endians),
+        MessageBody::Union(spec) => {
+            if spec.tag.byte_len() > 1 {
+                endians.push(spec.endian);
+            }
+            for variant in &spec.variants {
+                collect(&variant.spec, &mut endians);
+            }
+        }
+        _ => {}
+    }
+    match endians.first() {
+        None => "-",
+        Some(first) if endians.iter().any(|e| e != first) => "mixed",
+        Some(Endian::Little) => "little",
+        Some(Endian::Big) => "big",
+    }
+}
+
+/// Renders the messages whose name contains `filter` (case-insensitive)
+/// as a column-aligned table.
+pub(crate) fn render_table(
+    messages: &[MessageDefinition],
+    sort: SortKey,
+    filter: Option<&str>,
+) -> String {
+    let filter = filter.map(str::to_ascii_lowercase);
+    let mut selected: Vec<&MessageDefinition> = messages
+        .iter()
+        .filter(|m| {
+            filter
+                .as_deref()
+                .is_none_or(|f| m.name.to_ascii_lowercase().contains(f))
+        })
+        .collect();
+    match sort {
+        SortKey::Id => selected.sort_by_key(|m| m.packet_id),
+        SortKey::Name => selected.sort_by(|a, b| a.name.cmp(&b.name)),
+        SortKey::Size => selected.sort_by_key(|m| {
+            let (min, max) = wire_size(&m.body);
+            (max, min, m.packet_id)
+        }),
+    }
+
+    let mut rows = vec![["ID", "NAME", "KIND", "SIZE", "ENDIAN", "DESCRIPTION"].map(String::from)];
+    for message in selected {
+        let size = match wire_size(&message.body) {
+            (min, max) if min == max => max.to_string(),
+            (min, max) => format!("{}-{}", min, max),
+        };
+        rows.push([
+            message.packet_id.to_string(),
+            message.name.clone(),
+            message.body.kind().to_string(),
+            size,
+            endian_summary(&message.body).to_string(),
+            message.description.clone().unwrap_or_default(),
+        ]);
+    }
+
+    let mut widths = [0usize; 6];
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let mut out = String::new();
+    for row in &rows {
+        let mut line = String::new();
+        for (i, cell) in row.iter().enumerate() {
+            if i > 0 {
+                line.push_str("  ");
+            }
+            // The ID and size columns are right-aligned
+            match i {
+                0 | 3 => line.push_str(&format!("{:>1$}", cell, widths[i])),
+                _ => line.push_str(&format!("{:<1$}", cell, widths[i])),
+            }
+        }
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+/// Summarises the packet ID space `0..=max_address` (the largest ID of the
+/// `packet_id_width` without one): how many IDs are used, reserved and
+/// free, and the largest contiguous run of free IDs.
+pub(crate) fn render_id_space(metadata: &Metadata, messages: &[MessageDefinition]) -> String {
+    let last = metadata
+        .max_address
+        .unwrap_or(u32::MAX)
+        .min(max_packet_id(metadata.packet_id_width));
+    let used = |id: u32| messages.iter().any(|m| m.packet_id == id);
+    let reserved = |id: u32| {
+        metadata
+            .reserved_ids
+            .iter()
+            .any(|&(first, last)| (first..=last).contains(&id))
+    };
+
+    let (mut used_count, mut reserved_count, mut free_count) = (0, 0, 0);
+    let mut largest: Option<(u32, u32)> = None;
+    let mut run_start = None;
+    for id in 0..=last {
+        let free = if used(id) {
+            used_count += 1;
+            false
+        } else if reserved(id) {
+            reserved_count += 1;
+            false
+        } else {
+            free_count += 1;
+            true
+        };
+        if free {
+            let start = *run_start.get_or_insert(id);
+            if largest.is_none_or(|(first, last)| id - start > last - first) {
+                largest = Some((start, id));
+            }
+        } else {
+            run_start = None;
+        }
+    }
+
+    let mut out = format!("Packet IDs 0-{}: {} used", last, used_count);
+    if reserved_count > 0 {
+        out.push_str(&format!(", {} reserved", reserved_count));
+    }
+    out.push_str(&format!(", {} free\n", free_count));
+    match largest {
+        Some((first, last)) => out.push_str(&format!(
+            "Largest free range: {} ({} ID{})\n",
+            format_id_range(first, last),
+            last - first + 1,
+            if first == last { "" } else { "s" }
+        )),
+        None => out.push_str("Largest free range: none\n"),
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+
+    fn messages() -> Vec<MessageDefinition> {
+        let (_, messages) = parse_str(
+            r#"{
+                "packets": {
+                    "ping": { "packet_id": 2, "msg_type": "uint8", "msg_desc": "Liveness check" },
+                    "log": { "packet_id": 1, "msg_type": "uint16", "array": true, "max_length": 8, "endianness": "big" },
+                    "pose": {
+                        "packet_id": 10,
+                        "msg_type": "struct",
+                        "fields": {
+                            "x": { "type": "float32" },
+                            "y": { "type": "float32", "endianness": "big" },
+                            "tags": { "type": "uint8", "array": true, "max_length": 4 }
+                        }
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+        messages
+    }
+
+    #[test]
+    fn test_render_table_columns_and_sizes() {
+        assert_eq!(
+            render_table(&messages(), SortKey::Id, None),
+            "ID  NAME  KIND    SIZE  ENDIAN  DESCRIPTION\n\
+             \x201  log   array   0-16  big\n\
+             \x202  ping  scalar     1  -       Liveness check\n\
+             10  pose  struct  8-12  mixed\n"
+        );
+    }
+
+    #[test]
+    fn test_render_table_sort_and_filter() {
+        let names = |table: String| -> Vec<String> {
+            table
+                .lines()
+                .skip(1)
+                .map(|line| line.split_whitespace().nth(1).unwrap().to_string())
+                .collect()
+        };
+        assert_eq!(
+            names(render_table(&messages(), SortKey::Name, None)),
+            ["log", "ping", "pose"]
+        );
+        assert_eq!(
+            names(render_table(&messages(), SortKey::Size, None)),
+            ["ping", "pose", "log"]
+        );
+        assert_eq!(
+            names(render_table(&messages(), SortKey::Id, Some("PO"))),
+            ["pose"]
+        );
+        assert!(<SortKey as clap::ValueEnum>::from_str("weight", true).is_err());
+    }
+
+    #[test]
+    fn test_render_id_space() {
+        let (metadata, messages) = parse_str(
+            r#"{
+                "max_address": 15,
+                "reserved_ids": [[4, 5]],
+                "a": { "packet_id": 0, "msg_type": "uint8" },
+                "b": { "packet_id": 3, "msg_type": "uint8" },
+                "c": { "packet_id": 12, "msg_type": "uint8" }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            render_id_space(&metadata, &messages),
+            "Packet IDs 0-15: 3 used, 2 reserved, 11 free\n\
+             Largest free range: 6-11 (6 IDs)\n"
+        );
+
+        let (metadata, messages) =
+            parse_str(r#"{ "max_address": 1, "a": { "packet_id": 1, "msg_type": "u8" } }"#)
+                .unwrap();
+        assert_eq!(
+            render_id_space(&metadata, &messages),
+            "Packet IDs 0-1: 1 used, 1 free\nLargest free range: 0 (1 ID)\n"
+        );
+    }
+}
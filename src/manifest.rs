@@ -0,0 +1,136 @@
+//! The `--manifest` file: a JSON record of what one run read and wrote.
+//!
+//! Build systems compare it between runs instead of hashing the outputs
+//! themselves. It lists every input file, includes too, and every output
+//! file with its byte length and SHA-256; the C files of a role and the
+//! per-client documents also name their role and client ID.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde_json::{Value, json};
+
+use crate::display_path;
+use crate::emit_c::Role;
+use crate::fingerprint::sha256_hex;
+
+/// Layout version of the manifest, raised on incompatible changes.
+const MANIFEST_VERSION: u32 = 1;
+
+/// One file written, or kept because it already matched.
+pub(crate) struct OutputEntry {
+    pub(crate) path: PathBuf,
+    pub(crate) content: String,
+    /// `languages` name of the target that generated it
+    pub(crate) target: &'static str,
+    pub(crate) role: Option<Role>,
+}
+
+/// Renders the manifest of a run that read `inputs` and produced `outputs`,
+/// hashing the inputs as they are on disk now. `manifest_path` is left out
+/// of the outputs.
+pub(crate) fn render(
+    inputs: &[PathBuf],
+    ir_fingerprint: &str,
+    outputs: &[OutputEntry],
+    manifest_path: &Path,
+) -> Result<String> {
+    let inputs = inputs
+        .iter()
+        .map(|path| {
+            let data = fs::read(path)
+                .with_context(|| format!("failed to hash input {}", path.display()))?;
+            Ok(json!({
+                "path": display_path(path),
+                "sha256": sha256_hex(&data),
+            }))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let outputs: Vec<Value> = outputs
+        .iter()
+        .filter(|output| !same_file(&output.path, manifest_path))
+        .map(|output| {
+            let (role, client_id) = match output.role {
+                Some(Role::Server) => (json!("server"), Value::Null),
+                Some(Role::ClientCommon) => (json!("client_common"), Value::Null),
+                Some(Role::Client(id)) => (json!("client"), json!(id)),
+                None => (Value::Null, Value::Null),
+            };
+            json!({
+                "path": display_path(&output.path),
+                "target": output.target,
+                "role": role,
+                "client_id": client_id,
+                "bytes": output.content.len(),
+                "sha256": sha256_hex(output.content.as_bytes()),
+            })
+        })
+        .collect();
+    let manifest = json!({
+        "manifest_version": MANIFEST_VERSION,
+        "generator": {
+            "name": env!("CARGO_PKG_NAME"),
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "ir_fingerprint": ir_fingerprint,
+        "inputs": inputs,
+        "outputs": outputs,
+    });
+    let mut text = serde_json::to_string_pretty(&manifest)?;
+    text.push('\n');
+    Ok(text)
+}
+
+/// Whether `a` and `b` name the same file, however each is spelled.
+fn same_file(a: &Path, b: &Path) -> bool {
+    let resolve = |path: &Path| {
+        let parent = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty());
+        let dir = fs::canonicalize(parent.unwrap_or(Path::new("."))).ok()?;
+        Some(dir.join(path.file_name()?))
+    };
+    a == b || resolve(a).is_some_and(|a| Some(a) == resolve(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_skips_the_manifest_itself() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let input = dir.path().join("msgs.json");
+        fs::write(&input, "abc").unwrap();
+        let entry = |name: &str, role| OutputEntry {
+            path: dir.path().join(name),
+            content: "x".to_string(),
+            target: "c",
+            role,
+        };
+        let outputs = [
+            entry("proto_client_3.h", Some(Role::Client(3))),
+            entry("manifest.json", None),
+        ];
+        let text = render(
+            &[input],
+            "0123",
+            &outputs,
+            &dir.path().join("manifest.json"),
+        )
+        .unwrap();
+        let manifest: Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(manifest["manifest_version"], 1);
+        assert_eq!(manifest["ir_fingerprint"], "0123");
+        assert_eq!(
+            manifest["inputs"][0]["sha256"],
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        let outputs = manifest["outputs"].as_array().unwrap();
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(outputs[0]["role"], "client");
+        assert_eq!(outputs[0]["client_id"], 3);
+        assert_eq!(outputs[0]["bytes"], 1);
+    }
+}
@@ -0,0 +1,65 @@
+static {{inline}} void {{prefix}}_write_f16_be(float value, uint8_t *out) {
+    uint32_t f;
+    uint32_t sign;
+    uint32_t exponent;
+    uint32_t mantissa;
+    uint32_t half;
+    uint32_t shift;
+    uint32_t remainder;
+    uint32_t halfway;
+    memcpy(&f, &value, sizeof(uint32_t));
+    sign = (f >> 16) & 0x8000u;
+    exponent = (f >> 23) & 0xFFu;
+    mantissa = f & 0x7FFFFFu;
+    if (exponent == 0xFFu) {
+        /* Infinity, or NaN with the quiet bit set so it stays a NaN */
+        half = (mantissa == 0u) ? 0x7C00u : (0x7E00u | (mantissa >> 13));
+    } else if (exponent > 142u) {
+        half = 0x7C00u;
+    } else if (exponent > 112u) {
+        half = ((exponent - 112u) << 10) | (mantissa >> 13);
+        remainder = mantissa & 0x1FFFu;
+        if (remainder > 0x1000u || (remainder == 0x1000u && (half & 1u) != 0u)) {
+            half += 1u;
+        }
+    } else if (exponent > 101u) {
+        /* Subnormal */
+        shift = 126u - exponent;
+        mantissa |= 0x800000u;
+        half = mantissa >> shift;
+        remainder = mantissa & ((((uint32_t)1u) << shift) - 1u);
+        halfway = ((uint32_t)1u) << (shift - 1u);
+        if (remainder > halfway || (remainder == halfway && (half & 1u) != 0u)) {
+            half += 1u;
+        }
+    } else {
+        half = 0u;
+    }
+    {{prefix}}_write_u16_be((uint16_t)(sign | half), out);
+}
+
+static {{inline}} float {{prefix}}_read_f16_be(const uint8_t *in) {
+    uint32_t half = (uint32_t){{prefix}}_read_u16_be(in);
+    uint32_t sign = (half & 0x8000u) << 16;
+    uint32_t exponent = (half >> 10) & 0x1Fu;
+    uint32_t mantissa = half & 0x3FFu;
+    uint32_t f;
+    float value;
+    if (exponent == 0x1Fu) {
+        f = sign | 0x7F800000u | (mantissa << 13);
+    } else if (exponent != 0u) {
+        f = sign | ((exponent + 112u) << 23) | (mantissa << 13);
+    } else if (mantissa != 0u) {
+        /* Subnormal: normalize into the wider exponent range */
+        exponent = 113u;
+        while ((mantissa & 0x400u) == 0u) {
+            mantissa <<= 1;
+            exponent -= 1u;
+        }
+        f = sign | (exponent << 23) | ((mantissa & 0x3FFu) << 13);
+    } else {
+        f = sign;
+    }
+    memcpy(&value, &f, sizeof(float));
+    return value;
+}
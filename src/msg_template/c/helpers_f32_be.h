@@ -0,0 +1,12 @@
+static {{inline}} void {{prefix}}_write_f32_be(float value, uint8_t *out) {
+    uint32_t u;
+    memcpy(&u, &value, sizeof(uint32_t));
+    {{prefix}}_write_u32_be(u, out);
+}
+
+static {{inline}} float {{prefix}}_read_f32_be(const uint8_t *in) {
+    uint32_t u = {{prefix}}_read_u32_be(in);
+    float f;
+    memcpy(&f, &u, sizeof(float));
+    return f;
+}
@@ -0,0 +1,12 @@
+static {{inline}} void {{prefix}}_write_f64_le(double value, uint8_t *out) {
+    uint64_t u;
+    memcpy(&u, &value, sizeof(uint64_t));
+    {{prefix}}_write_u64_le(u, out);
+}
+
+static {{inline}} double {{prefix}}_read_f64_le(const uint8_t *in) {
+    uint64_t u = {{prefix}}_read_u64_le(in);
+    double f;
+    memcpy(&f, &u, sizeof(double));
+    return f;
+}
@@ -0,0 +1,8 @@
+static {{inline}} void {{prefix}}_write_u16_be(uint16_t value, uint8_t *out) {
+    out[0] = (uint8_t)((value >> 8) & 0xFFu);
+    out[1] = (uint8_t)(value & 0xFFu);
+}
+
+static {{inline}} uint16_t {{prefix}}_read_u16_be(const uint8_t *in) {
+    return (uint16_t)(((uint16_t)in[0] << 8) | (uint16_t)in[1]);
+}
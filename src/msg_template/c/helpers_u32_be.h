@@ -0,0 +1,13 @@
+static {{inline}} void {{prefix}}_write_u32_be(uint32_t value, uint8_t *out) {
+    out[0] = (uint8_t)((value >> 24) & 0xFFu);
+    out[1] = (uint8_t)((value >> 16) & 0xFFu);
+    out[2] = (uint8_t)((value >> 8) & 0xFFu);
+    out[3] = (uint8_t)(value & 0xFFu);
+}
+
+static {{inline}} uint32_t {{prefix}}_read_u32_be(const uint8_t *in) {
+    return ((uint32_t)in[0] << 24) |
+           ((uint32_t)in[1] << 16) |
+           ((uint32_t)in[2] << 8) |
+           ((uint32_t)in[3]);
+}
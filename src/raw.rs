@@ -0,0 +1,300 @@
+//! Typed view of the JSON input.
+//!
+//! These structs mirror the input format key for key; the parser converts
+//! them into the validated IR. Unknown keys are ignored here and reported by
+//! [`crate::validate`]. A value of the wrong JSON type does not fail the
+//! whole document: it is kept as [`Loose::Invalid`] so the parser can report
+//! it with its JSON pointer next to every other problem.
+
+use serde::Deserialize;
+use serde_json::{Map, Number, Value};
+
+use crate::validate::pointer_push;
+use crate::{IdlError, Radix};
+
+/// A value of the expected type, or whatever JSON was found instead.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum Loose<T> {
+    Valid(T),
+    Invalid(Value),
+}
+
+impl<T> Loose<T> {
+    /// The value, if it had the expected type.
+    pub(crate) fn valid(&self) -> Option<&T> {
+        match self {
+            Loose::Valid(value) => Some(value),
+            Loose::Invalid(_) => None,
+        }
+    }
+}
+
+/// Reads an optional key, failing with [`IdlError::InvalidType`] at
+/// `<pointer>/<key>` when it holds the wrong JSON type.
+pub(crate) fn typed<'a, T>(
+    value: &'a Option<Loose<T>>,
+    pointer: &str,
+    key: &str,
+    message: impl FnOnce() -> String,
+) -> Result<Option<&'a T>, IdlError> {
+    match value {
+        None => Ok(None),
+        Some(Loose::Valid(value)) => Ok(Some(value)),
+        Some(Loose::Invalid(found)) => Err(IdlError::invalid_type(
+            &pointer_push(pointer, key),
+            found,
+            message(),
+        )),
+    }
+}
+
+/// Like [`typed`] for an integer key, which may also be a string: decimal,
+/// `0x` hex or `0b` binary digits (see [`parse_int_literal`]). Returns the
+/// value with the radix it was written in; a malformed string fails with
+/// [`IdlError::InvalidValue`].
+pub(crate) fn typed_int(
+    value: &Option<Loose<u64>>,
+    pointer: &str,
+    key: &str,
+    message: impl FnOnce() -> String,
+) -> Result<Option<(u64, Radix)>, IdlError> {
+    match value {
+        Some(Loose::Invalid(Value::String(text))) => {
+            parse_int_literal(text).map(Some).map_err(|reason| {
+                IdlError::invalid_value(
+                    &pointer_push(pointer, key),
+                    text,
+                    format!("'{}' {}", key, reason),
+                )
+            })
+        }
+        _ => Ok(typed(value, pointer, key, message)?.map(|value| (*value, Radix::Decimal))),
+    }
+}
+
+/// Reads an integer literal written as a string: decimal digits, or hex
+/// digits after `0x` or binary digits after `0b`. The error says what is
+/// wrong with it.
+pub(crate) fn parse_int_literal(text: &str) -> Result<(u64, Radix), String> {
+    let prefixed = |lower: &str, upper: &str| {
+        text.strip_prefix(lower)
+            .or_else(|| text.strip_prefix(upper))
+    };
+    let (digits, radix) = if let Some(digits) = prefixed("0x", "0X") {
+        (digits, Radix::Hex)
+    } else if let Some(digits) = prefixed("0b", "0B") {
+        (digits, Radix::Binary)
+    } else {
+        (text, Radix::Decimal)
+    };
+    let base = match radix {
+        Radix::Decimal => 10,
+        Radix::Hex => 16,
+        Radix::Binary => 2,
+    };
+    // from_str_radix takes a leading '+', which no literal has
+    if digits.starts_with('+') {
+        return Err(format!("\"{}\" is not an integer literal", text));
+    }
+    u64::from_str_radix(digits, base)
+        .map(|value| (value, radix))
+        .map_err(|error| match error.kind() {
+            std::num::IntErrorKind::PosOverflow => {
+                format!("\"{}\" does not fit in 64 bits", text)
+            }
+            _ => format!(
+                "\"{}\" is not an integer literal (expected decimal digits, or 0x hex or 0b binary digits)",
+                text
+            ),
+        })
+}
+
+/// Top-level protocol metadata. Message definitions are collected
+/// separately because their location depends on the layout.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct RawMetadata {
+    pub(crate) ir_version: Option<Loose<u64>>,
+    pub(crate) version: Option<Loose<String>>,
+    pub(crate) max_address: Option<Loose<u64>>,
+    pub(crate) packet_id_width: Option<Loose<u64>>,
+    pub(crate) devices: Option<Loose<Map<String, Value>>>,
+    pub(crate) max_payload_bytes: Option<Loose<u64>>,
+    pub(crate) max_array_length: Option<Loose<u64>>,
+    pub(crate) max_nesting_depth: Option<Loose<u64>>,
+    pub(crate) default_endianness: Option<Loose<String>>,
+    pub(crate) include: Option<Loose<Vec<String>>>,
+}
+
+/// One entry of the `devices` object.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct RawDevice {
+    pub(crate) role: Option<Loose<String>>,
+    pub(crate) id: Option<Loose<u64>>,
+    pub(crate) description: Option<Loose<String>>,
+}
+
+/// A message definition. The shape follows from the keys: `msg_type:
+/// "struct"` with `fields`, a primitive `msg_type` with `array: true` and
+/// `max_length` or `fixed_length`, `msg_type: "bytes"` with `length`,
+/// `msg_type: "union"` with `tag` and `variants`, or a plain primitive
+/// scalar.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct RawMessage {
+    pub(crate) packet_id: Option<Loose<u64>>,
+    pub(crate) msg_type: Option<Loose<String>>,
+    pub(crate) msg_desc: Option<Loose<String>>,
+    pub(crate) desc: Option<Loose<String>>,
+    pub(crate) unit: Option<Loose<String>>,
+    pub(crate) request_type: Option<Loose<String>>,
+    pub(crate) target_client_id: Option<Loose<i64>>,
+    pub(crate) deprecated: Option<Loose<RawDeprecated>>,
+    pub(crate) reserved_ok: Option<Loose<bool>>,
+    pub(crate) response: Option<Loose<String>>,
+    pub(crate) array: Option<Loose<bool>>,
+    pub(crate) max_length: Option<Loose<u64>>,
+    pub(crate) fixed_length: Option<Loose<u64>>,
+    pub(crate) length: Option<Loose<u64>>,
+    pub(crate) sector_bytes: Option<Loose<u64>>,
+    pub(crate) string_semantics: Option<Loose<bool>>,
+    pub(crate) endianess: Option<Loose<String>>,
+    pub(crate) endianness: Option<Loose<String>>,
+    pub(crate) fields: Option<Loose<Map<String, Value>>>,
+    pub(crate) field_order: Option<Loose<Vec<String>>>,
+    pub(crate) scale: Option<Loose<f64>>,
+    pub(crate) offset: Option<Loose<f64>>,
+    pub(crate) min: Option<Loose<Number>>,
+    pub(crate) max: Option<Loose<Number>>,
+    pub(crate) tag: Option<Loose<String>>,
+    pub(crate) variants: Option<Loose<Map<String, Value>>>,
+    pub(crate) msg_version: Option<Loose<u64>>,
+    pub(crate) group: Option<Loose<String>>,
+}
+
+/// One entry of the `variants` object of a union message, keyed by its
+/// tag value: a struct given by its `fields` or a shared `type`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct RawVariant {
+    pub(crate) name: Option<Loose<String>>,
+    #[serde(rename = "type")]
+    pub(crate) variant_type: Option<Loose<String>>,
+    pub(crate) fields: Option<Loose<Map<String, Value>>>,
+    pub(crate) field_order: Option<Loose<Vec<String>>>,
+    pub(crate) desc: Option<Loose<String>>,
+}
+
+/// `deprecated` of a message: a flag or the reason.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum RawDeprecated {
+    Flag(bool),
+    Reason(String),
+}
+
+/// A struct field definition; `type` and `msg_type` are synonyms.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct RawField {
+    #[serde(rename = "type")]
+    pub(crate) field_type: Option<Loose<String>>,
+    pub(crate) msg_type: Option<Loose<String>>,
+    pub(crate) msg_desc: Option<Loose<String>>,
+    pub(crate) desc: Option<Loose<String>>,
+    pub(crate) unit: Option<Loose<String>>,
+    pub(crate) array: Option<Loose<bool>>,
+    pub(crate) max_length: Option<Loose<u64>>,
+    pub(crate) fixed_length: Option<Loose<u64>>,
+    pub(crate) length: Option<Loose<u64>>,
+    pub(crate) string_semantics: Option<Loose<bool>>,
+    pub(crate) endianess: Option<Loose<String>>,
+    pub(crate) endianness: Option<Loose<String>>,
+    pub(crate) fields: Option<Loose<Map<String, Value>>>,
+    pub(crate) field_order: Option<Loose<Vec<String>>>,
+    pub(crate) scale: Option<Loose<f64>>,
+    pub(crate) offset: Option<Loose<f64>>,
+    pub(crate) min: Option<Loose<Number>>,
+    pub(crate) max: Option<Loose<Number>>,
+    pub(crate) storage: Option<Loose<String>>,
+    pub(crate) resolution: Option<Loose<String>>,
+    pub(crate) since_version: Option<Loose<u64>>,
+    pub(crate) at: Option<Loose<u64>>,
+}
+
+impl RawMessage {
+    /// The endianness key in use (`endianess` wins over `endianness`).
+    pub(crate) fn endian(&self) -> Option<(&'static str, &Loose<String>)> {
+        endian_key(&self.endianess, &self.endianness)
+    }
+}
+
+impl RawField {
+    /// The endianness key in use (`endianess` wins over `endianness`).
+    pub(crate) fn endian(&self) -> Option<(&'static str, &Loose<String>)> {
+        endian_key(&self.endianess, &self.endianness)
+    }
+
+    /// The type key in use (`type` wins over `msg_type`).
+    pub(crate) fn type_key(&self) -> Option<(&'static str, &Loose<String>)> {
+        match (&self.field_type, &self.msg_type) {
+            (Some(value), _) => Some(("type", value)),
+            (None, Some(value)) => Some(("msg_type", value)),
+            (None, None) => None,
+        }
+    }
+}
+
+fn endian_key<'a>(
+    endianess: &'a Option<Loose<String>>,
+    endianness: &'a Option<Loose<String>>,
+) -> Option<(&'static str, &'a Loose<String>)> {
+    match (endianess, endianness) {
+        (Some(value), _) => Some(("endianess", value)),
+        (None, Some(value)) => Some(("endianness", value)),
+        (None, None) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_wrong_types_are_kept_as_invalid() {
+        let json = json!({
+            "packet_id": "7",
+            "msg_type": "uint8",
+            "array": true,
+            "max_length": -1,
+            "endianness": "big"
+        });
+        let raw = RawMessage::deserialize(json.as_object().unwrap()).unwrap();
+        assert!(matches!(
+            raw.packet_id,
+            Some(Loose::Invalid(Value::String(_)))
+        ));
+        assert_eq!(
+            raw.msg_type.as_ref().and_then(Loose::valid).unwrap(),
+            "uint8"
+        );
+        assert!(matches!(raw.max_length, Some(Loose::Invalid(_))));
+        assert_eq!(raw.endian().unwrap().0, "endianness");
+        assert!(raw.fields.is_none());
+
+        let err = typed(&raw.packet_id, "/packets/a", "packet_id", || "bad".into()).unwrap_err();
+        assert_eq!(err.to_string(), "/packets/a/packet_id: bad");
+    }
+
+    #[test]
+    fn test_field_type_prefers_type_key() {
+        let json = json!({ "type": "int16", "msg_type": "float32" });
+        let raw = RawField::deserialize(json.as_object().unwrap()).unwrap();
+        let (key, value) = raw.type_key().unwrap();
+        assert_eq!(key, "type");
+        assert_eq!(value.valid().unwrap(), "int16");
+    }
+}
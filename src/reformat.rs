@@ -0,0 +1,323 @@
+//! `--fmt`: rewrites an input document in the canonical style.
+//!
+//! The metadata keys come first, in the order of [`METADATA_KEYS`], then the
+//! messages sorted by packet ID, inside their `packets` (or `messages`)
+//! object or at the top level as given. The keys of every message, field,
+//! union variant and device follow a fixed order, with unknown keys after
+//! the known ones. Struct fields and union variants keep their order, which
+//! is the wire order. The aliases take the spelling the README documents:
+//! `endianness` becomes `endianess` and, in fields, `msg_type` becomes
+//! `type` and `msg_desc` becomes `desc`. The result is JSON indented by two
+//! spaces with a final newline.
+
+use serde_json::{Map, Value};
+
+use crate::raw::parse_int_literal;
+use crate::{MESSAGE_WRAPPER_KEYS, METADATA_KEYS};
+
+/// Key order of a message.
+const MESSAGE_KEYS: &[&str] = &[
+    "packet_id",
+    "msg_type",
+    "msg_desc",
+    "desc",
+    "group",
+    "unit",
+    "request_type",
+    "target_client_id",
+    "response",
+    "deprecated",
+    "reserved_ok",
+    "msg_version",
+    "array",
+    "max_length",
+    "fixed_length",
+    "length",
+    "sector_bytes",
+    "string_semantics",
+    "endianess",
+    "scale",
+    "offset",
+    "min",
+    "max",
+    "tag",
+    "field_order",
+    "fields",
+    "variants",
+];
+
+/// Key order of a struct field or shared type.
+const FIELD_KEYS: &[&str] = &[
+    "type",
+    "desc",
+    "unit",
+    "array",
+    "max_length",
+    "fixed_length",
+    "length",
+    "string_semantics",
+    "endianess",
+    "storage",
+    "resolution",
+    "scale",
+    "offset",
+    "min",
+    "max",
+    "at",
+    "since_version",
+    "field_order",
+    "fields",
+];
+
+/// Key order of a union variant.
+const VARIANT_KEYS: &[&str] = &["name", "type", "desc", "field_order", "fields"];
+
+/// Key order of a device.
+const DEVICE_KEYS: &[&str] = &["role", "id", "description"];
+
+/// The canonical text of an input document.
+pub(crate) fn format_document(doc: &Map<String, Value>) -> String {
+    let mut out = Map::new();
+    for key in METADATA_KEYS {
+        let Some(value) = doc.get(*key) else {
+            continue;
+        };
+        let value = match *key {
+            "devices" => map_entries(value, |device| reorder(device.clone(), DEVICE_KEYS)),
+            "types" => map_entries(value, format_field),
+            _ => value.clone(),
+        };
+        out.insert(key.to_string(), value);
+    }
+    let wrapper = MESSAGE_WRAPPER_KEYS
+        .iter()
+        .find(|key| doc.get(**key).is_some_and(Value::is_object));
+    match wrapper {
+        Some(wrapper) => {
+            let messages = sorted_messages(doc[*wrapper].as_object().into_iter().flatten());
+            out.insert(wrapper.to_string(), Value::Object(messages));
+        }
+        None => out.extend(sorted_messages(doc.iter().filter(|(key, value)| {
+            !METADATA_KEYS.contains(&key.as_str()) && value.is_object()
+        }))),
+    }
+    // Whatever else there is keeps its place at the end
+    for (key, value) in doc {
+        if !out.contains_key(key) {
+            out.insert(key.clone(), value.clone());
+        }
+    }
+    let mut text =
+        serde_json::to_string_pretty(&Value::Object(out)).expect("serializing JSON cannot fail");
+    text.push('\n');
+    text
+}
+
+/// The messages formatted and sorted by packet ID, which may be a string
+/// literal; the sort is stable, so messages without a valid one keep their
+/// order at the end.
+fn sorted_messages<'a>(
+    entries: impl Iterator<Item = (&'a String, &'a Value)>,
+) -> Map<String, Value> {
+    let mut messages: Vec<(&String, &Value)> = entries.collect();
+    messages.sort_by_key(|(_, msg)| {
+        msg.get("packet_id")
+            .and_then(|id| {
+                id.as_u64()
+                    .or_else(|| Some(parse_int_literal(id.as_str()?).ok()?.0))
+            })
+            .map_or((1, 0), |id| (0, id))
+    });
+    messages
+        .into_iter()
+        .map(|(name, msg)| (name.clone(), format_message(msg)))
+        .collect()
+}
+
+fn format_message(msg: &Value) -> Value {
+    let Value::Object(map) = msg else {
+        return msg.clone();
+    };
+    let mut map = map.clone();
+    rename(&mut map, "endianness", "endianess");
+    if let Some(fields) = map.get_mut("fields") {
+        *fields = map_entries(fields, format_field);
+    }
+    if let Some(variants) = map.get_mut("variants") {
+        *variants = map_entries(variants, format_variant);
+    }
+    reorder(Value::Object(map), MESSAGE_KEYS)
+}
+
+fn format_field(field: &Value) -> Value {
+    let Value::Object(map) = field else {
+        return field.clone();
+    };
+    let mut map = map.clone();
+    rename(&mut map, "endianness", "endianess");
+    rename(&mut map, "msg_type", "type");
+    rename(&mut map, "msg_desc", "desc");
+    if let Some(fields) = map.get_mut("fields") {
+        *fields = map_entries(fields, format_field);
+    }
+    reorder(Value::Object(map), FIELD_KEYS)
+}
+
+fn format_variant(variant: &Value) -> Value {
+    let Value::Object(map) = variant else {
+        return variant.clone();
+    };
+    let mut map = map.clone();
+    if let Some(fields) = map.get_mut("fields") {
+        *fields = map_entries(fields, format_field);
+    }
+    reorder(Value::Object(map), VARIANT_KEYS)
+}
+
+/// Applies `format` to every entry of an object, keeping their order.
+fn map_entries(value: &Value, format: impl Fn(&Value) -> Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, entry)| (key.clone(), format(entry)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+/// Renames the alias `from` to `to`, unless both are given; the parser
+/// then reports the conflict and the formatter leaves it alone.
+fn rename(map: &mut Map<String, Value>, from: &str, to: &str) {
+    if !map.contains_key(to)
+        && let Some(value) = map.remove(from)
+    {
+        map.insert(to.to_string(), value);
+    }
+}
+
+/// The keys of `order` first, in that order, then the others as they came.
+fn reorder(value: Value, order: &[&str]) -> Value {
+    let Value::Object(mut map) = value else {
+        return value;
+    };
+    let mut out = Map::new();
+    for key in order {
+        if let Some(value) = map.remove(*key) {
+            out.insert(key.to_string(), value);
+        }
+    }
+    out.extend(map);
+    Value::Object(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Formats `input` and checks that it parses to the same IR and that
+    /// formatting again changes nothing.
+    fn round_trip(input: &str) -> String {
+        let doc: Map<String, Value> = serde_json::from_str(input).unwrap();
+        let formatted = format_document(&doc);
+        let (metadata, messages) = crate::parse_str(input).unwrap();
+        let (formatted_metadata, formatted_messages) = crate::parse_str(&formatted).unwrap();
+        assert_eq!(
+            crate::emit_ir::generate(&formatted_metadata, &formatted_messages).unwrap(),
+            crate::emit_ir::generate(&metadata, &messages).unwrap()
+        );
+        let again: Map<String, Value> = serde_json::from_str(&formatted).unwrap();
+        assert_eq!(format_document(&again), formatted);
+        formatted
+    }
+
+    #[test]
+    fn test_example_round_trip() {
+        let input = std::fs::read_to_string("example/c_usage/example.json").unwrap();
+        let formatted = round_trip(&input);
+        assert!(formatted.starts_with("{\n  \"version\": \"1.0.0\",\n"));
+    }
+
+    #[test]
+    fn test_canonical_order_and_aliases() {
+        let formatted = round_trip(
+            r#"{
+                "packets": {
+                    "pose": { "msg_type": "struct", "packet_id": 7,
+                              "fields": {
+                                  "z": { "endianness": "big", "msg_type": "i16", "msg_desc": "up" },
+                                  "x": { "unit": "m", "type": "f32" } } },
+                    "ping": { "request_type": "sub", "packet_id": 2, "msg_type": "u8" },
+                    "mode": { "packet_id": 5, "msg_type": "union", "tag": "u8",
+                              "variants": {
+                                  "2": { "fields": { "b": { "type": "u8" } }, "name": "beta" },
+                                  "1": { "name": "alpha", "fields": { "a": { "type": "u8" } } } } }
+                },
+                "devices": { "hub": { "description": "main", "role": "server" } },
+                "version": "2.0"
+            }"#,
+        );
+        let doc: Map<String, Value> = serde_json::from_str(&formatted).unwrap();
+        let keys =
+            |value: &Value| -> Vec<String> { value.as_object().unwrap().keys().cloned().collect() };
+        assert_eq!(
+            keys(&Value::Object(doc.clone())),
+            ["version", "devices", "packets"]
+        );
+        assert_eq!(keys(&doc["devices"]["hub"]), ["role", "description"]);
+        assert_eq!(keys(&doc["packets"]), ["ping", "mode", "pose"]);
+        assert_eq!(
+            keys(&doc["packets"]["ping"]),
+            ["packet_id", "msg_type", "request_type"]
+        );
+        // Fields and variants keep their wire order
+        assert_eq!(keys(&doc["packets"]["pose"]["fields"]), ["z", "x"]);
+        assert_eq!(keys(&doc["packets"]["mode"]["variants"]), ["2", "1"]);
+        assert_eq!(
+            keys(&doc["packets"]["mode"]["variants"]["2"]),
+            ["name", "fields"]
+        );
+        assert_eq!(
+            keys(&doc["packets"]["pose"]["fields"]["z"]),
+            ["type", "desc", "endianess"]
+        );
+        assert!(formatted.contains("\n    \"ping\": {\n      \"packet_id\": 2,\n"));
+    }
+
+    #[test]
+    fn test_string_packet_ids_sort_by_value() {
+        let formatted = round_trip(
+            r#"{ "b": { "packet_id": "0x10", "msg_type": "u8" },
+                 "a": { "packet_id": 3, "msg_type": "u8" },
+                 "c": { "packet_id": "0b100", "msg_type": "u8" } }"#,
+        );
+        let doc: Map<String, Value> = serde_json::from_str(&formatted).unwrap();
+        assert_eq!(doc.keys().collect::<Vec<_>>(), ["a", "c", "b"]);
+        // The literals keep their radix
+        assert_eq!(doc["b"]["packet_id"], "0x10");
+    }
+
+    #[test]
+    fn test_flat_layout_stays_flat() {
+        let formatted = round_trip(
+            r#"{ "b": { "packet_id": 3, "msg_type": "u8" }, "version": "1",
+                 "a": { "msg_type": "u8", "packet_id": 1 } }"#,
+        );
+        assert_eq!(
+            formatted,
+            concat!(
+                "{\n",
+                "  \"version\": \"1\",\n",
+                "  \"a\": {\n",
+                "    \"packet_id\": 1,\n",
+                "    \"msg_type\": \"u8\"\n",
+                "  },\n",
+                "  \"b\": {\n",
+                "    \"packet_id\": 3,\n",
+                "    \"msg_type\": \"u8\"\n",
+                "  }\n",
+                "}\n",
+            )
+        );
+    }
+}
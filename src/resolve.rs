@@ -0,0 +1,443 @@
+//! Loading documents together with the files they include.
+//!
+//! A document may list other documents in a top-level `include` array, with
+//! paths relative to its own directory. The included files are loaded first
+//! and merged with the local definitions by [`merge_message_sets`], so a
+//! local message can neither reuse the name nor the packet ID of an
+//! included one. A [`Resolver`] loads every file once, however many
+//! documents include it, and reports include cycles with the chain of files.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+use crate::validate::{ValidationWarning, pointer_push};
+use crate::{
+    IdlError, InputFormat, MessageDefinition, Metadata, ParseOptions, StructSpec, document_value,
+    merge_message_sets, parse_value,
+};
+
+type MessageSet = (Metadata, Vec<MessageDefinition>);
+
+/// Loads documents and the files they include.
+///
+/// # Example
+/// ```
+/// use h6xserial_idl::{InputFormat, ParseOptions, Resolver};
+///
+/// let dir = std::env::temp_dir().join("h6xserial_resolver_doc");
+/// std::fs::create_dir_all(&dir).unwrap();
+/// std::fs::write(
+///     dir.join("common.json"),
+///     r#"{ "packets": { "ping": { "packet_id": 1, "msg_type": "uint8" } } }"#,
+/// )
+/// .unwrap();
+///
+/// let app = r#"{
+///     "include": ["common.json"],
+///     "packets": { "led": { "packet_id": 40, "msg_type": "bool" } }
+/// }"#;
+/// let mut resolver = Resolver::new(ParseOptions::default());
+/// let (_, messages) = resolver
+///     .load_str(app, &dir.join("app.json"), InputFormat::Json)
+///     .unwrap()
+///     .unwrap();
+/// let names: Vec<&str> = messages.iter().map(|m| m.name.as_str()).collect();
+/// assert_eq!(names, ["ping", "led"]);
+///
+/// // common.json is part of the set already
+/// assert!(resolver.load_file(&dir.join("common.json")).unwrap().is_none());
+/// ```
+pub struct Resolver {
+    options: ParseOptions,
+    /// Files being loaded, outermost first: the canonical path and the
+    /// path it was reached by
+    stack: Vec<(PathBuf, PathBuf)>,
+    /// Canonical paths of the files loaded so far
+    loaded: HashSet<PathBuf>,
+    /// The same files as they were reached, in loading order
+    files: Vec<PathBuf>,
+    /// Shared types in effect in each loaded file, by canonical path, so a
+    /// file included a second time still provides them
+    types: HashMap<PathBuf, Vec<StructSpec>>,
+    warnings: Vec<(PathBuf, ValidationWarning)>,
+}
+
+impl Resolver {
+    /// Creates a resolver applying `options` to every file. The format of an
+    /// included file follows from its extension (see
+    /// [`InputFormat::from_path`]).
+    pub fn new(options: ParseOptions) -> Self {
+        Resolver {
+            options,
+            stack: Vec::new(),
+            loaded: HashSet::new(),
+            files: Vec::new(),
+            types: HashMap::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Reads and loads `path`, with the format given by its extension.
+    ///
+    /// # Returns
+    /// * `Ok(Some(set))` - The messages of the file and its includes
+    /// * `Ok(None)` - The file was loaded before, directly or as an include
+    /// * `Err(errors)` - Every problem found; those in included files are
+    ///   [`IdlError::InFile`]
+    pub fn load_file(&mut self, path: &Path) -> Result<Option<MessageSet>, Vec<IdlError>> {
+        if self.loaded.contains(&canonical(path)) {
+            return Ok(None);
+        }
+        let input = fs::read_to_string(path).map_err(|source| {
+            vec![IdlError::Read {
+                path: path.to_path_buf(),
+                source,
+            }]
+        })?;
+        self.load_str(&input, path, InputFormat::from_path(path))
+    }
+
+    /// Same as [`Resolver::load_file`] for a document already read from
+    /// `path`. Its includes are looked up next to `path`; for input from
+    /// stdin pass `-`, which resolves them against the current directory.
+    pub fn load_str(
+        &mut self,
+        input: &str,
+        path: &Path,
+        format: InputFormat,
+    ) -> Result<Option<MessageSet>, Vec<IdlError>> {
+        let key = canonical(path);
+        if !self.loaded.insert(key.clone()) {
+            return Ok(None);
+        }
+        self.files.push(path.to_path_buf());
+        self.stack.push((key.clone(), path.to_path_buf()));
+        let result = self.load_document(input, path, format);
+        self.stack.pop();
+        if let Ok((metadata, _)) = &result {
+            self.types.insert(key, metadata.types.clone());
+        }
+        result.map(Some)
+    }
+
+    /// Every file loaded so far, includes after the file naming them.
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// Unknown-key warnings found since the last call, each with the file it
+    /// is in. Outside strict mode only; strict mode makes them errors.
+    pub fn take_warnings(&mut self) -> Vec<(PathBuf, ValidationWarning)> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    fn load_document(
+        &mut self,
+        input: &str,
+        path: &Path,
+        format: InputFormat,
+    ) -> Result<MessageSet, Vec<IdlError>> {
+        let json = document_value(input, format).map_err(|e| vec![e])?;
+        let base = path.parent().unwrap_or(Path::new(""));
+        let mut sets = Vec::new();
+        let mut errors = Vec::new();
+        // Shared types of the included files are usable here
+        let mut inherited = Vec::new();
+        for (pointer, include) in includes(&json) {
+            let file = base.join(include);
+            let key = canonical(&file);
+            if let Some(start) = self.stack.iter().position(|(open, _)| *open == key) {
+                let mut chain: Vec<PathBuf> = self.stack[start..]
+                    .iter()
+                    .map(|(_, shown)| shown.clone())
+                    .collect();
+                chain.push(file);
+                errors.push(IdlError::IncludeCycle {
+                    path: pointer,
+                    chain,
+                });
+                continue;
+            }
+            if self.loaded.contains(&key) {
+                inherited.extend(self.types.get(&key).into_iter().flatten().cloned());
+                continue;
+            }
+            let input = match fs::read_to_string(&file) {
+                Ok(input) => input,
+                Err(e) => {
+                    errors.push(IdlError::invalid_value(
+                        &pointer,
+                        include,
+                        format!("failed to read included file {}: {}", file.display(), e),
+                    ));
+                    continue;
+                }
+            };
+            match self.load_str(&input, &file, InputFormat::from_path(&file)) {
+                Ok(set) => {
+                    inherited.extend(set.iter().flat_map(|(metadata, _)| metadata.types.clone()));
+                    sets.extend(set);
+                }
+                Err(included) => errors.extend(included.into_iter().map(|e| e.in_file(&file))),
+            }
+        }
+
+        let options = ParseOptions {
+            format,
+            ..self.options.clone()
+        };
+        let mut warnings = Vec::new();
+        match parse_value(&json, &options, &inherited, |w| warnings.push(w)) {
+            Ok(local) => sets.push(local),
+            Err(local) => errors.extend(local),
+        }
+        self.warnings
+            .extend(warnings.into_iter().map(|w| (path.to_path_buf(), w)));
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        if sets.len() == 1 {
+            return Ok(sets.remove(0));
+        }
+        merge_message_sets(sets).map_err(|error| match error {
+            IdlError::Multiple(errors) => errors,
+            error => vec![error],
+        })
+    }
+}
+
+/// Parses the file at `path` and everything it includes into one message
+/// set, as [`crate::parse_str_with_options`] does for a single document.
+/// The format follows from the extension rather than `options.format`.
+pub fn parse_file(path: &Path, options: &ParseOptions) -> Result<MessageSet, IdlError> {
+    Resolver::new(options.clone())
+        .load_file(path)
+        .map(|set| set.expect("a new resolver has loaded nothing"))
+        .map_err(IdlError::from_list)
+}
+
+/// The `include` entries of a document with their JSON pointers. A list of
+/// the wrong shape is reported by the parser and yields nothing here.
+fn includes(json: &Value) -> Vec<(String, &str)> {
+    let Some(Value::Array(list)) = json.get("include") else {
+        return Vec::new();
+    };
+    if !list.iter().all(Value::is_string) {
+        return Vec::new();
+    }
+    list.iter()
+        .filter_map(Value::as_str)
+        .enumerate()
+        .map(|(index, file)| (pointer_push("/include", &index.to_string()), file))
+        .collect()
+}
+
+/// Identity of a file for cycle and duplicate checks.
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(dir: &Path, name: &str, content: &str) -> PathBuf {
+        let path = dir.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn names(set: &MessageSet) -> Vec<&str> {
+        set.1.iter().map(|m| m.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_nested_includes_are_loaded_once() {
+        let dir = tempfile::tempdir().unwrap();
+        write(
+            dir.path(),
+            "shared/base.json",
+            r#"{ "version": "2", "packets": { "ping": { "packet_id": 1, "msg_type": "uint8" } } }"#,
+        );
+        write(
+            dir.path(),
+            "msgs/common.json",
+            r#"{ "include": ["../shared/base.json"],
+                 "packets": { "status": { "packet_id": 2, "msg_type": "uint16" } } }"#,
+        );
+        // base.json is reached twice, directly and through common.json
+        let app = write(
+            dir.path(),
+            "msgs/app.json",
+            r#"{ "include": ["common.json", "../shared/base.json"],
+                 "packets": { "led": { "packet_id": 40, "msg_type": "bool" } } }"#,
+        );
+
+        let mut resolver = Resolver::new(ParseOptions::default());
+        let set = resolver.load_file(&app).unwrap().unwrap();
+        assert_eq!(names(&set), ["ping", "status", "led"]);
+        let files: Vec<&Path> = resolver
+            .files()
+            .iter()
+            .map(|f| f.strip_prefix(dir.path()).unwrap())
+            .collect();
+        assert_eq!(
+            files,
+            [
+                "msgs/app.json",
+                "msgs/common.json",
+                "msgs/../shared/base.json"
+            ]
+            .map(Path::new)
+        );
+        assert_eq!(set.0.version.as_deref(), Some("2"));
+        assert!(
+            resolver
+                .load_file(&dir.path().join("msgs/common.json"))
+                .unwrap()
+                .is_none()
+        );
+
+        // A document of includes only
+        let all = write(
+            dir.path(),
+            "all.json",
+            r#"{ "include": ["msgs/app.json"] }"#,
+        );
+        let set = parse_file(&all, &ParseOptions::default()).unwrap();
+        assert_eq!(names(&set), ["ping", "status", "led"]);
+    }
+
+    #[test]
+    fn test_include_cycle_reports_the_chain() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = write(
+            dir.path(),
+            "a.json",
+            r#"{ "include": ["b.json"], "packets": { "a": { "packet_id": 1, "msg_type": "uint8" } } }"#,
+        );
+        write(
+            dir.path(),
+            "b.json",
+            r#"{ "include": ["c.json"], "packets": { "b": { "packet_id": 2, "msg_type": "uint8" } } }"#,
+        );
+        write(
+            dir.path(),
+            "c.json",
+            r#"{ "include": ["a.json"], "packets": { "c": { "packet_id": 3, "msg_type": "uint8" } } }"#,
+        );
+
+        let errors = Resolver::new(ParseOptions::default())
+            .load_file(&a)
+            .unwrap_err();
+        assert_eq!(errors.len(), 1);
+        let IdlError::InFile { file, error } = &errors[0] else {
+            panic!("expected an error in an included file, got {:?}", errors[0]);
+        };
+        assert_eq!(*file, dir.path().join("c.json"));
+        let IdlError::IncludeCycle { path, chain } = error.as_ref() else {
+            panic!("expected an include cycle, got {:?}", error);
+        };
+        assert_eq!(path, "/include/0");
+        let chain: Vec<&Path> = chain
+            .iter()
+            .map(|f| f.strip_prefix(dir.path()).unwrap())
+            .collect();
+        assert_eq!(
+            chain,
+            ["a.json", "b.json", "c.json", "a.json"].map(Path::new)
+        );
+
+        // A file including itself
+        let own = write(dir.path(), "own.json", r#"{ "include": ["./own.json"] }"#);
+        let error = parse_file(&own, &ParseOptions::default()).unwrap_err();
+        assert!(matches!(error, IdlError::IncludeCycle { .. }));
+        assert!(error.to_string().starts_with("/include/0: include cycle: "));
+    }
+
+    #[test]
+    fn test_local_definitions_cannot_override_included_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        write(
+            dir.path(),
+            "common.json",
+            r#"{ "packets": { "ping": { "packet_id": 1, "msg_type": "uint8" } } }"#,
+        );
+        let app = write(
+            dir.path(),
+            "app.json",
+            r#"{ "include": ["common.json"],
+                 "packets": { "pong": { "packet_id": 1, "msg_type": "uint8" } } }"#,
+        );
+        let error = parse_file(&app, &ParseOptions::default()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "message 'pong' has packet_id 1 which is already used by message 'ping'"
+        );
+
+        // Errors inside an included file name that file
+        let broken = write(
+            dir.path(),
+            "broken.json",
+            r#"{ "packets": { "bad": { "packet_id": 300, "msg_type": "uint8" } } }"#,
+        );
+        let user = write(
+            dir.path(),
+            "user.json",
+            r#"{ "include": ["broken.json", "missing.json"] }"#,
+        );
+        let errors = Resolver::new(ParseOptions::default())
+            .load_file(&user)
+            .unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert!(matches!(&errors[0], IdlError::InFile { file, .. } if *file == broken));
+        assert_eq!(errors[0].pointer(), Some("/packets/bad/packet_id"));
+        assert!(
+            errors[0]
+                .to_string()
+                .starts_with(&format!("{}: /packets/bad/packet_id: ", broken.display()))
+        );
+        assert_eq!(errors[1].pointer(), Some("/include/1"));
+
+        // The list itself must be an array of paths
+        let errors = crate::parse_str_with_diagnostics(
+            r#"{ "include": "common.json", "packets": {} }"#,
+            &ParseOptions::default(),
+        )
+        .unwrap_err();
+        assert_eq!(errors[0].pointer(), Some("/include"));
+    }
+
+    #[test]
+    fn test_included_shared_types_are_visible() {
+        let dir = tempfile::tempdir().unwrap();
+        write(
+            dir.path(),
+            "types.json",
+            r#"{ "types": { "vector3": { "fields": { "x": { "type": "f32" } } } }, "packets": {} }"#,
+        );
+        write(
+            dir.path(),
+            "motion.json",
+            r#"{ "include": ["types.json"],
+                 "packets": { "target": { "packet_id": 1, "msg_type": "vector3" } } }"#,
+        );
+        // types.json is already loaded when the second include reaches it
+        let app = write(
+            dir.path(),
+            "app.json",
+            r#"{ "include": ["motion.json", "types.json"],
+                 "packets": { "pose": { "packet_id": 2, "msg_type": "struct",
+                                        "fields": { "position": { "type": "vector3" } } } } }"#,
+        );
+
+        let mut resolver = Resolver::new(ParseOptions::default());
+        let (metadata, messages) = resolver.load_file(&app).unwrap().unwrap();
+        assert_eq!(metadata.types.len(), 1);
+        assert_eq!(crate::shared_types(&metadata, &messages).len(), 1);
+    }
+}
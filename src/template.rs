@@ -0,0 +1,140 @@
+//! Placeholder substitution for helper templates.
+//!
+//! Templates may contain `{{name}}` tokens (surrounding spaces allowed)
+//! that are replaced with values from a [`TemplateContext`]:
+//!
+//! - `{{prefix}}` - symbol prefix of the helper functions (`h6xserial`)
+//! - `{{inline}}` - `inline`, or the `H6XSERIAL_INLINE` macro in C89 output
+//! - `{{version}}` - protocol `version` from the metadata, or empty
+//! - `{{input_file}}` - the input document as named in the `Source:`
+//!   comment (empty with `--source-comment none`)
+//!
+//! Every value comes from the input and the options, so the output does not
+//! depend on when it is generated. `\{{` produces a literal `{{`. Any other
+//! token is an error naming the template file, so typos do not end up in
+//! generated code.
+
+use crate::{IdlError, Metadata};
+
+/// Values substituted for `{{name}}` placeholders.
+#[derive(Clone, Debug)]
+pub(crate) struct TemplateContext {
+    pub(crate) prefix: String,
+    pub(crate) inline: String,
+    pub(crate) version: String,
+    pub(crate) input_file: String,
+}
+
+impl TemplateContext {
+    /// `input_file` is the input as named in the `Source:` comment.
+    pub(crate) fn new(prefix: &str, inline: &str, metadata: &Metadata, input_file: &str) -> Self {
+        Self {
+            prefix: prefix.to_string(),
+            inline: inline.to_string(),
+            version: metadata.version.clone().unwrap_or_default(),
+            input_file: input_file.to_string(),
+        }
+    }
+
+    fn lookup(&self, token: &str) -> Option<&str> {
+        match token {
+            "prefix" => Some(&self.prefix),
+            "inline" => Some(&self.inline),
+            "version" => Some(&self.version),
+            "input_file" => Some(&self.input_file),
+            _ => None,
+        }
+    }
+}
+
+/// Replaces the placeholders in `content`, read from template `file_name`.
+pub(crate) fn render(
+    file_name: &str,
+    content: &str,
+    context: &TemplateContext,
+) -> Result<String, IdlError> {
+    let mut out = String::with_capacity(content.len());
+    let mut rest = content;
+    while let Some(start) = rest.find("{{") {
+        if rest[..start].ends_with('\\') {
+            out.push_str(&rest[..start - 1]);
+            out.push_str("{{");
+            rest = &rest[start + 2..];
+            continue;
+        }
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else {
+            return Err(template_error(file_name, "unterminated '{{' placeholder"));
+        };
+        let token = after[..end].trim();
+        let value = context.lookup(token).ok_or_else(|| {
+            template_error(
+                file_name,
+                &format!(
+                    "unknown placeholder '{{{{{}}}}}' (expected prefix, inline, version or input_file)",
+                    token
+                ),
+            )
+        })?;
+        out.push_str(value);
+        rest = &after[end + 2..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+fn template_error(file_name: &str, message: &str) -> IdlError {
+    IdlError::Template {
+        file: file_name.to_string(),
+        message: message.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context() -> TemplateContext {
+        TemplateContext {
+            prefix: "acme".to_string(),
+            inline: "inline".to_string(),
+            version: "1.2.0".to_string(),
+            input_file: "msgs/acme.json".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_render_substitutes_placeholders_and_escapes() {
+        let rendered = render(
+            "prologue.h",
+            "/* v{{ version }} from {{input_file}} */\n\
+             void {{prefix}}_init(void);\n\
+             /* \\{{prefix}} stays literal */",
+            &context(),
+        )
+        .unwrap();
+        assert_eq!(
+            rendered,
+            "/* v1.2.0 from msgs/acme.json */\n\
+             void acme_init(void);\n\
+             /* {{prefix}} stays literal */"
+        );
+    }
+
+    #[test]
+    fn test_render_rejects_unknown_and_unterminated_placeholders() {
+        let err = render("helpers_u16.h", "{{prefx}}_write", &context()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "template helpers_u16.h: unknown placeholder '{{prefx}}' \
+             (expected prefix, inline, version or input_file)"
+        );
+        // The generation date is not a placeholder: output must not change
+        // from one run to the next
+        let err = render("prologue.h", "/* (c) {{year}} */", &context()).unwrap_err();
+        assert!(err.to_string().contains("'{{year}}'"), "{}", err);
+        let err = render("epilogue.h", "{{prefix", &context()).unwrap_err();
+        assert!(err.to_string().contains("unterminated"));
+    }
+}
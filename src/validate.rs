@@ -0,0 +1,294 @@
+//! Strict validation of input keys.
+//!
+//! The parser only looks up the keys it knows, so a misspelled key such as
+//! `endianes` would silently fall back to a default. This pass reports every
+//! unrecognized key in message, field, union variant, shared type and device
+//! definitions together with its JSON pointer and the closest known key.
+
+use std::fmt;
+
+use serde_json::{Map, Value};
+
+use crate::collect_message_maps;
+
+/// Keys accepted in a message definition.
+const MESSAGE_KEYS: &[&str] = &[
+    "packet_id",
+    "msg_type",
+    "msg_desc",
+    "desc",
+    "group",
+    "unit",
+    "request_type",
+    "target_client_id",
+    "deprecated",
+    "reserved_ok",
+    "response",
+    "array",
+    "max_length",
+    "fixed_length",
+    "length",
+    "sector_bytes",
+    "string_semantics",
+    "endianess",
+    "endianness",
+    "fields",
+    "field_order",
+    "scale",
+    "offset",
+    "min",
+    "max",
+    "tag",
+    "variants",
+    "msg_version",
+    // Computed sizes of `--export_ir`, ignored on input
+    "min_size",
+    "max_size",
+];
+
+/// Keys accepted in a struct field definition.
+const FIELD_KEYS: &[&str] = &[
+    "type",
+    "msg_type",
+    "msg_desc",
+    "desc",
+    "unit",
+    "array",
+    "max_length",
+    "fixed_length",
+    "length",
+    "string_semantics",
+    "endianess",
+    "endianness",
+    "fields",
+    "field_order",
+    "scale",
+    "offset",
+    "min",
+    "max",
+    "storage",
+    "resolution",
+    "at",
+    "since_version",
+];
+
+/// Keys accepted in a variant of a union message.
+const VARIANT_KEYS: &[&str] = &["name", "type", "fields", "field_order", "desc"];
+
+/// Keys accepted in a shared type definition.
+const TYPE_KEYS: &[&str] = &["type", "msg_type", "fields", "field_order"];
+
+/// Keys accepted in a device definition.
+const DEVICE_KEYS: &[&str] = &["role", "id", "description"];
+
+/// A non-fatal problem found in the input.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidationWarning {
+    /// JSON pointer (RFC 6901) of the offending key
+    pub path: String,
+    /// The unrecognized key itself
+    pub key: String,
+    pub message: String,
+}
+
+impl fmt::Display for ValidationWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// Appends `segment` to a JSON pointer, escaping `~` and `/`.
+pub(crate) fn pointer_push(pointer: &str, segment: &str) -> String {
+    format!(
+        "{}/{}",
+        pointer,
+        segment.replace('~', "~0").replace('/', "~1")
+    )
+}
+
+/// Levenshtein edit distance between two strings.
+pub(crate) fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut curr = vec![0; b.len() + 1];
+    for (i, ca) in a.chars().enumerate() {
+        curr[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = prev[j] + usize::from(ca != *cb);
+            curr[j + 1] = substitution.min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+    prev[b.len()]
+}
+
+/// Returns the known key closest to `key`, if it is plausibly a typo.
+pub(crate) fn closest_key<'a>(key: &str, known: &[&'a str]) -> Option<&'a str> {
+    let lowered = key.to_ascii_lowercase();
+    known
+        .iter()
+        .map(|candidate| (levenshtein(&lowered, candidate), *candidate))
+        .filter(|(distance, candidate)| *distance <= 3 && *distance < candidate.len())
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+fn check_keys(
+    map: &Map<String, Value>,
+    known: &[&str],
+    pointer: &str,
+    warnings: &mut Vec<ValidationWarning>,
+) {
+    for key in map.keys() {
+        if known.contains(&key.as_str()) {
+            continue;
+        }
+        let mut message = format!("unknown key '{}'", key);
+        if let Some(suggestion) = closest_key(key, known) {
+            message.push_str(&format!(" (did you mean '{}'?)", suggestion));
+        }
+        warnings.push(ValidationWarning {
+            path: pointer_push(pointer, key),
+            key: key.clone(),
+            message,
+        });
+    }
+}
+
+fn check_fields(fields: &Map<String, Value>, pointer: &str, warnings: &mut Vec<ValidationWarning>) {
+    for (name, value) in fields {
+        let Some(field_map) = value.as_object() else {
+            continue;
+        };
+        let field_pointer = pointer_push(pointer, name);
+        check_keys(field_map, FIELD_KEYS, &field_pointer, warnings);
+        if let Some(nested) = field_map.get("fields").and_then(|v| v.as_object()) {
+            check_fields(nested, &pointer_push(&field_pointer, "fields"), warnings);
+        }
+    }
+}
+
+/// Reports unrecognized keys in message, field, union variant, shared type
+/// and device definitions.
+///
+/// Structural problems (missing `packets`, stray top-level keys) are left to
+/// the parser; this pass only returns warnings.
+///
+/// # Example
+/// ```
+/// use serde_json::json;
+/// use h6xserial_idl::validate::unknown_key_warnings;
+///
+/// let json = json!({
+///     "packets": {
+///         "temp": { "packet_id": 1, "msg_type": "float32", "endianes": "big" }
+///     }
+/// });
+/// let warnings = unknown_key_warnings(json.as_object().unwrap());
+/// assert_eq!(
+///     warnings[0].to_string(),
+///     "/packets/temp/endianes: unknown key 'endianes' (did you mean 'endianess'?)"
+/// );
+/// ```
+pub fn unknown_key_warnings(map: &Map<String, Value>) -> Vec<ValidationWarning> {
+    let mut warnings = Vec::new();
+
+    if let Some(devices) = map.get("devices").and_then(|v| v.as_object()) {
+        for (name, value) in devices {
+            if let Some(device_map) = value.as_object() {
+                let pointer = pointer_push(&pointer_push("", "devices"), name);
+                check_keys(device_map, DEVICE_KEYS, &pointer, &mut warnings);
+            }
+        }
+    }
+
+    if let Some(types) = map.get("types").and_then(|v| v.as_object()) {
+        for (name, value) in types {
+            if let Some(type_map) = value.as_object() {
+                let pointer = pointer_push(&pointer_push("", "types"), name);
+                check_keys(type_map, TYPE_KEYS, &pointer, &mut warnings);
+                if let Some(fields) = type_map.get("fields").and_then(|v| v.as_object()) {
+                    check_fields(fields, &pointer_push(&pointer, "fields"), &mut warnings);
+                }
+            }
+        }
+    }
+
+    let Ok(messages) = collect_message_maps(map) else {
+        return warnings;
+    };
+    for (pointer, _, msg_map) in messages {
+        check_keys(msg_map, MESSAGE_KEYS, &pointer, &mut warnings);
+        if let Some(fields) = msg_map.get("fields").and_then(|v| v.as_object()) {
+            check_fields(fields, &pointer_push(&pointer, "fields"), &mut warnings);
+        }
+        let Some(variants) = msg_map.get("variants").and_then(|v| v.as_object()) else {
+            continue;
+        };
+        for (tag, value) in variants {
+            let Some(variant_map) = value.as_object() else {
+                continue;
+            };
+            let variant_pointer = pointer_push(&pointer_push(&pointer, "variants"), tag);
+            check_keys(variant_map, VARIANT_KEYS, &variant_pointer, &mut warnings);
+            if let Some(fields) = variant_map.get("fields").and_then(|v| v.as_object()) {
+                check_fields(
+                    fields,
+                    &pointer_push(&variant_pointer, "fields"),
+                    &mut warnings,
+                );
+            }
+        }
+    }
+
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_levenshtein() {
+        assert_eq!(levenshtein("", "abc"), 3);
+        assert_eq!(levenshtein("endianes", "endianess"), 1);
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("same", "same"), 0);
+    }
+
+    #[test]
+    fn test_common_typos_suggest_known_keys() {
+        assert_eq!(closest_key("endianes", MESSAGE_KEYS), Some("endianess"));
+        assert_eq!(closest_key("max_lenght", MESSAGE_KEYS), Some("max_length"));
+        assert_eq!(closest_key("packetid", MESSAGE_KEYS), Some("packet_id"));
+        assert_eq!(closest_key("Array", FIELD_KEYS), Some("array"));
+        assert_eq!(closest_key("tpye", FIELD_KEYS), Some("type"));
+        assert_eq!(closest_key("completely_unrelated", MESSAGE_KEYS), None);
+    }
+
+    #[test]
+    fn test_nested_field_and_flat_layout_paths() {
+        let json = json!({
+            "devices": { "board/a": { "role": "server", "desc": "x" } },
+            "imu": {
+                "packet_id": 3,
+                "msg_type": "struct",
+                "fields": {
+                    "gyro": {
+                        "type": "struct",
+                        "fields": { "x": { "type": "float32", "endian": "big" } }
+                    }
+                }
+            }
+        });
+        let paths: Vec<String> = unknown_key_warnings(json.as_object().unwrap())
+            .into_iter()
+            .map(|w| w.path)
+            .collect();
+        assert_eq!(
+            paths,
+            vec!["/devices/board~1a/desc", "/imu/fields/gyro/fields/x/endian"]
+        );
+    }
+}
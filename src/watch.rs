@@ -0,0 +1,205 @@
+//! `--watch`: regenerate whenever the input or the templates change.
+//!
+//! Changes are reported by the platform's file notification API through the
+//! `notify` crate. Files are watched through their directory, which copes
+//! with editors that replace a file on save and with files that do not
+//! exist yet. The loop runs until Ctrl-C, which ends it cleanly.
+
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+
+/// Delay after a change is seen, so an editor can finish writing.
+const SETTLE_DELAY: Duration = Duration::from_millis(50);
+
+/// What wakes the watch loop.
+enum Signal {
+    Event(notify::Result<Event>),
+    Interrupted,
+}
+
+/// The paths being watched and the directories registered for them.
+#[derive(Debug, Default)]
+struct WatchSet {
+    /// Watched files and directories, absolute
+    paths: Vec<PathBuf>,
+    /// Directories registered with the watcher
+    dirs: Vec<PathBuf>,
+}
+
+impl WatchSet {
+    /// Directory to register for `path`: a directory itself, otherwise the
+    /// directory holding the file.
+    fn dir_of(path: &Path) -> Option<PathBuf> {
+        if path.is_dir() {
+            Some(path.to_path_buf())
+        } else {
+            path.parent()
+                .filter(|parent| parent.is_dir())
+                .map(Path::to_path_buf)
+        }
+    }
+
+    /// Watches `paths` from now on, registering and dropping directories
+    /// as needed.
+    fn update(&mut self, watcher: &mut impl Watcher, paths: &[PathBuf]) -> Result<()> {
+        let paths: Vec<PathBuf> = paths
+            .iter()
+            .map(std::path::absolute)
+            .collect::<std::io::Result<_>>()?;
+        let mut dirs: Vec<PathBuf> = paths.iter().filter_map(|p| Self::dir_of(p)).collect();
+        dirs.sort();
+        dirs.dedup();
+        for dir in self.dirs.iter().filter(|dir| !dirs.contains(dir)) {
+            // The directory may be gone, which already ended its watch
+            let _ = watcher.unwatch(dir);
+        }
+        for dir in dirs.iter().filter(|dir| !self.dirs.contains(dir)) {
+            watcher
+                .watch(dir, RecursiveMode::NonRecursive)
+                .with_context(|| format!("failed to watch {}", dir.display()))?;
+        }
+        self.paths = paths;
+        self.dirs = dirs;
+        Ok(())
+    }
+
+    /// Whether `event` creates, changes or removes a watched file, or a file
+    /// in a watched directory.
+    fn affected_by(&self, event: &Event) -> bool {
+        if !matches!(
+            event.kind,
+            EventKind::Any | EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+        ) {
+            return false;
+        }
+        event.paths.iter().any(|path| {
+            self.paths.contains(path)
+                || path
+                    .parent()
+                    .is_some_and(|parent| self.paths.iter().any(|p| p == parent))
+        })
+    }
+}
+
+/// `HH:MM:SS` of the current UTC time.
+fn timestamp() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    format!(
+        "{:02}:{:02}:{:02}",
+        secs / 3600 % 24,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+/// Runs `regenerate` now and after every change to `paths`, printing its
+/// status line (or error) with a timestamp. Errors do not stop the loop;
+/// Ctrl-C does, and returns `Ok`.
+///
+/// `regenerate` fills in the files it read, such as included documents;
+/// they are watched as well until the next run.
+pub(crate) fn watch(
+    paths: &[PathBuf],
+    mut regenerate: impl FnMut(&mut Vec<PathBuf>) -> Result<String>,
+) -> Result<()> {
+    fn report(result: Result<String>) {
+        match result {
+            Ok(status) => println!("[{} UTC] {}", timestamp(), status),
+            Err(error) => eprintln!("[{} UTC] error: {:#}", timestamp(), error),
+        }
+    }
+
+    let (sender, signals) = mpsc::channel();
+    let interrupt = sender.clone();
+    ctrlc::set_handler(move || {
+        let _ = interrupt.send(Signal::Interrupted);
+    })
+    .context("failed to install the Ctrl-C handler")?;
+    let mut watcher = notify::recommended_watcher(move |event| {
+        let _ = sender.send(Signal::Event(event));
+    })
+    .context("failed to start the file watcher")?;
+
+    let names: Vec<String> = paths.iter().map(|p| p.display().to_string()).collect();
+    println!("Watching {} (Ctrl-C to stop)", names.join(", "));
+    let mut watched = WatchSet::default();
+    loop {
+        let mut read = Vec::new();
+        report(regenerate(&mut read));
+        let mut now_watched = paths.to_vec();
+        now_watched.extend(read.into_iter().filter(|file| !paths.contains(file)));
+        watched.update(&mut watcher, &now_watched)?;
+
+        loop {
+            match signals.recv() {
+                Ok(Signal::Event(Ok(event))) if watched.affected_by(&event) => break,
+                Ok(Signal::Event(Ok(_))) => {}
+                Ok(Signal::Event(Err(error))) => {
+                    eprintln!("[{} UTC] watch error: {}", timestamp(), error)
+                }
+                Ok(Signal::Interrupted) | Err(_) => {
+                    println!("Stopped watching");
+                    return Ok(());
+                }
+            }
+        }
+        // The rest of the save arrives meanwhile and needs no second run
+        thread::sleep(SETTLE_DELAY);
+        if signals
+            .try_iter()
+            .any(|signal| matches!(signal, Signal::Interrupted))
+        {
+            println!("Stopped watching");
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{AccessKind, CreateKind, ModifyKind};
+
+    #[test]
+    fn test_watch_set_matches_files_and_directory_entries() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let input = dir.path().join("protocol.json");
+        let templates = dir.path().join("templates");
+        std::fs::create_dir(&templates).unwrap();
+
+        assert_eq!(WatchSet::dir_of(&input), Some(dir.path().to_path_buf()));
+        assert_eq!(WatchSet::dir_of(&templates), Some(templates.clone()));
+
+        let watched = WatchSet {
+            paths: vec![input.clone(), templates.clone()],
+            dirs: Vec::new(),
+        };
+        let event = |kind, path: PathBuf| Event::new(kind).add_path(path);
+        let modify = EventKind::Modify(ModifyKind::Any);
+        assert!(watched.affected_by(&event(modify, input.clone())));
+        assert!(watched.affected_by(&event(
+            EventKind::Create(CreateKind::File),
+            templates.join("prologue.h")
+        )));
+        // Other files next to the input, such as generated output
+        assert!(!watched.affected_by(&event(modify, dir.path().join("out.h"))));
+        // Reading the input is not a change
+        assert!(!watched.affected_by(&event(EventKind::Access(AccessKind::Any), input.clone())));
+    }
+
+    #[test]
+    fn test_timestamp_format() {
+        let stamp = timestamp();
+        assert_eq!(stamp.len(), 8);
+        assert_eq!(stamp.as_bytes()[2], b':');
+        assert_eq!(stamp.as_bytes()[5], b':');
+    }
+}
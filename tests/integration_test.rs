@@ -1,5 +1,5 @@
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tempfile::TempDir;
 
 #[test]
@@ -145,10 +145,12 @@ fn test_generate_c_header_for_all_message_types() {
     assert!(source.contains("test_input_msg_array_char_t"));
     assert!(source.contains("test_input_msg_struct_mixed_t"));
 
-    // Verify endian helper functions are included
+    // Verify the endian helpers the messages use are included, and only those
     assert!(source.contains("h6xserial_write_u16_le"));
-    assert!(source.contains("h6xserial_read_u16_be"));
     assert!(source.contains("h6xserial_write_f32_be"));
+    assert!(source.contains("h6xserial_write_u32_be"));
+    assert!(!source.contains("h6xserial_read_u16_be"));
+    assert!(!source.contains("h6xserial_write_f64_le"));
 
     fs::write(&output_path, source).unwrap();
     assert!(output_path.exists());
@@ -411,3 +413,5436 @@ fn test_payload_size_limit_valid() {
         "Should accept struct message at exactly 251 bytes"
     );
 }
+
+#[test]
+fn test_payload_size_limit_configurable() {
+    // 1000 * 1 (uint8) = 1000 bytes, only valid with a two-byte length frame
+    let json_content = r#"{
+        "max_payload_bytes": 1024,
+        "packets": {
+            "firmware_chunk": {
+                "packet_id": 1,
+                "msg_type": "uint8",
+                "array": true,
+                "max_length": 1000
+            }
+        }
+    }"#;
+    let json: serde_json::Value = serde_json::from_str(json_content).unwrap();
+    let obj = json.as_object().unwrap();
+
+    let (metadata, messages) = h6xserial_idl::parse_messages(obj).unwrap();
+    assert_eq!(metadata.max_payload_bytes, 1024);
+    let header = h6xserial_idl::emit_c::generate(
+        &metadata,
+        &messages,
+        &PathBuf::from("chunk.json"),
+        &PathBuf::from("chunk.h"),
+    )
+    .unwrap();
+    assert!(header.contains("#define H6XSERIAL_MAX_PAYLOAD_BYTES 1024"));
+
+    // The CLI override takes precedence over the metadata key
+    let options = h6xserial_idl::ParseOptions {
+        max_payload_bytes: Some(512),
+        ..Default::default()
+    };
+    let err_msg = h6xserial_idl::parse_messages_with_options(obj, &options)
+        .unwrap_err()
+        .to_string();
+    assert!(
+        err_msg.contains("1000 bytes") && err_msg.contains("512 bytes"),
+        "Error should mention the actual size and the limit: {}",
+        err_msg
+    );
+
+    // Limits beyond the sanity cap are rejected
+    let options = h6xserial_idl::ParseOptions {
+        max_payload_bytes: Some(1_000_000),
+        ..Default::default()
+    };
+    let err_msg = h6xserial_idl::parse_messages_with_options(obj, &options)
+        .unwrap_err()
+        .to_string();
+    assert!(err_msg.contains("max_payload_bytes 1000000 is out of range"));
+}
+
+#[test]
+fn test_max_array_length_configurable() {
+    let parse = |json: &str, options: &h6xserial_idl::ParseOptions| {
+        let json: serde_json::Value = serde_json::from_str(json).unwrap();
+        h6xserial_idl::parse_messages_with_options(json.as_object().unwrap(), options)
+    };
+    let defaults = h6xserial_idl::ParseOptions::default();
+
+    // A small micro lowers the cap in metadata; struct fields are checked too
+    let small = r#"{
+        "max_array_length": 16,
+        "packets": {
+            "log": {
+                "packet_id": 1,
+                "msg_type": "struct",
+                "fields": { "lines": { "type": "char", "array": true, "max_length": 32 } }
+            }
+        }
+    }"#;
+    let err_msg = parse(small, &defaults).unwrap_err().to_string();
+    assert!(
+        err_msg.contains("max_length 32 which exceeds maximum of 16")
+            && err_msg.contains("metadata 'max_array_length'"),
+        "{}",
+        err_msg
+    );
+
+    // The CLI flag wins over metadata
+    let options = h6xserial_idl::ParseOptions {
+        max_array_length: Some(64),
+        ..Default::default()
+    };
+    let (metadata, _) = parse(small, &options).unwrap();
+    assert_eq!(metadata.max_array_length, 64);
+
+    // Flash transfers need a longer cap together with a larger payload limit
+    let flash = r#"{
+        "max_payload_bytes": 4096,
+        "max_array_length": 4096,
+        "packets": {
+            "flash_block": { "packet_id": 2, "msg_type": "uint8", "array": true, "max_length": 4096 }
+        }
+    }"#;
+    assert!(parse(flash, &defaults).is_ok());
+    let options = h6xserial_idl::ParseOptions {
+        max_payload_bytes: Some(2048),
+        ..Default::default()
+    };
+    let err_msg = parse(flash, &options).unwrap_err().to_string();
+    assert!(
+        err_msg.contains("max_array_length 4096 (from metadata 'max_array_length') exceeds max_payload_bytes 2048"),
+        "{}",
+        err_msg
+    );
+
+    let default_err = parse(
+        r#"{ "max_payload_bytes": 4096, "packets": {
+            "blob": { "packet_id": 3, "msg_type": "uint8", "array": true, "max_length": 2000 }
+        } }"#,
+        &defaults,
+    )
+    .unwrap_err()
+    .to_string();
+    assert!(default_err.contains("exceeds maximum of 1024 (default limit)"));
+}
+
+#[test]
+fn test_nesting_depth_and_field_count_limited() {
+    use serde_json::{Map, Value, json};
+
+    // A struct message whose fields nest `levels` structs deep
+    let nested = |levels: usize, metadata: Value| {
+        let mut fields = json!({ "value": { "type": "u8" } });
+        for level in (1..=levels).rev() {
+            fields = json!({ format!("level{}", level): { "type": "struct", "fields": fields } });
+        }
+        let mut doc = metadata.as_object().cloned().unwrap_or_default();
+        doc.insert(
+            "deep".into(),
+            json!({ "packet_id": 1, "msg_type": "struct", "fields": fields }),
+        );
+        doc
+    };
+    let defaults = h6xserial_idl::ParseOptions::default();
+
+    let err =
+        h6xserial_idl::parse_messages_with_options(&nested(50, json!({})), &defaults).unwrap_err();
+    let path = (1..=9)
+        .map(|level| format!("/level{}", level))
+        .collect::<Vec<_>>()
+        .join("/fields");
+    assert_eq!(
+        err.pointer(),
+        Some(format!("/deep/fields{}", path).as_str())
+    );
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "/deep/fields{}: struct field 'deep.level1.level2.level3.level4.level5.level6.level7.level8.level9' \
+             is nested 9 levels deep, more than max_nesting_depth 8 (from default)",
+            path
+        )
+    );
+    assert!(h6xserial_idl::parse_messages(&nested(8, json!({}))).is_ok());
+
+    // Raising the limit lets the C generator handle the full depth
+    let (metadata, messages) =
+        h6xserial_idl::parse_messages(&nested(50, json!({ "max_nesting_depth": 50 }))).unwrap();
+    assert_eq!(metadata.max_nesting_depth, 50);
+    let header = h6xserial_idl::emit_c::generate(
+        &metadata,
+        &messages,
+        &PathBuf::from("deep.json"),
+        &PathBuf::from("deep.h"),
+    )
+    .unwrap();
+    assert!(header.contains("level50"));
+    let options = h6xserial_idl::ParseOptions {
+        max_nesting_depth: Some(65),
+        ..Default::default()
+    };
+    let err =
+        h6xserial_idl::parse_messages_with_options(&nested(1, json!({})), &options).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "/max_nesting_depth: max_nesting_depth 65 (from --max-nesting-depth) is out of range, expected 0-64"
+    );
+
+    // Shared types count the levels they bring along
+    let shared: Map<String, Value> = serde_json::from_str(
+        r#"{
+            "max_nesting_depth": 2,
+            "types": {
+                "inner": { "fields": { "x": { "type": "u8" } } },
+                "outer": { "fields": { "inner": { "type": "inner" } } }
+            },
+            "pose": {
+                "packet_id": 2,
+                "msg_type": "struct",
+                "fields": { "a": { "type": "struct", "fields": { "o": { "type": "outer" } } } }
+            }
+        }"#,
+    )
+    .unwrap();
+    let err = h6xserial_idl::parse_messages(&shared).unwrap_err();
+    assert_eq!(err.pointer(), Some("/pose/fields/a/fields/o"));
+    assert!(
+        err.to_string()
+            .contains("'pose.a.o' is nested 3 levels deep")
+    );
+
+    // Machine-generated messages with thousands of fields are refused
+    let mut fields = Map::new();
+    for index in 0..1025 {
+        fields.insert(format!("f{}", index), json!({ "type": "bool" }));
+    }
+    let mut wide = Map::new();
+    wide.insert("max_payload_bytes".into(), json!(4096));
+    wide.insert(
+        "wide".into(),
+        json!({ "packet_id": 3, "msg_type": "struct", "fields": fields }),
+    );
+    let err = h6xserial_idl::parse_messages(&wide).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "/wide: message 'wide' has 1025 fields counting those of nested structs, more than the limit of 1024"
+    );
+
+    // Built definitions are checked the same way, and the generator refuses
+    // anything deeper than it supports whatever limit they were built with
+    let mut spec = h6xserial_idl::StructSpec::builder()
+        .field("value", h6xserial_idl::PrimitiveType::Uint8)
+        .build()
+        .unwrap();
+    for _ in 0..100 {
+        spec = h6xserial_idl::StructSpec::builder()
+            .nested("inner", spec)
+            .build()
+            .unwrap();
+    }
+    let deep = || h6xserial_idl::MessageDefinition::structure("deep", 1, spec.clone());
+    let err = deep().build().unwrap_err();
+    assert!(
+        err.to_string().contains(
+            "message 'deep' nests structs 100 levels deep, more than max_nesting_depth 8"
+        ),
+        "{}",
+        err
+    );
+    let metadata = h6xserial_idl::Metadata {
+        max_nesting_depth: 100,
+        ..Default::default()
+    };
+    let message = deep().limits(&metadata).build().unwrap();
+    let err = h6xserial_idl::emit_c::generate(
+        &metadata,
+        &[message],
+        &PathBuf::from("deep.json"),
+        &PathBuf::from("deep.h"),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "/packets/deep: message 'deep' nests structs 100 levels deep, more than the C generator supports (64)"
+    );
+}
+
+#[test]
+fn test_unknown_keys_warn_by_default_and_fail_in_strict_mode() {
+    let json_content = r#"{
+        "packets": {
+            "temperature": {
+                "packet_id": 20,
+                "msg_type": "float32",
+                "endianes": "big"
+            }
+        }
+    }"#;
+    let json: serde_json::Value = serde_json::from_str(json_content).unwrap();
+    let obj = json.as_object().unwrap();
+
+    // Default: parses (with little-endian fallback) and reports a warning
+    assert!(h6xserial_idl::parse_messages(obj).is_ok());
+    let warnings = h6xserial_idl::validate::unknown_key_warnings(obj);
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].path, "/packets/temperature/endianes");
+    assert!(warnings[0].message.contains("did you mean 'endianess'?"));
+
+    let options = h6xserial_idl::ParseOptions {
+        strict: true,
+        ..Default::default()
+    };
+    let err_msg = h6xserial_idl::parse_messages_with_options(obj, &options)
+        .unwrap_err()
+        .to_string();
+    assert!(
+        err_msg.contains("/packets/temperature/endianes: unknown key 'endianes'"),
+        "{}",
+        err_msg
+    );
+
+    // The example input is clean
+    let raw = fs::read_to_string("example/c_usage/example.json").unwrap();
+    let example: serde_json::Value = serde_json::from_str(&raw).unwrap();
+    assert!(h6xserial_idl::validate::unknown_key_warnings(example.as_object().unwrap()).is_empty());
+}
+
+#[test]
+fn test_all_errors_reported_in_one_pass() {
+    let json_content = r#"{
+        "packets": {
+            "status": { "packet_id": 1, "msg_type": "uint8", "endianes": "big" },
+            "oversized": { "packet_id": 2, "msg_type": "uint16", "array": true, "max_length": 200 },
+            "sensor_data": {
+                "packet_id": 3,
+                "msg_type": "struct",
+                "fields": { "temp": { "msg_type": "float32", "endianess": "middle" } }
+            }
+        }
+    }"#;
+    let json: serde_json::Value = serde_json::from_str(json_content).unwrap();
+    let obj = json.as_object().unwrap();
+
+    let options = h6xserial_idl::ParseOptions {
+        strict: true,
+        ..Default::default()
+    };
+    let errors = h6xserial_idl::parse_messages_with_diagnostics(obj, &options).unwrap_err();
+    let rendered: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+    assert_eq!(rendered.len(), 3, "{:#?}", rendered);
+    assert!(rendered[0].starts_with("/packets/status/endianes: unknown key"));
+    assert!(rendered[1].starts_with("/packets/oversized/max_length: "));
+    assert!(rendered[1].contains("400 bytes"));
+    assert_eq!(
+        rendered[2],
+        "/packets/sensor_data/fields/temp/endianess: unsupported endian value 'middle'"
+    );
+
+    let fail_fast = h6xserial_idl::ParseOptions {
+        fail_fast: true,
+        ..options
+    };
+    let errors = h6xserial_idl::parse_messages_with_diagnostics(obj, &fail_fast).unwrap_err();
+    assert_eq!(errors.len(), 1);
+}
+
+#[test]
+fn test_parse_str_and_writers_match_string_api() {
+    let input_path = PathBuf::from("example/c_usage/example.json");
+    let raw = fs::read_to_string(&input_path).unwrap();
+    let (metadata, messages) = h6xserial_idl::parse_str(&raw).unwrap();
+    assert!(
+        messages.windows(2).all(|w| w[0].packet_id < w[1].packet_id),
+        "parse_str should sort by packet_id"
+    );
+
+    let output_path = PathBuf::from("example.h");
+    let expected =
+        h6xserial_idl::emit_c::generate(&metadata, &messages, &input_path, &output_path).unwrap();
+    let mut streamed = Vec::new();
+    h6xserial_idl::emit_c::generate_to_writer(
+        &mut streamed,
+        &metadata,
+        &messages,
+        &input_path,
+        &output_path,
+    )
+    .unwrap();
+    assert_eq!(String::from_utf8(streamed).unwrap(), expected);
+
+    let temp_dir = TempDir::new().unwrap();
+    let written = h6xserial_idl::emit_c::generate_multiple_to_writers(
+        &metadata,
+        &messages,
+        &input_path,
+        "example",
+        &h6xserial_idl::emit_c::CGenOptions::default(),
+        temp_dir.path(),
+        |path| fs::File::create(path),
+    )
+    .unwrap();
+    let files =
+        h6xserial_idl::emit_c::generate_multiple(&metadata, &messages, &input_path, "example")
+            .unwrap();
+    assert_eq!(written.len(), files.len());
+    for file in &files {
+        let on_disk = fs::read_to_string(temp_dir.path().join(&file.filename)).unwrap();
+        assert_eq!(on_disk, file.content, "{} differs", file.filename);
+    }
+
+    let err = h6xserial_idl::parse_str("[1, 2]").unwrap_err();
+    assert_eq!(err.to_string(), "top-level JSON must be an object");
+    let err = h6xserial_idl::parse_str("{\n  \"packets\": }").unwrap_err();
+    assert!(matches!(
+        err,
+        h6xserial_idl::IdlError::Syntax { line: 2, .. }
+    ));
+}
+
+#[test]
+fn test_generation_works_without_templates_on_disk() {
+    let input = fs::canonicalize("example/c_usage/example.json").unwrap();
+    let work_dir = TempDir::new().unwrap();
+    let run = |extra: &[&str]| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+            .current_dir(work_dir.path())
+            .args(extra)
+            .arg(&input)
+            .arg("out")
+            .output()
+            .unwrap()
+    };
+
+    let output = run(&[]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let header = fs::read_to_string(work_dir.path().join("out/h6x_serial_byteorder.h")).unwrap();
+    assert!(header.contains("h6xserial_write_u16_le"));
+
+    let custom = work_dir.path().join("custom");
+    fs::create_dir(&custom).unwrap();
+    let embedded = fs::read_to_string("src/msg_template/c/helpers_u16_le.h").unwrap();
+    fs::write(
+        custom.join("helpers_u16_le.h"),
+        format!("/* customized u16 helpers */\n{}", embedded),
+    )
+    .unwrap();
+    let output = run(&["--template-dir", "custom"]);
+    assert!(output.status.success());
+    let header = fs::read_to_string(work_dir.path().join("out/h6x_serial_byteorder.h")).unwrap();
+    assert!(header.contains("/* customized u16 helpers */"));
+    assert!(header.contains("h6xserial_write_u32_be"));
+
+    // A combined helpers_u16.h from before the split covers both byte orders
+    fs::remove_file(custom.join("helpers_u16_le.h")).unwrap();
+    fs::write(
+        custom.join("helpers_u16.h"),
+        format!("/* combined u16 helpers */\n{}", embedded),
+    )
+    .unwrap();
+    let output = run(&["--template-dir", "custom"]);
+    assert!(output.status.success());
+    let header = fs::read_to_string(work_dir.path().join("out/h6x_serial_byteorder.h")).unwrap();
+    assert!(header.contains("/* combined u16 helpers */"));
+    assert_eq!(header.matches("h6xserial_write_u16_le(").count(), 1);
+
+    let output = run(&["--template-dir", "missing"]);
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr)
+            .contains("template directory 'missing' for language C99 does not exist")
+    );
+}
+
+#[test]
+fn test_template_dir_prologue_and_epilogue() {
+    let (metadata, messages) = parse_example_messages();
+    let input_path = PathBuf::from("example/c_usage/example.json");
+    let template_dir = TempDir::new().unwrap();
+    fs::write(
+        template_dir.path().join("prologue.h"),
+        "/* SPDX-License-Identifier: MIT */\n#include <stdio.h>",
+    )
+    .unwrap();
+    fs::write(
+        template_dir.path().join("epilogue.h"),
+        "/* end of generated code */\n",
+    )
+    .unwrap();
+    let options = h6xserial_idl::emit_c::CGenOptions {
+        template_dir: Some(template_dir.path().to_path_buf()),
+        ..Default::default()
+    };
+
+    let mut headers = h6xserial_idl::emit_c::generate_multiple_with_options(
+        &metadata,
+        &messages,
+        &input_path,
+        "example",
+        &options,
+    )
+    .unwrap();
+    headers.extend(
+        h6xserial_idl::emit_c::generate_files(
+            &metadata,
+            &messages,
+            &input_path,
+            &PathBuf::from("example.h"),
+            &options,
+        )
+        .unwrap(),
+    );
+    for file in headers.iter().filter(|f| f.filename.ends_with(".h")) {
+        let content = &file.content;
+        let prologue = content
+            .find("/* SPDX-License-Identifier: MIT */\n#include <stdio.h>\n\n")
+            .unwrap_or_else(|| panic!("{} lacks the prologue", file.filename));
+        let last_include = content[..prologue].rfind("#include").unwrap();
+        assert!(
+            !content[last_include..prologue].contains("extern"),
+            "{}: prologue should follow the includes",
+            file.filename
+        );
+        // The umbrella header only includes the others
+        if let Some(extern_c) = content.find("extern \"C\"") {
+            assert!(prologue < extern_c);
+        }
+        assert!(
+            content.trim_end().ends_with(&format!(
+                "/* end of generated code */\n\n{}",
+                content.trim_end().lines().last().unwrap()
+            )),
+            "{}: epilogue should precede the closing guard",
+            file.filename
+        );
+    }
+
+    let description = h6xserial_idl::emit_c::describe_templates(&options).unwrap();
+    assert!(description.contains("(prologue.h, epilogue.h)"));
+    let default =
+        h6xserial_idl::emit_c::generate_multiple(&metadata, &messages, &input_path, "example")
+            .unwrap();
+    assert!(default.iter().all(|f| !f.content.contains("SPDX")));
+}
+
+#[test]
+fn test_template_dir_is_read_once() {
+    let _cache = TEMPLATE_CACHE_LOCK
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    let (metadata, messages) = parse_example_messages();
+    let input_path = PathBuf::from("example/c_usage/example.json");
+    let temp_dir = TempDir::new().unwrap();
+    let template_dir = temp_dir.path().join("templates");
+    fs::create_dir(&template_dir).unwrap();
+    fs::write(template_dir.join("prologue.h"), "/* cached prologue */\n").unwrap();
+    fs::write(
+        template_dir.join("helpers_u16_le.h"),
+        "/* cached helpers */\n",
+    )
+    .unwrap();
+    let options = h6xserial_idl::emit_c::CGenOptions {
+        template_dir: Some(template_dir.clone()),
+        ..Default::default()
+    };
+    let generate = || {
+        h6xserial_idl::emit_c::generate_multiple_with_options(
+            &metadata,
+            &messages,
+            &input_path,
+            "example",
+            &options,
+        )
+    };
+
+    let first = generate().unwrap();
+    assert!(first[0].content.contains("/* cached helpers */"));
+    assert!(first[1].content.contains("/* cached prologue */"));
+
+    // The second run is served from memory, so the directory is not needed
+    fs::remove_dir_all(&template_dir).unwrap();
+    let second = generate().unwrap();
+    assert_eq!(first.len(), second.len());
+    for (a, b) in first.iter().zip(&second) {
+        assert!(a.content == b.content, "{} differs", a.filename);
+    }
+
+    h6xserial_idl::clear_template_cache();
+    let err = generate().unwrap_err();
+    assert!(matches!(
+        err,
+        h6xserial_idl::IdlError::TemplateNotFound { .. }
+    ));
+}
+
+/// Held by the tests that clear the process-wide template cache, so one
+/// cannot clear it halfway through the other.
+static TEMPLATE_CACHE_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+#[test]
+fn test_symbol_prefix_and_template_placeholders() {
+    let _cache = TEMPLATE_CACHE_LOCK
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    let (metadata, messages) = parse_example_messages();
+    let input_path = PathBuf::from("example.json");
+    let temp_dir = TempDir::new().unwrap();
+    let template_dir = temp_dir.path().join("templates");
+    fs::create_dir(&template_dir).unwrap();
+    fs::write(
+        template_dir.join("prologue.h"),
+        "/* {{input_file}} v{{version}}, \\{{prefix}} is {{ prefix }} */\n",
+    )
+    .unwrap();
+    let options = h6xserial_idl::emit_c::CGenOptions {
+        template_dir: Some(template_dir.clone()),
+        symbol_prefix: Some("acme".to_string()),
+        ..Default::default()
+    };
+
+    let files = h6xserial_idl::emit_c::generate_multiple_with_options(
+        &metadata,
+        &messages,
+        &input_path,
+        "example",
+        &options,
+    )
+    .unwrap();
+    let version = metadata.version.as_deref().unwrap_or_default();
+    for file in &files {
+        assert!(
+            !file.content.contains("h6xserial_write") && !file.content.contains("h6xserial_read"),
+            "{} still uses the default helper prefix",
+            file.filename
+        );
+        fs::write(temp_dir.path().join(&file.filename), &file.content).unwrap();
+    }
+    let byteorder = &files[0].content;
+    assert!(byteorder.contains("static inline void acme_write_u16_le("));
+    assert!(byteorder.contains(&format!(
+        "/* example.json v{}, {{{{prefix}}}} is acme */",
+        version
+    )));
+    let server = files
+        .iter()
+        .find(|f| f.filename == "example_server.h")
+        .unwrap();
+    assert!(server.content.contains("acme_write_u16_le("));
+
+    // Template files are cached, so new ones need a fresh read
+    fs::write(template_dir.join("epilogue.h"), "/* {{author}} */\n").unwrap();
+    h6xserial_idl::clear_template_cache();
+    let err = h6xserial_idl::emit_c::generate_multiple_with_options(
+        &metadata,
+        &messages,
+        &input_path,
+        "example",
+        &options,
+    )
+    .unwrap_err();
+    assert!(matches!(
+        &err,
+        h6xserial_idl::IdlError::Template { file, message }
+            if file == "epilogue.h" && message.contains("'{{author}}'")
+    ));
+
+    let Some(compiler) = find_c_compiler() else {
+        eprintln!("Skipping compile step: no C compiler found");
+        return;
+    };
+    fs::write(
+        temp_dir.path().join("main.c"),
+        "#include \"example_server.h\"\n#include \"example_client_common.h\"\nint main(void) { return 0; }\n",
+    )
+    .unwrap();
+    compile_c(&compiler, temp_dir.path(), &["main.c"]);
+}
+
+#[test]
+fn test_check_mode_reports_stale_outputs() {
+    let input = fs::canonicalize("example/c_usage/example.json").unwrap();
+    let work_dir = TempDir::new().unwrap();
+    let run = |extra: &[&str], out: &str| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+            .current_dir(work_dir.path())
+            .args(extra)
+            .arg(&input)
+            .arg(out)
+            .output()
+            .unwrap()
+    };
+
+    let output = run(&["--check"], "out");
+    assert!(!output.status.success(), "missing files are stale");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("out/example_types.h: missing"));
+    assert!(fs::read_dir(work_dir.path()).unwrap().next().is_none());
+
+    assert!(run(&[], "out").status.success());
+    let output = run(&["--check"], "out");
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("are up to date"));
+
+    let types = work_dir.path().join("out/example_types.h");
+    let original = fs::read_to_string(&types).unwrap();
+    fs::write(
+        &types,
+        original.replace(
+            "#define H6XSERIAL_MAX_PAYLOAD_BYTES 251",
+            "#define H6XSERIAL_MAX_PAYLOAD_BYTES 64",
+        ),
+    )
+    .unwrap();
+    let output = run(&["--check"], "out");
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("--- out/example_types.h\n+++ out/example_types.h (generated)\n"));
+    assert!(stdout.contains(
+        "\n-#define H6XSERIAL_MAX_PAYLOAD_BYTES 64\n+#define H6XSERIAL_MAX_PAYLOAD_BYTES 251\n"
+    ));
+    assert!(
+        String::from_utf8_lossy(&output.stderr)
+            .contains("1 of 8 generated file(s) are out of date")
+    );
+
+    assert!(run(&["--export_docs"], "docs").status.success());
+    assert!(run(&["--export_docs", "--check"], "docs").status.success());
+    fs::write(work_dir.path().join("docs/COMMANDS.md"), "# stale\n").unwrap();
+    let output = run(&["--export_docs", "--check"], "docs");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("-# stale\n"));
+}
+
+#[test]
+fn test_unchanged_outputs_keep_their_mtime() {
+    let input = fs::canonicalize("example/c_usage/example.json").unwrap();
+    let work_dir = TempDir::new().unwrap();
+    let run = |extra: &[&str], out: &str| {
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+            .current_dir(work_dir.path())
+            .args(extra)
+            .arg(&input)
+            .arg(out)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+    // Backdate the files so a rewrite is visible on any mtime resolution
+    let old = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000);
+    let backdate = |path: &Path| {
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+    };
+    let mtime = |path: &Path| fs::metadata(path).unwrap().modified().unwrap();
+
+    run(&[], "out");
+    let files: Vec<PathBuf> = fs::read_dir(work_dir.path().join("out"))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    assert_eq!(files.len(), 8);
+    files.iter().for_each(|path| backdate(path));
+
+    let stdout = run(&[], "out");
+    assert!(stdout.contains("Generated: out/example_client_2.h (unchanged)"));
+    assert!(stdout.contains("for 10 message definition(s), 8 unchanged."));
+    for path in &files {
+        assert_eq!(mtime(path), old, "{} was rewritten", path.display());
+    }
+
+    // Only the file that differs from the generated content is written
+    let client = work_dir.path().join("out/example_client_3.h");
+    fs::write(&client, "stale\n").unwrap();
+    backdate(&client);
+    let stdout = run(&[], "out");
+    assert!(stdout.contains("Generated: out/example_client_3.h\n"));
+    assert!(stdout.contains(", 7 unchanged."));
+    for path in &files {
+        assert_eq!(mtime(path) == old, *path != client, "{}", path.display());
+    }
+    assert!(fs::read_to_string(&client).unwrap().contains("#ifndef"));
+
+    let stdout = run(&["--force"], "out");
+    assert!(!stdout.contains("unchanged"));
+    for path in &files {
+        assert_ne!(mtime(path), old, "{} was not rewritten", path.display());
+    }
+
+    let stdout = run(&["--export_docs"], "docs");
+    assert!(!stdout.contains("(unchanged)"));
+    let stdout = run(&["--export_docs"], "docs");
+    assert!(stdout.contains("for 10 command(s) (unchanged)."));
+}
+
+#[test]
+fn test_role_filter_generates_one_client() {
+    let input = fs::canonicalize("example/c_usage/example.json").unwrap();
+    let work_dir = TempDir::new().unwrap();
+    let run = |extra: &[&str], out: &str| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+            .current_dir(work_dir.path())
+            .args(extra)
+            .arg(&input)
+            .arg(out)
+            .output()
+            .unwrap()
+    };
+    let listing = |out: &str| {
+        let mut names: Vec<String> = fs::read_dir(work_dir.path().join(out))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    };
+
+    assert!(run(&[], "all").status.success());
+    assert!(run(&["--client-id", "3"], "client").status.success());
+    assert_eq!(
+        listing("client"),
+        [
+            "example_client_3.h",
+            "example_types.h",
+            "h6x_serial_byteorder.h"
+        ]
+    );
+    for name in listing("client") {
+        assert_eq!(
+            fs::read(work_dir.path().join("client").join(&name)).unwrap(),
+            fs::read(work_dir.path().join("all").join(&name)).unwrap(),
+            "{} differs",
+            name
+        );
+    }
+
+    let output = run(
+        &["--role", "server", "--types-header", "example_types.h"],
+        "all",
+    );
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Generated 1 C99 file(s)"));
+    assert!(stdout.contains("all/example_server.h (unchanged)"));
+
+    let output = run(&["--role", "client", "--client-id", "9"], "none");
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr)
+            .contains("no message targets client 9 (clients: 2, 3, 4)")
+    );
+    assert!(!work_dir.path().join("none").exists());
+
+    for (args, message) in [
+        (
+            &["--role", "client"][..],
+            "required arguments were not provided",
+        ),
+        (
+            &["--role", "server", "--client-id", "2"][..],
+            "--client-id only applies to --role client",
+        ),
+        (
+            &["--types-header", "x.h"][..],
+            "<--role <ROLE>|--client-id <N>>",
+        ),
+        (&["--client-id", "2", "--emit-tests"][..], "need every role"),
+    ] {
+        let output = run(args, "err");
+        assert!(!output.status.success());
+        assert!(
+            String::from_utf8_lossy(&output.stderr).contains(message),
+            "{:?}",
+            args
+        );
+    }
+}
+
+#[test]
+fn test_client_documentation_files() {
+    let input = fs::canonicalize("example/c_usage/example.json").unwrap();
+    let work_dir = TempDir::new().unwrap();
+    let run = |extra: &[&str]| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+            .current_dir(work_dir.path())
+            .arg("--export_docs")
+            .args(extra)
+            .arg(&input)
+            .arg("docs")
+            .output()
+            .unwrap()
+    };
+
+    let output = run(&["--client-id", "3"]);
+    assert!(output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stdout)
+            .contains("Generated documentation at docs/COMMANDS_client_3.md for 6 command(s).")
+    );
+    let client_3 = fs::read_to_string(work_dir.path().join("docs/COMMANDS_client_3.md")).unwrap();
+    assert!(client_3.contains("CMD_MOTOR_SPEEDS"));
+    assert!(!client_3.contains("CMD_HUMIDITY"));
+
+    assert!(run(&["--all-clients"]).status.success());
+    let mut names: Vec<String> = fs::read_dir(work_dir.path().join("docs"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    assert_eq!(
+        names,
+        [
+            "COMMANDS_client_2.md",
+            "COMMANDS_client_3.md",
+            "COMMANDS_client_4.md"
+        ]
+    );
+    assert_eq!(
+        fs::read_to_string(work_dir.path().join("docs/COMMANDS_client_3.md")).unwrap(),
+        client_3
+    );
+
+    let output = run(&["--role", "server"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("split by client"));
+    let output = run(&["--all-clients", "--client-id", "3"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_out_dir_and_base_name() {
+    let input = fs::canonicalize("example/c_usage/example.json").unwrap();
+    let work_dir = TempDir::new().unwrap();
+    let root = fs::canonicalize(work_dir.path()).unwrap();
+    let out_dir = root.join("gen/c");
+    let run = |args: &[&std::ffi::OsStr]| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+            .current_dir(&root)
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    let output = run(&[
+        "--out-dir".as_ref(),
+        out_dir.as_os_str(),
+        "--base-name".as_ref(),
+        "proto".as_ref(),
+        input.as_os_str(),
+    ]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(out_dir.join("proto_server.h").exists());
+    assert!(
+        fs::read_to_string(out_dir.join("proto_types.h"))
+            .unwrap()
+            .contains("proto_msg_ping_t")
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("Generated: gen/c/proto_server.h\n"),
+        "paths should be relative to the working directory:\n{}",
+        stdout
+    );
+
+    let output = run(&[
+        "--export_docs".as_ref(),
+        "--base-name".as_ref(),
+        "protocol".as_ref(),
+        "--out-dir".as_ref(),
+        "docs".as_ref(),
+        input.as_os_str(),
+    ]);
+    assert!(output.status.success());
+    assert!(root.join("docs/protocol.md").exists());
+
+    let output = run(&[
+        "--out-dir".as_ref(),
+        "a".as_ref(),
+        input.as_os_str(),
+        "b".as_ref(),
+    ]);
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr)
+            .contains("both an output path ('b') and --out-dir ('a') were given")
+    );
+}
+
+/// Runs the generator binary with `stdin` piped in.
+fn run_with_stdin(args: &[&str], stdin: &str) -> std::process::Output {
+    use std::io::Write;
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+        .args(args)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn test_stdin_input_and_stdout_output() {
+    let raw = fs::read_to_string("example/c_usage/example.json").unwrap();
+
+    let output = run_with_stdin(&["-", "-"], &raw);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let header = String::from_utf8(output.stdout).unwrap();
+    assert!(header.starts_with(&format!(
+        "/*\n * Auto-generated by h6xserial_idl {}.\n * Source: <stdin>\n * IR fingerprint: ",
+        env!("CARGO_PKG_VERSION")
+    )));
+    assert!(header.contains("#ifndef MESSAGES_H"));
+    assert!(header.contains("messages_msg_ping_t"));
+    assert!(header.trim_end().ends_with("#endif /* MESSAGES_H */"));
+
+    let output = run_with_stdin(&["--export_docs", "-", "-"], &raw);
+    assert!(output.status.success());
+    let docs = String::from_utf8(output.stdout).unwrap();
+    assert!(docs.starts_with("# Command Definitions"));
+    assert!(docs.contains("Auto-generated from: `<stdin>`"));
+
+    let output = run_with_stdin(&["--emit-tests", "-", "-"], &raw);
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("'-' output writes a single header"));
+
+    let work_dir = TempDir::new().unwrap();
+    let out_dir = work_dir.path().join("out");
+    let output = run_with_stdin(&["-", out_dir.to_str().unwrap()], &raw);
+    assert!(output.status.success());
+    assert!(out_dir.join("messages_types.h").exists());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Generated: "));
+}
+
+/// A `cc` configuration for the host, as a build script would get it. The
+/// flags come from each test, so `cc` adds no optimization, debug or
+/// warning flags of its own; `CC`, `CXX` and `CFLAGS` still apply.
+fn cc_build() -> cc::Build {
+    let mut build = cc::Build::new();
+    build
+        .cargo_metadata(false)
+        .opt_level(0)
+        .debug(false)
+        .warnings(false)
+        .target(env!("H6XSERIAL_BUILD_TARGET"))
+        .host(env!("H6XSERIAL_BUILD_HOST"));
+    build
+}
+
+/// Returns the C compiler to use for compile checks, or `None` when no
+/// compiler is available (the compile tests are then skipped).
+fn find_c_compiler() -> Option<cc::Tool> {
+    runnable(cc_build().try_get_compiler().ok()?)
+}
+
+/// Same as [`find_c_compiler`] for the C++ compiler.
+fn find_cxx_compiler() -> Option<cc::Tool> {
+    runnable(cc_build().cpp(true).try_get_compiler().ok()?)
+}
+
+/// `tool`, if it answers `--version`.
+fn runnable(tool: cc::Tool) -> Option<cc::Tool> {
+    tool.to_command()
+        .arg("--version")
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+        .then_some(tool)
+}
+
+/// Compiles and links the given C sources in `dir`, panicking with the
+/// compiler output on failure.
+fn compile_c(compiler: &cc::Tool, dir: &std::path::Path, sources: &[&str]) {
+    let output = compiler
+        .to_command()
+        .current_dir(dir)
+        .args([
+            "-std=c99", "-Wall", "-Wextra", "-Werror", "-I.", "-o", "a.out",
+        ])
+        .args(sources)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "C compilation failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+fn parse_example_messages() -> (
+    h6xserial_idl::Metadata,
+    Vec<h6xserial_idl::MessageDefinition>,
+) {
+    let raw = fs::read_to_string("example/c_usage/example.json").unwrap();
+    let json: serde_json::Value = serde_json::from_str(&raw).unwrap();
+    let (metadata, mut messages) =
+        h6xserial_idl::parse_messages(json.as_object().unwrap()).unwrap();
+    messages.sort_by_key(|m| m.packet_id);
+    (metadata, messages)
+}
+
+#[test]
+fn test_single_header_compiles_in_both_impl_styles() {
+    let Some(compiler) = find_c_compiler() else {
+        eprintln!("Skipping test: no C compiler found");
+        return;
+    };
+    let (metadata, messages) = parse_example_messages();
+    let input_path = PathBuf::from("example.json");
+
+    for style in [
+        h6xserial_idl::emit_c::ImplStyle::Inline,
+        h6xserial_idl::emit_c::ImplStyle::Source,
+    ] {
+        let temp_dir = TempDir::new().unwrap();
+        let options = h6xserial_idl::emit_c::CGenOptions {
+            impl_style: style,
+            ..Default::default()
+        };
+        let files = h6xserial_idl::emit_c::generate_files(
+            &metadata,
+            &messages,
+            &input_path,
+            &temp_dir.path().join("example.h"),
+            &options,
+        )
+        .unwrap();
+
+        let mut sources = vec!["main.c".to_string()];
+        for file in &files {
+            fs::write(temp_dir.path().join(&file.filename), &file.content).unwrap();
+            if file.filename.ends_with(".c") {
+                sources.push(file.filename.clone());
+            }
+        }
+        match style {
+            h6xserial_idl::emit_c::ImplStyle::Inline => {
+                assert_eq!(files.len(), 1);
+                assert!(files[0].content.contains("static inline size_t"));
+            }
+            h6xserial_idl::emit_c::ImplStyle::Source => {
+                assert_eq!(files.len(), 2);
+                assert_eq!(files[1].filename, "example.c");
+                assert!(!files[0].content.contains("static inline"));
+                assert!(files[0].content.contains(
+                    "size_t example_msg_ping_encode(const example_msg_ping_t *msg, uint8_t *out_buf, const size_t out_len);"
+                ));
+                assert!(files[1].content.contains("h6xserial_write_u16_le"));
+            }
+        }
+
+        fs::write(
+            temp_dir.path().join("main.c"),
+            r#"#include "example.h"
+int main(void) {
+    example_msg_ping_t ping = {0};
+    uint8_t buf[8];
+    ping.value = 7;
+    size_t len = example_msg_ping_encode(&ping, buf, sizeof(buf));
+    example_msg_ping_t decoded = {0};
+    return (len == 1 && example_msg_ping_decode(&decoded, buf, len) && decoded.value == 7) ? 0 : 1;
+}
+"#,
+        )
+        .unwrap();
+        let refs: Vec<&str> = sources.iter().map(|s| s.as_str()).collect();
+        compile_c(&compiler, temp_dir.path(), &refs);
+    }
+}
+
+#[test]
+fn test_multiple_headers_compile_in_both_impl_styles() {
+    let Some(compiler) = find_c_compiler() else {
+        eprintln!("Skipping test: no C compiler found");
+        return;
+    };
+    let (metadata, messages) = parse_example_messages();
+    let input_path = PathBuf::from("example.json");
+
+    for style in [
+        h6xserial_idl::emit_c::ImplStyle::Inline,
+        h6xserial_idl::emit_c::ImplStyle::Source,
+    ] {
+        let temp_dir = TempDir::new().unwrap();
+        let options = h6xserial_idl::emit_c::CGenOptions {
+            impl_style: style,
+            ..Default::default()
+        };
+        let files = h6xserial_idl::emit_c::generate_multiple_with_options(
+            &metadata,
+            &messages,
+            &input_path,
+            "example",
+            &options,
+        )
+        .unwrap();
+
+        let mut sources = vec!["main.c".to_string()];
+        for file in &files {
+            fs::write(temp_dir.path().join(&file.filename), &file.content).unwrap();
+            if file.filename.ends_with(".c") {
+                sources.push(file.filename.clone());
+            }
+        }
+        if style == h6xserial_idl::emit_c::ImplStyle::Source {
+            assert!(files.iter().any(|f| f.filename == "example_server.c"));
+            assert!(
+                files
+                    .iter()
+                    .any(|f| f.filename == "example_client_common.c")
+            );
+        }
+
+        fs::write(
+            temp_dir.path().join("main.c"),
+            r#"#include "example_server.h"
+#include "example_client_2.h"
+#include "example_client_3.h"
+#include "example_client_4.h"
+int main(void) {
+    example_msg_temperature_t temp = {0};
+    uint8_t buf[8];
+    temp.value = 21.5f;
+    size_t len = example_msg_temperature_encode(&temp, buf, sizeof(buf));
+    example_msg_temperature_t decoded = {0};
+    return (len == 4 && example_msg_temperature_decode(&decoded, buf, len)) ? 0 : 1;
+}
+"#,
+        )
+        .unwrap();
+        let refs: Vec<&str> = sources.iter().map(|s| s.as_str()).collect();
+        compile_c(&compiler, temp_dir.path(), &refs);
+    }
+}
+
+#[test]
+fn test_static_asserts_compile_across_standards() {
+    let Some(compiler) = find_c_compiler() else {
+        eprintln!("Skipping test: no C compiler found");
+        return;
+    };
+    let (metadata, messages) = parse_example_messages();
+    let temp_dir = TempDir::new().unwrap();
+    let header = h6xserial_idl::emit_c::generate(
+        &metadata,
+        &messages,
+        &PathBuf::from("example.json"),
+        &temp_dir.path().join("example.h"),
+    )
+    .unwrap();
+    assert!(header.contains("#define EXAMPLE_MSG_SENSOR_DATA_MAX_SIZE 38"));
+    assert!(header.contains(
+        "H6XSERIAL_STATIC_ASSERT(EXAMPLE_MSG_SENSOR_DATA_MAX_SIZE == 38, example_msg_sensor_data_max_size)"
+    ));
+    fs::write(temp_dir.path().join("example.h"), &header).unwrap();
+
+    let compile = |std: &str, body: &str| {
+        fs::write(temp_dir.path().join("main.c"), body).unwrap();
+        compiler
+            .to_command()
+            .current_dir(temp_dir.path())
+            .args([std, "-Wall", "-Wextra", "-Werror", "-I.", "-c", "main.c"])
+            .output()
+            .unwrap()
+    };
+    let ok = "#include \"example.h\"\nint main(void) { return 0; }\n";
+    let broken = "#include \"example.h\"\nH6XSERIAL_STATIC_ASSERT(sizeof(uint8_t) == 2, broken)\nint main(void) { return 0; }\n";
+
+    for std in ["-std=c99", "-std=c11"] {
+        let output = compile(std, ok);
+        assert!(
+            output.status.success(),
+            "{} compilation failed:\n{}",
+            std,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert!(!compile(std, broken).status.success());
+    }
+}
+
+#[test]
+fn test_c89_output_compiles_as_ansi_c() {
+    let Some(compiler) = find_c_compiler() else {
+        eprintln!("Skipping test: no C compiler found");
+        return;
+    };
+    let (metadata, messages) = h6xserial_idl::parse_str(
+        r#"{ "packets": {
+            "temperature": { "packet_id": 1, "msg_type": "int16", "scale": 0.01, "offset": -40 },
+            "speed": { "packet_id": 2, "msg_type": "uint64", "min": 10, "max": 18000000000000000000, "request_type": "sub" },
+            "firmware": { "packet_id": 3, "msg_type": "uint16", "array": true, "max_length": 64, "sector_bytes": 16, "request_type": "sub" },
+            "old": { "packet_id": 4, "msg_type": "bool", "deprecated": true, "request_type": "sub" },
+            "get": { "packet_id": 5, "msg_type": "u8", "response": "reply", "request_type": "sub" },
+            "reply": { "packet_id": 6, "msg_type": "f64" },
+            "pose": { "packet_id": 7, "msg_type": "struct", "fields": {
+                "x": { "type": "f32", "min": -1.5, "max": 1.5 },
+                "levels": { "type": "u8", "array": true, "max_length": 2 },
+                "inner": { "type": "struct", "fields": { "v": { "type": "u32", "array": true, "max_length": 3 } } } } } } }"#,
+    )
+    .unwrap();
+
+    for style in [
+        h6xserial_idl::emit_c::ImplStyle::Inline,
+        h6xserial_idl::emit_c::ImplStyle::Source,
+    ] {
+        let temp_dir = TempDir::new().unwrap();
+        let options = h6xserial_idl::emit_c::CGenOptions {
+            impl_style: style,
+            dialect: h6xserial_idl::emit_c::CDialect::C89,
+            doc_comments: true,
+            encode_range: h6xserial_idl::emit_c::EncodeRange::Clamp,
+            send_stack_limit: Some(h6xserial_idl::emit_c::DEFAULT_SEND_STACK_LIMIT),
+            fast_path: true,
+            ..Default::default()
+        };
+        let mut files = h6xserial_idl::emit_c::generate_multiple_with_options(
+            &metadata,
+            &messages,
+            &PathBuf::from("c89.json"),
+            "c89",
+            &options,
+        )
+        .unwrap();
+        files.push(h6xserial_idl::emit_c::generate_server_skeleton(
+            &metadata, &messages, "c89", &options,
+        ));
+
+        let mut sources = vec!["main.c".to_string()];
+        for file in &files {
+            fs::write(temp_dir.path().join(&file.filename), &file.content).unwrap();
+            if file.filename.ends_with(".c") {
+                sources.push(file.filename.clone());
+            }
+        }
+        fs::write(
+            temp_dir.path().join("main.c"),
+            r#"#include "c89_server.h"
+#include "c89_client_common.h"
+int main(void) {
+    c89_msg_pose_t pose;
+    c89_msg_pose_t decoded;
+    c89_msg_firmware_t firmware;
+    c89_msg_firmware_t received;
+    uint8_t buf[C89_MSG_POSE_MAX_SIZE];
+    size_t len;
+    size_t i;
+    memset(&pose, 0, sizeof(pose));
+    memset(&decoded, 0, sizeof(decoded));
+    pose.x = 2.0f;
+    pose.levels_length = 2;
+    pose.levels[1] = 9;
+    pose.inner.v_length = 1;
+    pose.inner.v[0] = 0x01020304u;
+    len = c89_msg_pose_encode(&pose, buf, sizeof(buf));
+    if (len != 4 + 2 + 4 || !c89_msg_pose_decode(&decoded, buf, len)) {
+        return 1;
+    }
+    if (decoded.x != 1.5f || decoded.levels[1] != 9 || decoded.inner.v[0] != 0x01020304u) {
+        return 2;
+    }
+    memset(&firmware, 0, sizeof(firmware));
+    memset(&received, 0, sizeof(received));
+    firmware.length = 20;
+    for (i = 0; i < firmware.length; ++i) {
+        firmware.data[i] = (uint16_t)(i * 257);
+    }
+    for (i = 0; i < c89_msg_firmware_sector_count(&firmware); ++i) {
+        len = c89_msg_firmware_encode_sector(&firmware, i, buf, sizeof(buf));
+        if (len == 0 || !c89_msg_firmware_decode_sector(&received, i, buf, len)) {
+            return 3;
+        }
+    }
+    if (!c89_msg_firmware_sectors_complete(&received) || received.length != 20 ||
+        received.data[19] != firmware.data[19]) {
+        return 4;
+    }
+    return c89_response_packet_id_for(C89_MSG_GET_PACKET_ID) == C89_MSG_REPLY_PACKET_ID ? 0 : 5;
+}
+"#,
+        )
+        .unwrap();
+        let output = compiler
+            .to_command()
+            .current_dir(temp_dir.path())
+            .args([
+                "-std=c89",
+                "-pedantic",
+                "-Wall",
+                "-Wextra",
+                "-Werror",
+                "-Wdeclaration-after-statement",
+                "-I.",
+                "-o",
+                "a.out",
+            ])
+            .args(&sources)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{:?}: C89 compilation failed:\n{}",
+            style,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let run = run_c_program(temp_dir.path());
+        assert_eq!(run.status.code(), Some(0), "{:?}", style);
+    }
+}
+
+/// MISRA C:2012 rules of the "mandatory" category, which allow no deviation.
+const MISRA_MANDATORY_RULES: &[&str] = &[
+    "9.1", "12.5", "13.6", "17.3", "17.4", "17.6", "19.1", "21.13", "21.17", "21.18", "21.19",
+    "21.20", "22.2", "22.4", "22.5", "22.6",
+];
+
+#[test]
+fn test_misra_output_of_sensor_example() {
+    let Some(compiler) = find_c_compiler() else {
+        eprintln!("Skipping test: no C compiler found");
+        return;
+    };
+    let (metadata, messages) = parse_example_messages();
+    let temp_dir = TempDir::new().unwrap();
+    let options = h6xserial_idl::emit_c::CGenOptions {
+        misra: true,
+        emit_tests: true,
+        ..Default::default()
+    };
+    let files = h6xserial_idl::emit_c::generate_multiple_with_options(
+        &metadata,
+        &messages,
+        &PathBuf::from("example.json"),
+        "example",
+        &options,
+    )
+    .unwrap();
+    for file in &files {
+        fs::write(temp_dir.path().join(&file.filename), &file.content).unwrap();
+    }
+
+    let output = compiler
+        .to_command()
+        .current_dir(temp_dir.path())
+        .args([
+            "-std=c99",
+            "-Wall",
+            "-Wextra",
+            "-Wconversion",
+            "-Wsign-conversion",
+            "-Werror",
+            "-I.",
+            "-o",
+            "a.out",
+            "example_tests.c",
+        ])
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "C compilation failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(run_c_program(temp_dir.path()).status.code(), Some(0));
+
+    // The MISRA check needs cppcheck and its misra addon
+    let cppcheck = std::process::Command::new("cppcheck")
+        .current_dir(temp_dir.path())
+        .args([
+            "--addon=misra",
+            "--std=c99",
+            "--quiet",
+            "--template={id}",
+            "-I.",
+            "example_tests.c",
+        ])
+        .output();
+    let Ok(cppcheck) = cppcheck else {
+        eprintln!("Skipping MISRA check: cppcheck not found");
+        return;
+    };
+    let report = String::from_utf8_lossy(&cppcheck.stderr);
+    let mandatory: Vec<&str> = report
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("misra-c2012-"))
+        .filter(|rule| MISRA_MANDATORY_RULES.contains(rule))
+        .collect();
+    assert!(
+        mandatory.is_empty(),
+        "mandatory MISRA rules violated: {:?}\n{}",
+        mandatory,
+        report
+    );
+}
+
+fn run_c_program(dir: &std::path::Path) -> std::process::Output {
+    std::process::Command::new(dir.join("a.out"))
+        .current_dir(dir)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_emitted_self_tests_pass() {
+    let json_content = r#"{
+        "packets": {
+            "flag": { "packet_id": 1, "msg_type": "bool", "request_type": "pub" },
+            "counter": { "packet_id": 2, "msg_type": "int64", "endianess": "big", "target_client_id": 1 },
+            "samples": {
+                "packet_id": 3,
+                "msg_type": "int16",
+                "array": true,
+                "max_length": 10,
+                "endianess": "big",
+                "request_type": "sub"
+            },
+            "label": { "packet_id": 4, "msg_type": "char", "array": true, "max_length": 12 },
+            "record": {
+                "packet_id": 5,
+                "msg_type": "struct",
+                "target_client_id": 2,
+                "fields": {
+                    "enabled": { "type": "bool" },
+                    "ratio": { "type": "float64", "endianess": "big" },
+                    "pose": {
+                        "type": "struct",
+                        "fields": {
+                            "x": { "type": "float32" },
+                            "id": { "type": "uint32", "endianess": "big" }
+                        }
+                    },
+                    "readings": { "type": "uint16", "array": true, "max_length": 6 }
+                }
+            }
+        }
+    }"#;
+    let json: serde_json::Value = serde_json::from_str(json_content).unwrap();
+    let (metadata, mut messages) =
+        h6xserial_idl::parse_messages(json.as_object().unwrap()).unwrap();
+    messages.sort_by_key(|m| m.packet_id);
+    let input_path = PathBuf::from("selftest.json");
+
+    for style in [
+        h6xserial_idl::emit_c::ImplStyle::Inline,
+        h6xserial_idl::emit_c::ImplStyle::Source,
+    ] {
+        let options = h6xserial_idl::emit_c::CGenOptions {
+            impl_style: style,
+            emit_tests: true,
+            ..Default::default()
+        };
+        let multiple = h6xserial_idl::emit_c::generate_multiple_with_options(
+            &metadata,
+            &messages,
+            &input_path,
+            "selftest",
+            &options,
+        )
+        .unwrap();
+        let temp_dir = TempDir::new().unwrap();
+        let single = h6xserial_idl::emit_c::generate_files(
+            &metadata,
+            &messages,
+            &input_path,
+            &temp_dir.path().join("selftest.h"),
+            &options,
+        )
+        .unwrap();
+
+        for files in [multiple, single] {
+            let tests = files
+                .iter()
+                .find(|f| f.filename == "selftest_tests.c")
+                .expect("self-test file should be generated");
+            assert!(tests.content.contains("int main(void)"));
+            assert!(tests.content.contains("static void test_record_max(void)"));
+
+            let Some(compiler) = find_c_compiler() else {
+                eprintln!("Skipping compile step: no C compiler found");
+                return;
+            };
+            let temp_dir = TempDir::new().unwrap();
+            let mut sources = Vec::new();
+            for file in &files {
+                fs::write(temp_dir.path().join(&file.filename), &file.content).unwrap();
+                if file.filename.ends_with(".c") {
+                    sources.push(file.filename.as_str());
+                }
+            }
+            compile_c(&compiler, temp_dir.path(), &sources);
+            let output = run_c_program(temp_dir.path());
+            assert!(
+                output.status.success(),
+                "self-test failed:\n{}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+    }
+}
+
+#[test]
+fn test_emitted_self_tests_skip_at_gaps() {
+    let work_dir = TempDir::new().unwrap();
+    let input = work_dir.path().join("gap.json");
+    fs::write(
+        &input,
+        r#"{ "packets": {
+                 "status": { "packet_id": 1, "msg_type": "struct", "fields": {
+                     "mode": { "type": "u8" },
+                     "speed": { "type": "u16", "at": 4 },
+                     "flags": { "type": "u8" },
+                     "crc": { "type": "u16", "at": 8 } } }
+             } }"#,
+    )
+    .unwrap();
+
+    for style in ["inline", "source"] {
+        let out_dir = work_dir.path().join(style);
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+            .args(["--emit-tests", "--impl-style", style])
+            .arg(&input)
+            .arg(&out_dir)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let tests = fs::read_to_string(out_dir.join("gap_tests.c")).unwrap();
+        assert!(!tests.contains("_reserved_"), "{}", tests);
+
+        let Some(compiler) = find_c_compiler() else {
+            eprintln!("Skipping compilation: no C compiler found");
+            return;
+        };
+        let mut sources = vec!["gap_tests.c".to_string()];
+        for entry in fs::read_dir(&out_dir).unwrap() {
+            let name = entry.unwrap().file_name().into_string().unwrap();
+            if name.ends_with(".c") && name != "gap_tests.c" {
+                sources.push(name);
+            }
+        }
+        let sources: Vec<&str> = sources.iter().map(String::as_str).collect();
+        compile_c(&compiler, &out_dir, &sources);
+        let run = run_c_program(&out_dir);
+        assert!(
+            run.status.success(),
+            "{}",
+            String::from_utf8_lossy(&run.stderr)
+        );
+    }
+}
+
+#[test]
+fn test_emitted_fuzz_harness_runs_standalone() {
+    let json_content = r#"{
+        "packets": {
+            "counter": { "packet_id": 2, "msg_type": "int64", "endianess": "big" },
+            "samples": { "packet_id": 3, "msg_type": "int16", "array": true, "max_length": 10 },
+            "record": {
+                "packet_id": 5,
+                "msg_type": "struct",
+                "fields": {
+                    "enabled": { "type": "bool" },
+                    "pose": {
+                        "type": "struct",
+                        "fields": {
+                            "readings": { "type": "uint16", "array": true, "max_length": 6 }
+                        }
+                    },
+                    "tail": { "type": "uint8", "array": true, "max_length": 4 }
+                }
+            }
+        }
+    }"#;
+    let json: serde_json::Value = serde_json::from_str(json_content).unwrap();
+    let (metadata, mut messages) =
+        h6xserial_idl::parse_messages(json.as_object().unwrap()).unwrap();
+    messages.sort_by_key(|m| m.packet_id);
+
+    let options = h6xserial_idl::emit_c::CGenOptions {
+        emit_fuzz: true,
+        ..Default::default()
+    };
+    let files = h6xserial_idl::emit_c::generate_multiple_with_options(
+        &metadata,
+        &messages,
+        &PathBuf::from("fuzzme.json"),
+        "fuzzme",
+        &options,
+    )
+    .unwrap();
+    let harness = files
+        .iter()
+        .find(|f| f.filename == "fuzzme_fuzz.c")
+        .expect("fuzz harness should be generated");
+    assert!(
+        harness
+            .content
+            .contains("int LLVMFuzzerTestOneInput(const uint8_t *data, size_t size) {")
+    );
+    assert!(harness.content.contains(
+        "H6XSERIAL_FUZZ_ASSERT(msg.pose.readings_length <= FUZZME_MSG_RECORD_POSE_READINGS_MAX_LENGTH);"
+    ));
+    assert!(harness.content.contains("#ifndef H6XSERIAL_FUZZ_LIBFUZZER"));
+
+    let Some(compiler) = find_c_compiler() else {
+        eprintln!("Skipping compile step: no C compiler found");
+        return;
+    };
+    let temp_dir = TempDir::new().unwrap();
+    for file in &files {
+        fs::write(temp_dir.path().join(&file.filename), &file.content).unwrap();
+    }
+    compile_c(&compiler, temp_dir.path(), &["fuzzme_fuzz.c"]);
+
+    // Deterministic pseudo-random inputs for every selector and many lengths
+    let mut state: u32 = 0x1234_5678;
+    let mut inputs = Vec::new();
+    for selector in [0u8, 2, 3, 5, 255] {
+        for len in 0..40usize {
+            let mut input = vec![selector];
+            for _ in 0..len {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                input.push((state >> 16) as u8);
+            }
+            let name = format!("input_{}_{}", selector, len);
+            fs::write(temp_dir.path().join(&name), &input).unwrap();
+            inputs.push(name);
+        }
+    }
+    let output = std::process::Command::new(temp_dir.path().join("a.out"))
+        .current_dir(temp_dir.path())
+        .args(&inputs)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "fuzz harness failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn test_emitted_fuzz_harness_selects_wide_packet_ids() {
+    let json_content = r#"{
+        "packet_id_width": 16,
+        "default_endianness": "big",
+        "packets": {
+            "ping": { "packet_id": 1, "msg_type": "uint8" },
+            "samples": { "packet_id": 4660, "msg_type": "uint16", "array": true, "max_length": 3 }
+        }
+    }"#;
+    let json: serde_json::Value = serde_json::from_str(json_content).unwrap();
+    let (metadata, messages) = h6xserial_idl::parse_messages(json.as_object().unwrap()).unwrap();
+
+    let options = h6xserial_idl::emit_c::CGenOptions {
+        emit_fuzz: true,
+        ..Default::default()
+    };
+    let files = h6xserial_idl::emit_c::generate_multiple_with_options(
+        &metadata,
+        &messages,
+        &PathBuf::from("wide.json"),
+        "wide",
+        &options,
+    )
+    .unwrap();
+    let harness = files
+        .iter()
+        .find(|f| f.filename == "wide_fuzz.c")
+        .expect("fuzz harness should be generated");
+    assert!(harness.content.contains(
+        "    const uint8_t *payload = data + 2;\n    const size_t payload_len = size - 2;\n"
+    ));
+    assert!(
+        harness
+            .content
+            .contains("    switch ((uint16_t)(((uint16_t)data[0] << 8) | data[1])) {\n")
+    );
+    assert!(
+        harness
+            .content
+            .contains("    case WIDE_MSG_SAMPLES_PACKET_ID: {\n")
+    );
+
+    let Some(compiler) = find_c_compiler() else {
+        eprintln!("Skipping compile step: no C compiler found");
+        return;
+    };
+    let temp_dir = TempDir::new().unwrap();
+    for file in &files {
+        fs::write(temp_dir.path().join(&file.filename), &file.content).unwrap();
+    }
+    compile_c(&compiler, temp_dir.path(), &["wide_fuzz.c"]);
+    let inputs: [&[u8]; 4] = [
+        &[0x12],
+        &[0x12, 0x34],
+        &[0x12, 0x34, 1, 2, 3, 4, 5, 6, 7],
+        &[0, 1, 9],
+    ];
+    for (i, input) in inputs.iter().enumerate() {
+        fs::write(temp_dir.path().join(format!("input_{}", i)), input).unwrap();
+    }
+    let output = std::process::Command::new(temp_dir.path().join("a.out"))
+        .current_dir(temp_dir.path())
+        .args(["input_0", "input_1", "input_2", "input_3"])
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "fuzz harness failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+const NESTED_STRUCT_JSON: &str = r#"{
+    "version": "2.1.0",
+    "max_address": 32,
+    "packets": {
+        "status_text": {
+            "packet_id": 1,
+            "msg_type": "char",
+            "array": true,
+            "max_length": 24,
+            "request_type": "pub"
+        },
+        "vehicle_state": {
+            "packet_id": 2,
+            "msg_type": "struct",
+            "request_type": "sub",
+            "target_client_id": 1,
+            "fields": {
+                "name": { "type": "char", "array": true, "max_length": 8 },
+                "pose": {
+                    "type": "struct",
+                    "fields": {
+                        "x": { "type": "float32", "endianess": "big" },
+                        "y": { "type": "float32" },
+                        "covariance": {
+                            "type": "struct",
+                            "fields": {
+                                "xx": { "type": "float64" },
+                                "yy": { "type": "float64" }
+                            }
+                        }
+                    }
+                },
+                "armed": { "type": "bool" }
+            }
+        }
+    }
+}"#;
+
+const NESTED_STRUCT_DRIVER: &str = r#"
+int main(void) {
+    vehicle_msg_status_text_t text;
+    vehicle_msg_status_text_t text_out;
+    vehicle_msg_vehicle_state_t state;
+    vehicle_msg_vehicle_state_t state_out;
+    uint8_t buf[VEHICLE_MSG_VEHICLE_STATE_MAX_SIZE];
+    size_t len;
+
+    memset(&text, 0, sizeof(text));
+    memcpy(text.data, "ready", 5);
+    text.length = 5;
+    len = vehicle_msg_status_text_encode(&text, buf, sizeof(buf));
+    if (len != 5 || !vehicle_msg_status_text_decode(&text_out, buf, len)) return 1;
+    if (strcmp(text_out.data, "ready") != 0) return 2;
+
+    memset(&state, 0, sizeof(state));
+    memcpy(state.name, "rover", 5);
+    state.name_length = 5;
+    state.pose.x = 1.25f;
+    state.pose.covariance.yy = -0.5;
+    state.armed = true;
+    len = vehicle_msg_vehicle_state_encode(&state, buf, sizeof(buf));
+    if (len != 5 + 4 + 4 + 16 + 1) return 3;
+    if (!vehicle_msg_vehicle_state_decode(&state_out, buf, len)) return 4;
+    if (state_out.name_length != 5 || memcmp(state_out.name, "rover", 5) != 0) return 5;
+    if (state_out.pose.x != 1.25f || state_out.pose.covariance.yy != -0.5 || !state_out.armed) return 6;
+    return 0;
+}
+"#;
+
+#[test]
+fn test_generated_c_compiles_for_nested_structs_and_char_arrays() {
+    let json: serde_json::Value = serde_json::from_str(NESTED_STRUCT_JSON).unwrap();
+    let (metadata, mut messages) =
+        h6xserial_idl::parse_messages(json.as_object().unwrap()).unwrap();
+    messages.sort_by_key(|m| m.packet_id);
+    let input_path = PathBuf::from("vehicle.json");
+
+    let Some(compiler) = find_c_compiler() else {
+        eprintln!("Skipping test: no C compiler found");
+        return;
+    };
+
+    // Single-header `generate` path
+    let temp_dir = TempDir::new().unwrap();
+    let header = h6xserial_idl::emit_c::generate(
+        &metadata,
+        &messages,
+        &input_path,
+        &temp_dir.path().join("vehicle.h"),
+    )
+    .unwrap();
+    fs::write(temp_dir.path().join("vehicle.h"), header).unwrap();
+    fs::write(
+        temp_dir.path().join("main.c"),
+        format!("#include \"vehicle.h\"\n{}", NESTED_STRUCT_DRIVER),
+    )
+    .unwrap();
+    compile_c(&compiler, temp_dir.path(), &["main.c"]);
+    assert!(run_c_program(temp_dir.path()).status.success());
+
+    // Split headers from `generate_multiple`
+    let temp_dir = TempDir::new().unwrap();
+    let files =
+        h6xserial_idl::emit_c::generate_multiple(&metadata, &messages, &input_path, "vehicle")
+            .unwrap();
+    for file in &files {
+        fs::write(temp_dir.path().join(&file.filename), &file.content).unwrap();
+    }
+    fs::write(
+        temp_dir.path().join("main.c"),
+        format!(
+            "#include \"vehicle_server.h\"\n#include \"vehicle_client_1.h\"\n{}",
+            NESTED_STRUCT_DRIVER
+        ),
+    )
+    .unwrap();
+    compile_c(&compiler, temp_dir.path(), &["main.c"]);
+    assert!(run_c_program(temp_dir.path()).status.success());
+}
+
+#[test]
+fn test_umbrella_header_includes_the_defined_role() {
+    let (metadata, messages) = parse_example_messages();
+    let files = h6xserial_idl::emit_c::generate_multiple(
+        &metadata,
+        &messages,
+        &PathBuf::from("example.json"),
+        "example",
+    )
+    .unwrap();
+    let umbrella = &files
+        .iter()
+        .find(|f| f.filename == "example.h")
+        .unwrap()
+        .content;
+    assert!(umbrella.contains(
+        "#if defined(H6XSERIAL_ROLE_SERVER)\n#include \"example_server.h\"\n\
+         #elif defined(H6XSERIAL_ROLE_CLIENT_COMMON)\n#include \"example_client_common.h\"\n\
+         #elif defined(H6XSERIAL_ROLE_CLIENT_2)\n#include \"example_client_2.h\"\n"
+    ));
+    assert!(umbrella.contains("#elif defined(H6XSERIAL_ROLE_CLIENT_4)\n"));
+
+    let Some(compiler) = find_c_compiler() else {
+        eprintln!("Skipping test: no C compiler found");
+        return;
+    };
+    let temp_dir = TempDir::new().unwrap();
+    for file in &files {
+        fs::write(temp_dir.path().join(&file.filename), &file.content).unwrap();
+    }
+    fs::write(
+        temp_dir.path().join("main.c"),
+        "#include \"example.h\"\n\
+         #if !defined(EXAMPLE_CLIENT_3_H) || defined(EXAMPLE_SERVER_H)\n\
+         #error wrong role header\n\
+         #endif\n\
+         int main(void) { return 0; }\n",
+    )
+    .unwrap();
+    compile_c(
+        &compiler,
+        temp_dir.path(),
+        &["-DH6XSERIAL_ROLE_CLIENT_3", "main.c"],
+    );
+    let output = compiler
+        .to_command()
+        .current_dir(temp_dir.path())
+        .args(["-std=c99", "-I.", "-c", "main.c", "-o", "main.o"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("Define one of H6XSERIAL_ROLE_SERVER")
+    );
+}
+
+#[test]
+fn test_trailing_array_rejects_partial_elements() {
+    let (metadata, messages) = h6xserial_idl::parse_str(
+        r#"{ "trace": { "packet_id": 1, "msg_type": "struct", "fields": {
+                 "id": { "type": "u8" },
+                 "tags": { "type": "u8", "array": true, "max_length": 2 },
+                 "samples": { "type": "f32", "array": true, "max_length": 3 } } } }"#,
+    )
+    .unwrap();
+    let header = h6xserial_idl::emit_c::generate(
+        &metadata,
+        &messages,
+        &PathBuf::from("trace.json"),
+        &PathBuf::from("trace.h"),
+    )
+    .unwrap();
+    let Some(compiler) = find_c_compiler() else {
+        eprintln!("Skipping test: no C compiler found");
+        return;
+    };
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("trace.h"), header).unwrap();
+    fs::write(
+        temp_dir.path().join("main.c"),
+        r#"#include "trace.h"
+
+int main(void) {
+    uint8_t buf[TRACE_MSG_TRACE_MAX_SIZE] = {7, 1, 2};
+    trace_msg_trace_t msg;
+    /* id, both tags and two samples */
+    if (!trace_msg_trace_decode(&msg, buf, 11)) return 1;
+    if (msg.tags_length != 2 || msg.samples_length != 2) return 2;
+    /* A truncated sample fails instead of being dropped */
+    if (trace_msg_trace_decode(&msg, buf, 10)) return 3;
+    if (trace_msg_trace_decode(&msg, buf, 12)) return 4;
+    /* Short frames still fill the tags first */
+    if (!trace_msg_trace_decode(&msg, buf, 2)) return 5;
+    if (msg.tags_length != 1 || msg.samples_length != 0) return 6;
+    if (trace_msg_trace_decode(&msg, buf, 4)) return 7;
+    return 0;
+}
+"#,
+    )
+    .unwrap();
+    compile_c(&compiler, temp_dir.path(), &["main.c"]);
+    let output = run_c_program(temp_dir.path());
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn test_struct_encode_needs_only_the_bytes_in_use() {
+    let (metadata, messages) = h6xserial_idl::parse_str(
+        r#"{ "max_payload_bytes": 1024,
+             "burst": { "packet_id": 1, "msg_type": "struct", "fields": {
+                 "id": { "type": "u8" },
+                 "samples": { "type": "u16", "array": true, "max_length": 200 } } } }"#,
+    )
+    .unwrap();
+    let header = h6xserial_idl::emit_c::generate(
+        &metadata,
+        &messages,
+        &PathBuf::from("burst.json"),
+        &PathBuf::from("burst.h"),
+    )
+    .unwrap();
+    let Some(compiler) = find_c_compiler() else {
+        eprintln!("Skipping test: no C compiler found");
+        return;
+    };
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("burst.h"), header).unwrap();
+    fs::write(
+        temp_dir.path().join("main.c"),
+        r#"#include "burst.h"
+
+int main(void) {
+    static burst_msg_burst_t msg;
+    uint8_t buf[50];
+    msg.id = 3;
+    msg.samples_length = 10;
+    /* 21 bytes fit a buffer far below BURST_MSG_BURST_MAX_SIZE */
+    if (burst_msg_burst_encode(&msg, buf, sizeof(buf)) != 21) return 1;
+    if (burst_msg_burst_encode(&msg, buf, 21) != 21) return 2;
+    if (burst_msg_burst_encode(&msg, buf, 20) != 0) return 3;
+    /* A length above the maximum fails instead of being truncated */
+    msg.samples_length = BURST_MSG_BURST_SAMPLES_MAX_LENGTH + 1;
+    if (burst_msg_burst_encode(&msg, buf, sizeof(buf)) != 0) return 4;
+    return 0;
+}
+"#,
+    )
+    .unwrap();
+    compile_c(&compiler, temp_dir.path(), &["main.c"]);
+    let output = run_c_program(temp_dir.path());
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn test_deterministic_decode_leaves_no_stale_bytes() {
+    let work_dir = TempDir::new().unwrap();
+    fs::write(
+        work_dir.path().join("d.json"),
+        r#"{ "raw": { "packet_id": 1, "msg_type": "u16", "array": true, "max_length": 4 },
+             "log": { "packet_id": 2, "msg_type": "struct", "fields": {
+                 "level": { "type": "u8" },
+                 "text": { "type": "char", "array": true, "max_length": 6,
+                           "string_semantics": true } } },
+             "mode": { "packet_id": 3, "msg_type": "union", "tag": "u8", "variants": {
+                 "1": { "name": "idle", "fields": { "a": { "type": "u8" } } },
+                 "2": { "name": "run", "fields": { "speed": { "type": "u32" } } } } } }"#,
+    )
+    .unwrap();
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+        .current_dir(work_dir.path())
+        .args(["--deterministic-decode", "d.json", "out"])
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let Some(compiler) = find_c_compiler() else {
+        eprintln!("Skipping test: no C compiler found");
+        return;
+    };
+    let out_dir = work_dir.path().join("out");
+    fs::write(
+        out_dir.join("main.c"),
+        r#"#include <string.h>
+#include "d_client_common.h"
+
+static int all_zero(const void *p, size_t n) {
+    const unsigned char *b = (const unsigned char *)p;
+    size_t i;
+    for (i = 0; i < n; ++i) {
+        if (b[i] != 0) return 0;
+    }
+    return 1;
+}
+
+int main(void) {
+    const uint8_t raw[] = {1, 0};
+    const uint8_t log[] = {5, 'h', 'i'};
+    const uint8_t idle[] = {1, 9};
+    d_msg_raw_t r;
+    d_msg_log_t l;
+    d_msg_mode_t m;
+    memset(&r, 0xAA, sizeof(r));
+    memset(&l, 0xAA, sizeof(l));
+    memset(&m, 0xAA, sizeof(m));
+    if (!d_msg_raw_decode(&r, raw, sizeof(raw))) return 1;
+    if (r.data[0] != 1 || !all_zero(&r.data[1], sizeof(r.data) - sizeof(r.data[0]))) return 2;
+    if (!d_msg_log_decode(&l, log, sizeof(log))) return 3;
+    if (strcmp(l.text, "hi") != 0 || !all_zero(&l.text[2], sizeof(l.text) - 2)) return 4;
+    if (!d_msg_mode_decode(&m, idle, sizeof(idle))) return 5;
+    if (m.variant.idle.a != 9) return 6;
+    if (!all_zero((const unsigned char *)&m.variant + 1, sizeof(m.variant) - 1)) return 7;
+    return 0;
+}
+"#,
+    )
+    .unwrap();
+    compile_c(&compiler, &out_dir, &["main.c"]);
+    let output = run_c_program(&out_dir);
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn test_packed_struct_layout_keeps_wire_format() {
+    let json: serde_json::Value = serde_json::from_str(NESTED_STRUCT_JSON).unwrap();
+    let (metadata, mut messages) =
+        h6xserial_idl::parse_messages(json.as_object().unwrap()).unwrap();
+    messages.sort_by_key(|m| m.packet_id);
+    let input_path = PathBuf::from("vehicle.json");
+    let options = h6xserial_idl::emit_c::CGenOptions {
+        struct_layout: h6xserial_idl::emit_c::StructLayout::Packed,
+        ..Default::default()
+    };
+    // No padding: the name length and characters, x, y, xx, yy, armed
+    let driver = NESTED_STRUCT_DRIVER.replace(
+        "    return 0;\n}",
+        "    if (sizeof(state) != sizeof(size_t) + 8 + 4 + 4 + 16 + 1) return 7;\n    return 0;\n}",
+    );
+
+    let temp_dir = TempDir::new().unwrap();
+    let files = h6xserial_idl::emit_c::generate_files(
+        &metadata,
+        &messages,
+        &input_path,
+        &temp_dir.path().join("vehicle.h"),
+        &options,
+    )
+    .unwrap();
+    let header = &files[0].content;
+    assert!(header.contains("#define H6XSERIAL_PACKED __attribute__((packed))\n"));
+    assert!(header.contains("#ifdef H6XSERIAL_PACK_PRAGMA\n#pragma pack(push, 1)\n#endif\n"));
+    assert!(header.contains("#ifdef H6XSERIAL_PACK_PRAGMA\n#pragma pack(pop)\n#endif\n"));
+    assert!(header.contains(
+        "/* In memory: sizeof 48 with natural alignment on a 32-bit target, 37 packed; at most 33 bytes on the wire */\n\
+         typedef struct H6XSERIAL_PACKED {\n"
+    ));
+    let Some(compiler) = find_c_compiler() else {
+        eprintln!("Skipping test: no C compiler found");
+        return;
+    };
+    fs::write(temp_dir.path().join("vehicle.h"), header).unwrap();
+    fs::write(
+        temp_dir.path().join("main.c"),
+        format!("#include \"vehicle.h\"\n{}", driver),
+    )
+    .unwrap();
+    compile_c(&compiler, temp_dir.path(), &["main.c"]);
+    assert!(run_c_program(temp_dir.path()).status.success());
+
+    // Split headers, where the typedefs live in the types header
+    let temp_dir = TempDir::new().unwrap();
+    let files = h6xserial_idl::emit_c::generate_multiple_with_options(
+        &metadata,
+        &messages,
+        &input_path,
+        "vehicle",
+        &options,
+    )
+    .unwrap();
+    for file in &files {
+        fs::write(temp_dir.path().join(&file.filename), &file.content).unwrap();
+    }
+    fs::write(
+        temp_dir.path().join("main.c"),
+        format!(
+            "#include \"vehicle_server.h\"\n#include \"vehicle_client_1.h\"\n{}",
+            driver
+        ),
+    )
+    .unwrap();
+    compile_c(&compiler, temp_dir.path(), &["main.c"]);
+    assert!(run_c_program(temp_dir.path()).status.success());
+}
+
+#[test]
+fn test_fast_path_keeps_wire_format() {
+    let json_content = r#"{
+        "packets": {
+            "telemetry": {
+                "packet_id": 7,
+                "msg_type": "struct",
+                "fields": {
+                    "timestamp": { "type": "uint32" },
+                    "speed": { "type": "int16" },
+                    "mode": { "type": "uint8" },
+                    "flags": { "type": "uint8" },
+                    "pose": {
+                        "type": "struct",
+                        "fields": {
+                            "x": { "type": "float32" },
+                            "y": { "type": "float32" }
+                        }
+                    }
+                }
+            },
+            "unaligned": {
+                "packet_id": 8,
+                "msg_type": "struct",
+                "fields": {
+                    "a": { "type": "uint8" },
+                    "b": { "type": "uint32" }
+                }
+            }
+        }
+    }"#;
+    let json: serde_json::Value = serde_json::from_str(json_content).unwrap();
+    let (metadata, mut messages) =
+        h6xserial_idl::parse_messages(json.as_object().unwrap()).unwrap();
+    messages.sort_by_key(|m| m.packet_id);
+
+    let temp_dir = TempDir::new().unwrap();
+    let options = h6xserial_idl::emit_c::CGenOptions {
+        fast_path: true,
+        ..Default::default()
+    };
+    let files = h6xserial_idl::emit_c::generate_files(
+        &metadata,
+        &messages,
+        &PathBuf::from("fast.json"),
+        &temp_dir.path().join("fast.h"),
+        &options,
+    )
+    .unwrap();
+    let header = &files[0].content;
+    assert!(header.contains("fast_msg_telemetry_wire_t"));
+    assert!(header.contains("#if H6XSERIAL_HOST_LITTLE_ENDIAN"));
+    assert!(
+        !header.contains("fast_msg_unaligned_wire_t"),
+        "unaligned structs must not get the fast path"
+    );
+
+    let Some(compiler) = find_c_compiler() else {
+        eprintln!("Skipping compile step: no C compiler found");
+        return;
+    };
+    fs::write(temp_dir.path().join("fast.h"), header).unwrap();
+    fs::write(
+        temp_dir.path().join("main.c"),
+        r#"#include "fast.h"
+int main(void) {
+    static const uint8_t expected[16] = {
+        0x04, 0x03, 0x02, 0x01, 0xFE, 0xFF, 0x05, 0x06,
+        0x00, 0x00, 0xC0, 0x3F, 0x00, 0x00, 0x20, 0xC0
+    };
+    fast_msg_telemetry_t msg = {0};
+    msg.timestamp = 0x01020304u;
+    msg.speed = -2;
+    msg.mode = 5;
+    msg.flags = 6;
+    msg.pose.x = 1.5f;
+    msg.pose.y = -2.5f;
+    uint8_t buf[16];
+    if (fast_msg_telemetry_encode(&msg, buf, sizeof(buf)) != 16) return 1;
+    if (memcmp(buf, expected, sizeof(expected)) != 0) return 2;
+    fast_msg_telemetry_t decoded = {0};
+    if (!fast_msg_telemetry_decode(&decoded, buf, 16)) return 3;
+    if (decoded.timestamp != msg.timestamp || decoded.speed != -2 || decoded.pose.y != -2.5f) return 4;
+    return 0;
+}
+"#,
+    )
+    .unwrap();
+    compile_c(&compiler, temp_dir.path(), &["main.c"]);
+    let status = std::process::Command::new(temp_dir.path().join("a.out"))
+        .status()
+        .unwrap();
+    assert!(status.success(), "fast path round trip failed: {}", status);
+}
+
+#[test]
+fn test_int24_round_trip_sign_extends() {
+    let json_content = r#"{
+        "packets": {
+            "samples": {
+                "packet_id": 1,
+                "msg_type": "int24",
+                "array": true,
+                "max_length": 4
+            },
+            "reading": {
+                "packet_id": 2,
+                "msg_type": "struct",
+                "fields": {
+                    "le": { "type": "int24" },
+                    "be": { "type": "int24", "endianness": "big" },
+                    "raw": { "type": "uint24", "endianness": "big" }
+                }
+            }
+        }
+    }"#;
+    let json: serde_json::Value = serde_json::from_str(json_content).unwrap();
+    let (metadata, mut messages) =
+        h6xserial_idl::parse_messages(json.as_object().unwrap()).unwrap();
+    messages.sort_by_key(|m| m.packet_id);
+
+    let temp_dir = TempDir::new().unwrap();
+    let options = h6xserial_idl::emit_c::CGenOptions {
+        fast_path: true,
+        ..Default::default()
+    };
+    let files = h6xserial_idl::emit_c::generate_files(
+        &metadata,
+        &messages,
+        &PathBuf::from("wide.json"),
+        &temp_dir.path().join("wide.h"),
+        &options,
+    )
+    .unwrap();
+    let header = &files[0].content;
+    assert!(header.contains("#define WIDE_MSG_SAMPLES_MAX_SIZE 12"));
+    assert!(header.contains("#define WIDE_MSG_READING_MAX_SIZE 9"));
+    assert!(
+        !header.contains("wide_msg_reading_wire_t"),
+        "24-bit fields must not get the fast path"
+    );
+
+    let Some(compiler) = find_c_compiler() else {
+        eprintln!("Skipping compile step: no C compiler found");
+        return;
+    };
+    fs::write(temp_dir.path().join("wide.h"), header).unwrap();
+    fs::write(
+        temp_dir.path().join("main.c"),
+        r#"#include "wide.h"
+int main(void) {
+    static const uint8_t samples_wire[9] = {
+        0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x80, 0xFF, 0xFF, 0x7F
+    };
+    static const uint8_t reading_wire[9] = {
+        0xFE, 0xFF, 0xFF, 0x80, 0x00, 0x00, 0xFF, 0xFF, 0xFF
+    };
+    wide_msg_samples_t samples = {0};
+    wide_msg_reading_t reading = {0};
+    uint8_t buf[12];
+    if (!wide_msg_samples_decode(&samples, samples_wire, 9)) return 1;
+    if (samples.length != 3) return 2;
+    if (samples.data[0] != -1 || samples.data[1] != -8388608 || samples.data[2] != 8388607) return 3;
+    if (wide_msg_samples_decode(&samples, samples_wire, 8)) return 4;
+    if (wide_msg_samples_encode(&samples, buf, sizeof(buf)) != 9) return 5;
+    if (memcmp(buf, samples_wire, 9) != 0) return 6;
+
+    if (!wide_msg_reading_decode(&reading, reading_wire, 9)) return 7;
+    if (reading.le != -2 || reading.be != -8388608 || reading.raw != 0xFFFFFFu) return 8;
+    if (wide_msg_reading_encode(&reading, buf, sizeof(buf)) != 9) return 9;
+    if (memcmp(buf, reading_wire, 9) != 0) return 10;
+    return 0;
+}
+"#,
+    )
+    .unwrap();
+    compile_c(&compiler, temp_dir.path(), &["main.c"]);
+    let output = run_c_program(temp_dir.path());
+    assert_eq!(output.status.code(), Some(0), "int24 round trip failed");
+}
+
+#[test]
+fn test_bytes_round_trip_needs_exact_length() {
+    let (metadata, messages) = h6xserial_idl::parse_str(
+        r#"{ "packets": {
+            "device_id": { "packet_id": 1, "msg_type": "bytes", "length": 16 },
+            "beacon": {
+                "packet_id": 2,
+                "msg_type": "struct",
+                "fields": {
+                    "mac": { "type": "bytes", "length": 6 },
+                    "rssi": { "type": "int8" },
+                    "hash": { "type": "bytes", "length": 4 }
+                }
+            }
+        } }"#,
+    )
+    .unwrap();
+
+    let temp_dir = TempDir::new().unwrap();
+    let files = h6xserial_idl::emit_c::generate_files(
+        &metadata,
+        &messages,
+        &PathBuf::from("blob.json"),
+        &temp_dir.path().join("blob.h"),
+        &h6xserial_idl::emit_c::CGenOptions::default(),
+    )
+    .unwrap();
+    let header = &files[0].content;
+    assert!(header.contains("uint8_t value[BLOB_MSG_DEVICE_ID_VALUE_LENGTH];"));
+    assert!(header.contains("#define BLOB_MSG_BEACON_MAX_SIZE 11"));
+    assert!(
+        !header.contains("mac_length"),
+        "bytes fields have no length member"
+    );
+
+    let Some(compiler) = find_c_compiler() else {
+        eprintln!("Skipping compile step: no C compiler found");
+        return;
+    };
+    fs::write(temp_dir.path().join("blob.h"), header).unwrap();
+    fs::write(
+        temp_dir.path().join("main.c"),
+        r#"#include "blob.h"
+int main(void) {
+    static const uint8_t beacon_wire[11] = {
+        0x00, 0x1A, 0x2B, 0x3C, 0x4D, 0x5E, 0xC4, 0xDE, 0xAD, 0xBE, 0xEF
+    };
+    uint8_t id_wire[16];
+    uint8_t buf[16];
+    blob_msg_device_id_t id;
+    blob_msg_beacon_t beacon;
+    size_t i;
+    for (i = 0; i < sizeof(id.value); ++i) {
+        id.value[i] = (uint8_t)(0xF0u + i);
+    }
+    if (blob_msg_device_id_encode(&id, id_wire, sizeof(id_wire)) != 16) return 1;
+    if (id_wire[0] != 0xF0 || id_wire[15] != 0xFF) return 2;
+    if (blob_msg_device_id_encode(&id, buf, 15) != 0) return 3;
+    memset(&id, 0, sizeof(id));
+    if (!blob_msg_device_id_decode(&id, id_wire, 16)) return 4;
+    if (id.value[0] != 0xF0 || id.value[15] != 0xFF) return 5;
+    if (blob_msg_device_id_decode(&id, id_wire, 15)) return 6;
+
+    if (!blob_msg_beacon_decode(&beacon, beacon_wire, 11)) return 7;
+    if (beacon.mac[5] != 0x5E || beacon.rssi != -60 || beacon.hash[0] != 0xDE) return 8;
+    if (blob_msg_beacon_decode(&beacon, beacon_wire, 10)) return 9;
+    if (blob_msg_beacon_encode(&beacon, buf, sizeof(buf)) != 11) return 10;
+    if (memcmp(buf, beacon_wire, 11) != 0) return 11;
+    return 0;
+}
+"#,
+    )
+    .unwrap();
+    compile_c(&compiler, temp_dir.path(), &["main.c"]);
+    let output = run_c_program(temp_dir.path());
+    assert_eq!(output.status.code(), Some(0), "bytes round trip failed");
+}
+
+#[test]
+fn test_fixed_length_arrays_need_every_element() {
+    let (metadata, messages) = h6xserial_idl::parse_str(
+        r#"{ "packets": {
+            "calib": { "packet_id": 1, "msg_type": "int16", "array": true, "fixed_length": 4 },
+            "frame": {
+                "packet_id": 2,
+                "msg_type": "struct",
+                "fields": {
+                    "tag": { "type": "char", "array": true, "max_length": 4 },
+                    "gains": { "type": "uint16", "array": true, "fixed_length": 2, "endianness": "big" },
+                    "crc": { "type": "uint8" }
+                }
+            }
+        } }"#,
+    )
+    .unwrap();
+
+    let temp_dir = TempDir::new().unwrap();
+    let files = h6xserial_idl::emit_c::generate_files(
+        &metadata,
+        &messages,
+        &PathBuf::from("fixed.json"),
+        &temp_dir.path().join("fixed.h"),
+        &h6xserial_idl::emit_c::CGenOptions::default(),
+    )
+    .unwrap();
+    let header = &files[0].content;
+    assert!(header.contains("int16_t data[FIXED_MSG_CALIB_DATA_LENGTH];"));
+    assert!(header.contains("uint16_t gains[FIXED_MSG_FRAME_GAINS_LENGTH];"));
+    assert!(
+        !header.contains("gains_length"),
+        "fixed arrays have no length member"
+    );
+
+    let Some(compiler) = find_c_compiler() else {
+        eprintln!("Skipping compile step: no C compiler found");
+        return;
+    };
+    fs::write(temp_dir.path().join("fixed.h"), header).unwrap();
+    fs::write(
+        temp_dir.path().join("main.c"),
+        r#"#include "fixed.h"
+int main(void) {
+    static const uint8_t calib_wire[9] = {
+        0x01, 0x00, 0xFF, 0xFF, 0x00, 0x80, 0xFF, 0x7F, 0x00
+    };
+    /* The variable tag takes what the fixed fields leave */
+    static const uint8_t frame_wire[7] = { 'h', 'i', 0x12, 0x34, 0xAB, 0xCD, 0x5A };
+    fixed_msg_calib_t calib;
+    fixed_msg_frame_t frame;
+    uint8_t buf[16];
+    if (!fixed_msg_calib_decode(&calib, calib_wire, 8)) return 1;
+    if (calib.data[0] != 1 || calib.data[1] != -1 || calib.data[2] != -32768 || calib.data[3] != 32767) return 2;
+    if (fixed_msg_calib_decode(&calib, calib_wire, 6)) return 3;
+    if (fixed_msg_calib_decode(&calib, calib_wire, 9)) return 4;
+    if (fixed_msg_calib_encode(&calib, buf, 7) != 0) return 5;
+    if (fixed_msg_calib_encode(&calib, buf, sizeof(buf)) != 8) return 6;
+    if (memcmp(buf, calib_wire, 8) != 0) return 7;
+
+    if (!fixed_msg_frame_decode(&frame, frame_wire, 7)) return 8;
+    if (frame.tag_length != 2 || frame.gains[0] != 0x1234 || frame.gains[1] != 0xABCD || frame.crc != 0x5A) return 9;
+    if (fixed_msg_frame_decode(&frame, frame_wire, 4)) return 10;
+    if (fixed_msg_frame_encode(&frame, buf, sizeof(buf)) != 7) return 11;
+    if (memcmp(buf, frame_wire, 7) != 0) return 12;
+    return 0;
+}
+"#,
+    )
+    .unwrap();
+    compile_c(&compiler, temp_dir.path(), &["main.c"]);
+    let output = run_c_program(temp_dir.path());
+    assert_eq!(
+        output.status.code(),
+        Some(0),
+        "fixed_length round trip failed"
+    );
+}
+
+#[test]
+fn test_union_round_trip_switches_on_tag() {
+    let (metadata, messages) = h6xserial_idl::parse_str(
+        r#"{ "packets": {
+            "nav": {
+                "packet_id": 4,
+                "msg_type": "union",
+                "tag": "uint8",
+                "variants": {
+                    "1": { "name": "gps", "fields": {
+                        "lat": { "type": "int32", "endianness": "big" },
+                        "sats": { "type": "uint8" } } },
+                    "2": { "name": "odometry", "fields": {
+                        "ticks": { "type": "int16", "array": true, "max_length": 3 } } }
+                }
+            }
+        } }"#,
+    )
+    .unwrap();
+
+    let temp_dir = TempDir::new().unwrap();
+    let files = h6xserial_idl::emit_c::generate_files(
+        &metadata,
+        &messages,
+        &PathBuf::from("nav.json"),
+        &temp_dir.path().join("nav.h"),
+        &h6xserial_idl::emit_c::CGenOptions::default(),
+    )
+    .unwrap();
+    let header = &files[0].content;
+    assert!(header.contains("#define NAV_MSG_NAV_GPS_TAG 1"));
+    assert!(header.contains("#define NAV_MSG_NAV_MAX_SIZE 7"));
+    assert!(header.contains("    } variant;\n} nav_msg_nav_t;"));
+
+    let Some(compiler) = find_c_compiler() else {
+        eprintln!("Skipping compile step: no C compiler found");
+        return;
+    };
+    fs::write(temp_dir.path().join("nav.h"), header).unwrap();
+    fs::write(
+        temp_dir.path().join("main.c"),
+        r#"#include "nav.h"
+int main(void) {
+    static const uint8_t gps_wire[6] = { 0x01, 0x12, 0x34, 0x56, 0x78, 0x09 };
+    static const uint8_t odom_wire[5] = { 0x02, 0xFF, 0xFF, 0x05, 0x00 };
+    static const uint8_t unknown_wire[6] = { 0x03, 0x12, 0x34, 0x56, 0x78, 0x09 };
+    nav_msg_nav_t nav;
+    uint8_t buf[NAV_MSG_NAV_MAX_SIZE];
+    if (!nav_msg_nav_decode(&nav, gps_wire, 6)) return 1;
+    if (nav.tag != NAV_MSG_NAV_GPS_TAG || nav.variant.gps.lat != 0x12345678 || nav.variant.gps.sats != 9) return 2;
+    if (nav_msg_nav_decode(&nav, gps_wire, 5)) return 3;
+    if (nav_msg_nav_encode(&nav, buf, sizeof(buf)) != 6) return 4;
+    if (memcmp(buf, gps_wire, 6) != 0) return 5;
+
+    if (!nav_msg_nav_decode(&nav, odom_wire, 5)) return 6;
+    if (nav.tag != NAV_MSG_NAV_ODOMETRY_TAG || nav.variant.odometry.ticks_length != 2) return 7;
+    if (nav.variant.odometry.ticks[0] != -1 || nav.variant.odometry.ticks[1] != 5) return 8;
+    if (nav_msg_nav_encode(&nav, buf, sizeof(buf)) != 5) return 9;
+    if (memcmp(buf, odom_wire, 5) != 0) return 10;
+
+    if (nav_msg_nav_decode(&nav, unknown_wire, 6)) return 11;
+    nav.tag = 3;
+    if (nav_msg_nav_encode(&nav, buf, sizeof(buf)) != 0) return 12;
+
+    /* Array lengths are checked, and out_len covers the elements in use */
+    nav.tag = NAV_MSG_NAV_ODOMETRY_TAG;
+    nav.variant.odometry.ticks_length = 4;
+    if (nav_msg_nav_encode(&nav, buf, sizeof(buf)) != 0) return 13;
+    nav.variant.odometry.ticks_length = 1;
+    if (nav_msg_nav_encode(&nav, buf, 3) != 3) return 14;
+    if (nav_msg_nav_encode(&nav, buf, 2) != 0) return 15;
+    return 0;
+}
+"#,
+    )
+    .unwrap();
+    compile_c(&compiler, temp_dir.path(), &["main.c"]);
+    let output = run_c_program(temp_dir.path());
+    assert_eq!(output.status.code(), Some(0), "union round trip failed");
+}
+
+#[test]
+fn test_message_version_rejects_mismatch_and_decodes_older_payloads() {
+    let (metadata, messages) = h6xserial_idl::parse_str(
+        r#"{ "packets": {
+            "pose": {
+                "packet_id": 5,
+                "msg_type": "struct",
+                "msg_version": 2,
+                "fields": {
+                    "x": { "type": "int16" },
+                    "heading": { "type": "uint16", "since_version": 2 }
+                }
+            }
+        } }"#,
+    )
+    .unwrap();
+
+    let temp_dir = TempDir::new().unwrap();
+    let files = h6xserial_idl::emit_c::generate_files(
+        &metadata,
+        &messages,
+        &PathBuf::from("pose.json"),
+        &temp_dir.path().join("pose.h"),
+        &h6xserial_idl::emit_c::CGenOptions::default(),
+    )
+    .unwrap();
+    let header = &files[0].content;
+    assert!(header.contains("#define POSE_MSG_POSE_VERSION 2"));
+    assert!(header.contains("bool pose_msg_pose_decode_versioned("));
+    assert!(header.contains("bool pose_msg_pose_decode_compat("));
+
+    let Some(compiler) = find_c_compiler() else {
+        eprintln!("Skipping compile step: no C compiler found");
+        return;
+    };
+    fs::write(temp_dir.path().join("pose.h"), header).unwrap();
+    fs::write(
+        temp_dir.path().join("main.c"),
+        r#"#include "pose.h"
+int main(void) {
+    static const uint8_t v1_wire[2] = { 0x07, 0x00 };
+    static const uint8_t v2_wire[4] = { 0x07, 0x00, 0x5A, 0x00 };
+    pose_msg_pose_t pose;
+    if (!pose_msg_pose_decode_versioned(&pose, POSE_MSG_POSE_VERSION, v2_wire, 4)) return 1;
+    if (pose.x != 7 || pose.heading != 90) return 2;
+    if (pose_msg_pose_decode_versioned(&pose, 1, v1_wire, 2)) return 3;
+    if (pose_msg_pose_decode_versioned(&pose, 3, v2_wire, 4)) return 4;
+
+    if (!pose_msg_pose_decode_compat(&pose, 1, v1_wire, 2)) return 5;
+    if (pose.x != 7 || pose.heading != 0) return 6;
+    if (pose_msg_pose_decode_compat(&pose, 1, v2_wire, 4)) return 7;
+    if (!pose_msg_pose_decode_compat(&pose, 2, v2_wire, 4)) return 8;
+    if (pose.heading != 90) return 9;
+    if (pose_msg_pose_decode_compat(&pose, 0, v1_wire, 2)) return 10;
+    if (pose_msg_pose_decode_compat(&pose, 3, v2_wire, 4)) return 11;
+    return 0;
+}
+"#,
+    )
+    .unwrap();
+    compile_c(&compiler, temp_dir.path(), &["main.c"]);
+    let output = run_c_program(temp_dir.path());
+    assert_eq!(output.status.code(), Some(0), "versioned decode failed");
+}
+
+#[test]
+fn test_float16_conversion_matches_reference_codec() {
+    use h6xserial_idl::codec::{MessageValue, decode_message, encode_message};
+
+    let (metadata, messages) = h6xserial_idl::parse_str(
+        r#"{ "half": { "packet_id": 1, "msg_type": "f16", "endianess": "big" } }"#,
+    )
+    .unwrap();
+    let msg = &messages[0];
+
+    // Edge cases: zeros, subnormal boundaries, ties, overflow, infinity
+    let mut inputs: Vec<u32> = vec![
+        0x0000_0000,
+        0x8000_0000,
+        0x3F80_0000,
+        0xBF80_0000,
+        0x477F_E000,
+        0x477F_F000,
+        0x477F_EFFF,
+        0x4780_0000,
+        0x7F80_0000,
+        0xFF80_0000,
+        0x3380_0000,
+        0x3300_0000,
+        0x3300_0001,
+        0x3880_0000,
+        0x387F_C000,
+        0x3F80_1000,
+        0x3F80_3000,
+        0x3F80_1001,
+        0x0000_0001,
+        0x7F7F_FFFF,
+    ];
+    let mut state = 0x9E37_79B9_7F4A_7C15u64;
+    while inputs.len() < 4096 {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        let bits = state as u32;
+        if !f32::from_bits(bits).is_nan() {
+            inputs.push(bits);
+        }
+    }
+    let expected_encoded: Vec<[u8; 2]> = inputs
+        .iter()
+        .map(|&bits| {
+            let value = MessageValue::Float(f64::from(f32::from_bits(bits)));
+            let bytes = encode_message(msg, &value).unwrap();
+            [bytes[0], bytes[1]]
+        })
+        .collect();
+    let expected_decoded: Vec<Option<u32>> = (0..=u16::MAX)
+        .map(
+            |half| match decode_message(msg, &half.to_be_bytes()).unwrap() {
+                MessageValue::Float(value) if value.is_nan() => None,
+                MessageValue::Float(value) => Some((value as f32).to_bits()),
+                other => panic!("unexpected value {:?}", other),
+            },
+        )
+        .collect();
+    // Every half precision value survives a round trip
+    for (half, decoded) in expected_decoded.iter().enumerate() {
+        if let Some(bits) = decoded {
+            let value = MessageValue::Float(f64::from(f32::from_bits(*bits)));
+            assert_eq!(
+                encode_message(msg, &value).unwrap(),
+                (half as u16).to_be_bytes()
+            );
+        }
+    }
+
+    let Some(compiler) = find_c_compiler() else {
+        eprintln!("Skipping compile step: no C compiler found");
+        return;
+    };
+    let temp_dir = TempDir::new().unwrap();
+    let files = h6xserial_idl::emit_c::generate_files(
+        &metadata,
+        &messages,
+        &PathBuf::from("half.json"),
+        &temp_dir.path().join("half.h"),
+        &Default::default(),
+    )
+    .unwrap();
+    fs::write(temp_dir.path().join("half.h"), &files[0].content).unwrap();
+    let mut program = String::from(
+        "#include <stdio.h>\n#include \"half.h\"\nstatic const uint32_t inputs[] = {\n",
+    );
+    for bits in &inputs {
+        program.push_str(&format!("    0x{:08X}u,\n", bits));
+    }
+    program.push_str(
+        r#"};
+int main(void) {
+    size_t i;
+    uint32_t half;
+    for (i = 0; i < sizeof(inputs) / sizeof(inputs[0]); i++) {
+        half_msg_half_t msg;
+        uint8_t buf[2];
+        memcpy(&msg.value, &inputs[i], sizeof(float));
+        if (half_msg_half_encode(&msg, buf, sizeof(buf)) != 2) return 1;
+        printf("E %02X%02X\n", buf[0], buf[1]);
+    }
+    for (half = 0; half <= 0xFFFFu; half++) {
+        half_msg_half_t msg;
+        uint8_t buf[2];
+        uint32_t bits;
+        buf[0] = (uint8_t)(half >> 8);
+        buf[1] = (uint8_t)half;
+        if (!half_msg_half_decode(&msg, buf, sizeof(buf))) return 2;
+        memcpy(&bits, &msg.value, sizeof(float));
+        if (msg.value != msg.value) {
+            printf("D nan\n");
+        } else {
+            printf("D %08X\n", (unsigned)bits);
+        }
+    }
+    return 0;
+}
+"#,
+    );
+    fs::write(temp_dir.path().join("main.c"), program).unwrap();
+    compile_c(&compiler, temp_dir.path(), &["main.c"]);
+    let output = run_c_program(temp_dir.path());
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let mut expected = String::new();
+    for bytes in &expected_encoded {
+        expected.push_str(&format!("E {:02X}{:02X}\n", bytes[0], bytes[1]));
+    }
+    for decoded in &expected_decoded {
+        match decoded {
+            Some(bits) => expected.push_str(&format!("D {:08X}\n", bits)),
+            None => expected.push_str("D nan\n"),
+        }
+    }
+    for (line, (actual, expected)) in stdout.lines().zip(expected.lines()).enumerate() {
+        assert_eq!(actual, expected, "line {}", line);
+    }
+    assert_eq!(stdout.lines().count(), expected.lines().count());
+}
+
+#[test]
+fn test_cli_help_and_argument_errors() {
+    let run = |args: &[&str]| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    let output = run(&["--help"]);
+    assert!(output.status.success());
+    let help = String::from_utf8(output.stdout).unwrap();
+    assert!(help.contains("Usage: h6xserial_idl [OPTIONS] [INPUT] [OUTPUT]"));
+    assert!(help.contains("--impl-style <STYLE>"));
+
+    let output = run(&["--impl_style", "source"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("unexpected argument '--impl_style'")
+            && stderr.contains("a similar argument exists: '--impl-style'"),
+        "{}",
+        stderr
+    );
+
+    // The legacy positional language still works, with a warning
+    let work_dir = TempDir::new().unwrap();
+    let input = fs::canonicalize("example/c_usage/example.json").unwrap();
+    let output = run(&[
+        "c",
+        input.to_str().unwrap(),
+        work_dir.path().to_str().unwrap(),
+    ]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--lang"));
+    assert!(work_dir.path().join("example_server.h").exists());
+}
+
+#[test]
+fn test_validate_mode_reports_without_writing() {
+    let work_dir = TempDir::new().unwrap();
+    let input = work_dir.path().join("protocol.json");
+    fs::write(
+        &input,
+        r#"{
+            "packets": {
+                "ping": { "packet_id": 1, "msg_type": "uint8", "endianes": "little" },
+                "pong": { "packet_id": 2, "msg_type": "uint8" }
+            }
+        }"#,
+    )
+    .unwrap();
+    let out_dir = work_dir.path().join("out");
+    let run = |args: &[&std::ffi::OsStr]| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+            .current_dir(work_dir.path())
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    let output = run(&[
+        "--validate".as_ref(),
+        input.as_os_str(),
+        out_dir.as_os_str(),
+    ]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("warning: /packets/ping/endianes: unknown key 'endianes'"));
+    assert!(stdout.contains("protocol.json: 2 messages, 0 errors, 1 warnings"));
+    assert!(!out_dir.exists());
+
+    fs::write(
+        &input,
+        r#"{
+            "packets": {
+                "ping": { "packet_id": 1, "msg_type": "uint8" },
+                "pong": { "packet_id": 1, "msg_type": "uint9" }
+            }
+        }"#,
+    )
+    .unwrap();
+    let output = run(&[
+        "--validate".as_ref(),
+        "--format".as_ref(),
+        "json".as_ref(),
+        input.as_os_str(),
+    ]);
+    assert!(!output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["messages"], 2);
+    assert_eq!(report["errors"], 1);
+    assert_eq!(report["warnings"], 0);
+    assert_eq!(
+        report["diagnostics"][0],
+        serde_json::json!({
+            "path": "/packets/pong/msg_type",
+            "severity": "error",
+            "message": "unsupported 'msg_type' 'uint9' for message 'pong'",
+        })
+    );
+}
+
+#[test]
+fn test_list_mode_prints_message_table() {
+    let run = |args: &[&str]| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    let output = run(&["--list", "example/c_usage/example.json"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 11);
+    assert!(lines[0].starts_with("ID  NAME"));
+    assert_eq!(
+        lines[1],
+        " 0  ping               scalar      1  -       Ping command for connectivity check"
+    );
+    assert!(stdout.contains("30  sensor_data        struct  18-38  big"));
+
+    let output = run(&[
+        "--list",
+        "--sort",
+        "name",
+        "--filter",
+        "temp",
+        "example/c_usage/example.json",
+    ]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let names: Vec<&str> = stdout
+        .lines()
+        .skip(1)
+        .map(|line| line.split_whitespace().nth(1).unwrap())
+        .collect();
+    assert_eq!(names, ["multi_temperature", "temperature"]);
+
+    let output = run(&["--sort", "name", "example/c_usage/example.json"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("  --list\n"));
+}
+
+#[test]
+fn test_watch_mode_regenerates_on_change() {
+    let work_dir = TempDir::new().unwrap();
+    let input = work_dir.path().join("proto.json");
+    let out_dir = work_dir.path().join("out");
+    let log = work_dir.path().join("watch.log");
+    let ping = r#"{ "packets": { "ping": { "packet_id": 1, "msg_type": "uint8" } } }"#;
+    fs::write(&input, ping).unwrap();
+
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+        .arg("--watch")
+        .arg(&input)
+        .arg(&out_dir)
+        .stdout(fs::File::create(&log).unwrap())
+        .stderr(fs::File::create(work_dir.path().join("watch.err")).unwrap())
+        .spawn()
+        .unwrap();
+    let wait_for = |what: &str, condition: &dyn Fn() -> bool| {
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(20);
+        while !condition() {
+            assert!(std::time::Instant::now() < deadline, "timed out: {}", what);
+            std::thread::sleep(std::time::Duration::from_millis(50));
+        }
+    };
+    let server = out_dir.join("proto_server.h");
+    let log_lines = || fs::read_to_string(&log).unwrap_or_default().lines().count();
+
+    wait_for("initial generation", &|| log_lines() >= 2);
+
+    // Errors are reported and the watcher keeps running
+    fs::write(&input, "{ \"packets\": ").unwrap();
+    wait_for("error report", &|| {
+        fs::read_to_string(work_dir.path().join("watch.err"))
+            .unwrap()
+            .contains("error:")
+    });
+    assert!(child.try_wait().unwrap().is_none());
+
+    fs::write(
+        &input,
+        r#"{ "packets": {
+            "ping": { "packet_id": 1, "msg_type": "uint8" },
+            "pong": { "packet_id": 2, "msg_type": "uint8" }
+        } }"#,
+    )
+    .unwrap();
+    wait_for("regeneration", &|| {
+        fs::read_to_string(&server).is_ok_and(|h| h.contains("pong"))
+    });
+    let server_mtime = fs::metadata(&server).unwrap().modified().unwrap();
+
+    // Reformatting the input leaves the IR, and so every output, unchanged
+    fs::write(
+        &input,
+        r#"{"packets":{"pong":{"msg_type":"uint8","packet_id":2},"ping":{"msg_type":"uint8","packet_id":1}}}"#,
+    )
+    .unwrap();
+    wait_for("unchanged report", &|| {
+        fs::read_to_string(&log)
+            .unwrap_or_default()
+            .contains("output unchanged")
+    });
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+    let log = fs::read_to_string(&log).unwrap();
+    assert!(log.starts_with("Watching "), "{}", log);
+    assert!(log.contains("UTC] regenerated"), "{}", log);
+    // Files with unchanged content are not rewritten
+    assert_eq!(
+        fs::metadata(&server).unwrap().modified().unwrap(),
+        server_mtime
+    );
+}
+
+#[test]
+fn test_config_file_drives_multi_language_generation() {
+    let work_dir = TempDir::new().unwrap();
+    let root = work_dir.path();
+    fs::create_dir(root.join("msgs")).unwrap();
+    fs::write(
+        root.join("msgs/proto.json"),
+        r#"{ "packets": { "blob": { "packet_id": 1, "msg_type": "uint8", "array": true, "max_length": 300 } } }"#,
+    )
+    .unwrap();
+    fs::write(
+        root.join("h6xserial.toml"),
+        r#"
+input = "msgs/proto.json"
+languages = ["c", "docs"]
+symbol_prefix = "acme"
+max_payload_bytes = 512
+all_helpers = true
+
+[output]
+c = "gen/c"
+docs = "gen/docs"
+"#,
+    )
+    .unwrap();
+    let run = |args: &[&str]| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+            .current_dir(root)
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    let output = run(&[]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(root.join("gen/docs/COMMANDS.md").exists());
+    let byteorder = fs::read_to_string(root.join("gen/c/h6x_serial_byteorder.h")).unwrap();
+    assert!(byteorder.contains("acme_write_u16_le"));
+    assert!(root.join("gen/c/proto_server.h").exists());
+
+    // Command line values win over the file: the 300-byte array no longer fits
+    let output = run(&["--max-payload", "256"]);
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("exceeds protocol limit of 256 bytes")
+    );
+
+    // A single language on the command line replaces the configured list
+    fs::remove_dir_all(root.join("gen")).unwrap();
+    let output = run(&["--export_docs"]);
+    assert!(output.status.success());
+    assert!(root.join("gen/docs/COMMANDS.md").exists());
+    assert!(!root.join("gen/c").exists());
+
+    let output = run(&["msgs/proto.json", "out"]);
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr)
+            .contains("an output path applies to a single language")
+    );
+
+    fs::write(
+        root.join("other.toml"),
+        "languages = [\"c\"]\nprefix = \"x\"\n",
+    )
+    .unwrap();
+    let output = run(&["--config", "other.toml"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("invalid configuration other.toml"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("unknown field `prefix`"), "{}", stderr);
+}
+
+#[test]
+fn test_source_comment_makes_output_independent_of_input_location() {
+    let work_dir = TempDir::new().unwrap();
+    let json = r#"{ "packets": { "ping": { "packet_id": 1, "msg_type": "uint8" } } }"#;
+    let generate = |dir: &str, language: &str, mode: &str| -> PathBuf {
+        let input = work_dir.path().join(dir).join("proto.json");
+        fs::create_dir_all(input.parent().unwrap()).unwrap();
+        fs::write(&input, json).unwrap();
+        let out = work_dir
+            .path()
+            .join(dir)
+            .join(format!("{}-{}", language, mode));
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+            .arg(match language {
+                "docs" => "--export_docs".to_string(),
+                code => format!("--lang={}", code),
+            })
+            .arg(format!("--source-comment={}", mode))
+            .arg(&input)
+            .arg(&out)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        out
+    };
+
+    for language in ["c", "docs"] {
+        let first = generate("a", language, "none");
+        let second = generate("b/nested", language, "none");
+        let mut names: Vec<_> = fs::read_dir(&first)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        names.sort();
+        assert!(!names.is_empty());
+        for name in names {
+            let a = fs::read_to_string(first.join(&name)).unwrap();
+            let b = fs::read_to_string(second.join(&name)).unwrap();
+            assert_eq!(a, b, "{:?} differs", name);
+            assert!(!a.contains("proto.json"), "{:?} names the input", name);
+        }
+    }
+
+    let out = generate("a", "c", "basename");
+    let server = fs::read_to_string(out.join("proto_server.h")).unwrap();
+    assert!(server.contains(" * Source: proto.json\n"), "{}", server);
+    let out = generate("a", "docs", "basename");
+    let docs = fs::read_to_string(out.join("COMMANDS.md")).unwrap();
+    assert!(
+        docs.contains("Auto-generated from: `proto.json`"),
+        "{}",
+        docs
+    );
+}
+
+#[test]
+fn test_toml_input_generates_the_same_code_as_json() {
+    let work_dir = TempDir::new().unwrap();
+    let generate = |args: &[&str], out: &str| -> PathBuf {
+        let out = work_dir.path().join(out);
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+            .args(["--source-comment", "none", "--base-name", "example"])
+            .args(args)
+            .arg(&out)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        out
+    };
+
+    let from_json = generate(&["example/c_usage/example.json"], "json");
+    let from_toml = generate(&["example/c_usage/example.toml"], "toml");
+    let mut names: Vec<_> = fs::read_dir(&from_json)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    names.sort();
+    assert_eq!(names.len(), 8);
+    for name in &names {
+        assert_eq!(
+            fs::read_to_string(from_json.join(name)).unwrap(),
+            fs::read_to_string(from_toml.join(name)).unwrap(),
+            "{:?} differs",
+            name
+        );
+    }
+
+    let docs_json = generate(
+        &["--export_docs", "example/c_usage/example.json"],
+        "docs_json",
+    );
+    let docs_toml = generate(
+        &["--export_docs", "example/c_usage/example.toml"],
+        "docs_toml",
+    );
+    assert_eq!(
+        fs::read_to_string(docs_json.join("example.md")).unwrap(),
+        fs::read_to_string(docs_toml.join("example.md")).unwrap()
+    );
+
+    // --input-format overrides the extension
+    let renamed = work_dir.path().join("example.txt");
+    fs::copy("example/c_usage/example.toml", &renamed).unwrap();
+    let forced = generate(
+        &["--input-format", "toml", renamed.to_str().unwrap()],
+        "forced",
+    );
+    assert_eq!(
+        fs::read_to_string(forced.join("example_server.h")).unwrap(),
+        fs::read_to_string(from_json.join("example_server.h")).unwrap()
+    );
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+        .args(["--validate", renamed.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stdout)
+            .contains("failed to parse intermediate representation")
+    );
+}
+
+#[test]
+fn test_jsonc_input_allows_comments_and_trailing_commas() {
+    let work_dir = TempDir::new().unwrap();
+    let annotated = r#"{
+    // Sensor board protocol
+    "packets": {
+        "ping": { "packet_id": 1, "msg_type": "uint8" },
+        "pose": {
+            "packet_id": 2,
+            "msg_type": "struct",
+            "fields": {
+                /* millimetres */
+                "x": { "type": "int16" }, // east
+                "y": { "type": "int16" },
+            },
+        },
+    },
+}
+"#;
+    let input = work_dir.path().join("proto.json");
+    fs::write(&input, annotated).unwrap();
+    let run = |args: &[&str]| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    // Strict JSON stays the default
+    let output = run(&["--validate", input.to_str().unwrap()]);
+    assert!(!output.status.success());
+
+    let output = run(&[
+        "--input-format",
+        "jsonc",
+        "--validate",
+        input.to_str().unwrap(),
+    ]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+    assert!(String::from_utf8_lossy(&output.stdout).contains("2 messages, 0 errors"));
+
+    // The .jsonc extension selects the format; errors point into the original file
+    let broken = work_dir.path().join("broken.jsonc");
+    fs::write(
+        &broken,
+        annotated.replace("\"y\": { \"type\": \"int16\" },", "\"y\": { \"type\": },"),
+    )
+    .unwrap();
+    let output = run(&["--validate", "--format", "json", broken.to_str().unwrap()]);
+    assert!(!output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let diagnostic = &report["diagnostics"][0];
+    assert_eq!(diagnostic["line"], 11);
+    assert_eq!(diagnostic["column"], 32);
+
+    let out = work_dir.path().join("out");
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+        .args(["--input-format", "jsonc"])
+        .arg(&input)
+        .arg(&out)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let types = fs::read_to_string(out.join("proto_types.h")).unwrap();
+    assert!(types.contains("int16_t y;"), "{}", types);
+}
+
+#[test]
+fn test_multiple_inputs_are_merged() {
+    let work_dir = TempDir::new().unwrap();
+    let msgs = work_dir.path().join("msgs");
+    fs::create_dir(&msgs).unwrap();
+    fs::write(
+        msgs.join("base_commands.json"),
+        r#"{ "version": "1.0", "packets": { "ping": { "packet_id": 0, "msg_type": "uint8" } } }"#,
+    )
+    .unwrap();
+    fs::write(
+        msgs.join("app_commands.json"),
+        r#"{ "version": "1.0", "packets": { "led": { "packet_id": 40, "msg_type": "bool" } } }"#,
+    )
+    .unwrap();
+    fs::write(msgs.join("notes.txt"), "not an input").unwrap();
+    let run = |args: &[&str]| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+            .current_dir(work_dir.path())
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    let output = run(&[
+        "--base-name",
+        "proto",
+        "msgs/base_commands.json",
+        "--input",
+        "msgs/app_commands.json",
+        "gen",
+    ]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let types = fs::read_to_string(work_dir.path().join("gen/proto_types.h")).unwrap();
+    assert!(types.contains(" * Source: msgs/base_commands.json, msgs/app_commands.json\n"));
+    assert!(types.contains("PROTO_MSG_PING"));
+    assert!(types.contains("PROTO_MSG_LED"));
+
+    // A directory stands for its *.json files in name order
+    let output = run(&["--list", "msgs"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().count(), 3, "{}", stdout);
+
+    // Conflicts between the files are errors
+    fs::write(
+        msgs.join("extra.json"),
+        r#"{ "version": "2.0", "packets": { "pong": { "packet_id": 40, "msg_type": "uint8" } } }"#,
+    )
+    .unwrap();
+    let output = run(&["msgs", "gen"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("error: /version: conflicting 'version' values '1.0' and '2.0'"),
+        "{}",
+        stderr
+    );
+    assert!(
+        stderr.contains(
+            "error: message 'pong' has packet_id 40 which is already used by message 'led'"
+        )
+    );
+    assert!(stderr.contains("2 conflict(s) between the merged inputs"));
+
+    let output = run(&["--validate", "msgs"]);
+    assert!(!output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.contains("msgs/extra.json: 1 messages, 0 errors"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains(
+            "msgs/app_commands.json, msgs/base_commands.json, msgs/extra.json: 3 messages, 2 errors"
+        ),
+        "{}",
+        stdout
+    );
+
+    let output = run(&["a.json", "b.json", "c.json"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("use --input to merge"));
+}
+
+#[test]
+fn test_include_composes_definition_files() {
+    let work_dir = TempDir::new().unwrap();
+    fs::create_dir_all(work_dir.path().join("shared")).unwrap();
+    fs::create_dir_all(work_dir.path().join("msgs")).unwrap();
+    fs::write(
+        work_dir.path().join("shared/base.json"),
+        r#"{ "packets": { "ping": { "packet_id": 0, "msg_type": "uint8", "endianes": "big" } } }"#,
+    )
+    .unwrap();
+    fs::write(
+        work_dir.path().join("msgs/protocol.json"),
+        r#"{ "include": ["../shared/base.json"],
+             "packets": { "led": { "packet_id": 40, "msg_type": "bool" } } }"#,
+    )
+    .unwrap();
+    let run = |args: &[&str]| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+            .current_dir(work_dir.path())
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    let output = run(&["--list", "msgs/protocol.json"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().count(), 3, "{}", stdout);
+    // Warnings in an included file name it
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains(
+            "warning: msgs/../shared/base.json: /packets/ping/endianes: unknown key 'endianes'"
+        ),
+        "{}",
+        stderr
+    );
+
+    // A directory holding the included file loads it only once
+    fs::copy(
+        work_dir.path().join("shared/base.json"),
+        work_dir.path().join("msgs/base.json"),
+    )
+    .unwrap();
+    fs::write(
+        work_dir.path().join("msgs/protocol.json"),
+        r#"{ "include": ["base.json"],
+             "packets": { "led": { "packet_id": 40, "msg_type": "bool" } } }"#,
+    )
+    .unwrap();
+    let output = run(&["--list", "msgs"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(String::from_utf8(output.stdout).unwrap().lines().count(), 3);
+
+    // Cycles are reported with the chain of files
+    fs::write(
+        work_dir.path().join("msgs/base.json"),
+        r#"{ "include": ["protocol.json"] }"#,
+    )
+    .unwrap();
+    let output = run(&["--validate", "msgs/protocol.json"]);
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains(
+            "error: msgs/base.json: /include/0: include cycle: msgs/protocol.json -> msgs/base.json -> msgs/protocol.json"
+        ),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn test_shared_types_emitted_once() {
+    let work_dir = TempDir::new().unwrap();
+    let input = work_dir.path().join("robot.json");
+    fs::write(
+        &input,
+        r#"{ "types": {
+                 "vector3": { "fields": { "x": { "type": "f32" }, "y": { "type": "f32" }, "z": { "type": "f32" } } }
+             },
+             "packets": {
+                 "target": { "packet_id": 1, "msg_type": "vector3" },
+                 "odometry": { "packet_id": 2, "msg_type": "struct",
+                               "fields": { "position": { "type": "vector3" },
+                                           "velocity": { "type": "vector3" },
+                                           "seq": { "type": "u16" } } }
+             } }"#,
+    )
+    .unwrap();
+    let out_dir = work_dir.path().join("out");
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+        .arg("--emit-tests")
+        .arg(&input)
+        .arg(&out_dir)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let types = fs::read_to_string(out_dir.join("robot_types.h")).unwrap();
+    assert_eq!(
+        types.matches("} h6xserial_type_vector3_t;").count(),
+        1,
+        "{}",
+        types
+    );
+    assert!(types.contains("typedef h6xserial_type_vector3_t robot_msg_target_t;"));
+    assert!(types.contains("    h6xserial_type_vector3_t position;\n"));
+    assert!(types.contains("    h6xserial_type_vector3_t velocity;\n"));
+
+    let docs_dir = work_dir.path().join("docs");
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+        .arg("--export_docs")
+        .arg(&input)
+        .arg(&docs_dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let docs = fs::read_to_string(docs_dir.join("COMMANDS.md")).unwrap();
+    assert!(docs.contains("## Shared Types\n\n### `vector3`\n"));
+    assert!(docs.contains("| `x` | `float` |"));
+
+    let Some(compiler) = find_c_compiler() else {
+        eprintln!("Skipping compilation: no C compiler found");
+        return;
+    };
+    compile_c(&compiler, &out_dir, &["robot_tests.c"]);
+    let run = run_c_program(&out_dir);
+    assert!(
+        run.status.success(),
+        "{}",
+        String::from_utf8_lossy(&run.stdout)
+    );
+}
+
+#[test]
+fn test_scaled_accessors_round_and_range_check() {
+    let work_dir = TempDir::new().unwrap();
+    let input = work_dir.path().join("sensors.json");
+    fs::write(
+        &input,
+        r#"{ "packets": {
+                 "temperature": { "packet_id": 1, "msg_type": "int16", "scale": 0.01, "offset": -40 },
+                 "env": { "packet_id": 2, "msg_type": "struct", "fields": {
+                     "pressure": { "type": "struct", "fields": { "hpa": { "type": "u16", "scale": 0.1 } } },
+                     "levels": { "type": "u8", "array": true, "max_length": 2, "scale": -0.5 } } }
+             } }"#,
+    )
+    .unwrap();
+    let out_dir = work_dir.path().join("out");
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+        .arg(&input)
+        .arg(&out_dir)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let types = fs::read_to_string(out_dir.join("sensors_types.h")).unwrap();
+    assert!(types.contains(
+        "static inline double sensors_msg_temperature_get_value_scaled(const sensors_msg_temperature_t *msg) {"
+    ));
+    assert!(types.contains("    msg->pressure.hpa = (uint16_t)raw;\n"));
+
+    let Some(compiler) = find_c_compiler() else {
+        eprintln!("Skipping compilation: no C compiler found");
+        return;
+    };
+    fs::write(
+        out_dir.join("main.c"),
+        r#"#include "sensors_types.h"
+#include <stdio.h>
+
+int main(void) {
+    sensors_msg_temperature_t temp;
+    sensors_msg_env_t env;
+    int failures = 0;
+    failures += !sensors_msg_temperature_set_value_scaled(&temp, 21.234);
+    failures += temp.value != 6123;
+    failures += sensors_msg_temperature_get_value_scaled(&temp) != 6123 * 0.01 - 40.0;
+    failures += sensors_msg_temperature_set_value_scaled(&temp, 300.0);
+    failures += sensors_msg_temperature_set_value_scaled(&temp, 0.0 / 0.0);
+    failures += !sensors_msg_env_set_pressure_hpa_scaled(&env, 1013.25);
+    failures += env.pressure.hpa != 10133;
+    failures += !sensors_msg_env_set_levels_scaled(&env, 1, -127.5);
+    failures += env.levels[1] != 255;
+    failures += sensors_msg_env_set_levels_scaled(&env, 1, 1.0);
+    failures += sensors_msg_env_set_levels_scaled(&env, 2, -1.0);
+    printf("%d failure(s)\n", failures);
+    return failures;
+}
+"#,
+    )
+    .unwrap();
+    compile_c(&compiler, &out_dir, &["main.c"]);
+    let run = run_c_program(&out_dir);
+    assert!(
+        run.status.success(),
+        "{}",
+        String::from_utf8_lossy(&run.stdout)
+    );
+}
+
+#[test]
+fn test_min_max_ranges_in_decode_and_encode() {
+    let work_dir = TempDir::new().unwrap();
+    let input = work_dir.path().join("limits.json");
+    fs::write(
+        &input,
+        r#"{ "packets": {
+                 "speed": { "packet_id": 1, "msg_type": "uint16", "min": 10, "max": 1000 },
+                 "pose": { "packet_id": 2, "msg_type": "struct", "fields": {
+                     "x": { "type": "i16", "min": -50, "max": 50 },
+                     "gain": { "type": "f32", "max": 2.5 } } }
+             } }"#,
+    )
+    .unwrap();
+    let Some(compiler) = find_c_compiler() else {
+        eprintln!("Skipping compilation: no C compiler found");
+        return;
+    };
+    let main_c = r#"#include "limits_server.h"
+#include "limits_client_common.h"
+#include <stdio.h>
+
+int main(void) {
+    const uint8_t too_fast[2] = {0xE9, 0x03};
+    const uint8_t fast[2] = {0xE8, 0x03};
+    const uint8_t too_far[6] = {0xC4, 0xFF, 0x00, 0x00, 0x00, 0x00};
+    uint8_t buf[8];
+    limits_msg_speed_t speed;
+    limits_msg_pose_t pose;
+    int failures = 0;
+    failures += limits_msg_speed_decode(&speed, too_fast, sizeof(too_fast));
+    failures += !limits_msg_speed_decode(&speed, fast, sizeof(fast));
+    failures += limits_msg_pose_decode(&pose, too_far, sizeof(too_far));
+    speed.value = 5;
+    pose.x = 70;
+    pose.gain = 3.0f;
+#if defined(EXPECT_CLAMP)
+    failures += limits_msg_speed_encode(&speed, buf, sizeof(buf)) != 2;
+    failures += buf[0] != 10 || buf[1] != 0;
+    failures += limits_msg_pose_encode(&pose, buf, sizeof(buf)) != 6;
+    failures += !limits_msg_pose_decode(&pose, buf, 6);
+    failures += pose.x != 50 || pose.gain != 2.5f;
+#else
+    failures += limits_msg_speed_encode(&speed, buf, sizeof(buf)) != 0;
+    failures += limits_msg_pose_encode(&pose, buf, sizeof(buf)) != 0;
+#endif
+    printf("%d failure(s)\n", failures);
+    return failures;
+}
+"#;
+    for (mode, define) in [("clamp", "#define EXPECT_CLAMP\n"), ("reject", "")] {
+        let out_dir = work_dir.path().join(mode);
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+            .arg(&input)
+            .arg(&out_dir)
+            .args(["--encode-range", mode])
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        fs::write(out_dir.join("main.c"), format!("{}{}", define, main_c)).unwrap();
+        compile_c(&compiler, &out_dir, &["main.c"]);
+        let run = run_c_program(&out_dir);
+        assert!(
+            run.status.success(),
+            "{}: {}",
+            mode,
+            String::from_utf8_lossy(&run.stdout)
+        );
+    }
+}
+
+#[test]
+fn test_field_units_and_descriptions_in_comments() {
+    let work_dir = TempDir::new().unwrap();
+    let input = work_dir.path().join("motor.json");
+    fs::write(
+        &input,
+        r#"{ "packets": {
+                 "speed": { "packet_id": 1, "msg_type": "int16", "unit": "rpm", "desc": "target */ speed" },
+                 "status": { "packet_id": 2, "msg_type": "struct", "msg_desc": "Status /* v2 */", "fields": {
+                     "current": { "type": "f32", "unit": "A", "desc": "phase current" },
+                     "samples": { "type": "u16", "array": true, "max_length": 4, "desc": "raw ADC" } } }
+             } }"#,
+    )
+    .unwrap();
+    let out_dir = work_dir.path().join("out");
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+        .arg(&input)
+        .arg(&out_dir)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let types = fs::read_to_string(out_dir.join("motor_types.h")).unwrap();
+    assert!(types.contains("    int16_t value; /* rpm: target * / speed */\n"));
+    assert!(types.contains("    float current; /* A: phase current */\n"));
+    assert!(
+        types
+            .contains("    uint16_t samples[MOTOR_MSG_STATUS_SAMPLES_MAX_LENGTH]; /* raw ADC */\n")
+    );
+    assert!(types.contains("/* Status / * v2 * / */\n"));
+
+    let Some(compiler) = find_c_compiler() else {
+        eprintln!("Skipping compilation: no C compiler found");
+        return;
+    };
+    fs::write(
+        out_dir.join("main.c"),
+        "#include \"motor_server.h\"\nint main(void) { return 0; }\n",
+    )
+    .unwrap();
+    compile_c(&compiler, &out_dir, &["main.c"]);
+}
+
+#[test]
+fn test_deprecated_messages_still_round_trip() {
+    let work_dir = TempDir::new().unwrap();
+    let input = work_dir.path().join("legacy.json");
+    fs::write(
+        &input,
+        r#"{ "packets": {
+                 "old_speed": { "packet_id": 1, "msg_type": "uint16", "msg_desc": "Speed", "deprecated": "use 'speed'" },
+                 "old_pose": { "packet_id": 2, "msg_type": "struct", "deprecated": true,
+                               "fields": { "x": { "type": "f32" }, "id": { "type": "u8" } } },
+                 "speed": { "packet_id": 3, "msg_type": "uint16" }
+             } }"#,
+    )
+    .unwrap();
+    let out_dir = work_dir.path().join("out");
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+        .arg(&input)
+        .arg(&out_dir)
+        .arg("--emit-tests")
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let server = fs::read_to_string(out_dir.join("legacy_server.h")).unwrap();
+    assert!(server.contains(
+        "H6XSERIAL_DEPRECATED(\"use 'speed'\") static inline size_t legacy_msg_old_speed_encode("
+    ));
+
+    let docs_dir = work_dir.path().join("docs");
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+        .arg("--export_docs")
+        .arg(&input)
+        .arg(&docs_dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let docs = fs::read_to_string(docs_dir.join("COMMANDS.md")).unwrap();
+    assert!(docs.contains(
+        "| [`CMD_OLD_SPEED`](#cmd_old_speed) | 1 | unspecified | unspecified | Speed (**deprecated**: use 'speed') |"
+    ));
+    assert!(
+        docs.contains("| [`CMD_OLD_POSE`](#cmd_old_pose) | 2 | unspecified | unspecified | No description (**deprecated**) |")
+    );
+    assert!(docs.contains(
+        "| [`CMD_SPEED`](#cmd_speed) | 3 | unspecified | unspecified | No description |"
+    ));
+
+    let Some(compiler) = find_c_compiler() else {
+        eprintln!("Skipping compilation: no C compiler found");
+        return;
+    };
+    compile_c(&compiler, &out_dir, &["legacy_tests.c"]);
+    let run = run_c_program(&out_dir);
+    assert!(
+        run.status.success(),
+        "{}",
+        String::from_utf8_lossy(&run.stdout)
+    );
+}
+
+#[test]
+fn test_c_decoder_agrees_with_reference_codec() {
+    use h6xserial_idl::codec::{self, MessageValue};
+
+    let json = r#"{ "frame": { "packet_id": 1, "msg_type": "struct", "fields": {
+                        "label": { "type": "char", "array": true, "max_length": 3 },
+                        "seq": { "type": "u8" },
+                        "ids": { "type": "u16", "array": true, "max_length": 4, "endianess": "big" },
+                        "tail": { "type": "u8" } } } }"#;
+    let work_dir = TempDir::new().unwrap();
+    let input = work_dir.path().join("frames.json");
+    fs::write(&input, json).unwrap();
+    let (_, messages) = h6xserial_idl::parse_str(json).unwrap();
+    let msg = &messages[0];
+
+    let value = |label: &str, ids: &[i128]| {
+        MessageValue::Struct(vec![
+            ("label".to_string(), MessageValue::text(label)),
+            ("seq".to_string(), MessageValue::Int(7)),
+            (
+                "ids".to_string(),
+                MessageValue::Array(ids.iter().map(|&id| MessageValue::Int(id)).collect()),
+            ),
+            ("tail".to_string(), MessageValue::Int(9)),
+        ])
+    };
+    let mut payloads = vec![
+        codec::encode_message(msg, &value("ABC", &[0x0102, 0x0304])).unwrap(),
+        codec::encode_message(msg, &value("A", &[])).unwrap(),
+        codec::encode_message(msg, &value("ABC", &[1, 2, 3, 4])).unwrap(),
+    ];
+    // Five spare bytes: `label` takes three, `ids` one element
+    payloads.push(vec![b'X', b'Y', b'Z', 1, 0, 5, 2]);
+
+    let Some(compiler) = find_c_compiler() else {
+        eprintln!("Skipping compilation: no C compiler found");
+        return;
+    };
+    let out_dir = work_dir.path().join("out");
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+        .arg(&input)
+        .arg(&out_dir)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let mut main_c = String::from(
+        "#include \"frames_server.h\"\n#include \"frames_client_common.h\"\n#include <stdio.h>\n\n\
+         static void check(const uint8_t *data, size_t len) {\n\
+         \x20   frames_msg_frame_t frame;\n\
+         \x20   uint8_t buf[16];\n\
+         \x20   if (!frames_msg_frame_decode(&frame, data, len)) {\n\
+         \x20       printf(\"reject\\n\");\n\
+         \x20       return;\n\
+         \x20   }\n\
+         \x20   size_t n = frames_msg_frame_encode(&frame, buf, sizeof(buf));\n\
+         \x20   printf(\"%u %u\", (unsigned)frame.label_length, (unsigned)frame.ids_length);\n\
+         \x20   for (size_t i = 0; i < n; ++i) {\n\
+         \x20       printf(\" %02X\", buf[i]);\n\
+         \x20   }\n\
+         \x20   printf(\"\\n\");\n\
+         }\n\nint main(void) {\n",
+    );
+    let mut expected = String::new();
+    for (i, payload) in payloads.iter().enumerate() {
+        let bytes: Vec<String> = payload.iter().map(|b| b.to_string()).collect();
+        main_c.push_str(&format!(
+            "    static const uint8_t p{}[] = {{{}}};\n    check(p{}, sizeof(p{}));\n",
+            i,
+            bytes.join(", "),
+            i,
+            i
+        ));
+        let decoded = codec::decode_message(msg, payload).unwrap();
+        let MessageValue::Struct(fields) = &decoded else {
+            panic!("expected a struct");
+        };
+        let length = |name: &str| match fields.iter().find(|(n, _)| n == name) {
+            Some((_, MessageValue::Array(elements))) => elements.len(),
+            other => panic!("unexpected {:?}", other),
+        };
+        expected.push_str(&format!("{} {}", length("label"), length("ids")));
+        for b in codec::encode_message(msg, &decoded).unwrap() {
+            expected.push_str(&format!(" {:02X}", b));
+        }
+        expected.push('\n');
+    }
+    main_c.push_str("    return 0;\n}\n");
+    fs::write(out_dir.join("main.c"), main_c).unwrap();
+    compile_c(&compiler, &out_dir, &["main.c"]);
+    let run = run_c_program(&out_dir);
+    assert!(run.status.success());
+    assert_eq!(String::from_utf8_lossy(&run.stdout), expected);
+}
+
+#[test]
+fn test_export_docs_as_csv_codebook() {
+    let work_dir = TempDir::new().unwrap();
+    let input = work_dir.path().join("motor.json");
+    fs::write(
+        &input,
+        r#"{ "packets": {
+                 "speed": { "packet_id": 1, "msg_type": "int16", "unit": "rpm", "desc": "target, signed" }
+             } }"#,
+    )
+    .unwrap();
+    let run = |args: &[&str]| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+            .args(args)
+            .arg(&input)
+            .arg(work_dir.path().join("docs"))
+            .output()
+            .unwrap()
+    };
+
+    let output = run(&["--export_docs", "--format", "csv"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Generated codebook at"));
+    let csv = fs::read_to_string(work_dir.path().join("docs/COMMANDS.csv")).unwrap();
+    assert!(csv.ends_with("speed,1,value,int16_t,2,0,little,rpm,,,\"target, signed\"\r\n"));
+    assert!(!work_dir.path().join("docs/COMMANDS.md").exists());
+
+    for (format, file, edge) in [
+        (
+            "mermaid",
+            "COMMANDS.mmd",
+            "    server -->|\"speed (1)\"| all_clients\n",
+        ),
+        (
+            "dot",
+            "COMMANDS.dot",
+            "    server -> all_clients [label=\"speed (1)\"];\n",
+        ),
+    ] {
+        let output = run(&["--export_docs", "--format", format]);
+        assert!(output.status.success());
+        assert!(
+            String::from_utf8_lossy(&output.stdout).contains("Generated message flow diagram at")
+        );
+        let diagram = fs::read_to_string(work_dir.path().join("docs").join(file)).unwrap();
+        assert!(diagram.contains(edge), "{}", diagram);
+    }
+
+    let output = run(&["--export_docs", "--format", "json"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains(
+        "unsupported --export_docs format 'json', expected 'markdown', 'csv', 'mermaid' or 'dot'"
+    ));
+    let output = run(&["--format", "csv"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("<--validate|--export_docs>"));
+}
+
+#[test]
+fn test_diff_reports_breaking_changes() {
+    let work_dir = TempDir::new().unwrap();
+    let old = work_dir.path().join("old.json");
+    let new = work_dir.path().join("new.jsonc");
+    fs::write(
+        &old,
+        r#"{ "packets": {
+                 "ping": { "packet_id": 1, "msg_type": "uint8" },
+                 "speed": { "packet_id": 2, "msg_type": "int16" }
+             } }"#,
+    )
+    .unwrap();
+    fs::write(
+        &new,
+        r#"{ "packets": {
+                 // Byte order fixed to match the motor controller
+                 "ping": { "packet_id": 1, "msg_type": "uint8" },
+                 "speed": { "packet_id": 2, "msg_type": "int16", "endianess": "big" },
+                 "reset": { "packet_id": 3, "msg_type": "uint8" },
+             } }"#,
+    )
+    .unwrap();
+    let run = |args: &[&str]| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+            .arg("diff")
+            .args(args)
+            .output()
+            .unwrap()
+    };
+    let (old, new) = (old.to_str().unwrap(), new.to_str().unwrap());
+
+    let output = run(&[old, new]);
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("1 breaking and 1 compatible change(s)."));
+    assert!(stdout.contains("| `speed` | 2 | `value` | endianness changed: little -> big |"));
+    assert!(
+        String::from_utf8_lossy(&output.stderr)
+            .contains("1 breaking change(s); pass --allow-breaking to accept them")
+    );
+
+    let output = run(&[old, new, "--allow-breaking", "--format", "json"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["breaking"], 1);
+    assert_eq!(report["changes"][1]["kind"], "message_added");
+    assert_eq!(report["changes"][1]["message"], "reset");
+
+    // Identical definitions
+    let output = run(&[old, old]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("No wire-level changes."));
+}
+
+#[test]
+fn test_export_ir_round_trips() {
+    let work_dir = TempDir::new().unwrap();
+    let run = |input: &PathBuf, output: &PathBuf| {
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+            .arg("--export_ir")
+            .arg(input)
+            .arg(output)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+
+    let stdout = run(
+        &PathBuf::from("example/c_usage/example.json"),
+        &work_dir.path().join("first"),
+    );
+    assert!(stdout.contains("Generated IR at"));
+    let exported = work_dir.path().join("first/COMMANDS.ir.json");
+    let first = fs::read_to_string(&exported).unwrap();
+    let ir: serde_json::Value = serde_json::from_str(&first).unwrap();
+    assert_eq!(ir["ir_version"], h6xserial_idl::emit_ir::IR_VERSION);
+
+    // The export is an input in its own right and exports to itself
+    run(&exported, &work_dir.path().join("second"));
+    let second = fs::read_to_string(work_dir.path().join("second/COMMANDS.ir.json")).unwrap();
+    assert_eq!(first, second);
+}
+
+#[test]
+fn test_export_ksy() {
+    let work_dir = TempDir::new().unwrap();
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+        .arg("--export_ksy")
+        .arg("example/c_usage/example.json")
+        .arg(work_dir.path())
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        String::from_utf8_lossy(&output.stdout).contains("Generated Kaitai Struct description at")
+    );
+
+    let ksy = fs::read_to_string(work_dir.path().join("commands.ksy")).unwrap();
+    assert!(ksy.contains("  id: commands\n"));
+    assert!(ksy.contains("  packet_id:\n    0: ping\n"));
+    assert!(ksy.contains("'packet_id::firmware_version': firmware_version"));
+    assert!(ksy.contains("  firmware_version:\n"));
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+        .args([
+            "--export_ksy",
+            "--export_ir",
+            "example/c_usage/example.json",
+        ])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr)
+            .contains("the argument '--export_ksy' cannot be used with '--export_ir'")
+    );
+}
+
+#[test]
+fn test_export_proto() {
+    let work_dir = TempDir::new().unwrap();
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+        .arg("--export_proto")
+        .arg("example/c_usage/example.json")
+        .arg(work_dir.path())
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Generated protobuf schema at"));
+
+    let proto = fs::read_to_string(work_dir.path().join("commands.proto")).unwrap();
+    assert!(proto.contains("syntax = \"proto3\";\n\npackage commands;\n"));
+    assert!(proto.contains("enum PacketId {\n  PACKET_ID_PING = 0;\n"));
+    assert!(proto.contains(
+        "message FirmwareVersion {\n  string data = 1; // char[32], up to 32 bytes\n}\n"
+    ));
+}
+
+#[test]
+fn test_export_dbc() {
+    let work_dir = TempDir::new().unwrap();
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+        .arg("--export_dbc")
+        .arg("example/c_usage/example.json")
+        .arg(work_dir.path())
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Generated CAN database at"));
+    // The 32-byte version string needs more than a classic CAN frame
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains(
+            "warning: 'firmware_version' (packet ID 4) is not in the DBC: up to 32 bytes, more than the 8-byte CAN payload"
+        ),
+        "{}",
+        stderr
+    );
+
+    let dbc = fs::read_to_string(work_dir.path().join("COMMANDS.dbc")).unwrap();
+    assert!(dbc.contains("BO_ 0 ping: 1 Vector__XXX\n SG_ value : 0|8@1+ (1,0) [0|255]"));
+    assert!(!dbc.contains("firmware_version"));
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+        .args(["--export_dbc", "--can-fd", "example/c_usage/example.json"])
+        .arg(work_dir.path())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let dbc = fs::read_to_string(work_dir.path().join("COMMANDS.dbc")).unwrap();
+    assert!(dbc.contains("BO_ 4 firmware_version: 32 Vector__XXX\n"));
+}
+
+#[test]
+fn test_python_bindings_match_the_c_layout() {
+    let Some(compiler) = find_c_compiler() else {
+        eprintln!("Skipping test: no C compiler found");
+        return;
+    };
+    let python_found = std::process::Command::new("python3")
+        .arg("--version")
+        .output()
+        .is_ok_and(|output| output.status.success());
+    if !python_found {
+        eprintln!("Skipping test: python3 not found");
+        return;
+    }
+    let work_dir = TempDir::new().unwrap();
+    let dir = work_dir.path();
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+        .args(["--impl-style", "source", "--emit-python"])
+        .arg("example/c_usage/example.json")
+        .arg(dir)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let mut sources: Vec<String> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .filter(|name| name.ends_with(".c"))
+        .collect();
+    sources.sort();
+    let output = compiler
+        .to_command()
+        .current_dir(dir)
+        .args([
+            "-std=c99",
+            "-Wall",
+            "-Wextra",
+            "-Werror",
+            "-I.",
+            "-shared",
+            "-fPIC",
+            "-o",
+            "libproto.so",
+        ])
+        .args(&sources)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // Sizes and offsets as the C compiler lays the structs out
+    fs::write(
+        dir.join("main.c"),
+        r#"#include <stdio.h>
+#include "example_types.h"
+int main(void) {
+    printf("%zu %zu %zu %zu\n", sizeof(example_msg_sensor_data_t),
+           offsetof(example_msg_sensor_data_t, room_b),
+           offsetof(example_msg_sensor_data_room_b_t, co2_level),
+           sizeof(example_msg_large_data_t));
+    return 0;
+}
+"#,
+    )
+    .unwrap();
+    compile_c(&compiler, dir, &["main.c"]);
+    let c_layout = std::process::Command::new(dir.join("a.out"))
+        .output()
+        .unwrap();
+
+    fs::write(
+        dir.join("check.py"),
+        r#"import ctypes
+import example_ctypes as p
+
+print(ctypes.sizeof(p.example_msg_sensor_data_t),
+      p.example_msg_sensor_data_t.room_b.offset,
+      p.example_msg_sensor_data_room_b_t.co2_level.offset,
+      ctypes.sizeof(p.example_msg_large_data_t))
+
+lib = p.Library("./libproto.so")
+msg = p.example_msg_sensor_data_t(temperature=21.5, humidity=40, pressure=101325)
+msg.room_b.temperatures_length = 2
+msg.room_b.temperatures[1] = -3.0
+payload = lib.sensor_data_encode(msg)
+decoded = lib.sensor_data_decode(payload)
+assert decoded.pressure == 101325, decoded.pressure
+assert decoded.room_b.temperatures_length == 2
+assert decoded.room_b.temperatures[1] == -3.0
+assert p.PACKET_ID_SENSOR_DATA == 30
+try:
+    lib.sensor_data_decode(b"\x00")
+    raise AssertionError("a short payload decoded")
+except ValueError:
+    pass
+"#,
+    )
+    .unwrap();
+    let output = std::process::Command::new("python3")
+        .current_dir(dir)
+        .arg("check.py")
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&c_layout.stdout)
+    );
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+        .args(["--emit-python", "example/c_usage/example.json"])
+        .arg(dir)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("needs --impl-style source"));
+}
+
+#[test]
+fn test_emit_cmake_lists_every_file_with_relative_paths() {
+    let work_dir = TempDir::new().unwrap();
+    let out_dir = work_dir.path().join("generated");
+    let input = work_dir.path().join("proto").join("example.json");
+    fs::create_dir_all(input.parent().unwrap()).unwrap();
+    fs::copy("example/c_usage/example.json", &input).unwrap();
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+        .args(["--impl-style", "source", "--emit-cmake"])
+        .arg(&input)
+        .arg(&out_dir)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let module = fs::read_to_string(out_dir.join("example.cmake")).unwrap();
+    assert!(module.contains("\"${EXAMPLE_IDL_DIR}/../proto/example.json\""));
+    assert!(module.contains("--base-name example --impl-style source --emit-cmake"));
+    assert!(module.contains("add_library(example_idl INTERFACE)"));
+    assert!(!module.contains(work_dir.path().to_str().unwrap()));
+    for entry in fs::read_dir(&out_dir).unwrap() {
+        let name = entry.unwrap().file_name().into_string().unwrap();
+        if name != "example.cmake" {
+            assert!(
+                module.contains(&format!("\"${{EXAMPLE_IDL_DIR}}/{}\"", name)),
+                "{} is not listed",
+                name
+            );
+        }
+    }
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+        .arg("--emit-cmake")
+        .arg("-")
+        .arg(&out_dir)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("cannot read stdin"));
+}
+
+/// Just enough of the Arduino core for the generated sketches, printing
+/// to stdout.
+const ARDUINO_STUB: &str = r#"#include <stdint.h>
+#include <stddef.h>
+#include <stdio.h>
+struct SerialStub {
+    void begin(long) {}
+    explicit operator bool() const { return true; }
+    void print(const char *s) { printf("%s", s); }
+    void print(char c) { printf("%c", c); }
+    void print(long v) { printf("%ld", v); }
+    void print(unsigned long v) { printf("%lu", v); }
+    void print(double v, int digits = 2) { printf("%.*f", digits, v); }
+    template <typename T> void println(T v) { print(v); println(); }
+    void println(double v, int digits) { print(v, digits); println(); }
+    void println() { printf("\n"); }
+    void write(const char *s, size_t n) { fwrite(s, 1, n, stdout); }
+};
+inline SerialStub Serial;
+void setup();
+void loop();
+"#;
+
+#[test]
+fn test_arduino_package_sketch_decodes_its_sample() {
+    let Some(cxx) = find_cxx_compiler() else {
+        eprintln!("Skipping test: no C++ compiler found");
+        return;
+    };
+    let work_dir = TempDir::new().unwrap();
+    let dir = work_dir.path();
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+        .args(["--package", "arduino"])
+        .arg("example/c_usage/example.json")
+        .arg(dir)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let properties = fs::read_to_string(dir.join("library.properties")).unwrap();
+    assert!(properties.contains("name=example\n"));
+    assert!(properties.contains("version=1.0.0\n"));
+    assert!(properties.contains("includes=example_server.h,"));
+    assert!(dir.join("src/example_types.h").exists());
+    assert!(!dir.join("example_types.h").exists());
+
+    // The IDE compiles sketches as C++ with Arduino.h included first
+    fs::write(dir.join("Arduino.h"), ARDUINO_STUB).unwrap();
+    fs::write(
+        dir.join("main.cpp"),
+        "void setup();\nint main() { setup(); }\n",
+    )
+    .unwrap();
+    let output = cxx
+        .to_command()
+        .current_dir(dir)
+        .args([
+            "-Wall",
+            "-Wextra",
+            "-Werror",
+            "-include",
+            "Arduino.h",
+            "-Isrc",
+        ])
+        .args([
+            "-x",
+            "c++",
+            "examples/Decode/Decode.ino",
+            "-x",
+            "none",
+            "main.cpp",
+        ])
+        .args(["-o", "sketch"])
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "C++ compilation failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let output = std::process::Command::new(dir.join("sketch"))
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout, "ping (packet ID 0):\n  value: 255\n");
+}
+
+#[test]
+fn test_sector_functions_round_trip() {
+    let Some(compiler) = find_c_compiler() else {
+        eprintln!("Skipping test: no C compiler found");
+        return;
+    };
+    let (metadata, messages) = h6xserial_idl::parse_str(
+        r#"{ "packets": {
+             "image": { "packet_id": 1, "msg_type": "uint16", "array": true,
+                        "max_length": 10, "sector_bytes": 8, "endianness": "big" } } }"#,
+    )
+    .unwrap();
+    for style in [
+        h6xserial_idl::emit_c::ImplStyle::Inline,
+        h6xserial_idl::emit_c::ImplStyle::Source,
+    ] {
+        let temp_dir = TempDir::new().unwrap();
+        let options = h6xserial_idl::emit_c::CGenOptions {
+            impl_style: style,
+            ..Default::default()
+        };
+        let files = h6xserial_idl::emit_c::generate_files(
+            &metadata,
+            &messages,
+            &PathBuf::from("flash.json"),
+            &temp_dir.path().join("flash.h"),
+            &options,
+        )
+        .unwrap();
+        let mut sources = vec!["main.c".to_string()];
+        for file in &files {
+            fs::write(temp_dir.path().join(&file.filename), &file.content).unwrap();
+            if file.filename.ends_with(".c") {
+                sources.push(file.filename.clone());
+            }
+        }
+        assert!(
+            files[0]
+                .content
+                .contains("#define FLASH_MSG_IMAGE_SECTOR_COUNT 3")
+        );
+        assert!(files.iter().any(|file| file.content.contains(
+            "sectors_received[sector_index / 8] |= (uint8_t)(1u << (sector_index % 8));"
+        )));
+
+        // 7 elements: a full 4-element sector and a short one, sent in reverse
+        fs::write(
+            temp_dir.path().join("main.c"),
+            r#"#include <string.h>
+#include "flash.h"
+int main(void) {
+    flash_msg_image_t msg;
+    flash_msg_image_t received;
+    uint8_t buf[FLASH_MSG_IMAGE_SECTOR_BYTES];
+    memset(&msg, 0, sizeof(msg));
+    memset(&received, 0, sizeof(received));
+    msg.length = 7;
+    for (size_t i = 0; i < msg.length; ++i) {
+        msg.data[i] = (uint16_t)(0x1100 * (i + 1));
+    }
+    if (flash_msg_image_sector_count(&msg) != 2) {
+        return 1;
+    }
+    if (flash_msg_image_encode_sector(&msg, 2, buf, sizeof(buf)) != 0) {
+        return 2;
+    }
+    size_t len = flash_msg_image_encode_sector(&msg, 1, buf, sizeof(buf));
+    if (len != 6 || buf[0] != 0x55 || buf[1] != 0x00) {
+        return 3;
+    }
+    if (!flash_msg_image_decode_sector(&received, 1, buf, len)) {
+        return 4;
+    }
+    if (flash_msg_image_sectors_complete(&received)) {
+        return 5;
+    }
+    len = flash_msg_image_encode_sector(&msg, 0, buf, sizeof(buf));
+    if (len != 8 || !flash_msg_image_decode_sector(&received, 0, buf, len)) {
+        return 6;
+    }
+    if (!flash_msg_image_sectors_complete(&received) || received.length != 7) {
+        return 7;
+    }
+    if (memcmp(received.data, msg.data, sizeof(msg.data)) != 0) {
+        return 8;
+    }
+    /* Odd lengths and sectors past the array are rejected */
+    if (flash_msg_image_decode_sector(&received, 0, buf, 3)
+        || flash_msg_image_decode_sector(&received, 3, buf, 2)) {
+        return 9;
+    }
+    return 0;
+}
+"#,
+        )
+        .unwrap();
+        let refs: Vec<&str> = sources.iter().map(|s| s.as_str()).collect();
+        compile_c(&compiler, temp_dir.path(), &refs);
+        let status = std::process::Command::new(temp_dir.path().join("a.out"))
+            .status()
+            .unwrap();
+        assert_eq!(status.code(), Some(0));
+    }
+}
+
+#[test]
+fn test_sector_decode_rejects_short_middle_sectors() {
+    let Some(compiler) = find_c_compiler() else {
+        eprintln!("Skipping test: no C compiler found");
+        return;
+    };
+    let (metadata, messages) = h6xserial_idl::parse_str(
+        r#"{ "packets": {
+             "image": { "packet_id": 1, "msg_type": "uint16", "array": true,
+                        "max_length": 10, "sector_bytes": 6 } } }"#,
+    )
+    .unwrap();
+    let temp_dir = TempDir::new().unwrap();
+    let files = h6xserial_idl::emit_c::generate_files(
+        &metadata,
+        &messages,
+        &PathBuf::from("flash.json"),
+        &temp_dir.path().join("flash.h"),
+        &Default::default(),
+    )
+    .unwrap();
+    for file in &files {
+        fs::write(temp_dir.path().join(&file.filename), &file.content).unwrap();
+    }
+    // Sectors of 3 elements; the fourth holds the tenth element only
+    fs::write(
+        temp_dir.path().join("main.c"),
+        r#"#include <string.h>
+#include "flash.h"
+int main(void) {
+    flash_msg_image_t received;
+    const uint8_t buf[FLASH_MSG_IMAGE_SECTOR_BYTES] = {1, 2, 3, 4, 5, 6};
+    size_t i;
+    /* Four 2-byte sectors: the first ends the message */
+    memset(&received, 0, sizeof(received));
+    if (!flash_msg_image_decode_sector(&received, 0, buf, 2)) {
+        return 1;
+    }
+    for (i = 1; i < FLASH_MSG_IMAGE_SECTOR_COUNT; ++i) {
+        if (flash_msg_image_decode_sector(&received, i, buf, 2)) {
+            return 2;
+        }
+    }
+    if (received.length != 1 || !flash_msg_image_sectors_complete(&received)) {
+        return 3;
+    }
+    /* A short sector before one already stored */
+    memset(&received, 0, sizeof(received));
+    if (!flash_msg_image_decode_sector(&received, 3, buf, 2)) {
+        return 4;
+    }
+    if (flash_msg_image_decode_sector(&received, 1, buf, 2)
+        || flash_msg_image_decode_sector(&received, 3, buf, 4)) {
+        return 5;
+    }
+    for (i = 0; i < 3; ++i) {
+        if (!flash_msg_image_decode_sector(&received, i, buf, sizeof(buf))) {
+            return 6;
+        }
+    }
+    if (received.length != 10 || !flash_msg_image_sectors_complete(&received)) {
+        return 7;
+    }
+    return 0;
+}
+"#,
+    )
+    .unwrap();
+    compile_c(&compiler, temp_dir.path(), &["main.c"]);
+    let status = std::process::Command::new(temp_dir.path().join("a.out"))
+        .status()
+        .unwrap();
+    assert_eq!(status.code(), Some(0));
+}
+
+#[test]
+fn test_char_array_strings_are_terminated_at_full_length() {
+    let Some(compiler) = find_c_compiler() else {
+        eprintln!("Skipping test: no C compiler found");
+        return;
+    };
+    let (metadata, messages) = h6xserial_idl::parse_str(
+        r#"{ "packets": {
+             "label": { "packet_id": 1, "msg_type": "char", "array": true, "max_length": 4 } } }"#,
+    )
+    .unwrap();
+    let temp_dir = TempDir::new().unwrap();
+    let files = h6xserial_idl::emit_c::generate_files(
+        &metadata,
+        &messages,
+        &PathBuf::from("text.json"),
+        &temp_dir.path().join("text.h"),
+        &h6xserial_idl::emit_c::CGenOptions::default(),
+    )
+    .unwrap();
+    assert!(
+        files[0]
+            .content
+            .contains("    char data[TEXT_MSG_LABEL_MAX_LENGTH + 1];")
+    );
+    fs::write(temp_dir.path().join("text.h"), &files[0].content).unwrap();
+
+    // A full-length payload used to leave data unterminated
+    fs::write(
+        temp_dir.path().join("main.c"),
+        r#"#include <string.h>
+#include "text.h"
+int main(void) {
+    text_msg_label_t msg;
+    char small[3];
+    char large[16];
+    memset(&msg, 'x', sizeof(msg));
+    if (!text_msg_label_decode(&msg, (const uint8_t *)"ABCD", 4)) {
+        return 1;
+    }
+    if (msg.length != 4 || strcmp(msg.data, "ABCD") != 0) {
+        return 2;
+    }
+    if (text_msg_label_get_str(&msg, small, sizeof(small)) != 2 || strcmp(small, "AB") != 0) {
+        return 3;
+    }
+    if (text_msg_label_get_str(&msg, large, sizeof(large)) != 4 || strcmp(large, "ABCD") != 0) {
+        return 4;
+    }
+    if (text_msg_label_get_str(&msg, large, 0) != 0 || text_msg_label_get_str(NULL, large, 4) != 0) {
+        return 5;
+    }
+    if (text_msg_label_set_str(&msg, "hello") != 4 || msg.length != 4 || strcmp(msg.data, "hell") != 0) {
+        return 6;
+    }
+    if (text_msg_label_set_str(&msg, "") != 0 || msg.length != 0 || msg.data[0] != '\0') {
+        return 7;
+    }
+    if (!text_msg_label_decode(&msg, (const uint8_t *)"", 0) || msg.data[0] != '\0') {
+        return 8;
+    }
+    return 0;
+}
+"#,
+    )
+    .unwrap();
+    compile_c(&compiler, temp_dir.path(), &["main.c"]);
+    let status = std::process::Command::new(temp_dir.path().join("a.out"))
+        .status()
+        .unwrap();
+    assert_eq!(status.code(), Some(0));
+}
+
+#[test]
+fn test_string_semantics_derive_lengths_and_terminate() {
+    let Some(compiler) = find_c_compiler() else {
+        eprintln!("Skipping test: no C compiler found");
+        return;
+    };
+    let (metadata, messages) = h6xserial_idl::parse_str(
+        r#"{ "packets": {
+             "label": { "packet_id": 1, "msg_type": "char", "array": true, "max_length": 4,
+                        "string_semantics": true },
+             "status": { "packet_id": 2, "msg_type": "struct", "fields": {
+               "code": { "type": "uint8" },
+               "raw": { "type": "char", "array": true, "fixed_length": 2 },
+               "meta": { "type": "struct", "fields": {
+                 "name": { "type": "char", "array": true, "max_length": 4, "string_semantics": true } } } } },
+             "note": { "packet_id": 3, "msg_type": "struct", "fields": {
+               "code": { "type": "uint8" },
+               "text": { "type": "char", "array": true, "max_length": 2 } } } } }"#,
+    )
+    .unwrap();
+    for style in [
+        h6xserial_idl::emit_c::ImplStyle::Inline,
+        h6xserial_idl::emit_c::ImplStyle::Source,
+    ] {
+        let temp_dir = TempDir::new().unwrap();
+        let options = h6xserial_idl::emit_c::CGenOptions {
+            impl_style: style,
+            ..Default::default()
+        };
+        let files = h6xserial_idl::emit_c::generate_files(
+            &metadata,
+            &messages,
+            &PathBuf::from("text.json"),
+            &temp_dir.path().join("text.h"),
+            &options,
+        )
+        .unwrap();
+        // Only arrays with the flag get the terminator slot
+        assert!(
+            files[0]
+                .content
+                .contains("    char name[TEXT_MSG_STATUS_META_NAME_MAX_LENGTH + 1];")
+        );
+        assert!(
+            files[0]
+                .content
+                .contains("    char text[TEXT_MSG_NOTE_TEXT_MAX_LENGTH];")
+        );
+        assert!(!files[0].content.contains("text_msg_note_encode_str"));
+        let mut sources = vec!["main.c"];
+        for file in &files {
+            fs::write(temp_dir.path().join(&file.filename), &file.content).unwrap();
+            if file.filename.ends_with(".c") {
+                sources.push(&file.filename);
+            }
+        }
+
+        fs::write(
+            temp_dir.path().join("main.c"),
+            r#"#include <string.h>
+#include "text.h"
+int main(void) {
+    text_msg_label_t label;
+    text_msg_status_t status;
+    text_msg_status_t decoded;
+    uint8_t buf[TEXT_MSG_STATUS_MAX_SIZE];
+    size_t len;
+    memset(&label, 0, sizeof(label));
+    strcpy(label.data, "ab");
+    label.length = 99;
+    if (text_msg_label_encode_str(&label, buf, sizeof(buf)) != 2 || label.length != 2) {
+        return 1;
+    }
+    /* Text filling the whole array needs no terminator to be measured */
+    memcpy(label.data, "wxyz", 4);
+    label.data[4] = 'q';
+    if (text_msg_label_encode_str(&label, buf, sizeof(buf)) != 4 || label.length != 4) {
+        return 2;
+    }
+    if (text_msg_label_encode_str(NULL, buf, sizeof(buf)) != 0) {
+        return 3;
+    }
+
+    memset(&status, 0, sizeof(status));
+    status.code = 7;
+    status.raw[0] = 'r';
+    status.raw[1] = 's';
+    strcpy(status.meta.name, "abc");
+    len = text_msg_status_encode_str(&status, buf, sizeof(buf));
+    if (len != 1 + 2 + 3 || status.meta.name_length != 3) {
+        return 4;
+    }
+    /* The last string fills its array and is still terminated */
+    strcpy(status.meta.name, "abcd");
+    len = text_msg_status_encode_str(&status, buf, sizeof(buf));
+    if (len != 1 + 2 + 4 || status.meta.name_length != 4) {
+        return 5;
+    }
+    memset(&decoded, 'x', sizeof(decoded));
+    if (!text_msg_status_decode(&decoded, buf, len)) {
+        return 6;
+    }
+    if (strcmp(decoded.meta.name, "abcd") != 0 || decoded.code != 7) {
+        return 7;
+    }
+    if (memcmp(decoded.raw, "rs", 2) != 0) {
+        return 8;
+    }
+    return 0;
+}
+"#,
+        )
+        .unwrap();
+        compile_c(&compiler, temp_dir.path(), &sources);
+        assert_eq!(run_c_program(temp_dir.path()).status.code(), Some(0));
+    }
+
+    let err = h6xserial_idl::parse_str(
+        r#"{ "packets": { "blob": { "packet_id": 1, "msg_type": "uint8", "array": true,
+                                   "max_length": 4, "string_semantics": true } } }"#,
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("/packets/blob/string_semantics"));
+    let err = h6xserial_idl::parse_str(
+        r#"{ "packets": { "id": { "packet_id": 1, "msg_type": "struct", "fields": {
+             "code": { "type": "char", "array": true, "fixed_length": 4, "string_semantics": true } } } } }"#,
+    )
+    .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("/packets/id/fields/code/string_semantics")
+    );
+}
+
+#[test]
+fn test_send_stubs_pass_the_payload_to_the_callback() {
+    let Some(compiler) = find_c_compiler() else {
+        eprintln!("Skipping test: no C compiler found");
+        return;
+    };
+    let (metadata, messages) = h6xserial_idl::parse_str(
+        r#"{ "packets": {
+             "ping": { "packet_id": 1, "msg_type": "u16", "deprecated": true },
+             "log": { "packet_id": 2, "msg_type": "char", "array": true, "max_length": 40 } } }"#,
+    )
+    .unwrap();
+    for style in [
+        h6xserial_idl::emit_c::ImplStyle::Inline,
+        h6xserial_idl::emit_c::ImplStyle::Source,
+    ] {
+        let temp_dir = TempDir::new().unwrap();
+        let options = h6xserial_idl::emit_c::CGenOptions {
+            impl_style: style,
+            send_stack_limit: Some(16),
+            ..Default::default()
+        };
+        let files = h6xserial_idl::emit_c::generate_multiple_with_options(
+            &metadata,
+            &messages,
+            &PathBuf::from("dev.json"),
+            "dev",
+            &options,
+        )
+        .unwrap();
+        let mut sources = vec!["main.c".to_string()];
+        for file in &files {
+            fs::write(temp_dir.path().join(&file.filename), &file.content).unwrap();
+            if file.filename.ends_with(".c") {
+                sources.push(file.filename.clone());
+            }
+        }
+
+        fs::write(
+            temp_dir.path().join("main.c"),
+            r#"#define H6XSERIAL_NO_DEPRECATED
+#include <string.h>
+#include "dev_server.h"
+static uint8_t sent[64];
+static size_t sent_len;
+static bool tx(const uint8_t *data, size_t len, void *ctx) {
+    memcpy(sent, data, len);
+    sent_len = len;
+    return ctx == (void *)sent;
+}
+int main(void) {
+    dev_msg_ping_t ping = {0};
+    dev_msg_log_t log = {0};
+    uint8_t buf[DEV_MSG_LOG_MAX_SIZE];
+    ping.value = 0x1234;
+    if (!h6xserial_send_ping(&ping, tx, sent) || sent_len != 2 || sent[0] != 0x34) {
+        return 1;
+    }
+    if (h6xserial_send_ping(&ping, tx, NULL)) {
+        return 2;
+    }
+    /* Empty arrays are sent; oversized ones are not */
+    if (!h6xserial_send_log(&log, buf, sizeof(buf), tx, sent) || sent_len != 0) {
+        return 3;
+    }
+    log.length = 41;
+    if (h6xserial_send_log(&log, buf, sizeof(buf), tx, sent)) {
+        return 4;
+    }
+    return 0;
+}
+"#,
+        )
+        .unwrap();
+        let refs: Vec<&str> = sources.iter().map(|s| s.as_str()).collect();
+        compile_c(&compiler, temp_dir.path(), &refs);
+        assert_eq!(run_c_program(temp_dir.path()).status.code(), Some(0));
+
+        // The library itself builds without the opt-out
+        fs::write(
+            temp_dir.path().join("plain.c"),
+            "#include \"dev_server.h\"\nint main(void) {\n    return 0;\n}\n",
+        )
+        .unwrap();
+        let mut refs = refs;
+        refs[0] = "plain.c";
+        compile_c(&compiler, temp_dir.path(), &refs);
+    }
+}
+
+#[test]
+fn test_server_skeleton_is_never_overwritten_and_compiles() {
+    let input = fs::canonicalize("example/c_usage/example.json").unwrap();
+    let work_dir = TempDir::new().unwrap();
+    let out_dir = work_dir.path();
+    let run = || {
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+            .args([input.as_os_str(), out_dir.as_os_str()])
+            .arg("--emit-skeleton")
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+
+    run();
+    let skeleton = out_dir.join("example_server.example.c");
+    let generated = fs::read_to_string(&skeleton).unwrap();
+    assert!(run().contains("Skeleton unchanged:"));
+    assert!(!out_dir.join("example_server.example.1.c").exists());
+
+    let edited = format!("{}/* edited */\n", generated);
+    fs::write(&skeleton, &edited).unwrap();
+    assert!(run().contains("example_server.example.c exists and is kept"));
+    assert_eq!(fs::read_to_string(&skeleton).unwrap(), edited);
+    assert_eq!(
+        fs::read_to_string(out_dir.join("example_server.example.1.c")).unwrap(),
+        generated
+    );
+    assert!(run().contains("Skeleton unchanged:"));
+
+    let Some(compiler) = find_c_compiler() else {
+        eprintln!("Skipping compilation: no C compiler found");
+        return;
+    };
+    fs::write(
+        out_dir.join("main.c"),
+        r#"#include "example_server.h"
+typedef int (*h6xserial_read_fn)(uint8_t *packet_id, uint8_t *payload, size_t capacity, void *ctx);
+typedef bool (*h6xserial_tx_fn)(const uint8_t *data, size_t len, void *ctx);
+size_t h6xserial_server_poll(h6xserial_read_fn read, h6xserial_tx_fn write, void *ctx);
+static int reads;
+static int read_packet(uint8_t *packet_id, uint8_t *payload, size_t capacity, void *ctx) {
+    (void)payload;
+    (void)capacity;
+    (void)ctx;
+    /* An unknown packet ID, then nothing */
+    *packet_id = 0xFF;
+    return reads++ == 0 ? 0 : -1;
+}
+static bool write_packet(const uint8_t *data, size_t len, void *ctx) {
+    (void)data;
+    (void)len;
+    (void)ctx;
+    return true;
+}
+int main(void) {
+    return h6xserial_server_poll(read_packet, write_packet, NULL) == 0 && reads == 2 ? 0 : 1;
+}
+"#,
+    )
+    .unwrap();
+    compile_c(&compiler, out_dir, &["main.c", "example_server.example.c"]);
+    assert_eq!(run_c_program(out_dir).status.code(), Some(0));
+}
+
+#[test]
+fn test_float_helpers_compile_as_c_and_cpp_with_strict_aliasing() {
+    let (metadata, messages) = h6xserial_idl::parse_str(
+        r#"{ "packets": { "ping": { "packet_id": 1, "msg_type": "uint8" } } }"#,
+    )
+    .unwrap();
+    let options = h6xserial_idl::emit_c::CGenOptions {
+        all_helpers: true,
+        ..Default::default()
+    };
+    let files = h6xserial_idl::emit_c::generate_multiple_with_options(
+        &metadata,
+        &messages,
+        &PathBuf::from("floats.json"),
+        "floats",
+        &options,
+    )
+    .unwrap();
+    let temp_dir = TempDir::new().unwrap();
+    for file in &files {
+        fs::write(temp_dir.path().join(&file.filename), &file.content).unwrap();
+    }
+    let byteorder = &files
+        .iter()
+        .find(|file| file.filename == "h6x_serial_byteorder.h")
+        .unwrap()
+        .content;
+    assert!(byteorder.contains("sizeof(float) == 4"));
+    assert!(byteorder.contains("sizeof(double) == 8"));
+    assert!(!byteorder.contains("union"));
+
+    // The wire bytes are fixed regardless of the host byte order
+    fs::write(
+        temp_dir.path().join("main.c"),
+        r#"#include "h6x_serial_byteorder.h"
+int main(void) {
+    uint8_t buf[8];
+    h6xserial_write_f32_be(1.5f, buf);
+    if (buf[0] != 0x3F || buf[1] != 0xC0 || buf[2] != 0 || buf[3] != 0) {
+        return 1;
+    }
+    if (h6xserial_read_f32_be(buf) != 1.5f) {
+        return 2;
+    }
+    h6xserial_write_f32_le(-2.0f, buf);
+    if (buf[0] != 0 || buf[3] != 0xC0 || h6xserial_read_f32_le(buf) != -2.0f) {
+        return 3;
+    }
+    h6xserial_write_f64_be(1.5, buf);
+    if (buf[0] != 0x3F || buf[1] != 0xF8 || buf[7] != 0 || h6xserial_read_f64_be(buf) != 1.5) {
+        return 4;
+    }
+    h6xserial_write_f64_le(0.1, buf);
+    if (buf[7] != 0x3F || buf[0] != 0x9A || h6xserial_read_f64_le(buf) != 0.1) {
+        return 5;
+    }
+    return 0;
+}
+"#,
+    )
+    .unwrap();
+
+    let strict = ["-O2", "-fstrict-aliasing", "-Wall", "-Wextra", "-Werror"];
+    let mut compilers = Vec::new();
+    if let Some(cc) = find_c_compiler() {
+        compilers.push((cc, vec!["-std=c99"]));
+    }
+    if let Some(cxx) = find_cxx_compiler() {
+        compilers.push((cxx, vec!["-std=c++17", "-x", "c++"]));
+    }
+    if compilers.is_empty() {
+        eprintln!("Skipping test: no C or C++ compiler found");
+        return;
+    }
+    for (compiler, language) in compilers {
+        let output = compiler
+            .to_command()
+            .current_dir(temp_dir.path())
+            .args(&language)
+            .args(strict)
+            .args(["-I.", "main.c", "-o", "a.out"])
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{} {:?} failed:\n{}",
+            compiler.path().display(),
+            language,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert_eq!(
+            run_c_program(temp_dir.path()).status.code(),
+            Some(0),
+            "{} {:?}",
+            compiler.path().display(),
+            language
+        );
+    }
+}
+
+/// A synthetic protocol of `count` struct, array and scalar messages
+/// spread over the server, the common client header and a dozen clients.
+/// The generator does not check packet IDs, so they repeat past 255.
+fn large_message_set(count: usize) -> Vec<h6xserial_idl::MessageDefinition> {
+    use h6xserial_idl::{MessageDefinition, PrimitiveType, RequestType, StructSpec};
+
+    (0..count)
+        .map(|i| {
+            let name = format!("msg_{:04}", i);
+            let packet_id = (i % 256) as u32;
+            let builder = match i % 3 {
+                0 => {
+                    let position = StructSpec::builder()
+                        .field("x", PrimitiveType::Float32)
+                        .field("y", PrimitiveType::Float32)
+                        .build()
+                        .unwrap();
+                    let fields = StructSpec::builder()
+                        .field("seq", PrimitiveType::Uint16)
+                        .nested("position", position)
+                        .fixed_array("gains", PrimitiveType::Int16, 4)
+                        .string("label", 8)
+                        .build()
+                        .unwrap();
+                    MessageDefinition::structure(name, packet_id, fields)
+                }
+                1 => MessageDefinition::array(name, packet_id, PrimitiveType::Uint16, 32),
+                _ => MessageDefinition::scalar(name, packet_id, PrimitiveType::Int32),
+            };
+            let request_type = if i % 2 == 0 {
+                RequestType::Pub
+            } else {
+                RequestType::Sub
+            };
+            builder
+                .request_type(request_type)
+                .target_client_id((i % 14) as i32 - 1)
+                .build()
+                .unwrap()
+        })
+        .collect()
+}
+
+fn generate_large_message_set(
+    messages: &[h6xserial_idl::MessageDefinition],
+    threads: usize,
+) -> Vec<h6xserial_idl::emit_c::OutputFile> {
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .unwrap()
+        .install(|| {
+            h6xserial_idl::emit_c::generate_multiple(
+                &h6xserial_idl::Metadata::default(),
+                messages,
+                &PathBuf::from("large.json"),
+                "large",
+            )
+            .unwrap()
+        })
+}
+
+#[test]
+fn test_large_message_sets_generate_the_same_files_in_parallel() {
+    let messages = large_message_set(1000);
+    let serial = generate_large_message_set(&messages, 1);
+    let parallel = generate_large_message_set(&messages, 4);
+    // Byte order helpers, types, server, client common, 12 clients and the
+    // umbrella header
+    assert_eq!(serial.len(), 17);
+    assert_eq!(serial.len(), parallel.len());
+    for (a, b) in serial.iter().zip(&parallel) {
+        assert_eq!(a.filename, b.filename);
+        assert!(a.content == b.content, "{} differs", a.filename);
+    }
+    let server = &serial[2];
+    assert_eq!(server.filename, "large_server.h");
+    assert!(server.content.contains("large_msg_msg_0999_decode("));
+    let client = serial
+        .iter()
+        .find(|file| file.filename == "large_client_12.h")
+        .unwrap();
+    assert!(client.content.contains("large_msg_msg_0013_encode("));
+    assert!(!client.content.contains("large_msg_msg_0012_"));
+}
+
+/// Timing of the test above: `cargo test --release --test integration_test
+/// bench_ -- --ignored --nocapture`.
+#[test]
+#[ignore = "timing run, see the doc comment"]
+fn bench_generate_multiple_large_message_set() {
+    const RUNS: u32 = 5;
+    let messages = large_message_set(1000);
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    for pool in [1, threads] {
+        let start = std::time::Instant::now();
+        for _ in 0..RUNS {
+            generate_large_message_set(&messages, pool);
+        }
+        eprintln!(
+            "generate_multiple, 1000 messages, {} thread(s): {:?} per run",
+            pool,
+            start.elapsed() / RUNS
+        );
+    }
+}
+
+#[test]
+fn test_manifest_lists_inputs_and_outputs() {
+    let input = fs::canonicalize("example/c_usage/example.json").unwrap();
+    let work_dir = TempDir::new().unwrap();
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+        .current_dir(work_dir.path())
+        .args(["--manifest", "out/manifest.json"])
+        .arg(&input)
+        .arg("out")
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let raw = fs::read_to_string(work_dir.path().join("out/manifest.json")).unwrap();
+    let manifest: serde_json::Value = serde_json::from_str(&raw).unwrap();
+    assert_eq!(manifest["generator"]["version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(manifest["ir_fingerprint"].as_str().unwrap().len(), 16);
+    let inputs = manifest["inputs"].as_array().unwrap();
+    assert_eq!(inputs.len(), 1);
+    assert_eq!(inputs[0]["sha256"].as_str().unwrap().len(), 64);
+
+    let outputs = manifest["outputs"].as_array().unwrap();
+    let mut listed: Vec<&str> = outputs
+        .iter()
+        .map(|entry| entry["path"].as_str().unwrap())
+        .collect();
+    listed.sort();
+    let mut on_disk: Vec<String> = fs::read_dir(work_dir.path().join("out"))
+        .unwrap()
+        .map(|entry| format!("out/{}", entry.unwrap().file_name().to_string_lossy()))
+        .filter(|path| path != "out/manifest.json")
+        .collect();
+    on_disk.sort();
+    assert_eq!(listed, on_disk, "no temporary file is left behind");
+    for entry in outputs {
+        let path = work_dir.path().join(entry["path"].as_str().unwrap());
+        assert_eq!(entry["target"], "c");
+        assert_eq!(entry["bytes"], fs::metadata(&path).unwrap().len());
+    }
+    let client_3 = outputs
+        .iter()
+        .find(|entry| entry["path"] == "out/example_client_3.h")
+        .unwrap();
+    assert_eq!(client_3["role"], "client");
+    assert_eq!(client_3["client_id"], 3);
+    let types = outputs
+        .iter()
+        .find(|entry| entry["path"] == "out/example_types.h")
+        .unwrap();
+    assert!(types["role"].is_null());
+
+    // An unchanged rerun writes the same manifest
+    let rerun = std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+        .current_dir(work_dir.path())
+        .args(["--manifest", "out/manifest.json"])
+        .arg(&input)
+        .arg("out")
+        .output()
+        .unwrap();
+    assert!(rerun.status.success());
+    assert_eq!(
+        fs::read_to_string(work_dir.path().join("out/manifest.json")).unwrap(),
+        raw
+    );
+}
+
+#[test]
+fn test_atomic_writes_leave_no_temporary_files() {
+    let input = fs::canonicalize("example/c_usage/example.json").unwrap();
+    let work_dir = TempDir::new().unwrap();
+    let run = |extra: &[&str]| {
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+            .current_dir(work_dir.path())
+            .args(extra)
+            .arg(&input)
+            .arg("out")
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    };
+
+    // A fresh run, a forced rewrite over existing files and the documents
+    run(&["--impl-style", "source"]);
+    run(&["--impl-style", "source", "--force"]);
+    run(&["--export_docs", "--all-clients"]);
+    let names: Vec<String> = fs::read_dir(work_dir.path().join("out"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    assert!(names.contains(&"example_client_3.c".to_string()));
+    assert!(names.contains(&"COMMANDS_client_3.md".to_string()));
+    for name in &names {
+        assert!(
+            !name.starts_with('.') && !name.ends_with(".tmp"),
+            "{} left behind",
+            name
+        );
+    }
+}
+
+#[test]
+fn test_readonly_output() {
+    let input = fs::canonicalize("example/c_usage/example.json").unwrap();
+    let work_dir = TempDir::new().unwrap();
+    let run = |extra: &[&str]| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+            .current_dir(work_dir.path())
+            .args(extra)
+            .arg(&input)
+            .arg("out")
+            .output()
+            .unwrap()
+    };
+    let header = work_dir.path().join("out/example_client_3.h");
+
+    assert!(run(&["--readonly-output"]).status.success());
+    let content = fs::read_to_string(&header).unwrap();
+    assert!(
+        content.contains(" * DO NOT EDIT: changes are lost when this file is regenerated with\n")
+    );
+    assert!(content.contains(&format!(
+        " *   h6xserial_idl --readonly-output {} out\n",
+        input.display()
+    )));
+    assert!(fs::metadata(&header).unwrap().permissions().readonly());
+
+    // The read-only files of the last run are replaced, and stay read-only
+    fs::remove_file(work_dir.path().join("out/example_types.h")).unwrap();
+    let rerun = run(&["--readonly-output", "--force"]);
+    assert!(
+        rerun.status.success(),
+        "{}",
+        String::from_utf8_lossy(&rerun.stderr)
+    );
+    for entry in fs::read_dir(work_dir.path().join("out")).unwrap() {
+        let path = entry.unwrap().path();
+        assert!(
+            fs::metadata(&path).unwrap().permissions().readonly(),
+            "{} is writable",
+            path.display()
+        );
+    }
+    assert!(run(&["--readonly-output", "--check"]).status.success());
+
+    // Without the option the banner goes away, the permissions are kept
+    assert!(run(&[]).status.success());
+    assert!(!fs::read_to_string(&header).unwrap().contains("DO NOT EDIT"));
+    assert!(fs::metadata(&header).unwrap().permissions().readonly());
+}
+
+#[test]
+fn test_fmt_rewrites_input_without_changing_output() {
+    let work_dir = TempDir::new().unwrap();
+    let input = work_dir.path().join("example.json");
+    fs::copy("example/c_usage/example.json", &input).unwrap();
+    let run = |extra: &[&str]| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_h6xserial_idl"))
+            .current_dir(work_dir.path())
+            .args(extra)
+            .output()
+            .unwrap()
+    };
+    let original = fs::read_to_string(&input).unwrap();
+    assert!(run(&["example.json", "before"]).status.success());
+
+    let check = run(&["--fmt-check", "example.json"]);
+    assert!(!check.status.success());
+    assert!(String::from_utf8_lossy(&check.stdout).contains("+++ example.json (formatted)"));
+    assert_eq!(fs::read_to_string(&input).unwrap(), original);
+
+    let fmt = run(&["--fmt", "example.json"]);
+    assert!(fmt.status.success());
+    assert!(String::from_utf8_lossy(&fmt.stdout).contains("Formatted example.json"));
+    let formatted = fs::read_to_string(&input).unwrap();
+    assert!(formatted.starts_with("{\n  \"version\": \"1.0.0\",\n"));
+    assert!(run(&["--fmt-check", "example.json"]).status.success());
+    assert!(run(&["--fmt", "example.json"]).status.success());
+    assert_eq!(fs::read_to_string(&input).unwrap(), formatted);
+
+    assert!(run(&["example.json", "after"]).status.success());
+    for entry in fs::read_dir(work_dir.path().join("before")).unwrap() {
+        let name = entry.unwrap().file_name();
+        assert_eq!(
+            fs::read(work_dir.path().join("before").join(&name)).unwrap(),
+            fs::read(work_dir.path().join("after").join(&name)).unwrap(),
+            "{:?} differs",
+            name
+        );
+    }
+}
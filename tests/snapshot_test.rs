@@ -0,0 +1,154 @@
+//! Golden snapshot tests for the generators.
+//!
+//! Every `tests/fixtures/<name>.json` is run through `parse_messages` and each
+//! emitter; the outputs are compared with `tests/snapshots/<name>/`. After an
+//! intentional output change, rewrite the snapshots with:
+//!
+//! ```text
+//! UPDATE_SNAPSHOTS=1 cargo test --test snapshot_test
+//! ```
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use h6xserial_idl::diff::unified_diff;
+
+const FIXTURES_DIR: &str = "tests/fixtures";
+const SNAPSHOTS_DIR: &str = "tests/snapshots";
+
+/// Generates every output for one fixture as `(file name, content)` pairs.
+fn render_fixture(fixture: &Path) -> Vec<(String, String)> {
+    let stem = fixture.file_stem().unwrap().to_str().unwrap();
+    let raw = fs::read_to_string(fixture).unwrap();
+    let json: serde_json::Value = serde_json::from_str(&raw).unwrap();
+    let (metadata, mut messages) =
+        h6xserial_idl::parse_messages(json.as_object().unwrap()).unwrap();
+    messages.sort_by_key(|m| m.packet_id);
+
+    // Relative path keeps the "Source:" comments machine independent
+    let input_path = PathBuf::from(format!("{}.json", stem));
+    let header_name = format!("{}.h", stem);
+
+    let mut outputs = vec![(
+        header_name.clone(),
+        h6xserial_idl::emit_c::generate(&metadata, &messages, &input_path, Path::new(&header_name))
+            .unwrap(),
+    )];
+    for file in
+        h6xserial_idl::emit_c::generate_multiple(&metadata, &messages, &input_path, stem).unwrap()
+    {
+        outputs.push((format!("split/{}", file.filename), file.content));
+    }
+    outputs.push((
+        "COMMANDS.md".to_string(),
+        h6xserial_idl::emit_markdown::generate(&metadata, &messages, &input_path).unwrap(),
+    ));
+    outputs.push((
+        "COMMANDS.csv".to_string(),
+        h6xserial_idl::emit_csv::generate(&metadata, &messages).unwrap(),
+    ));
+    outputs.push((
+        "COMMANDS.ir.json".to_string(),
+        h6xserial_idl::emit_ir::generate(&metadata, &messages).unwrap(),
+    ));
+    outputs.push((
+        "commands.ksy".to_string(),
+        h6xserial_idl::emit_kaitai::generate(&metadata, &messages, "commands").unwrap(),
+    ));
+    outputs.push((
+        "commands.proto".to_string(),
+        h6xserial_idl::emit_proto::generate(&metadata, &messages, "commands").unwrap(),
+    ));
+    outputs.push((
+        "COMMANDS.dbc".to_string(),
+        h6xserial_idl::emit_dbc::generate(&metadata, &messages, &Default::default())
+            .unwrap()
+            .content,
+    ));
+    outputs
+}
+
+/// Lists snapshot files below `dir` relative to it, in sorted order.
+fn list_snapshot_files(dir: &Path, prefix: &str, out: &mut Vec<String>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries {
+        let entry = entry.unwrap();
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let relative = format!("{}{}", prefix, name);
+        if entry.file_type().unwrap().is_dir() {
+            list_snapshot_files(&entry.path(), &format!("{}/", relative), out);
+        } else {
+            out.push(relative);
+        }
+    }
+    out.sort();
+}
+
+#[test]
+fn test_generated_output_matches_snapshots() {
+    let update = std::env::var("UPDATE_SNAPSHOTS").is_ok_and(|v| v == "1");
+
+    let mut fixtures: Vec<PathBuf> = fs::read_dir(FIXTURES_DIR)
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    fixtures.sort();
+    assert!(
+        !fixtures.is_empty(),
+        "no fixtures found in {}",
+        FIXTURES_DIR
+    );
+
+    let mut failures = Vec::new();
+    for fixture in &fixtures {
+        let stem = fixture.file_stem().unwrap().to_str().unwrap();
+        let snapshot_dir = Path::new(SNAPSHOTS_DIR).join(stem);
+        let outputs = render_fixture(fixture);
+
+        if update {
+            if snapshot_dir.exists() {
+                fs::remove_dir_all(&snapshot_dir).unwrap();
+            }
+            for (name, content) in &outputs {
+                let path = snapshot_dir.join(name);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, content).unwrap();
+            }
+            continue;
+        }
+
+        for (name, content) in &outputs {
+            let path = snapshot_dir.join(name);
+            let expected = fs::read_to_string(&path).unwrap_or_default();
+            if expected != *content {
+                let label = path.display().to_string();
+                let diff = unified_diff(&expected, content, &label, "generated");
+                failures.push(if diff.is_empty() {
+                    format!("{}: line endings or trailing newline differ", label)
+                } else {
+                    diff
+                });
+            }
+        }
+
+        let mut existing = Vec::new();
+        list_snapshot_files(&snapshot_dir, "", &mut existing);
+        for name in existing {
+            if !outputs.iter().any(|(n, _)| *n == name) {
+                failures.push(format!(
+                    "{}: stale snapshot, no longer generated",
+                    snapshot_dir.join(name).display()
+                ));
+            }
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "generated output differs from snapshots (rerun with UPDATE_SNAPSHOTS=1 if intended):\n{}",
+        failures.join("\n")
+    );
+}
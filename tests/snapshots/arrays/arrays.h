@@ -0,0 +1,359 @@
+/*
+ * Auto-generated by h6xserial_idl 0.1.0.
+ * Source: arrays.json
+ * IR fingerprint: e4f7b6acebb6d576
+ * Protocol version: 0.3.0
+ */
+
+#ifndef ARRAYS_H
+#define ARRAYS_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <string.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Payload size limit of the transport, in bytes */
+#define H6XSERIAL_MAX_PAYLOAD_BYTES 251
+
+/* Width of the packet ID field, in bits */
+#define H6XSERIAL_PACKET_ID_BITS 8
+
+/* Smallest payload of any message, in bytes */
+#define H6XSERIAL_MIN_PAYLOAD_SIZE 0
+
+/* The float helpers copy the bits into an integer of the same size with
+ * memcpy, which is defined in C and C++ alike, and write the integer byte
+ * by byte, independent of the host byte order. */
+#ifndef H6XSERIAL_STATIC_ASSERT
+#if defined(H6XSERIAL_NO_STATIC_ASSERT)
+#define H6XSERIAL_STATIC_ASSERT(cond, name)
+#elif defined(__cplusplus) && __cplusplus >= 201103L
+#define H6XSERIAL_STATIC_ASSERT(cond, name) static_assert(cond, #name);
+#elif defined(__STDC_VERSION__) && __STDC_VERSION__ >= 201112L
+#define H6XSERIAL_STATIC_ASSERT(cond, name) _Static_assert(cond, #name);
+#else
+#define H6XSERIAL_STATIC_ASSERT(cond, name) typedef char h6xserial_static_assert_##name[(cond) ? 1 : -1];
+#endif
+#endif
+H6XSERIAL_STATIC_ASSERT(sizeof(float) == 4, h6xserial_helpers_float_size)
+
+static inline void h6xserial_write_u32_be(uint32_t value, uint8_t *out) {
+    out[0] = (uint8_t)((value >> 24) & 0xFFu);
+    out[1] = (uint8_t)((value >> 16) & 0xFFu);
+    out[2] = (uint8_t)((value >> 8) & 0xFFu);
+    out[3] = (uint8_t)(value & 0xFFu);
+}
+
+static inline uint32_t h6xserial_read_u32_be(const uint8_t *in) {
+    return ((uint32_t)in[0] << 24) |
+           ((uint32_t)in[1] << 16) |
+           ((uint32_t)in[2] << 8) |
+           ((uint32_t)in[3]);
+}
+
+static inline void h6xserial_write_u64_le(uint64_t value, uint8_t *out) {
+    out[0] = (uint8_t)(value & 0xFFu);
+    out[1] = (uint8_t)((value >> 8) & 0xFFu);
+    out[2] = (uint8_t)((value >> 16) & 0xFFu);
+    out[3] = (uint8_t)((value >> 24) & 0xFFu);
+    out[4] = (uint8_t)((value >> 32) & 0xFFu);
+    out[5] = (uint8_t)((value >> 40) & 0xFFu);
+    out[6] = (uint8_t)((value >> 48) & 0xFFu);
+    out[7] = (uint8_t)((value >> 56) & 0xFFu);
+}
+
+static inline uint64_t h6xserial_read_u64_le(const uint8_t *in) {
+    return ((uint64_t)in[0]) |
+           ((uint64_t)in[1] << 8) |
+           ((uint64_t)in[2] << 16) |
+           ((uint64_t)in[3] << 24) |
+           ((uint64_t)in[4] << 32) |
+           ((uint64_t)in[5] << 40) |
+           ((uint64_t)in[6] << 48) |
+           ((uint64_t)in[7] << 56);
+}
+
+static inline void h6xserial_write_f32_be(float value, uint8_t *out) {
+    uint32_t u;
+    memcpy(&u, &value, sizeof(uint32_t));
+    h6xserial_write_u32_be(u, out);
+}
+
+static inline float h6xserial_read_f32_be(const uint8_t *in) {
+    uint32_t u = h6xserial_read_u32_be(in);
+    float f;
+    memcpy(&f, &u, sizeof(float));
+    return f;
+}
+
+
+/* Device name */
+#define ARRAYS_MSG_NAME_PACKET_ID 1
+#define ARRAYS_MSG_NAME_MIN_SIZE 0
+#define ARRAYS_MSG_NAME_MAX_SIZE 16
+#define ARRAYS_MSG_NAME_MAX_LENGTH 16
+
+/* In memory: sizeof 24 with natural alignment on a 32-bit target, 21 packed; at most 16 bytes on the wire */
+typedef struct {
+    size_t length;
+    char data[ARRAYS_MSG_NAME_MAX_LENGTH + 1];
+} arrays_msg_name_t;
+
+/* Copies the text into dst as a C string, truncated to dst_len - 1 characters */
+static inline size_t arrays_msg_name_get_str(const arrays_msg_name_t *msg, char *dst, size_t dst_len) {
+    if (!msg || !dst || dst_len == 0) {
+        return 0;
+    }
+    size_t n = msg->length;
+    if (n > ARRAYS_MSG_NAME_MAX_LENGTH) {
+        n = ARRAYS_MSG_NAME_MAX_LENGTH;
+    }
+    if (n > dst_len - 1) {
+        n = dst_len - 1;
+    }
+    for (size_t i = 0; i < n; ++i) {
+        dst[i] = msg->data[i];
+    }
+    dst[n] = '\0';
+    return n;
+}
+
+/* Sets the text from the C string src, truncated to ARRAYS_MSG_NAME_MAX_LENGTH characters */
+static inline size_t arrays_msg_name_set_str(arrays_msg_name_t *msg, const char *src) {
+    if (!msg || !src) {
+        return 0;
+    }
+    size_t n = 0;
+    while ((n < ARRAYS_MSG_NAME_MAX_LENGTH) && (src[n] != '\0')) {
+        msg->data[n] = src[n];
+        ++n;
+    }
+    msg->data[n] = '\0';
+    msg->length = n;
+    return n;
+}
+
+static inline size_t arrays_msg_name_encode(const arrays_msg_name_t *msg, uint8_t *out_buf, const size_t out_len) {
+    if (!msg || !out_buf) {
+        return 0;
+    }
+    if (msg->length > ARRAYS_MSG_NAME_MAX_LENGTH) {
+        return 0;
+    }
+    size_t required = msg->length * 1;
+    if (out_len < required) {
+        return 0;
+    }
+    if (required > 0) {
+        memcpy(out_buf, msg->data, required);
+    }
+    return required;
+}
+
+static inline bool arrays_msg_name_decode(arrays_msg_name_t *msg, const uint8_t *data, const size_t data_len) {
+    if (!msg || !data) {
+        return false;
+    }
+    if (data_len % 1 != 0) {
+        return false;
+    }
+    size_t element_count = data_len / 1;
+    if (element_count > ARRAYS_MSG_NAME_MAX_LENGTH) {
+        return false;
+    }
+    msg->length = element_count;
+    if (element_count == 0) {
+        msg->data[0] = '\0';
+        return true;
+    }
+    memcpy(msg->data, data, element_count);
+    msg->data[element_count] = '\0';
+    return true;
+}
+
+
+#define ARRAYS_MSG_RAW_PACKET_ID 2
+#define ARRAYS_MSG_RAW_MIN_SIZE 0
+#define ARRAYS_MSG_RAW_MAX_SIZE 32
+#define ARRAYS_MSG_RAW_MAX_LENGTH 32
+
+typedef struct {
+    size_t length;
+    uint8_t data[ARRAYS_MSG_RAW_MAX_LENGTH];
+} arrays_msg_raw_t;
+
+static inline size_t arrays_msg_raw_encode(const arrays_msg_raw_t *msg, uint8_t *out_buf, const size_t out_len) {
+    if (!msg || !out_buf) {
+        return 0;
+    }
+    if (msg->length > ARRAYS_MSG_RAW_MAX_LENGTH) {
+        return 0;
+    }
+    size_t required = msg->length * 1;
+    if (out_len < required) {
+        return 0;
+    }
+    if (required > 0) {
+        memcpy(out_buf, msg->data, required);
+    }
+    return required;
+}
+
+static inline bool arrays_msg_raw_decode(arrays_msg_raw_t *msg, const uint8_t *data, const size_t data_len) {
+    if (!msg || !data) {
+        return false;
+    }
+    if (data_len % 1 != 0) {
+        return false;
+    }
+    size_t element_count = data_len / 1;
+    if (element_count > ARRAYS_MSG_RAW_MAX_LENGTH) {
+        return false;
+    }
+    msg->length = element_count;
+    if (element_count == 0) {
+        return true;
+    }
+    memcpy(msg->data, data, element_count);
+    return true;
+}
+
+
+/* Temperature readings */
+#define ARRAYS_MSG_TEMPERATURES_PACKET_ID 20
+#define ARRAYS_MSG_TEMPERATURES_MIN_SIZE 0
+#define ARRAYS_MSG_TEMPERATURES_MAX_SIZE 32
+#define ARRAYS_MSG_TEMPERATURES_MAX_LENGTH 8
+
+typedef struct {
+    size_t length;
+    float data[ARRAYS_MSG_TEMPERATURES_MAX_LENGTH];
+} arrays_msg_temperatures_t;
+
+static inline size_t arrays_msg_temperatures_encode(const arrays_msg_temperatures_t *msg, uint8_t *out_buf, const size_t out_len) {
+    if (!msg || !out_buf) {
+        return 0;
+    }
+    if (msg->length > ARRAYS_MSG_TEMPERATURES_MAX_LENGTH) {
+        return 0;
+    }
+    size_t required = msg->length * 4;
+    if (out_len < required) {
+        return 0;
+    }
+    size_t offset = 0;
+    for (size_t i = 0; i < msg->length; ++i) {
+        h6xserial_write_f32_be(msg->data[i], out_buf + offset);
+        offset += 4;
+    }
+    return offset;
+}
+
+static inline bool arrays_msg_temperatures_decode(arrays_msg_temperatures_t *msg, const uint8_t *data, const size_t data_len) {
+    if (!msg || !data) {
+        return false;
+    }
+    if (data_len % 4 != 0) {
+        return false;
+    }
+    size_t element_count = data_len / 4;
+    if (element_count > ARRAYS_MSG_TEMPERATURES_MAX_LENGTH) {
+        return false;
+    }
+    msg->length = element_count;
+    if (element_count == 0) {
+        return true;
+    }
+    size_t offset = 0;
+    for (size_t i = 0; i < element_count; ++i) {
+        msg->data[i] = h6xserial_read_f32_be(data + offset);
+        offset += 4;
+    }
+    return true;
+}
+
+
+#define ARRAYS_MSG_COUNTERS_PACKET_ID 30
+#define ARRAYS_MSG_COUNTERS_MIN_SIZE 0
+#define ARRAYS_MSG_COUNTERS_MAX_SIZE 32
+#define ARRAYS_MSG_COUNTERS_MAX_LENGTH 4
+
+/* In memory: sizeof 40 with natural alignment on a 32-bit target, 36 packed; at most 32 bytes on the wire */
+typedef struct {
+    size_t length;
+    int64_t data[ARRAYS_MSG_COUNTERS_MAX_LENGTH];
+} arrays_msg_counters_t;
+
+static inline size_t arrays_msg_counters_encode(const arrays_msg_counters_t *msg, uint8_t *out_buf, const size_t out_len) {
+    if (!msg || !out_buf) {
+        return 0;
+    }
+    if (msg->length > ARRAYS_MSG_COUNTERS_MAX_LENGTH) {
+        return 0;
+    }
+    size_t required = msg->length * 8;
+    if (out_len < required) {
+        return 0;
+    }
+    size_t offset = 0;
+    for (size_t i = 0; i < msg->length; ++i) {
+        h6xserial_write_u64_le((uint64_t)(msg->data[i]), out_buf + offset);
+        offset += 8;
+    }
+    return offset;
+}
+
+static inline bool arrays_msg_counters_decode(arrays_msg_counters_t *msg, const uint8_t *data, const size_t data_len) {
+    if (!msg || !data) {
+        return false;
+    }
+    if (data_len % 8 != 0) {
+        return false;
+    }
+    size_t element_count = data_len / 8;
+    if (element_count > ARRAYS_MSG_COUNTERS_MAX_LENGTH) {
+        return false;
+    }
+    msg->length = element_count;
+    if (element_count == 0) {
+        return true;
+    }
+    size_t offset = 0;
+    for (size_t i = 0; i < element_count; ++i) {
+        msg->data[i] = (int64_t)h6xserial_read_u64_le(data + offset);
+        offset += 8;
+    }
+    return true;
+}
+
+
+/* Compile-time checks (define H6XSERIAL_NO_STATIC_ASSERT to disable) */
+#ifndef H6XSERIAL_STATIC_ASSERT
+#if defined(H6XSERIAL_NO_STATIC_ASSERT)
+#define H6XSERIAL_STATIC_ASSERT(cond, name)
+#elif defined(__cplusplus) && __cplusplus >= 201103L
+#define H6XSERIAL_STATIC_ASSERT(cond, name) static_assert(cond, #name);
+#elif defined(__STDC_VERSION__) && __STDC_VERSION__ >= 201112L
+#define H6XSERIAL_STATIC_ASSERT(cond, name) _Static_assert(cond, #name);
+#else
+#define H6XSERIAL_STATIC_ASSERT(cond, name) typedef char h6xserial_static_assert_##name[(cond) ? 1 : -1];
+#endif
+#endif
+
+H6XSERIAL_STATIC_ASSERT(sizeof(int64_t) == 8, arrays_int64_size_8)
+H6XSERIAL_STATIC_ASSERT(sizeof(float) == 4, arrays_float_size_4)
+H6XSERIAL_STATIC_ASSERT(ARRAYS_MSG_NAME_MAX_SIZE == 16, arrays_msg_name_max_size)
+H6XSERIAL_STATIC_ASSERT(ARRAYS_MSG_RAW_MAX_SIZE == 32, arrays_msg_raw_max_size)
+H6XSERIAL_STATIC_ASSERT(ARRAYS_MSG_TEMPERATURES_MAX_SIZE == 32, arrays_msg_temperatures_max_size)
+H6XSERIAL_STATIC_ASSERT(ARRAYS_MSG_COUNTERS_MAX_SIZE == 32, arrays_msg_counters_max_size)
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* ARRAYS_H */
@@ -0,0 +1,26 @@
+/*
+ * Auto-generated by h6xserial_idl 0.1.0.
+ * Source: arrays.json
+ * IR fingerprint: e4f7b6acebb6d576
+ * Umbrella header: define one of these macros before including it
+ *   H6XSERIAL_ROLE_SERVER
+ *   H6XSERIAL_ROLE_CLIENT_COMMON
+ *   H6XSERIAL_ROLE_CLIENT_3
+ */
+
+#ifndef ARRAYS_H
+#define ARRAYS_H
+
+#include "arrays_types.h"
+
+#if defined(H6XSERIAL_ROLE_SERVER)
+#include "arrays_server.h"
+#elif defined(H6XSERIAL_ROLE_CLIENT_COMMON)
+#include "arrays_client_common.h"
+#elif defined(H6XSERIAL_ROLE_CLIENT_3)
+#include "arrays_client_3.h"
+#else
+#error "Define one of H6XSERIAL_ROLE_SERVER, H6XSERIAL_ROLE_CLIENT_COMMON, H6XSERIAL_ROLE_CLIENT_3 before including arrays.h"
+#endif
+
+#endif /* ARRAYS_H */
@@ -0,0 +1,74 @@
+/*
+ * Auto-generated by h6xserial_idl 0.1.0.
+ * Source: arrays.json
+ * IR fingerprint: e4f7b6acebb6d576
+ * Role: Client (ID: 3)
+ * Protocol version: 0.3.0
+ */
+
+#ifndef ARRAYS_CLIENT_3_H
+#define ARRAYS_CLIENT_3_H
+
+#include "arrays_types.h"
+#include "arrays_client_common.h"
+
+#ifndef OWN_ID
+#define OWN_ID 3
+#else
+#endif
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+
+/* Temperature readings */
+static inline bool arrays_msg_temperatures_decode(arrays_msg_temperatures_t *msg, const uint8_t *data, const size_t data_len) {
+    if (!msg || !data) {
+        return false;
+    }
+    if (data_len % 4 != 0) {
+        return false;
+    }
+    size_t element_count = data_len / 4;
+    if (element_count > ARRAYS_MSG_TEMPERATURES_MAX_LENGTH) {
+        return false;
+    }
+    msg->length = element_count;
+    if (element_count == 0) {
+        return true;
+    }
+    size_t offset = 0;
+    for (size_t i = 0; i < element_count; ++i) {
+        msg->data[i] = h6xserial_read_f32_be(data + offset);
+        offset += 4;
+    }
+    return true;
+}
+
+
+static inline size_t arrays_msg_counters_encode(const arrays_msg_counters_t *msg, uint8_t *out_buf, const size_t out_len) {
+    if (!msg || !out_buf) {
+        return 0;
+    }
+    if (msg->length > ARRAYS_MSG_COUNTERS_MAX_LENGTH) {
+        return 0;
+    }
+    size_t required = msg->length * 8;
+    if (out_len < required) {
+        return 0;
+    }
+    size_t offset = 0;
+    for (size_t i = 0; i < msg->length; ++i) {
+        h6xserial_write_u64_le((uint64_t)(msg->data[i]), out_buf + offset);
+        offset += 8;
+    }
+    return offset;
+}
+
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* ARRAYS_CLIENT_3_H */
@@ -0,0 +1,62 @@
+/*
+ * Auto-generated by h6xserial_idl 0.1.0.
+ * Source: arrays.json
+ * IR fingerprint: e4f7b6acebb6d576
+ * Role: Client (Common)
+ * Protocol version: 0.3.0
+ */
+
+#ifndef ARRAYS_CLIENT_COMMON_H
+#define ARRAYS_CLIENT_COMMON_H
+
+#include "arrays_types.h"
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+
+/* Device name */
+static inline size_t arrays_msg_name_encode(const arrays_msg_name_t *msg, uint8_t *out_buf, const size_t out_len) {
+    if (!msg || !out_buf) {
+        return 0;
+    }
+    if (msg->length > ARRAYS_MSG_NAME_MAX_LENGTH) {
+        return 0;
+    }
+    size_t required = msg->length * 1;
+    if (out_len < required) {
+        return 0;
+    }
+    if (required > 0) {
+        memcpy(out_buf, msg->data, required);
+    }
+    return required;
+}
+
+
+static inline bool arrays_msg_raw_decode(arrays_msg_raw_t *msg, const uint8_t *data, const size_t data_len) {
+    if (!msg || !data) {
+        return false;
+    }
+    if (data_len % 1 != 0) {
+        return false;
+    }
+    size_t element_count = data_len / 1;
+    if (element_count > ARRAYS_MSG_RAW_MAX_LENGTH) {
+        return false;
+    }
+    msg->length = element_count;
+    if (element_count == 0) {
+        return true;
+    }
+    memcpy(msg->data, data, element_count);
+    return true;
+}
+
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* ARRAYS_CLIENT_COMMON_H */
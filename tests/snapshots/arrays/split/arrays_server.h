@@ -0,0 +1,114 @@
+/*
+ * Auto-generated by h6xserial_idl 0.1.0.
+ * Source: arrays.json
+ * IR fingerprint: e4f7b6acebb6d576
+ * Role: Server
+ * Protocol version: 0.3.0
+ */
+
+#ifndef ARRAYS_SERVER_H
+#define ARRAYS_SERVER_H
+
+#include "arrays_types.h"
+
+#ifndef OWN_ID
+#define OWN_ID 0
+#else
+#endif
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+
+/* Device name */
+static inline bool arrays_msg_name_decode(arrays_msg_name_t *msg, const uint8_t *data, const size_t data_len) {
+    if (!msg || !data) {
+        return false;
+    }
+    if (data_len % 1 != 0) {
+        return false;
+    }
+    size_t element_count = data_len / 1;
+    if (element_count > ARRAYS_MSG_NAME_MAX_LENGTH) {
+        return false;
+    }
+    msg->length = element_count;
+    if (element_count == 0) {
+        msg->data[0] = '\0';
+        return true;
+    }
+    memcpy(msg->data, data, element_count);
+    msg->data[element_count] = '\0';
+    return true;
+}
+
+
+static inline size_t arrays_msg_raw_encode(const arrays_msg_raw_t *msg, uint8_t *out_buf, const size_t out_len) {
+    if (!msg || !out_buf) {
+        return 0;
+    }
+    if (msg->length > ARRAYS_MSG_RAW_MAX_LENGTH) {
+        return 0;
+    }
+    size_t required = msg->length * 1;
+    if (out_len < required) {
+        return 0;
+    }
+    if (required > 0) {
+        memcpy(out_buf, msg->data, required);
+    }
+    return required;
+}
+
+
+/* Temperature readings */
+static inline size_t arrays_msg_temperatures_encode(const arrays_msg_temperatures_t *msg, uint8_t *out_buf, const size_t out_len) {
+    if (!msg || !out_buf) {
+        return 0;
+    }
+    if (msg->length > ARRAYS_MSG_TEMPERATURES_MAX_LENGTH) {
+        return 0;
+    }
+    size_t required = msg->length * 4;
+    if (out_len < required) {
+        return 0;
+    }
+    size_t offset = 0;
+    for (size_t i = 0; i < msg->length; ++i) {
+        h6xserial_write_f32_be(msg->data[i], out_buf + offset);
+        offset += 4;
+    }
+    return offset;
+}
+
+
+static inline bool arrays_msg_counters_decode(arrays_msg_counters_t *msg, const uint8_t *data, const size_t data_len) {
+    if (!msg || !data) {
+        return false;
+    }
+    if (data_len % 8 != 0) {
+        return false;
+    }
+    size_t element_count = data_len / 8;
+    if (element_count > ARRAYS_MSG_COUNTERS_MAX_LENGTH) {
+        return false;
+    }
+    msg->length = element_count;
+    if (element_count == 0) {
+        return true;
+    }
+    size_t offset = 0;
+    for (size_t i = 0; i < element_count; ++i) {
+        msg->data[i] = (int64_t)h6xserial_read_u64_le(data + offset);
+        offset += 8;
+    }
+    return true;
+}
+
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* ARRAYS_SERVER_H */
@@ -0,0 +1,139 @@
+/*
+ * Auto-generated by h6xserial_idl 0.1.0.
+ * Source: arrays.json
+ * IR fingerprint: e4f7b6acebb6d576
+ * Common type definitions and helper functions
+ * Protocol version: 0.3.0
+ */
+
+#ifndef ARRAYS_TYPES_H
+#define ARRAYS_TYPES_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <string.h>
+
+#include "h6x_serial_byteorder.h"
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Payload size limit of the transport, in bytes */
+#define H6XSERIAL_MAX_PAYLOAD_BYTES 251
+
+/* Width of the packet ID field, in bits */
+#define H6XSERIAL_PACKET_ID_BITS 8
+
+/* Smallest payload of any message, in bytes */
+#define H6XSERIAL_MIN_PAYLOAD_SIZE 0
+
+
+/* Device name */
+#define ARRAYS_MSG_NAME_PACKET_ID 1
+#define ARRAYS_MSG_NAME_MIN_SIZE 0
+#define ARRAYS_MSG_NAME_MAX_SIZE 16
+#define ARRAYS_MSG_NAME_MAX_LENGTH 16
+
+/* In memory: sizeof 24 with natural alignment on a 32-bit target, 21 packed; at most 16 bytes on the wire */
+typedef struct {
+    size_t length;
+    char data[ARRAYS_MSG_NAME_MAX_LENGTH + 1];
+} arrays_msg_name_t;
+
+/* Copies the text into dst as a C string, truncated to dst_len - 1 characters */
+static inline size_t arrays_msg_name_get_str(const arrays_msg_name_t *msg, char *dst, size_t dst_len) {
+    if (!msg || !dst || dst_len == 0) {
+        return 0;
+    }
+    size_t n = msg->length;
+    if (n > ARRAYS_MSG_NAME_MAX_LENGTH) {
+        n = ARRAYS_MSG_NAME_MAX_LENGTH;
+    }
+    if (n > dst_len - 1) {
+        n = dst_len - 1;
+    }
+    for (size_t i = 0; i < n; ++i) {
+        dst[i] = msg->data[i];
+    }
+    dst[n] = '\0';
+    return n;
+}
+
+/* Sets the text from the C string src, truncated to ARRAYS_MSG_NAME_MAX_LENGTH characters */
+static inline size_t arrays_msg_name_set_str(arrays_msg_name_t *msg, const char *src) {
+    if (!msg || !src) {
+        return 0;
+    }
+    size_t n = 0;
+    while ((n < ARRAYS_MSG_NAME_MAX_LENGTH) && (src[n] != '\0')) {
+        msg->data[n] = src[n];
+        ++n;
+    }
+    msg->data[n] = '\0';
+    msg->length = n;
+    return n;
+}
+
+
+#define ARRAYS_MSG_RAW_PACKET_ID 2
+#define ARRAYS_MSG_RAW_MIN_SIZE 0
+#define ARRAYS_MSG_RAW_MAX_SIZE 32
+#define ARRAYS_MSG_RAW_MAX_LENGTH 32
+
+typedef struct {
+    size_t length;
+    uint8_t data[ARRAYS_MSG_RAW_MAX_LENGTH];
+} arrays_msg_raw_t;
+
+
+/* Temperature readings */
+#define ARRAYS_MSG_TEMPERATURES_PACKET_ID 20
+#define ARRAYS_MSG_TEMPERATURES_MIN_SIZE 0
+#define ARRAYS_MSG_TEMPERATURES_MAX_SIZE 32
+#define ARRAYS_MSG_TEMPERATURES_MAX_LENGTH 8
+
+typedef struct {
+    size_t length;
+    float data[ARRAYS_MSG_TEMPERATURES_MAX_LENGTH];
+} arrays_msg_temperatures_t;
+
+
+#define ARRAYS_MSG_COUNTERS_PACKET_ID 30
+#define ARRAYS_MSG_COUNTERS_MIN_SIZE 0
+#define ARRAYS_MSG_COUNTERS_MAX_SIZE 32
+#define ARRAYS_MSG_COUNTERS_MAX_LENGTH 4
+
+/* In memory: sizeof 40 with natural alignment on a 32-bit target, 36 packed; at most 32 bytes on the wire */
+typedef struct {
+    size_t length;
+    int64_t data[ARRAYS_MSG_COUNTERS_MAX_LENGTH];
+} arrays_msg_counters_t;
+
+
+/* Compile-time checks (define H6XSERIAL_NO_STATIC_ASSERT to disable) */
+#ifndef H6XSERIAL_STATIC_ASSERT
+#if defined(H6XSERIAL_NO_STATIC_ASSERT)
+#define H6XSERIAL_STATIC_ASSERT(cond, name)
+#elif defined(__cplusplus) && __cplusplus >= 201103L
+#define H6XSERIAL_STATIC_ASSERT(cond, name) static_assert(cond, #name);
+#elif defined(__STDC_VERSION__) && __STDC_VERSION__ >= 201112L
+#define H6XSERIAL_STATIC_ASSERT(cond, name) _Static_assert(cond, #name);
+#else
+#define H6XSERIAL_STATIC_ASSERT(cond, name) typedef char h6xserial_static_assert_##name[(cond) ? 1 : -1];
+#endif
+#endif
+
+H6XSERIAL_STATIC_ASSERT(sizeof(int64_t) == 8, arrays_int64_size_8)
+H6XSERIAL_STATIC_ASSERT(sizeof(float) == 4, arrays_float_size_4)
+H6XSERIAL_STATIC_ASSERT(ARRAYS_MSG_NAME_MAX_SIZE == 16, arrays_msg_name_max_size)
+H6XSERIAL_STATIC_ASSERT(ARRAYS_MSG_RAW_MAX_SIZE == 32, arrays_msg_raw_max_size)
+H6XSERIAL_STATIC_ASSERT(ARRAYS_MSG_TEMPERATURES_MAX_SIZE == 32, arrays_msg_temperatures_max_size)
+H6XSERIAL_STATIC_ASSERT(ARRAYS_MSG_COUNTERS_MAX_SIZE == 32, arrays_msg_counters_max_size)
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* ARRAYS_TYPES_H */
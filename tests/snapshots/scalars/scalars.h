@@ -0,0 +1,302 @@
+/*
+ * Auto-generated by h6xserial_idl 0.1.0.
+ * Source: scalars.json
+ * IR fingerprint: fc3fb515c9a19c80
+ * Protocol version: 1.2.0
+ * Max address: 64
+ */
+
+#ifndef SCALARS_H
+#define SCALARS_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <string.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Payload size limit of the transport, in bytes */
+#define H6XSERIAL_MAX_PAYLOAD_BYTES 251
+
+/* Width of the packet ID field, in bits */
+#define H6XSERIAL_PACKET_ID_BITS 8
+
+/* Smallest payload of any message, in bytes */
+#define H6XSERIAL_MIN_PAYLOAD_SIZE 1
+
+/* The float helpers copy the bits into an integer of the same size with
+ * memcpy, which is defined in C and C++ alike, and write the integer byte
+ * by byte, independent of the host byte order. */
+#ifndef H6XSERIAL_STATIC_ASSERT
+#if defined(H6XSERIAL_NO_STATIC_ASSERT)
+#define H6XSERIAL_STATIC_ASSERT(cond, name)
+#elif defined(__cplusplus) && __cplusplus >= 201103L
+#define H6XSERIAL_STATIC_ASSERT(cond, name) static_assert(cond, #name);
+#elif defined(__STDC_VERSION__) && __STDC_VERSION__ >= 201112L
+#define H6XSERIAL_STATIC_ASSERT(cond, name) _Static_assert(cond, #name);
+#else
+#define H6XSERIAL_STATIC_ASSERT(cond, name) typedef char h6xserial_static_assert_##name[(cond) ? 1 : -1];
+#endif
+#endif
+H6XSERIAL_STATIC_ASSERT(sizeof(double) == 8, h6xserial_helpers_double_size)
+
+static inline void h6xserial_write_u32_be(uint32_t value, uint8_t *out) {
+    out[0] = (uint8_t)((value >> 24) & 0xFFu);
+    out[1] = (uint8_t)((value >> 16) & 0xFFu);
+    out[2] = (uint8_t)((value >> 8) & 0xFFu);
+    out[3] = (uint8_t)(value & 0xFFu);
+}
+
+static inline uint32_t h6xserial_read_u32_be(const uint8_t *in) {
+    return ((uint32_t)in[0] << 24) |
+           ((uint32_t)in[1] << 16) |
+           ((uint32_t)in[2] << 8) |
+           ((uint32_t)in[3]);
+}
+
+static inline void h6xserial_write_u64_le(uint64_t value, uint8_t *out) {
+    out[0] = (uint8_t)(value & 0xFFu);
+    out[1] = (uint8_t)((value >> 8) & 0xFFu);
+    out[2] = (uint8_t)((value >> 16) & 0xFFu);
+    out[3] = (uint8_t)((value >> 24) & 0xFFu);
+    out[4] = (uint8_t)((value >> 32) & 0xFFu);
+    out[5] = (uint8_t)((value >> 40) & 0xFFu);
+    out[6] = (uint8_t)((value >> 48) & 0xFFu);
+    out[7] = (uint8_t)((value >> 56) & 0xFFu);
+}
+
+static inline uint64_t h6xserial_read_u64_le(const uint8_t *in) {
+    return ((uint64_t)in[0]) |
+           ((uint64_t)in[1] << 8) |
+           ((uint64_t)in[2] << 16) |
+           ((uint64_t)in[3] << 24) |
+           ((uint64_t)in[4] << 32) |
+           ((uint64_t)in[5] << 40) |
+           ((uint64_t)in[6] << 48) |
+           ((uint64_t)in[7] << 56);
+}
+
+static inline void h6xserial_write_u64_be(uint64_t value, uint8_t *out) {
+    out[0] = (uint8_t)((value >> 56) & 0xFFu);
+    out[1] = (uint8_t)((value >> 48) & 0xFFu);
+    out[2] = (uint8_t)((value >> 40) & 0xFFu);
+    out[3] = (uint8_t)((value >> 32) & 0xFFu);
+    out[4] = (uint8_t)((value >> 24) & 0xFFu);
+    out[5] = (uint8_t)((value >> 16) & 0xFFu);
+    out[6] = (uint8_t)((value >> 8) & 0xFFu);
+    out[7] = (uint8_t)(value & 0xFFu);
+}
+
+static inline uint64_t h6xserial_read_u64_be(const uint8_t *in) {
+    return ((uint64_t)in[0] << 56) |
+           ((uint64_t)in[1] << 48) |
+           ((uint64_t)in[2] << 40) |
+           ((uint64_t)in[3] << 32) |
+           ((uint64_t)in[4] << 24) |
+           ((uint64_t)in[5] << 16) |
+           ((uint64_t)in[6] << 8) |
+           ((uint64_t)in[7]);
+}
+
+static inline void h6xserial_write_f64_be(double value, uint8_t *out) {
+    uint64_t u;
+    memcpy(&u, &value, sizeof(uint64_t));
+    h6xserial_write_u64_be(u, out);
+}
+
+static inline double h6xserial_read_f64_be(const uint8_t *in) {
+    uint64_t u = h6xserial_read_u64_be(in);
+    double f;
+    memcpy(&f, &u, sizeof(double));
+    return f;
+}
+
+
+/* Keep-alive flag */
+#define SCALARS_MSG_HEARTBEAT_PACKET_ID 0
+#define SCALARS_MSG_HEARTBEAT_MIN_SIZE 1
+#define SCALARS_MSG_HEARTBEAT_MAX_SIZE 1
+
+typedef struct {
+    bool value;
+} scalars_msg_heartbeat_t;
+
+static inline size_t scalars_msg_heartbeat_encode(const scalars_msg_heartbeat_t *msg, uint8_t *out_buf, const size_t out_len) {
+    if (!msg || !out_buf) {
+        return 0;
+    }
+    if (out_len < 1) {
+        return 0;
+    }
+    (out_buf)[0] = (msg->value) ? 1 : 0;
+    return 1;
+}
+
+static inline bool scalars_msg_heartbeat_decode(scalars_msg_heartbeat_t *msg, const uint8_t *data, const size_t data_len) {
+    if (!msg || !data) {
+        return false;
+    }
+    if (data_len != 1) {
+        return false;
+    }
+    msg->value = ((data)[0]) != 0;
+    return true;
+}
+
+
+/* Operating mode */
+#define SCALARS_MSG_MODE_PACKET_ID 3
+#define SCALARS_MSG_MODE_MIN_SIZE 1
+#define SCALARS_MSG_MODE_MAX_SIZE 1
+
+typedef struct {
+    uint8_t value;
+} scalars_msg_mode_t;
+
+static inline size_t scalars_msg_mode_encode(const scalars_msg_mode_t *msg, uint8_t *out_buf, const size_t out_len) {
+    if (!msg || !out_buf) {
+        return 0;
+    }
+    if (out_len < 1) {
+        return 0;
+    }
+    (out_buf)[0] = (uint8_t)(msg->value);
+    return 1;
+}
+
+static inline bool scalars_msg_mode_decode(scalars_msg_mode_t *msg, const uint8_t *data, const size_t data_len) {
+    if (!msg || !data) {
+        return false;
+    }
+    if (data_len != 1) {
+        return false;
+    }
+    msg->value = (uint8_t)((data)[0]);
+    return true;
+}
+
+
+#define SCALARS_MSG_OFFSET_PACKET_ID 21
+#define SCALARS_MSG_OFFSET_MIN_SIZE 4
+#define SCALARS_MSG_OFFSET_MAX_SIZE 4
+
+typedef struct {
+    int32_t value;
+} scalars_msg_offset_t;
+
+static inline size_t scalars_msg_offset_encode(const scalars_msg_offset_t *msg, uint8_t *out_buf, const size_t out_len) {
+    if (!msg || !out_buf) {
+        return 0;
+    }
+    if (out_len < 4) {
+        return 0;
+    }
+    h6xserial_write_u32_be((uint32_t)(msg->value), out_buf);
+    return 4;
+}
+
+static inline bool scalars_msg_offset_decode(scalars_msg_offset_t *msg, const uint8_t *data, const size_t data_len) {
+    if (!msg || !data) {
+        return false;
+    }
+    if (data_len != 4) {
+        return false;
+    }
+    msg->value = (int32_t)h6xserial_read_u32_be(data);
+    return true;
+}
+
+
+/* Milliseconds since boot */
+#define SCALARS_MSG_UPTIME_PACKET_ID 22
+#define SCALARS_MSG_UPTIME_MIN_SIZE 8
+#define SCALARS_MSG_UPTIME_MAX_SIZE 8
+
+typedef struct {
+    uint64_t value;
+} scalars_msg_uptime_t;
+
+static inline size_t scalars_msg_uptime_encode(const scalars_msg_uptime_t *msg, uint8_t *out_buf, const size_t out_len) {
+    if (!msg || !out_buf) {
+        return 0;
+    }
+    if (out_len < 8) {
+        return 0;
+    }
+    h6xserial_write_u64_le((uint64_t)(msg->value), out_buf);
+    return 8;
+}
+
+static inline bool scalars_msg_uptime_decode(scalars_msg_uptime_t *msg, const uint8_t *data, const size_t data_len) {
+    if (!msg || !data) {
+        return false;
+    }
+    if (data_len != 8) {
+        return false;
+    }
+    msg->value = h6xserial_read_u64_le(data);
+    return true;
+}
+
+
+#define SCALARS_MSG_GAIN_PACKET_ID 23
+#define SCALARS_MSG_GAIN_MIN_SIZE 8
+#define SCALARS_MSG_GAIN_MAX_SIZE 8
+
+typedef struct {
+    double value;
+} scalars_msg_gain_t;
+
+static inline size_t scalars_msg_gain_encode(const scalars_msg_gain_t *msg, uint8_t *out_buf, const size_t out_len) {
+    if (!msg || !out_buf) {
+        return 0;
+    }
+    if (out_len < 8) {
+        return 0;
+    }
+    h6xserial_write_f64_be(msg->value, out_buf);
+    return 8;
+}
+
+static inline bool scalars_msg_gain_decode(scalars_msg_gain_t *msg, const uint8_t *data, const size_t data_len) {
+    if (!msg || !data) {
+        return false;
+    }
+    if (data_len != 8) {
+        return false;
+    }
+    msg->value = h6xserial_read_f64_be(data);
+    return true;
+}
+
+
+/* Compile-time checks (define H6XSERIAL_NO_STATIC_ASSERT to disable) */
+#ifndef H6XSERIAL_STATIC_ASSERT
+#if defined(H6XSERIAL_NO_STATIC_ASSERT)
+#define H6XSERIAL_STATIC_ASSERT(cond, name)
+#elif defined(__cplusplus) && __cplusplus >= 201103L
+#define H6XSERIAL_STATIC_ASSERT(cond, name) static_assert(cond, #name);
+#elif defined(__STDC_VERSION__) && __STDC_VERSION__ >= 201112L
+#define H6XSERIAL_STATIC_ASSERT(cond, name) _Static_assert(cond, #name);
+#else
+#define H6XSERIAL_STATIC_ASSERT(cond, name) typedef char h6xserial_static_assert_##name[(cond) ? 1 : -1];
+#endif
+#endif
+
+H6XSERIAL_STATIC_ASSERT(sizeof(int32_t) == 4, scalars_int32_size_4)
+H6XSERIAL_STATIC_ASSERT(sizeof(uint64_t) == 8, scalars_uint64_size_8)
+H6XSERIAL_STATIC_ASSERT(sizeof(double) == 8, scalars_double_size_8)
+H6XSERIAL_STATIC_ASSERT(SCALARS_MSG_HEARTBEAT_MAX_SIZE == 1, scalars_msg_heartbeat_max_size)
+H6XSERIAL_STATIC_ASSERT(SCALARS_MSG_MODE_MAX_SIZE == 1, scalars_msg_mode_max_size)
+H6XSERIAL_STATIC_ASSERT(SCALARS_MSG_OFFSET_MAX_SIZE == 4, scalars_msg_offset_max_size)
+H6XSERIAL_STATIC_ASSERT(SCALARS_MSG_UPTIME_MAX_SIZE == 8, scalars_msg_uptime_max_size)
+H6XSERIAL_STATIC_ASSERT(SCALARS_MSG_GAIN_MAX_SIZE == 8, scalars_msg_gain_max_size)
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* SCALARS_H */
@@ -0,0 +1,110 @@
+/*
+ * Auto-generated by h6xserial_idl 0.1.0.
+ * Source: scalars.json
+ * IR fingerprint: fc3fb515c9a19c80
+ * Byte order helper functions
+ */
+
+#ifndef H6X_SERIAL_BYTEORDER_H
+#define H6X_SERIAL_BYTEORDER_H
+
+#include <stdint.h>
+#include <string.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* The float helpers copy the bits into an integer of the same size with
+ * memcpy, which is defined in C and C++ alike, and write the integer byte
+ * by byte, independent of the host byte order. */
+#ifndef H6XSERIAL_STATIC_ASSERT
+#if defined(H6XSERIAL_NO_STATIC_ASSERT)
+#define H6XSERIAL_STATIC_ASSERT(cond, name)
+#elif defined(__cplusplus) && __cplusplus >= 201103L
+#define H6XSERIAL_STATIC_ASSERT(cond, name) static_assert(cond, #name);
+#elif defined(__STDC_VERSION__) && __STDC_VERSION__ >= 201112L
+#define H6XSERIAL_STATIC_ASSERT(cond, name) _Static_assert(cond, #name);
+#else
+#define H6XSERIAL_STATIC_ASSERT(cond, name) typedef char h6xserial_static_assert_##name[(cond) ? 1 : -1];
+#endif
+#endif
+H6XSERIAL_STATIC_ASSERT(sizeof(double) == 8, h6xserial_helpers_double_size)
+
+static inline void h6xserial_write_u32_be(uint32_t value, uint8_t *out) {
+    out[0] = (uint8_t)((value >> 24) & 0xFFu);
+    out[1] = (uint8_t)((value >> 16) & 0xFFu);
+    out[2] = (uint8_t)((value >> 8) & 0xFFu);
+    out[3] = (uint8_t)(value & 0xFFu);
+}
+
+static inline uint32_t h6xserial_read_u32_be(const uint8_t *in) {
+    return ((uint32_t)in[0] << 24) |
+           ((uint32_t)in[1] << 16) |
+           ((uint32_t)in[2] << 8) |
+           ((uint32_t)in[3]);
+}
+
+static inline void h6xserial_write_u64_le(uint64_t value, uint8_t *out) {
+    out[0] = (uint8_t)(value & 0xFFu);
+    out[1] = (uint8_t)((value >> 8) & 0xFFu);
+    out[2] = (uint8_t)((value >> 16) & 0xFFu);
+    out[3] = (uint8_t)((value >> 24) & 0xFFu);
+    out[4] = (uint8_t)((value >> 32) & 0xFFu);
+    out[5] = (uint8_t)((value >> 40) & 0xFFu);
+    out[6] = (uint8_t)((value >> 48) & 0xFFu);
+    out[7] = (uint8_t)((value >> 56) & 0xFFu);
+}
+
+static inline uint64_t h6xserial_read_u64_le(const uint8_t *in) {
+    return ((uint64_t)in[0]) |
+           ((uint64_t)in[1] << 8) |
+           ((uint64_t)in[2] << 16) |
+           ((uint64_t)in[3] << 24) |
+           ((uint64_t)in[4] << 32) |
+           ((uint64_t)in[5] << 40) |
+           ((uint64_t)in[6] << 48) |
+           ((uint64_t)in[7] << 56);
+}
+
+static inline void h6xserial_write_u64_be(uint64_t value, uint8_t *out) {
+    out[0] = (uint8_t)((value >> 56) & 0xFFu);
+    out[1] = (uint8_t)((value >> 48) & 0xFFu);
+    out[2] = (uint8_t)((value >> 40) & 0xFFu);
+    out[3] = (uint8_t)((value >> 32) & 0xFFu);
+    out[4] = (uint8_t)((value >> 24) & 0xFFu);
+    out[5] = (uint8_t)((value >> 16) & 0xFFu);
+    out[6] = (uint8_t)((value >> 8) & 0xFFu);
+    out[7] = (uint8_t)(value & 0xFFu);
+}
+
+static inline uint64_t h6xserial_read_u64_be(const uint8_t *in) {
+    return ((uint64_t)in[0] << 56) |
+           ((uint64_t)in[1] << 48) |
+           ((uint64_t)in[2] << 40) |
+           ((uint64_t)in[3] << 32) |
+           ((uint64_t)in[4] << 24) |
+           ((uint64_t)in[5] << 16) |
+           ((uint64_t)in[6] << 8) |
+           ((uint64_t)in[7]);
+}
+
+static inline void h6xserial_write_f64_be(double value, uint8_t *out) {
+    uint64_t u;
+    memcpy(&u, &value, sizeof(uint64_t));
+    h6xserial_write_u64_be(u, out);
+}
+
+static inline double h6xserial_read_f64_be(const uint8_t *in) {
+    uint64_t u = h6xserial_read_u64_be(in);
+    double f;
+    memcpy(&f, &u, sizeof(double));
+    return f;
+}
+
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* H6X_SERIAL_BYTEORDER_H */
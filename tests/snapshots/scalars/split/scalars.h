@@ -0,0 +1,29 @@
+/*
+ * Auto-generated by h6xserial_idl 0.1.0.
+ * Source: scalars.json
+ * IR fingerprint: fc3fb515c9a19c80
+ * Umbrella header: define one of these macros before including it
+ *   H6XSERIAL_ROLE_SERVER
+ *   H6XSERIAL_ROLE_CLIENT_COMMON
+ *   H6XSERIAL_ROLE_CLIENT_1
+ *   H6XSERIAL_ROLE_CLIENT_2
+ */
+
+#ifndef SCALARS_H
+#define SCALARS_H
+
+#include "scalars_types.h"
+
+#if defined(H6XSERIAL_ROLE_SERVER)
+#include "scalars_server.h"
+#elif defined(H6XSERIAL_ROLE_CLIENT_COMMON)
+#include "scalars_client_common.h"
+#elif defined(H6XSERIAL_ROLE_CLIENT_1)
+#include "scalars_client_1.h"
+#elif defined(H6XSERIAL_ROLE_CLIENT_2)
+#include "scalars_client_2.h"
+#else
+#error "Define one of H6XSERIAL_ROLE_SERVER, H6XSERIAL_ROLE_CLIENT_COMMON, H6XSERIAL_ROLE_CLIENT_1, H6XSERIAL_ROLE_CLIENT_2 before including scalars.h"
+#endif
+
+#endif /* SCALARS_H */
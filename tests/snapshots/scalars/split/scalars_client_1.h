@@ -0,0 +1,55 @@
+/*
+ * Auto-generated by h6xserial_idl 0.1.0.
+ * Source: scalars.json
+ * IR fingerprint: fc3fb515c9a19c80
+ * Role: Client (ID: 1)
+ * Protocol version: 1.2.0
+ * Max address: 64
+ */
+
+#ifndef SCALARS_CLIENT_1_H
+#define SCALARS_CLIENT_1_H
+
+#include "scalars_types.h"
+#include "scalars_client_common.h"
+
+#ifndef OWN_ID
+#define OWN_ID 1
+#else
+#endif
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+
+/* Operating mode */
+static inline size_t scalars_msg_mode_encode(const scalars_msg_mode_t *msg, uint8_t *out_buf, const size_t out_len) {
+    if (!msg || !out_buf) {
+        return 0;
+    }
+    if (out_len < 1) {
+        return 0;
+    }
+    (out_buf)[0] = (uint8_t)(msg->value);
+    return 1;
+}
+
+
+static inline size_t scalars_msg_gain_encode(const scalars_msg_gain_t *msg, uint8_t *out_buf, const size_t out_len) {
+    if (!msg || !out_buf) {
+        return 0;
+    }
+    if (out_len < 8) {
+        return 0;
+    }
+    h6xserial_write_f64_be(msg->value, out_buf);
+    return 8;
+}
+
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* SCALARS_CLIENT_1_H */
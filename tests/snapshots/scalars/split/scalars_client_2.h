@@ -0,0 +1,42 @@
+/*
+ * Auto-generated by h6xserial_idl 0.1.0.
+ * Source: scalars.json
+ * IR fingerprint: fc3fb515c9a19c80
+ * Role: Client (ID: 2)
+ * Protocol version: 1.2.0
+ * Max address: 64
+ */
+
+#ifndef SCALARS_CLIENT_2_H
+#define SCALARS_CLIENT_2_H
+
+#include "scalars_types.h"
+#include "scalars_client_common.h"
+
+#ifndef OWN_ID
+#define OWN_ID 2
+#else
+#endif
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+
+static inline size_t scalars_msg_offset_encode(const scalars_msg_offset_t *msg, uint8_t *out_buf, const size_t out_len) {
+    if (!msg || !out_buf) {
+        return 0;
+    }
+    if (out_len < 4) {
+        return 0;
+    }
+    h6xserial_write_u32_be((uint32_t)(msg->value), out_buf);
+    return 4;
+}
+
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* SCALARS_CLIENT_2_H */
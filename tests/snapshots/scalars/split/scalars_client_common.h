@@ -0,0 +1,50 @@
+/*
+ * Auto-generated by h6xserial_idl 0.1.0.
+ * Source: scalars.json
+ * IR fingerprint: fc3fb515c9a19c80
+ * Role: Client (Common)
+ * Protocol version: 1.2.0
+ * Max address: 64
+ */
+
+#ifndef SCALARS_CLIENT_COMMON_H
+#define SCALARS_CLIENT_COMMON_H
+
+#include "scalars_types.h"
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+
+/* Keep-alive flag */
+static inline bool scalars_msg_heartbeat_decode(scalars_msg_heartbeat_t *msg, const uint8_t *data, const size_t data_len) {
+    if (!msg || !data) {
+        return false;
+    }
+    if (data_len != 1) {
+        return false;
+    }
+    msg->value = ((data)[0]) != 0;
+    return true;
+}
+
+
+/* Milliseconds since boot */
+static inline bool scalars_msg_uptime_decode(scalars_msg_uptime_t *msg, const uint8_t *data, const size_t data_len) {
+    if (!msg || !data) {
+        return false;
+    }
+    if (data_len != 8) {
+        return false;
+    }
+    msg->value = h6xserial_read_u64_le(data);
+    return true;
+}
+
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* SCALARS_CLIENT_COMMON_H */
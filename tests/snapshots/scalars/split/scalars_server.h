@@ -0,0 +1,92 @@
+/*
+ * Auto-generated by h6xserial_idl 0.1.0.
+ * Source: scalars.json
+ * IR fingerprint: fc3fb515c9a19c80
+ * Role: Server
+ * Protocol version: 1.2.0
+ * Max address: 64
+ */
+
+#ifndef SCALARS_SERVER_H
+#define SCALARS_SERVER_H
+
+#include "scalars_types.h"
+
+#ifndef OWN_ID
+#define OWN_ID 0
+#else
+#endif
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+
+/* Keep-alive flag */
+static inline size_t scalars_msg_heartbeat_encode(const scalars_msg_heartbeat_t *msg, uint8_t *out_buf, const size_t out_len) {
+    if (!msg || !out_buf) {
+        return 0;
+    }
+    if (out_len < 1) {
+        return 0;
+    }
+    (out_buf)[0] = (msg->value) ? 1 : 0;
+    return 1;
+}
+
+
+/* Operating mode */
+static inline bool scalars_msg_mode_decode(scalars_msg_mode_t *msg, const uint8_t *data, const size_t data_len) {
+    if (!msg || !data) {
+        return false;
+    }
+    if (data_len != 1) {
+        return false;
+    }
+    msg->value = (uint8_t)((data)[0]);
+    return true;
+}
+
+
+static inline bool scalars_msg_offset_decode(scalars_msg_offset_t *msg, const uint8_t *data, const size_t data_len) {
+    if (!msg || !data) {
+        return false;
+    }
+    if (data_len != 4) {
+        return false;
+    }
+    msg->value = (int32_t)h6xserial_read_u32_be(data);
+    return true;
+}
+
+
+/* Milliseconds since boot */
+static inline size_t scalars_msg_uptime_encode(const scalars_msg_uptime_t *msg, uint8_t *out_buf, const size_t out_len) {
+    if (!msg || !out_buf) {
+        return 0;
+    }
+    if (out_len < 8) {
+        return 0;
+    }
+    h6xserial_write_u64_le((uint64_t)(msg->value), out_buf);
+    return 8;
+}
+
+
+static inline bool scalars_msg_gain_decode(scalars_msg_gain_t *msg, const uint8_t *data, const size_t data_len) {
+    if (!msg || !data) {
+        return false;
+    }
+    if (data_len != 8) {
+        return false;
+    }
+    msg->value = h6xserial_read_f64_be(data);
+    return true;
+}
+
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* SCALARS_SERVER_H */
@@ -0,0 +1,108 @@
+/*
+ * Auto-generated by h6xserial_idl 0.1.0.
+ * Source: scalars.json
+ * IR fingerprint: fc3fb515c9a19c80
+ * Common type definitions and helper functions
+ * Protocol version: 1.2.0
+ * Max address: 64
+ */
+
+#ifndef SCALARS_TYPES_H
+#define SCALARS_TYPES_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <string.h>
+
+#include "h6x_serial_byteorder.h"
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Payload size limit of the transport, in bytes */
+#define H6XSERIAL_MAX_PAYLOAD_BYTES 251
+
+/* Width of the packet ID field, in bits */
+#define H6XSERIAL_PACKET_ID_BITS 8
+
+/* Smallest payload of any message, in bytes */
+#define H6XSERIAL_MIN_PAYLOAD_SIZE 1
+
+
+/* Keep-alive flag */
+#define SCALARS_MSG_HEARTBEAT_PACKET_ID 0
+#define SCALARS_MSG_HEARTBEAT_MIN_SIZE 1
+#define SCALARS_MSG_HEARTBEAT_MAX_SIZE 1
+
+typedef struct {
+    bool value;
+} scalars_msg_heartbeat_t;
+
+
+/* Operating mode */
+#define SCALARS_MSG_MODE_PACKET_ID 3
+#define SCALARS_MSG_MODE_MIN_SIZE 1
+#define SCALARS_MSG_MODE_MAX_SIZE 1
+
+typedef struct {
+    uint8_t value;
+} scalars_msg_mode_t;
+
+
+#define SCALARS_MSG_OFFSET_PACKET_ID 21
+#define SCALARS_MSG_OFFSET_MIN_SIZE 4
+#define SCALARS_MSG_OFFSET_MAX_SIZE 4
+
+typedef struct {
+    int32_t value;
+} scalars_msg_offset_t;
+
+
+/* Milliseconds since boot */
+#define SCALARS_MSG_UPTIME_PACKET_ID 22
+#define SCALARS_MSG_UPTIME_MIN_SIZE 8
+#define SCALARS_MSG_UPTIME_MAX_SIZE 8
+
+typedef struct {
+    uint64_t value;
+} scalars_msg_uptime_t;
+
+
+#define SCALARS_MSG_GAIN_PACKET_ID 23
+#define SCALARS_MSG_GAIN_MIN_SIZE 8
+#define SCALARS_MSG_GAIN_MAX_SIZE 8
+
+typedef struct {
+    double value;
+} scalars_msg_gain_t;
+
+
+/* Compile-time checks (define H6XSERIAL_NO_STATIC_ASSERT to disable) */
+#ifndef H6XSERIAL_STATIC_ASSERT
+#if defined(H6XSERIAL_NO_STATIC_ASSERT)
+#define H6XSERIAL_STATIC_ASSERT(cond, name)
+#elif defined(__cplusplus) && __cplusplus >= 201103L
+#define H6XSERIAL_STATIC_ASSERT(cond, name) static_assert(cond, #name);
+#elif defined(__STDC_VERSION__) && __STDC_VERSION__ >= 201112L
+#define H6XSERIAL_STATIC_ASSERT(cond, name) _Static_assert(cond, #name);
+#else
+#define H6XSERIAL_STATIC_ASSERT(cond, name) typedef char h6xserial_static_assert_##name[(cond) ? 1 : -1];
+#endif
+#endif
+
+H6XSERIAL_STATIC_ASSERT(sizeof(int32_t) == 4, scalars_int32_size_4)
+H6XSERIAL_STATIC_ASSERT(sizeof(uint64_t) == 8, scalars_uint64_size_8)
+H6XSERIAL_STATIC_ASSERT(sizeof(double) == 8, scalars_double_size_8)
+H6XSERIAL_STATIC_ASSERT(SCALARS_MSG_HEARTBEAT_MAX_SIZE == 1, scalars_msg_heartbeat_max_size)
+H6XSERIAL_STATIC_ASSERT(SCALARS_MSG_MODE_MAX_SIZE == 1, scalars_msg_mode_max_size)
+H6XSERIAL_STATIC_ASSERT(SCALARS_MSG_OFFSET_MAX_SIZE == 4, scalars_msg_offset_max_size)
+H6XSERIAL_STATIC_ASSERT(SCALARS_MSG_UPTIME_MAX_SIZE == 8, scalars_msg_uptime_max_size)
+H6XSERIAL_STATIC_ASSERT(SCALARS_MSG_GAIN_MAX_SIZE == 8, scalars_msg_gain_max_size)
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* SCALARS_TYPES_H */
@@ -0,0 +1,26 @@
+/*
+ * Auto-generated by h6xserial_idl 0.1.0.
+ * Source: structs.json
+ * IR fingerprint: de6084b6fa469ebd
+ * Umbrella header: define one of these macros before including it
+ *   H6XSERIAL_ROLE_SERVER
+ *   H6XSERIAL_ROLE_CLIENT_COMMON
+ *   H6XSERIAL_ROLE_CLIENT_1
+ */
+
+#ifndef STRUCTS_H
+#define STRUCTS_H
+
+#include "structs_types.h"
+
+#if defined(H6XSERIAL_ROLE_SERVER)
+#include "structs_server.h"
+#elif defined(H6XSERIAL_ROLE_CLIENT_COMMON)
+#include "structs_client_common.h"
+#elif defined(H6XSERIAL_ROLE_CLIENT_1)
+#include "structs_client_1.h"
+#else
+#error "Define one of H6XSERIAL_ROLE_SERVER, H6XSERIAL_ROLE_CLIENT_COMMON, H6XSERIAL_ROLE_CLIENT_1 before including structs.h"
+#endif
+
+#endif /* STRUCTS_H */
@@ -0,0 +1,51 @@
+/*
+ * Auto-generated by h6xserial_idl 0.1.0.
+ * Source: structs.json
+ * IR fingerprint: de6084b6fa469ebd
+ * Role: Client (ID: 1)
+ * Protocol version: 2.0.0
+ * Max address: 128
+ */
+
+#ifndef STRUCTS_CLIENT_1_H
+#define STRUCTS_CLIENT_1_H
+
+#include "structs_types.h"
+#include "structs_client_common.h"
+
+#ifndef OWN_ID
+#define OWN_ID 1
+#else
+#endif
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+
+/* Motor setpoints */
+static inline size_t structs_msg_motor_command_encode(const structs_msg_motor_command_t *msg, uint8_t *out_buf, const size_t out_len) {
+    if (!msg || !out_buf) {
+        return 0;
+    }
+    if (out_len < 11) {
+        return 0;
+    }
+    size_t offset = 0;
+    (out_buf + offset)[0] = (msg->enabled) ? 1 : 0;
+    offset += 1;
+    h6xserial_write_u16_be((uint16_t)(msg->speed), out_buf + offset);
+    offset += 2;
+    h6xserial_write_f32_le(msg->limits.current, out_buf + offset);
+    offset += 4;
+    h6xserial_write_f32_le(msg->limits.torque, out_buf + offset);
+    offset += 4;
+    return offset;
+}
+
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* STRUCTS_CLIENT_1_H */
@@ -0,0 +1,78 @@
+/*
+ * Auto-generated by h6xserial_idl 0.1.0.
+ * Source: structs.json
+ * IR fingerprint: de6084b6fa469ebd
+ * Role: Client (Common)
+ * Protocol version: 2.0.0
+ * Max address: 128
+ */
+
+#ifndef STRUCTS_CLIENT_COMMON_H
+#define STRUCTS_CLIENT_COMMON_H
+
+#include "structs_types.h"
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+
+/* Range scan with nested samples */
+static inline bool structs_msg_scan_decode(structs_msg_scan_t *msg, const uint8_t *data, const size_t data_len) {
+    if (!msg || !data) {
+        return false;
+    }
+    if (data_len < 12) {
+        return false;
+    }
+    if (data_len > 44) {
+        return false;
+    }
+    size_t offset = 0;
+    size_t remaining = data_len;
+    remaining -= 12;
+    {
+        size_t elem_count = remaining / 1;
+        if (elem_count > STRUCTS_MSG_SCAN_LABEL_MAX_LENGTH) {
+            elem_count = STRUCTS_MSG_SCAN_LABEL_MAX_LENGTH;
+        }
+        msg->label_length = elem_count;
+        for (size_t i = 0; i < elem_count; ++i) {
+            msg->label[i] = (char)((data + offset)[0]);
+            offset += 1;
+        }
+        remaining -= elem_count;
+    }
+    msg->origin.x = h6xserial_read_f32_be(data + offset);
+    offset += 4;
+    msg->origin.y = h6xserial_read_f32_be(data + offset);
+    offset += 4;
+    msg->frame.sequence = h6xserial_read_u32_le(data + offset);
+    offset += 4;
+    if (remaining % 2 != 0) {
+        return false;
+    }
+    {
+        size_t elem_count = remaining / 2;
+        if (elem_count > STRUCTS_MSG_SCAN_FRAME_RANGES_MAX_LENGTH) {
+            elem_count = STRUCTS_MSG_SCAN_FRAME_RANGES_MAX_LENGTH;
+        }
+        if (elem_count * 2 != remaining) {
+            return false;
+        }
+        msg->frame.ranges_length = elem_count;
+        for (size_t i = 0; i < elem_count; ++i) {
+            msg->frame.ranges[i] = h6xserial_read_u16_le(data + offset);
+            offset += 2;
+        }
+        remaining -= elem_count * 2;
+    }
+    return true;
+}
+
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* STRUCTS_CLIENT_COMMON_H */
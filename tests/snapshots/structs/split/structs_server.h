@@ -0,0 +1,83 @@
+/*
+ * Auto-generated by h6xserial_idl 0.1.0.
+ * Source: structs.json
+ * IR fingerprint: de6084b6fa469ebd
+ * Role: Server
+ * Protocol version: 2.0.0
+ * Max address: 128
+ */
+
+#ifndef STRUCTS_SERVER_H
+#define STRUCTS_SERVER_H
+
+#include "structs_types.h"
+
+#ifndef OWN_ID
+#define OWN_ID 0
+#else
+#endif
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+
+/* Motor setpoints */
+static inline bool structs_msg_motor_command_decode(structs_msg_motor_command_t *msg, const uint8_t *data, const size_t data_len) {
+    if (!msg || !data) {
+        return false;
+    }
+    if (data_len != 11) {
+        return false;
+    }
+    size_t offset = 0;
+    msg->enabled = ((data + offset)[0]) != 0;
+    offset += 1;
+    msg->speed = (int16_t)h6xserial_read_u16_be(data + offset);
+    offset += 2;
+    msg->limits.current = h6xserial_read_f32_le(data + offset);
+    offset += 4;
+    msg->limits.torque = h6xserial_read_f32_le(data + offset);
+    offset += 4;
+    return true;
+}
+
+
+/* Range scan with nested samples */
+static inline size_t structs_msg_scan_encode(const structs_msg_scan_t *msg, uint8_t *out_buf, const size_t out_len) {
+    if (!msg || !out_buf) {
+        return 0;
+    }
+    if (msg->label_length > STRUCTS_MSG_SCAN_LABEL_MAX_LENGTH) {
+        return 0;
+    }
+    if (msg->frame.ranges_length > STRUCTS_MSG_SCAN_FRAME_RANGES_MAX_LENGTH) {
+        return 0;
+    }
+    if (out_len < 12 + msg->label_length + msg->frame.ranges_length * 2) {
+        return 0;
+    }
+    size_t offset = 0;
+    for (size_t i = 0; i < msg->label_length && i < STRUCTS_MSG_SCAN_LABEL_MAX_LENGTH; ++i) {
+        (out_buf + offset)[0] = (uint8_t)(msg->label[i]);
+        offset += 1;
+    }
+    h6xserial_write_f32_be(msg->origin.x, out_buf + offset);
+    offset += 4;
+    h6xserial_write_f32_be(msg->origin.y, out_buf + offset);
+    offset += 4;
+    h6xserial_write_u32_le((uint32_t)(msg->frame.sequence), out_buf + offset);
+    offset += 4;
+    for (size_t i = 0; i < msg->frame.ranges_length && i < STRUCTS_MSG_SCAN_FRAME_RANGES_MAX_LENGTH; ++i) {
+        h6xserial_write_u16_le((uint16_t)(msg->frame.ranges[i]), out_buf + offset);
+        offset += 2;
+    }
+    return offset;
+}
+
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* STRUCTS_SERVER_H */
@@ -0,0 +1,114 @@
+/*
+ * Auto-generated by h6xserial_idl 0.1.0.
+ * Source: structs.json
+ * IR fingerprint: de6084b6fa469ebd
+ * Common type definitions and helper functions
+ * Protocol version: 2.0.0
+ * Max address: 128
+ */
+
+#ifndef STRUCTS_TYPES_H
+#define STRUCTS_TYPES_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <string.h>
+
+#include "h6x_serial_byteorder.h"
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Payload size limit of the transport, in bytes */
+#define H6XSERIAL_MAX_PAYLOAD_BYTES 251
+
+/* Width of the packet ID field, in bits */
+#define H6XSERIAL_PACKET_ID_BITS 8
+
+/* Smallest payload of any message, in bytes */
+#define H6XSERIAL_MIN_PAYLOAD_SIZE 11
+
+
+/* Motor setpoints */
+#define STRUCTS_MSG_MOTOR_COMMAND_PACKET_ID 10
+#define STRUCTS_MSG_MOTOR_COMMAND_MIN_SIZE 11
+#define STRUCTS_MSG_MOTOR_COMMAND_MAX_SIZE 11
+#define STRUCTS_MSG_MOTOR_COMMAND_ENABLED_OFFSET 0
+#define STRUCTS_MSG_MOTOR_COMMAND_ENABLED_SIZE 1
+#define STRUCTS_MSG_MOTOR_COMMAND_SPEED_OFFSET 1
+#define STRUCTS_MSG_MOTOR_COMMAND_SPEED_SIZE 2
+#define STRUCTS_MSG_MOTOR_COMMAND_LIMITS_CURRENT_OFFSET 3
+#define STRUCTS_MSG_MOTOR_COMMAND_LIMITS_CURRENT_SIZE 4
+#define STRUCTS_MSG_MOTOR_COMMAND_LIMITS_TORQUE_OFFSET 7
+#define STRUCTS_MSG_MOTOR_COMMAND_LIMITS_TORQUE_SIZE 4
+
+typedef struct {
+    float current; /* A */
+    float torque;
+} structs_msg_motor_command_limits_t;
+
+/* In memory: sizeof 12 with natural alignment on a 32-bit target, 11 packed; at most 11 bytes on the wire */
+typedef struct {
+    bool enabled;
+    int16_t speed; /* rpm: motor speed */
+    structs_msg_motor_command_limits_t limits;
+} structs_msg_motor_command_t;
+
+
+
+/* Range scan with nested samples */
+#define STRUCTS_MSG_SCAN_PACKET_ID 40
+#define STRUCTS_MSG_SCAN_MIN_SIZE 12
+#define STRUCTS_MSG_SCAN_MAX_SIZE 44
+#define STRUCTS_MSG_SCAN_LABEL_OFFSET 0
+/* No offsets after 'label': its length varies, so the fields after it move */
+
+typedef struct {
+    float x;
+    float y;
+} structs_msg_scan_origin_t;
+
+#define STRUCTS_MSG_SCAN_FRAME_RANGES_MAX_LENGTH 12
+typedef struct {
+    uint32_t sequence;
+    size_t ranges_length;
+    uint16_t ranges[STRUCTS_MSG_SCAN_FRAME_RANGES_MAX_LENGTH]; /* mm: distances, 0 = no return * / | */
+} structs_msg_scan_frame_t;
+
+#define STRUCTS_MSG_SCAN_LABEL_MAX_LENGTH 8
+typedef struct {
+    size_t label_length;
+    char label[STRUCTS_MSG_SCAN_LABEL_MAX_LENGTH];
+    structs_msg_scan_origin_t origin;
+    structs_msg_scan_frame_t frame;
+} structs_msg_scan_t;
+
+
+
+/* Compile-time checks (define H6XSERIAL_NO_STATIC_ASSERT to disable) */
+#ifndef H6XSERIAL_STATIC_ASSERT
+#if defined(H6XSERIAL_NO_STATIC_ASSERT)
+#define H6XSERIAL_STATIC_ASSERT(cond, name)
+#elif defined(__cplusplus) && __cplusplus >= 201103L
+#define H6XSERIAL_STATIC_ASSERT(cond, name) static_assert(cond, #name);
+#elif defined(__STDC_VERSION__) && __STDC_VERSION__ >= 201112L
+#define H6XSERIAL_STATIC_ASSERT(cond, name) _Static_assert(cond, #name);
+#else
+#define H6XSERIAL_STATIC_ASSERT(cond, name) typedef char h6xserial_static_assert_##name[(cond) ? 1 : -1];
+#endif
+#endif
+
+H6XSERIAL_STATIC_ASSERT(sizeof(uint16_t) == 2, structs_uint16_size_2)
+H6XSERIAL_STATIC_ASSERT(sizeof(int16_t) == 2, structs_int16_size_2)
+H6XSERIAL_STATIC_ASSERT(sizeof(uint32_t) == 4, structs_uint32_size_4)
+H6XSERIAL_STATIC_ASSERT(sizeof(float) == 4, structs_float_size_4)
+H6XSERIAL_STATIC_ASSERT(STRUCTS_MSG_MOTOR_COMMAND_MAX_SIZE == 11, structs_msg_motor_command_max_size)
+H6XSERIAL_STATIC_ASSERT(STRUCTS_MSG_SCAN_MAX_SIZE == 44, structs_msg_scan_max_size)
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* STRUCTS_TYPES_H */
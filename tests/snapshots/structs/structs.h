@@ -0,0 +1,320 @@
+/*
+ * Auto-generated by h6xserial_idl 0.1.0.
+ * Source: structs.json
+ * IR fingerprint: de6084b6fa469ebd
+ * Protocol version: 2.0.0
+ * Max address: 128
+ */
+
+#ifndef STRUCTS_H
+#define STRUCTS_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <string.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Payload size limit of the transport, in bytes */
+#define H6XSERIAL_MAX_PAYLOAD_BYTES 251
+
+/* Width of the packet ID field, in bits */
+#define H6XSERIAL_PACKET_ID_BITS 8
+
+/* Smallest payload of any message, in bytes */
+#define H6XSERIAL_MIN_PAYLOAD_SIZE 11
+
+/* The float helpers copy the bits into an integer of the same size with
+ * memcpy, which is defined in C and C++ alike, and write the integer byte
+ * by byte, independent of the host byte order. */
+#ifndef H6XSERIAL_STATIC_ASSERT
+#if defined(H6XSERIAL_NO_STATIC_ASSERT)
+#define H6XSERIAL_STATIC_ASSERT(cond, name)
+#elif defined(__cplusplus) && __cplusplus >= 201103L
+#define H6XSERIAL_STATIC_ASSERT(cond, name) static_assert(cond, #name);
+#elif defined(__STDC_VERSION__) && __STDC_VERSION__ >= 201112L
+#define H6XSERIAL_STATIC_ASSERT(cond, name) _Static_assert(cond, #name);
+#else
+#define H6XSERIAL_STATIC_ASSERT(cond, name) typedef char h6xserial_static_assert_##name[(cond) ? 1 : -1];
+#endif
+#endif
+H6XSERIAL_STATIC_ASSERT(sizeof(float) == 4, h6xserial_helpers_float_size)
+
+static inline void h6xserial_write_u16_le(uint16_t value, uint8_t *out) {
+    out[0] = (uint8_t)(value & 0xFFu);
+    out[1] = (uint8_t)((value >> 8) & 0xFFu);
+}
+
+static inline uint16_t h6xserial_read_u16_le(const uint8_t *in) {
+    return (uint16_t)((uint16_t)in[0] | ((uint16_t)in[1] << 8));
+}
+
+static inline void h6xserial_write_u16_be(uint16_t value, uint8_t *out) {
+    out[0] = (uint8_t)((value >> 8) & 0xFFu);
+    out[1] = (uint8_t)(value & 0xFFu);
+}
+
+static inline uint16_t h6xserial_read_u16_be(const uint8_t *in) {
+    return (uint16_t)(((uint16_t)in[0] << 8) | (uint16_t)in[1]);
+}
+
+static inline void h6xserial_write_u32_le(uint32_t value, uint8_t *out) {
+    out[0] = (uint8_t)(value & 0xFFu);
+    out[1] = (uint8_t)((value >> 8) & 0xFFu);
+    out[2] = (uint8_t)((value >> 16) & 0xFFu);
+    out[3] = (uint8_t)((value >> 24) & 0xFFu);
+}
+
+static inline uint32_t h6xserial_read_u32_le(const uint8_t *in) {
+    return ((uint32_t)in[0]) |
+           ((uint32_t)in[1] << 8) |
+           ((uint32_t)in[2] << 16) |
+           ((uint32_t)in[3] << 24);
+}
+
+static inline void h6xserial_write_u32_be(uint32_t value, uint8_t *out) {
+    out[0] = (uint8_t)((value >> 24) & 0xFFu);
+    out[1] = (uint8_t)((value >> 16) & 0xFFu);
+    out[2] = (uint8_t)((value >> 8) & 0xFFu);
+    out[3] = (uint8_t)(value & 0xFFu);
+}
+
+static inline uint32_t h6xserial_read_u32_be(const uint8_t *in) {
+    return ((uint32_t)in[0] << 24) |
+           ((uint32_t)in[1] << 16) |
+           ((uint32_t)in[2] << 8) |
+           ((uint32_t)in[3]);
+}
+
+static inline void h6xserial_write_f32_le(float value, uint8_t *out) {
+    uint32_t u;
+    memcpy(&u, &value, sizeof(uint32_t));
+    h6xserial_write_u32_le(u, out);
+}
+
+static inline float h6xserial_read_f32_le(const uint8_t *in) {
+    uint32_t u = h6xserial_read_u32_le(in);
+    float f;
+    memcpy(&f, &u, sizeof(float));
+    return f;
+}
+
+static inline void h6xserial_write_f32_be(float value, uint8_t *out) {
+    uint32_t u;
+    memcpy(&u, &value, sizeof(uint32_t));
+    h6xserial_write_u32_be(u, out);
+}
+
+static inline float h6xserial_read_f32_be(const uint8_t *in) {
+    uint32_t u = h6xserial_read_u32_be(in);
+    float f;
+    memcpy(&f, &u, sizeof(float));
+    return f;
+}
+
+
+/* Motor setpoints */
+#define STRUCTS_MSG_MOTOR_COMMAND_PACKET_ID 10
+#define STRUCTS_MSG_MOTOR_COMMAND_MIN_SIZE 11
+#define STRUCTS_MSG_MOTOR_COMMAND_MAX_SIZE 11
+#define STRUCTS_MSG_MOTOR_COMMAND_ENABLED_OFFSET 0
+#define STRUCTS_MSG_MOTOR_COMMAND_ENABLED_SIZE 1
+#define STRUCTS_MSG_MOTOR_COMMAND_SPEED_OFFSET 1
+#define STRUCTS_MSG_MOTOR_COMMAND_SPEED_SIZE 2
+#define STRUCTS_MSG_MOTOR_COMMAND_LIMITS_CURRENT_OFFSET 3
+#define STRUCTS_MSG_MOTOR_COMMAND_LIMITS_CURRENT_SIZE 4
+#define STRUCTS_MSG_MOTOR_COMMAND_LIMITS_TORQUE_OFFSET 7
+#define STRUCTS_MSG_MOTOR_COMMAND_LIMITS_TORQUE_SIZE 4
+
+typedef struct {
+    float current; /* A */
+    float torque;
+} structs_msg_motor_command_limits_t;
+
+/* In memory: sizeof 12 with natural alignment on a 32-bit target, 11 packed; at most 11 bytes on the wire */
+typedef struct {
+    bool enabled;
+    int16_t speed; /* rpm: motor speed */
+    structs_msg_motor_command_limits_t limits;
+} structs_msg_motor_command_t;
+
+
+static inline size_t structs_msg_motor_command_encode(const structs_msg_motor_command_t *msg, uint8_t *out_buf, const size_t out_len) {
+    if (!msg || !out_buf) {
+        return 0;
+    }
+    if (out_len < 11) {
+        return 0;
+    }
+    size_t offset = 0;
+    (out_buf + offset)[0] = (msg->enabled) ? 1 : 0;
+    offset += 1;
+    h6xserial_write_u16_be((uint16_t)(msg->speed), out_buf + offset);
+    offset += 2;
+    h6xserial_write_f32_le(msg->limits.current, out_buf + offset);
+    offset += 4;
+    h6xserial_write_f32_le(msg->limits.torque, out_buf + offset);
+    offset += 4;
+    return offset;
+}
+
+static inline bool structs_msg_motor_command_decode(structs_msg_motor_command_t *msg, const uint8_t *data, const size_t data_len) {
+    if (!msg || !data) {
+        return false;
+    }
+    if (data_len != 11) {
+        return false;
+    }
+    size_t offset = 0;
+    msg->enabled = ((data + offset)[0]) != 0;
+    offset += 1;
+    msg->speed = (int16_t)h6xserial_read_u16_be(data + offset);
+    offset += 2;
+    msg->limits.current = h6xserial_read_f32_le(data + offset);
+    offset += 4;
+    msg->limits.torque = h6xserial_read_f32_le(data + offset);
+    offset += 4;
+    return true;
+}
+
+
+/* Range scan with nested samples */
+#define STRUCTS_MSG_SCAN_PACKET_ID 40
+#define STRUCTS_MSG_SCAN_MIN_SIZE 12
+#define STRUCTS_MSG_SCAN_MAX_SIZE 44
+#define STRUCTS_MSG_SCAN_LABEL_OFFSET 0
+/* No offsets after 'label': its length varies, so the fields after it move */
+
+typedef struct {
+    float x;
+    float y;
+} structs_msg_scan_origin_t;
+
+#define STRUCTS_MSG_SCAN_FRAME_RANGES_MAX_LENGTH 12
+typedef struct {
+    uint32_t sequence;
+    size_t ranges_length;
+    uint16_t ranges[STRUCTS_MSG_SCAN_FRAME_RANGES_MAX_LENGTH]; /* mm: distances, 0 = no return * / | */
+} structs_msg_scan_frame_t;
+
+#define STRUCTS_MSG_SCAN_LABEL_MAX_LENGTH 8
+typedef struct {
+    size_t label_length;
+    char label[STRUCTS_MSG_SCAN_LABEL_MAX_LENGTH];
+    structs_msg_scan_origin_t origin;
+    structs_msg_scan_frame_t frame;
+} structs_msg_scan_t;
+
+
+static inline size_t structs_msg_scan_encode(const structs_msg_scan_t *msg, uint8_t *out_buf, const size_t out_len) {
+    if (!msg || !out_buf) {
+        return 0;
+    }
+    if (msg->label_length > STRUCTS_MSG_SCAN_LABEL_MAX_LENGTH) {
+        return 0;
+    }
+    if (msg->frame.ranges_length > STRUCTS_MSG_SCAN_FRAME_RANGES_MAX_LENGTH) {
+        return 0;
+    }
+    if (out_len < 12 + msg->label_length + msg->frame.ranges_length * 2) {
+        return 0;
+    }
+    size_t offset = 0;
+    for (size_t i = 0; i < msg->label_length && i < STRUCTS_MSG_SCAN_LABEL_MAX_LENGTH; ++i) {
+        (out_buf + offset)[0] = (uint8_t)(msg->label[i]);
+        offset += 1;
+    }
+    h6xserial_write_f32_be(msg->origin.x, out_buf + offset);
+    offset += 4;
+    h6xserial_write_f32_be(msg->origin.y, out_buf + offset);
+    offset += 4;
+    h6xserial_write_u32_le((uint32_t)(msg->frame.sequence), out_buf + offset);
+    offset += 4;
+    for (size_t i = 0; i < msg->frame.ranges_length && i < STRUCTS_MSG_SCAN_FRAME_RANGES_MAX_LENGTH; ++i) {
+        h6xserial_write_u16_le((uint16_t)(msg->frame.ranges[i]), out_buf + offset);
+        offset += 2;
+    }
+    return offset;
+}
+
+static inline bool structs_msg_scan_decode(structs_msg_scan_t *msg, const uint8_t *data, const size_t data_len) {
+    if (!msg || !data) {
+        return false;
+    }
+    if (data_len < 12) {
+        return false;
+    }
+    if (data_len > 44) {
+        return false;
+    }
+    size_t offset = 0;
+    size_t remaining = data_len;
+    remaining -= 12;
+    {
+        size_t elem_count = remaining / 1;
+        if (elem_count > STRUCTS_MSG_SCAN_LABEL_MAX_LENGTH) {
+            elem_count = STRUCTS_MSG_SCAN_LABEL_MAX_LENGTH;
+        }
+        msg->label_length = elem_count;
+        for (size_t i = 0; i < elem_count; ++i) {
+            msg->label[i] = (char)((data + offset)[0]);
+            offset += 1;
+        }
+        remaining -= elem_count;
+    }
+    msg->origin.x = h6xserial_read_f32_be(data + offset);
+    offset += 4;
+    msg->origin.y = h6xserial_read_f32_be(data + offset);
+    offset += 4;
+    msg->frame.sequence = h6xserial_read_u32_le(data + offset);
+    offset += 4;
+    if (remaining % 2 != 0) {
+        return false;
+    }
+    {
+        size_t elem_count = remaining / 2;
+        if (elem_count > STRUCTS_MSG_SCAN_FRAME_RANGES_MAX_LENGTH) {
+            elem_count = STRUCTS_MSG_SCAN_FRAME_RANGES_MAX_LENGTH;
+        }
+        if (elem_count * 2 != remaining) {
+            return false;
+        }
+        msg->frame.ranges_length = elem_count;
+        for (size_t i = 0; i < elem_count; ++i) {
+            msg->frame.ranges[i] = h6xserial_read_u16_le(data + offset);
+            offset += 2;
+        }
+        remaining -= elem_count * 2;
+    }
+    return true;
+}
+
+
+/* Compile-time checks (define H6XSERIAL_NO_STATIC_ASSERT to disable) */
+#ifndef H6XSERIAL_STATIC_ASSERT
+#if defined(H6XSERIAL_NO_STATIC_ASSERT)
+#define H6XSERIAL_STATIC_ASSERT(cond, name)
+#elif defined(__cplusplus) && __cplusplus >= 201103L
+#define H6XSERIAL_STATIC_ASSERT(cond, name) static_assert(cond, #name);
+#elif defined(__STDC_VERSION__) && __STDC_VERSION__ >= 201112L
+#define H6XSERIAL_STATIC_ASSERT(cond, name) _Static_assert(cond, #name);
+#else
+#define H6XSERIAL_STATIC_ASSERT(cond, name) typedef char h6xserial_static_assert_##name[(cond) ? 1 : -1];
+#endif
+#endif
+
+H6XSERIAL_STATIC_ASSERT(sizeof(uint16_t) == 2, structs_uint16_size_2)
+H6XSERIAL_STATIC_ASSERT(sizeof(int16_t) == 2, structs_int16_size_2)
+H6XSERIAL_STATIC_ASSERT(sizeof(uint32_t) == 4, structs_uint32_size_4)
+H6XSERIAL_STATIC_ASSERT(sizeof(float) == 4, structs_float_size_4)
+H6XSERIAL_STATIC_ASSERT(STRUCTS_MSG_MOTOR_COMMAND_MAX_SIZE == 11, structs_msg_motor_command_max_size)
+H6XSERIAL_STATIC_ASSERT(STRUCTS_MSG_SCAN_MAX_SIZE == 44, structs_msg_scan_max_size)
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* STRUCTS_H */